@@ -0,0 +1,232 @@
+//! Benchmarks of the hot paths of message processing: parsing, serialization,
+//! encryption, signing and decompression of messages.
+//!
+//! Run using `cargo bench -p smb-bench`. Use `--save-baseline <name>` and `--baseline <name>`
+//! to compare against a previous run.
+
+use std::hint::black_box;
+use std::io::Cursor;
+
+use binrw::prelude::*;
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use smb::compression::Decompressor;
+use smb::connection::MessageProtectionPolicy;
+use smb::crypto::{ENCRYPTING_ALGOS, make_encrypting_algo, make_signing_algo};
+use smb::session::{MessageDecryptor, MessageEncryptor, MessageSigner};
+use smb_bench::*;
+use smb_fscc::{ChainedItemList, FileIdBothDirectoryInformation, QueryDirectoryInfo};
+use smb_msg::{
+    CompressedMessage, EncryptedHeader, EncryptedMessage, EncryptionCipher, Header, PlainRequest,
+    PlainResponse, Response, SigningAlgorithmId,
+};
+use smb_transport::IoVec;
+
+fn create_round_trip(c: &mut Criterion) {
+    let request = create_request();
+    let response = create_response();
+
+    let mut group = c.benchmark_group("create");
+    group.bench_function("request_parse", |b| {
+        b.iter(|| PlainRequest::read_le(&mut Cursor::new(black_box(&request))).unwrap())
+    });
+    let parsed_request = PlainRequest::read_le(&mut Cursor::new(&request)).unwrap();
+    group.bench_function("request_serialize", |b| {
+        b.iter(|| serialize(black_box(&parsed_request)))
+    });
+    group.bench_function("response_parse", |b| {
+        b.iter(|| PlainResponse::read_le(&mut Cursor::new(black_box(&response))).unwrap())
+    });
+    let parsed_response = PlainResponse::read_le(&mut Cursor::new(&response)).unwrap();
+    group.bench_function("response_serialize", |b| {
+        b.iter(|| serialize(black_box(&parsed_response)))
+    });
+    group.finish();
+}
+
+fn read_response_parse(c: &mut Criterion) {
+    let response = read_response();
+
+    let mut group = c.benchmark_group("read");
+    group.throughput(Throughput::Bytes(READ_SIZE as u64));
+    group.bench_function("response_64k_parse", |b| {
+        b.iter(|| PlainResponse::read_le(&mut Cursor::new(black_box(&response))).unwrap())
+    });
+    group.finish();
+}
+
+fn directory_listing_parse(c: &mut Criterion) {
+    let listing = directory_listing();
+
+    let mut group = c.benchmark_group("query_directory");
+    group.throughput(Throughput::Elements(DIRECTORY_ENTRIES as u64));
+    group.bench_function("id_both_1000_parse", |b| {
+        b.iter(|| {
+            ChainedItemList::<
+                FileIdBothDirectoryInformation,
+                { QueryDirectoryInfo::CHAINED_ALIGNMENT },
+            >::read_le(&mut Cursor::new(black_box(&listing)))
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn encryption(c: &mut Criterion) {
+    let message = read_response();
+
+    let mut group = c.benchmark_group("encryption");
+    group.throughput(Throughput::Bytes(message.len() as u64));
+    for &cipher in ENCRYPTING_ALGOS {
+        let key = encryption_key(cipher);
+        let mut encryptor =
+            MessageEncryptor::new(make_encrypting_algo(cipher, key).unwrap(), u64::MAX);
+        let mut decryptor = MessageDecryptor::new(make_encrypting_algo(cipher, key).unwrap());
+
+        group.bench_function(format!("{cipher:?}_encrypt"), |b| {
+            b.iter_batched(
+                || IoVec::from(message.clone()),
+                |mut iovec| encryptor.encrypt_message(&mut iovec, 1).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+
+        let mut iovec = IoVec::from(message.clone());
+        let header = encryptor.encrypt_message(&mut iovec, 1).unwrap();
+        let encrypted = iovec.consolidate().clone();
+        group.bench_function(format!("{cipher:?}_decrypt"), |b| {
+            b.iter_batched(
+                || EncryptedMessage {
+                    header: EncryptedHeader { ..header },
+                    encrypted_message: encrypted.clone(),
+                },
+                |message| decryptor.decrypt(message).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+
+        // A received frame, decrypted either from a parsed copy of its ciphertext,
+        // or in the receive buffer itself, as done by the transformer.
+        let mut frame = Cursor::new(vec![]);
+        header.write(&mut frame).unwrap();
+        let mut frame = frame.into_inner();
+        frame.extend_from_slice(&encrypted);
+        group.bench_function(format!("{cipher:?}_receive_parse_decrypt"), |b| {
+            b.iter_batched(
+                || frame.clone(),
+                |frame| match Response::read(&mut Cursor::new(&frame)).unwrap() {
+                    Response::Encrypted(message) => decryptor.decrypt(message).unwrap(),
+                    _ => unreachable!(),
+                },
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(format!("{cipher:?}_receive_in_place"), |b| {
+            b.iter_batched(
+                || frame.clone(),
+                |mut frame| {
+                    let header = EncryptedHeader::read(&mut Cursor::new(&frame)).unwrap();
+                    decryptor.decrypt_in_place(&header, &mut frame).unwrap();
+                    frame
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Receiving a 64KiB read response under each [`MessageProtectionPolicy`], with AES-128-GCM encryption
+/// and AES-GMAC signing: decrypting and parsing it, verifying its signature and parsing it, or only parsing it.
+fn protection_policy(c: &mut Criterion) {
+    let message = read_response();
+    let cipher = EncryptionCipher::Aes128Gcm;
+    let key = encryption_key(cipher);
+    let mut encryptor = MessageEncryptor::new(make_encrypting_algo(cipher, key).unwrap(), u64::MAX);
+    let mut decryptor = MessageDecryptor::new(make_encrypting_algo(cipher, key).unwrap());
+    let signer =
+        MessageSigner::new(make_signing_algo(SigningAlgorithmId::AesGmac, &SIGNING_KEY).unwrap());
+
+    let mut iovec = IoVec::from(message.clone());
+    let header = encryptor.encrypt_message(&mut iovec, 1).unwrap();
+    let mut encrypted = Cursor::new(vec![]);
+    header.write(&mut encrypted).unwrap();
+    let mut encrypted = encrypted.into_inner();
+    encrypted.extend_from_slice(iovec.consolidate());
+
+    let mut header = Header::read(&mut Cursor::new(&message)).unwrap();
+    header.flags.set_signed(true);
+    let mut iovec = IoVec::from(message.clone());
+    signer
+        .clone()
+        .sign_message(&mut header, &mut iovec)
+        .unwrap();
+    let signed = iovec.consolidate().clone();
+
+    let mut group = c.benchmark_group("protection_policy");
+    group.throughput(Throughput::Bytes(message.len() as u64));
+    for policy in [
+        MessageProtectionPolicy::SignAndEncryptEverything,
+        MessageProtectionPolicy::SignAllEncryptNone,
+        MessageProtectionPolicy::ControlOnly,
+    ] {
+        let frame = match policy {
+            MessageProtectionPolicy::SignAndEncryptEverything => &encrypted,
+            MessageProtectionPolicy::SignAllEncryptNone => &signed,
+            MessageProtectionPolicy::ControlOnly => &message,
+        };
+        group.bench_function(format!("{policy:?}_receive_read"), |b| {
+            b.iter_batched(
+                // Signers are single-use, so a channel clones its signer for each message.
+                || (frame.clone(), signer.clone()),
+                |(mut frame, mut signer)| {
+                    match policy {
+                        MessageProtectionPolicy::SignAndEncryptEverything => {
+                            let header = EncryptedHeader::read(&mut Cursor::new(&frame)).unwrap();
+                            decryptor.decrypt_in_place(&header, &mut frame).unwrap();
+                        }
+                        MessageProtectionPolicy::SignAllEncryptNone => {
+                            let mut header = Header::read(&mut Cursor::new(&frame)).unwrap();
+                            let mut iovec = IoVec::from(frame);
+                            signer.verify_signature(&mut header, &iovec).unwrap();
+                            frame = std::mem::take(iovec.consolidate());
+                        }
+                        MessageProtectionPolicy::ControlOnly => {}
+                    }
+                    PlainResponse::read_le(&mut Cursor::new(&frame)).unwrap()
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn decompression(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compression");
+    for (name, (caps, message)) in [
+        ("chained_pattern_decompress", compressed_read_response()),
+        ("lz4_64k_decompress", lz4_read_response()),
+    ] {
+        let decompressor = Decompressor::new(&caps);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let compressed =
+                    CompressedMessage::read(&mut Cursor::new(black_box(&message))).unwrap();
+                let (response, _): (Response, _) = decompressor.decompress(&compressed).unwrap();
+                response
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    hot_paths,
+    create_round_trip,
+    read_response_parse,
+    directory_listing_parse,
+    encryption,
+    protection_policy,
+    decompression
+);
+criterion_main!(hot_paths);
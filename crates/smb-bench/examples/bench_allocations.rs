@@ -0,0 +1,253 @@
+//! Counts the allocations of the message path, with and without the buffer pool of the connection.
+//!
+//! Encodes `--requests` stat compounds (a create, two metadata queries and a close, as sent by `Client::stat_many`)
+//! and decodes their compounded responses, the way the worker does: frames are received into buffers taken
+//! from the pool, and sent messages are returned to it. The same workload runs with [`BufferPool::disabled`],
+//! and the allocations (and reallocations) of encoding and decoding are reported for both.
+//!
+//! ```sh
+//! cargo run --release -p smb-bench --example bench_allocations -- --requests 10000
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::error::Error;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use binrw::prelude::*;
+use clap::Parser;
+use smb::connection::transformer::Transformer;
+use smb::msg_handler::OutgoingMessage;
+use smb_fscc::{FileAccessMask, FileAttributes, QueryFileInfoClass};
+use smb_msg::*;
+use smb_transport::BufferPool;
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// The number of stat compounds to encode and decode.
+    #[arg(long, default_value_t = 10_000)]
+    requests: usize,
+}
+
+/// Counts all the allocations and reallocations of the process.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// The allocations of a workload run.
+#[derive(Debug, Default)]
+struct Counts {
+    encode: usize,
+    decode: usize,
+}
+
+impl Counts {
+    fn total(&self) -> usize {
+        self.encode + self.decode
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let responses = stat_responses();
+
+    let unpooled = Transformer::with_buffer_pool(BufferPool::disabled());
+    let unpooled = run_stat_workload(&unpooled, &responses, args.requests).await?;
+    let pooled = Transformer::default();
+    let pooled = run_stat_workload(&pooled, &responses, args.requests).await?;
+
+    println!("Allocations of {} stat compounds:", args.requests);
+    println!(
+        "{:<10} {:>12} {:>12} {:>12}",
+        "", "encode", "decode", "total"
+    );
+    for (name, counts) in [("unpooled", &unpooled), ("pooled", &pooled)] {
+        println!(
+            "{name:<10} {:>12} {:>12} {:>12}",
+            counts.encode,
+            counts.decode,
+            counts.total()
+        );
+    }
+    let per_compound = |counts: &Counts| counts.total() as f64 / args.requests as f64;
+    println!(
+        "{:.1} allocations per compound unpooled, {:.1} pooled ({:.1}% fewer)",
+        per_compound(&unpooled),
+        per_compound(&pooled),
+        100.0 * (1.0 - pooled.total() as f64 / unpooled.total() as f64)
+    );
+    Ok(())
+}
+
+/// Encodes the requests and decodes the responses of `requests` stat compounds.
+async fn run_stat_workload(
+    transformer: &Transformer,
+    responses: &[u8],
+    requests: usize,
+) -> Result<Counts, Box<dyn Error>> {
+    let mut counts = Counts::default();
+    for _ in 0..requests {
+        let compound = stat_compound();
+
+        let start = allocations();
+        let sent = transformer.transform_outgoing_compound(compound).await?;
+        transformer.buffer_pool().recycle_iovec(sent);
+        counts.encode += allocations() - start;
+
+        let mut frame = transformer.buffer_pool().take_zeroed(responses.len());
+        frame.copy_from_slice(responses);
+        let start = allocations();
+        let received = transformer.transform_incoming(frame).await?;
+        counts.decode += allocations() - start;
+
+        if received.len() != 4 || received.iter().any(Result::is_err) {
+            return Err("the stat responses were not decoded".into());
+        }
+    }
+    Ok(counts)
+}
+
+/// Returns a stat compound: an open, two metadata queries, and a close.
+fn stat_compound() -> Vec<OutgoingMessage> {
+    let query = |info_class| {
+        QueryInfoRequest {
+            info_type: InfoType::File,
+            info_class: QueryInfoClass::File(info_class),
+            output_buffer_length: 0x10000,
+            additional_info: AdditionalInfo::new(),
+            flags: QueryInfoFlags::new(),
+            file_id: FileId::FULL,
+            data: GetInfoRequestData::None(()),
+        }
+        .into()
+    };
+    let mut msgs = vec![
+        OutgoingMessage::new(
+            CreateRequest {
+                requested_oplock_level: OplockLevel::None,
+                impersonation_level: ImpersonationLevel::Impersonation,
+                desired_access: FileAccessMask::new().with_file_read_attributes(true),
+                file_attributes: FileAttributes::new(),
+                share_access: ShareAccessFlags::new().with_read(true),
+                create_disposition: CreateDisposition::Open,
+                create_options: CreateOptions::new(),
+                name: r"dir\file.txt".into(),
+                contexts: vec![].into(),
+            }
+            .into(),
+        ),
+        OutgoingMessage::new(query(QueryFileInfoClass::AllInformation)),
+        OutgoingMessage::new(query(QueryFileInfoClass::AttributeTagInformation)),
+        OutgoingMessage::new(
+            CloseRequest {
+                file_id: FileId::FULL,
+            }
+            .into(),
+        ),
+    ];
+    for msg in msgs.iter_mut().skip(1) {
+        msg.message.header.flags.set_related_operations(true);
+    }
+    msgs
+}
+
+/// Returns the compounded responses to a stat compound.
+///
+/// The queries return zeroed buffers, since the transformer does not parse their data.
+fn stat_responses() -> Vec<u8> {
+    let query_response = |length: u32| {
+        let mut body = vec![9, 0, Header::STRUCT_SIZE as u8 + 8, 0];
+        body.extend_from_slice(&length.to_le_bytes());
+        body.resize(body.len() + length as usize, 0);
+        body
+    };
+    let create = CreateResponse {
+        oplock_level: OplockLevel::None,
+        flags: CreateResponseFlags::new(),
+        create_action: CreateAction::Opened,
+        creation_time: Default::default(),
+        last_access_time: Default::default(),
+        last_write_time: Default::default(),
+        change_time: Default::default(),
+        allocation_size: 0,
+        endof_file: 0,
+        file_attributes: FileAttributes::new(),
+        file_id: FileId::FULL,
+        create_contexts: vec![].into(),
+    };
+    let close = CloseResponse {
+        flags: CloseFlags::new(),
+        creation_time: Default::default(),
+        last_access_time: Default::default(),
+        last_write_time: Default::default(),
+        change_time: Default::default(),
+        allocation_size: 0,
+        endof_file: 0,
+        file_attributes: FileAttributes::new(),
+    };
+
+    let mut frame = vec![];
+    let commands = [
+        Command::Create,
+        Command::QueryInfo,
+        Command::QueryInfo,
+        Command::Close,
+    ];
+    for (i, command) in commands.into_iter().enumerate() {
+        let mut message = Cursor::new(vec![0; Header::STRUCT_SIZE]);
+        message.set_position(Header::STRUCT_SIZE as u64);
+        match i {
+            0 => create.write_le(&mut message).unwrap(),
+            1 => message.get_mut().extend(query_response(0x68)),
+            2 => message.get_mut().extend(query_response(8)),
+            _ => close.write_le(&mut message).unwrap(),
+        }
+        let mut message = message.into_inner();
+        let is_last = i + 1 == commands.len();
+        if !is_last {
+            message.resize(message.len().next_multiple_of(8), 0);
+        }
+        let header = Header {
+            credit_charge: 1,
+            status: Status::Success as u32,
+            command,
+            credit_request: 1,
+            flags: HeaderFlags::new()
+                .with_server_to_redir(true)
+                .with_related_operations(i > 0),
+            next_command: if is_last { 0 } else { message.len() as u32 },
+            message_id: i as u64,
+            tree_id: Some(1),
+            async_id: None,
+            session_id: 0,
+            signature: 0,
+        };
+        header.write(&mut Cursor::new(&mut message)).unwrap();
+        frame.extend(message);
+    }
+    frame
+}
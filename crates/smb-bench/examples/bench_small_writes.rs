@@ -0,0 +1,198 @@
+//! Compares small sequential writes with and without a [`FileWriter`], over a mock transport that delays every response.
+//!
+//! Writes `--records` records of `--record-size` bytes each, first with a [`File::write_all_at`] call per record,
+//! and then through a [`FileWriter`], which coalesces them into writes of `--buffer-size` bytes.
+//! Each write request costs a round trip of `--rtt-ms`, so the writer is faster by about the number of records per buffer.
+//!
+//! ```sh
+//! cargo run --release -p smb-bench --example bench_small_writes -- --records 200 --rtt-ms 50
+//! ```
+
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use smb::resource::*;
+use smb::{
+    Connection, ConnectionConfig, Credentials, FileAccessMask, FileAttributes, Guid, UncPath,
+};
+use smb_dtyp::binrw_util::prelude::FileTime;
+use smb_msg::*;
+use smb_transport::mock::{MockScript, MockStep, MockTransport};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// The number of records to write.
+    #[arg(long, default_value_t = 200)]
+    records: usize,
+    /// The size of each record, in bytes.
+    #[arg(long, default_value_t = 128)]
+    record_size: usize,
+    /// The buffer size of the writer, in bytes. Must be a multiple of the record size.
+    #[arg(long, default_value_t = 64 * 1024)]
+    buffer_size: usize,
+    /// The delay of each response, in milliseconds.
+    #[arg(long, default_value_t = 50)]
+    rtt_ms: u64,
+}
+
+/// A NEG_TOKEN_RESP with an NTLM CHALLENGE_MESSAGE, for an anonymous session setup.
+const CHALLENGE_TOKEN: &str = "a181b03081ada0030a0101a10c060a2b06010401823702020aa281970481944e544c4d53535000020000000c000c003800000015c28ae2abf194bdb756daa9140001000000000050005000440000000a005d580000000f410056004900560056004d0002000c00410056004900560056004d0001000c00410056004900560056004d0004000c00410076006900760056006d0003000c00410076006900760056006d0007000800a876d878c569db0100000000";
+
+const SESSION_ID: u64 = 0x4000_0000_0011;
+const FILE_ID: FileId = FileId {
+    persistent: 0x70,
+    volatile: 0x80,
+};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    if args.record_size == 0 || args.buffer_size % args.record_size != 0 {
+        return Err("--buffer-size must be a multiple of a non-zero --record-size".into());
+    }
+    let rtt = Duration::from_millis(args.rtt_ms);
+    let total = args.records * args.record_size;
+
+    // A write per record, and then the writes of the writer: full buffers, and the rest on finish.
+    let mut sizes = vec![args.record_size; args.records];
+    sizes.extend(std::iter::repeat_n(
+        args.buffer_size,
+        total / args.buffer_size,
+    ));
+    if total % args.buffer_size != 0 {
+        sizes.push(total % args.buffer_size);
+    }
+    let writes = sizes.iter().map(|&size| {
+        MockStep::expect(Command::Write)
+            .respond(WriteResponse { count: size as u32 })
+            .delay(rtt)
+    });
+    let script = MockScript::from_iter(connect_steps())
+        .steps([MockStep::expect(Command::Create).respond(create_response())])
+        .steps(writes)
+        .steps([MockStep::expect(Command::Close).respond(close_response())]);
+    let (transport, handle) = MockTransport::new(script);
+
+    let config = ConnectionConfig {
+        min_dialect: Some(Dialect::Smb021),
+        max_dialect: Some(Dialect::Smb021),
+        smb2_only_negotiate: true,
+        allow_unsigned_guest_access: true,
+        ..Default::default()
+    };
+    let connection =
+        Connection::from_transport(Box::new(transport), "server", Guid::generate(), config).await?;
+    let session = connection.authenticate(Credentials::Anonymous).await?;
+    let tree = session
+        .tree_connect(&r"\\server\share".parse::<UncPath>()?)
+        .await?;
+    let file = tree
+        .create(
+            "records.log",
+            &FileCreateArgs::make_open_existing(FileAccessMask::standard_rw()),
+        )
+        .await?
+        .unwrap_file();
+    let record = vec![0x42; args.record_size];
+
+    let start = Instant::now();
+    for i in 0..args.records {
+        file.write_all_at(&record, (i * args.record_size) as u64)
+            .await?;
+    }
+    report("write_all_at", args.records, start.elapsed());
+
+    let start = Instant::now();
+    let mut writer = FileWriter::new(
+        &file,
+        0,
+        FileWriterOptions {
+            buffer_size: Some(args.buffer_size),
+            ..Default::default()
+        },
+    );
+    for _ in 0..args.records {
+        writer.write(&record).await?;
+    }
+    writer.finish().await?;
+    report("FileWriter", sizes.len() - args.records, start.elapsed());
+
+    file.close().await?;
+    handle.assert_done();
+    Ok(())
+}
+
+fn report(name: &str, requests: usize, elapsed: Duration) {
+    println!("{name:<16} {requests:>6} req in {elapsed:.2?}");
+}
+
+/// The steps that negotiate SMB 2.1, set up an anonymous session, and connect to a share.
+fn connect_steps() -> Vec<MockStep> {
+    vec![
+        MockStep::expect(Command::Negotiate).respond(NegotiateResponse {
+            security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+            dialect_revision: NegotiateDialect::Smb021,
+            server_guid: Guid::from([0x11; 16]),
+            capabilities: GlobalCapabilities::new().with_large_mtu(true),
+            max_transact_size: 0x100000,
+            max_read_size: 0x100000,
+            max_write_size: 0x100000,
+            system_time: FileTime::default(),
+            server_start_time: FileTime::default(),
+            buffer: vec![],
+            negotiate_context_list: None,
+        }),
+        MockStep::expect(Command::SessionSetup)
+            .respond_with_status(
+                Status::MoreProcessingRequired,
+                SessionSetupResponse {
+                    session_flags: SessionFlags::new(),
+                    buffer: hex::decode(CHALLENGE_TOKEN).unwrap(),
+                },
+            )
+            .session_id(SESSION_ID),
+        MockStep::expect(Command::SessionSetup).respond(SessionSetupResponse {
+            session_flags: SessionFlags::new().with_is_null_session(true),
+            buffer: vec![],
+        }),
+        MockStep::expect(Command::TreeConnect)
+            .respond(TreeConnectResponse {
+                share_type: ShareType::Disk,
+                share_flags: ShareFlags::new(),
+                capabilities: TreeCapabilities::new(),
+                maximal_access: 0x001f01ff,
+            })
+            .tree_id(1),
+    ]
+}
+
+fn create_response() -> CreateResponse {
+    CreateResponse {
+        oplock_level: OplockLevel::None,
+        flags: CreateResponseFlags::new(),
+        create_action: CreateAction::Opened,
+        creation_time: FileTime::default(),
+        last_access_time: FileTime::default(),
+        last_write_time: FileTime::default(),
+        change_time: FileTime::default(),
+        allocation_size: 0,
+        endof_file: 0,
+        file_attributes: FileAttributes::new(),
+        file_id: FILE_ID,
+        create_contexts: Default::default(),
+    }
+}
+
+fn close_response() -> CloseResponse {
+    CloseResponse {
+        flags: CloseFlags::new(),
+        creation_time: FileTime::default(),
+        last_access_time: FileTime::default(),
+        last_write_time: FileTime::default(),
+        change_time: FileTime::default(),
+        allocation_size: 0,
+        endof_file: 0,
+        file_attributes: FileAttributes::new(),
+    }
+}
@@ -0,0 +1,202 @@
+//! Measures the throughput of the client against a live server.
+//!
+//! Runs a sequential read and a pipelined read of a single file, a read of it using `File::read_all_at`,
+//! and a metadata crawl of a directory tree, reporting MB/s and operations per second for each of them:
+//! read requests for the reads (calls, for `read_all_at`), and listed entries for the crawl.
+//!
+//! ```sh
+//! SMB_BENCH_USER=user SMB_BENCH_PASSWORD='...' cargo run --release -p smb-bench --example bench_throughput -- \
+//!     --file '\\server\share\large.bin' --dir '\\server\share\tree'
+//! ```
+
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use futures_util::{StreamExt, TryStreamExt, stream};
+use smb::client::WalkOptions;
+use smb::resource::*;
+use smb::{Client, ClientConfig, FileAccessMask, UncPath};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// A file to read, for the read benchmarks.
+    #[arg(long)]
+    file: Option<UncPath>,
+    /// A directory to crawl, for the metadata benchmark.
+    #[arg(long)]
+    dir: Option<UncPath>,
+    /// The size of each read request, in bytes.
+    #[arg(long, default_value_t = 64 * 1024)]
+    chunk_size: usize,
+    /// The number of read requests in flight during the pipelined read.
+    #[arg(long, default_value_t = 16)]
+    depth: usize,
+    /// The number of directories listed in parallel during the metadata crawl.
+    #[arg(long, default_value_t = 4)]
+    parallelism: usize,
+    /// Stop reading after this many bytes, if the file is larger.
+    #[arg(long)]
+    max_bytes: Option<u64>,
+}
+
+/// Environment variables of the credentials.
+const USER: &str = "SMB_BENCH_USER";
+const PASSWORD: &str = "SMB_BENCH_PASSWORD";
+
+struct Measurement {
+    bytes: u64,
+    operations: u64,
+    /// The name of the counted operations, e.g. `req`.
+    unit: &'static str,
+    elapsed: Duration,
+}
+
+impl Measurement {
+    fn report(&self, name: &str) {
+        let secs = self.elapsed.as_secs_f64();
+        println!(
+            "{name:<16} {:>10.2} MB/s {:>10.0} {unit}/s ({} {unit}, {} bytes in {:.2?})",
+            self.bytes as f64 / secs / 1e6,
+            self.operations as f64 / secs,
+            self.operations,
+            self.bytes,
+            self.elapsed,
+            unit = self.unit
+        );
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    if args.file.is_none() && args.dir.is_none() {
+        return Err("Nothing to benchmark: specify --file and/or --dir".into());
+    }
+    let user = std::env::var(USER).map_err(|_| format!("{USER} is not set"))?;
+    let password = std::env::var(PASSWORD).map_err(|_| format!("{PASSWORD} is not set"))?;
+
+    let client = Client::new(ClientConfig::default());
+    if let Some(path) = &args.file {
+        client.share_connect(path, &user, password.clone()).await?;
+        let file = client
+            .create_file(
+                path,
+                &FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_read(true)),
+            )
+            .await?
+            .unwrap_file();
+        let len = file.get_len().await?;
+        let len = args.max_bytes.map_or(len, |max| len.min(max));
+
+        sequential_read(&file, len, args.chunk_size)
+            .await?
+            .report("sequential read");
+        pipelined_read(&file, len, args.chunk_size, args.depth)
+            .await?
+            .report("pipelined read");
+        read_all_at(&file, len).await?.report("read_all_at");
+        file.close().await?;
+    }
+    if let Some(path) = &args.dir {
+        client.share_connect(path, &user, password).await?;
+        metadata_crawl(&client, path, args.parallelism)
+            .await?
+            .report("metadata crawl");
+    }
+    client.close().await?;
+    Ok(())
+}
+
+/// Reads the first `len` bytes of the file, one request at a time.
+async fn sequential_read(file: &File, len: u64, chunk_size: usize) -> smb::Result<Measurement> {
+    let mut buf = vec![0; chunk_size];
+    let mut measurement = Measurement {
+        bytes: 0,
+        operations: 0,
+        unit: "req",
+        elapsed: Duration::ZERO,
+    };
+    let start = Instant::now();
+    while measurement.bytes < len {
+        let size = chunk_size.min((len - measurement.bytes) as usize);
+        let read = file
+            .read_block(&mut buf[..size], measurement.bytes, None, false)
+            .await?;
+        if read == 0 {
+            break;
+        }
+        measurement.bytes += read as u64;
+        measurement.operations += 1;
+    }
+    measurement.elapsed = start.elapsed();
+    Ok(measurement)
+}
+
+/// Reads the first `len` bytes of the file, with up to `depth` requests in flight.
+async fn pipelined_read(
+    file: &File,
+    len: u64,
+    chunk_size: usize,
+    depth: usize,
+) -> smb::Result<Measurement> {
+    let start = Instant::now();
+    let reads = stream::iter((0..len).step_by(chunk_size))
+        .map(|offset| async move {
+            let mut buf = vec![0; chunk_size.min((len - offset) as usize)];
+            file.read_block(&mut buf, offset, None, false).await
+        })
+        .buffer_unordered(depth.max(1))
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(Measurement {
+        bytes: reads.iter().map(|&read| read as u64).sum(),
+        operations: reads.len() as u64,
+        unit: "req",
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Reads the first `len` bytes of the file into memory with a single [`File::read_all_at`] call,
+/// which pipelines the requests as configured by [`smb::ConnectionConfig::io_pipeline_depth`].
+async fn read_all_at(file: &File, len: u64) -> smb::Result<Measurement> {
+    let mut buf = vec![0; len as usize];
+    let start = Instant::now();
+    let read = file.read_all_at(&mut buf, 0).await?;
+    Ok(Measurement {
+        bytes: read as u64,
+        operations: 1,
+        unit: "calls",
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Walks the whole tree of the directory, counting the listed entries,
+/// and the size of their `FILE_ID_BOTH_DIR_INFORMATION` as their bytes.
+async fn metadata_crawl(
+    client: &Client,
+    path: &UncPath,
+    parallelism: usize,
+) -> smb::Result<Measurement> {
+    /// The size of the fixed part of `FILE_ID_BOTH_DIR_INFORMATION` (MS-FSCC 2.4.17).
+    const ENTRY_SIZE: u64 = 104;
+
+    let options = WalkOptions {
+        parallelism,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let (entries, bytes) = client
+        .walk(path, &options)
+        .try_fold((0u64, 0u64), |(entries, bytes), entry| async move {
+            let name_size = entry.name().encode_utf16().count() as u64 * 2;
+            Ok((entries + 1, bytes + ENTRY_SIZE + name_size))
+        })
+        .await?;
+    Ok(Measurement {
+        bytes,
+        operations: entries,
+        unit: "entries",
+        elapsed: start.elapsed(),
+    })
+}
@@ -0,0 +1,177 @@
+//! Fixtures for the `smb-rs` benchmarks.
+//!
+//! The messages are taken from the unit tests of `smb-msg` and `smb`, or built from them,
+//! so the benchmarks exercise the same parsing paths as the tests.
+//! See `benches/hot_paths.rs`, and `examples/bench_throughput.rs` for benchmarking against a server.
+
+use binrw::prelude::*;
+use smb::compression::Compressor;
+use smb_dtyp::binrw_util::prelude::{FileTime, SizedWideString};
+use smb_fscc::{
+    ChainedItemList, FileAttributes, FileIdBothDirectoryInformation, QueryDirectoryInfo,
+};
+use smb_msg::*;
+use std::io::Cursor;
+
+/// The content of a create request, from the `smb-msg` create tests.
+const CREATE_REQUEST: &str = "390000000200000000000000000000000000000000000000810010000000000007000000010000002000020078000a008800000068\
+    000000680065006c006c006f0000000000000038000000100004000000180020000000444832510000000000000000000000000000000000\
+    00000020a379c6a0c0ef118b7b000c29801682180000001000040000001800000000004d7841630000000000000000100004000000180000\
+    0000005146696400000000";
+
+/// The content of a create response, from the `smb-msg` create tests.
+const CREATE_RESPONSE: &str = "59000000010000003c083896ae4bdb01c8554b706b58db01620ccdc1c84bdb01620ccdc1c84bdb0100000000000000000000\
+    0000000000001000000000000000490100000c000000090000000c0000009800000058000000200000001000040000001800080000\
+    004d7841630000000000000000ff011f000000000010000400000018002000000051466964000000002ae7010000000400d9cf17b0\
+    0000000000000000000000000000000000000000";
+
+/// The uncompressed part of a chained compressed read response, from the `smb` compression tests.
+/// It is followed by a pattern of 0x400 bytes of data.
+const COMPRESSED_READ_RESPONSE_HEADER: [u8; 80] = [
+    0xfe, 0x53, 0x4d, 0x42, 0x40, 0x0, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x8, 0x0, 0x1, 0x0, 0x19, 0x0,
+    0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x7, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1,
+    0x0, 0x0, 0x0, 0x51, 0x0, 0x0, 0x0, 0x0, 0x34, 0x0, 0x0, 0xa, 0x9b, 0xe1, 0x41, 0x4b, 0x98,
+    0x8c, 0xf0, 0xd4, 0xcd, 0x0, 0xa3, 0xfa, 0x8a, 0x7c, 0x64, 0x11, 0x0, 0x50, 0x0, 0x0, 0x4, 0x0,
+    0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+];
+
+/// The size of the data of a large read response.
+pub const READ_SIZE: usize = 64 * 1024;
+
+/// The number of entries of a large directory listing.
+pub const DIRECTORY_ENTRIES: usize = 1000;
+
+/// A fixed session key, for the encryption benchmarks.
+const ENCRYPTION_KEY: [u8; 32] = [0x42; 32];
+
+/// A fixed signing key, for the signing benchmarks.
+pub const SIGNING_KEY: [u8; 16] = [0x24; 16];
+
+fn decode_hex(hex: &str) -> Vec<u8> {
+    let hex = hex.split_whitespace().collect::<String>();
+    hex::decode(hex).expect("Invalid fixture hex")
+}
+
+/// Builds a message of the `content` bytes of `command`, with a sync header.
+fn make_message(command: Command, is_response: bool, content: &[u8]) -> Vec<u8> {
+    let header = Header {
+        credit_charge: 0,
+        status: Status::Success as u32,
+        command,
+        credit_request: 0,
+        flags: HeaderFlags::new().with_server_to_redir(is_response),
+        next_command: 0,
+        message_id: 1,
+        tree_id: Some(1),
+        async_id: None,
+        session_id: 1,
+        signature: 0,
+    };
+    let mut cursor = Cursor::new(Vec::new());
+    header.write(&mut cursor).unwrap();
+    let mut message = cursor.into_inner();
+    message.extend_from_slice(content);
+    message
+}
+
+/// Serializes a message, as it is sent or received.
+pub fn serialize<T>(message: &T) -> Vec<u8>
+where
+    T: for<'a> BinWrite<Args<'a> = ()> + binrw::meta::WriteEndian,
+{
+    let mut cursor = Cursor::new(Vec::new());
+    message.write(&mut cursor).unwrap();
+    cursor.into_inner()
+}
+
+/// Returns a create request message.
+pub fn create_request() -> Vec<u8> {
+    make_message(Command::Create, false, &decode_hex(CREATE_REQUEST))
+}
+
+/// Returns a create response message.
+pub fn create_response() -> Vec<u8> {
+    make_message(Command::Create, true, &decode_hex(CREATE_RESPONSE))
+}
+
+/// Returns a read response message, with [`READ_SIZE`] bytes of data.
+pub fn read_response() -> Vec<u8> {
+    let mut response = PlainResponse::new(
+        ReadResponse {
+            buffer: (0..READ_SIZE).map(|i| i as u8).collect(),
+        }
+        .into(),
+    );
+    response.header.flags.set_server_to_redir(true);
+    serialize(&response)
+}
+
+/// Returns the output buffer of a query directory response, with [`DIRECTORY_ENTRIES`] entries.
+pub fn directory_listing() -> Vec<u8> {
+    let entries = (0..DIRECTORY_ENTRIES)
+        .map(|i| FileIdBothDirectoryInformation {
+            file_index: 0,
+            creation_time: FileTime::default(),
+            last_access_time: FileTime::default(),
+            last_write_time: FileTime::default(),
+            change_time: FileTime::default(),
+            end_of_file: i as u64 * 512,
+            allocation_size: i as u64 * 4096,
+            file_attributes: FileAttributes::new().with_archive(true),
+            ea_size: Some(0),
+            reparse_tag: None,
+            short_name_length: 0,
+            short_name: Default::default(),
+            file_id: i as u64,
+            file_name: SizedWideString::from(format!("file_{i:04}.txt").as_str()),
+        })
+        .collect::<Vec<_>>();
+    let list: ChainedItemList<_, { QueryDirectoryInfo::CHAINED_ALIGNMENT }> = entries.into();
+    let mut cursor = Cursor::new(Vec::new());
+    list.write_le(&mut cursor).unwrap();
+    cursor.into_inner()
+}
+
+/// Returns a chained compressed read response, with the capabilities to decompress it.
+pub fn compressed_read_response() -> (CompressionCapabilities, Vec<u8>) {
+    let caps = CompressionCapabilities {
+        flags: CompressionCapsFlags::new().with_chained(true),
+        compression_algorithms: vec![CompressionAlgorithm::None, CompressionAlgorithm::PatternV1],
+    };
+    let message = CompressedMessage::Chained(CompressedChainedMessage {
+        original_size: 1104,
+        items: vec![
+            CompressedChainedItem {
+                compression_algorithm: CompressionAlgorithm::None,
+                flags: 1,
+                original_size: None,
+                payload_data: COMPRESSED_READ_RESPONSE_HEADER.to_vec(),
+            },
+            CompressedChainedItem {
+                compression_algorithm: CompressionAlgorithm::PatternV1,
+                flags: 0,
+                original_size: None,
+                payload_data: vec![0x64, 0x0, 0x0, 0x0, 0x0, 0x4, 0x0, 0x0],
+            },
+        ],
+    });
+    (caps, serialize(&message))
+}
+
+/// Returns [`read_response`], compressed using LZ4, with the capabilities to decompress it.
+pub fn lz4_read_response() -> (CompressionCapabilities, Vec<u8>) {
+    let caps = CompressionCapabilities {
+        flags: CompressionCapsFlags::new(),
+        compression_algorithms: vec![CompressionAlgorithm::LZ4],
+    };
+    let message = Compressor::new(&caps).compress(&read_response()).unwrap();
+    (caps, serialize(&message))
+}
+
+/// Returns a fixed key of the size `cipher` requires, for the encryption benchmarks.
+pub fn encryption_key(cipher: EncryptionCipher) -> &'static [u8] {
+    match cipher {
+        EncryptionCipher::Aes128Ccm | EncryptionCipher::Aes128Gcm => &ENCRYPTION_KEY[..16],
+        EncryptionCipher::Aes256Ccm | EncryptionCipher::Aes256Gcm => &ENCRYPTION_KEY,
+    }
+}
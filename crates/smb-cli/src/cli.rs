@@ -1,9 +1,12 @@
-use crate::{copy::CopyCmd, info::InfoCmd, security::SecurityCmd, watch::WatchCmd};
+use crate::{
+    copy::CopyCmd, info::InfoCmd, ls::LsCmd, put::PutCmd, security::SecurityCmd, watch::WatchCmd,
+};
 use clap::{Parser, Subcommand, ValueEnum};
 use smb::connection::MultiChannelConfig;
 use smb::transport::config::*;
 use smb::{
     ClientConfig, ConnectionConfig,
+    client::MultiChannelOptions,
     connection::{AuthMethodsConfig, EncryptionMode},
 };
 use smb::{Dialect, Guid};
@@ -15,16 +18,25 @@ pub struct Cli {
     pub port: Option<u16>,
     #[arg(short, long)]
     pub timeout: Option<u16>,
+    /// How long to wait for the response of each request, in seconds. Defaults to the connection timeout.
+    #[arg(long)]
+    pub request_timeout: Option<u16>,
 
     #[arg(long)]
     pub negotiate_smb2_only: bool,
     /// Disables DFS referral resolution.
     #[arg(long)]
     pub no_dfs: bool,
+    /// The site name of the client to send in DFS referral requests, so that targets of that site are preferred.
+    #[arg(long)]
+    pub dfs_site_name: Option<String>,
 
     /// Configures multi-channel support.
     #[arg(long, default_value_t = MultiChannelMode::default())]
     pub multichannel: MultiChannelMode,
+    /// The maximal number of alternate channels to bind to each session, when multi-channel is used.
+    #[arg(long)]
+    pub max_channels: Option<usize>,
 
     #[cfg(feature = "rdma")]
     #[arg(long)]
@@ -45,6 +57,11 @@ pub struct Cli {
     #[arg(long)]
     pub use_transport: Option<CliUseTransport>,
 
+    /// Falls back to NetBIOS (port 139) if the server refuses connections to port 445,
+    /// and resolves server names that DNS can't resolve using NetBIOS name queries.
+    #[arg(long)]
+    pub netbios_fallback: bool,
+
     #[arg(short, long)]
     pub username: String,
     #[arg(short, long)]
@@ -135,15 +152,26 @@ impl Cli {
     pub fn make_smb_client_config(&self) -> Result<ClientConfig, &'static str> {
         Ok(ClientConfig {
             dfs: !self.no_dfs,
+            dfs_site_name: self.dfs_site_name.clone(),
             #[cfg(feature = "rdma")]
             rdma_type: self.rdma_type.map(|x| x.into()),
             client_guid: Guid::generate(),
+            metadata_cache_ttl: None,
+            read_file_size_limit: None,
+            auto_reauth: false,
+            multichannel: MultiChannelOptions {
+                max_channels: self.max_channels,
+                ..Default::default()
+            },
             connection: ConnectionConfig {
                 max_dialect: Some(Dialect::MAX),
                 encryption_mode: EncryptionMode::Allowed,
                 timeout: self
                     .timeout
                     .map(|t| std::time::Duration::from_secs(t.into())),
+                request_timeout: self
+                    .request_timeout
+                    .map(|t| std::time::Duration::from_secs(t.into())),
                 smb2_only_negotiate: self.negotiate_smb2_only,
                 transport: match self
                     .use_transport
@@ -154,6 +182,7 @@ impl Cli {
                     CliUseTransport::Quic => TransportConfig::Quic(QuicConfig {
                         local_address: None,
                         cert_validation: QuicCertValidationOptions::PlatformVerifier,
+                        ..Default::default()
                     }),
                     #[cfg(feature = "rdma")]
                     CliUseTransport::Rdma => TransportConfig::Rdma(RdmaConfig {
@@ -173,6 +202,7 @@ impl Cli {
                 },
                 allow_unsigned_guest_access: self.disable_message_signing,
                 compression_enabled: self.compress,
+                netbios_fallback: self.netbios_fallback,
                 multichannel: self.multichannel.into(),
                 ..Default::default()
             },
@@ -184,8 +214,12 @@ impl Cli {
 pub enum Commands {
     /// Copies files to/from a share.
     Copy(CopyCmd),
-    /// Retrieves information about a share or a path.
+    /// Retrieves information about a share or a path, and the negotiated properties of its connection, session and share.
     Info(InfoCmd),
+    /// Lists the contents of a directory, one entry per line.
+    Ls(LsCmd),
+    /// Uploads a local file to a share, optionally resuming an interrupted upload.
+    Put(PutCmd),
     /// Configures object security
     Security(SecurityCmd),
     /// Watches for changes in a directory.
@@ -2,6 +2,7 @@ use crate::{Cli, path::*};
 use clap::Parser;
 use indicatif::{ProgressBar, ProgressStyle};
 use maybe_async::*;
+use smb::client::DownloadDirOptions;
 use smb::sync_helpers::*;
 use smb::{Client, CreateOptions, FileAccessMask, FileAttributes, resource::*};
 use std::collections::HashMap;
@@ -22,6 +23,23 @@ pub struct CopyCmd {
     #[arg(short, long)]
     pub force: bool,
 
+    /// Preserve the object ID of the source file, including its birth IDs, so distributed link tracking keeps working.
+    /// Requires both paths to be remote.
+    #[arg(long)]
+    pub preserve_object_id: bool,
+
+    /// Copy a remote directory and all of its content into a local directory.
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// The maximum number of files downloaded at the same time, when copying recursively.
+    #[arg(long, default_value_t = DownloadDirOptions::DEFAULT_CONCURRENCY)]
+    pub jobs: usize,
+
+    /// Verify the copied data once copied, by comparing the SHA-256 digests of the source and the destination.
+    #[arg(long)]
+    pub verify: bool,
+
     /// Source path
     pub from: Path,
     /// Destination path
@@ -64,9 +82,7 @@ impl CopyFile {
                     .share_connect(unc_path, cli.username.as_str(), cli.password.clone())
                     .await?;
                 let create_args = if read {
-                    FileCreateArgs::make_open_existing(
-                        FileAccessMask::new().with_generic_read(true),
-                    )
+                    FileCreateArgs::make_open_existing(FileAccessMask::standard_read())
                 } else if cmd.force {
                     FileCreateArgs::make_overwrite(
                         FileAttributes::new().with_archive(true),
@@ -91,6 +107,15 @@ impl CopyFile {
         })
     }
 
+    /// Returns the length of the file, in bytes.
+    #[maybe_async]
+    async fn len(&self) -> smb::Result<u64> {
+        match &self.value {
+            CopyFileValue::Local(file) => file.get_len().await,
+            CopyFileValue::Remote(file) => file.get_len().await,
+        }
+    }
+
     #[cfg(not(feature = "single_threaded"))]
     #[maybe_async]
     async fn _get_channel_to_jobs_map(
@@ -147,7 +172,7 @@ impl CopyFile {
     }
 
     #[maybe_async]
-    async fn copy_to(self, to: CopyFile, client: &Client) -> Result<(), smb::Error> {
+    async fn copy_to(self, to: CopyFile, client: &Client, cmd: &CopyCmd) -> Result<(), smb::Error> {
         use CopyFileValue::*;
 
         let channel_jobs = self._get_channel_to_jobs_map(&to, client).await?;
@@ -160,13 +185,24 @@ impl CopyFile {
             Remote(from_remote) => match to.value {
                 Local(to_local) => Self::do_copy(from_remote, to_local, channel_jobs).await?,
                 Remote(to_remote) => {
+                    if cmd.preserve_object_id {
+                        Self::copy_object_id(&from_remote, &to_remote, cmd.force).await?;
+                    }
                     if to.path.as_remote().unwrap().server()
                         == self.path.as_remote().unwrap().server()
                         && to.path.as_remote().unwrap().share()
                             == self.path.as_remote().unwrap().share()
                     {
-                        // Use server-side copy if both files are on the same server
-                        to_remote.srv_copy(&from_remote).await?
+                        // Use server-side copy if both files are on the same share
+                        match from_remote.copy_to(&to_remote).await {
+                            Err(smb::Error::UnsupportedOperation(reason)) => {
+                                log::warn!("{reason}, copying through the client instead");
+                                Self::do_copy(from_remote, to_remote, channel_jobs).await?
+                            }
+                            result => {
+                                result?;
+                            }
+                        }
                     } else {
                         Self::do_copy(from_remote, to_remote, channel_jobs).await?
                     }
@@ -176,6 +212,15 @@ impl CopyFile {
         Ok(())
     }
 
+    /// Sets the object ID of the source file on the destination file, if the source file has one.
+    #[maybe_async]
+    async fn copy_object_id(from: &File, to: &File, replace: bool) -> Result<(), smb::Error> {
+        if let Some(object_id) = from.get_object_id().await? {
+            to.set_object_id(object_id, replace).await?;
+        }
+        Ok(())
+    }
+
     #[maybe_async]
     #[cfg(not(feature = "single_threaded"))]
     pub async fn do_copy<
@@ -189,13 +234,13 @@ impl CopyFile {
         let state = prepare_parallel_copy(&from, &to, channel_jobs).await?;
         let state = Arc::new(state);
         let progress_handle = Self::progress(state.clone());
-        start_parallel_copy(from, to, state).await?;
+        let result = start_parallel_copy(from, to, state).await;
 
         #[cfg(feature = "async")]
         progress_handle.await.unwrap();
         #[cfg(not(feature = "async"))]
         progress_handle.join().unwrap();
-        Ok(())
+        result
     }
 
     /// Single-threaded copy implementation.
@@ -205,7 +250,7 @@ impl CopyFile {
         to: T,
         _channels: HashMap<Option<u32>, usize>,
     ) -> smb::Result<()> {
-        let progress = Self::make_progress_bar(from.get_len()?);
+        let progress = make_progress_bar(from.get_len()?);
         block_copy_progress(
             from,
             to,
@@ -233,8 +278,12 @@ impl CopyFile {
     #[cfg(not(feature = "single_threaded"))]
     #[maybe_async]
     async fn progress_loop(state: Arc<CopyState>) {
-        let progress_bar = Self::make_progress_bar(state.total_size());
+        let progress_bar = make_progress_bar(state.total_size());
         loop {
+            if state.failed() {
+                progress_bar.abandon_with_message("Copy failed");
+                return;
+            }
             let bytes_copied = state.bytes_copied();
             progress_bar.set_position(bytes_copied);
             if bytes_copied >= state.total_size() {
@@ -244,14 +293,14 @@ impl CopyFile {
         }
         progress_bar.finish_with_message("Copy complete");
     }
+}
 
-    /// Returns a new progress bar instance for copying files.
-    fn make_progress_bar(len: u64) -> ProgressBar {
-        let progress = ProgressBar::new(len);
-        progress.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-                    .unwrap().progress_chars("#>-"));
-        progress
-    }
+/// Returns a new progress bar instance for copying files.
+pub(crate) fn make_progress_bar(len: u64) -> ProgressBar {
+    let progress = ProgressBar::new(len);
+    progress.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+                .unwrap().progress_chars("#>-"));
+    progress
 }
 
 #[maybe_async]
@@ -259,14 +308,102 @@ pub async fn copy(cmd: &CopyCmd, cli: &Cli) -> Result<(), Box<dyn Error>> {
     if matches!(cmd.from, Path::Local(_)) && matches!(cmd.to, Path::Local(_)) {
         return Err("Copying between two local files is not supported. Use `cp` or `copy` shell commands instead :)".into());
     }
+    if cmd.preserve_object_id
+        && (matches!(cmd.from, Path::Local(_)) || matches!(cmd.to, Path::Local(_)))
+    {
+        return Err("Object IDs can only be preserved when copying between remote files".into());
+    }
 
     let client = Client::new(cli.make_smb_client_config()?);
+    if cmd.recursive {
+        let result = copy_dir(cmd, cli, &client).await;
+        client.close().await?;
+        return result;
+    }
     let from = CopyFile::open(&cmd.from, &client, cli, cmd, true).await?;
     let to = CopyFile::open(&cmd.to, &client, cli, cmd, false).await?;
+    let total = from.len().await?;
 
-    let copy_ok = from.copy_to(to, &client).await;
+    let mut copy_ok = from.copy_to(to, &client, cmd).await;
+    if copy_ok.is_ok() && cmd.verify {
+        copy_ok = verify(cmd, cli, &client).await;
+    }
 
     client.close().await?;
 
-    Ok(copy_ok?)
+    match copy_ok {
+        Err(smb::Error::DiskFull { written, .. }) => {
+            Err(format!("server out of space: wrote {written} of {total} bytes").into())
+        }
+        Err(smb::Error::QuotaExceeded { written, .. }) => {
+            Err(format!("server quota exceeded: wrote {written} of {total} bytes").into())
+        }
+        copy_ok => Ok(copy_ok?),
+    }
+}
+
+/// Reopens the source and the destination of a copy, and verifies that their data is identical.
+#[maybe_async]
+async fn verify(cmd: &CopyCmd, cli: &Cli, client: &Client) -> Result<(), smb::Error> {
+    let from = CopyFile::open(&cmd.from, client, cli, cmd, true).await?;
+    let to = CopyFile::open(&cmd.to, client, cli, cmd, true).await?;
+    let options = IntegrityOptions::default();
+    let digest = match (&from.value, &to.value) {
+        (CopyFileValue::Local(from), CopyFileValue::Remote(to)) => {
+            verify_copy(from, to, &options).await?
+        }
+        (CopyFileValue::Remote(from), CopyFileValue::Local(to)) => {
+            verify_copy(from, to, &options).await?
+        }
+        (CopyFileValue::Remote(from), CopyFileValue::Remote(to)) => {
+            verify_copy(from, to, &options).await?
+        }
+        (CopyFileValue::Local(_), CopyFileValue::Local(_)) => unreachable!(),
+    };
+    log::info!("Verified the copied data: {digest}");
+    Ok(())
+}
+
+/// Downloads a remote directory tree, for `copy --recursive`.
+#[maybe_async]
+async fn copy_dir(cmd: &CopyCmd, cli: &Cli, client: &Client) -> Result<(), Box<dyn Error>> {
+    let (Path::Remote(from), Path::Local(to)) = (&cmd.from, &cmd.to) else {
+        return Err(
+            "Recursive copy is only supported from a remote directory to a local one".into(),
+        );
+    };
+    if !cmd.force && to.exists() {
+        return Err(format!(
+            "{} already exists, use --force to merge into it",
+            to.display()
+        )
+        .into());
+    }
+
+    client
+        .share_connect(from, cli.username.as_str(), cli.password.clone())
+        .await?;
+    let options = DownloadDirOptions {
+        concurrency: cmd.jobs,
+        integrity: cmd.verify.then(IntegrityOptions::default),
+        ..Default::default()
+    };
+    let report = client.download_dir(from, to, options).await?;
+    for collision in &report.collisions {
+        match &collision.local_name {
+            Some(local_name) => log::warn!(
+                "{} collides with {} ({}), downloaded as {local_name}",
+                collision.name,
+                collision.conflicts_with,
+                collision.kind
+            ),
+            None => log::warn!(
+                "{} collides with {} ({}), skipped",
+                collision.name,
+                collision.conflicts_with,
+                collision.kind
+            ),
+        }
+    }
+    Ok(())
 }
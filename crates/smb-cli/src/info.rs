@@ -3,13 +3,11 @@ use clap::{Parser, ValueEnum};
 #[cfg(feature = "async")]
 use futures_util::StreamExt;
 use maybe_async::*;
+use smb::client::{WalkEntry, WalkOptions, WalkOrder};
 use smb::{Client, FileAccessMask, FileBasicInformation, QueryQuotaInfo, UncPath, resource::*};
-use std::collections::VecDeque;
 use std::fmt::Display;
 use std::{error::Error, sync::Arc};
 
-type DirectoryInfoQueryType = smb::FileIdBothDirectoryInformation;
-
 /// Recursion mode options
 #[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq, Eq, PartialOrd, Ord)]
 pub enum RecursiveMode {
@@ -39,6 +37,16 @@ pub struct InfoCmd {
     #[clap(default_value_t = RecursiveMode::NonRecursive)]
     pub recursive: RecursiveMode,
 
+    /// The maximum number of directories to list concurrently, when recursing.
+    #[arg(long)]
+    #[clap(default_value_t = 1)]
+    pub parallelism: usize,
+
+    /// Whether to list recursively in depth-first order, instead of listing each directory once it is available.
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    pub depth_first: bool,
+
     /// Whether to display quota information on the directory being queried.
     #[arg(long)]
     #[clap(default_value_t = false)]
@@ -58,6 +66,7 @@ pub async fn info(cmd: &InfoCmd, cli: &Cli) -> Result<(), Box<dyn Error>> {
         client
             .ipc_connect(cmd.path.server(), &cli.username, cli.password.clone())
             .await?;
+        show_connection_info(&client, &UncPath::ipc_share(cmd.path.server())?).await?;
         let shares_info = client.list_shares(cmd.path.server()).await?;
         log::info!("Available shares on {}: ", cmd.path.server());
         for share in shares_info {
@@ -69,10 +78,11 @@ pub async fn info(cmd: &InfoCmd, cli: &Cli) -> Result<(), Box<dyn Error>> {
     client
         .share_connect(&cmd.path, cli.username.as_ref(), cli.password.clone())
         .await?;
+    show_connection_info(&client, &cmd.path).await?;
     let resource = client
         .create_file(
             &cmd.path,
-            &FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_read(true)),
+            &FileCreateArgs::make_open_existing(FileAccessMask::standard_read()),
         )
         .await?;
 
@@ -115,12 +125,10 @@ pub async fn info(cmd: &InfoCmd, cli: &Cli) -> Result<(), Box<dyn Error>> {
                 try_query_and_show_quota(&dir).await;
             }
             iterate_directory(
-                &dir,
                 &cmd.path,
-                "*",
+                &make_walk_options(cmd),
                 &IterateParams {
                     client: &client,
-                    recursive: cmd.recursive,
                     show_quota: cmd.show_quota,
                 },
             )
@@ -138,18 +146,32 @@ pub async fn info(cmd: &InfoCmd, cli: &Cli) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn display_item_info(info: &DirectoryInfoQueryType, dir_path: &UncPath) {
-    if info.file_name == "." || info.file_name == ".." {
-        return; // Skip current and parent directory entries
-    }
+/// Displays the negotiated properties of the connection, the session and the share of `path`, as JSON.
+#[maybe_async]
+async fn show_connection_info(client: &Client, path: &UncPath) -> Result<(), Box<dyn Error>> {
+    let connection = client.get_connection(path.server()).await?;
+    let negotiate_info = connection
+        .negotiate_info()
+        .ok_or("Connection is not negotiated")?;
+    let session_info = client.get_session(path).await?.info().await?;
+    log::info!(
+        "Connection: {}",
+        serde_json::to_string_pretty(&negotiate_info)?
+    );
+    log::info!("Session: {}", serde_json::to_string_pretty(&session_info)?);
+    let share_info = client.get_tree(path).await?.share_info()?;
+    log::info!("Share: {}", serde_json::to_string_pretty(&share_info)?);
+    Ok(())
+}
 
-    match info.file_attributes.directory() {
-        true => log::info!("  - {} {dir_path}/{}/", "(D)", info.file_name),
+fn display_item_info(entry: &WalkEntry) {
+    match entry.is_directory() {
+        true => log::info!("  - {} {}/", "(D)", entry.path),
         false => log::info!(
-            "  - {} {dir_path}/{} ~{}",
+            "  - {} {} ~{}",
             "(F)",
-            info.file_name,
-            get_size_string(info.end_of_file)
+            entry.path,
+            get_size_string(entry.info.end_of_file)
         ),
     }
 }
@@ -186,125 +208,94 @@ fn get_size_string(size_bytes: u64) -> String {
 
 struct IterateParams<'a> {
     client: &'a Client,
-    recursive: RecursiveMode,
     show_quota: bool,
 }
-struct IteratedItem {
-    dir: Arc<Directory>,
-    path: UncPath,
-}
 
-#[maybe_async]
-async fn iterate_directory(
-    dir: &Arc<Directory>,
-    dir_path: &UncPath,
-    pattern: &str,
-    params: &IterateParams<'_>,
-) -> smb::Result<()> {
-    let mut subdirs = VecDeque::new();
-    subdirs.push_back(IteratedItem {
-        dir: Arc::clone(dir),
-        path: dir_path.clone(),
-    });
-
-    while subdirs.front().is_some() {
-        iterate_dir_items(&subdirs.pop_front().unwrap(), pattern, &mut subdirs, params).await?;
-
-        assert!(params.recursive >= RecursiveMode::List || subdirs.is_empty())
+fn make_walk_options(cmd: &InfoCmd) -> WalkOptions {
+    WalkOptions {
+        parallelism: cmd.parallelism,
+        order: if cmd.depth_first {
+            WalkOrder::DepthFirst
+        } else {
+            WalkOrder::Unordered
+        },
+        max_depth: match cmd.recursive {
+            RecursiveMode::NonRecursive => Some(0),
+            RecursiveMode::List => None,
+        },
+        ..Default::default()
     }
-    Ok(())
 }
 
 #[async_impl]
-async fn iterate_dir_items(
-    item: &IteratedItem,
-    pattern: &str,
-    subdirs: &mut VecDeque<IteratedItem>,
+async fn iterate_directory(
+    dir_path: &UncPath,
+    options: &WalkOptions,
     params: &IterateParams<'_>,
 ) -> smb::Result<()> {
-    let mut info_stream = Directory::query::<DirectoryInfoQueryType>(&item.dir, pattern).await?;
-    while let Some(info) = info_stream.next().await {
-        if let Some(to_push) = handle_iteration_item(&info?, &item.path, params).await {
-            subdirs.push_back(to_push);
-        }
+    let walk = params.client.walk(dir_path, options);
+    futures_util::pin_mut!(walk);
+    while let Some(entry) = walk.next().await {
+        handle_walk_entry(entry, params).await;
     }
     Ok(())
 }
 
 #[sync_impl]
-fn iterate_dir_items(
-    item: &IteratedItem,
-    pattern: &str,
-    subdirs: &mut VecDeque<IteratedItem>,
+fn iterate_directory(
+    dir_path: &UncPath,
+    options: &WalkOptions,
     params: &IterateParams<'_>,
 ) -> smb::Result<()> {
-    for info in Directory::query::<DirectoryInfoQueryType>(&item.dir, pattern)? {
-        if let Some(to_push) = handle_iteration_item(&info?, &item.path, params) {
-            subdirs.push_back(to_push);
-        }
+    for entry in params.client.walk(dir_path, options) {
+        handle_walk_entry(entry, params);
     }
     Ok(())
 }
 
 #[maybe_async]
-async fn handle_iteration_item(
-    info: &DirectoryInfoQueryType,
-    dir_path: &UncPath,
-    params: &IterateParams<'_>,
-) -> Option<IteratedItem> {
-    display_item_info(info, dir_path);
-
-    if params.recursive < RecursiveMode::List && !params.show_quota {
-        return None;
-    }
+async fn handle_walk_entry(entry: smb::Result<WalkEntry>, params: &IterateParams<'_>) {
+    let entry = match entry {
+        Ok(entry) => entry,
+        Err(e) => {
+            log::warn!("Failed to list directory: {e}");
+            return;
+        }
+    };
+    display_item_info(&entry);
 
-    if !info.file_attributes.directory() || info.file_name == "." || info.file_name == ".." {
-        return None;
+    if !params.show_quota || !entry.is_directory() {
+        return;
     }
 
-    let path_of_subdir = dir_path.clone().with_add_path(&info.file_name.to_string());
     let dir_result = params
         .client
         .create_file(
-            &path_of_subdir,
-            &FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_read(true)),
+            &entry.path,
+            &FileCreateArgs::make_open_existing(FileAccessMask::standard_read()),
         )
         .await;
-
-    if let Err(e) = dir_result {
-        log::warn!("Failed to open directory {}: {}", path_of_subdir, e);
-        return None;
-    }
-
-    let dir = dir_result.unwrap();
-
+    let dir = match dir_result {
+        Ok(dir) => dir,
+        Err(e) => {
+            log::warn!("Failed to open directory {}: {}", entry.path, e);
+            return;
+        }
+    };
     let dir: Directory = match dir.try_into() {
         Ok(dir) => dir,
         Err(e) => {
             log::warn!(
                 "Failed to convert resource to directory {}: {}",
-                path_of_subdir,
+                entry.path,
                 e.0
             );
-            return None;
+            return;
         }
     };
 
-    // Quota information
-    if params.show_quota {
-        try_query_and_show_quota(&dir).await;
-    }
-
-    // Recursion
-    if params.recursive >= RecursiveMode::List {
-        Some(IteratedItem {
-            dir: Arc::new(dir),
-            path: path_of_subdir,
-        })
-    } else {
-        dir.close().await.ok()?;
-        None
-    }
+    try_query_and_show_quota(&dir).await;
+    dir.close().await.ok();
 }
 
 #[maybe_async]
@@ -1,7 +1,9 @@
 pub mod cli;
 pub mod copy;
 pub mod info;
+pub mod ls;
 pub mod path;
+pub mod put;
 pub mod security;
 pub mod watch;
 
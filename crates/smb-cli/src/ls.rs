@@ -0,0 +1,225 @@
+use crate::Cli;
+use clap::Parser;
+use maybe_async::*;
+use smb::binrw_util::prelude::FileTime;
+use smb::{Client, FileAccessMask, FileAttributes, UncPath, resource::*, sync_helpers::*};
+use std::error::Error;
+use std::sync::OnceLock;
+use time::{UtcOffset, format_description::BorrowedFormatItem, macros::format_description};
+
+/// The format of the last write time column.
+const TIME_FORMAT: &[BorrowedFormatItem<'static>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+
+/// The offset of the local time zone, as determined by [`init_local_offset`].
+static LOCAL_OFFSET: OnceLock<UtcOffset> = OnceLock::new();
+
+/// Determines the offset of the local time zone, which the last write time column is shown in.
+///
+/// This must be called before the process starts any other thread: the offset can't be determined
+/// once the process is multi-threaded, and times are then shown in UTC.
+pub fn init_local_offset() {
+    if let Ok(offset) = UtcOffset::current_local_offset() {
+        LOCAL_OFFSET.set(offset).ok();
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct LsCmd {
+    /// The UNC path of the directory to list.
+    pub path: UncPath,
+
+    /// Whether to list all the subdirectories, recursively.
+    #[arg(short = 'R', long, default_value_t = false)]
+    pub recursive: bool,
+
+    /// Whether to show the attributes, size and last write time (local time) of each entry.
+    #[arg(short, long, default_value_t = false)]
+    pub long: bool,
+
+    /// Whether to show the last write time in UTC, instead of local time.
+    #[arg(long, default_value_t = false)]
+    pub utc: bool,
+
+    /// Whether to show the file ID of each entry.
+    #[arg(short = 'i', long, default_value_t = false)]
+    pub file_ids: bool,
+
+    /// The wildcard pattern of the entries to list, e.g. `*.txt`.
+    /// Subdirectories are recursed into regardless of the pattern.
+    #[arg(long, default_value = "*")]
+    pub pattern: String,
+
+    /// Whether to print each entry as a JSON object, one per line, instead of columns.
+    /// The last write time of each object is in UTC.
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+/// The entries of a listed directory.
+struct Listing {
+    /// The entries that match the pattern.
+    matched: Vec<DirectoryEntry>,
+    /// The names of the subdirectories to recurse into.
+    subdirs: Vec<String>,
+}
+
+#[maybe_async]
+pub async fn ls(cmd: &LsCmd, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    if cmd.path.share().is_none_or(|share| share.is_empty()) {
+        return Err("Path must include a share name".into());
+    }
+
+    if cmd.long && !cmd.utc && LOCAL_OFFSET.get().is_none() {
+        log::warn!("Could not determine the local time zone, showing times in UTC.");
+    }
+
+    let client = Client::new(cli.make_smb_client_config()?);
+    client
+        .share_connect(&cmd.path, &cli.username, cli.password.clone())
+        .await?;
+
+    // Directories are listed from an explicit stack, rather than recursively,
+    // so that deeply nested trees are listed one level at a time, in depth-first order.
+    let mut pending = vec![String::new()];
+    while let Some(relative) = pending.pop() {
+        let path = match relative.is_empty() {
+            true => cmd.path.clone(),
+            false => cmd.path.clone().with_add_path(&relative),
+        };
+        let listing = match list_directory(&client, &path, cmd).await {
+            Ok(listing) => listing,
+            Err(e) if !relative.is_empty() => {
+                log::warn!("Failed to list directory {path}: {e}");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        for entry in &listing.matched {
+            let name = join_path(&relative, &entry.name);
+            println!("{}", format_entry(entry, &name, cmd));
+        }
+        pending.extend(
+            listing
+                .subdirs
+                .iter()
+                .rev()
+                .map(|subdir| join_path(&relative, subdir)),
+        );
+    }
+
+    client.close().await?;
+    Ok(())
+}
+
+#[maybe_async]
+async fn list_directory(client: &Client, path: &UncPath, cmd: &LsCmd) -> smb::Result<Listing> {
+    let access = FileAccessMask::new()
+        .with_file_read_data(true)
+        .with_file_read_attributes(true)
+        .with_synchronize(true);
+    let dir: Directory = client
+        .create_file(path, &FileCreateArgs::make_open_existing(access))
+        .await?
+        .try_into()
+        .map_err(|_| smb::Error::InvalidArgument(format!("{path} is not a directory")))?;
+    let dir = Arc::new(dir);
+
+    let listing = query_listing(&dir, cmd).await;
+    let closed = dir.close().await;
+    let listing = listing?;
+    closed?;
+    Ok(listing)
+}
+
+#[maybe_async]
+async fn query_listing(dir: &Arc<Directory>, cmd: &LsCmd) -> smb::Result<Listing> {
+    let matched = query_directory(dir, &cmd.pattern).await?;
+    let subdirs = match (cmd.recursive, cmd.pattern.as_str()) {
+        (false, _) => vec![],
+        (true, "*") => subdirectory_names(&matched),
+        // The pattern may exclude subdirectories, so they are listed separately.
+        (true, _) => subdirectory_names(&query_directory(dir, "*").await?),
+    };
+    Ok(Listing { matched, subdirs })
+}
+
+#[maybe_async]
+async fn query_directory(dir: &Arc<Directory>, pattern: &str) -> smb::Result<Vec<DirectoryEntry>> {
+    Directory::entries(dir, pattern, &QueryDirectoryOptions::DEFAULT)
+        .await?
+        .collect_entries()
+        .await
+}
+
+/// Returns the names of the subdirectories to recurse into.
+///
+/// Directories that are reparse points (e.g. junctions) are listed, but not recursed into.
+fn subdirectory_names(entries: &[DirectoryEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|entry| entry.is_dir && !entry.is_reparse_point())
+        .map(|entry| entry.name.clone())
+        .collect()
+}
+
+fn join_path(parent: &str, name: &str) -> String {
+    match parent.is_empty() {
+        true => name.to_string(),
+        false => format!("{parent}\\{name}"),
+    }
+}
+
+fn format_entry(entry: &DirectoryEntry, name: &str, cmd: &LsCmd) -> String {
+    if cmd.json {
+        let mut json = serde_json::json!({
+            "name": name,
+            "directory": entry.is_dir,
+            "attributes": attribute_letters(&entry.attributes),
+            "size": entry.size,
+            "last_write_time": entry.last_write_time.to_string(),
+        });
+        if cmd.file_ids {
+            json["file_id"] = entry.file_id.into();
+        }
+        return json.to_string();
+    }
+
+    let mut columns = vec![];
+    if cmd.long {
+        columns.push(attribute_letters(&entry.attributes));
+        columns.push(format!("{:>15}", entry.size));
+        columns.push(format_time(&entry.last_write_time, cmd.utc));
+    }
+    if cmd.file_ids {
+        columns.push(format!("{:#018x}", entry.file_id));
+    }
+    columns.push(name.to_string());
+    columns.join(" ")
+}
+
+/// Returns the `DRHSA` letters of the attributes, with `-` in place of each attribute that is not set.
+fn attribute_letters(attributes: &FileAttributes) -> String {
+    [
+        (attributes.directory(), 'D'),
+        (attributes.readonly(), 'R'),
+        (attributes.hidden(), 'H'),
+        (attributes.system(), 'S'),
+        (attributes.archive(), 'A'),
+    ]
+    .into_iter()
+    .map(|(set, letter)| if set { letter } else { '-' })
+    .collect()
+}
+
+fn format_time(time: &FileTime, utc: bool) -> String {
+    let datetime = time.to_datetime();
+    let datetime = match (utc, LOCAL_OFFSET.get()) {
+        (false, Some(&offset)) => datetime.to_offset(offset),
+        _ => datetime,
+    };
+    datetime
+        .format(TIME_FORMAT)
+        .unwrap_or_else(|_| time.to_string())
+}
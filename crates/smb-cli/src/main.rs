@@ -7,6 +7,7 @@ use smb_cli::*;
 
 #[cfg(not(feature = "async"))]
 fn main() -> Result<(), Box<dyn Error>> {
+    ls::init_local_offset();
     _main().map_err(|e| {
         log::error!("Error: {e}");
         e
@@ -14,12 +15,18 @@ fn main() -> Result<(), Box<dyn Error>> {
 }
 
 #[cfg(feature = "async")]
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    _main().await.map_err(|e| {
-        log::error!("Error: {e}");
-        e
-    })
+fn main() -> Result<(), Box<dyn Error>> {
+    // The local time zone can only be determined before the runtime starts its worker threads.
+    ls::init_local_offset();
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            _main().await.map_err(|e| {
+                log::error!("Error: {e}");
+                e
+            })
+        })
 }
 
 #[maybe_async]
@@ -49,6 +56,13 @@ async fn _main() -> Result<(), Box<dyn Error>> {
             log::info!("Getting info for {:?}", cmd.path);
             info::info(cmd, &cli).await?;
         }
+        Commands::Ls(cmd) => {
+            ls::ls(cmd, &cli).await?;
+        }
+        Commands::Put(cmd) => {
+            log::info!("Uploading {:?} to {:?}", cmd.from, cmd.to);
+            put::put(cmd, &cli).await?;
+        }
         Commands::Security(cmd) => {
             security::security(cmd, &cli).await?;
         }
@@ -0,0 +1,225 @@
+use crate::{Cli, copy::make_progress_bar};
+use clap::Parser;
+use maybe_async::*;
+use smb::binrw_util::prelude::FileTime;
+use smb::{
+    Client, CreateDisposition, CreateOptions, FileAccessMask, FileAttributes, FileBasicInformation,
+    FileStandardInformation, UncPath, resource::*,
+};
+use std::error::Error;
+use std::hash::{DefaultHasher, Hasher};
+use std::io::SeekFrom;
+use time::OffsetDateTime;
+
+#[cfg(not(feature = "async"))]
+use std::{
+    fs,
+    io::{Read, Seek},
+};
+#[cfg(feature = "async")]
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+#[derive(Parser, Debug)]
+pub struct PutCmd {
+    /// Continue a previous, interrupted upload from the current end of the remote file,
+    /// instead of overwriting it.
+    ///
+    /// The end of the remote file is compared to the local file before continuing.
+    #[arg(long)]
+    pub resume: bool,
+
+    /// The number of KiB at the end of the remote file to compare to the local file, when resuming.
+    #[arg(long, default_value_t = 64)]
+    pub verify_kib: u64,
+
+    /// Local source file
+    pub from: std::path::PathBuf,
+    /// Remote destination file, or a directory to upload the file into, under its local name.
+    pub to: UncPath,
+}
+
+#[maybe_async]
+pub async fn put(cmd: &PutCmd, cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let metadata = fs::metadata(&cmd.from).await?;
+    if !metadata.is_file() {
+        return Err(format!("{} is not a file", cmd.from.display()).into());
+    }
+
+    let client = Client::new(cli.make_smb_client_config()?);
+    client
+        .share_connect(&cmd.to, cli.username.as_str(), cli.password.clone())
+        .await?;
+    let result = upload(cmd, &client, metadata.len(), metadata.modified()?.into()).await;
+    client.close().await?;
+    result
+}
+
+/// Opens the remote file and uploads the local file into it, then closes it.
+#[maybe_async]
+async fn upload(
+    cmd: &PutCmd,
+    client: &Client,
+    local_len: u64,
+    modified: OffsetDateTime,
+) -> Result<(), Box<dyn Error>> {
+    let to = destination(cmd, client).await?;
+    log::debug!("Uploading to {to}");
+    let args = FileCreateArgs {
+        disposition: if cmd.resume {
+            CreateDisposition::OpenIf
+        } else {
+            CreateDisposition::OverwriteIf
+        },
+        attributes: FileAttributes::new().with_archive(true),
+        options: CreateOptions::new().with_non_directory_file(true),
+        // Reading is required to verify the remote file when resuming.
+        desired_access: FileAccessMask::standard_rw(),
+        durable: None,
+        snapshot: None,
+    };
+    let remote = client.create_file(&to, &args).await?.unwrap_file();
+
+    let max_write_size = client
+        .get_connection(to.server())
+        .await?
+        .negotiate_info()
+        .ok_or("Connection is not negotiated")?
+        .max_write_size;
+    let result = write_remote(cmd, &remote, local_len, max_write_size as usize).await;
+    let result = match result {
+        Ok(()) => set_modified(&remote, modified).await,
+        Err(e) => Err(e),
+    };
+    // The remote file is kept after a failure, so the upload may be resumed.
+    let closed = remote.close().await;
+    result?;
+    Ok(closed?)
+}
+
+/// Returns the path of the remote file: the destination, or the local file name under it,
+/// if the destination is an existing directory.
+#[maybe_async]
+async fn destination(cmd: &PutCmd, client: &Client) -> Result<UncPath, Box<dyn Error>> {
+    let is_dir = match cmd.to.path() {
+        None | Some("") => true,
+        Some(path) if path.ends_with('\\') => true,
+        Some(_) => match client.metadata(&cmd.to).await {
+            Ok(info) => info.basic.file_attributes.directory(),
+            Err(e) if e.is_not_found() => false,
+            Err(e) => return Err(e.into()),
+        },
+    };
+    if !is_dir {
+        return Ok(cmd.to.clone());
+    }
+
+    let file_name = cmd
+        .from
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("{} has no valid file name", cmd.from.display()))?;
+    Ok(cmd.to.clone().with_add_path(file_name))
+}
+
+/// Writes the local file to the remote file, from the offset to resume from (or the start),
+/// one chunk of up to `max_write_size` bytes at a time.
+///
+/// Chunks are written in order, so the end of the remote file always marks where the upload stopped.
+#[maybe_async]
+async fn write_remote(
+    cmd: &PutCmd,
+    remote: &File,
+    local_len: u64,
+    max_write_size: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut local = fs::File::open(&cmd.from).await?;
+    let mut position = if cmd.resume {
+        resume_offset(cmd, remote, &mut local, local_len).await?
+    } else {
+        0
+    };
+    local.seek(SeekFrom::Start(position)).await?;
+
+    let progress = make_progress_bar(local_len);
+    progress.set_position(position);
+    let mut buf = vec![0; max_write_size.max(1)];
+    while position < local_len {
+        let chunk = &mut buf[..max_write_size.min((local_len - position) as usize)];
+        local.read_exact(chunk).await?;
+        if let Err(e) = remote.write_block(chunk, position, None).await {
+            progress.abandon_with_message("Upload failed");
+            return Err(format!(
+                "upload failed after {position} of {local_len} bytes, use --resume to continue it: {e}"
+            )
+            .into());
+        }
+        position += chunk.len() as u64;
+        progress.set_position(position);
+    }
+    progress.finish_with_message("Upload complete");
+    Ok(())
+}
+
+/// Returns the offset to resume the upload from, which is the current end of the remote file,
+/// after verifying that the last [`PutCmd::verify_kib`] KiB before it match the local file.
+#[maybe_async]
+async fn resume_offset(
+    cmd: &PutCmd,
+    remote: &File,
+    local: &mut fs::File,
+    local_len: u64,
+) -> Result<u64, Box<dyn Error>> {
+    let remote_len = remote
+        .query_info::<FileStandardInformation>()
+        .await?
+        .end_of_file;
+    if remote_len > local_len {
+        return Err(format!(
+            "the remote file is larger than the local file ({remote_len} > {local_len} bytes), upload without --resume to overwrite it"
+        )
+        .into());
+    }
+
+    let tail_len = remote_len.min(cmd.verify_kib * 1024);
+    let tail_start = remote_len - tail_len;
+    let mut remote_tail = vec![0; tail_len as usize];
+    let read = remote.read_all_at(&mut remote_tail, tail_start).await?;
+    remote_tail.truncate(read);
+    let mut local_tail = vec![0; tail_len as usize];
+    local.seek(SeekFrom::Start(tail_start)).await?;
+    local.read_exact(&mut local_tail).await?;
+    if hash(&remote_tail) != hash(&local_tail) {
+        return Err(format!(
+            "the last {tail_len} bytes of the remote file differ from the local file, upload without --resume to overwrite it"
+        )
+        .into());
+    }
+
+    log::info!("Resuming upload at {remote_len} of {local_len} bytes");
+    Ok(remote_len)
+}
+
+fn hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(data);
+    hasher.finish()
+}
+
+/// Sets the last write time of the remote file to the modification time of the local file.
+#[maybe_async]
+async fn set_modified(remote: &File, modified: OffsetDateTime) -> Result<(), Box<dyn Error>> {
+    // Zero times and attributes are left unchanged.
+    remote
+        .set_info(FileBasicInformation {
+            creation_time: FileTime::default(),
+            last_access_time: FileTime::default(),
+            last_write_time: modified.into(),
+            change_time: FileTime::default(),
+            file_attributes: FileAttributes::new(),
+        })
+        .await?;
+    Ok(())
+}
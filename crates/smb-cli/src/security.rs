@@ -139,8 +139,8 @@ pub async fn get_security(
     let resource = open_resource(security_cmd, cli, access).await?;
     let resource_handle = resource_handle(&resource);
 
-    let additional_info = AdditionalInfo::new().with_dacl_security_information(cmd.dacl);
-    let security_info = resource_handle.query_security_info(additional_info).await?;
+    let to_query = SecurityInformation::new().with_dacl(cmd.dacl);
+    let security_info = resource_handle.query_security_info(to_query).await?;
 
     log::info!("Security info for {}:", security_cmd.path);
     // TODO: pretty print
@@ -166,9 +166,9 @@ pub async fn set_security(
     let resource_handle = resource_handle(&resource);
 
     // Query only the required information ot perform the update
-    let to_set = AdditionalInfo::new().with_dacl_security_information(write_dacl);
+    let to_set = SecurityInformation::new().with_dacl(write_dacl);
 
-    if to_set.into_bytes().iter().all(|f| *f == 0u8) {
+    if to_set.is_empty() {
         log::debug!("No security information to set.");
         return Ok(());
     }
@@ -6,8 +6,9 @@ use std::ops::Deref;
 use std::time::{Duration, SystemTime};
 
 use binrw::prelude::*;
-use time::PrimitiveDateTime;
+use time::format_description::well_known::Rfc3339;
 use time::macros::datetime;
+use time::{OffsetDateTime, PrimitiveDateTime};
 
 #[derive(BinRead, BinWrite, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct FileTime {
@@ -24,8 +25,8 @@ impl FileTime {
     ///
     /// > This is a legacy method. Use `FileTime::Into<PrimitiveDateTime>` instead.
     pub fn date_time(&self) -> PrimitiveDateTime {
-        let duration = core::time::Duration::from_nanos(self.value * Self::SCALE_VALUE_TO_NANOS);
-        Self::EPOCH + duration
+        let dt = self.to_datetime();
+        PrimitiveDateTime::new(dt.date(), dt.time())
     }
 
     /// A constant representing a zero FileTime value.
@@ -38,6 +39,41 @@ impl FileTime {
         self.value == 0
     }
 
+    /// Returns true if the FileTime value is zero, which means "not specified":
+    /// a time the server did not report, or that is left unchanged when setting information.
+    ///
+    /// Check this before converting the time using [`FileTime::to_datetime`],
+    /// which converts it to the FILETIME epoch (January 1, 1601).
+    pub fn is_unspecified(&self) -> bool {
+        self.is_zero()
+    }
+
+    /// Returns the current time.
+    pub fn now() -> Self {
+        OffsetDateTime::now_utc().into()
+    }
+
+    /// Returns the FileTime of a Unix timestamp, in seconds since January 1, 1970 (UTC).
+    ///
+    /// Returns `None` if the timestamp is before the FILETIME epoch (January 1, 1601),
+    /// or out of the range of dates supported by [`time`].
+    pub fn from_unix_timestamp(timestamp: i64) -> Option<Self> {
+        let dt = OffsetDateTime::from_unix_timestamp(timestamp).ok()?;
+        (dt >= Self::EPOCH.assume_utc()).then(|| dt.into())
+    }
+
+    /// Converts the FileTime to a UTC [`OffsetDateTime`].
+    ///
+    /// Times past the last date supported by [`time`] (the end of year 9999), such as the
+    /// maximum value some fields use for "never", are clamped to it.
+    pub fn to_datetime(&self) -> OffsetDateTime {
+        let duration = time::Duration::try_from(self.since_epoch()).unwrap_or(time::Duration::MAX);
+        Self::EPOCH
+            .checked_add(duration)
+            .unwrap_or(PrimitiveDateTime::MAX)
+            .assume_utc()
+    }
+
     /// Returns the duration since the FILETIME epoch (January 1, 1601).
     ///
     /// This is useful for cases where the file time represents a duration offset.
@@ -48,9 +84,14 @@ impl FileTime {
     }
 }
 
+/// Formats the time as an RFC 3339 UTC timestamp, such as `2025-01-20T15:36:20.2776324Z`.
 impl Display for FileTime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.date_time().fmt(f)
+        let formatted = self
+            .to_datetime()
+            .format(&Rfc3339)
+            .map_err(|_| std::fmt::Error)?;
+        f.write_str(&formatted)
     }
 }
 
@@ -81,6 +122,24 @@ impl From<FileTime> for PrimitiveDateTime {
     }
 }
 
+/// Converts times after the FILETIME epoch (January 1, 1601). Earlier times are converted to [`FileTime::ZERO`].
+impl From<OffsetDateTime> for FileTime {
+    fn from(dt: OffsetDateTime) -> Self {
+        let duration = dt - Self::EPOCH.assume_utc();
+        Self {
+            value: (duration.whole_nanoseconds() / Self::SCALE_VALUE_TO_NANOS as i128).max(0)
+                as u64,
+        }
+    }
+}
+
+/// See [`FileTime::to_datetime`].
+impl From<FileTime> for OffsetDateTime {
+    fn from(val: FileTime) -> Self {
+        val.to_datetime()
+    }
+}
+
 impl Deref for FileTime {
     type Target = u64;
 
@@ -96,6 +155,19 @@ impl From<FileTime> for SystemTime {
     }
 }
 
+/// Converts times after the FILETIME epoch (January 1, 1601), truncated to 100 nanoseconds.
+/// Earlier times are converted to [`FileTime::ZERO`].
+impl From<SystemTime> for FileTime {
+    fn from(src: SystemTime) -> FileTime {
+        let epoch = SystemTime::from(FileTime::EPOCH.as_utc());
+        let since_epoch = src.duration_since(epoch).unwrap_or_default();
+        let value = since_epoch.as_nanos() / Self::SCALE_VALUE_TO_NANOS as u128;
+        Self {
+            value: value.try_into().unwrap_or(u64::MAX),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -111,6 +183,22 @@ mod tests {
         assert_eq!(time::UtcDateTime::from(result), TEST_VAL1_DT.as_utc());
     }
 
+    #[test]
+    pub fn test_file_time_system_time() {
+        let st = SystemTime::from(TEST_VAL1_DT.as_utc());
+        assert_eq!(*FileTime::from(st), TEST_VAL1_U64);
+        assert_eq!(SystemTime::from(FileTime::from(st)), st);
+        // Truncated to 100 nanoseconds.
+        assert_eq!(
+            *FileTime::from(st + Duration::from_nanos(99)),
+            TEST_VAL1_U64
+        );
+        assert_eq!(
+            FileTime::from(SystemTime::UNIX_EPOCH - Duration::from_secs(11644473601)),
+            FileTime::ZERO
+        );
+    }
+
     #[test]
     pub fn test_file_time_from_datetime_correct() {
         assert_eq!(*FileTime::from(TEST_VAL1_DT), TEST_VAL1_U64)
@@ -120,6 +208,51 @@ mod tests {
     pub fn test_zero_file_time() {
         let ft = FileTime::ZERO;
         assert!(ft.is_zero());
+        assert!(ft.is_unspecified());
         assert_eq!(ft.date_time(), FileTime::EPOCH);
+        assert!(!FileTime::from(TEST_VAL1_U64).is_unspecified());
+    }
+
+    #[test]
+    pub fn test_file_time_offset_date_time() {
+        let ft = FileTime::from(TEST_VAL1_U64);
+        assert_eq!(ft.to_datetime(), TEST_VAL1_DT.assume_utc());
+        assert_eq!(FileTime::from(OffsetDateTime::from(ft)), ft);
+        // Times before the epoch, and after the last supported date.
+        assert_eq!(
+            FileTime::from(datetime!(1600-12-31 00:00:00 UTC)),
+            FileTime::ZERO
+        );
+        assert_eq!(
+            FileTime::from(u64::MAX).to_datetime(),
+            PrimitiveDateTime::MAX.assume_utc()
+        );
+    }
+
+    #[test]
+    pub fn test_file_time_unix_timestamp() {
+        assert_eq!(
+            FileTime::from_unix_timestamp(0).unwrap().to_datetime(),
+            OffsetDateTime::UNIX_EPOCH
+        );
+        assert_eq!(
+            FileTime::from_unix_timestamp(1737387380).unwrap(),
+            FileTime::from(datetime!(2025-01-20 15:36:20))
+        );
+        assert_eq!(
+            FileTime::from_unix_timestamp(-11644473600),
+            Some(FileTime::ZERO)
+        );
+        assert_eq!(FileTime::from_unix_timestamp(-11644473601), None);
+        assert!(FileTime::now() > FileTime::from_unix_timestamp(1737387380).unwrap());
+    }
+
+    #[test]
+    pub fn test_file_time_display_and_order() {
+        let ft = FileTime::from(TEST_VAL1_U64);
+        assert_eq!(ft.to_string(), "2025-01-20T15:36:20.2776324Z");
+        assert_eq!(FileTime::ZERO.to_string(), "1601-01-01T00:00:00Z");
+        assert!(FileTime::ZERO < ft);
+        assert!(ft < FileTime::from(TEST_VAL1_U64 + 1));
     }
 }
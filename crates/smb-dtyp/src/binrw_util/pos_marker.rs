@@ -35,7 +35,7 @@ impl<T> PosMarker<T> {
     }
 
     fn get_pos(&self) -> binrw::BinResult<u64> {
-        let value = self.pos.get().ok_or(binrw::error::Error::Custom {
+        let value = self.pos.get().ok_or_else(|| binrw::error::Error::Custom {
             pos: 0,
             err: Box::new("PosMarker has not been written to yet"),
         })?;
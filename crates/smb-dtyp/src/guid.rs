@@ -149,7 +149,7 @@ impl Display for Guid {
         // Print first fields in little endian, and the rest in big endian:
         write!(
             f,
-            "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:12x}",
+            "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:012x}",
             self.0,
             self.1,
             self.2,
@@ -181,7 +181,7 @@ mod tests {
         0x1543,
         [0xb0, 0x4f, 0x10, 0xe6, 0x90, 0x84, 0xc9, 0xae],
     );
-    const TEST_GUID_BYTES: &'static str = "f1ad5e06af6d4315b04f10e69084c9ae";
+    const TEST_GUID_BYTES: &str = "f1ad5e06af6d4315b04f10e69084c9ae";
 
     #[test]
     pub fn test_guid_parse_runtime() {
@@ -190,6 +190,13 @@ mod tests {
         assert_eq!(guid.to_string(), TEST_GUID_STR);
     }
 
+    #[test]
+    pub fn test_guid_display_leading_zeros() {
+        const GUID_STR: &str = "00000001-0002-0003-0004-000000000005";
+        let guid = GUID_STR.parse::<Guid>().unwrap();
+        assert_eq!(guid.to_string(), GUID_STR);
+    }
+
     #[test]
     pub fn test_const_guid() {
         assert_eq!(make_guid!(TEST_GUID_STR), PARSED_GUID_VALUE);
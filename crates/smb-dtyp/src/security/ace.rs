@@ -15,8 +15,100 @@ use super::SID;
 ///
 /// It's input is the name of the struct to generate, and in {}, the list of fields to add
 /// before the common fields. include support for `#[skip]` fields, without visibility (all fields are public).
+///
+/// The struct may be followed by a `generic_mapping` block, listing the fields that each generic right
+/// maps to for the type. This generates the `standard_*` and `full_control` constructors,
+/// and conversions from and to [`GenericRights`][crate::GenericRights].
 #[macro_export]
 macro_rules! access_mask {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+        $(
+            $(#[$field_meta:meta])*
+            $field_name:ident : $field_ty:ty,
+        )*
+        }
+        generic_mapping {
+            read: [$($read:ident),+ $(,)?],
+            write: [$($write:ident),+ $(,)?],
+            execute: [$($execute:ident),+ $(,)?],
+            all: [$($all:ident),+ $(,)?] $(,)?
+        }
+    ) => {
+    $crate::access_mask! {
+        $(#[$meta])*
+        $vis struct $name {
+        $(
+            $(#[$field_meta])*
+            $field_name : $field_ty,
+        )*
+        }
+    }
+
+    ::pastey::paste! {
+    impl $name {
+        /// Returns the rights that `GENERIC_READ` maps to.
+        pub fn standard_read() -> Self {
+            Self::new()$(.[<with_ $read>](true))+
+        }
+
+        /// Returns the rights that `GENERIC_WRITE` maps to.
+        pub fn standard_write() -> Self {
+            Self::new()$(.[<with_ $write>](true))+
+        }
+
+        /// Returns the rights that `GENERIC_EXECUTE` maps to.
+        pub fn standard_execute() -> Self {
+            Self::new()$(.[<with_ $execute>](true))+
+        }
+
+        /// Returns the rights that both `GENERIC_READ` and `GENERIC_WRITE` map to.
+        pub fn standard_rw() -> Self {
+            Self::from_generic($crate::GenericRights::READ | $crate::GenericRights::WRITE)
+        }
+
+        /// Returns the rights that `GENERIC_ALL` maps to.
+        pub fn full_control() -> Self {
+            Self::new()$(.[<with_ $all>](true))+
+        }
+
+        /// Maps the generic rights to the specific and standard rights of this type.
+        pub fn from_generic(rights: $crate::GenericRights) -> Self {
+            let mut bits = 0;
+            for (set, mapping) in [
+                (rights.read, Self::standard_read()),
+                (rights.write, Self::standard_write()),
+                (rights.execute, Self::standard_execute()),
+                (rights.all, Self::full_control()),
+            ] {
+                if set {
+                    bits |= u32::from_le_bytes(mapping.into_bytes());
+                }
+            }
+            Self::from_bytes(bits.to_le_bytes())
+        }
+
+        /// Returns the generic rights that are set, or whose whole mapping is included in the mask.
+        ///
+        /// This is an approximation: rights that do not complete the mapping of any generic right are dropped.
+        pub fn to_generic_approximation(&self) -> $crate::GenericRights {
+            let bits = u32::from_le_bytes(self.into_bytes());
+            let covers = |mapping: Self| {
+                let mapping = u32::from_le_bytes(mapping.into_bytes());
+                bits & mapping == mapping
+            };
+            let all = self.generic_all() || covers(Self::full_control());
+            $crate::GenericRights {
+                read: all || self.generic_read() || covers(Self::standard_read()),
+                write: all || self.generic_write() || covers(Self::standard_write()),
+                execute: all || self.generic_execute() || covers(Self::standard_execute()),
+                all,
+            }
+        }
+    }
+    }
+    };
     (
         $(#[$meta:meta])*
         $vis:vis struct $name:ident {
@@ -81,6 +173,57 @@ macro_rules! access_mask {
 
 }
 
+/// A set of [generic access rights](<https://learn.microsoft.com/en-us/windows/win32/secauthz/generic-access-rights>).
+///
+/// Each object type maps the generic rights to its own specific and standard rights.
+/// Access masks that define a generic mapping using [`access_mask!`][crate::access_mask]
+/// convert from and to this type with `from_generic` and `to_generic_approximation`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GenericRights {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+    pub all: bool,
+}
+
+impl GenericRights {
+    pub const NONE: Self = Self {
+        read: false,
+        write: false,
+        execute: false,
+        all: false,
+    };
+    pub const READ: Self = Self {
+        read: true,
+        ..Self::NONE
+    };
+    pub const WRITE: Self = Self {
+        write: true,
+        ..Self::NONE
+    };
+    pub const EXECUTE: Self = Self {
+        execute: true,
+        ..Self::NONE
+    };
+    pub const ALL: Self = Self {
+        all: true,
+        ..Self::NONE
+    };
+}
+
+impl std::ops::BitOr for GenericRights {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            read: self.read || rhs.read,
+            write: self.write || rhs.write,
+            execute: self.execute || rhs.execute,
+            all: self.all || rhs.all,
+        }
+    }
+}
+
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct ACE {
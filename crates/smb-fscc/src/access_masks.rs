@@ -34,6 +34,16 @@ pub struct FileAccessMask {
     file_write_attributes: bool,
     #[skip]
     __: B7,
+}
+generic_mapping {
+    read: [file_read_data, file_read_ea, file_read_attributes, read_control, synchronize],
+    write: [file_write_data, file_append_data, file_write_ea, file_write_attributes, read_control, synchronize],
+    execute: [file_execute, file_read_attributes, read_control, synchronize],
+    all: [
+        file_read_data, file_write_data, file_append_data, file_read_ea, file_write_ea, file_execute,
+        file_delete_child, file_read_attributes, file_write_attributes,
+        delete, read_control, write_dacl, write_owner, synchronize,
+    ],
 }}
 
 access_mask! {
@@ -67,6 +77,16 @@ access_mask! {
         #[skip]
         __: B7,
     }
+    generic_mapping {
+        read: [list_directory, read_ea, read_attributes, read_control, synchronize],
+        write: [add_file, add_subdirectory, write_ea, write_attributes, read_control, synchronize],
+        execute: [traverse, read_attributes, read_control, synchronize],
+        all: [
+            list_directory, add_file, add_subdirectory, read_ea, write_ea, traverse,
+            delete_child, read_attributes, write_attributes,
+            delete, read_control, write_dacl, write_owner, synchronize,
+        ],
+    }
 }
 
 impl From<FileAccessMask> for DirAccessMask {
@@ -82,3 +102,96 @@ impl From<DirAccessMask> for FileAccessMask {
         FileAccessMask::from_bytes(val.into_bytes())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smb_dtyp::GenericRights;
+
+    /// `FILE_GENERIC_READ`, `FILE_GENERIC_WRITE`, `FILE_GENERIC_EXECUTE` and `FILE_ALL_ACCESS`,
+    /// as documented for both files and directories.
+    const FILE_GENERIC_READ: u32 = 0x0012_0089;
+    const FILE_GENERIC_WRITE: u32 = 0x0012_0116;
+    const FILE_GENERIC_EXECUTE: u32 = 0x0012_00a0;
+    const FILE_ALL_ACCESS: u32 = 0x001f_01ff;
+
+    fn file_mask(bits: u32) -> FileAccessMask {
+        FileAccessMask::from_bytes(bits.to_le_bytes())
+    }
+
+    #[test]
+    fn test_generic_mapping() {
+        assert_eq!(
+            FileAccessMask::standard_read(),
+            file_mask(FILE_GENERIC_READ)
+        );
+        assert_eq!(
+            FileAccessMask::standard_write(),
+            file_mask(FILE_GENERIC_WRITE)
+        );
+        assert_eq!(
+            FileAccessMask::standard_execute(),
+            file_mask(FILE_GENERIC_EXECUTE)
+        );
+        assert_eq!(
+            FileAccessMask::standard_rw(),
+            file_mask(FILE_GENERIC_READ | FILE_GENERIC_WRITE)
+        );
+        assert_eq!(FileAccessMask::full_control(), file_mask(FILE_ALL_ACCESS));
+
+        for (rights, bits) in [
+            (GenericRights::READ, FILE_GENERIC_READ),
+            (GenericRights::WRITE, FILE_GENERIC_WRITE),
+            (GenericRights::EXECUTE, FILE_GENERIC_EXECUTE),
+            (GenericRights::ALL, FILE_ALL_ACCESS),
+        ] {
+            assert_eq!(FileAccessMask::from_generic(rights), file_mask(bits));
+            assert_eq!(
+                DirAccessMask::from_generic(rights),
+                DirAccessMask::from(file_mask(bits))
+            );
+        }
+        assert_eq!(
+            FileAccessMask::from_generic(GenericRights::NONE),
+            FileAccessMask::new()
+        );
+    }
+
+    #[test]
+    fn test_to_generic_approximation() {
+        assert_eq!(
+            file_mask(FILE_GENERIC_READ | FILE_GENERIC_EXECUTE).to_generic_approximation(),
+            GenericRights::READ | GenericRights::EXECUTE
+        );
+        // Missing a single bit of the mapping.
+        assert_eq!(
+            FileAccessMask::standard_write()
+                .with_file_append_data(false)
+                .with_delete(true)
+                .to_generic_approximation(),
+            GenericRights::NONE
+        );
+        assert_eq!(
+            FileAccessMask::full_control().to_generic_approximation(),
+            GenericRights::READ
+                | GenericRights::WRITE
+                | GenericRights::EXECUTE
+                | GenericRights::ALL
+        );
+        assert_eq!(
+            FileAccessMask::new()
+                .with_generic_write(true)
+                .to_generic_approximation(),
+            GenericRights::WRITE
+        );
+        assert_eq!(
+            DirAccessMask::new()
+                .with_traverse(true)
+                .with_read_attributes(true)
+                .with_read_control(true)
+                .with_synchronize(true)
+                .to_generic_approximation(),
+            GenericRights::EXECUTE
+        );
+    }
+}
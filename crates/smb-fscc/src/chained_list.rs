@@ -4,7 +4,11 @@
 //! This struct wraps the value, and the offset, and provides a way to iterate over them.
 //! See [`ChainedItemList<T>`][crate::ChainedItemList] to see how to write this type when in a list.
 
-use std::{io::SeekFrom, ops::Deref};
+use std::{
+    io::{Cursor, SeekFrom},
+    marker::PhantomData,
+    ops::Deref,
+};
 
 use binrw::prelude::*;
 use smb_dtyp::binrw_util::prelude::*;
@@ -256,3 +260,86 @@ where
         Self { values }
     }
 }
+
+/// Lazily reads the items of a chained item list from a buffer.
+///
+/// Unlike [`ChainedItemList<T>`], which parses all the items up front, each item is parsed
+/// only when the iterator reaches it, so only the raw buffer is held in memory.
+///
+/// The iteration ends after the last item, or after the first item that fails to parse.
+pub struct ChainedItemReader<T, const OFFSET_PAD: u32 = CHAINED_ITEM_DEFAULT_OFFSET_PAD> {
+    cursor: Cursor<Vec<u8>>,
+    done: bool,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T, const OFFSET_PAD: u32> ChainedItemReader<T, OFFSET_PAD> {
+    /// Creates a reader over the little-endian chained items in `buffer`. An empty buffer has no items.
+    pub fn new(buffer: Vec<u8>) -> Self {
+        Self {
+            done: buffer.is_empty(),
+            cursor: Cursor::new(buffer),
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns whether the iteration has ended. A reader over an empty buffer is finished from the start.
+    pub fn is_finished(&self) -> bool {
+        self.done
+    }
+}
+
+impl<T, const OFFSET_PAD: u32> Iterator for ChainedItemReader<T, OFFSET_PAD>
+where
+    T: BinRead + BinWrite,
+    for<'a> <T as BinRead>::Args<'a>: Default,
+    for<'b> <T as BinWrite>::Args<'b>: Default,
+{
+    type Item = BinResult<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let position_before = self.cursor.position();
+        match ChainedItem::<T, OFFSET_PAD>::read_le(&mut self.cursor) {
+            Ok(item) => {
+                // See `ChainedItem::read_chained`: the last item seeks back to its start.
+                self.done = self.cursor.position() == position_before;
+                Some(Ok(item.value))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chained_item_reader() {
+        let list: ChainedItemList<u64, 8> = vec![1u64, 2, 3].into();
+        let mut buffer = Cursor::new(vec![]);
+        list.write_le(&mut buffer).unwrap();
+
+        let items = ChainedItemReader::<u64, 8>::new(buffer.into_inner())
+            .collect::<BinResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(items, [1, 2, 3]);
+        assert!(ChainedItemReader::<u64, 8>::new(vec![]).is_finished());
+    }
+
+    #[test]
+    fn test_chained_item_reader_stops_on_error() {
+        // The first item points at a second item, which is truncated.
+        let buffer = vec![8, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0];
+        let mut reader = ChainedItemReader::<u32, 4>::new(buffer);
+        assert_eq!(reader.next().unwrap().unwrap(), 1);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+}
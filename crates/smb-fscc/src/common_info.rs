@@ -8,7 +8,7 @@
 
 use std::ops::Deref;
 
-use binrw::{NullString, prelude::*};
+use binrw::{NullString, io::TakeSeekExt, prelude::*};
 use modular_bitfield::prelude::*;
 
 use smb_dtyp::binrw_util::prelude::*;
@@ -38,17 +38,21 @@ pub struct FileBasicInformation {
 
 /// Query or Set extended attribute (EA) information for a file.
 ///
+/// Use [`FileFullEaInformation::builder`] to build a list of entries.
+///
 /// [MS-FSCC 2.4.16](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/0eb94f48-6aac-41df-a878-79f4dcfd8989>)
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
 pub struct FileFullEaInformationInner {
     /// Can contain zero or more of the following flag values. Unused bit fields should be set to 0.
     pub flags: EaFlags,
+    // Length does NOT include the null terminator.
     #[bw(try_calc = ea_name.len().try_into())]
     ea_name_length: u8,
-    #[bw(calc = ea_value.len() as u16)]
+    #[bw(try_calc = ea_value.len().try_into())]
     ea_value_length: u16,
-    /// The name of the extended attribute. This field is not null-terminated.
+    /// The name of the extended attribute, which is followed by a null terminator.
+    #[br(map_stream = |s| s.take_seek(ea_name_length as u64 + 1))]
     #[br(assert(ea_name.len() == ea_name_length as usize))]
     pub ea_name: NullString,
     /// The value of the extended attribute. This field can be zero bytes in length.
@@ -63,7 +67,6 @@ pub struct FileFullEaInformationInner {
 #[derive(BinWrite, BinRead, Debug, Default, Clone, Copy, PartialEq, Eq)]
 #[bw(map = |&x| Self::into_bytes(x))]
 #[br(map = Self::from_bytes)]
-#[repr(u8)]
 pub struct EaFlags {
     #[skip]
     __: B7,
@@ -73,6 +76,63 @@ pub struct EaFlags {
 
 pub type FileFullEaInformation = ChainedItemList<FileFullEaInformationInner, 4>;
 
+impl FileFullEaInformationInner {
+    /// Creates a new entry, with no flags.
+    pub fn new(name: &str, value: &[u8]) -> Self {
+        Self {
+            flags: EaFlags::new(),
+            ea_name: name.into(),
+            ea_value: value.to_vec(),
+        }
+    }
+}
+
+impl FileFullEaInformation {
+    /// Returns a builder of a [`FileFullEaInformation`] list.
+    pub fn builder() -> FileFullEaInformationBuilder {
+        Default::default()
+    }
+}
+
+/// A builder of a [`FileFullEaInformation`] list, from `(name, value)` pairs.
+///
+/// The entries are encoded in the order they are added.
+#[derive(Debug, Default)]
+pub struct FileFullEaInformationBuilder {
+    entries: Vec<FileFullEaInformationInner>,
+}
+
+impl FileFullEaInformationBuilder {
+    /// Adds an extended attribute. Setting an empty value removes the attribute from the file.
+    pub fn add(self, name: &str, value: &[u8]) -> Self {
+        self.add_entry(FileFullEaInformationInner::new(name, value))
+    }
+
+    /// Adds an extended attribute, with [`EaFlags::file_need_ea`] set.
+    pub fn add_need_ea(self, name: &str, value: &[u8]) -> Self {
+        let mut entry = FileFullEaInformationInner::new(name, value);
+        entry.flags.set_file_need_ea(true);
+        self.add_entry(entry)
+    }
+
+    /// Adds all the `(name, value)` pairs.
+    pub fn extend<'a>(self, pairs: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> Self {
+        pairs
+            .into_iter()
+            .fold(self, |builder, (name, value)| builder.add(name, value))
+    }
+
+    /// Adds an entry.
+    pub fn add_entry(mut self, entry: FileFullEaInformationInner) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    pub fn build(self) -> FileFullEaInformation {
+        self.entries.into()
+    }
+}
+
 /// Query or Set file mode information.
 ///
 /// [MS-FSCC 2.4.31](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/52df7798-8330-474b-ac31-9afe8075640c>)
@@ -103,6 +163,33 @@ pub struct FileModeInformation {
     __: B19,
 }
 
+/// Query or Set the case sensitivity of a directory.
+///
+/// The names of the entries of a case-sensitive directory are compared case-sensitively,
+/// as on POSIX file systems, so that names differing only by case may coexist.
+/// Setting the flag requires write attributes access, and is supported by some file systems only,
+/// e.g. NTFS on Windows 10 1803 and later, which fail with `STATUS_NOT_SUPPORTED` otherwise.
+///
+/// MS-FSCC FileCaseSensitiveInformation (information class 71)
+#[binrw::binrw]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FileCaseSensitiveInformation {
+    /// The case sensitivity flags.
+    pub flags: FileCaseSensitiveFlags,
+}
+
+/// Flags of [`FileCaseSensitiveInformation`].
+#[bitfield]
+#[derive(BinWrite, BinRead, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[bw(map = |&x| Self::into_bytes(x))]
+#[br(map = Self::from_bytes)]
+pub struct FileCaseSensitiveFlags {
+    /// The directory is case-sensitive.
+    pub case_sensitive_dir: bool,
+    #[skip]
+    __: B31,
+}
+
 /// Query or Set named pipe information.
 ///
 /// [MS-FSCC 2.4.37](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/cd805dd2-9248-4024-ac0f-b87a702dd366>)
@@ -190,7 +277,7 @@ impl From<&str> for FileNameInformation {
 ///
 /// [MS-FSCC 2.1.2.1](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/c8e77b37-3909-4fe6-a4ea-2b9d423b1ee4>):
 #[binrw::binrw]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 #[brw(repr(u32))]
 pub enum ReparseTag {
@@ -365,6 +452,12 @@ mod tests {
     use super::*;
     use smb_tests::*;
 
+    test_binrw! {
+        struct FileCaseSensitiveInformation {
+            flags: FileCaseSensitiveFlags::new().with_case_sensitive_dir(true),
+        } => "01000000"
+    }
+
     test_binrw! {
         FileFullEaInformation: FileFullEaInformation::from(vec![
             FileFullEaInformationInner {
@@ -380,6 +473,29 @@ mod tests {
         ]) => "80000000000f67002443492e434154414c4f4748494e5400010063004d6963726f736f66742d57696e646f77732d436c69656e742d4465736b746f702d52657175697265642d5061636b6167653034313032317e333162663338353661643336346533357e61726d36347e7e31302e302e32323632312e353138352e636174000000000000064100534b544558540054686973206973206e6f74207265616c6c792074686520534b2c206974206973206a75737420736f6d652066616b6520746f206861766520736f6d652066756e00"
     }
 
+    // Entries are aligned to 4 bytes, except for the last one. The name lengths exclude the null terminators.
+    test_binrw! {
+        FileFullEaInformation => aligned: FileFullEaInformation::builder()
+            .add("A", b"xy")
+            .add_need_ea("NAME2", b"")
+            .add("C", &[1, 2, 3])
+            .build() => "0c00000000010200410078791000000080050000
+            4e414d4532000000000000000001030043000102
+            03"
+    }
+
+    #[test]
+    fn test_file_full_ea_information_builder() {
+        let built = FileFullEaInformation::builder()
+            .extend([("A", b"xy".as_slice()), ("B", b"z".as_slice())])
+            .build();
+        let expected = FileFullEaInformation::from(vec![
+            FileFullEaInformationInner::new("A", b"xy"),
+            FileFullEaInformationInner::new("B", b"z"),
+        ]);
+        assert_eq!(built, expected);
+    }
+
     test_binrw! {
         struct FilePipeInformation {
             read_mode: PipeReadMode::Message,
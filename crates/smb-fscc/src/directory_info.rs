@@ -37,6 +37,45 @@ impl QueryDirectoryInfo {
     pub const CHAINED_ALIGNMENT: u32 = 8;
 }
 
+/// The name of an entry in a directory listing, common to all the directory information classes.
+pub trait QueryDirectoryEntryName {
+    /// The name of the entry.
+    fn entry_name(&self) -> &SizedWideString;
+
+    /// Returns whether this is the `.` or the `..` entry, which some servers include in listings
+    /// (depending on the information class and pattern), and others omit.
+    fn is_dot_entry(&self) -> bool {
+        const DOT: u16 = b'.' as u16;
+        matches!(self.entry_name().as_slice(), [DOT] | [DOT, DOT])
+    }
+}
+
+macro_rules! impl_entry_name {
+    ($($name:ident,)+) => {
+        $(
+            impl QueryDirectoryEntryName for $name {
+                fn entry_name(&self) -> &SizedWideString {
+                    &self.file_name
+                }
+            }
+        )+
+    };
+}
+
+impl_entry_name! {
+    FileDirectoryInformation,
+    FileFullDirectoryInformation,
+    FileIdFullDirectoryInformation,
+    FileBothDirectoryInformation,
+    FileIdBothDirectoryInformation,
+    FileNamesInformation,
+    FileIdExtdDirectoryInformation,
+    FileId64ExtdDirectoryInformation,
+    FileId64ExtdBothDirectoryInformation,
+    FileIdAllExtdDirectoryInformation,
+    FileIdAllExtdBothDirectoryInformation,
+}
+
 /// Since most of the directory information types are very similar (or at least share a lot of fields in their beginning),
 /// we use this macro to reduce code duplication when defining them.
 macro_rules! query_dir_type {
@@ -51,7 +90,7 @@ macro_rules! query_dir_type {
     ) => {
         pastey::paste! {
             #[binrw::binrw]
-            #[derive(Debug, PartialEq, Eq)]
+            #[derive(Debug, Clone, PartialEq, Eq)]
             $(#[$meta])*
             ///
             /// > Note: This should be wrapped in [`ChainedItemList<T>`][crate::ChainedItemList] to represent a list of these structures.
@@ -710,4 +749,22 @@ mod tests {
         FullDirectory: "48000000000000003d22211904e1db01e34e133604e1db01e34e133604e1db01a7e0363604e1db01000000000000000000000000000000001000000002000000000000002e000000480000000000000022fdbb73afa5db0162f647ed6a3cdc0162f647ed6a3cdc0162f647ed6a3cdc01000000000000000000000000000000001000000004000000000000002e002e0060000000000000009843301904e1db0111cb0e1c04e1db01242f8155b6a5db0111cb0e1c04e1db0100b4ff000000000000c0ff0000000000200000001800000080000000420069006e0067004d006100700073002e0064006c006c00000000006000000000000000ee6a511c04e1db01aff3941e04e1db012f9aa1dac7acdb01f6702a3d7f3fdc0100c60b030000000000d00b03000000002000000018000000780000006500640067006500680074006d006c002e0064006c006c00000000000000000000000000a042a32704e1db01fc50352a04e1db01053587dbc7acdb01fc50352a04e1db01005686020000000000608602000000002000000014000000780000006d007300680074006d006c002e0064006c006c00",
         Directory: "48000000000000003d22211904e1db01e34e133604e1db01e34e133604e1db01a7e0363604e1db010000000000000000000000000000000010000000020000002e00000000000000480000000000000022fdbb73afa5db0162f647ed6a3cdc0162f647ed6a3cdc0162f647ed6a3cdc010000000000000000000000000000000010000000040000002e002e000000000058000000000000009843301904e1db0111cb0e1c04e1db01242f8155b6a5db0111cb0e1c04e1db0100b4ff000000000000c0ff00000000002000000018000000420069006e0067004d006100700073002e0064006c006c005800000000000000ee6a511c04e1db01aff3941e04e1db012f9aa1dac7acdb01f6702a3d7f3fdc0100c60b030000000000d00b030000000020000000180000006500640067006500680074006d006c002e0064006c006c000000000000000000a042a32704e1db01fc50352a04e1db01053587dbc7acdb01fc50352a04e1db010056860200000000006086020000000020000000140000006d007300680074006d006c002e0064006c006c00"
     );
+
+    #[test]
+    fn test_is_dot_entry() {
+        for (name, is_dot) in [
+            (".", true),
+            ("..", true),
+            ("...", false),
+            (".hidden", false),
+            ("a.", false),
+            ("", false),
+        ] {
+            let entry = FileNamesInformation {
+                file_index: 0,
+                file_name: SizedWideString::from(name),
+            };
+            assert_eq!(entry.is_dot_entry(), is_dot, "{name:?}");
+        }
+    }
 }
@@ -80,7 +80,12 @@ pub struct FileSystemAttributes {
     /// The file system supports remote storage.
     pub supports_remote_storage: bool,
     #[skip]
-    __: B6,
+    __: B1,
+    /// The file system supports POSIX-style delete and rename operations,
+    /// see [`FileDispositionInformationEx`][crate::FileDispositionInformationEx].
+    pub supports_posix_unlink_rename: bool,
+    #[skip]
+    __: B4,
     /// The specified volume is a compressed volume. This flag is incompatible with the `file_compression` flag.
     pub volume_is_compressed: bool,
     /// The file system supports object identifiers.
@@ -28,11 +28,14 @@ pub trait FileInfoType:
 /// An internal macro for generating a file class enums,
 /// for both the file information class, and information value.
 /// including a trait for the value types.
+///
+/// The value type of each class is `File<Name>Information`,
+/// unless specified explicitly, e.g. `pub DispositionEx: FileDispositionInformationEx = 64`.
 macro_rules! file_info_classes {
     (
         $(#[doc = $docstring:literal])*
         $svis:vis $name:ident {
-            $($vis:vis $field_name:ident = $cid:literal,)+
+            $($vis:vis $field_name:ident $(: $value_type:ident)? = $cid:literal,)+
         }
     ) => {
         #[allow(unused_imports)]
@@ -82,7 +85,7 @@ macro_rules! file_info_classes {
             $svis enum $name {
                 $(
                     #[br(pre_assert(matches!(c, [<$name Class>]::[<$field_name Information>])))]
-                    [<$field_name Information>]([<File $field_name Information>]),
+                    [<$field_name Information>]($crate::file_info_value_type!($field_name $(, $value_type)?)),
                 )*
             }
 
@@ -108,13 +111,13 @@ macro_rules! file_info_classes {
             }
 
             $(
-                impl From<[<File $field_name Information>]> for $name {
-                    fn from(value: [<File $field_name Information>]) -> $name {
+                impl From<$crate::file_info_value_type!($field_name $(, $value_type)?)> for $name {
+                    fn from(value: $crate::file_info_value_type!($field_name $(, $value_type)?)) -> $name {
                         $name::[<$field_name Information>](value)
                     }
                 }
 
-                impl TryFrom<$name> for [<File $field_name Information>] {
+                impl TryFrom<$name> for $crate::file_info_value_type!($field_name $(, $value_type)?) {
                     type Error = $crate::SmbFsccError;
 
                     fn try_from(value: $name) -> Result<Self, Self::Error> {
@@ -126,7 +129,7 @@ macro_rules! file_info_classes {
                     }
                 }
 
-                impl [<$name Value>] for [<File $field_name Information>] {
+                impl [<$name Value>] for $crate::file_info_value_type!($field_name $(, $value_type)?) {
                     const CLASS_ID: [<$name Class>] = [<$name Class>]::[<$field_name Information>];
                 }
             )*
@@ -135,3 +138,15 @@ macro_rules! file_info_classes {
 }
 
 pub(crate) use file_info_classes;
+
+/// Resolves the value type of an information class in [`file_info_classes`].
+macro_rules! file_info_value_type {
+    ($field_name:ident) => {
+        pastey::paste! { [<File $field_name Information>] }
+    };
+    ($field_name:ident, $value_type:ident) => {
+        $value_type
+    };
+}
+
+pub(crate) use file_info_value_type;
@@ -13,6 +13,8 @@
 //! * Directory query types [`QueryDirectoryInfo`]
 //! * Change notifications [`FileNotifyInformation`]
 //! * Access masks [`FileAccessMask`], [`DirAccessMask`]
+//! * Reparse point buffers [`ReparseDataBuffer`]
+//! * Sparse file ranges [`FileAllocatedRangeBuffer`]
 
 #![allow(unused_parens)]
 
@@ -27,10 +29,12 @@ mod info_classes;
 mod notify;
 mod query_file_info;
 mod quota;
+mod reparse;
 mod set_file_info;
+mod sparse;
 
 pub use access_masks::*;
-pub use chained_list::{CHAINED_ITEM_PREFIX_SIZE, ChainedItemList};
+pub use chained_list::{CHAINED_ITEM_PREFIX_SIZE, ChainedItemList, ChainedItemReader};
 pub use common_info::*;
 pub use directory_info::*;
 pub use error::SmbFsccError;
@@ -40,4 +44,6 @@ pub use info_classes::*;
 pub use notify::*;
 pub use query_file_info::*;
 pub use quota::*;
+pub use reparse::*;
 pub use set_file_info::*;
+pub use sparse::*;
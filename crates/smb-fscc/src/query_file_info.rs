@@ -9,8 +9,9 @@ use std::ops::Deref;
 use binrw::{NullString, io::TakeSeekExt, prelude::*};
 
 use super::{
-    ChainedItemList, FileAccessMask, FileAttributes, FileBasicInformation, FileFullEaInformation,
-    FileModeInformation, FileNameInformation, FilePipeInformation, FilePositionInformation,
+    ChainedItemList, FileAccessMask, FileAttributes, FileBasicInformation,
+    FileCaseSensitiveInformation, FileFullEaInformation, FileModeInformation, FileNameInformation,
+    FilePipeInformation, FilePositionInformation,
 };
 use crate::{ReparseTag, file_info_classes};
 use smb_dtyp::binrw_util::prelude::*;
@@ -24,6 +25,7 @@ file_info_classes! {
         pub AlternateName = 21,
         pub AttributeTag = 35,
         pub Basic = 4,
+        pub CaseSensitive = 71,
         pub Compression = 28,
         pub Ea = 7,
         pub FullEa = 15,
@@ -15,7 +15,7 @@ use smb_dtyp::binrw_util::prelude::*;
 /// _Note_: This structure is partial: it does not contain the NextEntryOffset field, as it is intended to be used
 /// in a chained list, see [`ChainedItemList<T>`][crate::ChainedItemList].
 #[binrw::binrw]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FileQuotaInformation {
     #[bw(calc = PosMarker::default())]
     sid_length: PosMarker<u32>,
@@ -38,6 +38,73 @@ impl FileQuotaInformation {
         + SID::MIN_SIZE;
 }
 
+/// A quota entry of a user on a volume.
+///
+/// This is a typed representation of the [`FileQuotaInformation`] wire record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaEntry {
+    /// The SID of the user that the entry applies to.
+    pub sid: SID,
+    /// The amount of disk space used by the user, in bytes. This is ignored when setting quotas.
+    pub used: u64,
+    /// The warning threshold, in bytes. [`QuotaEntry::UNLIMITED`] means there is no threshold.
+    pub threshold: u64,
+    /// The quota limit, in bytes. [`QuotaEntry::UNLIMITED`] means there is no limit.
+    pub limit: u64,
+    /// The last time the entry was changed.
+    pub change_time: FileTime,
+}
+
+impl QuotaEntry {
+    /// The threshold or limit value that means no threshold or limit.
+    pub const UNLIMITED: u64 = u64::MAX;
+
+    /// Returns a new entry for setting the quota of a user.
+    pub fn new(sid: SID, threshold: u64, limit: u64) -> Self {
+        Self {
+            sid,
+            used: 0,
+            threshold,
+            limit,
+            change_time: FileTime::ZERO,
+        }
+    }
+
+    /// Returns whether the entry has a warning threshold.
+    pub fn has_threshold(&self) -> bool {
+        self.threshold != Self::UNLIMITED
+    }
+
+    /// Returns whether the entry has a quota limit.
+    pub fn has_limit(&self) -> bool {
+        self.limit != Self::UNLIMITED
+    }
+}
+
+impl From<FileQuotaInformation> for QuotaEntry {
+    fn from(value: FileQuotaInformation) -> Self {
+        Self {
+            sid: value.sid,
+            used: value.quota_used,
+            threshold: value.quota_threshold,
+            limit: value.quota_limit,
+            change_time: value.change_time,
+        }
+    }
+}
+
+impl From<QuotaEntry> for FileQuotaInformation {
+    fn from(value: QuotaEntry) -> Self {
+        Self {
+            change_time: value.change_time,
+            quota_used: value.used,
+            quota_threshold: value.threshold,
+            quota_limit: value.limit,
+            sid: value.sid,
+        }
+    }
+}
+
 /// This structure is used to provide the list of SIDs for which quota query information is requested.
 ///
 /// [MS-FSCC 2.4.41.1](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/56adae21-add4-4434-97ec-e40e87739d52>)
@@ -45,7 +112,7 @@ impl FileQuotaInformation {
 /// _Note_: This structure is partial: it does not contain the NextEntryOffset field, as it is intended to be used
 /// in a chained list, see [`ChainedItemList<T>`][crate::ChainedItemList].
 #[binrw::binrw]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FileGetQuotaInformation {
     #[bw(calc = PosMarker::default())]
     sid_length: PosMarker<u32>,
@@ -53,3 +120,52 @@ pub struct FileGetQuotaInformation {
     #[bw(write_with = PosMarker::write_size, args(&sid_length))]
     pub sid: SID,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChainedItemList;
+    use smb_tests::*;
+
+    fn make_entries() -> Vec<QuotaEntry> {
+        vec![
+            QuotaEntry {
+                sid: "S-1-5-21-1-2-3-1001".parse().unwrap(),
+                used: 0x1000,
+                threshold: 8 << 30,
+                limit: 10 << 30,
+                change_time: FileTime::from(0x01d9a1b2c3d4e5f6),
+            },
+            QuotaEntry::new(
+                SID::S_ADMINISTRATORS.parse().unwrap(),
+                QuotaEntry::UNLIMITED,
+                QuotaEntry::UNLIMITED,
+            ),
+        ]
+    }
+
+    type FileQuotaInformationList = ChainedItemList<FileQuotaInformation>;
+
+    fn make_quota_list() -> FileQuotaInformationList {
+        make_entries()
+            .into_iter()
+            .map(FileQuotaInformation::from)
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    test_binrw! {
+        FileQuotaInformationList: make_quota_list() => "440000001c000000f6e5d4c3b2a1d901001000000000000000000000020000000000008002000000010500000000000515000000010000000200000003000000e9030000000000001000000000000000000000000000000000000000ffffffffffffffffffffffffffffffff01020000000000052000000020020000"
+    }
+
+    #[test]
+    fn test_quota_entry_conversion() {
+        for entry in make_entries() {
+            let info = FileQuotaInformation::from(entry.clone());
+            assert_eq!(QuotaEntry::from(info), entry);
+        }
+        let [limited, unlimited] = make_entries().try_into().unwrap();
+        assert!(limited.has_threshold() && limited.has_limit());
+        assert!(!unlimited.has_threshold() && !unlimited.has_limit());
+    }
+}
@@ -0,0 +1,381 @@
+//! Reparse point data buffers
+//!
+//! MS-FSCC 2.1.2
+//!
+//! These are the outputs of `FSCTL_GET_REPARSE_POINT`, and the inputs of `FSCTL_SET_REPARSE_POINT`.
+
+use std::io::{Cursor, Read, Seek, Write};
+
+use binrw::prelude::*;
+use modular_bitfield::prelude::*;
+use smb_dtyp::Guid;
+
+use crate::ReparseTag;
+
+/// REPARSE_DATA_BUFFER and REPARSE_GUID_DATA_BUFFER - MS-FSCC 2.1.2.2,
+/// [MS-FSCC 2.1.2.3](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/a4d08374-0e92-43e2-8f88-88b94112f070>)
+///
+/// The reparse data of a file, by its reparse tag. Symbolic links and mount points are parsed,
+/// and other reparse points are kept as raw data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReparseDataBuffer {
+    /// A symbolic link, tagged [`ReparseTag::Symlink`].
+    Symlink(SymbolicLinkReparseDataBuffer),
+    /// A mount point (junction), tagged [`ReparseTag::MountPoint`].
+    MountPoint(MountPointReparseDataBuffer),
+    /// Any other reparse point.
+    Other {
+        reparse_tag: u32,
+        /// The GUID of the reparse point. Set if, and only if, the tag is not a Microsoft tag.
+        reparse_guid: Option<Guid>,
+        data: Vec<u8>,
+    },
+}
+
+impl ReparseDataBuffer {
+    /// The size of the header of the buffer, without the GUID of non-Microsoft tags.
+    const HEADER_SIZE: usize = size_of::<u32>() + size_of::<u16>() * 2;
+    /// The maximum size of a reparse point buffer, including its header.
+    pub const MAX_SIZE: usize = 16 * 1024;
+
+    /// Returns whether a reparse tag is a Microsoft tag, which has no GUID in its buffer.
+    pub fn is_microsoft_tag(reparse_tag: u32) -> bool {
+        reparse_tag & 0x80000000 != 0
+    }
+
+    /// Returns the reparse tag of the buffer.
+    pub fn reparse_tag(&self) -> u32 {
+        match self {
+            ReparseDataBuffer::Symlink(_) => ReparseTag::Symlink as u32,
+            ReparseDataBuffer::MountPoint(_) => ReparseTag::MountPoint as u32,
+            ReparseDataBuffer::Other { reparse_tag, .. } => *reparse_tag,
+        }
+    }
+
+    /// Returns the GUID of the buffer, for non-Microsoft tags.
+    pub fn reparse_guid(&self) -> Option<Guid> {
+        match self {
+            ReparseDataBuffer::Other { reparse_guid, .. } => *reparse_guid,
+            _ => None,
+        }
+    }
+
+    /// Returns the reparse data of the buffer, without its header.
+    pub fn data(&self) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        match self {
+            ReparseDataBuffer::Symlink(symlink) => symlink.write_le(&mut cursor).unwrap(),
+            ReparseDataBuffer::MountPoint(mount_point) => {
+                mount_point.write_le(&mut cursor).unwrap()
+            }
+            ReparseDataBuffer::Other { data, .. } => return data.clone(),
+        }
+        cursor.into_inner()
+    }
+
+    /// Returns the size of the buffer, including its header.
+    pub fn size(&self) -> usize {
+        Self::HEADER_SIZE + self.reparse_guid().map_or(0, |_| Guid::GUID_SIZE) + self.data().len()
+    }
+
+    /// Returns the target of a symbolic link or a mount point, as displayed to users.
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            ReparseDataBuffer::Symlink(symlink) => Some(&symlink.print_name),
+            ReparseDataBuffer::MountPoint(mount_point) => Some(&mount_point.print_name),
+            ReparseDataBuffer::Other { .. } => None,
+        }
+    }
+}
+
+impl BinRead for ReparseDataBuffer {
+    type Args<'a> = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<Self> {
+        let reparse_tag = u32::read_options(reader, endian, ())?;
+        let data_length = u16::read_options(reader, endian, ())?;
+        let _reserved = u16::read_options(reader, endian, ())?;
+        let reparse_guid = if Self::is_microsoft_tag(reparse_tag) {
+            None
+        } else {
+            Some(Guid::read_options(reader, endian, ())?)
+        };
+        let mut data = vec![0; data_length.into()];
+        reader.read_exact(&mut data)?;
+
+        let mut data_reader = Cursor::new(&data);
+        Ok(match reparse_tag {
+            tag if tag == ReparseTag::Symlink as u32 => ReparseDataBuffer::Symlink(
+                SymbolicLinkReparseDataBuffer::read_options(&mut data_reader, endian, ())?,
+            ),
+            tag if tag == ReparseTag::MountPoint as u32 => ReparseDataBuffer::MountPoint(
+                MountPointReparseDataBuffer::read_options(&mut data_reader, endian, ())?,
+            ),
+            _ => ReparseDataBuffer::Other {
+                reparse_tag,
+                reparse_guid,
+                data,
+            },
+        })
+    }
+}
+
+impl BinWrite for ReparseDataBuffer {
+    type Args<'a> = ();
+
+    fn write_options<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<()> {
+        let reparse_tag = self.reparse_tag();
+        let reparse_guid = self.reparse_guid();
+        if Self::is_microsoft_tag(reparse_tag) == reparse_guid.is_some() {
+            return Err(binrw::Error::AssertFail {
+                pos: writer.stream_position()?,
+                message: format!(
+                    "Reparse tag {reparse_tag:#x} requires a GUID if, and only if, it is not a Microsoft tag"
+                ),
+            });
+        }
+        let data = self.data();
+        let data_length = u16::try_from(data.len()).map_err(|_| binrw::Error::AssertFail {
+            pos: 0,
+            message: format!("Reparse data of {} bytes is too large", data.len()),
+        })?;
+
+        reparse_tag.write_options(writer, endian, ())?;
+        data_length.write_options(writer, endian, ())?;
+        0u16.write_options(writer, endian, ())?;
+        reparse_guid.write_options(writer, endian, ())?;
+        data.write_options(writer, endian, ())
+    }
+}
+
+/// Flags of a [`SymbolicLinkReparseDataBuffer`].
+#[bitfield]
+#[derive(BinWrite, BinRead, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[bw(map = |&x| Self::into_bytes(x))]
+#[br(map = Self::from_bytes)]
+pub struct SymbolicLinkFlags {
+    /// The substitute name is a path relative to the directory containing the symbolic link (`SYMLINK_FLAG_RELATIVE`).
+    pub relative: bool,
+    #[skip]
+    __: B31,
+}
+
+/// Symbolic Link Reparse Data Buffer - MS-FSCC 2.1.2.4
+///
+/// This is the reparse data of a [`ReparseDataBuffer::Symlink`], without the header of the buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolicLinkReparseDataBuffer {
+    /// The target of the link, as interpreted by the server, e.g. `\??\C:\target` or `..\target`.
+    pub substitute_name: String,
+    /// The target of the link, as displayed to users, e.g. `C:\target`.
+    pub print_name: String,
+    pub flags: SymbolicLinkFlags,
+}
+
+impl SymbolicLinkReparseDataBuffer {
+    /// Returns a link to `target`, using it as both the substitute and the print names.
+    ///
+    /// An absolute `target` should be in the NT form, which servers resolve (e.g. `\??\C:\target`);
+    /// the prefix is stripped from the print name. A relative `target` is relative to the directory of the link.
+    pub fn new(target: &str, relative: bool) -> Self {
+        let print_name = target.strip_prefix(r"\??\").unwrap_or(target);
+        Self {
+            substitute_name: target.to_string(),
+            print_name: print_name.to_string(),
+            flags: SymbolicLinkFlags::new().with_relative(relative),
+        }
+    }
+}
+
+impl BinRead for SymbolicLinkReparseDataBuffer {
+    type Args<'a> = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<Self> {
+        let offsets = NameOffsets::read_options(reader, endian, ())?;
+        let flags = SymbolicLinkFlags::read_options(reader, endian, ())?;
+        let (substitute_name, print_name) = offsets.read_names(reader)?;
+        Ok(Self {
+            substitute_name,
+            print_name,
+            flags,
+        })
+    }
+}
+
+impl BinWrite for SymbolicLinkReparseDataBuffer {
+    type Args<'a> = ();
+
+    fn write_options<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<()> {
+        let (offsets, path_buffer) = NameOffsets::make(&self.substitute_name, &self.print_name)?;
+        offsets.write_options(writer, endian, ())?;
+        self.flags.write_options(writer, endian, ())?;
+        path_buffer.write_options(writer, endian, ())
+    }
+}
+
+/// Mount Point Reparse Data Buffer - MS-FSCC 2.1.2.5
+///
+/// This is the reparse data of a [`ReparseDataBuffer::MountPoint`], without the header of the buffer.
+/// Mount points (junctions) always have an absolute target, such as `\??\C:\target`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountPointReparseDataBuffer {
+    /// The target of the mount point, as interpreted by the server, e.g. `\??\C:\target`.
+    pub substitute_name: String,
+    /// The target of the mount point, as displayed to users, e.g. `C:\target`.
+    pub print_name: String,
+}
+
+impl BinRead for MountPointReparseDataBuffer {
+    type Args<'a> = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<Self> {
+        let offsets = NameOffsets::read_options(reader, endian, ())?;
+        let (substitute_name, print_name) = offsets.read_names(reader)?;
+        Ok(Self {
+            substitute_name,
+            print_name,
+        })
+    }
+}
+
+impl BinWrite for MountPointReparseDataBuffer {
+    type Args<'a> = ();
+
+    fn write_options<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<()> {
+        let (offsets, path_buffer) = NameOffsets::make(&self.substitute_name, &self.print_name)?;
+        offsets.write_options(writer, endian, ())?;
+        path_buffer.write_options(writer, endian, ())
+    }
+}
+
+/// The offsets and lengths of the names in the path buffer of symbolic link and mount point buffers, in bytes.
+#[binrw::binrw]
+#[derive(Debug)]
+struct NameOffsets {
+    substitute_name_offset: u16,
+    substitute_name_length: u16,
+    print_name_offset: u16,
+    print_name_length: u16,
+}
+
+impl NameOffsets {
+    /// Makes the offsets and the path buffer of the names, with the substitute name first.
+    fn make(substitute_name: &str, print_name: &str) -> BinResult<(Self, Vec<u8>)> {
+        let path_buffer = substitute_name
+            .encode_utf16()
+            .chain(print_name.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        let too_long = |_| binrw::Error::AssertFail {
+            pos: 0,
+            message: "Reparse point names are too long".to_string(),
+        };
+        let substitute_name_length =
+            u16::try_from(substitute_name.encode_utf16().count() * 2).map_err(too_long)?;
+        let print_name_length =
+            u16::try_from(print_name.encode_utf16().count() * 2).map_err(too_long)?;
+        Ok((
+            Self {
+                substitute_name_offset: 0,
+                substitute_name_length,
+                print_name_offset: substitute_name_length,
+                print_name_length,
+            },
+            path_buffer,
+        ))
+    }
+
+    /// Reads the names from the path buffer, which is the rest of the reader.
+    fn read_names<R: Read + Seek>(&self, reader: &mut R) -> BinResult<(String, String)> {
+        let pos = reader.stream_position()?;
+        let mut path_buffer = vec![];
+        reader.read_to_end(&mut path_buffer)?;
+        let name = |offset: u16, length: u16| {
+            let range = offset as usize..offset as usize + length as usize;
+            let bytes = path_buffer
+                .get(range)
+                .filter(|bytes| bytes.len() % 2 == 0)
+                .ok_or_else(|| binrw::Error::AssertFail {
+                    pos,
+                    message: format!(
+                        "Invalid reparse point name at {offset}+{length} of {} bytes",
+                        path_buffer.len()
+                    ),
+                })?;
+            let chars = bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+            String::from_utf16(&chars).map_err(|e| binrw::Error::Custom {
+                pos,
+                err: Box::new(e),
+            })
+        };
+        Ok((
+            name(self.substitute_name_offset, self.substitute_name_length)?,
+            name(self.print_name_offset, self.print_name_length)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smb_tests::*;
+
+    test_binrw! {
+        ReparseDataBuffer => symlink: ReparseDataBuffer::Symlink(SymbolicLinkReparseDataBuffer::new(
+            r"\??\C:\t",
+            false,
+        )) => "0c0000a0240000000000100010000800000000005c003f003f005c0043003a005c00740043003a005c007400"
+    }
+
+    test_binrw! {
+        ReparseDataBuffer => relative_symlink: ReparseDataBuffer::Symlink(SymbolicLinkReparseDataBuffer::new(
+            r"..\t",
+            true,
+        )) => "0c0000a01c0000000000080008000800010000002e002e005c0074002e002e005c007400"
+    }
+
+    test_binrw! {
+        ReparseDataBuffer => mount_point: ReparseDataBuffer::MountPoint(MountPointReparseDataBuffer {
+            substitute_name: r"\??\C:\t".to_string(),
+            print_name: String::new(),
+        }) => "030000a01800000000001000100000005c003f003f005c0043003a005c007400"
+    }
+
+    test_binrw! {
+        ReparseDataBuffer => other: ReparseDataBuffer::Other {
+            reparse_tag: 0x00001234,
+            reparse_guid: Some("b0a1b2c3-d4e5-f607-0819-2a3b4c5d6e7f".parse().unwrap()),
+            data: vec![0x01, 0x02, 0x03],
+        } => "3412000003000000c3b2a1b0e5d407f608192a3b4c5d6e7f010203"
+    }
+}
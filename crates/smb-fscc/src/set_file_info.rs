@@ -8,11 +8,13 @@ use std::ops::Deref;
 
 use crate::file_info_classes;
 
+use binrw::prelude::*;
+use modular_bitfield::prelude::*;
 use smb_dtyp::binrw_util::prelude::*;
 
 use super::{
-    FileBasicInformation, FileFullEaInformation, FileModeInformation, FileNameInformation,
-    FilePipeInformation, FilePositionInformation,
+    FileBasicInformation, FileCaseSensitiveInformation, FileFullEaInformation, FileModeInformation,
+    FileNameInformation, FilePipeInformation, FilePositionInformation,
 };
 
 file_info_classes! {
@@ -20,7 +22,9 @@ file_info_classes! {
     pub SetFileInfo {
         pub Allocation = 19,
         pub Basic = 4,
+        pub CaseSensitive = 71,
         pub Disposition = 13,
+        pub DispositionEx: FileDispositionInformationEx = 64,
         pub EndOfFile = 20,
         pub FullEa = 15,
         pub Link = 11,
@@ -28,6 +32,7 @@ file_info_classes! {
         pub Pipe = 23,
         pub Position = 14,
         pub Rename = 10,
+        pub RenameEx: FileRenameInformationEx = 65,
         pub ShortName = 40,
         pub ValidDataLength = 39,
     }
@@ -65,6 +70,51 @@ impl Default for FileDispositionInformation {
     }
 }
 
+/// Mark a file for deletion, with extended options, such as POSIX semantics.
+///
+/// Check [`FileSystemAttributes::supports_posix_unlink_rename`][crate::FileSystemAttributes::supports_posix_unlink_rename]
+/// before requesting POSIX semantics, as not all file systems support them.
+///
+/// MS-FSCC 2.4.12
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileDispositionInformationEx {
+    /// The disposition flags. If none are set, the file is not deleted.
+    pub flags: FileDispositionFlags,
+}
+
+impl Default for FileDispositionInformationEx {
+    /// Marks the file for deletion, with POSIX semantics.
+    fn default() -> Self {
+        Self {
+            flags: FileDispositionFlags::new()
+                .with_delete(true)
+                .with_posix_semantics(true),
+        }
+    }
+}
+
+/// Flags of [`FileDispositionInformationEx`].
+#[bitfield]
+#[derive(BinWrite, BinRead, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[bw(map = |&x| Self::into_bytes(x))]
+#[br(map = Self::from_bytes)]
+pub struct FileDispositionFlags {
+    /// Set to delete the file, clear to cancel a pending deletion.
+    pub delete: bool,
+    /// The file name is removed from the namespace as soon as the handle is closed,
+    /// even if other handles to the file are still open, rather than when the last handle is closed.
+    pub posix_semantics: bool,
+    /// If the file is an image file, checks whether an image section is mapped for it, and fails if so.
+    pub force_image_section_check: bool,
+    /// Sets or clears the delete-on-close state of the file, rather than deleting it when the handle is closed.
+    pub on_close: bool,
+    /// Allows deleting a file that has the read-only attribute set.
+    pub ignore_readonly_attribute: bool,
+    #[skip]
+    __: B27,
+}
+
 /// Rename a file within the SMB2 protocol.
 ///
 /// [MS-FSCC 2.4.42.2](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/52aa0b70-8094-4971-862d-79793f41e6a8>) - FileRenameInformation for SMB2 protocol
@@ -88,6 +138,58 @@ pub struct FileRenameInformation {
     pub file_name: SizedWideString,
 }
 
+/// Rename a file, with extended options, such as POSIX semantics.
+///
+/// The same as [`FileRenameInformation`], with flags instead of its `ReplaceIfExists` field.
+/// Servers that do not support this class fail with `STATUS_INVALID_INFO_CLASS`
+/// (or `STATUS_INVALID_PARAMETER`, on older Windows versions).
+///
+/// Information class 65, the `FILE_RENAME_INFORMATION` structure of the Windows driver documentation, with `Flags`.
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileRenameInformationEx {
+    /// The rename flags.
+    pub flags: FileRenameFlags,
+    #[bw(calc = 0)]
+    _reserved: u32,
+    /// A file handle for the root directory. For network operations, this value must be zero.
+    pub root_directory: u64,
+    #[bw(try_calc = file_name.size().try_into())]
+    _file_name_length: u32,
+    /// The new name for the file, including the full path.
+    #[br(args { size: SizedStringSize::bytes(_file_name_length) })]
+    pub file_name: SizedWideString,
+}
+
+/// Flags of [`FileRenameInformationEx`].
+#[bitfield]
+#[derive(BinWrite, BinRead, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[bw(map = |&x| Self::into_bytes(x))]
+#[br(map = Self::from_bytes)]
+pub struct FileRenameFlags {
+    /// If a file with the given name already exists, it is replaced. Otherwise, the rename fails.
+    pub replace_if_exists: bool,
+    /// When replacing a file, the file is replaced even if other handles to it are still open,
+    /// and its name is removed from the namespace immediately.
+    pub posix_semantics: bool,
+    /// The pin state of the destination directory is not inherited by the renamed file.
+    pub suppress_pin_state_inheritance: bool,
+    /// The storage reserve ID of the destination directory is not inherited by the renamed file.
+    pub suppress_storage_reserve_inheritance: bool,
+    /// Fails the rename if it would increase the available space of a storage reserve area.
+    pub no_increase_available_space: bool,
+    /// Fails the rename if it would decrease the available space of a storage reserve area.
+    pub no_decrease_available_space: bool,
+    /// Allows replacing a file that has the read-only attribute set.
+    pub ignore_readonly_attribute: bool,
+    /// Resizes the storage reserve area of the destination, if needed.
+    pub force_resize_target_sr: bool,
+    /// Resizes the storage reserve area of the source, if needed.
+    pub force_resize_source_sr: bool,
+    #[skip]
+    __: B23,
+}
+
 /// Set the allocation size for a file.
 ///
 /// The file system is passed a 64-bit signed integer containing the file allocation size, in bytes.
@@ -105,8 +207,6 @@ pub struct FileAllocationInformation {
 
 /// Create a hard link to an existing file via the SMB Version 2 Protocol, as specified in [MS-SMB2].
 ///
-/// WARNING: This operation is currently unstable and untested, and may lead to data loss or corruption if used improperly!
-///
 /// [MS-FSCC 2.4.8.2](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/58f44021-120d-4662-bf2c-9905ed4940dc>) - FileLinkInformation for SMB2 protocol
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
@@ -120,13 +220,11 @@ pub struct FileLinkInformation {
     #[bw(calc = 0)]
     _reserved3: u32,
     /// A file handle for the root directory. For network operations, this value must be zero.
-    #[bw(calc = 0)]
-    #[br(assert(root_directory == 0))]
-    root_directory: u64,
+    pub root_directory: u64,
     #[bw(try_calc = file_name.size().try_into())]
     _file_name_length: u32,
-    /// The name to be assigned to the newly created link.
-    #[br(args {size: SizedStringSize::bytes(_file_name_length)})]
+    /// The name to be assigned to the newly created link, including the full path.
+    #[br(args { size: SizedStringSize::bytes(_file_name_length) })]
     pub file_name: SizedWideString,
 }
 
@@ -194,6 +292,15 @@ mod tests {
         } => "01"
     }
 
+    test_binrw! {
+        struct FileDispositionInformationEx {
+            flags: FileDispositionFlags::new()
+                .with_delete(true)
+                .with_posix_semantics(true)
+                .with_ignore_readonly_attribute(true),
+        } => "13000000"
+    }
+
     test_binrw_read! {
         struct FileRenameInformation {
             replace_if_exists: false.into(),
@@ -210,6 +317,17 @@ mod tests {
         } => "000000000000000000000000000000000a00000062002e00740078007400"
     }
 
+    test_binrw! {
+        struct FileRenameInformationEx {
+            flags: FileRenameFlags::new()
+                .with_replace_if_exists(true)
+                .with_posix_semantics(true)
+                .with_ignore_readonly_attribute(true),
+            root_directory: 0,
+            file_name: SizedWideString::from("d\\b.txt"),
+        } => "430000000000000000000000000000000e00000064005c0062002e00740078007400"
+    }
+
     test_binrw! {
         struct FileBasicInformation {
             creation_time: FileTime::ZERO,
@@ -234,6 +352,11 @@ mod tests {
         } => "18000000530048004f00520054004e007e0031002e00540058005400"
     }
 
-    // TODO: the following test is currently missing.
-    //     pub Link = 11,
+    test_binrw! {
+        struct FileLinkInformation {
+            replace_if_exists: true.into(),
+            root_directory: 0,
+            file_name: SizedWideString::from("d\\l.txt"),
+        } => "010000000000000000000000000000000e00000064005c006c002e00740078007400"
+    }
 }
@@ -0,0 +1,36 @@
+//! Sparse file structures
+
+use binrw::prelude::*;
+
+/// FILE_ALLOCATED_RANGE_BUFFER - a range of a file, used by `FSCTL_QUERY_ALLOCATED_RANGES`
+/// to specify the range to query, and to return the allocated ranges in it (MS-FSCC).
+#[binrw::binrw]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileAllocatedRangeBuffer {
+    /// The offset of the start of the range, in bytes.
+    pub file_offset: u64,
+    /// The length of the range, in bytes.
+    pub length: u64,
+}
+
+impl FileAllocatedRangeBuffer {
+    pub const SIZE: usize = size_of::<u64>() * 2;
+
+    /// Returns the offset of the end of the range, in bytes.
+    pub fn end(&self) -> u64 {
+        self.file_offset.saturating_add(self.length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smb_tests::*;
+
+    test_binrw! {
+        struct FileAllocatedRangeBuffer {
+            file_offset: 0x2000,
+            length: 0xb6d1,
+        } => "0020000000000000d1b6000000000000"
+    }
+}
@@ -23,8 +23,7 @@ impl CompressedMessage {
                 m.data.len() + CompressedUnchainedMessage::STRUCT_SIZE
             }
             CompressedMessage::Chained(m) => {
-                m.items.iter().map(|i| i.payload_data.len()).sum::<usize>()
-                    + m.items.len() * 4
+                m.items.iter().map(|i| i.total_size()).sum::<usize>()
                     + CompressedChainedMessage::STRUCT_SIZE
             }
         }
@@ -95,6 +94,22 @@ pub struct CompressedChainedItem {
     pub payload_data: Vec<u8>,
 }
 
+impl CompressedChainedItem {
+    /// `SMB2_COMPRESSION_FLAG_CHAINED`, set in the flags of the first item of a chained message.
+    pub const FLAG_CHAINED: u16 = 0x0001;
+
+    const HEADER_SIZE: usize = std::mem::size_of::<CompressionAlgorithm>()
+        + std::mem::size_of::<u16>()
+        + std::mem::size_of::<u32>();
+
+    /// Returns the size of the item, when written.
+    pub fn total_size(&self) -> usize {
+        Self::HEADER_SIZE
+            + add_original_size_to_total_length(&self.compression_algorithm) as usize
+            + self.payload_data.len()
+    }
+}
+
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
 pub struct CompressedData {
@@ -149,7 +164,7 @@ mod tests {
     }
 
     /// I do it for the sake of some real, big data.
-    const CHAINED1_ITEM2_DATA: &'static str = "f2034d5a90000300000004000000ffff0000b8000\
+    const CHAINED1_ITEM2_DATA: &str = "f2034d5a90000300000004000000ffff0000b8000\
     100124007000f02000af32e200100000e1fba0e00b409cd21b8014ccd21546869732070726f677\
     2616d2063616e6e6f742062652072756e20696e20444f53206d6f64652e0d0d0a245a0084a98ee\
     eb9edef80ea0400d1996e86ebddef80ea9f6e83ebe81000b181ebe1ef80eabe9084ebec0800318\
@@ -183,7 +198,7 @@ mod tests {
     8014150616434400050d01f00003022070e020080800000ca2e727372fe0301c4059d004001009\
     0030000805000c1422e72656c6f630000dc5501dc0578006001000010c15500500040000042";
 
-    const CHAINED1_TEST_DATA: &'static str = const_format::concatcp!(
+    const CHAINED1_TEST_DATA: &str = const_format::concatcp!(
         "fc534d42501000000000010050000000fe534d424000010000000000080001001900000000000000070000000000000000000000010000001d00000000600000251698bc898e3e86aeb713557cfaf1bb1100500000100000000000000000000005000000f7040000c8070000",
         CHAINED1_ITEM2_DATA,
         "04000000080000000000000038080000"
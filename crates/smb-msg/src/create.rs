@@ -14,7 +14,7 @@ use smb_fscc::*;
 
 /// 2.2.14.1: SMB2_FILEID
 #[binrw::binrw]
-#[derive(PartialEq, Eq, Clone, Copy, Default)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Default)]
 pub struct FileId {
     pub persistent: u64,
     pub volatile: u64,
@@ -419,7 +419,7 @@ impl CreateContextType {
 
 make_create_context!(
     /// The data contains the extended attributes that MUST be stored on the created file.
-    exta: b"ExtA", ChainedItemList<FileFullEaInformation>;
+    exta: b"ExtA", FileFullEaInformation;
     /// The data contains a security descriptor that MUST be stored on the created file.
     secd: b"SecD", SecurityDescriptor;
     /// The client is requesting the open to be durable
@@ -503,6 +503,24 @@ pub enum RequestLease {
     RqLsReqv2(RequestLeaseV2),
 }
 
+impl RequestLease {
+    /// Returns the key of the lease.
+    pub fn lease_key(&self) -> u128 {
+        match self {
+            RequestLease::RqLsReqv1(lease) => lease.lease_key,
+            RequestLease::RqLsReqv2(lease) => lease.lease_key,
+        }
+    }
+
+    /// Returns the state of the lease, as requested by the client, or granted by the server.
+    pub fn lease_state(&self) -> LeaseState {
+        match self {
+            RequestLease::RqLsReqv1(lease) => lease.lease_state,
+            RequestLease::RqLsReqv2(lease) => lease.lease_state,
+        }
+    }
+}
+
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
 pub struct RequestLeaseV1 {
@@ -571,9 +589,9 @@ pub struct DurableHandleV2Flags {
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
 pub struct DurableHandleReconnectV2 {
-    file_id: FileId,
-    create_guid: Guid,
-    flags: DurableHandleV2Flags,
+    pub file_id: FileId,
+    pub create_guid: Guid,
+    pub flags: DurableHandleV2Flags,
 }
 
 #[binrw::binrw]
@@ -807,9 +825,31 @@ mod tests {
     use time::macros::datetime;
 
     // Tests for the following contexts are not implemented here:
-    // - ExtA - already tested in smb-fscc & query info/ea tests
     // - SecD - already tested in smb-dtyp tests
 
+    /// The data of the ExtA context is the EA list itself (encoding is tested in smb-fscc), without an additional prefix.
+    #[test]
+    fn test_exta_create_context() {
+        let contexts: ChainedItemList<RequestCreateContext, 8> = vec![
+            FileFullEaInformation::builder()
+                .add("A", b"xy")
+                .build()
+                .into(),
+        ]
+        .into();
+        let expected = hex_to_u8_array! {
+            "000000001000040000001800 0c00000045787441 0000000000000000 000102004100 7879"
+        };
+
+        let mut cursor = Cursor::new(Vec::new());
+        contexts.write_le(&mut cursor).unwrap();
+        assert_eq!(cursor.get_ref(), &expected);
+
+        cursor.set_position(0);
+        let read = ChainedItemList::<RequestCreateContext, 8>::read_le(&mut cursor).unwrap();
+        assert_eq!(read, contexts);
+    }
+
     test_binrw! {
         struct DurableHandleRequest {} => "00000000000000000000000000000000"
     }
@@ -29,15 +29,22 @@ pub enum ReferralLevel {
     V4 = 4,
 }
 
+/// MS-DFSC 2.2.3: An extended DFS referral request, REQ_GET_DFS_REFERRAL_EX, which may carry the site name of the client.
+///
+/// The server uses the site name, instead of the site of the address of the client, to order the referral targets by their cost.
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
 pub struct ReqGetDfsReferralEx {
     /// An integer that indicates the highest DFS referral version understood by the client. The DFS referral versions specified by this document are 1 through 4 inclusive. A DFS client MUST support DFS referral version 1 through the version number set in this field. The referral response messages are referral version dependent and are specified in sections 2.2.5.1 through 2.2.5.4.
-    pub max_referral_level: u16,
-    pub request_flags: DfsRequestFlags,
+    pub max_referral_level: ReferralLevel,
+    #[bw(calc = DfsRequestFlags::new().with_site_name(request_data.site_name.is_some()))]
+    #[br(temp)]
+    request_flags: DfsRequestFlags,
+    #[bw(calc = PosMarker::default())]
+    #[br(temp)]
     request_data_length: PosMarker<u32>,
-    #[bw(write_with = PosMarker::write_size, args(request_data_length))]
-    #[br(map_stream = |s| s.take_seek(request_data_length.value as u64))]
+    #[bw(write_with = PosMarker::write_size, args(&request_data_length))]
+    #[br(map_stream = |s| s.take_seek(request_data_length.value as u64), args(request_flags.site_name()))]
     pub request_data: DfsRequestData,
 }
 
@@ -55,24 +62,29 @@ pub struct DfsRequestFlags {
 /// RequestData is part of the REQ_GET_DFS_REFERRAL_EX message (section 2.2.3).
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
+#[br(import(has_site_name: bool))]
 pub struct DfsRequestData {
     #[bw(try_calc = request_file_name.size().try_into())]
     request_file_name_length: u16,
     /// A Unicode string specifying the path to be resolved. The specified path MUST be interpreted in a case-insensitive manner. Its format depends on the type of referral request, as specified in section 3.1.4.2.
     #[br(args { size: SizedStringSize::bytes16(request_file_name_length) })]
-    request_file_name: SizedWideString,
-    #[bw(try_calc = site_name.size().try_into())]
-    site_name_length: u16,
-    /// A Unicode string specifying the name of the site to which the DFS client computer belongs. The length of this string is determined by the value of the SiteNameLength field.
-    #[br(args { size: SizedStringSize::bytes16(site_name_length) })]
-    site_name: SizedWideString,
+    pub request_file_name: SizedWideString,
+    #[bw(try_calc = site_name.as_ref().map(|s| s.size().try_into()).transpose())]
+    #[br(if(has_site_name))]
+    site_name_length: Option<u16>,
+    /// A Unicode string specifying the name of the site to which the DFS client computer belongs.
+    /// Present only if [`DfsRequestFlags::site_name`] is set, which is done when writing the request.
+    #[br(if(has_site_name), args { size: SizedStringSize::bytes16(site_name_length.unwrap_or_default()) })]
+    pub site_name: Option<SizedWideString>,
 }
 
 impl DfsRequestData {
     pub fn get_bin_size(&self) -> usize {
-        size_of::<u16>() * 2 // lengths
+        size_of::<u16>() // request_file_name_length
             + self.request_file_name.len() * size_of::<u16>() // + request_file_name (wstring)
-            + self.site_name.len() * size_of::<u16>() // + site_name (wstring)
+            + self.site_name.as_ref().map_or(0, |site_name| {
+                size_of::<u16>() + site_name.len() * size_of::<u16>() // + site_name_length + site_name (wstring)
+            })
     }
 }
 
@@ -371,6 +383,17 @@ pub struct ReferralEntryValueV4 {
     pub refs: EntryV3V4DfsPaths,
 }
 
+impl ReferralEntryValueV4 {
+    /// Whether this target is the first of a target set: all the targets of a set have the same cost for the client,
+    /// and sets are ordered from the lowest cost to the highest.
+    ///
+    /// The first target of a referral always starts a target set.
+    pub fn target_set_boundary(&self) -> bool {
+        ReferralEntryFlagsV4::from_bytes(self.referral_entry_flags.to_le_bytes())
+            .target_set_boundary()
+    }
+}
+
 /// Internal.
 #[bitfield]
 #[derive(BinWrite, BinRead, Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -379,7 +402,6 @@ pub struct ReferralEntryValueV4 {
 struct ReferralEntryFlagsV4 {
     #[skip]
     __: B2,
-    #[skip(getters)]
     target_set_boundary: bool,
     #[skip]
     __: B13,
@@ -397,6 +419,27 @@ mod tests {
         } => "04005c004100440043002e0061007600690076002e006c006f00630061006c005c006400660073005c0044006f00630073000000"
     }
 
+    test_binrw! {
+        struct ReqGetDfsReferralEx {
+            max_referral_level: ReferralLevel::V4,
+            request_data: DfsRequestData {
+                request_file_name: r"\ADC.aviv.local\dfs\Docs".into(),
+                site_name: Some("Default-First-Site-Name".into()),
+            },
+        } => "040001006200000030005c004100440043002e0061007600690076002e006c006f00630061006c005c006400660073005c0044006f0063007300
+        2e00440065006600610075006c0074002d00460069007200730074002d0053006900740065002d004e0061006d006500"
+    }
+
+    test_binrw! {
+        struct ReqGetDfsReferralEx => no_site {
+            max_referral_level: ReferralLevel::V4,
+            request_data: DfsRequestData {
+                request_file_name: r"\ADC.aviv.local\dfs\Docs".into(),
+                site_name: None,
+            },
+        } => "040000003200000030005c004100440043002e0061007600690076002e006c006f00630061006c005c006400660073005c0044006f0063007300"
+    }
+
     test_binrw_read! {
         struct RespGetDfsReferral {
             path_consumed: 48,
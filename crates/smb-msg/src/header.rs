@@ -3,56 +3,103 @@ use std::io::Cursor;
 use binrw::prelude::*;
 use modular_bitfield::prelude::*;
 
-#[derive(BinRead, BinWrite, Debug, PartialEq, Eq, Clone, Copy)]
-#[brw(repr(u16))]
+macro_rules! make_command {
+    (
+        $($name:ident = $value:literal: $description:literal, )+
+    ) => {
+
+/// SMB2 command codes.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Command {
-    Negotiate = 0,
-    SessionSetup = 1,
-    Logoff = 2,
-    TreeConnect = 3,
-    TreeDisconnect = 4,
-    Create = 5,
-    Close = 6,
-    Flush = 7,
-    Read = 8,
-    Write = 9,
-    Lock = 0xA,
-    Ioctl = 0xB,
-    Cancel = 0xC,
-    Echo = 0xD,
-    QueryDirectory = 0xE,
-    ChangeNotify = 0xF,
-    QueryInfo = 0x10,
-    SetInfo = 0x11,
-    OplockBreak = 0x12,
-    ServerToClientNotification = 0x13,
+    $(
+        $name,
+    )+
+    /// A command code that is not known to this crate.
+    Unknown(u16),
+}
+
+impl Command {
+    /// Returns the command code, as sent on the wire.
+    pub const fn code(&self) -> u16 {
+        match self {
+            $(
+                Command::$name => $value,
+            )+
+            Command::Unknown(code) => *code,
+        }
+    }
+}
+
+impl From<u16> for Command {
+    fn from(code: u16) -> Self {
+        match code {
+            $(
+                $value => Command::$name,
+            )+
+            _ => Command::Unknown(code),
+        }
+    }
 }
 
 impl std::fmt::Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let message_as_string = match self {
-            Command::Negotiate => "Negotiate",
-            Command::SessionSetup => "Session Setup",
-            Command::Logoff => "Logoff",
-            Command::TreeConnect => "Tree Connect",
-            Command::TreeDisconnect => "Tree Disconnect",
-            Command::Create => "Create",
-            Command::Close => "Close",
-            Command::Flush => "Flush",
-            Command::Read => "Read",
-            Command::Write => "Write",
-            Command::Lock => "Lock",
-            Command::Ioctl => "Ioctl",
-            Command::Cancel => "Cancel",
-            Command::Echo => "Echo",
-            Command::QueryDirectory => "Query Directory",
-            Command::ChangeNotify => "Change Notify",
-            Command::QueryInfo => "Query Info",
-            Command::SetInfo => "Set Info",
-            Command::OplockBreak => "Oplock Break",
-            Command::ServerToClientNotification => "Server to Client Notification",
+            $(
+                Command::$name => $description,
+            )+
+            Command::Unknown(_) => "Unknown",
         };
-        write!(f, "{} ({:#x})", message_as_string, *self as u16)
+        write!(f, "{} ({:#x})", message_as_string, self.code())
+    }
+}
+    };
+}
+
+make_command! {
+    Negotiate = 0: "Negotiate",
+    SessionSetup = 1: "Session Setup",
+    Logoff = 2: "Logoff",
+    TreeConnect = 3: "Tree Connect",
+    TreeDisconnect = 4: "Tree Disconnect",
+    Create = 5: "Create",
+    Close = 6: "Close",
+    Flush = 7: "Flush",
+    Read = 8: "Read",
+    Write = 9: "Write",
+    Lock = 0xA: "Lock",
+    Ioctl = 0xB: "Ioctl",
+    Cancel = 0xC: "Cancel",
+    Echo = 0xD: "Echo",
+    QueryDirectory = 0xE: "Query Directory",
+    ChangeNotify = 0xF: "Change Notify",
+    QueryInfo = 0x10: "Query Info",
+    SetInfo = 0x11: "Set Info",
+    OplockBreak = 0x12: "Oplock Break",
+    ServerToClientNotification = 0x13: "Server to Client Notification",
+}
+
+impl BinRead for Command {
+    type Args<'a> = ();
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<Self> {
+        Ok(u16::read_options(reader, endian, ())?.into())
+    }
+}
+
+impl BinWrite for Command {
+    type Args<'a> = ();
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<()> {
+        self.code().write_options(writer, endian, ())
     }
 }
 
@@ -129,12 +176,14 @@ make_status! {
     SmbUseStandard = 0x00FB0002: "SMB Use Standard",
     BufferOverflow = 0x80000005: "Buffer Overflow",
     NoMoreFiles = 0x80000006: "No More Files",
+    NoMoreEntries = 0x8000001A: "No More Entries",
     StoppedOnSymlink = 0x8000002D: "Stopped on Symlink",
     NotImplemented = 0xC0000002: "Not Implemented",
     InvalidInfoClass = 0xC0000003: "Invalid Info Class",
     InfoLengthMismatch = 0xC0000004: "Info Length Mismatch",
     InvalidParameter = 0xC000000D: "Invalid Parameter",
     NoSuchDevice = 0xC000000E: "No Such Device",
+    NoSuchFile = 0xC000000F: "No Such File",
     InvalidDeviceRequest0 = 0xC0000010: "Invalid Device Request",
     EndOfFile = 0xC0000011: "End of File",
     MoreProcessingRequired = 0xC0000016: "More Processing Required",
@@ -145,8 +194,15 @@ make_status! {
     ObjectNameCollision = 0xC0000035: "Object Name Collision",
     SharingViolation = 0xC0000043: "Sharing Violation",
     ObjectPathNotFound = 0xC000003A: "Object Path Not Found",
-    NoEasOnFile = 0xC0000044: "No EAs on File",
+    QuotaExceeded = 0xC0000044: "Quota Exceeded",
+    NoEasOnFile = 0xC0000052: "No EAs on File",
+    FileLockConflict = 0xC0000054: "File Lock Conflict",
+    LockNotGranted = 0xC0000055: "Lock Not Granted",
+    DeletePending = 0xC0000056: "Delete Pending",
+    PrivilegeNotHeld = 0xC0000061: "Privilege Not Held",
     LogonFailure = 0xC000006D: "Logon Failure",
+    RangeNotLocked = 0xC000007E: "Range Not Locked",
+    DiskFull = 0xC000007F: "Disk Full",
     BadImpersonationLevel = 0xC00000A5: "Bad Impersonation Level",
     IoTimeout = 0xC00000B5: "I/O Timeout",
     FileIsADirectory = 0xC00000BA: "File is a Directory",
@@ -154,14 +210,19 @@ make_status! {
     NetworkNameDeleted = 0xC00000C9: "Network Name Deleted",
     BadNetworkName = 0xC00000CC: "Bad Network Name",
     RequestNotAccepted = 0xC00000D0: "Request Not Accepted",
+    NotSameDevice = 0xC00000D4: "Not Same Device",
     DirectoryNotEmpty = 0xC0000101: "Directory Not Empty",
     Cancelled = 0xC0000120: "Cancelled",
+    FileClosed = 0xC0000128: "File Closed",
     UserSessionDeleted = 0xC0000203: "User Session Deleted",
     UserAccountLockedOut = 0xC0000234: "User Account Locked Out",
     PathNotCovered = 0xC0000257: "Path Not Covered",
+    NotAReparsePoint = 0xC0000275: "Not a Reparse Point",
+    ObjectIdNotFound = 0xC00002F0: "Object ID Not Found",
     NetworkSessionExpired = 0xC000035C: "Network Session Expired",
     SmbTooManyUids = 0xC000205A: "SMB Too Many UIDs",
     DeviceFeatureNotSupported = 0xC0000463: "Device Feature Not Supported",
+    FileNotAvailable = 0xC0000467: "File Not Available",
 }
 
 /// Sync and Async SMB2 Message header.
@@ -2,6 +2,7 @@
 
 use binrw::prelude::*;
 use modular_bitfield::prelude::*;
+use smb_fscc::FileAccessMask;
 
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -34,6 +35,127 @@ pub struct AdditionalInfo {
     __: B15,
 }
 
+/// The parts of a security descriptor to query or set (MS-DTYP 2.4.7 `SECURITY_INFORMATION`).
+///
+/// This is the [`AdditionalInfo`] of security queries and sets. Each part requires an access right
+/// on the handle, see [`SecurityInformation::query_access`] and [`SecurityInformation::set_access`]:
+///
+/// | Flag                                    | Query                    | Set                      |
+/// |-----------------------------------------|--------------------------|--------------------------|
+/// | `owner`, `group`, `label`               | `read_control`           | `write_owner`            |
+/// | `dacl`                                  | `read_control`           | `write_dacl`             |
+/// | `attribute`                             | `read_control`           | `write_dacl`             |
+/// | `sacl`                                  | `access_system_security` | `access_system_security` |
+/// | `scope`                                 | `read_control`           | `access_system_security` |
+/// | `backup`                                | all of the above         | all of the above         |
+///
+/// Requesting a part the handle has no access to fails with `STATUS_ACCESS_DENIED`.
+/// Note that [`FileAccessMask::access_system_security`] is never granted implicitly,
+/// and requires the `SeSecurityPrivilege` privilege.
+#[bitfield]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SecurityInformation {
+    /// The owner SID of the descriptor.
+    pub owner: bool,
+    /// The primary group SID of the descriptor.
+    pub group: bool,
+    /// The discretionary ACL of the descriptor.
+    pub dacl: bool,
+    /// The system ACL of the descriptor, excluding its mandatory label, attribute and scope ACEs.
+    pub sacl: bool,
+
+    /// The mandatory label ACE of the SACL.
+    pub label: bool,
+    /// The resource attribute ACEs of the SACL.
+    pub attribute: bool,
+    /// The central access policy ACEs of the SACL.
+    pub scope: bool,
+
+    #[skip]
+    __: B9,
+    /// All the parts of the descriptor, as used by backup applications.
+    pub backup: bool,
+    #[skip]
+    __: B15,
+}
+
+impl SecurityInformation {
+    /// Only the DACL, for inspecting or updating the permissions of a file.
+    pub fn dacl_only() -> Self {
+        Self::new().with_dacl(true)
+    }
+
+    /// The owner, group and DACL, which is the descriptor a `read_control` handle may query.
+    pub fn owner_group_dacl() -> Self {
+        Self::new()
+            .with_owner(true)
+            .with_group(true)
+            .with_dacl(true)
+    }
+
+    /// Returns whether no part of the descriptor is requested.
+    pub fn is_empty(&self) -> bool {
+        self.into_bytes() == [0; 4]
+    }
+
+    /// Returns the access a handle requires to query this information.
+    pub fn query_access(&self) -> FileAccessMask {
+        let read = self.owner()
+            || self.group()
+            || self.dacl()
+            || self.label()
+            || self.attribute()
+            || self.scope()
+            || self.backup();
+        FileAccessMask::new()
+            .with_read_control(read)
+            .with_access_system_security(self.sacl() || self.backup())
+    }
+
+    /// Returns the access a handle requires to set this information.
+    pub fn set_access(&self) -> FileAccessMask {
+        FileAccessMask::new()
+            .with_write_owner(self.owner() || self.group() || self.label() || self.backup())
+            .with_write_dacl(self.dacl() || self.attribute() || self.backup())
+            .with_access_system_security(self.sacl() || self.scope() || self.backup())
+    }
+}
+
+impl std::fmt::Display for SecurityInformation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flags = [
+            (self.owner(), "owner"),
+            (self.group(), "group"),
+            (self.dacl(), "dacl"),
+            (self.sacl(), "sacl"),
+            (self.label(), "label"),
+            (self.attribute(), "attribute"),
+            (self.scope(), "scope"),
+            (self.backup(), "backup"),
+        ];
+        let mut names = flags.iter().filter(|(set, _)| *set).map(|(_, name)| *name);
+        match names.next() {
+            Some(first) => {
+                write!(f, "{first}")?;
+                names.try_for_each(|name| write!(f, " | {name}"))
+            }
+            None => write!(f, "none"),
+        }
+    }
+}
+
+impl From<SecurityInformation> for AdditionalInfo {
+    fn from(value: SecurityInformation) -> Self {
+        AdditionalInfo::from_bytes(value.into_bytes())
+    }
+}
+
+impl From<AdditionalInfo> for SecurityInformation {
+    fn from(value: AdditionalInfo) -> Self {
+        SecurityInformation::from_bytes(value.into_bytes())
+    }
+}
+
 /// Internal helper macro to easily generate fields & methods for [QueryInfoData](super::query::QueryInfoData).
 ///
 /// Builds:
@@ -159,3 +281,83 @@ macro_rules! query_info_data {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_security_information_encoding() {
+        assert_eq!(
+            SecurityInformation::dacl_only().into_bytes(),
+            0x4u32.to_le_bytes()
+        );
+        assert_eq!(
+            SecurityInformation::owner_group_dacl().into_bytes(),
+            0x7u32.to_le_bytes()
+        );
+        assert_eq!(
+            SecurityInformation::new()
+                .with_sacl(true)
+                .with_label(true)
+                .with_attribute(true)
+                .with_scope(true)
+                .into_bytes(),
+            0x78u32.to_le_bytes()
+        );
+        assert_eq!(
+            SecurityInformation::new().with_backup(true).into_bytes(),
+            0x10000u32.to_le_bytes()
+        );
+
+        let info = SecurityInformation::owner_group_dacl().with_sacl(true);
+        let additional_info = AdditionalInfo::from(info);
+        assert!(additional_info.owner_security_information());
+        assert!(additional_info.group_security_information());
+        assert!(additional_info.dacl_security_information());
+        assert!(additional_info.sacl_security_information());
+        assert!(!additional_info.label_security_information());
+        assert_eq!(SecurityInformation::from(additional_info), info);
+
+        assert_eq!(info.to_string(), "owner | group | dacl | sacl");
+        assert_eq!(SecurityInformation::new().to_string(), "none");
+        assert!(SecurityInformation::new().is_empty());
+    }
+
+    #[test]
+    fn test_security_information_access() {
+        let none = FileAccessMask::new();
+        let read = FileAccessMask::new().with_read_control(true);
+        let system = FileAccessMask::new().with_access_system_security(true);
+        let owner = FileAccessMask::new().with_write_owner(true);
+        let dacl = FileAccessMask::new().with_write_dacl(true);
+        let all_set = owner
+            .with_write_dacl(true)
+            .with_access_system_security(true);
+
+        let info = SecurityInformation::new;
+        for (info, query, set) in [
+            (info(), none, none),
+            (info().with_owner(true), read, owner),
+            (info().with_group(true), read, owner),
+            (info().with_dacl(true), read, dacl),
+            (info().with_sacl(true), system, system),
+            (info().with_label(true), read, owner),
+            (info().with_attribute(true), read, dacl),
+            (info().with_scope(true), read, system),
+            (
+                info().with_backup(true),
+                read.with_access_system_security(true),
+                all_set,
+            ),
+            (
+                SecurityInformation::owner_group_dacl(),
+                read,
+                owner.with_write_dacl(true),
+            ),
+        ] {
+            assert_eq!(info.query_access(), query, "Query access of {info}");
+            assert_eq!(info.set_access(), set, "Set access of {info}");
+        }
+    }
+}
@@ -173,6 +173,30 @@ impl QueryQuotaInfo {
             sid: Some(sid),
         }
     }
+
+    /// Builds a new [`QueryQuotaInfo`], querying the quota entries of the specified SIDs.
+    ///
+    /// The SIDs are sent as a chained list of [`FileGetQuotaInformation`] structs (option #1 under 2.2.37.1),
+    /// and the scan is restarted. Use [`with_return_single`][Self::with_return_single] to only return the first entry.
+    pub fn for_sids(sids: impl IntoIterator<Item = SID>) -> Self {
+        let content = sids
+            .into_iter()
+            .map(|sid| FileGetQuotaInformation { sid })
+            .collect();
+        Self::new(false, true, content)
+    }
+
+    /// Sets whether only a single entry is returned.
+    pub fn with_return_single(mut self, return_single: bool) -> Self {
+        self.return_single = return_single.into();
+        self
+    }
+
+    /// Sets whether the scan starts from the first entry, rather than after the last returned entry.
+    pub fn with_restart_scan(mut self, restart_scan: bool) -> Self {
+        self.restart_scan = restart_scan.into();
+        self
+    }
 }
 
 #[derive(BinRead, BinWrite, Debug, PartialEq, Eq)]
@@ -197,6 +221,11 @@ pub struct QueryInfoResponse {
 }
 
 impl QueryInfoResponse {
+    /// Creates a new query info response, with the specified raw output buffer.
+    pub fn new(data: impl Into<QueryInfoResponseData>) -> Self {
+        QueryInfoResponse { data: data.into() }
+    }
+
     /// Call this method first when parsing an incoming query info response.
     /// It will parse the raw data into a [QueryInfoResponseData] struct, which has
     /// a variation for each information type: File, FileSystem, Security, Quota.
@@ -243,10 +272,11 @@ mod tests {
 
     use crate::*;
     use smb_dtyp::*;
+    use smb_tests::*;
 
     use super::*;
 
-    const QUERY_INFO_HEADER_DATA: &'static str = "";
+    const QUERY_INFO_HEADER_DATA: &str = "";
 
     test_request! {
         query_info_basic: QueryInfo {
@@ -299,6 +329,21 @@ mod tests {
         } => const_format::concatcp!(QUERY_INFO_HEADER_DATA, "290003000000000068000000000000000f000000000000002b0000000d000000310000000d000000")
     }
 
+    // Multiple SIDs are chained, each entry pointing to the next one.
+    test_binrw! {
+        QueryQuotaInfo => sids: QueryQuotaInfo::for_sids([
+            "S-1-5-21-1-2-3-1001".parse().unwrap(),
+            SID::S_ADMINISTRATORS.parse().unwrap(),
+        ]) => "000100003c0000000000000000000000240000001c000000010500000000000515000000010000000200000003000000e9030000000000001000000001020000000000052000000020020000"
+    }
+
+    test_binrw! {
+        QueryQuotaInfo => single_sid: QueryQuotaInfo::for_sids([SID::S_ADMINISTRATORS.parse().unwrap()])
+            .with_return_single(true)
+            .with_restart_scan(false)
+            => "01000000180000000000000000000000000000001000000001020000000000052000000020020000"
+    }
+
     test_response! {
         QueryInfo {
             data: [
@@ -1,9 +1,11 @@
 //! SMB2 IOCTL packet implementation
 
 mod common;
+mod fs_statistics;
 mod fsctl;
 mod msg;
 
 pub use common::*;
+pub use fs_statistics::*;
 pub use fsctl::*;
 pub use msg::*;
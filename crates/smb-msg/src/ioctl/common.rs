@@ -36,6 +36,12 @@ impl From<&[u8]> for IoctlBuffer {
     }
 }
 
+impl From<IoctlBuffer> for Vec<u8> {
+    fn from(buffer: IoctlBuffer) -> Self {
+        buffer.buffer
+    }
+}
+
 impl IoctlRequestContent for IoctlBuffer {
     fn get_bin_size(&self) -> u32 {
         self.len() as u32
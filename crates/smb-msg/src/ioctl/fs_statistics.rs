@@ -0,0 +1,1086 @@
+//! File system statistics, as returned by `FSCTL_FILESYSTEM_GET_STATISTICS(_EX)`.
+//!
+//! The statistics are kept by the file system per processor: the output buffer of both FSCTLs is an array of
+//! per-processor entries. Each entry starts with a [`FileSystemStatistics`] (or [`FileSystemStatisticsEx`]) header,
+//! followed by the statistics specific to the file system. The header's `SizeOfCompleteStructure` field is the size
+//! of a single entry, including the file system specific part and any trailing padding.
+//!
+//! The file system specific structures grew over Windows versions. Entries that are shorter than the structures
+//! defined here are parsed as if zero-padded, so counters missing from older servers are 0,
+//! and trailing data added by newer servers is ignored.
+//!
+//! See the `FILESYSTEM_STATISTICS`, `NTFS_STATISTICS` and `FAT_STATISTICS` structures of the Windows SDK (`winioctl.h`),
+//! and their `_EX` versions.
+
+use std::io::{Cursor, Read, Seek};
+use std::ops::AddAssign;
+
+use binrw::{Endian, prelude::*};
+
+use super::fsctl::{FsctlCodes, FsctlResponseContent};
+
+/// The file system of a volume, as reported by [`FileSystemStatistics::file_system_type`].
+#[binrw::binrw]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[brw(repr(u16))]
+pub enum FileSystemType {
+    Ntfs = 1,
+    Fat = 2,
+    ExFat = 3,
+    Refs = 4,
+}
+
+/// The statistics of a file system, specific to its type.
+///
+/// `N` is [`NtfsStatistics`] for `FSCTL_FILESYSTEM_GET_STATISTICS`,
+/// and [`NtfsStatisticsEx`] for `FSCTL_FILESYSTEM_GET_STATISTICS_EX`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileSystemStatisticsData<N> {
+    Ntfs(N),
+    Fat(FatStatistics),
+    ExFat(FatStatistics),
+    /// The raw statistics of file systems without a typed structure (e.g. ReFS), including any trailing padding.
+    Other(Vec<u8>),
+}
+
+impl<N> FileSystemStatisticsData<N> {
+    /// The size of the largest file system specific structure, which shorter entries are zero-padded to.
+    const PADDED_SIZE: usize = NtfsStatisticsEx::SIZE;
+
+    fn add(&mut self, other: &Self)
+    where
+        N: for<'a> AddAssign<&'a N>,
+    {
+        use FileSystemStatisticsData::*;
+        match (self, other) {
+            (Ntfs(a), Ntfs(b)) => *a += b,
+            (Fat(a), Fat(b)) | (ExFat(a), ExFat(b)) => *a += b,
+            // Raw statistics can't be summed.
+            (Other(a), _) => a.clear(),
+            (a, _) => *a = Other(vec![]),
+        }
+    }
+}
+
+/// Reads the file system specific part of a per-processor entry, of `len` bytes.
+fn read_statistics_data<R: Read + Seek, N: for<'a> BinRead<Args<'a> = ()>>(
+    reader: &mut R,
+    endian: Endian,
+    (file_system_type, len): (FileSystemType, usize),
+) -> BinResult<FileSystemStatisticsData<N>> {
+    let mut data = vec![0; len];
+    reader.read_exact(&mut data)?;
+    let padded = |mut data: Vec<u8>| {
+        data.resize(len.max(FileSystemStatisticsData::<N>::PADDED_SIZE), 0);
+        Cursor::new(data)
+    };
+    Ok(match file_system_type {
+        FileSystemType::Ntfs => {
+            FileSystemStatisticsData::Ntfs(N::read_options(&mut padded(data), endian, ())?)
+        }
+        FileSystemType::Fat => FileSystemStatisticsData::Fat(FatStatistics::read_options(
+            &mut padded(data),
+            endian,
+            (),
+        )?),
+        FileSystemType::ExFat => FileSystemStatisticsData::ExFat(FatStatistics::read_options(
+            &mut padded(data),
+            endian,
+            (),
+        )?),
+        FileSystemType::Refs => FileSystemStatisticsData::Other(data),
+    })
+}
+
+/// A helper trait for summing counters, which wrap around like the server's counters do.
+trait Counter {
+    fn accumulate(&mut self, other: &Self);
+}
+
+macro_rules! impl_counter_int {
+    ($($type:ty),+) => {
+        $(
+            impl Counter for $type {
+                fn accumulate(&mut self, other: &Self) {
+                    *self = self.wrapping_add(*other);
+                }
+            }
+        )+
+    };
+}
+
+impl_counter_int!(u16, u32, u64);
+
+/// Implements [`AddAssign`] for a statistics struct, by summing each of the listed counters.
+macro_rules! impl_add_counters {
+    ($type:ty { $($field:ident),+ $(,)? }) => {
+        impl Counter for $type {
+            fn accumulate(&mut self, other: &Self) {
+                $(
+                    self.$field.accumulate(&other.$field);
+                )+
+            }
+        }
+
+        impl AddAssign<&$type> for $type {
+            fn add_assign(&mut self, other: &$type) {
+                self.accumulate(other);
+            }
+        }
+    };
+}
+
+/// A single per-processor entry of `FSCTL_FILESYSTEM_GET_STATISTICS` (`FILESYSTEM_STATISTICS`).
+#[binrw::binread]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSystemStatistics {
+    pub file_system_type: FileSystemType,
+    /// The version of the structure, currently 1.
+    pub version: u16,
+    #[br(temp, assert(
+        size_of_complete_structure as usize >= FileSystemStatistics::HEADER_SIZE,
+        "Invalid per-processor statistics size: {}", size_of_complete_structure
+    ))]
+    size_of_complete_structure: u32,
+
+    pub user_file_reads: u32,
+    pub user_file_read_bytes: u32,
+    pub user_disk_reads: u32,
+    pub user_file_writes: u32,
+    pub user_file_write_bytes: u32,
+    pub user_disk_writes: u32,
+
+    pub meta_data_reads: u32,
+    pub meta_data_read_bytes: u32,
+    pub meta_data_disk_reads: u32,
+    pub meta_data_writes: u32,
+    pub meta_data_write_bytes: u32,
+    pub meta_data_disk_writes: u32,
+
+    #[br(
+        parse_with = read_statistics_data,
+        args(file_system_type, size_of_complete_structure as usize - FileSystemStatistics::HEADER_SIZE)
+    )]
+    pub data: FileSystemStatisticsData<NtfsStatistics>,
+}
+
+impl FileSystemStatistics {
+    /// The size of the common part of the entry, before the file system specific statistics.
+    pub const HEADER_SIZE: usize = 56;
+}
+
+/// A single per-processor entry of `FSCTL_FILESYSTEM_GET_STATISTICS_EX` (`FILESYSTEM_STATISTICS_EX`).
+///
+/// This is the same as [`FileSystemStatistics`], with 64-bit counters.
+#[binrw::binread]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSystemStatisticsEx {
+    pub file_system_type: FileSystemType,
+    /// The version of the structure, currently 1.
+    pub version: u16,
+    #[br(temp, assert(
+        size_of_complete_structure as usize >= FileSystemStatisticsEx::HEADER_SIZE,
+        "Invalid per-processor statistics size: {}", size_of_complete_structure
+    ))]
+    size_of_complete_structure: u32,
+
+    pub user_file_reads: u64,
+    pub user_file_read_bytes: u64,
+    pub user_disk_reads: u64,
+    pub user_file_writes: u64,
+    pub user_file_write_bytes: u64,
+    pub user_disk_writes: u64,
+
+    pub meta_data_reads: u64,
+    pub meta_data_read_bytes: u64,
+    pub meta_data_disk_reads: u64,
+    pub meta_data_writes: u64,
+    pub meta_data_write_bytes: u64,
+    pub meta_data_disk_writes: u64,
+
+    #[br(
+        parse_with = read_statistics_data,
+        args(file_system_type, size_of_complete_structure as usize - FileSystemStatisticsEx::HEADER_SIZE)
+    )]
+    pub data: FileSystemStatisticsData<NtfsStatisticsEx>,
+}
+
+impl FileSystemStatisticsEx {
+    /// The size of the common part of the entry, before the file system specific statistics.
+    pub const HEADER_SIZE: usize = 104;
+
+    /// Sums the statistics of all the processors, or returns `None` if there are none.
+    ///
+    /// Raw statistics ([`FileSystemStatisticsData::Other`]) can't be summed, so they are empty in the result.
+    pub fn sum<'a>(
+        mut processors: impl Iterator<Item = &'a FileSystemStatisticsEx>,
+    ) -> Option<Self> {
+        let mut total = processors.next()?.clone();
+        if let FileSystemStatisticsData::Other(raw) = &mut total.data {
+            raw.clear();
+        }
+        for processor in processors {
+            total += processor;
+        }
+        Some(total)
+    }
+}
+
+impl AddAssign<&FileSystemStatisticsEx> for FileSystemStatisticsEx {
+    fn add_assign(&mut self, other: &FileSystemStatisticsEx) {
+        self.user_file_reads.accumulate(&other.user_file_reads);
+        self.user_file_read_bytes
+            .accumulate(&other.user_file_read_bytes);
+        self.user_disk_reads.accumulate(&other.user_disk_reads);
+        self.user_file_writes.accumulate(&other.user_file_writes);
+        self.user_file_write_bytes
+            .accumulate(&other.user_file_write_bytes);
+        self.user_disk_writes.accumulate(&other.user_disk_writes);
+        self.meta_data_reads.accumulate(&other.meta_data_reads);
+        self.meta_data_read_bytes
+            .accumulate(&other.meta_data_read_bytes);
+        self.meta_data_disk_reads
+            .accumulate(&other.meta_data_disk_reads);
+        self.meta_data_writes.accumulate(&other.meta_data_writes);
+        self.meta_data_write_bytes
+            .accumulate(&other.meta_data_write_bytes);
+        self.meta_data_disk_writes
+            .accumulate(&other.meta_data_disk_writes);
+        self.data.add(&other.data);
+    }
+}
+
+impl From<&FileSystemStatistics> for FileSystemStatisticsEx {
+    fn from(value: &FileSystemStatistics) -> Self {
+        Self {
+            file_system_type: value.file_system_type,
+            version: value.version,
+            user_file_reads: value.user_file_reads.into(),
+            user_file_read_bytes: value.user_file_read_bytes.into(),
+            user_disk_reads: value.user_disk_reads.into(),
+            user_file_writes: value.user_file_writes.into(),
+            user_file_write_bytes: value.user_file_write_bytes.into(),
+            user_disk_writes: value.user_disk_writes.into(),
+            meta_data_reads: value.meta_data_reads.into(),
+            meta_data_read_bytes: value.meta_data_read_bytes.into(),
+            meta_data_disk_reads: value.meta_data_disk_reads.into(),
+            meta_data_writes: value.meta_data_writes.into(),
+            meta_data_write_bytes: value.meta_data_write_bytes.into(),
+            meta_data_disk_writes: value.meta_data_disk_writes.into(),
+            data: match &value.data {
+                FileSystemStatisticsData::Ntfs(ntfs) => FileSystemStatisticsData::Ntfs(ntfs.into()),
+                FileSystemStatisticsData::Fat(fat) => FileSystemStatisticsData::Fat(fat.clone()),
+                FileSystemStatisticsData::ExFat(fat) => {
+                    FileSystemStatisticsData::ExFat(fat.clone())
+                }
+                FileSystemStatisticsData::Other(raw) => {
+                    FileSystemStatisticsData::Other(raw.clone())
+                }
+            },
+        }
+    }
+}
+
+/// The output of `FSCTL_FILESYSTEM_GET_STATISTICS`: an entry per processor.
+#[binrw::binread]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSystemStatisticsResponse {
+    #[br(parse_with = binrw::helpers::until_eof)]
+    pub processors: Vec<FileSystemStatistics>,
+}
+
+impl FsctlResponseContent for FileSystemStatisticsResponse {
+    const FSCTL_CODES: &'static [FsctlCodes] = &[FsctlCodes::FilesystemGetStatistics];
+}
+
+/// The output of `FSCTL_FILESYSTEM_GET_STATISTICS_EX`: an entry per processor.
+#[binrw::binread]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSystemStatisticsExResponse {
+    #[br(parse_with = binrw::helpers::until_eof)]
+    pub processors: Vec<FileSystemStatisticsEx>,
+}
+
+impl FsctlResponseContent for FileSystemStatisticsExResponse {
+    const FSCTL_CODES: &'static [FsctlCodes] = &[FsctlCodes::FilesystemGetStatisticsEx];
+}
+
+/// The number of writes of NTFS metadata, by the user level operation that caused them.
+#[binrw::binrw]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NtfsWritesUserLevel {
+    pub write: u16,
+    pub create: u16,
+    pub set_info: u16,
+    pub flush: u16,
+}
+
+impl_add_counters!(NtfsWritesUserLevel {
+    write,
+    create,
+    set_info,
+    flush
+});
+
+/// The number of writes of the NTFS volume bitmap, by the user level operation that caused them.
+#[binrw::binrw]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NtfsBitmapWritesUserLevel {
+    pub write: u16,
+    pub create: u16,
+    pub set_info: u16,
+}
+
+impl_add_counters!(NtfsBitmapWritesUserLevel {
+    write,
+    create,
+    set_info
+});
+
+/// Cluster allocation statistics of NTFS.
+#[binrw::binrw]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NtfsAllocateStatistics {
+    pub calls: u32,
+    pub clusters: u32,
+    pub hints: u32,
+    pub runs_returned: u32,
+    pub hints_honored: u32,
+    pub hints_clusters: u32,
+    pub cache: u32,
+    pub cache_clusters: u32,
+    pub cache_miss: u32,
+    pub cache_miss_clusters: u32,
+}
+
+impl_add_counters!(NtfsAllocateStatistics {
+    calls,
+    clusters,
+    hints,
+    runs_returned,
+    hints_honored,
+    hints_clusters,
+    cache,
+    cache_clusters,
+    cache_miss,
+    cache_miss_clusters,
+});
+
+/// NTFS statistics of a single processor (`NTFS_STATISTICS`).
+#[binrw::binrw]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NtfsStatistics {
+    pub log_file_full_exceptions: u32,
+    pub other_exceptions: u32,
+
+    pub mft_reads: u32,
+    pub mft_read_bytes: u32,
+    pub mft_writes: u32,
+    pub mft_write_bytes: u32,
+    pub mft_writes_user_level: NtfsWritesUserLevel,
+    pub mft_writes_flush_for_log_file_full: u16,
+    pub mft_writes_lazy_writer: u16,
+    pub mft_writes_user_request: u16,
+    #[bw(calc = 0)]
+    _reserved: u16,
+
+    pub mft2_writes: u32,
+    pub mft2_write_bytes: u32,
+    pub mft2_writes_user_level: NtfsWritesUserLevel,
+    pub mft2_writes_flush_for_log_file_full: u16,
+    pub mft2_writes_lazy_writer: u16,
+    pub mft2_writes_user_request: u16,
+    #[bw(calc = 0)]
+    _reserved2: u16,
+
+    pub root_index_reads: u32,
+    pub root_index_read_bytes: u32,
+    pub root_index_writes: u32,
+    pub root_index_write_bytes: u32,
+
+    pub bitmap_reads: u32,
+    pub bitmap_read_bytes: u32,
+    pub bitmap_writes: u32,
+    pub bitmap_write_bytes: u32,
+    pub bitmap_writes_flush_for_log_file_full: u16,
+    pub bitmap_writes_lazy_writer: u16,
+    pub bitmap_writes_user_request: u16,
+    pub bitmap_writes_user_level: NtfsBitmapWritesUserLevel,
+
+    pub mft_bitmap_reads: u32,
+    pub mft_bitmap_read_bytes: u32,
+    pub mft_bitmap_writes: u32,
+    pub mft_bitmap_write_bytes: u32,
+    pub mft_bitmap_writes_flush_for_log_file_full: u16,
+    pub mft_bitmap_writes_lazy_writer: u16,
+    pub mft_bitmap_writes_user_request: u16,
+    pub mft_bitmap_writes_user_level: NtfsWritesUserLevel,
+    #[bw(calc = 0)]
+    _reserved3: u16,
+
+    pub user_index_reads: u32,
+    pub user_index_read_bytes: u32,
+    pub user_index_writes: u32,
+    pub user_index_write_bytes: u32,
+
+    pub log_file_reads: u32,
+    pub log_file_read_bytes: u32,
+    pub log_file_writes: u32,
+    pub log_file_write_bytes: u32,
+
+    pub allocate: NtfsAllocateStatistics,
+
+    /// Added in Windows 8, and 0 for older servers.
+    pub disk_resources_exhausted: u32,
+}
+
+impl NtfsStatistics {
+    pub const SIZE: usize = 216;
+}
+
+impl_add_counters!(NtfsStatistics {
+    log_file_full_exceptions,
+    other_exceptions,
+    mft_reads,
+    mft_read_bytes,
+    mft_writes,
+    mft_write_bytes,
+    mft_writes_user_level,
+    mft_writes_flush_for_log_file_full,
+    mft_writes_lazy_writer,
+    mft_writes_user_request,
+    mft2_writes,
+    mft2_write_bytes,
+    mft2_writes_user_level,
+    mft2_writes_flush_for_log_file_full,
+    mft2_writes_lazy_writer,
+    mft2_writes_user_request,
+    root_index_reads,
+    root_index_read_bytes,
+    root_index_writes,
+    root_index_write_bytes,
+    bitmap_reads,
+    bitmap_read_bytes,
+    bitmap_writes,
+    bitmap_write_bytes,
+    bitmap_writes_flush_for_log_file_full,
+    bitmap_writes_lazy_writer,
+    bitmap_writes_user_request,
+    bitmap_writes_user_level,
+    mft_bitmap_reads,
+    mft_bitmap_read_bytes,
+    mft_bitmap_writes,
+    mft_bitmap_write_bytes,
+    mft_bitmap_writes_flush_for_log_file_full,
+    mft_bitmap_writes_lazy_writer,
+    mft_bitmap_writes_user_request,
+    mft_bitmap_writes_user_level,
+    user_index_reads,
+    user_index_read_bytes,
+    user_index_writes,
+    user_index_write_bytes,
+    log_file_reads,
+    log_file_read_bytes,
+    log_file_writes,
+    log_file_write_bytes,
+    allocate,
+    disk_resources_exhausted,
+});
+
+/// The number of writes of NTFS metadata, by the user level operation that caused them.
+///
+/// This is the same as [`NtfsWritesUserLevel`], with 32-bit counters.
+#[binrw::binrw]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NtfsWritesUserLevelEx {
+    pub write: u32,
+    pub create: u32,
+    pub set_info: u32,
+    pub flush: u32,
+}
+
+impl_add_counters!(NtfsWritesUserLevelEx {
+    write,
+    create,
+    set_info,
+    flush
+});
+
+impl From<&NtfsWritesUserLevel> for NtfsWritesUserLevelEx {
+    fn from(value: &NtfsWritesUserLevel) -> Self {
+        Self {
+            write: value.write.into(),
+            create: value.create.into(),
+            set_info: value.set_info.into(),
+            flush: value.flush.into(),
+        }
+    }
+}
+
+impl From<&NtfsBitmapWritesUserLevel> for NtfsWritesUserLevelEx {
+    fn from(value: &NtfsBitmapWritesUserLevel) -> Self {
+        Self {
+            write: value.write.into(),
+            create: value.create.into(),
+            set_info: value.set_info.into(),
+            flush: 0,
+        }
+    }
+}
+
+/// Cluster allocation statistics of NTFS.
+///
+/// This is the same as [`NtfsAllocateStatistics`], with 64-bit cluster counters.
+#[binrw::binrw]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NtfsAllocateStatisticsEx {
+    pub calls: u32,
+    pub runs_returned: u32,
+    pub hints: u32,
+    pub hints_honored: u32,
+    pub cache: u32,
+    pub cache_miss: u32,
+    pub clusters: u64,
+    pub hints_clusters: u64,
+    pub cache_clusters: u64,
+    pub cache_miss_clusters: u64,
+}
+
+impl_add_counters!(NtfsAllocateStatisticsEx {
+    calls,
+    runs_returned,
+    hints,
+    hints_honored,
+    cache,
+    cache_miss,
+    clusters,
+    hints_clusters,
+    cache_clusters,
+    cache_miss_clusters,
+});
+
+impl From<&NtfsAllocateStatistics> for NtfsAllocateStatisticsEx {
+    fn from(value: &NtfsAllocateStatistics) -> Self {
+        Self {
+            calls: value.calls,
+            runs_returned: value.runs_returned,
+            hints: value.hints,
+            hints_honored: value.hints_honored,
+            cache: value.cache,
+            cache_miss: value.cache_miss,
+            clusters: value.clusters.into(),
+            hints_clusters: value.hints_clusters.into(),
+            cache_clusters: value.cache_clusters.into(),
+            cache_miss_clusters: value.cache_miss_clusters.into(),
+        }
+    }
+}
+
+/// NTFS statistics of a single processor (`NTFS_STATISTICS_EX`).
+///
+/// This is a superset of [`NtfsStatistics`], with wider counters.
+#[binrw::binrw]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NtfsStatisticsEx {
+    pub log_file_full_exceptions: u32,
+    pub other_exceptions: u32,
+
+    pub mft_reads: u64,
+    pub mft_read_bytes: u64,
+    pub mft_writes: u64,
+    pub mft_write_bytes: u64,
+    pub mft_writes_user_level: NtfsWritesUserLevelEx,
+    pub mft_writes_flush_for_log_file_full: u32,
+    pub mft_writes_lazy_writer: u32,
+    pub mft_writes_user_request: u32,
+    #[bw(calc = 0)]
+    _reserved: u32,
+
+    pub mft2_writes: u64,
+    pub mft2_write_bytes: u64,
+    pub mft2_writes_user_level: NtfsWritesUserLevelEx,
+    pub mft2_writes_flush_for_log_file_full: u32,
+    pub mft2_writes_lazy_writer: u32,
+    pub mft2_writes_user_request: u32,
+    #[bw(calc = 0)]
+    _reserved2: u32,
+
+    pub root_index_reads: u64,
+    pub root_index_read_bytes: u64,
+    pub root_index_writes: u64,
+    pub root_index_write_bytes: u64,
+
+    pub bitmap_reads: u64,
+    pub bitmap_read_bytes: u64,
+    pub bitmap_writes: u64,
+    pub bitmap_write_bytes: u64,
+    pub bitmap_writes_flush_for_log_file_full: u32,
+    pub bitmap_writes_lazy_writer: u32,
+    pub bitmap_writes_user_request: u32,
+    pub bitmap_writes_user_level: NtfsWritesUserLevelEx,
+    #[bw(calc = 0)]
+    _reserved3: u32,
+
+    pub mft_bitmap_reads: u64,
+    pub mft_bitmap_read_bytes: u64,
+    pub mft_bitmap_writes: u64,
+    pub mft_bitmap_write_bytes: u64,
+    pub mft_bitmap_writes_flush_for_log_file_full: u32,
+    pub mft_bitmap_writes_lazy_writer: u32,
+    pub mft_bitmap_writes_user_request: u32,
+    pub mft_bitmap_writes_user_level: NtfsWritesUserLevelEx,
+    #[bw(calc = 0)]
+    _reserved4: u32,
+
+    pub user_index_reads: u64,
+    pub user_index_read_bytes: u64,
+    pub user_index_writes: u64,
+    pub user_index_write_bytes: u64,
+
+    pub log_file_reads: u64,
+    pub log_file_read_bytes: u64,
+    pub log_file_writes: u64,
+    pub log_file_write_bytes: u64,
+
+    pub allocate: NtfsAllocateStatisticsEx,
+
+    pub disk_resources_exhausted: u32,
+    #[bw(calc = 0)]
+    _reserved5: u32,
+
+    pub volume_trim_count: u64,
+    pub volume_trim_time: u64,
+    pub volume_trim_byte_count: u64,
+    pub file_level_trim_count: u64,
+    pub file_level_trim_time: u64,
+    pub file_level_trim_byte_count: u64,
+    pub volume_trim_skipped_count: u64,
+    pub volume_trim_skipped_byte_count: u64,
+
+    // Added in Windows 10 1709, and 0 for older servers.
+    pub ntfs_fill_stat_info_from_mft_record_called_count: u64,
+    pub ntfs_fill_stat_info_from_mft_record_bailed_because_of_attribute_list_count: u64,
+    pub ntfs_fill_stat_info_from_mft_record_bailed_because_of_non_res_reparse_point_count: u64,
+}
+
+impl NtfsStatisticsEx {
+    pub const SIZE: usize = 496;
+}
+
+impl_add_counters!(NtfsStatisticsEx {
+    log_file_full_exceptions,
+    other_exceptions,
+    mft_reads,
+    mft_read_bytes,
+    mft_writes,
+    mft_write_bytes,
+    mft_writes_user_level,
+    mft_writes_flush_for_log_file_full,
+    mft_writes_lazy_writer,
+    mft_writes_user_request,
+    mft2_writes,
+    mft2_write_bytes,
+    mft2_writes_user_level,
+    mft2_writes_flush_for_log_file_full,
+    mft2_writes_lazy_writer,
+    mft2_writes_user_request,
+    root_index_reads,
+    root_index_read_bytes,
+    root_index_writes,
+    root_index_write_bytes,
+    bitmap_reads,
+    bitmap_read_bytes,
+    bitmap_writes,
+    bitmap_write_bytes,
+    bitmap_writes_flush_for_log_file_full,
+    bitmap_writes_lazy_writer,
+    bitmap_writes_user_request,
+    bitmap_writes_user_level,
+    mft_bitmap_reads,
+    mft_bitmap_read_bytes,
+    mft_bitmap_writes,
+    mft_bitmap_write_bytes,
+    mft_bitmap_writes_flush_for_log_file_full,
+    mft_bitmap_writes_lazy_writer,
+    mft_bitmap_writes_user_request,
+    mft_bitmap_writes_user_level,
+    user_index_reads,
+    user_index_read_bytes,
+    user_index_writes,
+    user_index_write_bytes,
+    log_file_reads,
+    log_file_read_bytes,
+    log_file_writes,
+    log_file_write_bytes,
+    allocate,
+    disk_resources_exhausted,
+    volume_trim_count,
+    volume_trim_time,
+    volume_trim_byte_count,
+    file_level_trim_count,
+    file_level_trim_time,
+    file_level_trim_byte_count,
+    volume_trim_skipped_count,
+    volume_trim_skipped_byte_count,
+    ntfs_fill_stat_info_from_mft_record_called_count,
+    ntfs_fill_stat_info_from_mft_record_bailed_because_of_attribute_list_count,
+    ntfs_fill_stat_info_from_mft_record_bailed_because_of_non_res_reparse_point_count,
+});
+
+impl From<&NtfsStatistics> for NtfsStatisticsEx {
+    fn from(value: &NtfsStatistics) -> Self {
+        Self {
+            log_file_full_exceptions: value.log_file_full_exceptions,
+            other_exceptions: value.other_exceptions,
+            mft_reads: value.mft_reads.into(),
+            mft_read_bytes: value.mft_read_bytes.into(),
+            mft_writes: value.mft_writes.into(),
+            mft_write_bytes: value.mft_write_bytes.into(),
+            mft_writes_user_level: (&value.mft_writes_user_level).into(),
+            mft_writes_flush_for_log_file_full: value.mft_writes_flush_for_log_file_full.into(),
+            mft_writes_lazy_writer: value.mft_writes_lazy_writer.into(),
+            mft_writes_user_request: value.mft_writes_user_request.into(),
+            mft2_writes: value.mft2_writes.into(),
+            mft2_write_bytes: value.mft2_write_bytes.into(),
+            mft2_writes_user_level: (&value.mft2_writes_user_level).into(),
+            mft2_writes_flush_for_log_file_full: value.mft2_writes_flush_for_log_file_full.into(),
+            mft2_writes_lazy_writer: value.mft2_writes_lazy_writer.into(),
+            mft2_writes_user_request: value.mft2_writes_user_request.into(),
+            root_index_reads: value.root_index_reads.into(),
+            root_index_read_bytes: value.root_index_read_bytes.into(),
+            root_index_writes: value.root_index_writes.into(),
+            root_index_write_bytes: value.root_index_write_bytes.into(),
+            bitmap_reads: value.bitmap_reads.into(),
+            bitmap_read_bytes: value.bitmap_read_bytes.into(),
+            bitmap_writes: value.bitmap_writes.into(),
+            bitmap_write_bytes: value.bitmap_write_bytes.into(),
+            bitmap_writes_flush_for_log_file_full: value
+                .bitmap_writes_flush_for_log_file_full
+                .into(),
+            bitmap_writes_lazy_writer: value.bitmap_writes_lazy_writer.into(),
+            bitmap_writes_user_request: value.bitmap_writes_user_request.into(),
+            bitmap_writes_user_level: (&value.bitmap_writes_user_level).into(),
+            mft_bitmap_reads: value.mft_bitmap_reads.into(),
+            mft_bitmap_read_bytes: value.mft_bitmap_read_bytes.into(),
+            mft_bitmap_writes: value.mft_bitmap_writes.into(),
+            mft_bitmap_write_bytes: value.mft_bitmap_write_bytes.into(),
+            mft_bitmap_writes_flush_for_log_file_full: value
+                .mft_bitmap_writes_flush_for_log_file_full
+                .into(),
+            mft_bitmap_writes_lazy_writer: value.mft_bitmap_writes_lazy_writer.into(),
+            mft_bitmap_writes_user_request: value.mft_bitmap_writes_user_request.into(),
+            mft_bitmap_writes_user_level: (&value.mft_bitmap_writes_user_level).into(),
+            user_index_reads: value.user_index_reads.into(),
+            user_index_read_bytes: value.user_index_read_bytes.into(),
+            user_index_writes: value.user_index_writes.into(),
+            user_index_write_bytes: value.user_index_write_bytes.into(),
+            log_file_reads: value.log_file_reads.into(),
+            log_file_read_bytes: value.log_file_read_bytes.into(),
+            log_file_writes: value.log_file_writes.into(),
+            log_file_write_bytes: value.log_file_write_bytes.into(),
+            allocate: (&value.allocate).into(),
+            disk_resources_exhausted: value.disk_resources_exhausted,
+            ..Default::default()
+        }
+    }
+}
+
+/// FAT and exFAT statistics of a single processor (`FAT_STATISTICS` and `EXFAT_STATISTICS`).
+#[binrw::binrw]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FatStatistics {
+    pub create_hits: u32,
+    pub successful_create_hits: u32,
+    pub failed_create_hits: u32,
+    pub non_cached_reads: u32,
+    pub non_cached_read_bytes: u32,
+    pub non_cached_writes: u32,
+    pub non_cached_write_bytes: u32,
+    pub non_cached_disk_reads: u32,
+    pub non_cached_disk_writes: u32,
+}
+
+impl FatStatistics {
+    pub const SIZE: usize = 36;
+}
+
+impl_add_counters!(FatStatistics {
+    create_hits,
+    successful_create_hits,
+    failed_create_hits,
+    non_cached_reads,
+    non_cached_read_bytes,
+    non_cached_writes,
+    non_cached_write_bytes,
+    non_cached_disk_reads,
+    non_cached_disk_writes,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smb_tests::*;
+
+    // The fixtures are laid out as Windows servers return them: each per-processor entry is padded to 64 bytes,
+    // except for `NTFS_WITHOUT_PADDING`, which is the 212 bytes `NTFS_STATISTICS` of servers older than Windows 8.
+    const NTFS_EX_TWO_PROCESSORS: &str = "\
+        01000100800200000a00000000000000000001000000000000000000000000000000000000000000\
+        00000000000000000000000000000000030000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000500000000000000\
+        00000000000000000000000000000000000000000000000000000000020000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000100000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000001000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000070000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        01000100800200001400000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000040000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000600000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000900000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000";
+
+    const NTFS_TWO_PROCESSORS: &str = "\
+        01000100400100000100000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000003000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000200000000000000000000000000000000000000000000000000000000000400000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000800000000000000000000000000000000000000010000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        01000100400100000200000000000000000000000000000000000000000000000000000000000000\
+        0000000000000000000000000000000000000000000000000b000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000";
+
+    const NTFS_WITHOUT_PADDING: &str = "\
+        010001000c0100000100000000000000000000000000000000000000000000000000000000000000\
+        0000000000000000000000000000000000000000000000000b000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000000000000000000005000000";
+
+    const FAT_ONE_PROCESSOR: &str = "\
+        02000100800000000100000000000000000000000000000000000000000000000000000000000000\
+        00000000000000000000000000000000010000000000000000000000000000000000000000000000\
+        00000000000000000200000000000000000000000000000000000000000000000000000000000000\
+        0000000000000000";
+
+    fn ex_entry(
+        user_file_reads: u64,
+        user_file_read_bytes: u64,
+        meta_data_reads: u64,
+        data: FileSystemStatisticsData<NtfsStatisticsEx>,
+    ) -> FileSystemStatisticsEx {
+        FileSystemStatisticsEx {
+            file_system_type: FileSystemType::Ntfs,
+            version: 1,
+            user_file_reads,
+            user_file_read_bytes,
+            user_disk_reads: 0,
+            user_file_writes: 0,
+            user_file_write_bytes: 0,
+            user_disk_writes: 0,
+            meta_data_reads,
+            meta_data_read_bytes: 0,
+            meta_data_disk_reads: 0,
+            meta_data_writes: 0,
+            meta_data_write_bytes: 0,
+            meta_data_disk_writes: 0,
+            data,
+        }
+    }
+
+    fn entry(
+        file_system_type: FileSystemType,
+        user_file_reads: u32,
+        data: FileSystemStatisticsData<NtfsStatistics>,
+    ) -> FileSystemStatistics {
+        FileSystemStatistics {
+            file_system_type,
+            version: 1,
+            user_file_reads,
+            user_file_read_bytes: 0,
+            user_disk_reads: 0,
+            user_file_writes: 0,
+            user_file_write_bytes: 0,
+            user_disk_writes: 0,
+            meta_data_reads: 0,
+            meta_data_read_bytes: 0,
+            meta_data_disk_reads: 0,
+            meta_data_writes: 0,
+            meta_data_write_bytes: 0,
+            meta_data_disk_writes: 0,
+            data,
+        }
+    }
+
+    test_binrw_read! {
+        FileSystemStatisticsExResponse: FileSystemStatisticsExResponse {
+            processors: vec![
+                ex_entry(10, 0x10000, 3, FileSystemStatisticsData::Ntfs(NtfsStatisticsEx {
+                    mft_reads: 5,
+                    mft_writes_user_level: NtfsWritesUserLevelEx {
+                        create: 2,
+                        ..Default::default()
+                    },
+                    bitmap_writes_user_level: NtfsWritesUserLevelEx {
+                        flush: 1,
+                        ..Default::default()
+                    },
+                    allocate: NtfsAllocateStatisticsEx {
+                        clusters: 0x1_0000_0000,
+                        ..Default::default()
+                    },
+                    ntfs_fill_stat_info_from_mft_record_called_count: 7,
+                    ..Default::default()
+                })),
+                ex_entry(20, 0, 4, FileSystemStatisticsData::Ntfs(NtfsStatisticsEx {
+                    mft_reads: 6,
+                    log_file_writes: 9,
+                    ..Default::default()
+                })),
+            ],
+        } => NTFS_EX_TWO_PROCESSORS
+    }
+
+    test_binrw_read! {
+        FileSystemStatisticsResponse: FileSystemStatisticsResponse {
+            processors: vec![
+                entry(FileSystemType::Ntfs, 1, FileSystemStatisticsData::Ntfs(NtfsStatistics {
+                    mft_writes_user_request: 3,
+                    bitmap_writes_user_level: NtfsBitmapWritesUserLevel {
+                        set_info: 2,
+                        ..Default::default()
+                    },
+                    mft_bitmap_writes_user_level: NtfsWritesUserLevel {
+                        flush: 4,
+                        ..Default::default()
+                    },
+                    allocate: NtfsAllocateStatistics {
+                        hints_clusters: 8,
+                        ..Default::default()
+                    },
+                    disk_resources_exhausted: 1,
+                    ..Default::default()
+                })),
+                entry(FileSystemType::Ntfs, 2, FileSystemStatisticsData::Ntfs(NtfsStatistics {
+                    mft_reads: 11,
+                    ..Default::default()
+                })),
+            ],
+        } => NTFS_TWO_PROCESSORS
+    }
+
+    test_binrw_read! {
+        FileSystemStatisticsResponse => without_padding: FileSystemStatisticsResponse {
+            processors: vec![
+                entry(FileSystemType::Ntfs, 1, FileSystemStatisticsData::Ntfs(NtfsStatistics {
+                    mft_reads: 11,
+                    allocate: NtfsAllocateStatistics {
+                        cache_miss_clusters: 5,
+                        ..Default::default()
+                    },
+                    // Missing from the entry.
+                    disk_resources_exhausted: 0,
+                    ..Default::default()
+                })),
+            ],
+        } => NTFS_WITHOUT_PADDING
+    }
+
+    test_binrw_read! {
+        FileSystemStatisticsResponse => fat: FileSystemStatisticsResponse {
+            processors: vec![entry(FileSystemType::Fat, 1, FileSystemStatisticsData::Fat(FatStatistics {
+                create_hits: 1,
+                non_cached_disk_writes: 2,
+                ..Default::default()
+            }))],
+        } => FAT_ONE_PROCESSOR
+    }
+
+    #[test]
+    fn test_statistics_sizes() {
+        for (size, expected) in [
+            (write_size(&NtfsStatistics::default()), NtfsStatistics::SIZE),
+            (
+                write_size(&NtfsStatisticsEx::default()),
+                NtfsStatisticsEx::SIZE,
+            ),
+            (write_size(&FatStatistics::default()), FatStatistics::SIZE),
+        ] {
+            assert_eq!(size, expected);
+        }
+    }
+
+    fn write_size(value: &impl for<'a> BinWrite<Args<'a> = ()>) -> usize {
+        let mut cursor = Cursor::new(vec![]);
+        value.write_le(&mut cursor).unwrap();
+        cursor.into_inner().len()
+    }
+
+    #[test]
+    fn test_filesystem_statistics_sum() {
+        let response = FileSystemStatisticsExResponse::read_le(&mut Cursor::new(
+            hex_to_u8_array! { NTFS_EX_TWO_PROCESSORS },
+        ))
+        .unwrap();
+        let total = FileSystemStatisticsEx::sum(response.processors.iter()).unwrap();
+        assert_eq!(total.user_file_reads, 30);
+        assert_eq!(total.user_file_read_bytes, 0x10000);
+        assert_eq!(total.meta_data_reads, 7);
+        let FileSystemStatisticsData::Ntfs(ntfs) = &total.data else {
+            panic!("Expected NTFS statistics, got {:?}", total.data);
+        };
+        assert_eq!(ntfs.mft_reads, 11);
+        assert_eq!(ntfs.mft_writes_user_level.create, 2);
+        assert_eq!(ntfs.log_file_writes, 9);
+        assert_eq!(ntfs.allocate.clusters, 0x1_0000_0000);
+
+        // Legacy entries are summed as their extended version.
+        let response = FileSystemStatisticsResponse::read_le(&mut Cursor::new(
+            hex_to_u8_array! { NTFS_TWO_PROCESSORS },
+        ))
+        .unwrap();
+        let extended = response
+            .processors
+            .iter()
+            .map(FileSystemStatisticsEx::from)
+            .collect::<Vec<_>>();
+        let total = FileSystemStatisticsEx::sum(extended.iter()).unwrap();
+        assert_eq!(total.user_file_reads, 3);
+        let FileSystemStatisticsData::Ntfs(ntfs) = &total.data else {
+            panic!("Expected NTFS statistics, got {:?}", total.data);
+        };
+        assert_eq!(ntfs.mft_reads, 11);
+        assert_eq!(ntfs.bitmap_writes_user_level.set_info, 2);
+        assert_eq!(ntfs.bitmap_writes_user_level.flush, 0);
+        assert_eq!(ntfs.allocate.hints_clusters, 8);
+
+        assert_eq!(FileSystemStatisticsEx::sum([].iter()), None);
+    }
+}
@@ -30,10 +30,19 @@ pub enum FsctlCodes {
     LmrRequestResiliency = 0x001401D4,
     QueryNetworkInterfaceInfo = 0x001401FC,
     SetReparsePoint = 0x000900A4,
+    GetReparsePoint = 0x000900A8,
     DfsGetReferralsEx = 0x000601B0,
     FileLevelTrim = 0x00098208,
     ValidateNegotiateInfo = 0x00140204,
     QueryAllocatedRanges = 0x000940CF,
+    SetSparse = 0x000900C4,
+    GetObjectId = 0x0009009C,
+    CreateOrGetObjectId = 0x000900C0,
+    SetObjectId = 0x00090098,
+    SetObjectIdExtended = 0x000900BC,
+    DeleteObjectId = 0x000900A0,
+    FilesystemGetStatistics = 0x00090060,
+    FilesystemGetStatisticsEx = 0x0009038B,
 }
 
 /// The Length of source/dest keys in SrvCopyChunk* FSCTLs contents.
@@ -120,7 +129,7 @@ impl IoctlRequestContent for NetworkResiliencyRequest {
 }
 
 #[binrw::binrw]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ValidateNegotiateInfoRequest {
     pub capabilities: u32,
     pub guid: Guid,
@@ -180,6 +189,11 @@ pub struct SrvRequestResumeKey {
 
 impl_fsctl_response!(SrvRequestResumeKey, SrvRequestResumeKey);
 
+/// The response of `FSCTL_SRV_COPYCHUNK` and `FSCTL_SRV_COPYCHUNK_WRITE` - MS-SMB2 2.2.32.1
+///
+/// If the server fails the request with `STATUS_INVALID_PARAMETER` for exceeding its limits,
+/// the fields hold the limits of the server instead: the maximal number of chunks in a request,
+/// the maximal size of a chunk, and the maximal total size of the chunks in a request, respectively.
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
 pub struct SrvCopychunkResponse {
@@ -188,7 +202,14 @@ pub struct SrvCopychunkResponse {
     pub total_bytes_written: u32,
 }
 
-impl_fsctl_response!(SrvCopychunk, SrvCopychunkResponse);
+impl SrvCopychunkResponse {
+    pub const SIZE: usize = size_of::<u32>() * 3;
+}
+
+impl FsctlResponseContent for SrvCopychunkResponse {
+    const FSCTL_CODES: &'static [FsctlCodes] =
+        &[FsctlCodes::SrvCopychunk, FsctlCodes::SrvCopychunkWrite];
+}
 
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
@@ -326,7 +347,7 @@ impl SocketAddrStorageV6 {
 }
 
 #[binrw::binrw]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ValidateNegotiateInfoResponse {
     pub capabilities: u32,
     pub guid: Guid,
@@ -354,39 +375,56 @@ impl IoctlRequestContent for ReqGetDfsReferralEx {
     }
 }
 
+/// FILE_SET_SPARSE_BUFFER - the input of `FSCTL_SET_SPARSE` (MS-FSCC).
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
-pub struct QueryAllocRangesItem {
-    pub offset: u64,
-    pub len: u64,
+pub struct SetSparseRequest {
+    /// Whether to make the file sparse, or to make it not sparse.
+    pub set_sparse: Boolean,
 }
 
-impl IoctlRequestContent for QueryAllocRangesItem {
+impl IoctlRequestContent for SetSparseRequest {
     fn get_bin_size(&self) -> u32 {
-        (size_of::<u64>() * 2) as u32
+        size_of::<Boolean>() as u32
     }
 }
 
+impl IoctlRequestContent for FileAllocatedRangeBuffer {
+    fn get_bin_size(&self) -> u32 {
+        Self::SIZE as u32
+    }
+}
+
+/// The output of `FSCTL_QUERY_ALLOCATED_RANGES`: the allocated ranges of the queried range.
+///
+/// If the output buffer is too small for all of the ranges, the server fails the request with
+/// `STATUS_BUFFER_OVERFLOW`, and returns as many ranges as fit.
 #[binrw::binrw]
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct QueryAllocRangesResult {
     #[br(parse_with = binrw::helpers::until_eof)]
-    values: Vec<QueryAllocRangesItem>,
+    values: Vec<FileAllocatedRangeBuffer>,
 }
 
 impl Deref for QueryAllocRangesResult {
-    type Target = Vec<QueryAllocRangesItem>;
+    type Target = Vec<FileAllocatedRangeBuffer>;
     fn deref(&self) -> &Self::Target {
         &self.values
     }
 }
 
-impl From<Vec<QueryAllocRangesItem>> for QueryAllocRangesResult {
-    fn from(value: Vec<QueryAllocRangesItem>) -> Self {
+impl From<Vec<FileAllocatedRangeBuffer>> for QueryAllocRangesResult {
+    fn from(value: Vec<FileAllocatedRangeBuffer>) -> Self {
         Self { values: value }
     }
 }
 
+impl From<QueryAllocRangesResult> for Vec<FileAllocatedRangeBuffer> {
+    fn from(value: QueryAllocRangesResult) -> Self {
+        value.values
+    }
+}
+
 impl_fsctl_response!(QueryAllocatedRanges, QueryAllocRangesResult);
 
 /// The FSCTL_PIPE_WAIT Request requests that the server wait until either a time-out interval elapses,
@@ -452,6 +490,18 @@ impl IoctlRequestContent for SetReparsePointRequest {
     }
 }
 
+impl From<ReparseDataBuffer> for SetReparsePointRequest {
+    fn from(buffer: ReparseDataBuffer) -> Self {
+        Self {
+            reparse_tag: buffer.reparse_tag(),
+            reparse_guid: buffer.reparse_guid(),
+            reparse_data: buffer.data(),
+        }
+    }
+}
+
+impl_fsctl_response!(GetReparsePoint, ReparseDataBuffer);
+
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
 pub struct FileLevelTrimRequest {
@@ -589,6 +639,58 @@ pub struct OffloadReadResponse {
 
 impl_fsctl_response!(OffloadRead, OffloadReadResponse);
 
+/// The object ID of a file, along with its distributed link tracking information.
+///
+/// This is the type 1 variant of the structure, which is used by NTFS.
+///
+/// See MS-FSCC 2.1.3.1 (FILE_OBJECTID_BUFFER Type 1).
+#[binrw::binrw]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileObjectIdBuffer {
+    /// The object ID of the file, which is unique on the volume.
+    pub object_id: Guid,
+    /// The extended information of the object ID.
+    pub extended_info: ObjectIdExtendedInfo,
+}
+
+impl FileObjectIdBuffer {
+    pub const SIZE: usize = Guid::GUID_SIZE + ObjectIdExtendedInfo::SIZE;
+}
+
+impl IoctlRequestContent for FileObjectIdBuffer {
+    fn get_bin_size(&self) -> u32 {
+        Self::SIZE as u32
+    }
+}
+
+impl FsctlResponseContent for FileObjectIdBuffer {
+    const FSCTL_CODES: &'static [FsctlCodes] =
+        &[FsctlCodes::GetObjectId, FsctlCodes::CreateOrGetObjectId];
+}
+
+/// The extended information of an object ID: the last 48 bytes of [`FileObjectIdBuffer`],
+/// which are also the input of `FSCTL_SET_OBJECT_ID_EXTENDED`.
+#[binrw::binrw]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectIdExtendedInfo {
+    /// The object ID of the volume on which the file was created.
+    pub birth_volume_id: Guid,
+    /// The object ID that was assigned to the file when it was created.
+    pub birth_object_id: Guid,
+    /// Reserved, and should be zero.
+    pub domain_id: Guid,
+}
+
+impl ObjectIdExtendedInfo {
+    pub const SIZE: usize = Guid::GUID_SIZE * 3;
+}
+
+impl IoctlRequestContent for ObjectIdExtendedInfo {
+    fn get_bin_size(&self) -> u32 {
+        Self::SIZE as u32
+    }
+}
+
 /// This macro wraps an existing type into a newtype that implements the `IoctlRequestContent` trait.
 /// It also provides a constructor and implements `From` and `Deref` traits for the new type.
 ///
@@ -654,6 +756,14 @@ make_req_newtype!(pub SrvRequestResumeKeyRequest(()));
 make_req_newtype!(pub QueryNetworkInterfaceInfoRequest(()));
 make_req_newtype!(pub PipeTransceiveRequest(IoctlBuffer));
 make_req_newtype!(pub SrvCopyChunkCopyWrite(SrvCopychunkCopy));
+make_req_newtype!(pub GetObjectIdRequest(()));
+make_req_newtype!(pub CreateOrGetObjectIdRequest(()));
+make_req_newtype!(pub SetObjectIdRequest(FileObjectIdBuffer));
+make_req_newtype!(pub SetObjectIdExtendedRequest(ObjectIdExtendedInfo));
+make_req_newtype!(pub DeleteObjectIdRequest(()));
+make_req_newtype!(pub GetReparsePointRequest(()));
+make_req_newtype!(pub FileSystemGetStatisticsRequest(()));
+make_req_newtype!(pub FileSystemGetStatisticsExRequest(()));
 
 make_res_newtype!(
     PipeWait: pub PipeWaitResponse(())
@@ -664,11 +774,24 @@ make_res_newtype!(
 make_res_newtype!(
     SetReparsePoint: pub SetReparsePointResponse(())
 );
+make_res_newtype!(
+    SetSparse: pub SetSparseResponse(())
+);
 
 make_res_newtype!(
     LmrRequestResiliency: pub LmrRequestResiliencyResponse(())
 );
 
+make_res_newtype!(
+    SetObjectId: pub SetObjectIdResponse(())
+);
+make_res_newtype!(
+    SetObjectIdExtended: pub SetObjectIdExtendedResponse(())
+);
+make_res_newtype!(
+    DeleteObjectId: pub DeleteObjectIdResponse(())
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -683,6 +806,24 @@ mod tests {
         } => "2000000000000000000000000000000000000000000000000000a00000000000"
     }
 
+    test_binrw! {
+        struct ValidateNegotiateInfoRequest {
+            capabilities: 0x44,
+            guid: make_guid!("{c12e0ddf-43dd-11f0-8b87-000c29801682}"),
+            security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+            dialects: vec![Dialect::Smb021, Dialect::Smb030, Dialect::Smb0302],
+        } => "44000000df0d2ec1dd43f0118b87000c2980168201000300100200030203"
+    }
+
+    test_binrw! {
+        struct ValidateNegotiateInfoResponse {
+            capabilities: 0x45,
+            guid: make_guid!("{a1b2c3d4-0102-0304-0506-0708090a0b0c}"),
+            security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+            dialect: Dialect::Smb0302,
+        } => "45000000d4c3b2a10201040305060708090a0b0c01000203"
+    }
+
     test_binrw! {
         struct SrvRequestResumeKey {
             resume_key: [
@@ -695,7 +836,7 @@ mod tests {
 
     const CHUNK_SIZE: u32 = 1 << 20; // 1 MiB
     const TOTAL_SIZE: u32 = 10417096;
-    const BLOCK_NUM: u32 = (TOTAL_SIZE + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    const BLOCK_NUM: u32 = TOTAL_SIZE.div_ceil(CHUNK_SIZE);
 
     test_binrw! {
         struct SrvCopychunkCopy {
@@ -733,13 +874,13 @@ mod tests {
     test_binrw! {
         struct QueryAllocRangesResult {
             values: vec![
-                QueryAllocRangesItem {
-                    offset: 0,
-                    len: 4096,
+                FileAllocatedRangeBuffer {
+                    file_offset: 0,
+                    length: 4096,
                 },
-                QueryAllocRangesItem {
-                    offset: 8192,
-                    len: 46801,
+                FileAllocatedRangeBuffer {
+                    file_offset: 8192,
+                    length: 46801,
                 },
             ],
         } => "000000000000000000100000000000000020000000000000d1b6000000000000"
@@ -777,5 +918,16 @@ mod tests {
             00000000000000000000"
     }
 
+    test_binrw! {
+        struct FileObjectIdBuffer {
+            object_id: make_guid!("065eadf1-6daf-1543-b04f-10e69084c9ae"),
+            extended_info: ObjectIdExtendedInfo {
+                birth_volume_id: make_guid!("b0e6a1f2-3c4d-4e5f-8a9b-0c1d2e3f4a5b"),
+                birth_object_id: make_guid!("065eadf1-6daf-1543-b04f-10e69084c9ae"),
+                domain_id: Guid::ZERO,
+            },
+        } => "f1ad5e06af6d4315b04f10e69084c9aef2a1e6b04d3c5f4e8a9b0c1d2e3f4a5bf1ad5e06af6d4315b04f10e69084c9ae00000000000000000000000000000000"
+    }
+
     // TODO(TEST): Add missing tests. Consider testing size calc as well.
 }
@@ -1,11 +1,13 @@
 use super::{
     common::{IoctlBuffer, IoctlRequestContent},
+    fs_statistics::*,
     fsctl::*,
 };
 use binrw::io::TakeSeekExt;
 use binrw::prelude::*;
 use modular_bitfield::prelude::*;
 use smb_dtyp::binrw_util::prelude::*;
+use smb_fscc::{FileAllocatedRangeBuffer, ReparseDataBuffer};
 use std::io::SeekFrom;
 
 use crate::{
@@ -113,10 +115,19 @@ ioctl_req_data! {
     PipeWait: PipeWaitRequest, PipeWaitResponse,
     PipeTransceive: PipeTransceiveRequest, PipeTransceiveResponse,
     SetReparsePoint: SetReparsePointRequest, SetReparsePointResponse,
+    GetReparsePoint: GetReparsePointRequest, ReparseDataBuffer,
     DfsGetReferralsEx: ReqGetDfsReferralEx, RespGetDfsReferral,
     FileLevelTrim: FileLevelTrimRequest, FileLevelTrimResponse,
-    QueryAllocatedRanges: QueryAllocRangesItem, QueryAllocRangesResult,
+    QueryAllocatedRanges: FileAllocatedRangeBuffer, QueryAllocRangesResult,
+    SetSparse: SetSparseRequest, SetSparseResponse,
     OffloadRead: OffloadReadRequest, OffloadReadResponse,
+    GetObjectId: GetObjectIdRequest, FileObjectIdBuffer,
+    CreateOrGetObjectId: CreateOrGetObjectIdRequest, FileObjectIdBuffer,
+    SetObjectId: SetObjectIdRequest, SetObjectIdResponse,
+    SetObjectIdExtended: SetObjectIdExtendedRequest, SetObjectIdExtendedResponse,
+    DeleteObjectId: DeleteObjectIdRequest, DeleteObjectIdResponse,
+    FilesystemGetStatistics: FileSystemGetStatisticsRequest, FileSystemStatisticsResponse,
+    FilesystemGetStatisticsEx: FileSystemGetStatisticsExRequest, FileSystemStatisticsExResponse,
 }
 
 #[bitfield]
@@ -182,7 +193,7 @@ impl IoctlResponse {
             return Err(crate::SmbMsgError::MissingFsctlDefinition(self.ctl_code));
         }
         let mut cursor = std::io::Cursor::new(&self.out_buffer);
-        Ok(T::read_le(&mut cursor).unwrap())
+        Ok(T::read_le(&mut cursor)?)
     }
 }
 
@@ -194,7 +205,7 @@ mod tests {
 
     use super::*;
 
-    const REQ_IOCTL_BUFFER_CONTENT: &'static str = "0500000310000000980000000300000080000000010039000000000013f8a58f166fb54482c28f2dae140df50000000001000000000000000000020000000000010000000000000000000200000000000500000000000000010500000000000515000000173da72e955653f915dff280e9030000000000000000000000000000000000000000000001000000000000000000000002000000";
+    const REQ_IOCTL_BUFFER_CONTENT: &str = "0500000310000000980000000300000080000000010039000000000013f8a58f166fb54482c28f2dae140df50000000001000000000000000000020000000000010000000000000000000200000000000500000000000000010500000000000515000000173da72e955653f915dff280e9030000000000000000000000000000000000000000000001000000000000000000000002000000";
 
     test_request! {
         Ioctl {
@@ -216,7 +227,7 @@ mod tests {
     }
 
     // Just to make things pretty; do NOT edit.
-    const IOCTL_TEST_BUFFER_CONTENT: &'static str = "05000203100000000401000003000000ec00000001000000000002000000000001000000000000000000020000000000200000000000000001000000000000000c000e000000000000000200000000000000020000000000070000000000000000000000000000000600000000000000410056004900560056004d00000000000400000000000000010400000000000515000000173da72e955653f915dff28001000000000000000000020000000000010000000000000001000000000000000a000c00000000000000020000000000000000000000000006000000000000000000000000000000050000000000000061007600690076006e0000000100000000000000";
+    const IOCTL_TEST_BUFFER_CONTENT: &str = "05000203100000000401000003000000ec00000001000000000002000000000001000000000000000000020000000000200000000000000001000000000000000c000e000000000000000200000000000000020000000000070000000000000000000000000000000600000000000000410056004900560056004d00000000000400000000000000010400000000000515000000173da72e955653f915dff28001000000000000000000020000000000010000000000000001000000000000000a000c00000000000000020000000000000000000000000006000000000000000000000000000000050000000000000061007600690076006e0000000100000000000000";
 
     test_response! {
         Ioctl {
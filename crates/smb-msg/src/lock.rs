@@ -2,8 +2,9 @@ use super::FileId;
 use binrw::prelude::*;
 use modular_bitfield::prelude::*;
 
+/// SMB2 LOCK request, locking or unlocking byte ranges of a file (MS-SMB2 2.2.26).
 #[binrw::binrw]
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct LockRequest {
     #[bw(calc = 48)]
     #[br(assert(_structure_size == 48))]
@@ -16,6 +17,9 @@ pub struct LockRequest {
     pub locks: Vec<LockElement>,
 }
 
+/// Identifies a lock request for replay detection on resilient and persistent handles.
+///
+/// Zero, unless the handle is resilient or persistent and the dialect is SMB 2.1 or 3.x.
 #[bitfield]
 #[derive(BinWrite, BinRead, Debug, Default, Clone, Copy, PartialEq, Eq)]
 #[bw(map = |&x| Self::into_bytes(x))]
@@ -25,8 +29,9 @@ pub struct LockSequence {
     pub index: B28,
 }
 
+/// A single byte range to lock or unlock.
 #[binrw::binrw]
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct LockElement {
     pub offset: u64,
     pub length: u64,
@@ -50,18 +55,46 @@ pub struct LockFlag {
 }
 
 #[binrw::binrw]
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct LockResponse {
     #[bw(calc = 4)]
     #[br(assert(_structure_size == 4))]
-    pub _structure_size: u16,
+    _structure_size: u16,
     #[bw(calc = 0)]
     #[br(assert(_reserved == 0))]
-    pub _reserved: u16,
+    _reserved: u16,
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::*;
+
+    use super::*;
+
+    test_request! {
+        Lock {
+            lock_sequence: LockSequence::new().with_number(1).with_index(5),
+            file_id: [
+                0x14, 0x04, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x51, 0x00, 0x10, 0x00, 0x0c, 0x00,
+                0x00, 0x00,
+            ]
+            .into(),
+            locks: vec![
+                LockElement {
+                    offset: 0x10,
+                    length: 0x20,
+                    flags: LockFlag::new().with_exclusive(true).with_fail_immediately(true),
+                },
+                LockElement {
+                    offset: 0x1000,
+                    length: 1,
+                    flags: LockFlag::new().with_unlock(true),
+                },
+            ],
+        } => "3000020051000000140400000c000000510010000c000000100000000000000020000000000000000a00000000000000001000000000000001000000000000000400000000000000"
+    }
 
-    // TODO: tests
+    test_response! {
+        Lock {} => "04000000"
+    }
 }
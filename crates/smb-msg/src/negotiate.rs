@@ -118,14 +118,17 @@ impl NegotiateResponse {
     }
 
     pub fn get_ctx_integrity_algo(&self) -> Option<HashAlgorithm> {
+        self.get_ctx_integrity()
+            .and_then(|caps| caps.hash_algorithms.first().copied())
+    }
+
+    pub fn get_ctx_integrity(&self) -> Option<&PreauthIntegrityCapabilities> {
         self.negotiate_context_list.as_ref().and_then(|contexts| {
             contexts
                 .iter()
                 .find_map(|context| match &context.context_type {
                     NegotiateContextType::PreauthIntegrityCapabilities => match &context.data {
-                        NegotiateContextValue::PreauthIntegrityCapabilities(caps) => {
-                            caps.hash_algorithms.first().copied()
-                        }
+                        NegotiateContextValue::PreauthIntegrityCapabilities(caps) => Some(caps),
                         _ => None,
                     },
                     _ => None,
@@ -300,11 +303,59 @@ negotiate_context_type!(
     SigningCapabilities = 0x0008,
 );
 
-// u16 enum hash algorithms binrw 0x01 is sha512.
-#[derive(BinRead, BinWrite, Debug, PartialEq, Eq, Clone, Copy)]
-#[brw(repr(u16))]
+/// Preauth integrity hash algorithms.
+///
+/// Unknown algorithm IDs are parsed into [`HashAlgorithm::Unknown`], so a server selecting
+/// an algorithm that is not supported can be reported properly.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum HashAlgorithm {
-    Sha512 = 0x01,
+    Sha512,
+    /// An algorithm that is not known to this crate.
+    Unknown(u16),
+}
+
+impl HashAlgorithm {
+    /// Returns the algorithm ID, as sent on the wire.
+    pub const fn code(&self) -> u16 {
+        match self {
+            HashAlgorithm::Sha512 => 0x01,
+            HashAlgorithm::Unknown(code) => *code,
+        }
+    }
+}
+
+impl From<u16> for HashAlgorithm {
+    fn from(code: u16) -> Self {
+        match code {
+            0x01 => HashAlgorithm::Sha512,
+            _ => HashAlgorithm::Unknown(code),
+        }
+    }
+}
+
+impl BinRead for HashAlgorithm {
+    type Args<'a> = ();
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<Self> {
+        Ok(u16::read_options(reader, endian, ())?.into())
+    }
+}
+
+impl BinWrite for HashAlgorithm {
+    type Args<'a> = ();
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> BinResult<()> {
+        self.code().write_options(writer, endian, ())
+    }
 }
 
 #[binrw::binrw]
@@ -461,7 +512,7 @@ pub enum SigningAlgorithmId {
 #[cfg(test)]
 mod tests {
     use smb_dtyp::make_guid;
-    use smb_tests::hex_to_u8_array;
+    use smb_tests::*;
     use time::macros::datetime;
 
     use super::*;
@@ -526,6 +577,19 @@ mod tests {
         000000007000c0000000000020000000000000001000200"
     }
 
+    test_binrw! {
+        struct PreauthIntegrityCapabilities {
+            hash_algorithms: vec![HashAlgorithm::Sha512, HashAlgorithm::Unknown(0x02)],
+            salt: vec![0x01, 0x02, 0x03, 0x04],
+        } => "020004000100020001020304"
+    }
+
+    test_binrw! {
+        NegotiateContext => signing_restricted: NegotiateContext::from(SigningCapabilities {
+            signing_algorithms: vec![SigningAlgorithmId::AesCmac],
+        }) => "080004000000000001000100"
+    }
+
     test_response! {
         Negotiate {
             security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
@@ -3,33 +3,41 @@ use binrw::prelude::*;
 use modular_bitfield::prelude::*;
 use smb_dtyp::Guid;
 
+/// 2.2.23.1, 2.2.24.1, 2.2.25.1: SMB2 OPLOCK_BREAK Notification, Acknowledgment and Response.
+///
+/// The server notifies the client that the oplock of an open is broken to `oplock_level`,
+/// and the client acknowledges the break with the level it holds from now on.
 #[binrw::binrw]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct OplockBreakMsg {
     #[bw(calc = 24)]
     #[br(assert(_structure_size == 24))]
     _structure_size: u16,
-    oplock_level: u8,
+    pub oplock_level: OplockLevel,
     #[bw(calc = 0)]
     #[br(assert(_reserved == 0))]
     _reserved: u8,
     #[bw(calc = 0)]
     #[br(assert(reserved2 == 0))]
     reserved2: u32,
-    file_id: FileId,
+    pub file_id: FileId,
 }
 
+/// 2.2.23.2: SMB2 Lease Break Notification
+///
+/// The server notifies the client that the lease of `lease_key` is broken from `current_lease_state`
+/// to `new_lease_state`.
 #[binrw::binrw]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct LeaseBreakNotify {
     #[bw(calc = 44)]
     #[br(assert(_structure_size == 44))]
     _structure_size: u16,
-    new_epoch: u16,
-    ack_required: u32,
-    lease_key: Guid,
-    current_lease_state: LeaseState,
-    new_lease_state: LeaseState,
+    pub new_epoch: u16,
+    pub flags: LeaseBreakNotifyFlags,
+    pub lease_key: Guid,
+    pub current_lease_state: LeaseState,
+    pub new_lease_state: LeaseState,
     #[bw(calc = 0)]
     #[br(assert(break_reason == 0))]
     break_reason: u32,
@@ -41,13 +49,26 @@ pub struct LeaseBreakNotify {
     share_mask_hint: u32,
 }
 
+#[bitfield]
+#[derive(BinWrite, BinRead, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[bw(map = |&x| Self::into_bytes(x))]
+#[br(map = Self::from_bytes)]
+pub struct LeaseBreakNotifyFlags {
+    /// The client must acknowledge the break, using a [`LeaseBreakAck`].
+    pub ack_required: bool,
+    #[skip]
+    __: B31,
+}
+
 #[binrw::binrw]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[brw(repr(u8))]
 pub enum OplockLevel {
     None = 0,
     II = 1,
-    Exclusive = 2,
+    Exclusive = 8,
+    Batch = 9,
+    Lease = 0xff,
 }
 
 #[bitfield]
@@ -67,8 +88,11 @@ pub type OplockBreakNotify = OplockBreakMsg;
 pub type OplockBreakAck = OplockBreakMsg;
 pub type OplockBreakResponse = OplockBreakMsg;
 
+/// 2.2.24.2, 2.2.25.2: SMB2 Lease Break Acknowledgment and Response.
+///
+/// The client acknowledges the break of the lease of `lease_key`, with the state it holds from now on.
 #[binrw::binrw]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct LeaseBreakAckResponse {
     #[bw(calc = 36)]
     #[br(assert(_structure_size == 36))]
@@ -79,8 +103,8 @@ pub struct LeaseBreakAckResponse {
     #[bw(calc = 0)] // reserved
     #[br(assert(flags == 0))]
     flags: u32,
-    lease_key: Guid,
-    lease_state: LeaseState,
+    pub lease_key: Guid,
+    pub lease_state: LeaseState,
     #[bw(calc = 0)] // reserved
     #[br(assert(lease_duration == 0))]
     lease_duration: u64,
@@ -98,7 +122,7 @@ mod tests {
     test_binrw! {
         struct LeaseBreakNotify {
             new_epoch: 2,
-            ack_required: 1,
+            flags: LeaseBreakNotifyFlags::new().with_ack_required(true),
             lease_key: "70c8619e-165d-315e-d492-a01b0cbb3af2".parse().unwrap(),
             current_lease_state: LeaseState::new()
                 .with_read_caching(true)
@@ -107,6 +131,16 @@ mod tests {
         } => "2c000200010000009e61c8705d165e31d492a01b0cbb3af20300000000000000000000000000000000000000"
     }
 
+    test_binrw! {
+        struct OplockBreakNotify {
+            oplock_level: OplockLevel::II,
+            file_id: FileId {
+                persistent: 0x11,
+                volatile: 0x22,
+            },
+        } => "180001000000000011000000000000002200000000000000"
+    }
+
     test_binrw! {
         struct LeaseBreakAck {
             lease_key: "70c8619e-165d-315e-d492-a01b0cbb3af2".parse().unwrap(),
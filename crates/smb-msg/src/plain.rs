@@ -3,6 +3,19 @@ use binrw::prelude::*;
 use super::header::*;
 use super::*;
 
+/// The parse error of a content variant of another command than the one of the message.
+///
+/// Contents are parsed by trying their variants in order, so this fails every variant before the matching one.
+/// Unlike the default assertion message, this error is not allocated.
+#[derive(Debug)]
+struct OtherCommand;
+
+impl std::fmt::Display for OtherCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The content variant is of another command")
+    }
+}
+
 /// Makes the [`RequestContent`] & [`ResponseContent`] methods
 macro_rules! make_content_impl {
     (
@@ -19,6 +32,7 @@ impl $struct_name {
             $(
                 [<$variant>](_) => stringify!([<$variant>]),
             )+
+            Unknown { .. } => "Unknown",
         }
     }
 
@@ -72,47 +86,69 @@ macro_rules! make_content {
 #[derive(BinRead, BinWrite, Debug)]
 #[brw(import(command: &Command))]
 #[brw(little)]
+#[non_exhaustive]
 pub enum RequestContent {
     $(
-        #[br(pre_assert(matches!(command, Command::$cmd)))]
+        #[br(pre_assert(matches!(command, Command::$cmd), OtherCommand))]
         $cmd($struct_pfx::[<$cmd Request>]),
     )*
 
     // cancel request
-    #[br(pre_assert(matches!(command, Command::Cancel)))]
+    #[br(pre_assert(matches!(command, Command::Cancel), OtherCommand))]
     Cancel(cancel::CancelRequest),
 
     // oplock
-    #[br(pre_assert(matches!(command, Command::OplockBreak)))]
+    #[br(pre_assert(matches!(command, Command::OplockBreak), OtherCommand))]
     OplockBreakAck(oplock::OplockBreakAck),
-    #[br(pre_assert(matches!(command, Command::OplockBreak)))]
+    #[br(pre_assert(matches!(command, Command::OplockBreak), OtherCommand))]
     LeaseBreakAck(oplock::LeaseBreakAck),
+
+    /// The content of a message of a command that is not known to this crate.
+    #[br(pre_assert(matches!(command, Command::Unknown(_)), OtherCommand))]
+    Unknown {
+        #[br(calc = command.code())]
+        #[bw(ignore)]
+        command: u16,
+        #[br(parse_with = binrw::helpers::until_eof)]
+        body: Vec<u8>,
+    },
 }
 
 #[derive(BinRead, BinWrite, Debug)]
 #[brw(import(command: &Command))]
 #[brw(little)]
+#[non_exhaustive]
 pub enum ResponseContent {
     $(
-        #[br(pre_assert(matches!(command, Command::$cmd)))]
+        #[br(pre_assert(matches!(command, Command::$cmd), OtherCommand))]
         $cmd($struct_pfx::[<$cmd Response>]),
     )*
 
-    #[br(pre_assert(matches!(command, Command::OplockBreak)))]
+    #[br(pre_assert(matches!(command, Command::OplockBreak), OtherCommand))]
     OplockBreakNotify(oplock::OplockBreakNotify),
-    #[br(pre_assert(matches!(command, Command::OplockBreak)))]
+    #[br(pre_assert(matches!(command, Command::OplockBreak), OtherCommand))]
     LeaseBreakNotify(oplock::LeaseBreakNotify),
-    #[br(pre_assert(matches!(command, Command::OplockBreak)))]
+    #[br(pre_assert(matches!(command, Command::OplockBreak), OtherCommand))]
     OplockBreak(oplock::OplockBreakResponse),
-    #[br(pre_assert(matches!(command, Command::OplockBreak)))]
+    #[br(pre_assert(matches!(command, Command::OplockBreak), OtherCommand))]
     LeaseBreak(oplock::LeaseBreakResponse),
 
     // server to client notification
-    #[br(pre_assert(matches!(command, Command::ServerToClientNotification)))]
+    #[br(pre_assert(matches!(command, Command::ServerToClientNotification), OtherCommand))]
     ServerToClientNotification(notify::ServerToClientNotification),
 
     // error response
     Error(error::ErrorResponse),
+
+    /// The content of a message of a command that is not known to this crate.
+    #[br(pre_assert(matches!(command, Command::Unknown(_)), OtherCommand))]
+    Unknown {
+        #[br(calc = command.code())]
+        #[bw(ignore)]
+        command: u16,
+        #[br(parse_with = binrw::helpers::until_eof)]
+        body: Vec<u8>,
+    },
 }
 
 impl RequestContent {
@@ -127,6 +163,7 @@ impl RequestContent {
             Cancel(_) => Command::Cancel,
             OplockBreakAck(_)
             | LeaseBreakAck(_) => Command::OplockBreak,
+            Unknown { command, .. } => Command::Unknown(*command),
         }
     }
 }
@@ -146,6 +183,7 @@ impl ResponseContent {
             | LeaseBreak(_) => Command::OplockBreak,
             ServerToClientNotification(_) => Command::ServerToClientNotification,
             Error(_) => panic!("Error has no matching command!"),
+            Unknown { command, .. } => Command::Unknown(*command),
         }
     }
 }
@@ -324,3 +362,134 @@ gen_req_resp!(binrw::binrw, binrw::binrw);
 gen_req_resp!(binrw::binwrite, binrw::binread);
 #[cfg(all(feature = "server", not(feature = "client")))]
 gen_req_resp!(binrw::binread, binrw::binwrite);
+
+/// Returns the (offset, length) pairs of the reserved fields of a response body,
+/// relative to the beginning of the body, that are validated when parsing the response.
+fn reserved_response_fields(command: u16, structure_size: u16) -> &'static [(usize, usize)] {
+    const LOGOFF: u16 = Command::Logoff.code();
+    const TREE_CONNECT: u16 = Command::TreeConnect.code();
+    const TREE_DISCONNECT: u16 = Command::TreeDisconnect.code();
+    const CREATE: u16 = Command::Create.code();
+    const FLUSH: u16 = Command::Flush.code();
+    const READ: u16 = Command::Read.code();
+    const WRITE: u16 = Command::Write.code();
+    const LOCK: u16 = Command::Lock.code();
+    const ECHO: u16 = Command::Echo.code();
+    const OPLOCK_BREAK: u16 = Command::OplockBreak.code();
+    match (command, structure_size) {
+        // Error response, for any command.
+        (_, 9) => &[(3, 1)],
+        (LOGOFF | TREE_DISCONNECT | FLUSH | LOCK | ECHO, 4) => &[(2, 2)],
+        (WRITE, 17) => &[(2, 2)],
+        (TREE_CONNECT, 16) => &[(3, 1)],
+        (CREATE, 89) => &[(60, 4)],
+        (READ, 17) => &[(3, 1), (12, 4)],
+        // Oplock break notification, and lease break acknowledgment response.
+        (OPLOCK_BREAK, 24) => &[(3, 1), (4, 4)],
+        (OPLOCK_BREAK, 36) => &[(2, 2)],
+        _ => &[],
+    }
+}
+
+/// Clears non-zero reserved fields of a raw, plain response message, in place.
+///
+/// The response structures strictly validate that reserved fields are zero, as the specification requires.
+/// Some servers do not zero them, and this function may be used to parse their responses anyway.
+///
+/// Only the reserved fields of the header-less structures that are validated when parsing are handled.
+/// Encrypted, compressed and malformed messages are left unchanged.
+///
+/// Returns the number of fields that have been cleared.
+pub fn clear_reserved_fields(raw: &mut [u8]) -> usize {
+    const PROTOCOL_ID: &[u8] = b"\xfeSMB";
+    if raw.len() < Header::STRUCT_SIZE + 2 || !raw.starts_with(PROTOCOL_ID) {
+        return 0;
+    }
+
+    let command = u16::from_le_bytes([raw[12], raw[13]]);
+    let body = &mut raw[Header::STRUCT_SIZE..];
+    let structure_size = u16::from_le_bytes([body[0], body[1]]);
+
+    let mut cleared = 0;
+    for &(offset, length) in reserved_response_fields(command, structure_size) {
+        let Some(field) = body.get_mut(offset..offset + length) else {
+            continue;
+        };
+        if field.iter().any(|&b| b != 0) {
+            field.fill(0);
+            cleared += 1;
+        }
+    }
+    cleared
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::prelude::*;
+    use smb_tests::*;
+
+    use crate::*;
+
+    /// A tree connect response, with a non-zero reserved field.
+    const TREE_CONNECT_RESPONSE: &str = const_format::concatcp!(
+        "fe534d424000000000000000030001000100000000000000",
+        "0200000000000000000000000100000011000000000000000000000000000000",
+        "0000000000000000",
+        "100001ff0008000000000000ff011f00"
+    );
+
+    #[test]
+    fn test_clear_reserved_fields() {
+        let mut raw = hex_to_u8_array!(TREE_CONNECT_RESPONSE);
+        assert!(Response::try_from(raw.as_ref()).is_err());
+
+        assert_eq!(clear_reserved_fields(&mut raw), 1);
+        let response = match Response::try_from(raw.as_ref()).unwrap() {
+            Response::Plain(response) => response,
+            _ => panic!("Expected a plain response"),
+        };
+        assert_eq!(
+            response.content.to_treeconnect().unwrap().share_type,
+            ShareType::Disk
+        );
+
+        // Nothing left to clear.
+        assert_eq!(clear_reserved_fields(&mut raw), 0);
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        const CODE: u16 = 0x7f;
+        let mut response = PlainResponse::new(ResponseContent::Unknown {
+            command: CODE,
+            body: vec![0x10, 0x00, 0xaa, 0xbb],
+        });
+        response.header.flags.set_server_to_redir(true);
+        assert_eq!(response.header.command, Command::Unknown(CODE));
+
+        let mut raw = binrw::io::Cursor::new(Vec::new());
+        response.write(&mut raw).unwrap();
+        let raw = raw.into_inner();
+        assert_eq!(u16::from_le_bytes([raw[12], raw[13]]), CODE);
+
+        let response = match Response::try_from(raw.as_ref()).unwrap() {
+            Response::Plain(response) => response,
+            _ => panic!("Expected a plain response"),
+        };
+        assert_eq!(response.header.command, Command::Unknown(CODE));
+        assert_eq!(response.content.content_name(), "Unknown");
+        assert!(matches!(
+            response.content,
+            ResponseContent::Unknown { command: CODE, body } if body == [0x10, 0x00, 0xaa, 0xbb]
+        ));
+    }
+
+    #[test]
+    fn test_clear_reserved_fields_ignores_other_messages() {
+        let mut raw = vec![0xfd, b'S', b'M', b'B'];
+        raw.resize(Header::STRUCT_SIZE + 16, 0xff);
+        let original = raw.clone();
+        assert_eq!(clear_reserved_fields(&mut raw), 0);
+        assert_eq!(raw, original);
+    }
+}
@@ -78,6 +78,20 @@ impl QueryDirectoryResponse {
                 .into(),
         )
     }
+
+    /// Returns an iterator over the entries of the output buffer, which parses each entry only when it is reached.
+    ///
+    /// Prefer this over [`read_output`][Self::read_output] for large buffers, to avoid holding all the parsed entries at once.
+    pub fn into_output_iter<T>(
+        self,
+    ) -> ChainedItemReader<T, { QueryDirectoryInfo::CHAINED_ALIGNMENT }>
+    where
+        T: QueryDirectoryInfoValue + BinRead + BinWrite,
+        for<'a> <T as BinRead>::Args<'a>: Default,
+        for<'b> <T as BinWrite>::Args<'b>: Default,
+    {
+        ChainedItemReader::new(self.output_buffer)
+    }
 }
 
 #[cfg(test)]
@@ -133,11 +147,17 @@ mod tests {
             0x0, 0x0, 0x4, 0x0, 0x64, 0x0, 0x2e, 0x0, 0x74, 0x0, 0x78, 0x0, 0x74, 0x0,
         ];
 
-        let res = QueryDirectoryResponse {
+        let response = QueryDirectoryResponse {
             output_buffer: data.to_vec(),
-        }
-        .read_output::<FileIdBothDirectoryInformation>()
-        .unwrap();
+        };
+        let res = response
+            .read_output::<FileIdBothDirectoryInformation>()
+            .unwrap();
+        let lazy_res = response
+            .into_output_iter::<FileIdBothDirectoryInformation>()
+            .collect::<BinResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lazy_res, res);
 
         assert_eq!(
             vec![
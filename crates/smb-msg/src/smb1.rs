@@ -2,6 +2,9 @@
 //!
 //! For multi-protocol negotiation only.
 
+/// The protocol ID of SMBv1 messages, which are prefixed by it.
+pub const SMB1_PROTOCOL_ID: &[u8; 4] = b"\xffSMB";
+
 use binrw::io::TakeSeekExt;
 use binrw::prelude::*;
 
@@ -48,39 +51,88 @@ impl SMB1NegotiateMessage {
     pub fn is_smb2_supported(&self) -> bool {
         self.dialects
             .iter()
-            .any(|d| d.name.to_string() == "SMB 2.002")
+            .any(|d| d.name.to_string() == Smb1Dialect::SMB_2_002)
     }
 }
 
-impl Default for SMB1NegotiateMessage {
-    fn default() -> Self {
+impl SMB1NegotiateMessage {
+    /// Creates a negotiate request offering the SMB2 dialects of the multi-protocol negotiation.
+    ///
+    /// `NT LM 0.12` is always offered, so servers that only support SMBv1 respond with an
+    /// SMBv1 negotiate response, rather than dropping the connection.
+    ///
+    /// ## Arguments
+    /// * `smb2_002` - Whether to offer `SMB 2.002`, which servers select to negotiate SMB 2.0.2 right away.
+    /// * `smb2_wildcard` - Whether to offer `SMB 2.???`, which servers select to continue with an SMB2 negotiate request.
+    pub fn new(smb2_002: bool, smb2_wildcard: bool) -> Self {
+        let mut dialects = vec![Smb1Dialect::NT_LM_0_12];
+        if smb2_002 {
+            dialects.push(Smb1Dialect::SMB_2_002);
+        }
+        if smb2_wildcard {
+            dialects.push(Smb1Dialect::SMB_2_WILDCARD);
+        }
         Self {
             status: 0,
             flags: 0x18,
             flags2: 0xc853,
             security_features: [0; 8],
             byte_count: PosMarker::default(),
-            dialects: vec![
-                Smb1Dialect {
-                    name: binrw::NullString::from("NT LM 0.12"),
-                },
-                Smb1Dialect {
-                    name: binrw::NullString::from("SMB 2.002"),
-                },
-                Smb1Dialect {
-                    name: binrw::NullString::from("SMB 2.???"),
-                },
-            ],
+            dialects: dialects
+                .into_iter()
+                .map(|name| Smb1Dialect {
+                    name: binrw::NullString::from(name),
+                })
+                .collect(),
         }
     }
 }
 
+impl Default for SMB1NegotiateMessage {
+    fn default() -> Self {
+        Self::new(true, true)
+    }
+}
+
 #[derive(BinRead, BinWrite, Debug)]
 #[brw(magic(b"\x02"))]
 pub struct Smb1Dialect {
     name: binrw::NullString,
 }
 
+impl Smb1Dialect {
+    pub const NT_LM_0_12: &str = "NT LM 0.12";
+    pub const SMB_2_002: &str = "SMB 2.002";
+    pub const SMB_2_WILDCARD: &str = "SMB 2.???";
+}
+
+/// The SMBv1 negotiate response of servers that do not support SMB2.
+///
+/// Only the dialect selected by the server is parsed, see MS-CIFS 2.2.4.52.2.
+#[binrw::binread]
+#[derive(Debug, PartialEq, Eq)]
+#[br(little, magic(b"\xffSMB"))]
+pub struct SMB1NegotiateResponse {
+    #[br(temp, assert(_command == 0x72))]
+    _command: u8,
+    pub status: u32,
+    #[br(temp)]
+    _flags: u8,
+    #[br(temp)]
+    _flags2: u16,
+    #[br(temp)]
+    _header_rest: [u8; 20],
+    #[br(temp)]
+    word_count: u8,
+    /// The index of the selected dialect in the request, or `0xFFFF` if none of the dialects is supported.
+    #[br(if(word_count > 0, SMB1NegotiateResponse::NO_DIALECT))]
+    pub dialect_index: u16,
+}
+
+impl SMB1NegotiateResponse {
+    pub const NO_DIALECT: u16 = 0xffff;
+}
+
 impl TryInto<Vec<u8>> for SMB1NegotiateMessage {
     type Error = binrw::Error;
     fn try_into(self) -> Result<Vec<u8>, Self::Error> {
@@ -97,4 +149,24 @@ mod tests {
     smb_tests::test_binrw_write! {
         SMB1NegotiateMessage: SMB1NegotiateMessage::default() => "ff534d4272000000001853c8000000000000000000000000ffff010000000000002200024e54204c4d20302e31320002534d4220322e3030320002534d4220322e3f3f3f00"
     }
+
+    smb_tests::test_binrw_write! {
+        SMB1NegotiateMessage => wildcard_only: SMB1NegotiateMessage::new(false, true) => "ff534d4272000000001853c8000000000000000000000000ffff010000000000001700024e54204c4d20302e31320002534d4220322e3f3f3f00"
+    }
+
+    // An SMBv1-only server, selecting NT LM 0.12 (the rest of the response is not parsed).
+    smb_tests::test_binrw_read! {
+        SMB1NegotiateResponse: SMB1NegotiateResponse {
+            status: 0,
+            dialect_index: 0,
+        } => "ff534d4272000000009853c8000000000000000000000000fffffeff000000001100000332000100041100000000010000000000fde30080"
+    }
+
+    // No common dialect.
+    smb_tests::test_binrw_read! {
+        SMB1NegotiateResponse => no_dialect: SMB1NegotiateResponse {
+            status: 0,
+            dialect_index: SMB1NegotiateResponse::NO_DIALECT,
+        } => "ff534d4272000000009853c8000000000000000000000000fffffeff0000000001ffff0000"
+    }
 }
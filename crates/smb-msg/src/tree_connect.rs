@@ -231,7 +231,7 @@ pub struct TreeConnectResponse {
     pub maximal_access: u32,
 }
 
-#[derive(BitfieldSpecifier, Debug, Clone, Copy)]
+#[derive(BitfieldSpecifier, Debug, Clone, Copy, PartialEq, Eq)]
 #[bits = 4]
 pub enum ShareCacheMode {
     Manual,
@@ -1,5 +1,11 @@
 mod base;
+mod dtyp;
+mod samr;
 mod srvsvc;
+mod wkssvc;
 
 pub use base::*;
+pub use dtyp::*;
+pub use samr::*;
 pub use srvsvc::*;
+pub use wkssvc::*;
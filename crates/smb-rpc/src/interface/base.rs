@@ -2,6 +2,7 @@ use binrw::prelude::*;
 use maybe_async::*;
 
 use crate::pdu::DceRpcSyntaxId;
+use smb_dtyp::Guid;
 
 pub trait RpcInterface<T>
 where
@@ -44,3 +45,81 @@ pub trait BoundRpcConnection {
 
     async fn send_receive_raw(&mut self, opnum: u16, stub_input: &[u8]) -> crate::Result<Vec<u8>>;
 }
+
+/// Declares the input and output stubs of an RPC operation, and implements [`RpcCall`] for them.
+///
+/// The input stub is named `<Operation>In`, and the output stub (out params and return value) `<Operation>Out`:
+/// ```ignore
+/// rpc_call! {
+///     /// NetrWkstaGetInfo (MS-WKST 3.2.4.1)
+///     NetrWkstaGetInfo = 0x0 {
+///         in {
+///             server_name: NdrAlign<NdrPtr<NdrString<u16>>, 4>,
+///             level: NdrAlign<u32, 4>,
+///         }
+///         out {
+///             wksta_info: NdrAlign<WkstaInfoUnion>,
+///             return_value: NdrAlign<u32, 4>,
+///         }
+///     }
+/// }
+/// ```
+macro_rules! rpc_call {
+    (
+        $(#[$meta:meta])*
+        $name:ident = $opnum:literal {
+            in {
+                $($(#[$in_meta:meta])* $in_field:ident: $in_type:ty,)*
+            }
+            out {
+                $($(#[$out_meta:meta])* $out_field:ident: $out_type:ty,)*
+            }
+        }
+    ) => {
+        pastey::paste! {
+            $(#[$meta])*
+            ///
+            /// Input arguments.
+            #[binrw::binrw]
+            #[derive(Debug, PartialEq, Eq)]
+            struct [<$name In>] {
+                $($(#[$in_meta])* $in_field: $in_type,)*
+            }
+
+            $(#[$meta])*
+            ///
+            /// Return value and out params.
+            #[binrw::binrw]
+            #[derive(Debug, PartialEq, Eq)]
+            struct [<$name Out>] {
+                $($(#[$out_meta])* $out_field: $out_type,)*
+            }
+
+            impl $crate::interface::RpcCall for [<$name In>] {
+                const OPNUM: u16 = $opnum;
+
+                type ResponseType = [<$name Out>];
+            }
+        }
+    };
+}
+
+pub(crate) use rpc_call;
+
+/// An RPC context handle, such as a SAMR policy handle.
+///
+/// Servers return context handles to refer to objects opened by a previous call on the same binding.
+/// On the wire, this is the 20-byte `ndr_context_handle` of DCE/RPC.
+#[binrw::binrw]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ContextHandle {
+    pub attributes: u32,
+    pub uuid: Guid,
+}
+
+impl ContextHandle {
+    /// Returns whether this is a null handle, which is returned by the server once a handle is closed.
+    pub fn is_null(&self) -> bool {
+        *self == Self::default()
+    }
+}
@@ -0,0 +1,114 @@
+//! Common RPC data types (MS-DTYP), shared by multiple interfaces.
+
+use std::fmt::Display;
+
+use crate::ndr64::*;
+use binrw::prelude::*;
+use smb_dtyp::SID;
+
+/// `RPC_UNICODE_STRING` (MS-DTYP 2.3.10)
+///
+/// A counted UTF-16 string, which is not null-terminated.
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[bw(import(stage: NdrPtrWriteStage))]
+#[br(import(prev: Option<&Self>))]
+pub struct RpcUnicodeString {
+    // The structure is aligned by its pointer, but is only written once, with the reference ID.
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[bw(align_before = if stage == NdrPtrWriteStage::ArraySupportWriteRefId { NDR64_ALIGNMENT } else { 1 })]
+    #[br(align_before = if prev.is_none() { NDR64_ALIGNMENT } else { 1 })]
+    #[br(args(prev.map(|x| &**x.length)))]
+    length: NdrArrayStructureElement<u16, 2>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.maximum_length)))]
+    maximum_length: NdrArrayStructureElement<u16, 2>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.buffer), NdrPtrReadMode::WithArraySupport, ()))]
+    buffer: NdrPtr<NdrString<u16>>,
+}
+
+impl RpcUnicodeString {
+    /// Returns the length of the string, in bytes.
+    pub fn len(&self) -> usize {
+        **self.length as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl From<&str> for RpcUnicodeString {
+    fn from(value: &str) -> Self {
+        let data: Vec<u16> = value.encode_utf16().collect();
+        let length = (data.len() * 2) as u16;
+        Self {
+            length: length.into(),
+            maximum_length: length.into(),
+            buffer: NdrString { data: data.into() }.into(),
+        }
+    }
+}
+
+impl Display for RpcUnicodeString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.buffer.as_ref() {
+            Some(buffer) => {
+                // The buffer may be larger than the string.
+                let length = (self.len() / 2).min(buffer.data.len());
+                write!(f, "{}", String::from_utf16_lossy(&buffer.data[..length]))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// `RPC_SID` (MS-DTYP 2.4.2.3)
+///
+/// The conformant form of a [`SID`], as it is encoded in RPC calls.
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RpcSid {
+    #[bw(calc = (sid.sub_authority.len() as u64).into())]
+    _max_count: NdrAlign<u64>,
+    #[br(assert(sid.sub_authority.len() as u64 == *_max_count))]
+    pub sid: SID,
+}
+
+impl NdrAligned for RpcSid {}
+
+impl From<SID> for RpcSid {
+    fn from(sid: SID) -> Self {
+        Self { sid }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use smb_tests::*;
+
+    use super::*;
+
+    #[binrw::binrw]
+    #[derive(Debug, PartialEq, Eq)]
+    struct TestUnicodeString {
+        unalign: u32,
+        string: NdrStructure<RpcUnicodeString>,
+    }
+
+    test_binrw! {
+        struct TestUnicodeString {
+            unalign: 0x1,
+            string: RpcUnicodeString::from("Builtin").into(),
+        } => "01000000000000000e000e000000000000000200000000000700000000000000000000000000000007000000000000004200750069006c00740069006e00"
+    }
+
+    test_binrw! {
+        struct RpcSid {
+            sid: SID::from_str("S-1-5-21-1-2-3").unwrap(),
+        } => "0400000000000000010400000000000515000000010000000200000003000000"
+    }
+}
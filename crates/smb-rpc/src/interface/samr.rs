@@ -0,0 +1,465 @@
+//! MS-SAMR: Security Account Manager (SAM) Remote Protocol (Client-to-Server)
+//!
+//! Only the calls required to enumerate the users of a domain are implemented.
+
+use crate::{interface::*, pdu::DceRpcSyntaxId};
+use smb_dtyp::{SID, make_guid};
+
+use crate::ndr64::*;
+use binrw::prelude::*;
+use maybe_async::maybe_async;
+
+/// Server access right: connect to the server.
+pub const SAM_SERVER_CONNECT: u32 = 0x00000001;
+/// Server access right: enumerate the domains of the server.
+pub const SAM_SERVER_ENUMERATE_DOMAINS: u32 = 0x00000010;
+/// Server access right: look up domains by name.
+pub const SAM_SERVER_LOOKUP_DOMAIN: u32 = 0x00000020;
+/// Domain access right: enumerate the accounts of the domain.
+pub const DOMAIN_LIST_ACCOUNTS: u32 = 0x00000004;
+/// Domain access right: look up accounts by name or RID.
+pub const DOMAIN_LOOKUP: u32 = 0x00000200;
+/// `UserAccountControl` filter: normal user accounts.
+pub const USER_NORMAL_ACCOUNT: u32 = 0x00000010;
+
+const STATUS_SUCCESS: u32 = 0x00000000;
+const STATUS_MORE_ENTRIES: u32 = 0x00000105;
+
+/// The name of the built-in domain, which exists on every server.
+pub const BUILTIN_DOMAIN_NAME: &str = "Builtin";
+
+/// `SAMPR_RID_ENUMERATION` (MS-SAMR 2.2.3.9)
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[bw(import(stage: NdrPtrWriteStage))]
+#[br(import(prev: Option<&Self>))]
+pub struct SamprRidEnumeration {
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.relative_id)))]
+    pub relative_id: NdrArrayStructureElement<u32, 4>,
+    #[bw(args(stage))]
+    #[br(args(prev.map(|x| &x.name)))]
+    pub name: RpcUnicodeString,
+}
+
+/// `SAMPR_ENUMERATION_BUFFER` (MS-SAMR 2.2.3.10)
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq)]
+struct SamprEnumerationBuffer {
+    #[bw(calc = (buffer.as_ref().map_or(0, |x| x.len() as u32)).into())]
+    entries_read: NdrAlign<u32>,
+    #[br(args(None, NdrPtrReadMode::NoArraySupport, (*entries_read as u64,)))]
+    buffer: NdrPtr<NdrArray<SamprRidEnumeration>>,
+}
+
+impl SamprEnumerationBuffer {
+    fn into_entries(self) -> Vec<SamprRidEnumeration> {
+        self.buffer
+            .into_inner()
+            .map(|array| array.data.into_iter().map(|entry| entry.value).collect())
+            .unwrap_or_default()
+    }
+}
+
+rpc_call! {
+    /// SamrConnect (MS-SAMR 3.1.5.1.4)
+    SamrConnect = 0x0 {
+        in {
+            server_name: NdrAlign<NdrPtr<u16>, 4>,
+            desired_access: NdrAlign<u32, 4>,
+        }
+        out {
+            server_handle: NdrAlign<ContextHandle, 4>,
+            return_value: NdrAlign<u32, 4>,
+        }
+    }
+}
+
+rpc_call! {
+    /// SamrCloseHandle (MS-SAMR 3.1.5.13.1)
+    SamrCloseHandle = 0x1 {
+        in {
+            sam_handle: NdrAlign<ContextHandle, 4>,
+        }
+        out {
+            sam_handle: NdrAlign<ContextHandle, 4>,
+            return_value: NdrAlign<u32, 4>,
+        }
+    }
+}
+
+rpc_call! {
+    /// SamrLookupDomainInSamServer (MS-SAMR 3.1.5.11.1)
+    SamrLookupDomainInSamServer = 0x5 {
+        in {
+            server_handle: NdrAlign<ContextHandle, 4>,
+            name: NdrStructure<RpcUnicodeString>,
+        }
+        out {
+            domain_id: NdrAlign<NdrPtr<RpcSid>>,
+            return_value: NdrAlign<u32, 4>,
+        }
+    }
+}
+
+rpc_call! {
+    /// SamrEnumerateDomainsInSamServer (MS-SAMR 3.1.5.2.1)
+    SamrEnumerateDomainsInSamServer = 0x6 {
+        in {
+            server_handle: NdrAlign<ContextHandle, 4>,
+            enumeration_context: NdrAlign<u32, 4>,
+            prefered_maximum_length: NdrAlign<u32, 4>,
+        }
+        out {
+            enumeration_context: NdrAlign<u32, 4>,
+            buffer: NdrAlign<NdrPtr<SamprEnumerationBuffer>>,
+            count_returned: NdrAlign<u32, 4>,
+            return_value: NdrAlign<u32, 4>,
+        }
+    }
+}
+
+rpc_call! {
+    /// SamrOpenDomain (MS-SAMR 3.1.5.1.5)
+    SamrOpenDomain = 0x7 {
+        in {
+            server_handle: NdrAlign<ContextHandle, 4>,
+            desired_access: NdrAlign<u32, 4>,
+            domain_id: RpcSid,
+        }
+        out {
+            domain_handle: NdrAlign<ContextHandle, 4>,
+            return_value: NdrAlign<u32, 4>,
+        }
+    }
+}
+
+rpc_call! {
+    /// SamrEnumerateUsersInDomain (MS-SAMR 3.1.5.2.5)
+    SamrEnumerateUsersInDomain = 0xd {
+        in {
+            domain_handle: NdrAlign<ContextHandle, 4>,
+            enumeration_context: NdrAlign<u32, 4>,
+            user_account_control: NdrAlign<u32, 4>,
+            prefered_maximum_length: NdrAlign<u32, 4>,
+        }
+        out {
+            enumeration_context: NdrAlign<u32, 4>,
+            buffer: NdrAlign<NdrPtr<SamprEnumerationBuffer>>,
+            count_returned: NdrAlign<u32, 4>,
+            return_value: NdrAlign<u32, 4>,
+        }
+    }
+}
+
+/// A user account of a domain, returned by [`Samr::list_domain_users`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DomainUser {
+    /// The name of the domain of the user.
+    pub domain: String,
+    /// The relative ID (RID) of the user in the domain.
+    pub rid: u32,
+    pub name: String,
+}
+
+pub struct Samr<T>
+where
+    T: BoundRpcConnection,
+{
+    bound_pipe: T,
+}
+
+impl<T> Samr<T>
+where
+    T: BoundRpcConnection,
+{
+    /// Connects to the SAM server, and returns the server handle.
+    #[maybe_async]
+    pub async fn samr_connect(&mut self, desired_access: u32) -> crate::Result<ContextHandle> {
+        let input_struct = SamrConnectIn {
+            server_name: NdrPtr::<u16>::from(None).into(),
+            desired_access: desired_access.into(),
+        };
+        let result = self.bound_pipe.send_receive(input_struct).await?;
+        Self::check_status(*result.return_value)?;
+        Ok(*result.server_handle)
+    }
+
+    /// Closes a handle returned by a previous call.
+    #[maybe_async]
+    pub async fn samr_close_handle(&mut self, handle: ContextHandle) -> crate::Result<()> {
+        let input_struct = SamrCloseHandleIn {
+            sam_handle: handle.into(),
+        };
+        let result = self.bound_pipe.send_receive(input_struct).await?;
+        Self::check_status(*result.return_value)
+    }
+
+    /// Returns the names of the domains of the server.
+    #[maybe_async]
+    pub async fn samr_enumerate_domains_in_sam_server(
+        &mut self,
+        server_handle: &ContextHandle,
+    ) -> crate::Result<Vec<SamprRidEnumeration>> {
+        let mut entries = vec![];
+        let mut enumeration_context = 0;
+        loop {
+            let input_struct = SamrEnumerateDomainsInSamServerIn {
+                server_handle: (*server_handle).into(),
+                enumeration_context: enumeration_context.into(),
+                prefered_maximum_length: u32::MAX.into(),
+            };
+            let result = self.bound_pipe.send_receive(input_struct).await?;
+            let status = *result.return_value;
+            if status != STATUS_MORE_ENTRIES {
+                Self::check_status(status)?;
+            }
+            if let Some(buffer) = result.buffer.value.into_inner() {
+                entries.extend(buffer.into_entries());
+            }
+            if status != STATUS_MORE_ENTRIES {
+                return Ok(entries);
+            }
+            enumeration_context = *result.enumeration_context;
+        }
+    }
+
+    /// Returns the SID of the domain with the specified name.
+    #[maybe_async]
+    pub async fn samr_lookup_domain_in_sam_server(
+        &mut self,
+        server_handle: &ContextHandle,
+        name: &str,
+    ) -> crate::Result<SID> {
+        let input_struct = SamrLookupDomainInSamServerIn {
+            server_handle: (*server_handle).into(),
+            name: RpcUnicodeString::from(name).into(),
+        };
+        let result = self.bound_pipe.send_receive(input_struct).await?;
+        Self::check_status(*result.return_value)?;
+        result
+            .domain_id
+            .value
+            .into_inner()
+            .map(|domain_id| domain_id.sid)
+            .ok_or(crate::SmbRpcError::InvalidResponseData(
+                "SamrLookupDomainInSamServer returned no data",
+            ))
+    }
+
+    /// Opens the domain with the specified SID, and returns the domain handle.
+    #[maybe_async]
+    pub async fn samr_open_domain(
+        &mut self,
+        server_handle: &ContextHandle,
+        desired_access: u32,
+        domain_id: &SID,
+    ) -> crate::Result<ContextHandle> {
+        let input_struct = SamrOpenDomainIn {
+            server_handle: (*server_handle).into(),
+            desired_access: desired_access.into(),
+            domain_id: domain_id.clone().into(),
+        };
+        let result = self.bound_pipe.send_receive(input_struct).await?;
+        Self::check_status(*result.return_value)?;
+        Ok(*result.domain_handle)
+    }
+
+    /// Returns the RIDs and names of the users of the domain,
+    /// whose `UserAccountControl` matches the filter (such as [`USER_NORMAL_ACCOUNT`]); 0 returns all users.
+    #[maybe_async]
+    pub async fn samr_enumerate_users_in_domain(
+        &mut self,
+        domain_handle: &ContextHandle,
+        user_account_control: u32,
+    ) -> crate::Result<Vec<SamprRidEnumeration>> {
+        let mut entries = vec![];
+        let mut enumeration_context = 0;
+        loop {
+            let input_struct = SamrEnumerateUsersInDomainIn {
+                domain_handle: (*domain_handle).into(),
+                enumeration_context: enumeration_context.into(),
+                user_account_control: user_account_control.into(),
+                prefered_maximum_length: u32::MAX.into(),
+            };
+            let result = self.bound_pipe.send_receive(input_struct).await?;
+            let status = *result.return_value;
+            if status != STATUS_MORE_ENTRIES {
+                Self::check_status(status)?;
+            }
+            if let Some(buffer) = result.buffer.value.into_inner() {
+                entries.extend(buffer.into_entries());
+            }
+            if status != STATUS_MORE_ENTRIES {
+                return Ok(entries);
+            }
+            enumeration_context = *result.enumeration_context;
+        }
+    }
+
+    /// Returns the normal user accounts of all the domains of the server, except for the built-in domain.
+    ///
+    /// Connects to the server, and looks up, opens and enumerates each domain.
+    #[maybe_async]
+    pub async fn list_domain_users(&mut self) -> crate::Result<Vec<DomainUser>> {
+        let server_handle = self
+            .samr_connect(
+                SAM_SERVER_CONNECT | SAM_SERVER_ENUMERATE_DOMAINS | SAM_SERVER_LOOKUP_DOMAIN,
+            )
+            .await?;
+
+        let mut users = vec![];
+        let domains = self
+            .samr_enumerate_domains_in_sam_server(&server_handle)
+            .await?;
+        for domain in domains {
+            let domain = domain.name.to_string();
+            if domain.eq_ignore_ascii_case(BUILTIN_DOMAIN_NAME) {
+                continue;
+            }
+            let domain_id = self
+                .samr_lookup_domain_in_sam_server(&server_handle, &domain)
+                .await?;
+            let domain_handle = self
+                .samr_open_domain(
+                    &server_handle,
+                    DOMAIN_LIST_ACCOUNTS | DOMAIN_LOOKUP,
+                    &domain_id,
+                )
+                .await?;
+            let entries = self
+                .samr_enumerate_users_in_domain(&domain_handle, USER_NORMAL_ACCOUNT)
+                .await?;
+            self.samr_close_handle(domain_handle).await?;
+
+            users.extend(entries.into_iter().map(|entry| DomainUser {
+                domain: domain.clone(),
+                rid: **entry.relative_id,
+                name: entry.name.to_string(),
+            }));
+        }
+
+        self.samr_close_handle(server_handle).await?;
+        Ok(users)
+    }
+
+    fn check_status(status: u32) -> crate::Result<()> {
+        match status {
+            STATUS_SUCCESS => Ok(()),
+            status => Err(crate::SmbRpcError::CallFailed(status)),
+        }
+    }
+}
+
+impl<T> super::base::RpcInterface<T> for Samr<T>
+where
+    T: BoundRpcConnection,
+{
+    const SYNTAX_ID: DceRpcSyntaxId = DceRpcSyntaxId {
+        uuid: make_guid!("12345778-1234-abcd-ef00-0123456789ac"),
+        version: 1,
+    };
+
+    fn new(bound_pipe: T) -> Self {
+        Samr { bound_pipe }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use smb_tests::*;
+
+    use super::*;
+
+    const SERVER_HANDLE: ContextHandle = ContextHandle {
+        attributes: 0,
+        uuid: make_guid!("d2b3cf68-3c14-4e8d-9d1a-0fe8a4e6e3a1"),
+    };
+
+    smb_tests::test_binrw_write! {
+        struct SamrConnectIn {
+            server_name: NdrPtr::<u16>::from(None).into(),
+            desired_access: 0x31.into(),
+        } => "000000000000000031000000"
+    }
+
+    test_binrw! {
+        struct SamrConnectOut {
+            server_handle: SERVER_HANDLE.into(),
+            return_value: 0.into(),
+        } => "0000000068cfb3d2143c8d4e9d1a0fe8a4e6e3a100000000"
+    }
+
+    test_binrw! {
+        struct SamrEnumerateDomainsInSamServerOut {
+            enumeration_context: 2.into(),
+            buffer: NdrPtr::from(SamprEnumerationBuffer {
+                buffer: NdrPtr::from(NdrArray::from(vec![
+                    SamprRidEnumeration {
+                        relative_id: 0.into(),
+                        name: RpcUnicodeString::from("CONTOSO"),
+                    },
+                    SamprRidEnumeration {
+                        relative_id: 1.into(),
+                        name: RpcUnicodeString::from("Builtin"),
+                    },
+                ])),
+            })
+            .into(),
+            count_returned: 2.into(),
+            return_value: 0.into(),
+        } => "02000000000000000000020000000000020000000000000000000200000000000200000000000000
+            00000000000000000e000e00000000000000020000000000
+            01000000000000000e000e00000000000000020000000000
+            07000000000000000000000000000000070000000000000043004f004e0054004f0053004f00
+            0000
+            070000000000000000000000000000000700000000000000420075006900
+            6c00740069006e00
+            0000
+            0200000000000000"
+    }
+
+    test_binrw! {
+        struct SamrLookupDomainInSamServerIn {
+            server_handle: SERVER_HANDLE.into(),
+            name: RpcUnicodeString::from("CONTOSO").into(),
+        } => "0000000068cfb3d2143c8d4e9d1a0fe8a4e6e3a100000000
+            0e000e00000000000000020000000000
+            0700000000000000000000000000000007000000000000004300
+            4f004e0054004f0053004f00"
+    }
+
+    test_binrw! {
+        struct SamrLookupDomainInSamServerOut {
+            domain_id: NdrPtr::from(RpcSid::from(SID::from_str("S-1-5-21-1-2-3").unwrap())).into(),
+            return_value: 0.into(),
+        } => "0000020000000000040000000000000001040000000000051500000001000000020000000300000000000000"
+    }
+
+    test_binrw! {
+        struct SamrOpenDomainIn {
+            server_handle: SERVER_HANDLE.into(),
+            desired_access: (DOMAIN_LIST_ACCOUNTS | DOMAIN_LOOKUP).into(),
+            domain_id: RpcSid::from(SID::from_str("S-1-5-21-1-2-3").unwrap()),
+        } => "0000000068cfb3d2143c8d4e9d1a0fe8a4e6e3a104020000
+            0400000000000000010400000000000515000000010000000200000003000000"
+    }
+
+    test_binrw! {
+        struct SamrEnumerateUsersInDomainIn {
+            domain_handle: SERVER_HANDLE.into(),
+            enumeration_context: 0.into(),
+            user_account_control: USER_NORMAL_ACCOUNT.into(),
+            prefered_maximum_length: u32::MAX.into(),
+        } => "0000000068cfb3d2143c8d4e9d1a0fe8a4e6e3a10000000010000000ffffffff"
+    }
+
+    test_binrw! {
+        struct SamrCloseHandleOut {
+            sam_handle: ContextHandle::default().into(),
+            return_value: 0.into(),
+        } => "000000000000000000000000000000000000000000000000"
+    }
+}
@@ -57,7 +57,7 @@ impl ShareEnumUnion {
 #[derive(Debug, PartialEq, Eq)]
 struct ShareInfoContainer<T>
 where
-    T: ShareInfo,
+    T: ShareInfoEntry,
 {
     #[bw(calc = (buffer.as_ref().map_or(0, |x| x.len() as u32)).into())]
     entries_read: NdrAlign<u32>,
@@ -65,7 +65,7 @@ where
     buffer: NdrPtr<NdrArray<T>>,
 }
 
-trait ShareInfo:
+trait ShareInfoEntry:
     for<'a> BinRead<Args<'a> = (Option<&'a Self>,)>
     + for<'a> BinWrite<Args<'a> = (NdrPtrWriteStage,)>
     + Clone
@@ -103,8 +103,115 @@ pub struct ShareInfo0 {
     netname: NdrPtr<NdrString<u16>>,
 }
 
-impl ShareInfo for ShareInfo0 {}
-impl ShareInfo for ShareInfo1 {}
+impl ShareInfoEntry for ShareInfo0 {}
+impl ShareInfoEntry for ShareInfo1 {}
+
+/// `SHARE_INFO_2` (MS-SRVS 2.2.4.24)
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[bw(import(stage: NdrPtrWriteStage))]
+#[br(import(prev: Option<&Self>))]
+pub struct ShareInfo2 {
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.netname), NdrPtrReadMode::WithArraySupport, ()))]
+    pub netname: NdrPtr<NdrString<u16>>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.share_type)))]
+    pub share_type: NdrArrayStructureElement<ShareType, 4>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.remark), NdrPtrReadMode::WithArraySupport, ()))]
+    pub remark: NdrPtr<NdrString<u16>>,
+    /// Share-level permissions, only used by servers with share-level security.
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.permissions)))]
+    pub permissions: NdrArrayStructureElement<u32, 4>,
+    /// The maximum number of concurrent connections to the share, or `u32::MAX` for unlimited.
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.max_uses)))]
+    pub max_uses: NdrArrayStructureElement<u32, 4>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.current_uses)))]
+    pub current_uses: NdrArrayStructureElement<u32, 4>,
+    /// The local path of the share on the server.
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.path), NdrPtrReadMode::WithArraySupport, ()))]
+    pub path: NdrPtr<NdrString<u16>>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.passwd), NdrPtrReadMode::WithArraySupport, ()))]
+    pub passwd: NdrPtr<NdrString<u16>>,
+}
+
+/// `SHARE_INFO_502_I` (MS-SRVS 2.2.4.26)
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[bw(import(stage: NdrPtrWriteStage))]
+#[br(import(prev: Option<&Self>))]
+pub struct ShareInfo502 {
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.netname), NdrPtrReadMode::WithArraySupport, ()))]
+    pub netname: NdrPtr<NdrString<u16>>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.share_type)))]
+    pub share_type: NdrArrayStructureElement<ShareType, 4>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.remark), NdrPtrReadMode::WithArraySupport, ()))]
+    pub remark: NdrPtr<NdrString<u16>>,
+    /// Share-level permissions, only used by servers with share-level security.
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.permissions)))]
+    pub permissions: NdrArrayStructureElement<u32, 4>,
+    /// The maximum number of concurrent connections to the share, or `u32::MAX` for unlimited.
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.max_uses)))]
+    pub max_uses: NdrArrayStructureElement<u32, 4>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.current_uses)))]
+    pub current_uses: NdrArrayStructureElement<u32, 4>,
+    /// The local path of the share on the server.
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.path), NdrPtrReadMode::WithArraySupport, ()))]
+    pub path: NdrPtr<NdrString<u16>>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.passwd), NdrPtrReadMode::WithArraySupport, ()))]
+    pub passwd: NdrPtr<NdrString<u16>>,
+    /// The length of [`security_descriptor`][Self::security_descriptor], in bytes.
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.reserved)))]
+    pub reserved: NdrArrayStructureElement<u32, 4>,
+    /// The self-relative security descriptor of the share.
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.security_descriptor), NdrPtrReadMode::WithArraySupport, ()))]
+    pub security_descriptor: NdrPtr<NdrByteArray>,
+}
+
+/// Information about a share, returned by [`SrvSvc::netr_share_get_info`].
+///
+/// The `SHARE_INFO` union of MS-SRVS.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ShareInfo {
+    Info2(ShareInfo2),
+    Info502(ShareInfo502),
+}
+
+impl ShareInfo {
+    /// Returns the level of the share info.
+    pub fn level(&self) -> ShareInfoLevel {
+        match self {
+            ShareInfo::Info2(_) => ShareInfoLevel::Info2,
+            ShareInfo::Info502(_) => ShareInfoLevel::Info502,
+        }
+    }
+}
+
+/// The `SHARE_INFO` union, as returned by NetrShareGetInfo (MS-SRVS 3.1.4.10)
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq)]
+enum ShareInfoUnion {
+    #[brw(magic = 2u64)]
+    Info2(NdrPtr<NdrStructure<ShareInfo2>>),
+    #[brw(magic = 502u64)]
+    Info502(NdrPtr<NdrStructure<ShareInfo502>>),
+}
 
 #[derive(BitfieldSpecifier, Debug, Clone, Copy, PartialEq, Eq)]
 #[bits = 2]
@@ -142,32 +249,239 @@ impl ShareType {
     }
 }
 
-// FYI: RPC top-level stub data is aligned to min(8, arg_size0, arg_size1, ...) bytes.
-// DCE/RPC Chap. 12.3: RPC PDU Encodings/Alignment.
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[brw(repr(u32))]
+pub enum ServerInfoLevel {
+    Info100 = 100,
+    Info101 = 101,
+    Info102 = 102,
+}
 
-/// Input arguments for [NetrShareEnum](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-srvs/c4a98e7b-d416-439c-97bd-4d9f52f8ba52>)
+/// The operating system of a server.
 #[binrw::binrw]
-#[derive(Debug, PartialEq, Eq)]
-struct NetrShareEnumIn {
-    server_name: NdrAlign<NdrPtr<NdrString<u16>>, 4>,
-    info_struct: NdrAlign<ShareEnumStruct, 4>,
-    prefered_maximum_length: NdrAlign<u32, 4>,
-    resume_handle: NdrAlign<NdrPtr<u32>, 4>,
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[brw(repr(u32))]
+pub enum PlatformId {
+    Dos = 300,
+    Os2 = 400,
+    Nt = 500,
+    Osf = 600,
+    Vms = 700,
+}
+
+/// Server types (`SV_TYPE_*`)
+#[bitfield]
+#[derive(BinWrite, BinRead, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[bw(map = |&x| Self::into_bytes(x))]
+#[br(map = Self::from_bytes)]
+pub struct ServerType {
+    pub workstation: bool,
+    pub server: bool,
+    pub sql_server: bool,
+    pub domain_ctrl: bool,
+    pub domain_bakctrl: bool,
+    pub time_source: bool,
+    pub afp: bool,
+    pub novell: bool,
+
+    pub domain_member: bool,
+    pub printq_server: bool,
+    pub dialin_server: bool,
+    pub server_unix: bool,
+    pub nt: bool,
+    pub wfw: bool,
+    pub server_mfpn: bool,
+    pub server_nt: bool,
+
+    pub potential_browser: bool,
+    pub backup_browser: bool,
+    pub master_browser: bool,
+    pub domain_master: bool,
+    pub server_osf: bool,
+    pub server_vms: bool,
+    pub windows: bool,
+    pub dfs: bool,
+
+    pub cluster_nt: bool,
+    pub terminal_server: bool,
+    pub cluster_vs_nt: bool,
+    #[skip]
+    __: B1,
+    pub dce: bool,
+    pub alternate_xport: bool,
+    pub local_list_only: bool,
+    pub domain_enum: bool,
+}
+
+/// `SERVER_INFO_100` (MS-SRVS 2.2.4.40)
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[bw(import(stage: NdrPtrWriteStage))]
+#[br(import(prev: Option<&Self>))]
+pub struct ServerInfo100 {
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.platform_id)))]
+    pub platform_id: NdrArrayStructureElement<PlatformId, 4>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.name), NdrPtrReadMode::WithArraySupport, ()))]
+    pub name: NdrPtr<NdrString<u16>>,
 }
 
-/// Return value and out params of [NetrShareEnum](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-srvs/c4a98e7b-d416-439c-97bd-4d9f52f8ba52>)
+/// `SERVER_INFO_101` (MS-SRVS 2.2.4.41)
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[bw(import(stage: NdrPtrWriteStage))]
+#[br(import(prev: Option<&Self>))]
+pub struct ServerInfo101 {
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.platform_id)))]
+    pub platform_id: NdrArrayStructureElement<PlatformId, 4>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.name), NdrPtrReadMode::WithArraySupport, ()))]
+    pub name: NdrPtr<NdrString<u16>>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.version_major)))]
+    pub version_major: NdrArrayStructureElement<u32, 4>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.version_minor)))]
+    pub version_minor: NdrArrayStructureElement<u32, 4>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.server_type)))]
+    pub server_type: NdrArrayStructureElement<ServerType, 4>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.comment), NdrPtrReadMode::WithArraySupport, ()))]
+    pub comment: NdrPtr<NdrString<u16>>,
+}
+
+/// `SERVER_INFO_102` (MS-SRVS 2.2.4.42)
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[bw(import(stage: NdrPtrWriteStage))]
+#[br(import(prev: Option<&Self>))]
+pub struct ServerInfo102 {
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.platform_id)))]
+    pub platform_id: NdrArrayStructureElement<PlatformId, 4>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.name), NdrPtrReadMode::WithArraySupport, ()))]
+    pub name: NdrPtr<NdrString<u16>>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.version_major)))]
+    pub version_major: NdrArrayStructureElement<u32, 4>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.version_minor)))]
+    pub version_minor: NdrArrayStructureElement<u32, 4>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.server_type)))]
+    pub server_type: NdrArrayStructureElement<ServerType, 4>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.comment), NdrPtrReadMode::WithArraySupport, ()))]
+    pub comment: NdrPtr<NdrString<u16>>,
+    /// The number of users that may log in to the server.
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.users)))]
+    pub users: NdrArrayStructureElement<u32, 4>,
+    /// The idle time, in minutes, after which sessions are disconnected.
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.disc)))]
+    pub disc: NdrArrayStructureElement<i32, 4>,
+    /// Non-zero if the server is hidden from browse lists.
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.hidden)))]
+    pub hidden: NdrArrayStructureElement<u32, 4>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.announce)))]
+    pub announce: NdrArrayStructureElement<u32, 4>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.anndelta)))]
+    pub anndelta: NdrArrayStructureElement<u32, 4>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.licenses)))]
+    pub licenses: NdrArrayStructureElement<u32, 4>,
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.userpath), NdrPtrReadMode::WithArraySupport, ()))]
+    pub userpath: NdrPtr<NdrString<u16>>,
+}
+
+/// Information about a server, returned by [`SrvSvc::netr_server_get_info`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ServerInfo {
+    Info100(ServerInfo100),
+    Info101(ServerInfo101),
+    Info102(ServerInfo102),
+}
+
+impl ServerInfo {
+    /// Returns the level of the server info.
+    pub fn level(&self) -> ServerInfoLevel {
+        match self {
+            ServerInfo::Info100(_) => ServerInfoLevel::Info100,
+            ServerInfo::Info101(_) => ServerInfoLevel::Info101,
+            ServerInfo::Info102(_) => ServerInfoLevel::Info102,
+        }
+    }
+}
+
+/// The `SERVER_INFO` union, as returned by NetrServerGetInfo (MS-SRVS 3.1.4.17)
 #[binrw::binrw]
 #[derive(Debug, PartialEq, Eq)]
-struct NetrShareEnumOut {
-    info_struct: NdrAlign<ShareEnumStruct, 4>,
-    total_entries: NdrAlign<u32, 4>,
-    resume_handle: NdrAlign<NdrPtr<u32>, 4>,
+enum ServerInfoUnion {
+    #[brw(magic = 100u64)]
+    Info100(NdrPtr<NdrStructure<ServerInfo100>>),
+    #[brw(magic = 101u64)]
+    Info101(NdrPtr<NdrStructure<ServerInfo101>>),
+    #[brw(magic = 102u64)]
+    Info102(NdrPtr<NdrStructure<ServerInfo102>>),
+}
+
+// FYI: RPC top-level stub data is aligned to min(8, arg_size0, arg_size1, ...) bytes.
+// DCE/RPC Chap. 12.3: RPC PDU Encodings/Alignment.
+
+rpc_call! {
+    /// [NetrShareEnum](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-srvs/c4a98e7b-d416-439c-97bd-4d9f52f8ba52>)
+    NetrShareEnum = 0xf {
+        in {
+            server_name: NdrAlign<NdrPtr<NdrString<u16>>, 4>,
+            info_struct: NdrAlign<ShareEnumStruct, 4>,
+            prefered_maximum_length: NdrAlign<u32, 4>,
+            resume_handle: NdrAlign<NdrPtr<u32>, 4>,
+        }
+        out {
+            info_struct: NdrAlign<ShareEnumStruct, 4>,
+            total_entries: NdrAlign<u32, 4>,
+            resume_handle: NdrAlign<NdrPtr<u32>, 4>,
+        }
+    }
 }
 
-impl RpcCall for NetrShareEnumIn {
-    const OPNUM: u16 = 0xf;
+rpc_call! {
+    /// NetrShareGetInfo (MS-SRVS 3.1.4.10)
+    NetrShareGetInfo = 0x10 {
+        in {
+            server_name: NdrAlign<NdrPtr<NdrString<u16>>, 4>,
+            net_name: NdrAlign<NdrString<u16>, 4>,
+            level: NdrAlign<ShareInfoLevel, 4>,
+        }
+        out {
+            info_struct: NdrAlign<ShareInfoUnion>,
+            return_value: NdrAlign<u32, 4>,
+        }
+    }
+}
 
-    type ResponseType = NetrShareEnumOut;
+rpc_call! {
+    /// NetrServerGetInfo (MS-SRVS 3.1.4.17)
+    NetrServerGetInfo = 0x15 {
+        in {
+            server_name: NdrAlign<NdrPtr<NdrString<u16>>, 4>,
+            level: NdrAlign<ServerInfoLevel, 4>,
+        }
+        out {
+            info_struct: NdrAlign<ServerInfoUnion>,
+            return_value: NdrAlign<u32, 4>,
+        }
+    }
 }
 
 pub struct SrvSvc<T>
@@ -221,6 +535,75 @@ where
         }
         Ok(result)
     }
+
+    /// Returns information about a share, at the specified level.
+    ///
+    /// Only [`ShareInfoLevel::Info2`] and [`ShareInfoLevel::Info502`] are supported.
+    /// Both levels usually require administrative rights on the server.
+    #[maybe_async]
+    pub async fn netr_share_get_info(
+        &mut self,
+        server_name: &str,
+        share_name: &str,
+        level: ShareInfoLevel,
+    ) -> crate::Result<ShareInfo> {
+        if !matches!(level, ShareInfoLevel::Info2 | ShareInfoLevel::Info502) {
+            return Err(crate::SmbRpcError::UnsupportedInfoLevel(level as u32));
+        }
+        let input_struct = NetrShareGetInfoIn {
+            server_name: NdrPtr::from(server_name.parse::<NdrString<u16>>().unwrap()).into(),
+            net_name: share_name.parse::<NdrString<u16>>().unwrap().into(),
+            level: level.into(),
+        };
+        let result = self.bound_pipe.send_receive(input_struct).await?;
+        if *result.return_value != 0 {
+            return Err(crate::SmbRpcError::CallFailed(*result.return_value));
+        }
+        let info = match result.info_struct.value {
+            ShareInfoUnion::Info2(ptr) => ptr.into_inner().map(|x| ShareInfo::Info2(x.value)),
+            ShareInfoUnion::Info502(ptr) => ptr.into_inner().map(|x| ShareInfo::Info502(x.value)),
+        };
+        match info {
+            Some(info) if info.level() == level => Ok(info),
+            Some(_) => Err(crate::SmbRpcError::InvalidResponseData(
+                "NetrShareGetInfo returned a different level than requested",
+            )),
+            None => Err(crate::SmbRpcError::InvalidResponseData(
+                "NetrShareGetInfo returned no data",
+            )),
+        }
+    }
+
+    /// Returns information about the server, at the specified level.
+    #[maybe_async]
+    pub async fn netr_server_get_info(
+        &mut self,
+        server_name: &str,
+        level: ServerInfoLevel,
+    ) -> crate::Result<ServerInfo> {
+        let input_struct = NetrServerGetInfoIn {
+            server_name: NdrPtr::from(server_name.parse::<NdrString<u16>>().unwrap()).into(),
+            level: level.into(),
+        };
+        let result = self.bound_pipe.send_receive(input_struct).await?;
+        if *result.return_value != 0 {
+            return Err(crate::SmbRpcError::CallFailed(*result.return_value));
+        }
+        let info = match result.info_struct.value {
+            ServerInfoUnion::Info100(ptr) => ptr.into_inner().map(|x| ServerInfo::Info100(x.value)),
+            ServerInfoUnion::Info101(ptr) => ptr.into_inner().map(|x| ServerInfo::Info101(x.value)),
+            ServerInfoUnion::Info102(ptr) => ptr.into_inner().map(|x| ServerInfo::Info102(x.value)),
+        };
+        match info {
+            Some(info) if info.level() == level => Ok(info),
+            Some(_) => Err(crate::SmbRpcError::InvalidResponseData(
+                "NetrServerGetInfo returned a different level than requested",
+            )),
+            None => Err(crate::SmbRpcError::InvalidResponseData(
+                "NetrServerGetInfo returned no data",
+            )),
+        }
+    }
 }
 
 impl<T> super::base::RpcInterface<T> for SrvSvc<T>
@@ -322,4 +705,123 @@ mod test {
             resume_handle: NdrPtr::<u32>::from(None).into(),
         } => "00000200000000000c0000000000000000000000000000000c000000000000005c005c006c006f00630061006c0068006f0073007400000001000000000000000100000000000000000002000000000000000000000000000000000000000000ffffffff000000000000000000000000"
     }
+
+    fn ndr_str(value: &str) -> NdrPtr<NdrString<u16>> {
+        value.parse::<NdrString<u16>>().unwrap().into()
+    }
+
+    smb_tests::test_binrw_write! {
+        struct NetrShareGetInfoIn {
+            server_name: ndr_str(r"\\srv").into(),
+            net_name: "C$".parse::<NdrString<u16>>().unwrap().into(),
+            level: ShareInfoLevel::Info502.into(),
+        } => "00000200000000000600000000000000000000000000000006000000000000005c005c007300720076000000000000000300000000000000000000000000000003000000000000004300240000000000f6010000"
+    }
+
+    test_binrw! {
+        struct NetrShareGetInfoOut => info502 {
+            info_struct: ShareInfoUnion::Info502(NdrPtr::from(NdrStructure::from(ShareInfo502 {
+                netname: ndr_str("C$"),
+                share_type: ShareType::new().with_special(true).into(),
+                remark: ndr_str("Default share"),
+                permissions: 0.into(),
+                max_uses: u32::MAX.into(),
+                current_uses: 1.into(),
+                path: ndr_str(r"C:\"),
+                passwd: None.into(),
+                reserved: 4.into(),
+                security_descriptor: NdrPtr::from(NdrByteArray::from(vec![0x01, 0x00, 0x04, 0x80])),
+            })))
+            .into(),
+            return_value: 0.into(),
+        } => "f601000000000000000002000000000000000200000000000000008000000000000002000000000000000000ffffffff0100000000000000000002000000000000000000000000000400000000000000000002000000000003000000000000000000000000000000030000000000000043002400000000000e0000000000000000000000000000000e00000000000000440065006600610075006c00740020007300680061007200650000000000000004000000000000000000000000000000040000000000000043003a005c00000004000000000000000100048000000000"
+    }
+
+    test_binrw! {
+        struct NetrShareGetInfoOut => info2 {
+            info_struct: ShareInfoUnion::Info2(NdrPtr::from(NdrStructure::from(ShareInfo2 {
+                netname: ndr_str("MyShare"),
+                share_type: ShareType::new().into(),
+                remark: ndr_str(""),
+                permissions: 0.into(),
+                max_uses: u32::MAX.into(),
+                current_uses: 2.into(),
+                path: ndr_str(r"C:\MyShare"),
+                passwd: None.into(),
+            })))
+            .into(),
+            return_value: 0.into(),
+        } => "0200000000000000000002000000000000000200000000000000000000000000000002000000000000000000ffffffff0200000000000000000002000000000000000000000000000800000000000000000000000000000008000000000000004d00790053006800610072006500000001000000000000000000000000000000010000000000000000000000000000000b0000000000000000000000000000000b0000000000000043003a005c004d007900530068006100720065000000000000000000"
+    }
+
+    test_binrw! {
+        struct NetrShareGetInfoOut => not_found {
+            info_struct: ShareInfoUnion::Info502(None.into()).into(),
+            // NERR_NetNameNotFound
+            return_value: 2310.into(),
+        } => "f601000000000000000000000000000006090000"
+    }
+
+    smb_tests::test_binrw_write! {
+        struct NetrServerGetInfoIn {
+            server_name: ndr_str(r"\\srv").into(),
+            level: ServerInfoLevel::Info102.into(),
+        } => "00000200000000000600000000000000000000000000000006000000000000005c005c00730072007600000066000000"
+    }
+
+    test_binrw! {
+        struct NetrServerGetInfoOut => info100 {
+            info_struct: ServerInfoUnion::Info100(NdrPtr::from(NdrStructure::from(ServerInfo100 {
+                platform_id: PlatformId::Nt.into(),
+                name: ndr_str("SRV"),
+            })))
+            .into(),
+            return_value: 0.into(),
+        } => "64000000000000000000020000000000f4010000000000000000020000000000040000000000000000000000000000000400000000000000530052005600000000000000"
+    }
+
+    test_binrw! {
+        struct NetrServerGetInfoOut => info101 {
+            info_struct: ServerInfoUnion::Info101(NdrPtr::from(NdrStructure::from(ServerInfo101 {
+                platform_id: PlatformId::Nt.into(),
+                name: ndr_str("SRV"),
+                version_major: 10.into(),
+                version_minor: 0.into(),
+                server_type: ServerType::new()
+                    .with_workstation(true)
+                    .with_server(true)
+                    .with_nt(true)
+                    .into(),
+                comment: ndr_str(""),
+            })))
+            .into(),
+            return_value: 0.into(),
+        } => "65000000000000000000020000000000f40100000000000000000200000000000a000000000000000310000000000000000002000000000004000000000000000000000000000000040000000000000053005200560000000100000000000000000000000000000001000000000000000000000000000000"
+    }
+
+    test_binrw! {
+        struct NetrServerGetInfoOut => info102 {
+            info_struct: ServerInfoUnion::Info102(NdrPtr::from(NdrStructure::from(ServerInfo102 {
+                platform_id: PlatformId::Nt.into(),
+                name: ndr_str("SRV"),
+                version_major: 10.into(),
+                version_minor: 0.into(),
+                server_type: ServerType::new()
+                    .with_workstation(true)
+                    .with_server(true)
+                    .with_nt(true)
+                    .into(),
+                comment: ndr_str(""),
+                users: u32::MAX.into(),
+                disc: (-1).into(),
+                hidden: 0.into(),
+                announce: 240.into(),
+                anndelta: 3000.into(),
+                licenses: 0.into(),
+                userpath: ndr_str(r"c:\"),
+            })))
+            .into(),
+            return_value: 0.into(),
+        } => "66000000000000000000020000000000f40100000000000000000200000000000a0000000000000003100000000000000000020000000000ffffffffffffffff00000000f0000000b80b00000000000000000200000000000400000000000000000000000000000004000000000000005300520056000000010000000000000000000000000000000100000000000000000000000000000004000000000000000000000000000000040000000000000063003a005c00000000000000"
+    }
 }
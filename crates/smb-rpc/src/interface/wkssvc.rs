@@ -0,0 +1,128 @@
+//! MS-WKST: Workstation Service Remote Protocol
+
+use crate::{interface::*, pdu::DceRpcSyntaxId};
+use smb_dtyp::make_guid;
+
+use crate::ndr64::*;
+use binrw::prelude::*;
+use maybe_async::maybe_async;
+
+/// `WKSTA_INFO_100` (MS-WKST 2.2.5.1)
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[bw(import(stage: NdrPtrWriteStage))]
+#[br(import(prev: Option<&Self>))]
+pub struct WkstaInfo100 {
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.platform_id)))]
+    pub platform_id: NdrArrayStructureElement<PlatformId, 4>,
+    /// The NetBIOS name of the computer.
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.computer_name), NdrPtrReadMode::WithArraySupport, ()))]
+    pub computer_name: NdrPtr<NdrString<u16>>,
+    /// The name of the domain or workgroup of the computer.
+    #[bw(args_raw(NdrPtrWriteArgs(stage, ())))]
+    #[br(args(prev.map(|x| &x.langroup), NdrPtrReadMode::WithArraySupport, ()))]
+    pub langroup: NdrPtr<NdrString<u16>>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.version_major)))]
+    pub version_major: NdrArrayStructureElement<u32, 4>,
+    #[bw(if(stage == NdrPtrWriteStage::ArraySupportWriteRefId))]
+    #[br(args(prev.map(|x| &**x.version_minor)))]
+    pub version_minor: NdrArrayStructureElement<u32, 4>,
+}
+
+/// The `WKSTA_INFO` union, as returned by NetrWkstaGetInfo (MS-WKST 3.2.4.1)
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq)]
+enum WkstaInfoUnion {
+    #[brw(magic = 100u64)]
+    Info100(NdrPtr<NdrStructure<WkstaInfo100>>),
+}
+
+rpc_call! {
+    /// NetrWkstaGetInfo (MS-WKST 3.2.4.1)
+    NetrWkstaGetInfo = 0x0 {
+        in {
+            server_name: NdrAlign<NdrPtr<NdrString<u16>>, 4>,
+            level: NdrAlign<u32, 4>,
+        }
+        out {
+            wksta_info: NdrAlign<WkstaInfoUnion>,
+            return_value: NdrAlign<u32, 4>,
+        }
+    }
+}
+
+pub struct WksSvc<T>
+where
+    T: BoundRpcConnection,
+{
+    bound_pipe: T,
+}
+
+impl<T> WksSvc<T>
+where
+    T: BoundRpcConnection,
+{
+    /// Returns the computer and domain names of the workstation (level 100).
+    #[maybe_async]
+    pub async fn netr_wksta_get_info(&mut self, server_name: &str) -> crate::Result<WkstaInfo100> {
+        let input_struct = NetrWkstaGetInfoIn {
+            server_name: NdrPtr::from(server_name.parse::<NdrString<u16>>().unwrap()).into(),
+            level: 100.into(),
+        };
+        let result = self.bound_pipe.send_receive(input_struct).await?;
+        if *result.return_value != 0 {
+            return Err(crate::SmbRpcError::CallFailed(*result.return_value));
+        }
+        let WkstaInfoUnion::Info100(info) = result.wksta_info.value;
+        info.into_inner()
+            .map(|info| info.value)
+            .ok_or(crate::SmbRpcError::InvalidResponseData(
+                "NetrWkstaGetInfo returned no data",
+            ))
+    }
+}
+
+impl<T> super::base::RpcInterface<T> for WksSvc<T>
+where
+    T: BoundRpcConnection,
+{
+    const SYNTAX_ID: DceRpcSyntaxId = DceRpcSyntaxId {
+        uuid: make_guid!("6bffd098-a112-3610-9833-46c3f87e345a"),
+        version: 1,
+    };
+
+    fn new(bound_pipe: T) -> Self {
+        WksSvc { bound_pipe }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use smb_tests::*;
+
+    use super::*;
+
+    smb_tests::test_binrw_write! {
+        struct NetrWkstaGetInfoIn {
+            server_name: NdrPtr::from(r"\\srv".parse::<NdrString<u16>>().unwrap()).into(),
+            level: 100.into(),
+        } => "00000200000000000600000000000000000000000000000006000000000000005c005c00730072007600000064000000"
+    }
+
+    test_binrw! {
+        struct NetrWkstaGetInfoOut {
+            wksta_info: WkstaInfoUnion::Info100(NdrPtr::from(NdrStructure::from(WkstaInfo100 {
+                platform_id: PlatformId::Nt.into(),
+                computer_name: NdrPtr::from("DC1".parse::<NdrString<u16>>().unwrap()),
+                langroup: NdrPtr::from("CONTOSO".parse::<NdrString<u16>>().unwrap()),
+                version_major: 10.into(),
+                version_minor: 0.into(),
+            })))
+            .into(),
+            return_value: 0.into(),
+        } => "64000000000000000000020000000000f401000000000000000002000000000000000200000000000a00000000000000040000000000000000000000000000000400000000000000440043003100000008000000000000000000000000000000080000000000000043004f004e0054004f0053004f00000000000000"
+    }
+}
@@ -17,6 +17,20 @@ pub enum SmbRpcError {
 
     #[error("Failed to parse response data: {0}")]
     FailedToParseRpcResponse(binrw::Error),
+
+    #[error("RPC call failed with status {0:#x}")]
+    CallFailed(u32),
+
+    /// The server returned a fault PDU for the call.
+    #[error("RPC call failed with fault {0}")]
+    Fault(pdu::RpcFaultStatus),
+
+    /// The server rejected binding to the interface.
+    #[error("RPC bind was rejected: {0:?}")]
+    BindRejected(pdu::DceRpcCoPktBindRejectReason),
+
+    #[error("Unsupported info level: {0}")]
+    UnsupportedInfoLevel(u32),
 }
 
 type Result<T> = std::result::Result<T, SmbRpcError>;
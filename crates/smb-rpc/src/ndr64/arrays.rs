@@ -127,16 +127,19 @@ where
 /// A helper for wrapping in-structure NDR elements, that may be used
 /// for arrays of structures.
 ///
+/// The element is aligned to `TO` bytes, which should be the natural alignment of `T`
+/// (e.g. 4 for a `u32` that follows another `u32` in the structure).
+///
 /// See example usage in the tests below.
 #[derive(Debug, PartialEq, Eq)]
-pub struct NdrArrayStructureElement<T>
+pub struct NdrArrayStructureElement<T, const TO: usize = NDR64_ALIGNMENT>
 where
     T: BinRead + BinWrite + 'static,
 {
-    val: NdrAlign<T>,
+    val: NdrAlign<T, TO>,
 }
 
-impl<T> BinRead for NdrArrayStructureElement<T>
+impl<T, const TO: usize> BinRead for NdrArrayStructureElement<T, TO>
 where
     T: BinRead<Args<'static> = ()> + BinWrite + Clone + 'static,
 {
@@ -152,14 +155,14 @@ where
                 val: (*prev).clone().into(),
             }),
             None => {
-                let val = NdrAlign::<T>::read_options(reader, endian, ())?;
+                let val = NdrAlign::<T, TO>::read_options(reader, endian, ())?;
                 Ok(Self { val })
             }
         }
     }
 }
 
-impl<T> BinWrite for NdrArrayStructureElement<T>
+impl<T, const TO: usize> BinWrite for NdrArrayStructureElement<T, TO>
 where
     for<'a> T: BinWrite<Args<'a> = ()> + BinRead + Clone + 'static,
 {
@@ -175,7 +178,7 @@ where
     }
 }
 
-impl<T> From<T> for NdrArrayStructureElement<T>
+impl<T, const TO: usize> From<T> for NdrArrayStructureElement<T, TO>
 where
     T: BinRead + BinWrite + Clone + 'static,
 {
@@ -185,20 +188,23 @@ where
         }
     }
 }
-impl<T> NdrAligned for NdrArrayStructureElement<T> where T: BinRead + BinWrite + Clone + 'static {}
+impl<T, const TO: usize> NdrAligned for NdrArrayStructureElement<T, TO> where
+    T: BinRead + BinWrite + Clone + 'static
+{
+}
 
-impl<T> Deref for NdrArrayStructureElement<T>
+impl<T, const TO: usize> Deref for NdrArrayStructureElement<T, TO>
 where
     T: BinRead + BinWrite + Clone + 'static,
 {
-    type Target = NdrAlign<T>;
+    type Target = NdrAlign<T, TO>;
 
     fn deref(&self) -> &Self::Target {
         &self.val
     }
 }
 
-impl<T> DerefMut for NdrArrayStructureElement<T>
+impl<T, const TO: usize> DerefMut for NdrArrayStructureElement<T, TO>
 where
     T: BinRead + BinWrite + Clone + 'static,
 {
@@ -207,7 +213,7 @@ where
     }
 }
 
-impl<T> Default for NdrArrayStructureElement<T>
+impl<T, const TO: usize> Default for NdrArrayStructureElement<T, TO>
 where
     T: BinRead + BinWrite + Clone + Default + 'static,
 {
@@ -218,7 +224,7 @@ where
     }
 }
 
-impl<T> Clone for NdrArrayStructureElement<T>
+impl<T, const TO: usize> Clone for NdrArrayStructureElement<T, TO>
 where
     T: BinRead + BinWrite + Clone + 'static,
 {
@@ -229,6 +235,103 @@ where
     }
 }
 
+/// A structure with embedded pointers, that is not an element of an array.
+///
+/// Like the elements of an [`NdrArray`], the structure is first written
+/// with the reference IDs of its pointers, followed by the pointers' referents.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NdrStructure<E>
+where
+    for<'a> E:
+        BinRead<Args<'a> = (Option<&'a E>,)> + BinWrite<Args<'a> = (NdrPtrWriteStage,)> + 'static,
+{
+    pub value: E,
+}
+
+impl<E> BinRead for NdrStructure<E>
+where
+    for<'a> E:
+        BinRead<Args<'a> = (Option<&'a E>,)> + BinWrite<Args<'a> = (NdrPtrWriteStage,)> + 'static,
+{
+    type Args<'a> = ();
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::endian::Endian,
+        _args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let refs_only = E::read_options(reader, endian, (None,))?;
+        let value = E::read_options(reader, endian, (Some(&refs_only),))?;
+        Ok(Self { value })
+    }
+}
+
+impl<E> BinWrite for NdrStructure<E>
+where
+    for<'a> E:
+        BinRead<Args<'a> = (Option<&'a E>,)> + BinWrite<Args<'a> = (NdrPtrWriteStage,)> + 'static,
+{
+    type Args<'a> = ();
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::endian::Endian,
+        _args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.value
+            .write_options(writer, endian, (NdrPtrWriteStage::ArraySupportWriteRefId,))?;
+        self.value
+            .write_options(writer, endian, (NdrPtrWriteStage::ArraySupportWriteData,))
+    }
+}
+
+impl<E> NdrAligned for NdrStructure<E> where
+    for<'a> E:
+        BinRead<Args<'a> = (Option<&'a E>,)> + BinWrite<Args<'a> = (NdrPtrWriteStage,)> + 'static
+{
+}
+
+impl<E> From<E> for NdrStructure<E>
+where
+    for<'a> E:
+        BinRead<Args<'a> = (Option<&'a E>,)> + BinWrite<Args<'a> = (NdrPtrWriteStage,)> + 'static,
+{
+    fn from(value: E) -> Self {
+        Self { value }
+    }
+}
+
+impl<E> Deref for NdrStructure<E>
+where
+    for<'a> E:
+        BinRead<Args<'a> = (Option<&'a E>,)> + BinWrite<Args<'a> = (NdrPtrWriteStage,)> + 'static,
+{
+    type Target = E;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+/// A conformant array of bytes, such as the referent of a `[size_is(n)] unsigned char*` pointer.
+#[binrw::binrw]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct NdrByteArray {
+    #[bw(calc = (data.len() as u64).into())]
+    max_count: NdrAlign<u64>,
+    #[br(count = *max_count)]
+    pub data: Vec<u8>,
+}
+
+impl NdrAligned for NdrByteArray {}
+
+impl From<Vec<u8>> for NdrByteArray {
+    fn from(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use smb_tests::*;
@@ -177,6 +177,23 @@ where
     }
 }
 
+impl<T> NdrPtr<T>
+where
+    T: BinRead + BinWrite,
+{
+    /// Returns the value of a resolved pointer.
+    ///
+    /// # Panics
+    /// Panics if the pointer is not resolved, same as dereferencing it.
+    pub fn into_inner(self) -> Option<T> {
+        match self {
+            Self::Resolved(value) => value.map(|value| value.value),
+            Self::RefIdRead(_) => panic!("Cannot take a pointer that is in RefIdRead state"),
+            Self::Uninit => panic!("Cannot take an uninitialized pointer"),
+        }
+    }
+}
+
 impl<T> NdrAligned for NdrPtr<T> where T: BinRead + BinWrite + NdrAligned {}
 
 impl<T> Deref for NdrPtr<T>
@@ -238,7 +255,6 @@ where
 }
 
 #[cfg(test)]
-
 mod tests {
     use smb_tests::*;
 
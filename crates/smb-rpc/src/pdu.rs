@@ -156,7 +156,7 @@ rpc_pkts! {
     },
     Response {
         Response = 2,
-        // Fault = 3,
+        Fault = 3,
         BindAck = 12,
         BindNak = 13,
         // AlterContextResp = 15,
@@ -356,6 +356,93 @@ pub struct DcRpcCoPktResponse {
     pub stub_data: Vec<u8>,
 }
 
+/// A fault PDU, returned instead of a response when a call fails.
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq)]
+pub struct DcRpcCoPktFault {
+    pub alloc_hint: u32,
+    pub context_id: u16,
+    pub cancel_count: u8,
+    #[bw(calc = 0)]
+    _reserved: u8,
+    /// See [`RpcFaultStatus`].
+    pub status: u32,
+    #[bw(calc = 0)]
+    _reserved2: u32,
+
+    #[br(parse_with = binrw::helpers::until_eof)]
+    pub stub_data: Vec<u8>,
+}
+
+/// The status of a [fault PDU][DcRpcCoPktFault] (C706 Appendix E, MS-RPCE 2.2.2.8).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcFaultStatus {
+    /// `nca_s_op_rng_error`: the interface does not have an operation with the requested opnum.
+    OperationRangeError,
+    /// `nca_s_unk_if`: the server does not support the interface.
+    UnknownInterface,
+    /// `nca_s_proto_error`: the server received a PDU that violates the protocol.
+    ProtocolError,
+    /// `nca_s_fault_context_mismatch`: the context handle of the call is not valid.
+    ContextMismatch,
+    /// `rpc_s_access_denied`: the user is not allowed to make the call.
+    AccessDenied,
+    /// `rpc_s_cannot_support`: the server does not support the requested operation.
+    CannotSupport,
+    /// `nca_s_fault_ndr`: the server failed to unmarshal the stub data of the call.
+    BadStubData,
+    /// Any other status.
+    Other(u32),
+}
+
+impl RpcFaultStatus {
+    pub const OPERATION_RANGE_ERROR: u32 = 0x1c010002;
+    pub const UNKNOWN_INTERFACE: u32 = 0x1c010003;
+    pub const PROTOCOL_ERROR: u32 = 0x1c01000b;
+    pub const CONTEXT_MISMATCH: u32 = 0x1c00001a;
+    pub const ACCESS_DENIED: u32 = 0x00000005;
+    pub const CANNOT_SUPPORT: u32 = 0x000006e4;
+    pub const BAD_STUB_DATA: u32 = 0x000006f7;
+
+    /// Returns the raw status code.
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::OperationRangeError => Self::OPERATION_RANGE_ERROR,
+            Self::UnknownInterface => Self::UNKNOWN_INTERFACE,
+            Self::ProtocolError => Self::PROTOCOL_ERROR,
+            Self::ContextMismatch => Self::CONTEXT_MISMATCH,
+            Self::AccessDenied => Self::ACCESS_DENIED,
+            Self::CannotSupport => Self::CANNOT_SUPPORT,
+            Self::BadStubData => Self::BAD_STUB_DATA,
+            Self::Other(code) => *code,
+        }
+    }
+}
+
+impl From<u32> for RpcFaultStatus {
+    fn from(code: u32) -> Self {
+        match code {
+            Self::OPERATION_RANGE_ERROR => Self::OperationRangeError,
+            Self::UNKNOWN_INTERFACE => Self::UnknownInterface,
+            Self::PROTOCOL_ERROR => Self::ProtocolError,
+            Self::CONTEXT_MISMATCH => Self::ContextMismatch,
+            Self::ACCESS_DENIED => Self::AccessDenied,
+            Self::CANNOT_SUPPORT => Self::CannotSupport,
+            Self::BAD_STUB_DATA => Self::BadStubData,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl std::fmt::Display for RpcFaultStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Other(code) => write!(f, "{code:#010x}"),
+            status => write!(f, "{status:?} ({:#010x})", status.code()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -593,4 +680,26 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_fault_parse() {
+        let data = [
+            0x5, 0x0, 0x3, 0x3, 0x10, 0x0, 0x0, 0x0, 0x20, 0x0, 0x0, 0x0, 0x3, 0x0, 0x0, 0x0, 0x20,
+            0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x2, 0x0, 0x1, 0x1c, 0x0, 0x0, 0x0, 0x0,
+        ];
+        let fault = DceRpcCoResponsePkt::try_from(data.as_ref()).unwrap();
+        assert_eq!(fault.call_id(), 3);
+        match fault.into_content() {
+            DcRpcCoPktResponseContent::Fault(fault) => {
+                assert_eq!(fault.context_id, 1);
+                assert_eq!(
+                    RpcFaultStatus::from(fault.status),
+                    RpcFaultStatus::OperationRangeError
+                );
+                assert!(fault.stub_data.is_empty());
+            }
+            content => panic!("Unexpected content: {content:?}"),
+        }
+        assert_eq!(RpcFaultStatus::from(0x1234).code(), 0x1234);
+    }
 }
@@ -0,0 +1,211 @@
+use std::sync::Mutex;
+
+use crate::{IoVec, IoVecBuf};
+
+/// A bounded freelist of byte buffers, for reusing the buffers of outgoing and incoming messages.
+///
+/// Most messages are small and similarly sized, so serializing a message into a recycled buffer
+/// usually avoids allocating. Buffers are returned to the pool using [`BufferPool::recycle`] once
+/// they were sent, or once their data was consumed. The segment lists of [`IoVec`]s are pooled as well,
+/// see [`BufferPool::take_iovec`] and [`BufferPool::recycle_iovec`].
+///
+/// The pool retains at most [`BufferPool::max_buffers`] buffers, each with a capacity of at most
+/// [`BufferPool::max_buffer_capacity`] bytes. Larger buffers are returned to the allocator.
+/// It retains at most as many segment lists, of up to [`BufferPool::MAX_IOVEC_SEGMENTS`] segments each.
+///
+/// The freelist is guarded by a [`std::sync::Mutex`], which is held only to push or pop a buffer,
+/// so the pool is used the same way in the async and sync builds.
+#[derive(Debug)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    segments: Mutex<Vec<Vec<IoVecBuf>>>,
+    max_buffers: usize,
+    max_buffer_capacity: usize,
+}
+
+impl BufferPool {
+    pub const DEFAULT_MAX_BUFFERS: usize = 64;
+    pub const DEFAULT_MAX_BUFFER_CAPACITY: usize = 64 * 1024;
+    /// The maximum capacity of a retained [`IoVec`] segment list.
+    pub const MAX_IOVEC_SEGMENTS: usize = 64;
+
+    /// Creates a new, empty pool, retaining up to `max_buffers` buffers
+    /// of up to `max_buffer_capacity` bytes each.
+    pub const fn new(max_buffers: usize, max_buffer_capacity: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            segments: Mutex::new(Vec::new()),
+            max_buffers,
+            max_buffer_capacity,
+        }
+    }
+
+    /// Creates a pool that retains no buffers, so every [`BufferPool::take`] allocates.
+    pub const fn disabled() -> Self {
+        Self::new(0, 0)
+    }
+
+    /// The maximum number of buffers the pool retains.
+    pub fn max_buffers(&self) -> usize {
+        self.max_buffers
+    }
+
+    /// The maximum capacity of a buffer the pool retains, in bytes.
+    pub fn max_buffer_capacity(&self) -> usize {
+        self.max_buffer_capacity
+    }
+
+    /// Returns the number of buffers currently retained by the pool.
+    pub fn len(&self) -> usize {
+        self.buffers.lock().map_or(0, |buffers| buffers.len())
+    }
+
+    /// Returns whether the pool currently retains no buffers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an empty buffer with a capacity of at least `capacity` bytes,
+    /// reusing a retained buffer if there is one.
+    pub fn take(&self, capacity: usize) -> Vec<u8> {
+        let recycled = self
+            .buffers
+            .lock()
+            .ok()
+            .and_then(|mut buffers| buffers.pop());
+        match recycled {
+            Some(mut buffer) => {
+                buffer.reserve(capacity);
+                buffer
+            }
+            None => Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns a buffer of `size` zero bytes, reusing a retained buffer if there is one.
+    pub fn take_zeroed(&self, size: usize) -> Vec<u8> {
+        let mut buffer = self.take(size);
+        buffer.resize(size, 0);
+        buffer
+    }
+
+    /// Returns a buffer to the pool, once its data is no longer needed.
+    ///
+    /// The buffer is dropped if it is too large, or if the pool is full.
+    pub fn recycle(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() == 0 || buffer.capacity() > self.max_buffer_capacity {
+            return;
+        }
+        let Ok(mut buffers) = self.buffers.lock() else {
+            return;
+        };
+        if buffers.len() < self.max_buffers {
+            buffer.clear();
+            buffers.push(buffer);
+        }
+    }
+
+    /// Returns an empty [`IoVec`] with room for at least `capacity` buffers,
+    /// reusing a retained segment list if there is one.
+    pub fn take_iovec(&self, capacity: usize) -> IoVec {
+        let recycled = self
+            .segments
+            .lock()
+            .ok()
+            .and_then(|mut segments| segments.pop());
+        match recycled {
+            Some(mut segments) => {
+                segments.reserve(capacity);
+                segments.into()
+            }
+            None => IoVec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the owned buffers of an [`IoVec`], and its segment list, to the pool, once it was sent.
+    ///
+    /// Shared buffers are left to their other owners.
+    pub fn recycle_iovec(&self, iovec: IoVec) {
+        let mut segments = Vec::from(iovec);
+        for buf in segments.drain(..) {
+            if let IoVecBuf::Owned(buffer) = buf {
+                self.recycle(buffer);
+            }
+        }
+        if segments.capacity() == 0 || segments.capacity() > Self::MAX_IOVEC_SEGMENTS {
+            return;
+        }
+        let Ok(mut retained) = self.segments.lock() else {
+            return;
+        };
+        if retained.len() < self.max_buffers {
+            retained.push(segments);
+        }
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_BUFFERS, Self::DEFAULT_MAX_BUFFER_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_take_reuses_recycled() {
+        let pool = BufferPool::default();
+        let mut buffer = pool.take(100);
+        buffer.extend_from_slice(&[1; 100]);
+        let ptr = buffer.as_ptr();
+        pool.recycle(buffer);
+        assert_eq!(pool.len(), 1);
+
+        let buffer = pool.take(50);
+        assert!(buffer.is_empty());
+        assert!(buffer.capacity() >= 100);
+        assert_eq!(buffer.as_ptr(), ptr);
+        assert!(pool.is_empty());
+
+        let buffer = pool.take_zeroed(10);
+        assert_eq!(buffer, [0; 10]);
+    }
+
+    #[test]
+    fn test_retained_memory_is_capped() {
+        let pool = BufferPool::new(2, 1024);
+        pool.recycle(Vec::with_capacity(2048));
+        assert!(pool.is_empty(), "Oversized buffers are dropped");
+
+        for _ in 0..3 {
+            pool.recycle(Vec::with_capacity(16));
+        }
+        assert_eq!(pool.len(), 2);
+
+        let disabled = BufferPool::disabled();
+        disabled.recycle(Vec::with_capacity(16));
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn test_recycle_iovec_owned_only() {
+        let pool = BufferPool::default();
+        let mut iovec = IoVec::default();
+        iovec.add_owned(vec![1, 2, 3]);
+        iovec.add_shared(Arc::from(vec![4, 5].into_boxed_slice()));
+        iovec.add_owned(vec![6]);
+        pool.recycle_iovec(iovec);
+        assert_eq!(pool.len(), 2);
+
+        let iovec = pool.take_iovec(1);
+        assert!(iovec.is_empty());
+        assert!(
+            Vec::from(iovec).capacity() >= 3,
+            "The segment list is reused"
+        );
+    }
+}
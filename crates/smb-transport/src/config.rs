@@ -1,3 +1,5 @@
+use std::{net::IpAddr, time::Duration};
+
 #[cfg(feature = "quic")]
 pub use crate::quic::config::*;
 #[cfg(feature = "rdma")]
@@ -22,3 +24,32 @@ pub enum TransportConfig {
     #[cfg(feature = "rdma")]
     Rdma(RdmaConfig),
 }
+
+/// Options for the TCP connections of the TCP and NetBIOS transports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpConnectOptions {
+    /// The local address to bind the connection to, to select the network interface that connects to the server.
+    /// If unset, the operating system selects the local address.
+    ///
+    /// Only server addresses of the same family (IPv4 or IPv6) as the bind address are connected to.
+    pub bind_address: Option<IpAddr>,
+
+    /// The delay before connecting to the next address of a server that has multiple addresses,
+    /// while connecting to the previous ones is still in progress.
+    /// See [`SmbTransport::connect_any`][crate::SmbTransport::connect_any].
+    pub attempt_delay: Duration,
+}
+
+impl TcpConnectOptions {
+    /// The connection attempt delay recommended by RFC 8305, section 5.
+    pub const DEFAULT_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+}
+
+impl Default for TcpConnectOptions {
+    fn default() -> Self {
+        Self {
+            bind_address: None,
+            attempt_delay: Self::DEFAULT_ATTEMPT_DELAY,
+        }
+    }
+}
@@ -17,8 +17,18 @@ pub enum TransportError {
     Timeout(std::time::Duration),
     #[error("Invalid address: {0}")]
     InvalidAddress(String),
+    /// The server certificate was rejected by the client, or the client certificate was rejected by the server.
+    #[error("Certificate rejected: {0}")]
+    CertificateRejected(String),
     #[error("IO Error: {0}")]
     IoError(#[from] std::io::Error),
+    /// Connecting failed for each of the addresses of the server, with the error of each address.
+    #[error("Failed to connect to any of the server addresses: {}", display_attempts(.0))]
+    ConnectFailed(Vec<(std::net::SocketAddr, TransportError)>),
+    /// The server rejected the NetBIOS session request, with a negative session response.
+    #[cfg(feature = "netbios-transport")]
+    #[error("NetBIOS session request rejected: {0}")]
+    NetBiosSessionRejected(crate::netbios::NBSSNegativeSessionResponseErrorCode),
 
     #[cfg(feature = "quic")]
     #[error("QUIC error: {0}")]
@@ -30,3 +40,24 @@ pub enum TransportError {
 }
 
 pub type Result<T> = std::result::Result<T, TransportError>;
+
+impl TransportError {
+    /// Returns the error of a connection, that failed with `errors` for each of the attempted addresses.
+    ///
+    /// Returns the error as is if only a single address was attempted.
+    pub(crate) fn connect_failed(mut errors: Vec<(std::net::SocketAddr, TransportError)>) -> Self {
+        match errors.len() {
+            0 => TransportError::InvalidAddress("No addresses to connect to".to_string()),
+            1 => errors.pop().unwrap().1,
+            _ => TransportError::ConnectFailed(errors),
+        }
+    }
+}
+
+fn display_attempts(attempts: &[(std::net::SocketAddr, TransportError)]) -> String {
+    attempts
+        .iter()
+        .map(|(address, error)| format!("{address}: {error}"))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
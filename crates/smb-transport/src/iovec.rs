@@ -54,6 +54,11 @@ impl From<Arc<[u8]>> for IoVecBuf {
 pub struct IoVec(Vec<IoVecBuf>);
 
 impl IoVec {
+    /// Creates an empty IoVec, with room for `capacity` buffers.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+
     /// Returns the total size of all buffers in the IoVec (in bytes).
     pub fn total_size(&self) -> usize {
         self.0.iter().map(|buf| buf.len()).sum()
@@ -95,6 +100,17 @@ impl IoVec {
         self.insert_shared(self.0.len(), buf)
     }
 
+    /// Moves all the buffers of `other` to the end of the IoVec.
+    pub fn append(&mut self, other: IoVec) {
+        self.0.extend(other.0);
+    }
+
+    /// Moves all the buffers of `other` to the end of the IoVec, leaving `other` empty,
+    /// so its segment list can be reused.
+    pub fn append_from(&mut self, other: &mut IoVec) {
+        self.0.append(&mut other.0);
+    }
+
     /// Consolidates all buffers into a single owned buffer,
     /// and puts it in the IoVec, replacing all previous buffers.
     pub fn consolidate(&mut self) -> &mut Vec<u8> {
@@ -113,6 +129,12 @@ impl From<Vec<IoVecBuf>> for IoVec {
     }
 }
 
+impl From<IoVec> for Vec<IoVecBuf> {
+    fn from(v: IoVec) -> Self {
+        v.0
+    }
+}
+
 impl From<IoVecBuf> for IoVec {
     fn from(v: IoVecBuf) -> Self {
         Self(vec![v])
@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+pub mod buffer_pool;
 pub mod config;
 pub mod error;
 pub mod iovec;
@@ -7,6 +8,7 @@ pub mod tcp;
 pub mod traits;
 pub mod utils;
 
+pub use buffer_pool::BufferPool;
 pub use config::*;
 pub use error::TransportError;
 pub use iovec::*;
@@ -29,6 +31,9 @@ pub mod rdma;
 #[cfg(feature = "rdma")]
 pub use rdma::*;
 
+#[cfg(feature = "test-util")]
+pub mod mock;
+
 /// Creates [`SmbTransport`] out of [`TransportConfig`].
 ///
 /// ## Arguments
@@ -37,12 +42,28 @@ pub use rdma::*;
 pub fn make_transport(
     transport: &TransportConfig,
     timeout: Duration,
+) -> Result<Box<dyn SmbTransport>, TransportError> {
+    make_transport_with_options(transport, timeout, TcpConnectOptions::default())
+}
+
+/// Creates [`SmbTransport`] out of [`TransportConfig`], just like [`make_transport`],
+/// with the specified options for TCP connections (see [`TcpConnectOptions`]).
+pub fn make_transport_with_options(
+    transport: &TransportConfig,
+    timeout: Duration,
+    tcp_options: TcpConnectOptions,
 ) -> Result<Box<dyn SmbTransport>, TransportError> {
     match transport {
-        TransportConfig::Tcp => Ok(Box::new(tcp::TcpTransport::new(timeout))),
+        TransportConfig::Tcp => Ok(Box::new(tcp::TcpTransport::with_options(
+            timeout,
+            tcp_options,
+        ))),
 
         #[cfg(feature = "netbios-transport")]
-        TransportConfig::NetBios => Ok(Box::new(NetBiosTransport::new(timeout))),
+        TransportConfig::NetBios => Ok(Box::new(NetBiosTransport::with_tcp_options(
+            timeout,
+            tcp_options,
+        ))),
 
         #[cfg(feature = "quic")]
         TransportConfig::Quic(quic_config) => {
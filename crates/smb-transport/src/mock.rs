@@ -0,0 +1,909 @@
+//! An in-memory transport, that replies to the requests of the client from a script.
+//!
+//! [`MockTransport`] lets the upper layers (negotiation, session setup, resources...) be tested
+//! without a server: each request sent by the client is matched against the next [`MockStep`]
+//! of a [`MockScript`], by its command and an optional predicate on the parsed request,
+//! and the canned response of the step is sent back, with a header that matches the request.
+//!
+//! ```
+//! use smb_msg::{Command, EchoResponse, Status};
+//! use smb_transport::mock::{MockScript, MockStep, MockTransport};
+//!
+//! let script = MockScript::new()
+//!     .step(MockStep::expect(Command::Echo).respond(EchoResponse::default()))
+//!     .step(MockStep::expect(Command::Echo).respond_error(Status::AccessDenied));
+//! let (transport, handle) = MockTransport::new(script);
+//! // Pass `transport` to the client, e.g. with `Connection::from_transport`, then:
+//! # drop(transport);
+//! assert_eq!(handle.remaining(), 2);
+//! ```
+//!
+//! The transport header of each frame is the direct TCP one (see [`SmbTcpMessageHeader`]).
+//! Only plain messages are supported: encrypted or compressed requests fail the script.
+
+use binrw::prelude::*;
+#[cfg(feature = "async")]
+use futures_core::future::BoxFuture;
+#[cfg(feature = "async")]
+use futures_util::FutureExt;
+use smb_msg::{Command, ErrorResponse, Header, HeaderFlags, PlainRequest, ResponseContent, Status};
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::Cursor,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    SmbTcpMessageHeader, SmbTransport, SmbTransportRead, SmbTransportWrite, TransportError,
+    error::Result,
+};
+
+#[cfg(feature = "async")]
+type EventSender = tokio::sync::mpsc::UnboundedSender<MockEvent>;
+#[cfg(feature = "async")]
+type EventReceiver = tokio::sync::mpsc::UnboundedReceiver<MockEvent>;
+#[cfg(not(feature = "async"))]
+type EventSender = std::sync::mpsc::Sender<MockEvent>;
+#[cfg(not(feature = "async"))]
+type EventReceiver = std::sync::mpsc::Receiver<MockEvent>;
+
+type RequestMatcher = Box<dyn Fn(&PlainRequest) -> bool + Send>;
+
+/// A single expected request, and what the server does once it is received.
+///
+/// A step with neither a response nor [`MockStep::disconnect`] consumes the request silently.
+pub struct MockStep {
+    command: Command,
+    matchers: Vec<(String, RequestMatcher)>,
+    response: Option<(Status, ResponseContent)>,
+    session_id: Option<u64>,
+    tree_id: Option<u32>,
+    credits: Option<u16>,
+    delay: Duration,
+    deferred: bool,
+    disconnect: bool,
+    async_id: Option<u64>,
+}
+
+impl MockStep {
+    /// Expects a request of the specified command.
+    pub fn expect(command: Command) -> Self {
+        Self {
+            command,
+            matchers: vec![],
+            response: None,
+            session_id: None,
+            tree_id: None,
+            credits: None,
+            delay: Duration::ZERO,
+            deferred: false,
+            disconnect: false,
+            async_id: None,
+        }
+    }
+
+    /// Expects the request to also match `matcher`. `description` is reported if it does not.
+    pub fn matching(
+        mut self,
+        description: &str,
+        matcher: impl Fn(&PlainRequest) -> bool + Send + 'static,
+    ) -> Self {
+        self.matchers
+            .push((description.to_string(), Box::new(matcher)));
+        self
+    }
+
+    /// Responds with `content`, and [`Status::Success`].
+    ///
+    /// A [`ResponseContent::Unknown`] response is sent with its own command code, rather than the one of the request.
+    pub fn respond(self, content: impl Into<ResponseContent>) -> Self {
+        self.respond_with_status(Status::Success, content)
+    }
+
+    /// Responds with `content`, and the specified status, e.g. [`Status::MoreProcessingRequired`].
+    pub fn respond_with_status(
+        mut self,
+        status: Status,
+        content: impl Into<ResponseContent>,
+    ) -> Self {
+        self.response = Some((status, content.into()));
+        self
+    }
+
+    /// Responds with an error response of the specified status.
+    pub fn respond_error(self, status: Status) -> Self {
+        self.respond_with_status(status, ErrorResponse { error_data: vec![] })
+    }
+
+    /// Sets the session ID of the response, instead of the one of the request,
+    /// e.g. for the first session setup response.
+    pub fn session_id(mut self, session_id: u64) -> Self {
+        self.session_id = Some(session_id);
+        self
+    }
+
+    /// Sets the tree ID of the response, instead of the one of the request,
+    /// e.g. for a tree connect response.
+    pub fn tree_id(mut self, tree_id: u32) -> Self {
+        self.tree_id = Some(tree_id);
+        self
+    }
+
+    /// Grants the specified credits in the response. By default, the requested credits are granted, and at least one.
+    pub fn credits(mut self, credits: u16) -> Self {
+        self.credits = Some(credits);
+        self
+    }
+
+    /// Delays the delivery of the response to the client.
+    ///
+    /// Responses are delivered in order, so this delays the following responses as well.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Holds the response back, until the response of a following, non-deferred step is sent.
+    ///
+    /// This reorders the responses of interleaved requests. Deferred responses are lost on [`MockStep::disconnect`].
+    pub fn deferred(mut self) -> Self {
+        self.deferred = true;
+        self
+    }
+
+    /// Goes async on the request: sends an interim response with [`Status::Pending`] and `async_id` right away,
+    /// and then the response of the step, as the final async response.
+    ///
+    /// The interim response is never deferred, so with [`MockStep::deferred`], the final response follows the ones of later steps.
+    pub fn interim(mut self, async_id: u64) -> Self {
+        self.async_id = Some(async_id);
+        self
+    }
+
+    /// Drops the connection once the request is received, after sending the response, if any.
+    ///
+    /// Any further request fails, and the client reads an end of stream ([`TransportError::NotConnected`]).
+    pub fn disconnect(mut self) -> Self {
+        self.disconnect = true;
+        self
+    }
+
+    /// Returns the first expectation of the step that `request` does not meet, if any.
+    fn mismatch(&self, request: &PlainRequest) -> Option<String> {
+        if request.header.command != self.command {
+            return Some(format!(
+                "expected a {} request, got {}",
+                self.command, request.header.command
+            ));
+        }
+        self.matchers
+            .iter()
+            .find(|(_, matcher)| !matcher(request))
+            .map(|(description, _)| {
+                format!("{} request does not match: {description}", self.command)
+            })
+    }
+
+    /// Returns the interim response frame to `request`, if the step goes async.
+    fn interim_frame(&self, request: &Header) -> Result<Option<Vec<u8>>> {
+        match self.async_id {
+            Some(_) => self
+                .frame(
+                    request,
+                    Status::Pending,
+                    ErrorResponse { error_data: vec![] }.into(),
+                )
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the response frame to `request`, including the transport header.
+    fn response_frame(&mut self, request: &Header) -> Result<Option<Vec<u8>>> {
+        let Some((status, content)) = self.response.take() else {
+            return Ok(None);
+        };
+        self.frame(request, status, content).map(Some)
+    }
+
+    fn frame(&self, request: &Header, status: Status, content: ResponseContent) -> Result<Vec<u8>> {
+        let command = match &content {
+            ResponseContent::Unknown { command, .. } => Command::Unknown(*command),
+            _ => request.command,
+        };
+        let mut header = Header {
+            credit_charge: request.credit_charge,
+            status: status as u32,
+            command,
+            credit_request: self.credits.unwrap_or(request.credit_request.max(1)),
+            flags: HeaderFlags::new().with_server_to_redir(true),
+            next_command: 0,
+            message_id: request.message_id,
+            tree_id: Some(self.tree_id.or(request.tree_id).unwrap_or(0)),
+            async_id: None,
+            session_id: self.session_id.unwrap_or(request.session_id),
+            signature: 0,
+        };
+        if let Some(async_id) = self.async_id {
+            header.to_async(async_id);
+        }
+        let mut message = Cursor::new(Vec::new());
+        smb_msg::PlainResponse { header, content }.write(&mut message)?;
+        let message = message.into_inner();
+
+        let mut frame = Vec::with_capacity(SmbTcpMessageHeader::SIZE + message.len());
+        SmbTcpMessageHeader {
+            stream_protocol_length: message.len() as u32,
+        }
+        .write(&mut Cursor::new(&mut frame))?;
+        frame.extend(message);
+        Ok(frame)
+    }
+}
+
+impl fmt::Debug for MockStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockStep")
+            .field("command", &self.command)
+            .field(
+                "matchers",
+                &self.matchers.iter().map(|(d, _)| d).collect::<Vec<_>>(),
+            )
+            .field("status", &self.response.as_ref().map(|(status, _)| status))
+            .field("deferred", &self.deferred)
+            .field("async_id", &self.async_id)
+            .field("disconnect", &self.disconnect)
+            .finish()
+    }
+}
+
+/// The ordered steps that a [`MockTransport`] expects.
+#[derive(Debug, Default)]
+pub struct MockScript {
+    steps: VecDeque<MockStep>,
+}
+
+impl MockScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a step to the script.
+    pub fn step(mut self, step: MockStep) -> Self {
+        self.steps.push_back(step);
+        self
+    }
+
+    /// Appends the steps to the script.
+    pub fn steps(mut self, steps: impl IntoIterator<Item = MockStep>) -> Self {
+        self.steps.extend(steps);
+        self
+    }
+}
+
+impl FromIterator<MockStep> for MockScript {
+    fn from_iter<T: IntoIterator<Item = MockStep>>(iter: T) -> Self {
+        Self::new().steps(iter)
+    }
+}
+
+/// What the reader of the client receives next.
+#[derive(Debug)]
+enum MockEvent {
+    Frame { delay: Duration, data: Vec<u8> },
+    Disconnect,
+}
+
+/// The state of the script, shared by the transport and its [`MockHandle`]s.
+#[derive(Debug, Default)]
+struct MockState {
+    steps: VecDeque<MockStep>,
+    /// The headers of the requests that matched their steps.
+    received: Vec<Header>,
+    failures: Vec<String>,
+    /// Responses of deferred steps, waiting for the next non-deferred response.
+    deferred: Vec<MockEvent>,
+    disconnected: bool,
+}
+
+/// Inspects the progress of the script of a [`MockTransport`], after (or while) the client uses it.
+#[derive(Debug, Clone)]
+pub struct MockHandle {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockHandle {
+    fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the number of steps that were not reached yet.
+    pub fn remaining(&self) -> usize {
+        self.state().steps.len()
+    }
+
+    /// Returns the headers of the requests received so far, that matched their steps.
+    pub fn received(&self) -> Vec<Header> {
+        self.state().received.clone()
+    }
+
+    /// Returns the descriptions of the unexpected requests and frames received so far.
+    ///
+    /// The connection is dropped on the first failure.
+    pub fn failures(&self) -> Vec<String> {
+        self.state().failures.clone()
+    }
+
+    /// Returns whether the script dropped the connection, either by a [`MockStep::disconnect`] or on a failure.
+    pub fn is_disconnected(&self) -> bool {
+        self.state().disconnected
+    }
+
+    /// Panics if a request did not match the script, or if some steps were not reached.
+    pub fn assert_done(&self) {
+        let state = self.state();
+        assert!(
+            state.failures.is_empty(),
+            "mock transport failures: {:?}",
+            state.failures
+        );
+        assert!(
+            state.steps.is_empty(),
+            "mock transport steps were not reached: {:?}",
+            state.steps
+        );
+    }
+}
+
+/// The writing half of [`MockTransport`]: handles the requests of the client, according to the script.
+pub struct MockWriter {
+    state: Arc<Mutex<MockState>>,
+    events: EventSender,
+    /// Bytes of the frame that is being sent.
+    pending: Vec<u8>,
+}
+
+impl MockWriter {
+    fn on_data(&mut self, buf: &[u8]) -> Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.disconnected {
+            return Err(TransportError::NotConnected);
+        }
+
+        self.pending.extend_from_slice(buf);
+        while self.pending.len() >= SmbTcpMessageHeader::SIZE {
+            let header = SmbTcpMessageHeader::read(&mut Cursor::new(&self.pending))?;
+            let length = SmbTcpMessageHeader::SIZE + header.stream_protocol_length as usize;
+            if self.pending.len() < length {
+                break;
+            }
+            let frame = self.pending.drain(..length).collect::<Vec<_>>();
+            if let Err(failure) = self.on_frame(&mut state, &frame[SmbTcpMessageHeader::SIZE..]) {
+                log::error!("Mock transport: {failure}");
+                state.failures.push(failure);
+                self.drop_connection(&mut state);
+            }
+            if state.disconnected {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles the requests of a single frame, which may be compounded.
+    fn on_frame(&self, state: &mut MockState, frame: &[u8]) -> std::result::Result<(), String> {
+        if !frame.starts_with(b"\xfeSMB") {
+            return Err(format!(
+                "unsupported frame, with protocol ID {:02x?}",
+                &frame[..frame.len().min(4)]
+            ));
+        }
+
+        let mut offset = 0;
+        while offset < frame.len() && !state.disconnected {
+            let request = PlainRequest::read(&mut Cursor::new(&frame[offset..]))
+                .map_err(|e| format!("failed to parse request: {e}"))?;
+            let next_command = request.header.next_command as usize;
+            self.on_request(state, request)?;
+            match next_command {
+                0 => break,
+                next => offset += next,
+            }
+        }
+        Ok(())
+    }
+
+    fn on_request(
+        &self,
+        state: &mut MockState,
+        request: PlainRequest,
+    ) -> std::result::Result<(), String> {
+        let header = &request.header;
+        let mut step = state.steps.pop_front().ok_or_else(|| {
+            format!(
+                "unexpected {} request (message ID {}): the script is done",
+                header.command, header.message_id
+            )
+        })?;
+        if let Some(mismatch) = step.mismatch(&request) {
+            return Err(format!("{mismatch} (message ID {})", header.message_id));
+        }
+        log::trace!(
+            "Mock transport: {} request (message ID {}) matched.",
+            header.command,
+            header.message_id
+        );
+        state.received.push(request.header.clone());
+
+        let interim = step
+            .interim_frame(&request.header)
+            .map_err(|e| format!("failed to write the interim response: {e}"))?;
+        if let Some(data) = interim {
+            self.send_event(MockEvent::Frame {
+                delay: Duration::ZERO,
+                data,
+            });
+        }
+        let frame = step
+            .response_frame(&request.header)
+            .map_err(|e| format!("failed to write the response: {e}"))?;
+        if let Some(data) = frame {
+            let event = MockEvent::Frame {
+                delay: step.delay,
+                data,
+            };
+            if step.deferred {
+                state.deferred.push(event);
+            } else {
+                self.send_event(event);
+                for event in std::mem::take(&mut state.deferred) {
+                    self.send_event(event);
+                }
+            }
+        }
+        if step.disconnect {
+            self.drop_connection(state);
+        }
+        Ok(())
+    }
+
+    fn drop_connection(&self, state: &mut MockState) {
+        state.disconnected = true;
+        state.deferred.clear();
+        self.send_event(MockEvent::Disconnect);
+    }
+
+    fn send_event(&self, event: MockEvent) {
+        // The reader may be gone already, if the client stopped reading.
+        let _ = self.events.send(event);
+    }
+}
+
+impl SmbTransportWrite for MockWriter {
+    #[cfg(feature = "async")]
+    fn send_raw<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        async { self.on_data(buf) }.boxed()
+    }
+    #[cfg(not(feature = "async"))]
+    fn send_raw(&mut self, buf: &[u8]) -> Result<()> {
+        self.on_data(buf)
+    }
+}
+
+/// The reading half of [`MockTransport`]: delivers the responses of the script to the client.
+pub struct MockReader {
+    events: EventReceiver,
+    /// Bytes of the delivered responses, that the client did not read yet.
+    pending: Vec<u8>,
+    disconnected: bool,
+    #[cfg(not(feature = "async"))]
+    read_timeout: std::sync::Mutex<Option<Duration>>,
+    /// A received frame that is not delivered yet, and the time it is delivered at.
+    #[cfg(not(feature = "async"))]
+    delayed: Option<(std::time::Instant, Vec<u8>)>,
+}
+
+impl MockReader {
+    /// Takes the read bytes out of the delivered ones, once there are enough of them.
+    fn take_pending(&mut self, out_buf: &mut [u8]) -> bool {
+        if self.pending.len() < out_buf.len() {
+            return false;
+        }
+        out_buf.copy_from_slice(&self.pending[..out_buf.len()]);
+        self.pending.drain(..out_buf.len());
+        true
+    }
+
+    /// Handles an event of the writer, and returns an error if the connection was dropped.
+    fn on_event(&mut self, event: Option<MockEvent>) -> Result<Duration> {
+        match event {
+            Some(MockEvent::Frame { delay, data }) => {
+                self.pending.extend(data);
+                Ok(delay)
+            }
+            // The writer was dropped, or the script dropped the connection.
+            Some(MockEvent::Disconnect) | None => {
+                self.disconnected = true;
+                Err(TransportError::NotConnected)
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn receive_exact(&mut self, out_buf: &mut [u8]) -> Result<()> {
+        while !self.take_pending(out_buf) {
+            if self.disconnected {
+                return Err(TransportError::NotConnected);
+            }
+            let event = self.events.recv().await;
+            let delay = self.on_event(event)?;
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn receive_exact(&mut self, out_buf: &mut [u8]) -> Result<()> {
+        use std::sync::mpsc::RecvTimeoutError;
+        use std::time::Instant;
+
+        while !self.take_pending(out_buf) {
+            if self.disconnected {
+                return Err(TransportError::NotConnected);
+            }
+            let timeout = *self.read_timeout.lock().unwrap_or_else(|e| e.into_inner());
+            let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+            if self.delayed.is_none() {
+                let event = match timeout {
+                    Some(timeout) => match self.events.recv_timeout(timeout) {
+                        Ok(event) => Some(event),
+                        // Frames are delivered whole, so nothing was read yet.
+                        Err(RecvTimeoutError::Timeout) => {
+                            return Err(std::io::Error::from(std::io::ErrorKind::WouldBlock).into());
+                        }
+                        Err(RecvTimeoutError::Disconnected) => None,
+                    },
+                    None => self.events.recv().ok(),
+                };
+                match event {
+                    Some(MockEvent::Frame { delay, data }) => {
+                        self.delayed = Some((Instant::now() + delay, data));
+                    }
+                    event => {
+                        self.on_event(event)?;
+                    }
+                }
+            }
+
+            // A frame delayed beyond the read timeout stays undelivered, and the read times out.
+            let (ready_at, _) = self.delayed.as_ref().unwrap();
+            let ready_at = *ready_at;
+            if let Some(deadline) = deadline.filter(|&deadline| deadline < ready_at) {
+                std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                return Err(std::io::Error::from(std::io::ErrorKind::WouldBlock).into());
+            }
+            std::thread::sleep(ready_at.saturating_duration_since(Instant::now()));
+            let (_, data) = self.delayed.take().unwrap();
+            self.on_event(Some(MockEvent::Frame {
+                delay: Duration::ZERO,
+                data,
+            }))?;
+        }
+        Ok(())
+    }
+}
+
+impl SmbTransportRead for MockReader {
+    #[cfg(feature = "async")]
+    fn receive_exact<'a>(&'a mut self, out_buf: &'a mut [u8]) -> BoxFuture<'a, Result<()>> {
+        self.receive_exact(out_buf).boxed()
+    }
+    #[cfg(not(feature = "async"))]
+    fn receive_exact(&mut self, out_buf: &mut [u8]) -> Result<()> {
+        self.receive_exact(out_buf)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn set_read_timeout(&self, timeout: Duration) -> Result<()> {
+        *self.read_timeout.lock().unwrap_or_else(|e| e.into_inner()) = Some(timeout);
+        Ok(())
+    }
+}
+
+/// An in-memory [`SmbTransport`], that replies to the requests of the client from a [`MockScript`].
+///
+/// The transport is connected once created, and [`SmbTransport::connect`] does nothing.
+/// See the [module documentation][self] for more information.
+pub struct MockTransport {
+    reader: Option<MockReader>,
+    writer: Option<MockWriter>,
+    address: SocketAddr,
+}
+
+impl MockTransport {
+    /// The address that [`SmbTransport::remote_address`] returns by default.
+    pub const DEFAULT_ADDRESS: SocketAddr = SocketAddr::new(
+        std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST),
+        Self::DEFAULT_PORT,
+    );
+    const DEFAULT_PORT: u16 = 445;
+
+    /// Creates a transport that follows `script`, and a handle to inspect its progress.
+    pub fn new(script: MockScript) -> (Self, MockHandle) {
+        let state = Arc::new(Mutex::new(MockState {
+            steps: script.steps,
+            ..Default::default()
+        }));
+        #[cfg(feature = "async")]
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        #[cfg(not(feature = "async"))]
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        let transport = Self {
+            reader: Some(MockReader {
+                events: receiver,
+                pending: vec![],
+                disconnected: false,
+                #[cfg(not(feature = "async"))]
+                read_timeout: Default::default(),
+                #[cfg(not(feature = "async"))]
+                delayed: None,
+            }),
+            writer: Some(MockWriter {
+                state: state.clone(),
+                events: sender,
+                pending: vec![],
+            }),
+            address: Self::DEFAULT_ADDRESS,
+        };
+        (transport, MockHandle { state })
+    }
+
+    /// Sets the address that [`SmbTransport::remote_address`] returns.
+    pub fn with_address(mut self, address: SocketAddr) -> Self {
+        self.address = address;
+        self
+    }
+
+    fn reader(&mut self) -> Result<&mut MockReader> {
+        self.reader.as_mut().ok_or(TransportError::AlreadySplit)
+    }
+
+    fn writer(&mut self) -> Result<&mut MockWriter> {
+        self.writer.as_mut().ok_or(TransportError::AlreadySplit)
+    }
+}
+
+impl SmbTransport for MockTransport {
+    #[cfg(feature = "async")]
+    fn connect<'a>(
+        &'a mut self,
+        _server_name: &'a str,
+        _address: SocketAddr,
+    ) -> BoxFuture<'a, Result<()>> {
+        async { Ok(()) }.boxed()
+    }
+    #[cfg(not(feature = "async"))]
+    fn connect(&mut self, _server_name: &str, _address: SocketAddr) -> Result<()> {
+        Ok(())
+    }
+
+    fn default_port(&self) -> u16 {
+        Self::DEFAULT_PORT
+    }
+
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn split(self: Box<Self>) -> Result<(Box<dyn SmbTransportRead>, Box<dyn SmbTransportWrite>)> {
+        match (self.reader, self.writer) {
+            (Some(reader), Some(writer)) => Ok((Box::new(reader), Box::new(writer))),
+            _ => Err(TransportError::AlreadySplit),
+        }
+    }
+
+    fn remote_address(&self) -> Result<SocketAddr> {
+        Ok(self.address)
+    }
+}
+
+impl SmbTransportWrite for MockTransport {
+    #[cfg(feature = "async")]
+    fn send_raw<'a>(&'a mut self, buf: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        async { self.writer()?.on_data(buf) }.boxed()
+    }
+    #[cfg(not(feature = "async"))]
+    fn send_raw(&mut self, buf: &[u8]) -> Result<()> {
+        self.writer()?.on_data(buf)
+    }
+}
+
+impl SmbTransportRead for MockTransport {
+    #[cfg(feature = "async")]
+    fn receive_exact<'a>(&'a mut self, out_buf: &'a mut [u8]) -> BoxFuture<'a, Result<()>> {
+        async { self.reader()?.receive_exact(out_buf).await }.boxed()
+    }
+    #[cfg(not(feature = "async"))]
+    fn receive_exact(&mut self, out_buf: &mut [u8]) -> Result<()> {
+        self.reader()?.receive_exact(out_buf)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn set_read_timeout(&self, timeout: Duration) -> Result<()> {
+        self.reader
+            .as_ref()
+            .ok_or(TransportError::AlreadySplit)?
+            .set_read_timeout(timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IoVec;
+    use smb_msg::{EchoRequest, EchoResponse, Response};
+
+    /// Returns an ECHO request frame, without the transport header.
+    fn echo_request(message_id: u64) -> Vec<u8> {
+        let mut request = PlainRequest::new(EchoRequest::default().into());
+        request.header.message_id = message_id;
+        request.header.credit_request = 4;
+        let mut data = Cursor::new(vec![]);
+        request.write(&mut data).unwrap();
+        data.into_inner()
+    }
+
+    #[maybe_async::maybe_async]
+    async fn receive(transport: &mut dyn SmbTransport) -> Result<Header> {
+        let data = transport.receive().await?;
+        match Response::try_from(data.as_slice())? {
+            Response::Plain(response) => Ok(response.header),
+            _ => panic!("expected a plain response"),
+        }
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_response_matches_request() {
+        let script = MockScript::new().step(
+            MockStep::expect(Command::Echo)
+                .matching("message ID 7", |r| r.header.message_id == 7)
+                .respond(EchoResponse::default())
+                .session_id(0x11),
+        );
+        let (transport, handle) = MockTransport::new(script);
+        let mut transport: Box<dyn SmbTransport> = Box::new(transport);
+
+        // The frame is sent in parts, like by the client.
+        transport.send(&IoVec::from(echo_request(7))).await.unwrap();
+        let header = receive(transport.as_mut()).await.unwrap();
+        assert_eq!(header.command, Command::Echo);
+        assert_eq!(header.message_id, 7);
+        assert_eq!(header.credit_request, 4);
+        assert_eq!(header.session_id, 0x11);
+        assert_eq!(header.status, Status::Success as u32);
+        assert!(header.flags.server_to_redir());
+
+        assert_eq!(handle.received().len(), 1);
+        handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_deferred_response() {
+        let script = MockScript::new()
+            .step(
+                MockStep::expect(Command::Echo)
+                    .respond(EchoResponse::default())
+                    .deferred(),
+            )
+            .step(
+                MockStep::expect(Command::Echo)
+                    .respond_error(Status::AccessDenied)
+                    .delay(Duration::from_millis(10)),
+            );
+        let (transport, handle) = MockTransport::new(script);
+        let mut transport: Box<dyn SmbTransport> = Box::new(transport);
+
+        // Both requests in a single, compounded frame.
+        let mut first = echo_request(1);
+        let next_command = first.len() as u32;
+        first[20..24].copy_from_slice(&next_command.to_le_bytes());
+        first.extend(echo_request(2));
+        transport.send(&IoVec::from(first)).await.unwrap();
+
+        let second = receive(transport.as_mut()).await.unwrap();
+        assert_eq!(second.message_id, 2);
+        assert_eq!(second.status, Status::AccessDenied as u32);
+        let first = receive(transport.as_mut()).await.unwrap();
+        assert_eq!(first.message_id, 1);
+        assert_eq!(first.status, Status::Success as u32);
+        handle.assert_done();
+    }
+
+    /// A response delayed beyond the read timeout is not delivered by the read that times out, but by a later one.
+    #[cfg(not(feature = "async"))]
+    #[test]
+    fn test_delay_beyond_read_timeout() {
+        let script = MockScript::new().step(
+            MockStep::expect(Command::Echo)
+                .respond(EchoResponse::default())
+                .delay(Duration::from_millis(300)),
+        );
+        let (transport, handle) = MockTransport::new(script);
+        let mut transport: Box<dyn SmbTransport> = Box::new(transport);
+        transport
+            .set_read_timeout(Duration::from_millis(50))
+            .unwrap();
+
+        transport.send(&IoVec::from(echo_request(1))).unwrap();
+        let start = std::time::Instant::now();
+        assert!(receive(transport.as_mut()).is_err());
+        assert!(start.elapsed() < Duration::from_millis(300));
+
+        transport.set_read_timeout(Duration::from_secs(5)).unwrap();
+        let header = receive(transport.as_mut()).unwrap();
+        assert_eq!(header.message_id, 1);
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_interim_response() {
+        let script = MockScript::new().step(
+            MockStep::expect(Command::Echo)
+                .respond(EchoResponse::default())
+                .interim(0x55),
+        );
+        let (transport, handle) = MockTransport::new(script);
+        let mut transport: Box<dyn SmbTransport> = Box::new(transport);
+
+        transport.send(&IoVec::from(echo_request(3))).await.unwrap();
+        for status in [Status::Pending, Status::Success] {
+            let header = receive(transport.as_mut()).await.unwrap();
+            assert_eq!(header.message_id, 3);
+            assert_eq!(header.status, status as u32);
+            assert!(header.flags.async_command());
+            assert_eq!(header.async_id, Some(0x55));
+        }
+        handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_disconnect() {
+        let script = MockScript::new()
+            .step(MockStep::expect(Command::Echo).disconnect())
+            .step(MockStep::expect(Command::Echo).respond(EchoResponse::default()));
+        let (transport, handle) = MockTransport::new(script);
+        let mut transport: Box<dyn SmbTransport> = Box::new(transport);
+
+        transport.send(&IoVec::from(echo_request(1))).await.unwrap();
+        let result = receive(transport.as_mut()).await;
+        assert!(matches!(result, Err(TransportError::NotConnected)));
+        let result = transport.send(&IoVec::from(echo_request(2))).await;
+        assert!(matches!(result, Err(TransportError::NotConnected)));
+        assert!(handle.is_disconnected());
+        assert_eq!(handle.remaining(), 1);
+        assert!(handle.failures().is_empty());
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_unexpected_request() {
+        let script = MockScript::new()
+            .step(MockStep::expect(Command::Negotiate).respond_error(Status::NotSupported));
+        let (transport, handle) = MockTransport::new(script);
+        let mut transport: Box<dyn SmbTransport> = Box::new(transport);
+
+        transport.send(&IoVec::from(echo_request(1))).await.unwrap();
+        let result = receive(transport.as_mut()).await;
+        assert!(matches!(result, Err(TransportError::NotConnected)));
+        assert_eq!(
+            handle.failures(),
+            ["expected a Negotiate (0x0) request, got Echo (0xd) (message ID 1)"]
+        );
+    }
+}
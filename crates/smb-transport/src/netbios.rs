@@ -4,6 +4,9 @@
 //! It is enabled by the `netbios-transport` feature flag.
 
 mod msg;
+mod name_service;
 mod transport;
 
+pub use msg::{NBSSNegativeSessionResponseErrorCode, NetBiosName};
+pub use name_service::NetBiosNameResolver;
 pub use transport::NetBiosTransport;
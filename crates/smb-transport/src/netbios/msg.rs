@@ -64,8 +64,9 @@ pub struct NBSessionRequest {
     pub calling_name: NetBiosName,
 }
 
-/// Represents a NetBIOS name.
-#[derive(Debug, PartialEq, Eq)]
+/// Represents a NetBIOS name: up to 15 characters, padded with spaces, followed by a suffix byte
+/// that specifies the type of the service the name belongs to.
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct NetBiosName {
     name: String,
     suffix: u8,
@@ -76,6 +77,16 @@ impl NetBiosName {
     const SUB_TO_GET_NIBBLE: u8 = b'A';
     /// NetBIOS names are exactly 16 bytes long, including the suffix.
     const TOTAL_NAME_BYTES: usize = 15;
+    /// The size of a first-level encoded name: two characters for each of the 16 bytes of the name.
+    pub const ENCODED_SIZE: usize = (Self::TOTAL_NAME_BYTES + 1) * 2;
+
+    /// The suffix of the names of workstation services, used for the calling name.
+    pub const WORKSTATION_SUFFIX: u8 = 0x00;
+    /// The suffix of the names of file server services.
+    pub const SERVER_SUFFIX: u8 = 0x20;
+    /// The called name to use when the NetBIOS name of the server is unknown.
+    /// Most servers (Windows, Samba) accept it in place of their own name.
+    pub const ANY_SERVER: &str = "*SMBSERVER";
 
     pub fn new(mut name: String, suffix: u8) -> Self {
         // Pad to length
@@ -84,6 +95,53 @@ impl NetBiosName {
 
         NetBiosName { name, suffix }
     }
+
+    /// Returns the file server name to call, for the server name that is being connected.
+    ///
+    /// NetBIOS names are the upper-case first label of the host name, e.g. `FILESRV` for `filesrv.corp.local`.
+    /// If `server_name` is an IP address, or can't be a NetBIOS name, [`Self::ANY_SERVER`] is used instead.
+    pub fn for_server(server_name: &str) -> Self {
+        let label = server_name.split('.').next().unwrap_or_default();
+        let is_valid_name = !label.is_empty()
+            && label.len() <= Self::TOTAL_NAME_BYTES
+            && label.bytes().all(|c| c.is_ascii_graphic() && c != b':')
+            && server_name.parse::<std::net::IpAddr>().is_err();
+        match is_valid_name {
+            true => Self::new(label.to_ascii_uppercase(), Self::SERVER_SUFFIX),
+            false => Self::new(Self::ANY_SERVER.to_string(), Self::SERVER_SUFFIX),
+        }
+    }
+
+    /// Returns the name, without its padding.
+    pub fn name(&self) -> &str {
+        self.name.trim_end_matches(' ')
+    }
+
+    /// Returns the suffix (the 16th byte) of the name.
+    pub fn suffix(&self) -> u8 {
+        self.suffix
+    }
+
+    /// Returns the first-level encoding of the name (RFC 1001, section 14.1):
+    /// each of the 16 bytes of the padded name and suffix is split into two nibbles,
+    /// and each nibble is encoded as a character between `A` and `P`.
+    ///
+    /// Returns `None` if the name contains non-ASCII characters.
+    pub fn first_level_encode(&self) -> Option<[u8; Self::ENCODED_SIZE]> {
+        let mut encoded = [0u8; Self::ENCODED_SIZE];
+        let name_and_suffix = self
+            .name
+            .chars()
+            .chain(std::iter::once(self.suffix as char));
+        for (i, c) in name_and_suffix.enumerate() {
+            if c as u32 > 0x7F {
+                return None;
+            }
+            encoded[i * 2] = ((c as u8) >> 4) + Self::SUB_TO_GET_NIBBLE;
+            encoded[i * 2 + 1] = ((c as u8) & 0x0F) + Self::SUB_TO_GET_NIBBLE;
+        }
+        Some(encoded)
+    }
 }
 
 impl std::fmt::Display for NetBiosName {
@@ -162,33 +220,14 @@ impl BinWrite for NetBiosName {
         endian: binrw::Endian,
         _args: Self::Args<'_>,
     ) -> BinResult<()> {
-        let number_of_bytes = self.name.len() * 2 + 1 + 1; // +1 for suffix and +1 for null byte
-        let length_to_write = number_of_bytes as u8;
-        u8::write_options(&length_to_write, writer, endian, ())?;
-
-        let name_and_suffix = self
-            .name
-            .chars()
-            .chain(std::iter::once(self.suffix as char));
-
-        // First-level encoding
-        for c in name_and_suffix {
-            if c as u8 > 0x7F {
-                return Err(binrw::Error::AssertFail {
-                    pos: writer.stream_position().unwrap(),
-                    message: "NetBiosName contains non-ASCII characters".to_string(),
-                });
-            }
-
-            let upper_nibble = (c as u8) >> 4;
-            let lower_nibble = (c as u8) & 0x0F;
-
-            let first_char = upper_nibble + Self::SUB_TO_GET_NIBBLE;
-            let second_char = lower_nibble + Self::SUB_TO_GET_NIBBLE;
-
-            u8::write_options(&first_char, writer, endian, ())?;
-            u8::write_options(&second_char, writer, endian, ())?;
-        }
+        let encoded = self
+            .first_level_encode()
+            .ok_or_else(|| binrw::Error::AssertFail {
+                pos: writer.stream_position().unwrap(),
+                message: "NetBiosName contains non-ASCII characters".to_string(),
+            })?;
+        u8::write_options(&(encoded.len() as u8), writer, endian, ())?;
+        encoded.write_options(writer, endian, ())?;
         // Write null byte at the end
         u8::write_options(&0x00u8, writer, endian, ())?;
         Ok(())
@@ -246,8 +285,9 @@ pub struct NBNegativeSessionResponse {
     pub error_code: NBSSNegativeSessionResponseErrorCode,
 }
 
+/// The error code of a NetBIOS negative session response (RFC 1002, section 4.3.4).
 #[binrw::binrw]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[brw(big, repr(u8))]
 pub enum NBSSNegativeSessionResponseErrorCode {
     NotListeningOnCalledName = 0x80,
@@ -257,6 +297,19 @@ pub enum NBSSNegativeSessionResponseErrorCode {
     UnspecifiedError = 0x8F,
 }
 
+impl std::fmt::Display for NBSSNegativeSessionResponseErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            Self::NotListeningOnCalledName => "not listening on called name",
+            Self::NotListeningForCallingName => "not listening for calling name",
+            Self::CalledNameNotPresent => "called name not present",
+            Self::InsufficientResources => "insufficient resources",
+            Self::UnspecifiedError => "unspecified error",
+        };
+        write!(f, "{description} ({:#04x})", *self as u8)
+    }
+}
+
 #[binrw::binrw]
 #[derive(Debug)]
 #[brw(big)]
@@ -284,6 +337,26 @@ mod tests {
         45424544454345504550454c464146434550434e4542454744494542414100"
     }
 
+    #[test]
+    fn test_netbios_name_for_server() {
+        let name = NetBiosName::for_server("filesrv.corp.local");
+        assert_eq!(
+            (name.name(), name.suffix()),
+            ("FILESRV", NetBiosName::SERVER_SUFFIX)
+        );
+        assert_eq!(
+            &name.first_level_encode().unwrap(),
+            b"EGEJEMEFFDFCFGCACACACACACACACACA"
+        );
+
+        for server_name in ["10.0.0.1", "fe80::1", "", "averyveryverylongname"] {
+            assert_eq!(
+                NetBiosName::for_server(server_name).name(),
+                NetBiosName::ANY_SERVER
+            );
+        }
+    }
+
     test_binrw! {
         struct NBSSPacketHeader {
             ptype: NBSSPacketType::PositiveSessionResponse,
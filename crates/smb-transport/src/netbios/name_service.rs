@@ -0,0 +1,309 @@
+//! NetBIOS Name Service (NBNS) name resolution (RFC 1002, section 4.2).
+//!
+//! Old devices are often known by their NetBIOS name only, which DNS can't resolve.
+//! [`NetBiosNameResolver`] resolves such names to IPv4 addresses, by broadcasting a name query
+//! on the local network, or by querying a NetBIOS name server (WINS).
+
+use std::io::Cursor;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use binrw::prelude::*;
+use maybe_async::*;
+
+use super::msg::NetBiosName;
+use crate::{TransportError, error::Result};
+
+/// A NetBIOS name query request (RFC 1002, section 4.2.12).
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq)]
+#[brw(big)]
+pub struct NBNSNameQueryRequest {
+    pub transaction_id: u16,
+    pub flags: u16,
+    #[bw(calc = 1)]
+    #[br(assert(_question_count == 1))]
+    _question_count: u16,
+    #[bw(calc = 0)]
+    #[br(assert(_answer_count == 0))]
+    _answer_count: u16,
+    #[bw(calc = 0)]
+    #[br(assert(_authority_count == 0))]
+    _authority_count: u16,
+    #[bw(calc = 0)]
+    #[br(assert(_additional_count == 0))]
+    _additional_count: u16,
+    pub question_name: NetBiosName,
+    #[bw(calc = NBNSNameQueryRequest::QUESTION_TYPE_NB)]
+    #[br(assert(_question_type == NBNSNameQueryRequest::QUESTION_TYPE_NB))]
+    _question_type: u16,
+    #[bw(calc = NBNSNameQueryRequest::QUESTION_CLASS_IN)]
+    #[br(assert(_question_class == NBNSNameQueryRequest::QUESTION_CLASS_IN))]
+    _question_class: u16,
+}
+
+impl NBNSNameQueryRequest {
+    /// The recursion desired (RD) flag.
+    pub const FLAGS_RECURSION_DESIRED: u16 = 0x0100;
+    /// The broadcast (B) flag, set for queries that are broadcast on the local network.
+    pub const FLAGS_BROADCAST: u16 = 0x0010;
+
+    const QUESTION_TYPE_NB: u16 = 0x0020;
+    const QUESTION_CLASS_IN: u16 = 0x0001;
+}
+
+/// A NetBIOS name query response (RFC 1002, sections 4.2.13 and 4.2.14).
+///
+/// Negative responses have a non-zero [`rcode`][Self::rcode], and no answer.
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq)]
+#[brw(big)]
+pub struct NBNSNameQueryResponse {
+    pub transaction_id: u16,
+    pub flags: u16,
+    #[bw(calc = 0)]
+    _question_count: u16,
+    #[bw(calc = answer.is_some() as u16)]
+    _answer_count: u16,
+    #[bw(calc = 0)]
+    _authority_count: u16,
+    #[bw(calc = 0)]
+    _additional_count: u16,
+    #[br(if(flags & Self::RCODE_MASK == 0 && _answer_count > 0))]
+    pub answer: Option<NBNSAddressRecord>,
+}
+
+impl NBNSNameQueryResponse {
+    /// The response (R) flag, set for all responses.
+    pub const FLAGS_RESPONSE: u16 = 0x8000;
+    const RCODE_MASK: u16 = 0x000F;
+
+    /// Returns the result code of the response. Zero for positive responses.
+    pub fn rcode(&self) -> u8 {
+        (self.flags & Self::RCODE_MASK) as u8
+    }
+}
+
+/// The answer resource record of a positive name query response, holding the addresses of the name.
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq)]
+#[brw(big)]
+pub struct NBNSAddressRecord {
+    pub name: NetBiosName,
+    #[bw(calc = NBNSNameQueryRequest::QUESTION_TYPE_NB)]
+    _rr_type: u16,
+    #[bw(calc = NBNSNameQueryRequest::QUESTION_CLASS_IN)]
+    _rr_class: u16,
+    pub ttl: u32,
+    #[bw(try_calc = (addresses.len() * NBNSAddressEntry::SIZE).try_into())]
+    _rd_length: u16,
+    #[br(count = _rd_length as usize / NBNSAddressEntry::SIZE)]
+    pub addresses: Vec<NBNSAddressEntry>,
+}
+
+/// An address of a NetBIOS name, in a name query response.
+#[binrw::binrw]
+#[derive(Debug, PartialEq, Eq)]
+#[brw(big)]
+pub struct NBNSAddressEntry {
+    pub flags: u16,
+    #[br(map = |octets: [u8; 4]| Ipv4Addr::from(octets))]
+    #[bw(map = |address: &Ipv4Addr| address.octets())]
+    pub address: Ipv4Addr,
+}
+
+impl NBNSAddressEntry {
+    const SIZE: usize = 6;
+}
+
+#[cfg(feature = "async")]
+type UdpSocket = tokio::net::UdpSocket;
+#[cfg(not(feature = "async"))]
+type UdpSocket = std::net::UdpSocket;
+
+/// Resolves NetBIOS names to IPv4 addresses, using NetBIOS name queries.
+///
+/// ```no_run
+/// # use smb_transport::{NetBiosName, NetBiosNameResolver};
+/// # use std::time::Duration;
+/// # async fn resolve() -> smb_transport::error::Result<()> {
+/// let resolver = NetBiosNameResolver::broadcast(Duration::from_secs(2));
+/// let address = resolver.resolve(&NetBiosName::for_server("filesrv")).await?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct NetBiosNameResolver {
+    name_server: SocketAddr,
+    timeout: Duration,
+}
+
+impl NetBiosNameResolver {
+    /// The UDP port of the NetBIOS name service.
+    pub const PORT: u16 = 137;
+
+    /// Creates a resolver that broadcasts its queries on the local network.
+    ///
+    /// A `timeout` of zero waits for a response forever.
+    pub fn broadcast(timeout: Duration) -> Self {
+        Self::with_name_server(Ipv4Addr::BROADCAST, timeout)
+    }
+
+    /// Creates a resolver that queries the NetBIOS name server (WINS) at `name_server`.
+    ///
+    /// A `timeout` of zero waits for a response forever.
+    pub fn with_name_server(name_server: Ipv4Addr, timeout: Duration) -> Self {
+        Self {
+            name_server: SocketAddr::from((name_server, Self::PORT)),
+            timeout,
+        }
+    }
+
+    /// Resolves `name` to the first IPv4 address in the response of the server that owns it.
+    ///
+    /// Returns [`TransportError::InvalidAddress`] if the name is not registered,
+    /// and [`TransportError::Timeout`] if no response arrives in time.
+    #[maybe_async]
+    pub async fn resolve(&self, name: &NetBiosName) -> Result<Ipv4Addr> {
+        let transaction_id = Self::transaction_id();
+        let mut flags = NBNSNameQueryRequest::FLAGS_RECURSION_DESIRED;
+        if self.is_broadcast() {
+            flags |= NBNSNameQueryRequest::FLAGS_BROADCAST;
+        }
+        let request = NBNSNameQueryRequest {
+            transaction_id,
+            flags,
+            question_name: name.clone(),
+        };
+        let mut request_buf = Cursor::new(vec![]);
+        request.write(&mut request_buf)?;
+
+        log::debug!("Resolving NetBIOS name {name} using {}", self.name_server);
+        let socket = self.bind().await?;
+        socket
+            .send_to(request_buf.get_ref(), self.name_server)
+            .await?;
+
+        let mut response_buf = [0u8; 576];
+        loop {
+            let (length, from) = self.receive(&socket, &mut response_buf).await?;
+            let response =
+                match NBNSNameQueryResponse::read(&mut Cursor::new(&response_buf[..length])) {
+                    Ok(response)
+                        if response.transaction_id == transaction_id
+                            && response.flags & NBNSNameQueryResponse::FLAGS_RESPONSE != 0 =>
+                    {
+                        response
+                    }
+                    // Unrelated datagrams are ignored, e.g. queries of other hosts, in broadcast mode.
+                    _ => continue,
+                };
+            log::debug!("NetBIOS name query response from {from}: {response:?}");
+            let address = response
+                .answer
+                .as_ref()
+                .and_then(|answer| answer.addresses.first());
+            return match address {
+                Some(entry) => Ok(entry.address),
+                None => Err(TransportError::InvalidAddress(format!(
+                    "{name} (NetBIOS name query failed with rcode {})",
+                    response.rcode()
+                ))),
+            };
+        }
+    }
+
+    fn is_broadcast(&self) -> bool {
+        self.name_server.ip() == Ipv4Addr::BROADCAST
+    }
+
+    /// Returns a transaction ID that is unlikely to match the ID of a previous query.
+    fn transaction_id() -> u16 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos() as u16)
+    }
+
+    #[cfg(feature = "async")]
+    async fn bind(&self) -> Result<UdpSocket> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        socket.set_broadcast(self.is_broadcast())?;
+        Ok(socket)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn bind(&self) -> Result<UdpSocket> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(self.is_broadcast())?;
+        if self.timeout != Duration::ZERO {
+            socket.set_read_timeout(Some(self.timeout))?;
+        }
+        Ok(socket)
+    }
+
+    #[cfg(feature = "async")]
+    async fn receive(&self, socket: &UdpSocket, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        if self.timeout == Duration::ZERO {
+            return Ok(socket.recv_from(buf).await?);
+        }
+        tokio::time::timeout(self.timeout, socket.recv_from(buf))
+            .await
+            .map_err(|_| TransportError::Timeout(self.timeout))?
+            .map_err(Into::into)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn receive(&self, socket: &UdpSocket, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        socket.recv_from(buf).map_err(|e| match e.kind() {
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                TransportError::Timeout(self.timeout)
+            }
+            _ => e.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::net::Ipv4Addr;
+
+    use binrw::prelude::*;
+    use smb_tests::*;
+
+    use super::{NBNSAddressEntry, NBNSAddressRecord, NBNSNameQueryRequest, NBNSNameQueryResponse};
+    use crate::netbios::NetBiosName;
+
+    test_binrw! {
+        struct NBNSNameQueryRequest {
+            transaction_id: 0x1234,
+            flags: NBNSNameQueryRequest::FLAGS_RECURSION_DESIRED | NBNSNameQueryRequest::FLAGS_BROADCAST,
+            question_name: NetBiosName::for_server("filesrv"),
+        } => "123401100001000000000000204547454a454d454646444643464743414341434143414341434143414341
+        43410000200001"
+    }
+
+    test_binrw! {
+        struct NBNSNameQueryResponse {
+            transaction_id: 0x1234,
+            flags: 0x8500,
+            answer: Some(NBNSAddressRecord {
+                name: NetBiosName::for_server("filesrv"),
+                ttl: 300000,
+                addresses: vec![NBNSAddressEntry {
+                    flags: 0,
+                    address: Ipv4Addr::new(192, 168, 1, 20),
+                }],
+            }),
+        } => "123485000000000100000000204547454a454d454646444643464743414341434143414341434143414341
+        43410000200001000493e000060000c0a80114"
+    }
+
+    #[test]
+    fn test_negative_name_query_response() {
+        // Name error (rcode 3), without an answer.
+        let data = smb_tests::hex_to_u8_array!("123485830000000000000000");
+        let response = NBNSNameQueryResponse::read(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(response.rcode(), 3);
+        assert!(response.answer.is_none());
+    }
+}
@@ -1,7 +1,7 @@
 use std::{io::Cursor, net::SocketAddr, time::Duration};
 
 use super::msg::*;
-use crate::{TcpTransport, TransportError, traits::*};
+use crate::{TcpConnectOptions, TcpTransport, TransportError, traits::*};
 
 use binrw::{BinRead, BinWrite};
 #[cfg(feature = "async")]
@@ -15,31 +15,46 @@ pub struct NetBiosTransport {
 use crate::error::Result;
 
 impl NetBiosTransport {
+    /// The NetBIOS name the client calls from.
+    const CALLING_NAME: &str = "SmbClient";
+
     pub fn new(timeout: Duration) -> NetBiosTransport {
+        Self::with_tcp_options(timeout, TcpConnectOptions::default())
+    }
+
+    /// Creates a new NetBIOS transport, whose TCP connection uses the specified [`TcpConnectOptions`].
+    pub fn with_tcp_options(timeout: Duration, options: TcpConnectOptions) -> NetBiosTransport {
         NetBiosTransport {
-            tcp: Box::new(TcpTransport::new(timeout)),
+            tcp: Box::new(TcpTransport::with_options(timeout, options)),
         }
     }
 
-    /// Starts the underlying TCP connection, and sends NetBIOS session request and expects a session response.
+    /// Starts the underlying TCP connection to the first of `addresses` that accepts it,
+    /// and sends NetBIOS session request and expects a session response.
     #[maybe_async]
-    async fn do_connect(&mut self, server_name: &str, address: SocketAddr) -> Result<()> {
+    async fn do_connect(&mut self, server_name: &str, addresses: &[SocketAddr]) -> Result<()> {
         log::debug!("Connecting to NetBIOS Session services TCP...");
-        self.tcp.connect(server_name, address).await?;
+        self.tcp.connect_any(server_name, addresses).await?;
 
         log::info!("Performing NetBIOS session setup...");
-        self.netbios_session_setup().await?;
+        self.netbios_session_setup(server_name).await?;
 
         log::debug!("NetBIOS session setup completed.");
         Ok(())
     }
 
+    /// Sends the NetBIOS session request, calling the NetBIOS name of `server_name`
+    /// (see [`NetBiosName::for_server`]), and expects a positive session response.
     #[maybe_async]
-    async fn netbios_session_setup(&mut self) -> Result<()> {
+    async fn netbios_session_setup(&mut self, server_name: &str) -> Result<()> {
         let session_request = NBSessionRequest {
-            called_name: NetBiosName::new("*SMBSERVER".to_string(), 0x20),
-            calling_name: NetBiosName::new("SmbClient".to_string(), 0x0),
+            called_name: NetBiosName::for_server(server_name),
+            calling_name: NetBiosName::new(
+                Self::CALLING_NAME.to_string(),
+                NetBiosName::WORKSTATION_SUFFIX,
+            ),
         };
+        log::debug!("Calling NetBIOS name {}", session_request.called_name);
 
         let mut req_buf = Vec::new();
         session_request.write(&mut Cursor::new(&mut req_buf))?;
@@ -58,23 +73,30 @@ impl NetBiosTransport {
 
         log::debug!("Waiting for NetBIOS session response");
         let header = self.netbios_receive_header().await?;
-        let mut result_packet = Vec::with_capacity(header.length as usize);
+        let mut result_packet = vec![0u8; header.length as usize];
         self.tcp.receive_exact(&mut result_packet).await?;
 
         let nbss_packet =
             NBSSTrailer::read_args(&mut Cursor::new(&result_packet), (header.ptype,))?;
+        Self::check_session_response(nbss_packet)
+    }
 
-        match nbss_packet {
+    /// Returns an error for any session response but a positive one.
+    fn check_session_response(response: NBSSTrailer) -> Result<()> {
+        match response {
             NBSSTrailer::PositiveSessionResponse(_) => {
                 log::debug!("NetBIOS session request succeeded.");
+                Ok(())
+            }
+            NBSSTrailer::NegativeSessionResponse(response) => {
+                log::debug!("NetBIOS session request rejected: {}", response.error_code);
+                Err(TransportError::NetBiosSessionRejected(response.error_code))
             }
             x => {
                 log::debug!("NetBIOS session request invalid with packet: {:?}", x);
-                return Err(TransportError::InvalidMessage);
+                Err(TransportError::InvalidMessage)
             }
         }
-
-        Ok(())
     }
 
     #[maybe_async]
@@ -94,18 +116,36 @@ impl SmbTransport for NetBiosTransport {
         server_name: &'a str,
         address: SocketAddr,
     ) -> futures_core::future::BoxFuture<'a, Result<()>> {
-        self.do_connect(server_name, address).boxed()
+        async move { self.do_connect(server_name, &[address]).await }.boxed()
     }
 
     #[cfg(not(feature = "async"))]
     fn connect(&mut self, server_name: &str, address: SocketAddr) -> Result<()> {
-        self.do_connect(server_name, address)
+        self.do_connect(server_name, &[address])
+    }
+
+    #[cfg(feature = "async")]
+    fn connect_any<'a>(
+        &'a mut self,
+        server_name: &'a str,
+        addresses: &'a [SocketAddr],
+    ) -> futures_core::future::BoxFuture<'a, Result<()>> {
+        self.do_connect(server_name, addresses).boxed()
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn connect_any(&mut self, server_name: &str, addresses: &[SocketAddr]) -> Result<()> {
+        self.do_connect(server_name, addresses)
     }
 
     fn default_port(&self) -> u16 {
         139
     }
 
+    fn name(&self) -> &'static str {
+        "netbios"
+    }
+
     fn split(self: Box<Self>) -> Result<(Box<dyn SmbTransportRead>, Box<dyn SmbTransportWrite>)> {
         // SMB2 default transport (TCP) is actuall compatible with NetBIOS,
         // after setting up the session as performed in `connect()` above.
@@ -144,3 +184,36 @@ impl SmbTransportWrite for NetBiosTransport {
         self.tcp.send_raw(buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use binrw::BinRead;
+
+    use super::super::msg::*;
+    use super::NetBiosTransport;
+    use crate::TransportError;
+
+    #[test]
+    fn test_check_session_response() {
+        let positive = NBSSTrailer::read_args(
+            &mut Cursor::new(&[]),
+            (NBSSPacketType::PositiveSessionResponse,),
+        )
+        .unwrap();
+        assert!(NetBiosTransport::check_session_response(positive).is_ok());
+
+        let negative = NBSSTrailer::read_args(
+            &mut Cursor::new(&[0x82]),
+            (NBSSPacketType::NegativeSessionResponse,),
+        )
+        .unwrap();
+        assert!(matches!(
+            NetBiosTransport::check_session_response(negative),
+            Err(TransportError::NetBiosSessionRejected(
+                NBSSNegativeSessionResponseErrorCode::CalledNameNotPresent
+            ))
+        ));
+    }
+}
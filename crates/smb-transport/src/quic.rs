@@ -4,6 +4,6 @@ pub mod config;
 mod error;
 mod transport;
 
-pub use config::QuicConfig;
+pub use config::{QuicClientCertificate, QuicConfig};
 pub use error::QuicError;
 pub use transport::QuicTransport;
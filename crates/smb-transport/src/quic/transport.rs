@@ -16,6 +16,7 @@ use std::{
     time::Duration,
 };
 
+use super::config::{QuicCertValidationOptions, parse_certificates};
 use super::error::*;
 use crate::{
     QuicConfig, TransportError,
@@ -24,8 +25,7 @@ use crate::{
 use futures_core::future::BoxFuture;
 use futures_util::FutureExt;
 use quinn::{Endpoint, crypto::rustls::QuicClientConfig};
-use rustls::pki_types::CertificateDer;
-use rustls_platform_verifier::ConfigVerifierExt;
+use rustls_platform_verifier::BuilderVerifierExt;
 use tokio::select;
 
 pub struct QuicTransport {
@@ -36,12 +36,18 @@ pub struct QuicTransport {
 
     endpoint: Endpoint,
     timeout: Duration,
+    server_name_override: Option<String>,
 }
 
 const LOCALHOST_V4: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0));
 
 static CRYPTO_PROVIDER_INSTALLED: AtomicBool = AtomicBool::new(false);
 
+/// TLS alerts that indicate a rejected certificate (RFC 8446, section 6):
+/// bad_certificate, unsupported_certificate, certificate_revoked, certificate_expired,
+/// certificate_unknown, unknown_ca, access_denied and certificate_required.
+const CERTIFICATE_REJECTION_ALERTS: [u8; 8] = [42, 43, 44, 45, 46, 48, 49, 116];
+
 impl QuicTransport {
     pub fn new(quic_config: &QuicConfig, timeout: Duration) -> crate::error::Result<Self> {
         Self::_init_crypto_provider();
@@ -55,6 +61,7 @@ impl QuicTransport {
             remote_address: None,
             endpoint,
             timeout,
+            server_name_override: quic_config.server_name_override.clone(),
         })
     }
 
@@ -68,41 +75,66 @@ impl QuicTransport {
     }
 
     fn make_client_config(quic_config: &QuicConfig) -> Result<quinn::ClientConfig> {
-        let mut quic_client_config = match &quic_config.cert_validation {
-            super::config::QuicCertValidationOptions::PlatformVerifier => {
-                rustls::ClientConfig::with_platform_verifier()?
-            }
-            super::config::QuicCertValidationOptions::CustomRootCerts(items) => {
-                let mut roots = rustls::RootCertStore::empty();
-                for cert in items {
-                    match std::fs::read(cert) {
-                        Ok(cert) => {
-                            roots.add(CertificateDer::from(cert))?;
-                        }
-                        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
-                            log::info!("local server certificate not found");
-                        }
-                        Err(e) => {
-                            log::error!("failed to open local server certificate: {e}");
-                        }
-                    }
-                }
-                rustls::ClientConfig::builder()
-                    .with_root_certificates(roots)
-                    .with_no_client_auth()
+        let builder = rustls::ClientConfig::builder();
+        let builder = match &quic_config.cert_validation {
+            QuicCertValidationOptions::PlatformVerifier => builder.with_platform_verifier()?,
+            QuicCertValidationOptions::CustomRootCerts(items) => {
+                builder.with_root_certificates(Self::load_root_certs(items)?)
             }
         };
+        let mut quic_client_config = match &quic_config.client_certificate {
+            Some(client_certificate) => builder.with_client_auth_cert(
+                client_certificate.parse_certificate_chain()?,
+                client_certificate.parse_private_key()?,
+            )?,
+            None => builder.with_no_client_auth(),
+        };
         quic_client_config.alpn_protocols = vec![b"smb".to_vec()];
         Ok(quinn::ClientConfig::new(Arc::new(
             QuicClientConfig::try_from(quic_client_config)?,
         )))
     }
 
+    fn load_root_certs(items: &[String]) -> Result<rustls::RootCertStore> {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in items {
+            match std::fs::read(cert) {
+                Ok(cert) => {
+                    for cert in parse_certificates(&cert)? {
+                        roots.add(cert)?;
+                    }
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    log::info!("local server certificate not found");
+                }
+                Err(e) => {
+                    log::error!("failed to open local server certificate: {e}");
+                }
+            }
+        }
+        Ok(roots)
+    }
+
+    /// Returns whether the connection failed because either side rejected the certificate of the other.
+    fn is_certificate_rejection(error: &quinn::ConnectionError) -> bool {
+        let code = match error {
+            // Raised locally, e.g. when the server certificate fails validation.
+            quinn::ConnectionError::TransportError(e) => e.code,
+            // Sent by the server, e.g. when the client certificate is missing or not trusted.
+            quinn::ConnectionError::ConnectionClosed(close) => close.error_code,
+            _ => return false,
+        };
+        CERTIFICATE_REJECTION_ALERTS
+            .iter()
+            .any(|&alert| code == quinn::TransportErrorCode::crypto(alert))
+    }
+
     async fn inner_connect(
         &mut self,
         server_name: &str,
         server_address: SocketAddr,
     ) -> crate::error::Result<()> {
+        let server_name = self.server_name_override.as_deref().unwrap_or(server_name);
         let connection = self
             .endpoint
             .connect(server_address, server_name)
@@ -113,6 +145,12 @@ impl QuicTransport {
                     log::error!("Connection timed out after {:?}", self.timeout);
                     crate::TransportError::Timeout(self.timeout)
                 }
+                e if Self::is_certificate_rejection(&e) => {
+                    log::error!(
+                        "Certificate rejected connecting to {server_name} at {server_address}: {e}"
+                    );
+                    crate::TransportError::CertificateRejected(e.to_string())
+                }
                 _ => {
                     log::error!("Failed to connect to {server_name} at {server_address}: {e}");
                     QuicError::ConnectionError(e).into()
@@ -193,6 +231,7 @@ impl SmbTransport for QuicTransport {
                 remote_address: self.remote_address,
                 endpoint: self.endpoint,
                 timeout: self.timeout,
+                server_name_override: self.server_name_override.clone(),
             }),
             Box::new(Self {
                 recv_stream: None,
@@ -200,6 +239,7 @@ impl SmbTransport for QuicTransport {
                 remote_address: self.remote_address,
                 endpoint: endpoint_clone,
                 timeout: self.timeout,
+                server_name_override: self.server_name_override,
             }),
         ))
     }
@@ -208,6 +248,10 @@ impl SmbTransport for QuicTransport {
         443
     }
 
+    fn name(&self) -> &'static str {
+        "quic"
+    }
+
     fn remote_address(&self) -> crate::error::Result<SocketAddr> {
         self.remote_address.ok_or(TransportError::NotConnected)
     }
@@ -237,3 +281,27 @@ impl SmbTransportRead for QuicTransport {
         unimplemented!("QUIC transport requires async feature to be enabled");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_certificate_rejection() {
+        let closed = |alert| {
+            quinn::ConnectionError::ConnectionClosed(quinn::ConnectionClose {
+                error_code: quinn::TransportErrorCode::crypto(alert),
+                frame_type: None,
+                reason: Default::default(),
+            })
+        };
+        // certificate_required, unknown_ca
+        assert!(QuicTransport::is_certificate_rejection(&closed(116)));
+        assert!(QuicTransport::is_certificate_rejection(&closed(48)));
+        // handshake_failure
+        assert!(!QuicTransport::is_certificate_rejection(&closed(40)));
+        assert!(!QuicTransport::is_certificate_rejection(
+            &quinn::ConnectionError::TimedOut
+        ));
+    }
+}
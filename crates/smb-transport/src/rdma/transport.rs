@@ -572,6 +572,10 @@ impl SmbTransport for RdmaTransport {
         Self::DEFAULT_SMBDIRECT_PORT
     }
 
+    fn name(&self) -> &'static str {
+        "rdma"
+    }
+
     fn split(
         self: Box<Self>,
     ) -> crate::error::Result<(Box<dyn SmbTransportRead>, Box<dyn SmbTransportWrite>)> {
@@ -614,6 +618,14 @@ impl SmbTransportRead for RdmaTransport {
     ) -> futures_core::future::BoxFuture<'a, crate::error::Result<Vec<u8>>> {
         async { Ok(self._receive_fragmented_data().await?) }.boxed()
     }
+
+    /// Fragments are reassembled into a new buffer, so the pool is not used.
+    fn receive_pooled<'a>(
+        &'a mut self,
+        _pool: &'a crate::BufferPool,
+    ) -> futures_core::future::BoxFuture<'a, crate::error::Result<Vec<u8>>> {
+        self.receive()
+    }
 }
 
 impl SmbTransportWrite for RdmaTransport {
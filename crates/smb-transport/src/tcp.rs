@@ -1,3 +1,4 @@
+mod happy_eyeballs;
 pub mod msg;
 pub mod transport;
 
@@ -0,0 +1,253 @@
+//! Connecting to the first of multiple addresses of a server that accepts the connection,
+//! using staggered connection attempts, as in the Happy Eyeballs algorithm (RFC 8305, section 5).
+//!
+//! An attempt to the next address is started when the previous attempt fails, or when it does not
+//! complete within the attempt delay. The first attempt that succeeds wins, and the rest are abandoned.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::error::*;
+
+/// Orders `addresses` so that IPv6 and IPv4 addresses alternate, starting with the family of the first address
+/// (RFC 8305, section 4). The relative order of addresses of the same family is kept.
+pub(crate) fn interleave_families(addresses: &[SocketAddr]) -> Vec<SocketAddr> {
+    let Some(first) = addresses.first() else {
+        return vec![];
+    };
+    let (preferred, other): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses
+        .iter()
+        .copied()
+        .partition(|address| address.is_ipv6() == first.is_ipv6());
+    let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+
+    let mut interleaved = Vec::with_capacity(addresses.len());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return interleaved,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Connects to the first of `addresses` for which `connect` succeeds,
+/// starting the next attempt after `attempt_delay`, or once the previous attempts failed.
+///
+/// Returns the connection along with its address, or the errors of all of the attempts
+/// (see [`TransportError::connect_failed`]).
+#[cfg(feature = "async")]
+pub(crate) async fn connect_any<T, F, Fut>(
+    addresses: &[SocketAddr],
+    attempt_delay: Duration,
+    connect: F,
+) -> Result<(T, SocketAddr)>
+where
+    F: Fn(SocketAddr) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    use futures_util::stream::{FuturesUnordered, StreamExt};
+
+    let attempt = |address| {
+        let connecting = connect(address);
+        async move { (address, connecting.await) }
+    };
+    let mut remaining = addresses.iter().copied().peekable();
+    let mut attempts = FuturesUnordered::new();
+    let mut errors = Vec::new();
+    loop {
+        if attempts.is_empty() {
+            match remaining.next() {
+                Some(address) => attempts.push(attempt(address)),
+                None => break,
+            }
+        }
+
+        tokio::select! {
+            Some((address, result)) = attempts.next() => match result {
+                Ok(connected) => return Ok((connected, address)),
+                Err(e) => {
+                    log::debug!("Connecting to {address} failed: {e}");
+                    errors.push((address, e));
+                    if let Some(next) = remaining.next() {
+                        attempts.push(attempt(next));
+                    }
+                }
+            },
+            _ = tokio::time::sleep(attempt_delay), if remaining.peek().is_some() => {
+                let next = remaining.next().unwrap();
+                log::debug!("Not connected within {attempt_delay:?}, also connecting to {next}");
+                attempts.push(attempt(next));
+            }
+        }
+    }
+    Err(TransportError::connect_failed(errors))
+}
+
+/// Connects to the first of `addresses` for which `connect` succeeds,
+/// starting the next attempt after `attempt_delay`, or once the previous attempts failed.
+///
+/// Each attempt runs on its own thread, and abandoned attempts complete in the background.
+/// Returns the connection along with its address, or the errors of all of the attempts
+/// (see [`TransportError::connect_failed`]).
+#[cfg(not(feature = "async"))]
+pub(crate) fn connect_any<T, F>(
+    addresses: &[SocketAddr],
+    attempt_delay: Duration,
+    connect: F,
+) -> Result<(T, SocketAddr)>
+where
+    T: Send + 'static,
+    F: Fn(SocketAddr) -> Result<T> + Clone + Send + 'static,
+{
+    use std::sync::mpsc::{self, RecvTimeoutError};
+
+    let (sender, receiver) = mpsc::channel();
+    let attempt = |address: SocketAddr| {
+        let sender = sender.clone();
+        let connect = connect.clone();
+        std::thread::spawn(move || {
+            // The receiver is gone once another attempt won.
+            let _ = sender.send((address, connect(address)));
+        });
+    };
+    let mut remaining = addresses.iter().copied().peekable();
+    let mut in_flight = 0;
+    let mut errors = Vec::new();
+    loop {
+        if in_flight == 0 {
+            match remaining.next() {
+                Some(address) => attempt(address),
+                None => break,
+            }
+            in_flight += 1;
+        }
+
+        let (address, result) = if remaining.peek().is_some() {
+            match receiver.recv_timeout(attempt_delay) {
+                Ok(completed) => completed,
+                Err(RecvTimeoutError::Timeout) => {
+                    let next = remaining.next().unwrap();
+                    log::debug!(
+                        "Not connected within {attempt_delay:?}, also connecting to {next}"
+                    );
+                    attempt(next);
+                    in_flight += 1;
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => unreachable!("a sender is held here"),
+            }
+        } else {
+            receiver.recv().expect("a sender is held here")
+        };
+        in_flight -= 1;
+        match result {
+            Ok(connected) => return Ok((connected, address)),
+            Err(e) => {
+                log::debug!("Connecting to {address} failed: {e}");
+                errors.push((address, e));
+                if let Some(next) = remaining.next() {
+                    attempt(next);
+                    in_flight += 1;
+                }
+            }
+        }
+    }
+    Err(TransportError::connect_failed(errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::time::Instant;
+
+    const BLACKHOLED: SocketAddr = SocketAddr::new(
+        IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+        445,
+    );
+    const WORKING: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), 445);
+    const REFUSING: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3)), 445);
+
+    const ATTEMPT_DELAY: Duration = Duration::from_millis(50);
+    const BLACKHOLE_TIME: Duration = Duration::from_secs(5);
+
+    /// A connection stub: connecting to [`BLACKHOLED`] hangs, to [`REFUSING`] is refused,
+    /// and to any other address succeeds with that address.
+    #[maybe_async::maybe_async]
+    async fn stub_connect(address: SocketAddr) -> Result<SocketAddr> {
+        match address {
+            BLACKHOLED => {
+                blackhole().await;
+                Err(TransportError::Timeout(BLACKHOLE_TIME))
+            }
+            REFUSING => Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into()),
+            _ => Ok(address),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn blackhole() {
+        tokio::time::sleep(BLACKHOLE_TIME).await;
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn blackhole() {
+        std::thread::sleep(BLACKHOLE_TIME);
+    }
+
+    #[test]
+    fn test_interleave_families() {
+        let v6 = |i| {
+            SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, i)),
+                445,
+            )
+        };
+        let v4 = |i| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, i as u8)), 445);
+        assert_eq!(
+            interleave_families(&[v6(1), v6(2), v6(3), v4(1), v4(2)]),
+            [v6(1), v4(1), v6(2), v4(2), v6(3)]
+        );
+        assert_eq!(
+            interleave_families(&[v4(1), v6(1), v4(2)]),
+            [v4(1), v6(1), v4(2)]
+        );
+        assert_eq!(interleave_families(&[]), []);
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_connect_any_skips_blackholed() {
+        let start = Instant::now();
+        let (connected, address) = connect_any(&[BLACKHOLED, WORKING], ATTEMPT_DELAY, stub_connect)
+            .await
+            .unwrap();
+        assert_eq!((connected, address), (WORKING, WORKING));
+        assert!(start.elapsed() >= ATTEMPT_DELAY);
+        assert!(start.elapsed() < BLACKHOLE_TIME);
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_connect_any_failure_starts_next() {
+        let start = Instant::now();
+        let result = connect_any(&[REFUSING, WORKING], BLACKHOLE_TIME, stub_connect).await;
+        assert_eq!(result.unwrap().1, WORKING);
+        assert!(start.elapsed() < BLACKHOLE_TIME);
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_connect_any_aggregates_errors() {
+        let result = connect_any(&[REFUSING, REFUSING], ATTEMPT_DELAY, stub_connect).await;
+        match result {
+            Err(TransportError::ConnectFailed(errors)) => {
+                assert_eq!(errors.len(), 2);
+                assert!(errors.iter().all(|(address, error)| *address == REFUSING
+                    && matches!(error, TransportError::IoError(e) if e.kind() == std::io::ErrorKind::ConnectionRefused)));
+            }
+            _ => panic!("expected an aggregated error"),
+        }
+
+        // A single address fails with its own error.
+        let result = connect_any(&[REFUSING], ATTEMPT_DELAY, stub_connect).await;
+        assert!(matches!(result, Err(TransportError::IoError(_))));
+    }
+}
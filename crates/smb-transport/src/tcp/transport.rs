@@ -1,10 +1,11 @@
+use super::happy_eyeballs;
 use crate::error::*;
-use crate::{SmbTransport, SmbTransportRead, SmbTransportWrite};
+use crate::{SmbTransport, SmbTransportRead, SmbTransportWrite, TcpConnectOptions};
 
 #[cfg(feature = "async")]
 use futures_core::future::BoxFuture;
 use maybe_async::*;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 
 #[cfg(feature = "async")]
@@ -17,7 +18,7 @@ use std::{
 #[cfg(feature = "async")]
 use tokio::{
     io::{self, AsyncReadExt, AsyncWriteExt},
-    net::{TcpStream, tcp},
+    net::{TcpSocket, TcpStream, tcp},
     select,
 };
 
@@ -36,34 +37,40 @@ pub struct TcpTransport {
     reader: Option<TcpRead>,
     writer: Option<TcpWrite>,
     timeout: Duration,
+    options: TcpConnectOptions,
 }
 
 impl TcpTransport {
     pub const DEFAULT_PORT: u16 = 445;
 
     pub fn new(timeout: Duration) -> TcpTransport {
+        Self::with_options(timeout, TcpConnectOptions::default())
+    }
+
+    /// Creates a new TCP transport, that connects using the specified [`TcpConnectOptions`].
+    pub fn with_options(timeout: Duration, options: TcpConnectOptions) -> TcpTransport {
         TcpTransport {
             reader: None,
             writer: None,
             timeout,
+            options,
         }
     }
 
-    /// Connects to a NetBios server in the specified endpoint with a timeout.
-    /// This is the threaded version of [connect](NetBiosClient::connect) -
+    /// Connects to the specified endpoint with a timeout, from the specified local address, if any.
+    /// This is the threaded version of [connect](TcpTransport::connect) -
     /// using the [std::net::TcpStream] as the underlying socket provider.
     #[cfg(not(feature = "async"))]
-    fn connect_timeout(&mut self, endpoint: &SocketAddr) -> Result<TcpStream> {
-        if self.timeout == Duration::ZERO {
-            log::debug!("Connecting to {endpoint}.");
-            return TcpStream::connect(endpoint).map_err(Into::into);
-        }
-
-        log::debug!("Connecting to {endpoint} with timeout {:?}.", self.timeout);
-        TcpStream::connect_timeout(endpoint, self.timeout).map_err(|e| match e.kind() {
+    fn connect_timeout(
+        endpoint: SocketAddr,
+        bind_address: Option<IpAddr>,
+        timeout: Duration,
+    ) -> Result<TcpStream> {
+        log::debug!("Connecting to {endpoint} with timeout {timeout:?}.");
+        Self::open_socket(endpoint, bind_address, timeout).map_err(|e| match e.kind() {
             io::ErrorKind::TimedOut => {
-                log::error!("Connection timed out after {:?}", self.timeout);
-                TransportError::Timeout(self.timeout)
+                log::error!("Connection to {endpoint} timed out after {timeout:?}");
+                TransportError::Timeout(timeout)
             }
             _ => {
                 log::error!("Failed to connect to {endpoint}: {e}");
@@ -72,25 +79,74 @@ impl TcpTransport {
         })
     }
 
-    /// Connects to a NetBios server in the specified endpoint with a timeout.
-    /// This is the async version of [connect](NetBiosClient::connect) -
+    #[cfg(not(feature = "async"))]
+    fn open_socket(
+        endpoint: SocketAddr,
+        bind_address: Option<IpAddr>,
+        timeout: Duration,
+    ) -> io::Result<TcpStream> {
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        let Some(bind_address) = bind_address else {
+            return match timeout {
+                Duration::ZERO => TcpStream::connect(endpoint),
+                timeout => TcpStream::connect_timeout(&endpoint, timeout),
+            };
+        };
+        let socket = Socket::new(
+            Domain::for_address(endpoint),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )?;
+        socket.bind(&SocketAddr::new(bind_address, 0).into())?;
+        match timeout {
+            Duration::ZERO => socket.connect(&endpoint.into())?,
+            timeout => socket.connect_timeout(&endpoint.into(), timeout)?,
+        };
+        Ok(socket.into())
+    }
+
+    /// Connects to the specified endpoint with a timeout, from the specified local address, if any.
+    /// This is the async version of [connect](TcpTransport::connect) -
     /// using the [tokio::net::TcpStream] as the underlying socket provider.
     #[cfg(feature = "async")]
-    async fn connect_timeout(&mut self, endpoint: &SocketAddr) -> Result<TcpStream> {
-        if self.timeout == Duration::ZERO {
+    async fn connect_timeout(
+        endpoint: SocketAddr,
+        bind_address: Option<IpAddr>,
+        timeout: Duration,
+    ) -> Result<TcpStream> {
+        if timeout == Duration::ZERO {
             log::debug!("Connecting to {endpoint}.",);
-            return TcpStream::connect(&endpoint).await.map_err(Into::into);
+            return Self::open_socket(endpoint, bind_address)
+                .await
+                .map_err(Into::into);
         }
 
-        log::debug!("Connecting to {endpoint} with timeout {:?}.", self.timeout);
+        log::debug!("Connecting to {endpoint} with timeout {timeout:?}.");
         select! {
-            res = TcpStream::connect(&endpoint) => res.map_err(Into::into),
-            _ = tokio::time::sleep(self.timeout) => Err(
-                TransportError::Timeout(self.timeout)
+            res = Self::open_socket(endpoint, bind_address) => res.map_err(Into::into),
+            _ = tokio::time::sleep(timeout) => Err(
+                TransportError::Timeout(timeout)
             ),
         }
     }
 
+    #[cfg(feature = "async")]
+    async fn open_socket(
+        endpoint: SocketAddr,
+        bind_address: Option<IpAddr>,
+    ) -> io::Result<TcpStream> {
+        let Some(bind_address) = bind_address else {
+            return TcpStream::connect(endpoint).await;
+        };
+        let socket = match endpoint {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        socket.bind(SocketAddr::new(bind_address, 0))?;
+        socket.connect(endpoint).await
+    }
+
     /// Async implementation of split socket to read and write halves.
     #[cfg(feature = "async")]
     fn split_socket(socket: TcpStream) -> (TcpRead, TcpWrite) {
@@ -159,10 +215,29 @@ impl TcpTransport {
         Ok(())
     }
 
+    /// Connects to the first of `addresses` that accepts the connection, see [`happy_eyeballs`].
+    ///
+    /// Addresses of another family than the bind address, if any, are skipped.
     #[maybe_async::maybe_async]
-    #[inline]
-    async fn do_connect(&mut self, _server_name: &str, server_address: SocketAddr) -> Result<()> {
-        let socket = self.connect_timeout(&server_address).await?;
+    async fn do_connect_any(&mut self, addresses: &[SocketAddr]) -> Result<()> {
+        let bind_address = self.options.bind_address;
+        let addresses = happy_eyeballs::interleave_families(addresses)
+            .into_iter()
+            .filter(|address| bind_address.is_none_or(|bind| bind.is_ipv6() == address.is_ipv6()))
+            .collect::<Vec<_>>();
+        if let (Some(bind), true) = (bind_address, addresses.is_empty()) {
+            return Err(TransportError::InvalidAddress(format!(
+                "None of the server addresses is of the family of the bind address {bind}"
+            )));
+        }
+
+        let timeout = self.timeout;
+        let (socket, address) =
+            happy_eyeballs::connect_any(&addresses, self.options.attempt_delay, move |address| {
+                Self::connect_timeout(address, bind_address, timeout)
+            })
+            .await?;
+        log::debug!("Connected to {address}.");
         let (r, w) = Self::split_socket(socket);
         self.reader = Some(r);
         self.writer = Some(w);
@@ -174,14 +249,27 @@ impl SmbTransport for TcpTransport {
     #[cfg(feature = "async")]
     fn connect<'a>(
         &'a mut self,
-        server_name: &'a str,
+        _server_name: &'a str,
         server_address: SocketAddr,
     ) -> BoxFuture<'a, Result<()>> {
-        self.do_connect(server_name, server_address).boxed()
+        async move { self.do_connect_any(&[server_address]).await }.boxed()
     }
     #[cfg(not(feature = "async"))]
-    fn connect(&mut self, server_name: &str, server_address: SocketAddr) -> Result<()> {
-        self.do_connect(server_name, server_address)
+    fn connect(&mut self, _server_name: &str, server_address: SocketAddr) -> Result<()> {
+        self.do_connect_any(&[server_address])
+    }
+
+    #[cfg(feature = "async")]
+    fn connect_any<'a>(
+        &'a mut self,
+        _server_name: &'a str,
+        addresses: &'a [SocketAddr],
+    ) -> BoxFuture<'a, Result<()>> {
+        self.do_connect_any(addresses).boxed()
+    }
+    #[cfg(not(feature = "async"))]
+    fn connect_any(&mut self, _server_name: &str, addresses: &[SocketAddr]) -> Result<()> {
+        self.do_connect_any(addresses)
     }
 
     fn split(self: Box<Self>) -> Result<(Box<dyn SmbTransportRead>, Box<dyn SmbTransportWrite>)> {
@@ -190,11 +278,13 @@ impl SmbTransport for TcpTransport {
                 reader: self.reader,
                 writer: None,
                 timeout: self.timeout,
+                options: self.options,
             }),
             Box::new(Self {
                 reader: None,
                 writer: self.writer,
                 timeout: self.timeout,
+                options: self.options,
             }),
         ))
     }
@@ -203,6 +293,10 @@ impl SmbTransport for TcpTransport {
         Self::DEFAULT_PORT
     }
 
+    fn name(&self) -> &'static str {
+        "tcp"
+    }
+
     fn remote_address(&self) -> Result<SocketAddr> {
         self.reader
             .as_ref()
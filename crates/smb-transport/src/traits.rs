@@ -5,7 +5,7 @@ use futures_core::future::BoxFuture;
 use futures_util::FutureExt;
 use std::{io::Cursor, net::SocketAddr};
 
-use crate::{IoVec, SmbTcpMessageHeader, error::Result};
+use crate::{BufferPool, IoVec, SmbTcpMessageHeader, TransportError, error::Result};
 
 #[allow(async_fn_in_trait)]
 pub trait SmbTransport: Send + SmbTransportRead + SmbTransportWrite {
@@ -18,8 +18,56 @@ pub trait SmbTransport: Send + SmbTransportRead + SmbTransportWrite {
     #[cfg(not(feature = "async"))]
     fn connect(&mut self, server_name: &str, address: SocketAddr) -> Result<()>;
 
+    /// Connects to the server at the first of `addresses` that accepts the connection.
+    ///
+    /// The default implementation connects to the addresses one after another, and transports may
+    /// override it to connect to multiple addresses concurrently, as [`TcpTransport`][crate::TcpTransport] does.
+    /// If connecting to each of the addresses fails, returns [`TransportError::ConnectFailed`]
+    /// with the error of each address, or the error itself, if there's a single address.
+    #[cfg(feature = "async")]
+    fn connect_any<'a>(
+        &'a mut self,
+        server_name: &'a str,
+        addresses: &'a [SocketAddr],
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let mut errors = Vec::new();
+            for address in addresses {
+                match self.connect(server_name, *address).await {
+                    Ok(()) => return Ok(()),
+                    Err(e) => errors.push((*address, e)),
+                }
+            }
+            Err(TransportError::connect_failed(errors))
+        }
+        .boxed()
+    }
+    /// Connects to the server at the first of `addresses` that accepts the connection.
+    ///
+    /// The default implementation connects to the addresses one after another, and transports may
+    /// override it to connect to multiple addresses concurrently, as [`TcpTransport`][crate::TcpTransport] does.
+    /// If connecting to each of the addresses fails, returns [`TransportError::ConnectFailed`]
+    /// with the error of each address, or the error itself, if there's a single address.
+    #[cfg(not(feature = "async"))]
+    fn connect_any(&mut self, server_name: &str, addresses: &[SocketAddr]) -> Result<()> {
+        let mut errors = Vec::new();
+        for address in addresses {
+            match self.connect(server_name, *address) {
+                Ok(()) => return Ok(()),
+                Err(e) => errors.push((*address, e)),
+            }
+        }
+        Err(TransportError::connect_failed(errors))
+    }
+
     fn default_port(&self) -> u16;
 
+    /// Returns a short, human-readable name of the transport protocol (e.g. `"tcp"`),
+    /// for diagnostic purposes.
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
     /// Splits the transport into two separate transports:
     /// One for reading, and one for writing,
     /// given that the transport has both the reading and writing capabilities.
@@ -92,6 +140,16 @@ pub trait SmbTransportRead: Send {
 
     #[cfg(feature = "async")]
     fn receive<'a>(&'a mut self) -> BoxFuture<'a, Result<Vec<u8>>> {
+        async {
+            let pool = BufferPool::disabled();
+            self.receive_pooled(&pool).await
+        }
+        .boxed()
+    }
+
+    /// Receives a message, like [`SmbTransportRead::receive`], into a buffer taken from `pool`.
+    #[cfg(feature = "async")]
+    fn receive_pooled<'a>(&'a mut self, pool: &'a BufferPool) -> BoxFuture<'a, Result<Vec<u8>>> {
         async {
             // Transport Header
             let mut header_data = [0; SmbTcpMessageHeader::SIZE];
@@ -99,7 +157,7 @@ pub trait SmbTransportRead: Send {
             let header = SmbTcpMessageHeader::read(&mut Cursor::new(header_data))?;
 
             // Content - final response.
-            let mut data = vec![0; header.stream_protocol_length as usize];
+            let mut data = pool.take_zeroed(header.stream_protocol_length as usize);
             self.receive_exact(&mut data).await?;
 
             log::trace!(
@@ -115,13 +173,19 @@ pub trait SmbTransportRead: Send {
 
     #[cfg(not(feature = "async"))]
     fn receive(&mut self) -> Result<Vec<u8>> {
+        self.receive_pooled(&BufferPool::disabled())
+    }
+
+    /// Receives a message, like [`SmbTransportRead::receive`], into a buffer taken from `pool`.
+    #[cfg(not(feature = "async"))]
+    fn receive_pooled(&mut self, pool: &BufferPool) -> Result<Vec<u8>> {
         // Transport Header
         let mut header_data = [0; SmbTcpMessageHeader::SIZE];
         self.receive_exact(&mut header_data)?;
         let header = SmbTcpMessageHeader::read(&mut Cursor::new(header_data))?;
 
         // Content - final response.
-        let mut data = vec![0; header.stream_protocol_length as usize];
+        let mut data = pool.take_zeroed(header.stream_protocol_length as usize);
         self.receive_exact(&mut data)?;
 
         log::trace!(
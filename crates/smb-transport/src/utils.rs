@@ -1,4 +1,4 @@
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
 
 pub struct TransportUtils;
 use crate::TransportError;
@@ -6,17 +6,70 @@ use crate::TransportError;
 impl TransportUtils {
     /// Parses a string endpoint into a [SocketAddr]. If no port is specified, port 0 is used.
     /// Returns [TransportError::InvalidAddress] if the address is invalid or cannot be resolved
+    ///
+    /// If the endpoint resolves to multiple addresses, returns the first one.
+    /// See [`TransportUtils::resolve_socket_addresses`].
     pub fn parse_socket_address(endpoint: &str) -> super::error::Result<SocketAddr> {
-        // TODO: IPv6, tests
+        Ok(Self::resolve_socket_addresses(endpoint)?[0])
+    }
+
+    /// Parses a string endpoint into all the [SocketAddr]s it resolves to, in the order of the resolver.
+    /// If no port is specified, port 0 is used.
+    ///
+    /// The endpoint may be a host name or an IP address, optionally followed by `:port`.
+    /// IPv6 addresses with a port must be enclosed in brackets (e.g. `[::1]:445`).
+    /// Returns [TransportError::InvalidAddress] if the address is invalid or cannot be resolved.
+    pub fn resolve_socket_addresses(endpoint: &str) -> super::error::Result<Vec<SocketAddr>> {
+        let unbracketed = endpoint
+            .strip_prefix('[')
+            .and_then(|e| e.strip_suffix(']'))
+            .unwrap_or(endpoint);
+        if let Ok(ip) = unbracketed.parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(ip, 0)]);
+        }
+
         let mut endpoint = endpoint.to_owned();
         if !endpoint.contains(':') {
             endpoint += ":0";
         }
-        let mut socket_addrs = endpoint
+        let mut socket_addrs = Vec::new();
+        for address in endpoint
             .to_socket_addrs()
-            .map_err(|_| TransportError::InvalidAddress(endpoint.to_string()))?;
-        socket_addrs
-            .next()
-            .ok_or(TransportError::InvalidAddress(endpoint))
+            .map_err(|_| TransportError::InvalidAddress(endpoint.to_string()))?
+        {
+            if !socket_addrs.contains(&address) {
+                socket_addrs.push(address);
+            }
+        }
+        if socket_addrs.is_empty() {
+            return Err(TransportError::InvalidAddress(endpoint));
+        }
+        Ok(socket_addrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_ip_literals() {
+        for (endpoint, expected) in [
+            ("192.0.2.1", "192.0.2.1:0"),
+            ("192.0.2.1:1445", "192.0.2.1:1445"),
+            ("2001:db8::1", "[2001:db8::1]:0"),
+            ("[2001:db8::1]", "[2001:db8::1]:0"),
+            ("[2001:db8::1]:1445", "[2001:db8::1]:1445"),
+        ] {
+            assert_eq!(
+                TransportUtils::resolve_socket_addresses(endpoint).unwrap(),
+                [expected.parse::<SocketAddr>().unwrap()],
+                "{endpoint}"
+            );
+        }
+        assert!(matches!(
+            TransportUtils::parse_socket_address("[2001:db8::1"),
+            Err(TransportError::InvalidAddress(_))
+        ));
     }
 }
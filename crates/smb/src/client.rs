@@ -1,9 +1,32 @@
 //! High-level SMB client interface.
 
 mod config;
+mod dfs_referrals;
+#[cfg(feature = "std-fs-impls")]
+mod download_dir;
+mod file_ops;
+mod metadata_cache;
+mod multichannel;
+#[cfg(feature = "glob")]
+mod path_filter;
+mod recycle_bin;
 mod smb_client;
+mod snapshot;
+mod stat_many;
 mod unc_path;
+mod walk;
+mod watch;
 
-pub use config::ClientConfig;
+pub use config::{ClientConfig, InterfaceSelectionPolicy, MultiChannelOptions};
+#[cfg(feature = "std-fs-impls")]
+pub use download_dir::{DownloadDirOptions, DownloadFilter};
+pub use file_ops::{WriteFileMode, WriteFileOptions};
+pub use metadata_cache::{DirEntry, MetadataCacheStats};
+#[cfg(feature = "glob")]
+pub use path_filter::{PathFilter, PatternMatches};
+pub use recycle_bin::{RecycleBinInfo, RecycledEntry};
 pub use smb_client::Client;
+pub use stat_many::StatManyOptions;
 pub use unc_path::UncPath;
+pub use walk::{WalkEntry, WalkOptions, WalkOrder};
+pub use watch::{WatchEvent, WatchHandle, WatchOptions};
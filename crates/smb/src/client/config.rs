@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use smb_dtyp::Guid;
 
 use crate::ConnectionConfig;
@@ -12,12 +14,44 @@ pub struct ClientConfig {
     ///   when trying to access DFS paths, instead of automatically resolving them.
     pub dfs: bool,
 
+    /// The name of the site of the client, e.g. its Active Directory site, to send in DFS referral requests.
+    ///
+    /// DFS roots order the targets of referrals by their cost from the site of the client,
+    /// which they otherwise look up by the address of the client, and which may be wrong, e.g. over a VPN.
+    /// If this is `None` (the default), the site name is not sent.
+    pub dfs_site_name: Option<String>,
+
     /// Configuration related to the SMB connections made by the client.
     /// See [`ConnectionConfig`] for more details.
     pub connection: ConnectionConfig,
 
     pub client_guid: Guid,
 
+    /// Enables caching the metadata of paths, for the specified duration.
+    /// The cache is populated by [`Client::stat`][crate::Client::stat], [`Client::exists`][crate::Client::exists]
+    /// and [`Client::walk`][crate::Client::walk], and is disabled if this is `None` (the default).
+    ///
+    /// Operations that modify paths through the client invalidate their cached metadata,
+    /// but changes made by other clients, or directly through opened handles, are not detected:
+    /// cached metadata may be stale for up to this duration. See [`Client::invalidate_metadata`][crate::Client::invalidate_metadata].
+    pub metadata_cache_ttl: Option<Duration>,
+
+    /// The maximum size of a file that may be read by [`Client::read_file`][crate::Client::read_file],
+    /// which fails with [`Error::FileTooLarge`][crate::Error::FileTooLarge] for larger files.
+    /// If not configured, uses [`DEFAULT_READ_FILE_SIZE_LIMIT`][Self::DEFAULT_READ_FILE_SIZE_LIMIT].
+    pub read_file_size_limit: Option<u64>,
+
+    /// Whether to re-authenticate sessions automatically when they expire, using the credentials they were set up with.
+    ///
+    /// When set, requests that fail with [`Status::NetworkSessionExpired`][smb_msg::Status::NetworkSessionExpired],
+    /// e.g. after the Kerberos ticket of the session expires, re-authenticate the session and are sent again, once.
+    /// See [`Session::set_auto_reauth`][crate::Session::set_auto_reauth] and [`Session::renew`][crate::Session::renew].
+    pub auto_reauth: bool,
+
+    /// Options for the alternate channels set up when multi-channel is enabled
+    /// by [`ConnectionConfig::multichannel`][crate::ConnectionConfig::multichannel].
+    pub multichannel: MultiChannelOptions,
+
     #[cfg(feature = "rdma")]
     pub rdma_type: Option<crate::transport::RdmaType>,
 }
@@ -26,10 +60,62 @@ impl Default for ClientConfig {
     fn default() -> Self {
         Self {
             dfs: true,
+            dfs_site_name: None,
             connection: ConnectionConfig::default(),
             client_guid: Guid::generate(),
+            metadata_cache_ttl: None,
+            read_file_size_limit: None,
+            auto_reauth: false,
+            multichannel: MultiChannelOptions::default(),
             #[cfg(feature = "rdma")]
             rdma_type: None,
         }
     }
 }
+
+impl ClientConfig {
+    pub const DEFAULT_READ_FILE_SIZE_LIMIT: u64 = 0x1000_0000;
+
+    /// Returns the effective value to be used if [`read_file_size_limit`][`Self::read_file_size_limit`] is not set.
+    pub fn read_file_size_limit(&self) -> u64 {
+        self.read_file_size_limit
+            .unwrap_or(Self::DEFAULT_READ_FILE_SIZE_LIMIT)
+    }
+}
+
+/// Options for setting up the alternate channels of sessions, when multi-channel is enabled.
+///
+/// The server reports its network interfaces, and the client binds the session to a connection to each
+/// selected interface, other than the one of the primary connection. Large reads and writes are then
+/// sent over all the channels of the session in turn.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MultiChannelOptions {
+    /// The maximal number of alternate channels to bind to each session, in addition to the primary one.
+    /// If not configured, uses [`DEFAULT_MAX_CHANNELS`][Self::DEFAULT_MAX_CHANNELS].
+    pub max_channels: Option<usize>,
+
+    /// How to order the interfaces of the server, when choosing which ones to bind channels to.
+    pub interface_policy: InterfaceSelectionPolicy,
+}
+
+impl MultiChannelOptions {
+    pub const DEFAULT_MAX_CHANNELS: usize = 4;
+
+    /// Returns the effective value to be used if [`max_channels`][`Self::max_channels`] is not set.
+    pub fn max_channels(&self) -> usize {
+        self.max_channels.unwrap_or(Self::DEFAULT_MAX_CHANNELS)
+    }
+}
+
+/// Specifies which network interfaces of the server are preferred for alternate channels,
+/// according to the information the server reports in `NETWORK_INTERFACE_INFO` (MS-SMB2 2.2.32.5).
+///
+/// Interfaces that rank equally keep the order reported by the server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InterfaceSelectionPolicy {
+    /// Prefer RSS-capable interfaces, and then interfaces with higher link speed.
+    #[default]
+    PreferRss,
+    /// Prefer interfaces with higher link speed, and then RSS-capable interfaces.
+    PreferLinkSpeed,
+}
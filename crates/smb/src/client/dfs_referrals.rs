@@ -0,0 +1,369 @@
+//! Parsing of DFS referrals, the order in which their targets are tried, and their client-side cache.
+//!
+//! Referrals are cached by the DFS path that they cover (the part of the requested path that the server consumed),
+//! for their time-to-live, so that opening other paths under the same DFS folder does not query the DFS root again.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use smb_msg::{ReferralEntryValue, RespGetDfsReferral};
+
+use super::UncPath;
+use crate::Error;
+
+/// A target of a [`DfsReferral`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DfsTarget {
+    /// The share (and folder) of the target, e.g. `\server\share\folder`.
+    network_address: String,
+    /// The index of the target set of the target. Sets with a lower index have a lower cost.
+    target_set: usize,
+}
+
+/// A V4 DFS referral of a path, as returned by the DFS root.
+#[derive(Debug, Clone)]
+pub(crate) struct DfsReferral {
+    /// The DFS path covered by the referral.
+    prefix: String,
+    /// The targets of the referral, in the order returned by the server, which is grouped by target set.
+    targets: Vec<DfsTarget>,
+    /// The index of the target that was last opened successfully, which is tried first.
+    hint: Option<usize>,
+    /// Whether targets of lower cost sets are tried before the hint.
+    target_failback: bool,
+    expires_at: Instant,
+}
+
+impl DfsReferral {
+    /// Parses the referral response of `requested_path`.
+    pub fn from_response(
+        requested_path: &str,
+        response: &RespGetDfsReferral,
+    ) -> crate::Result<Self> {
+        if !response.referral_header_flags.storage_servers() {
+            return Err(Error::InvalidMessage(
+                "DFS referral does not contain storage servers".to_string(),
+            ));
+        }
+
+        // The path consumed is a wstring size, in bytes.
+        let consumed_chars = response.path_consumed as usize / std::mem::size_of::<u16>();
+        if consumed_chars > requested_path.chars().count() {
+            return Err(Error::InvalidMessage(
+                "DFS path consumed is out of bounds".to_string(),
+            ));
+        }
+        let prefix = requested_path.chars().take(consumed_chars).collect();
+
+        let mut targets: Vec<DfsTarget> = vec![];
+        let mut ttl = u32::MAX;
+        for entry in response.referral_entries.iter() {
+            let ReferralEntryValue::V4(v4) = &entry.value else {
+                return Err(Error::UnsupportedOperation(
+                    "Unsupported DFS referral entry type".to_string(),
+                ));
+            };
+            let target_set = match targets.last() {
+                None if !v4.target_set_boundary() => {
+                    return Err(Error::InvalidMessage(
+                        "First DFS Referral is not primary one, invalid message!".to_string(),
+                    ));
+                }
+                None => 0,
+                Some(last) if v4.target_set_boundary() => last.target_set + 1,
+                Some(last) => last.target_set,
+            };
+            targets.push(DfsTarget {
+                network_address: v4.refs.network_address.to_string(),
+                target_set,
+            });
+            ttl = ttl.min(v4.time_to_live);
+        }
+        if targets.is_empty() {
+            return Err(Error::InvalidMessage(
+                "DFS referral contains no targets".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            prefix,
+            targets,
+            hint: None,
+            target_failback: response.referral_header_flags.target_failbacl(),
+            expires_at: Instant::now() + Duration::from_secs(ttl.into()),
+        })
+    }
+
+    /// Returns the DFS path covered by the referral.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns the paths of `path` under the targets of the referral, along with the index of each target,
+    /// in the order they should be tried.
+    ///
+    /// Target sets are tried from the lowest cost to the highest, and the targets of each set in the order returned by the server.
+    /// The target that was opened last is tried first: if the server enabled target failback, only before the other targets of its set.
+    pub fn targets_for(&self, path: &str) -> crate::Result<Vec<(usize, UncPath)>> {
+        let suffix_start = path
+            .char_indices()
+            .nth(self.prefix.chars().count())
+            .map_or(path.len(), |(i, _)| i);
+        let suffix = &path[suffix_start..];
+
+        let mut ordered = (0..self.targets.len()).collect::<Vec<_>>();
+        if let Some(hint) = self.hint {
+            ordered.remove(hint);
+            let hint_set = self.targets[hint].target_set;
+            let position = if self.target_failback {
+                ordered
+                    .iter()
+                    .position(|&i| self.targets[i].target_set >= hint_set)
+                    .unwrap_or(ordered.len())
+            } else {
+                0
+            };
+            ordered.insert(position, hint);
+        }
+
+        ordered
+            .into_iter()
+            .map(|i| {
+                let target = format!(r"\{}{suffix}", self.targets[i].network_address);
+                Ok((i, UncPath::from_str(&target)?))
+            })
+            .collect()
+    }
+
+    fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+}
+
+/// A cache of DFS path => [`DfsReferral`], where each referral expires after its time-to-live.
+///
+/// Keys are lowercased, since DFS paths are case-insensitive.
+#[derive(Default)]
+pub(crate) struct DfsReferralCache {
+    referrals: Mutex<HashMap<String, DfsReferral>>,
+}
+
+impl DfsReferralCache {
+    fn key(path: &str) -> String {
+        path.to_lowercase().trim_end_matches('\\').to_string()
+    }
+
+    /// Returns the cached referral of the longest DFS path that covers `path`, if it did not expire.
+    ///
+    /// Drops expired referrals.
+    pub fn get(&self, path: &str) -> Option<DfsReferral> {
+        let path = Self::key(path);
+        let mut referrals = self.referrals.lock().unwrap();
+        referrals.retain(|_, referral| !referral.is_expired());
+        referrals
+            .iter()
+            .filter(|(prefix, _)| {
+                path.strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('\\'))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, referral)| referral.clone())
+    }
+
+    /// Caches the referral, unless its time-to-live is zero.
+    pub fn insert(&self, referral: DfsReferral) {
+        if referral.is_expired() {
+            return;
+        }
+        self.referrals
+            .lock()
+            .unwrap()
+            .insert(Self::key(&referral.prefix), referral);
+    }
+
+    /// Marks a target of the cached referral as the one to try first.
+    pub fn set_hint(&self, referral: &DfsReferral, target: usize) {
+        if let Some(cached) = self
+            .referrals
+            .lock()
+            .unwrap()
+            .get_mut(&Self::key(&referral.prefix))
+        {
+            cached.hint = Some(target);
+        }
+    }
+
+    /// Drops the cached referral, so that it is queried again.
+    pub fn remove(&self, referral: &DfsReferral) {
+        self.referrals
+            .lock()
+            .unwrap()
+            .remove(&Self::key(&referral.prefix));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smb_msg::{
+        DfsServerType, EntryV3V4DfsPaths, ReferralEntry, ReferralEntryValueV4, ReferralHeaderFlags,
+    };
+
+    const DFS_PATH: &str = r"\\corp.local\dfs\Docs";
+    /// The TargetSetBoundary bit of the referral entry flags.
+    const TARGET_SET_BOUNDARY: u16 = 0x4;
+
+    /// Returns a referral response of [`DFS_PATH`], with the targets of each of `sets`.
+    fn make_response(sets: &[&[&str]], ttl: u32, target_failback: bool) -> RespGetDfsReferral {
+        let referral_entries = sets
+            .iter()
+            .flat_map(|set| {
+                set.iter().enumerate().map(|(i, address)| ReferralEntry {
+                    value: ReferralEntryValue::V4(ReferralEntryValueV4 {
+                        server_type: DfsServerType::NonRoot,
+                        referral_entry_flags: if i == 0 { TARGET_SET_BOUNDARY } else { 0 },
+                        time_to_live: ttl,
+                        refs: EntryV3V4DfsPaths {
+                            dfs_path: DFS_PATH.into(),
+                            dfs_alternate_path: DFS_PATH.into(),
+                            network_address: (*address).into(),
+                        },
+                    }),
+                })
+            })
+            .collect();
+        RespGetDfsReferral {
+            path_consumed: (DFS_PATH.len() * 2) as u16,
+            referral_header_flags: ReferralHeaderFlags::new()
+                .with_storage_servers(true)
+                .with_target_failbacl(target_failback),
+            referral_entries,
+        }
+    }
+
+    fn target_paths(referral: &DfsReferral, path: &str) -> Vec<String> {
+        referral
+            .targets_for(path)
+            .unwrap()
+            .into_iter()
+            .map(|(_, path)| path.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_targets_by_set() {
+        let response = make_response(
+            &[&[r"\site1\docs", r"\site1b\docs"], &[r"\remote\docs"]],
+            300,
+            false,
+        );
+        let referral = DfsReferral::from_response(DFS_PATH, &response).unwrap();
+        assert_eq!(referral.prefix(), DFS_PATH);
+        assert_eq!(
+            referral
+                .targets
+                .iter()
+                .map(|t| t.target_set)
+                .collect::<Vec<_>>(),
+            [0, 0, 1]
+        );
+        assert_eq!(
+            target_paths(&referral, &format!(r"{DFS_PATH}\dir\File.txt")),
+            [
+                r"\\site1\docs\dir\File.txt",
+                r"\\site1b\docs\dir\File.txt",
+                r"\\remote\docs\dir\File.txt"
+            ]
+        );
+        assert_eq!(
+            target_paths(&referral, DFS_PATH),
+            [r"\\site1\docs", r"\\site1b\docs", r"\\remote\docs"]
+        );
+
+        // The first target must start a target set.
+        let mut response = response;
+        if let ReferralEntryValue::V4(v4) = &mut response.referral_entries[0].value {
+            v4.referral_entry_flags = 0;
+        }
+        assert!(matches!(
+            DfsReferral::from_response(DFS_PATH, &response),
+            Err(Error::InvalidMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_target_hint() {
+        let sets: &[&[&str]] = &[&[r"\a\docs", r"\b\docs"], &[r"\c\docs", r"\d\docs"]];
+        let mut referral =
+            DfsReferral::from_response(DFS_PATH, &make_response(sets, 300, false)).unwrap();
+        referral.hint = Some(3);
+        assert_eq!(
+            target_paths(&referral, DFS_PATH),
+            [r"\\d\docs", r"\\a\docs", r"\\b\docs", r"\\c\docs"]
+        );
+
+        // With failback, the targets of lower cost sets come first.
+        let mut referral =
+            DfsReferral::from_response(DFS_PATH, &make_response(sets, 300, true)).unwrap();
+        referral.hint = Some(3);
+        assert_eq!(
+            target_paths(&referral, DFS_PATH),
+            [r"\\a\docs", r"\\b\docs", r"\\d\docs", r"\\c\docs"]
+        );
+        referral.hint = Some(1);
+        assert_eq!(
+            target_paths(&referral, DFS_PATH),
+            [r"\\b\docs", r"\\a\docs", r"\\c\docs", r"\\d\docs"]
+        );
+    }
+
+    #[test]
+    fn test_cache_lookup_and_expiry() {
+        let cache = DfsReferralCache::default();
+        let referral = DfsReferral::from_response(
+            DFS_PATH,
+            &make_response(&[&[r"\a\docs", r"\b\docs"]], 300, false),
+        )
+        .unwrap();
+        cache.insert(referral.clone());
+
+        // Paths under the DFS path are covered, case-insensitively.
+        let covered = cache.get(r"\\CORP.local\dfs\docs\dir\file.txt").unwrap();
+        assert_eq!(covered.prefix(), DFS_PATH);
+        assert!(cache.get(DFS_PATH).is_some());
+        assert!(cache.get(r"\\corp.local\dfs\Docs2\file.txt").is_none());
+        assert!(cache.get(r"\\corp.local\dfs").is_none());
+
+        // The longest covering DFS path wins.
+        let nested = format!(r"{DFS_PATH}\Nested");
+        let mut response = make_response(&[&[r"\n\nested"]], 300, false);
+        response.path_consumed = (nested.len() * 2) as u16;
+        cache.insert(DfsReferral::from_response(&nested, &response).unwrap());
+        let covered = cache.get(&format!(r"{nested}\file.txt")).unwrap();
+        assert_eq!(covered.prefix(), nested);
+        assert_eq!(
+            cache
+                .get(&format!(r"{DFS_PATH}\file.txt"))
+                .unwrap()
+                .prefix(),
+            DFS_PATH
+        );
+
+        cache.set_hint(&referral, 1);
+        assert_eq!(
+            target_paths(&cache.get(DFS_PATH).unwrap(), DFS_PATH),
+            [r"\\b\docs", r"\\a\docs"]
+        );
+        cache.remove(&referral);
+        assert!(cache.get(DFS_PATH).is_none());
+
+        // Referrals are not cached beyond their time-to-live.
+        let expired =
+            DfsReferral::from_response(DFS_PATH, &make_response(&[&[r"\a\docs"]], 0, false))
+                .unwrap();
+        cache.insert(expired);
+        assert!(cache.get(DFS_PATH).is_none());
+    }
+}
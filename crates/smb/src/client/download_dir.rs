@@ -0,0 +1,323 @@
+//! Recursive directory downloads.
+//!
+//! See [`Client::download_dir`] for more information.
+
+use maybe_async::*;
+use smb_fscc::{FileAccessMask, FileIdBothDirectoryInformation};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{UncPath, WalkEntry};
+use crate::resource::{
+    IntegrityOptions, NameCollisionPolicy, RemoteState, StreamHash, TransferOptions,
+    TransferReport, resolve_local_names_with_existing, verify_download,
+};
+use crate::{Client, File, FileCreateArgs};
+
+#[cfg(feature = "async")]
+use {
+    futures_util::{StreamExt, TryStreamExt, stream},
+    tokio::fs,
+    tokio::io::AsyncWriteExt,
+};
+#[cfg(not(feature = "async"))]
+use {std::fs, std::io::Write};
+
+/// Decides whether an entry is downloaded by [`Client::download_dir`]. See [`DownloadDirOptions::filter`].
+pub type DownloadFilter = dyn Fn(&WalkEntry) -> bool + Send + Sync;
+
+/// Options for [`Client::download_dir`].
+#[derive(Clone)]
+pub struct DownloadDirOptions {
+    /// The maximum number of files downloaded at the same time.
+    /// Defaults to [`DownloadDirOptions::DEFAULT_CONCURRENCY`].
+    ///
+    /// This is ignored in sync builds, where files are always downloaded one by one.
+    pub concurrency: usize,
+
+    /// Whether to set the last write and last access times of the downloaded files and directories
+    /// to those of the remote entries, where possible.
+    pub preserve_timestamps: bool,
+
+    /// If set, only the entries it returns `true` for are downloaded.
+    /// Directories it returns `false` for are not listed, so none of their entries are downloaded.
+    pub filter: Option<Arc<DownloadFilter>>,
+
+    /// The size of the chunks each file is read in.
+    /// If unset, defaults to [`TransferOptions::DEFAULT_CHUNK_SIZE`].
+    pub chunk_size: Option<u32>,
+
+    /// What to do with remote names that collide on the local file system. See [`NameCollisionPolicy`].
+    pub name_collision_policy: NameCollisionPolicy,
+
+    /// If set, the data of each file is hashed as it is downloaded, and verified against the remote file
+    /// once downloaded. See [`IntegrityOptions`].
+    pub integrity: Option<IntegrityOptions>,
+}
+
+impl DownloadDirOptions {
+    pub const DEFAULT_CONCURRENCY: usize = 4;
+}
+
+impl Default for DownloadDirOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: Self::DEFAULT_CONCURRENCY,
+            preserve_timestamps: true,
+            filter: None,
+            chunk_size: None,
+            name_collision_policy: NameCollisionPolicy::default(),
+            integrity: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for DownloadDirOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DownloadDirOptions")
+            .field("concurrency", &self.concurrency)
+            .field("preserve_timestamps", &self.preserve_timestamps)
+            .field(
+                "filter",
+                &self.filter.as_ref().map(|_| "Fn(&WalkEntry) -> bool"),
+            )
+            .field("chunk_size", &self.chunk_size)
+            .field("name_collision_policy", &self.name_collision_policy)
+            .field("integrity", &self.integrity)
+            .finish()
+    }
+}
+
+/// A file to download, once the local directories are created.
+struct FileDownload {
+    remote: UncPath,
+    local: PathBuf,
+    info: FileIdBothDirectoryInformation,
+}
+
+#[maybe_async(AFIT)]
+impl Client {
+    /// Downloads a remote directory tree into a local directory.
+    ///
+    /// Each remote directory is listed, and closed, before any of its subdirectories is opened,
+    /// so the number of open handles does not grow with the depth of the tree.
+    /// Once all the local directories are created, the files are downloaded, up to
+    /// [`DownloadDirOptions::concurrency`] at a time, over the already connected tree.
+    ///
+    /// The tree is merged into the existing content of `dest`: files that exist both locally and remotely
+    /// are overwritten, and other local files are kept.
+    /// Remote names that collide with each other, or with existing local names, on case- or normalization-insensitive
+    /// file systems are handled according to [`DownloadDirOptions::name_collision_policy`].
+    /// Directories that are reparse points (e.g. junctions) are skipped.
+    ///
+    /// ## Arguments
+    /// * `src` - The UNC path of the remote directory. The share must be connected using [`Client::share_connect`].
+    /// * `dest` - The local directory to download into. It is created if it does not exist.
+    /// * `options` - See [`DownloadDirOptions`].
+    ///
+    /// ## Returns
+    /// A report of the download, see [`TransferReport`].
+    /// The download stops at the first error, keeping everything that was downloaded so far.
+    pub async fn download_dir(
+        &self,
+        src: &UncPath,
+        dest: &Path,
+        options: DownloadDirOptions,
+    ) -> crate::Result<TransferReport> {
+        let mut report = TransferReport::default();
+        fs::create_dir_all(dest).await?;
+
+        let mut pending = vec![(src.clone(), dest.to_path_buf(), 0)];
+        let mut files = vec![];
+        let mut dirs = vec![];
+        while let Some((remote_dir, local_dir, depth)) = pending.pop() {
+            let entries = self
+                ._list_walked_dir(remote_dir.clone(), None)
+                .await?
+                .into_iter()
+                .filter(|info| {
+                    let name = info.file_name.to_string();
+                    name != "." && name != ".."
+                })
+                .map(|info| WalkEntry {
+                    path: remote_dir
+                        .clone()
+                        .with_add_path(&info.file_name.to_string()),
+                    depth,
+                    info,
+                })
+                .filter(|entry| options.filter.as_ref().is_none_or(|filter| filter(entry)))
+                .collect::<Vec<_>>();
+
+            let names = entries.iter().map(WalkEntry::name).collect::<Vec<_>>();
+            let existing = local_names(&local_dir).await?;
+            let local_names = resolve_local_names_with_existing(
+                &names,
+                &existing,
+                options.name_collision_policy,
+                &mut report,
+            )?;
+
+            for (entry, local_name) in entries.into_iter().zip(local_names) {
+                let Some(local_name) = local_name else {
+                    continue;
+                };
+                self._cache_listed_entry(&entry);
+                let local_path = local_dir.join(local_name);
+                if !entry.is_directory() {
+                    files.push(FileDownload {
+                        remote: entry.path,
+                        local: local_path,
+                        info: entry.info,
+                    });
+                } else if entry.info.file_attributes.reparse_point() {
+                    log::warn!(
+                        "Skipping {}: directory reparse points are not downloaded",
+                        entry.path
+                    );
+                } else {
+                    fs::create_dir_all(&local_path).await?;
+                    pending.push((entry.path, local_path.clone(), depth + 1));
+                    dirs.push((local_path, entry.info));
+                }
+            }
+        }
+
+        self._download_files(files, &options).await?;
+        // Creating entries updates the times of their directory, so these are set last, innermost first.
+        if options.preserve_timestamps {
+            for (local_path, info) in dirs.iter().rev() {
+                set_local_times(local_path, info);
+            }
+        }
+        Ok(report)
+    }
+
+    #[async_impl]
+    async fn _download_files(
+        &self,
+        files: Vec<FileDownload>,
+        options: &DownloadDirOptions,
+    ) -> crate::Result<()> {
+        stream::iter(files)
+            .map(Ok)
+            .try_for_each_concurrent(options.concurrency.max(1), |file| {
+                self._download_dir_file(file, options)
+            })
+            .await
+    }
+
+    #[sync_impl]
+    fn _download_files(
+        &self,
+        files: Vec<FileDownload>,
+        options: &DownloadDirOptions,
+    ) -> crate::Result<()> {
+        files
+            .into_iter()
+            .try_for_each(|file| self._download_dir_file(file, options))
+    }
+
+    async fn _download_dir_file(
+        &self,
+        file: FileDownload,
+        options: &DownloadDirOptions,
+    ) -> crate::Result<()> {
+        log::debug!("Downloading {} to {}", file.remote, file.local.display());
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::standard_read());
+        let remote =
+            Self::_unwrap_file(self.create_file(&file.remote, &args).await?, &file.remote).await?;
+        let downloaded = Self::_download_to(
+            &remote,
+            &file.local,
+            options.chunk_size,
+            options.integrity.as_ref(),
+        )
+        .await;
+        remote.close().await?;
+        downloaded?;
+        if options.preserve_timestamps {
+            set_local_times(&file.local, &file.info);
+        }
+        Ok(())
+    }
+
+    /// Downloads the content of a remote file into a new local file, one chunk at a time.
+    ///
+    /// If `integrity` is set, the downloaded data is verified against the remote file.
+    pub(super) async fn _download_to(
+        file: &File,
+        local_path: &Path,
+        chunk_size: Option<u32>,
+        integrity: Option<&IntegrityOptions>,
+    ) -> crate::Result<()> {
+        let chunk_size = chunk_size
+            .unwrap_or(TransferOptions::DEFAULT_CHUNK_SIZE)
+            .max(1);
+        let before = match integrity {
+            Some(integrity) => RemoteState::query(file, integrity).await,
+            None => None,
+        };
+        let mut hash = integrity.map(StreamHash::new);
+        let mut local = fs::File::create(local_path).await?;
+        let mut buf = vec![0; chunk_size as usize];
+        let mut offset = 0;
+        loop {
+            let read = file.read_all_at(&mut buf, offset).await?;
+            local.write_all(&buf[..read]).await?;
+            if let Some(hash) = &mut hash {
+                hash.update(&buf[..read]);
+            }
+            offset += read as u64;
+            if read < buf.len() {
+                break;
+            }
+        }
+        local.flush().await?;
+        if let (Some(integrity), Some(hash)) = (integrity, hash) {
+            verify_download(file, hash.finish(), before, integrity).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the names of the entries of a local directory.
+#[async_impl]
+pub(super) async fn local_names(dir: &Path) -> crate::Result<Vec<String>> {
+    let mut names = vec![];
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    Ok(names)
+}
+
+/// Returns the names of the entries of a local directory.
+#[sync_impl]
+pub(super) fn local_names(dir: &Path) -> crate::Result<Vec<String>> {
+    fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect()
+}
+
+/// Sets the last write and last access times of a local file or directory to those of a remote entry.
+///
+/// This is best-effort: not all platforms support setting the times of directories, so failures are only logged.
+pub(super) fn set_local_times(path: &Path, info: &FileIdBothDirectoryInformation) {
+    let mut times = std::fs::FileTimes::new();
+    if !info.last_write_time.is_zero() {
+        times = times.set_modified(info.last_write_time.into());
+    }
+    if !info.last_access_time.is_zero() {
+        times = times.set_accessed(info.last_access_time.into());
+    }
+    let result = if info.file_attributes.directory() {
+        std::fs::File::open(path)
+    } else {
+        std::fs::File::options().write(true).open(path)
+    }
+    .and_then(|file| file.set_times(times));
+    if let Err(e) = result {
+        log::warn!("Failed to set the times of {}: {e}", path.display());
+    }
+}
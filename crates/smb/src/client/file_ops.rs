@@ -0,0 +1,415 @@
+//! Whole-file helpers: [`Client::write_file`] and [`Client::read_file`].
+
+use maybe_async::*;
+use smb_dtyp::binrw_util::prelude::FileTime;
+use smb_fscc::{FileAccessMask, FileAttributes, FileBasicInformation};
+use smb_msg::{CreateDisposition, CreateOptions};
+
+use super::UncPath;
+use crate::resource::GetLen;
+use crate::{Client, Error, File, FileCreateArgs, Resource};
+
+/// How [`Client::write_file`] treats an existing file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WriteFileMode {
+    /// Replaces the contents of an existing file, truncating it. Creates the file if it does not exist.
+    #[default]
+    Overwrite,
+    /// Creates a new file, failing with `STATUS_OBJECT_NAME_COLLISION` if it already exists.
+    CreateNew,
+    /// Appends to the end of an existing file. Creates the file if it does not exist.
+    Append,
+}
+
+impl From<WriteFileMode> for CreateDisposition {
+    fn from(mode: WriteFileMode) -> Self {
+        match mode {
+            WriteFileMode::Overwrite => CreateDisposition::OverwriteIf,
+            WriteFileMode::CreateNew => CreateDisposition::Create,
+            WriteFileMode::Append => CreateDisposition::OpenIf,
+        }
+    }
+}
+
+/// Options for [`Client::write_file`].
+#[derive(Debug, Default, Clone)]
+pub struct WriteFileOptions {
+    /// See [`WriteFileMode`].
+    pub mode: WriteFileMode,
+    /// The attributes of the file, if it is created.
+    pub attributes: FileAttributes,
+    /// If set, the last write time of the file is set to this value after writing.
+    pub last_write_time: Option<FileTime>,
+}
+
+#[maybe_async(AFIT)]
+impl Client {
+    /// Writes a buffer to a remote file, as specified by the options.
+    ///
+    /// The buffer is split into requests of up to the negotiated maximum write size,
+    /// and short writes are retried (see [`File::write_all_at`]).
+    ///
+    /// ## Arguments
+    /// * `path` - The UNC path of the file. The share must be connected using [`Client::share_connect`].
+    /// * `data` - The data to write. An empty buffer creates (or truncates) the file, unless appending.
+    /// * `options` - See [`WriteFileOptions`].
+    ///
+    /// ## Example
+    /// ```
+    /// # use smb::client::{WriteFileMode, WriteFileOptions};
+    /// # use smb::{Client, ClientConfig, ConnectionConfig, Credentials, Error, UncPath};
+    /// # use smb_dtyp::{Guid, binrw_util::prelude::FileTime};
+    /// # use smb_fscc::FileAttributes;
+    /// # use smb_msg::*;
+    /// # use smb_transport::mock::{MockScript, MockStep, MockTransport};
+    /// # use std::{str::FromStr, time::Duration};
+    /// #
+    /// # // The server is scripted: see `smb_transport::mock`.
+    /// # const CHALLENGE: &str = "a181b03081ada0030a0101a10c060a2b06010401823702020aa281970481944e544c4d53535000020000000c000c003800000015c28ae2abf194bdb756daa9140001000000000050005000440000000a005d580000000f410056004900560056004d0002000c00410056004900560056004d0001000c00410056004900560056004d0004000c00410076006900760056006d0003000c00410076006900760056006d0007000800a876d878c569db0100000000";
+    /// #
+    /// # /// Negotiates SMB 2.1, sets up an anonymous session and connects to the share.
+    /// # fn connect_steps() -> Vec<MockStep> {
+    /// #     let token = (0..CHALLENGE.len()).step_by(2)
+    /// #         .map(|i| u8::from_str_radix(&CHALLENGE[i..i + 2], 16).unwrap())
+    /// #         .collect();
+    /// #     vec![
+    /// #         MockStep::expect(Command::Negotiate).respond(NegotiateResponse {
+    /// #             security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+    /// #             dialect_revision: NegotiateDialect::Smb021,
+    /// #             server_guid: Guid::from([0x11; 16]),
+    /// #             capabilities: GlobalCapabilities::new().with_large_mtu(true),
+    /// #             max_transact_size: 0x100000,
+    /// #             max_read_size: 0x100000,
+    /// #             max_write_size: 0x100000,
+    /// #             system_time: FileTime::default(),
+    /// #             server_start_time: FileTime::default(),
+    /// #             buffer: vec![],
+    /// #             negotiate_context_list: None,
+    /// #         }),
+    /// #         MockStep::expect(Command::SessionSetup)
+    /// #             .respond_with_status(Status::MoreProcessingRequired, SessionSetupResponse {
+    /// #                 session_flags: SessionFlags::new(),
+    /// #                 buffer: token,
+    /// #             })
+    /// #             .session_id(0x11),
+    /// #         MockStep::expect(Command::SessionSetup).respond(SessionSetupResponse {
+    /// #             session_flags: SessionFlags::new().with_is_null_session(true),
+    /// #             buffer: vec![],
+    /// #         }),
+    /// #         MockStep::expect(Command::TreeConnect)
+    /// #             .respond(TreeConnectResponse {
+    /// #                 share_type: ShareType::Disk,
+    /// #                 share_flags: ShareFlags::new(),
+    /// #                 capabilities: TreeCapabilities::new(),
+    /// #                 maximal_access: 0x001f01ff,
+    /// #             })
+    /// #             .tree_id(5),
+    /// #     ]
+    /// # }
+    /// #
+    /// # /// Expects a create with `disposition`, of a file that is `end_of_file` bytes long once opened.
+    /// # fn create(disposition: CreateDisposition, end_of_file: u64) -> MockStep {
+    /// #     MockStep::expect(Command::Create)
+    /// #         .matching("disposition", move |r| {
+    /// #             r.content.as_create().is_ok_and(|c| c.create_disposition == disposition)
+    /// #         })
+    /// #         .respond(CreateResponse {
+    /// #             oplock_level: OplockLevel::None,
+    /// #             flags: CreateResponseFlags::new(),
+    /// #             create_action: CreateAction::Opened,
+    /// #             creation_time: FileTime::default(),
+    /// #             last_access_time: FileTime::default(),
+    /// #             last_write_time: FileTime::default(),
+    /// #             change_time: FileTime::default(),
+    /// #             allocation_size: 0,
+    /// #             endof_file: end_of_file,
+    /// #             file_attributes: FileAttributes::new().with_archive(true),
+    /// #             file_id: FileId { persistent: 1, volatile: 1 },
+    /// #             create_contexts: Default::default(),
+    /// #         })
+    /// # }
+    /// #
+    /// # fn close() -> MockStep {
+    /// #     MockStep::expect(Command::Close).respond(CloseResponse {
+    /// #         flags: CloseFlags::new(),
+    /// #         creation_time: FileTime::default(),
+    /// #         last_access_time: FileTime::default(),
+    /// #         last_write_time: FileTime::default(),
+    /// #         change_time: FileTime::default(),
+    /// #         allocation_size: 0,
+    /// #         endof_file: 0,
+    /// #         file_attributes: FileAttributes::new(),
+    /// #     })
+    /// # }
+    /// #
+    /// # fn connection_config() -> ConnectionConfig {
+    /// #     ConnectionConfig {
+    /// #         timeout: Some(Duration::from_secs(5)),
+    /// #         min_dialect: Some(Dialect::Smb021),
+    /// #         max_dialect: Some(Dialect::Smb021),
+    /// #         smb2_only_negotiate: true,
+    /// #         allow_unsigned_guest_access: true,
+    /// #         ..Default::default()
+    /// #     }
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "async"))] fn main() {}
+    /// # #[cfg(feature = "async")]
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let write = |offset: u64, data: &'static [u8]| {
+    /// #     MockStep::expect(Command::Write)
+    /// #         .matching("offset", move |r| {
+    /// #             r.content.as_write().is_ok_and(|w| w.offset == offset && w.length as usize == data.len())
+    /// #         })
+    /// #         .respond(WriteResponse { count: data.len() as u32 })
+    /// # };
+    /// # let read = |data: &[u8]| MockStep::expect(Command::Read).respond(ReadResponse { buffer: data.to_vec() });
+    /// # let steps = [
+    /// #     // Overwriting opens the file with OverwriteIf, which truncates it.
+    /// #     create(CreateDisposition::OverwriteIf, 0), write(0, b"Hello, "), close(),
+    /// #     create(CreateDisposition::OpenIf, 7), write(7, b"world!"), close(),
+    /// #     create(CreateDisposition::Open, 13), read(b"Hello, world!"), close(),
+    /// #     create(CreateDisposition::OverwriteIf, 0), close(),
+    /// #     create(CreateDisposition::Open, 0), close(),
+    /// # ];
+    /// # let client = Client::new(ClientConfig { connection: connection_config(), ..Default::default() });
+    /// # let (transport, handle) = MockTransport::new(MockScript::from_iter(connect_steps()).steps(steps));
+    /// # client.connect_with_transport("127.0.0.1", Box::new(transport)).await?;
+    /// # let share = UncPath::from_str(r"\\127.0.0.1\share")?;
+    /// # client.share_connect_with(&share, Credentials::Anonymous).await?;
+    /// // The share is connected, see `Client::share_connect`.
+    /// let path = share.with_path("hello.txt");
+    /// // Creates the file, or truncates it if it exists.
+    /// client.write_file(&path, b"Hello, ", &Default::default()).await?;
+    ///
+    /// let append = WriteFileOptions {
+    ///     mode: WriteFileMode::Append,
+    ///     ..Default::default()
+    /// };
+    /// client.write_file(&path, b"world!", &append).await?;
+    /// assert_eq!(client.read_file(&path).await?, b"Hello, world!");
+    ///
+    /// // An empty buffer leaves the file empty.
+    /// client.write_file(&path, &[], &Default::default()).await?;
+    /// assert!(client.read_file(&path).await?.is_empty());
+    /// # handle.assert_done();
+    /// #   Ok(()) }
+    /// ```
+    pub async fn write_file(
+        &self,
+        path: &UncPath,
+        data: &[u8],
+        options: &WriteFileOptions,
+    ) -> crate::Result<()> {
+        let args = FileCreateArgs {
+            disposition: options.mode.into(),
+            attributes: options.attributes,
+            options: CreateOptions::new().with_non_directory_file(true),
+            desired_access: FileAccessMask::standard_write(),
+            durable: None,
+            snapshot: None,
+        };
+        let file = Self::_unwrap_file(self.create_file(path, &args).await?, path).await?;
+
+        let written = Self::_write_file(&file, data, options).await;
+        file.close().await?;
+        written
+    }
+
+    /// Reads the whole contents of a remote file.
+    ///
+    /// To protect against accidentally reading huge files into memory, this fails with [`Error::FileTooLarge`]
+    /// if the file is larger than [`ClientConfig::read_file_size_limit`][super::ClientConfig::read_file_size_limit].
+    ///
+    /// ## Arguments
+    /// * `path` - The UNC path of the file. The share must be connected using [`Client::share_connect`].
+    ///
+    /// ## Example
+    /// ```
+    /// # use smb::client::{WriteFileMode, WriteFileOptions};
+    /// # use smb::{Client, ClientConfig, ConnectionConfig, Credentials, Error, UncPath};
+    /// # use smb_dtyp::{Guid, binrw_util::prelude::FileTime};
+    /// # use smb_fscc::FileAttributes;
+    /// # use smb_msg::*;
+    /// # use smb_transport::mock::{MockScript, MockStep, MockTransport};
+    /// # use std::{str::FromStr, time::Duration};
+    /// #
+    /// # // The server is scripted: see `smb_transport::mock`.
+    /// # const CHALLENGE: &str = "a181b03081ada0030a0101a10c060a2b06010401823702020aa281970481944e544c4d53535000020000000c000c003800000015c28ae2abf194bdb756daa9140001000000000050005000440000000a005d580000000f410056004900560056004d0002000c00410056004900560056004d0001000c00410056004900560056004d0004000c00410076006900760056006d0003000c00410076006900760056006d0007000800a876d878c569db0100000000";
+    /// #
+    /// # /// Negotiates SMB 2.1, sets up an anonymous session and connects to the share.
+    /// # fn connect_steps() -> Vec<MockStep> {
+    /// #     let token = (0..CHALLENGE.len()).step_by(2)
+    /// #         .map(|i| u8::from_str_radix(&CHALLENGE[i..i + 2], 16).unwrap())
+    /// #         .collect();
+    /// #     vec![
+    /// #         MockStep::expect(Command::Negotiate).respond(NegotiateResponse {
+    /// #             security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+    /// #             dialect_revision: NegotiateDialect::Smb021,
+    /// #             server_guid: Guid::from([0x11; 16]),
+    /// #             capabilities: GlobalCapabilities::new().with_large_mtu(true),
+    /// #             max_transact_size: 0x100000,
+    /// #             max_read_size: 0x100000,
+    /// #             max_write_size: 0x100000,
+    /// #             system_time: FileTime::default(),
+    /// #             server_start_time: FileTime::default(),
+    /// #             buffer: vec![],
+    /// #             negotiate_context_list: None,
+    /// #         }),
+    /// #         MockStep::expect(Command::SessionSetup)
+    /// #             .respond_with_status(Status::MoreProcessingRequired, SessionSetupResponse {
+    /// #                 session_flags: SessionFlags::new(),
+    /// #                 buffer: token,
+    /// #             })
+    /// #             .session_id(0x11),
+    /// #         MockStep::expect(Command::SessionSetup).respond(SessionSetupResponse {
+    /// #             session_flags: SessionFlags::new().with_is_null_session(true),
+    /// #             buffer: vec![],
+    /// #         }),
+    /// #         MockStep::expect(Command::TreeConnect)
+    /// #             .respond(TreeConnectResponse {
+    /// #                 share_type: ShareType::Disk,
+    /// #                 share_flags: ShareFlags::new(),
+    /// #                 capabilities: TreeCapabilities::new(),
+    /// #                 maximal_access: 0x001f01ff,
+    /// #             })
+    /// #             .tree_id(5),
+    /// #     ]
+    /// # }
+    /// #
+    /// # /// Expects a create with `disposition`, of a file that is `end_of_file` bytes long once opened.
+    /// # fn create(disposition: CreateDisposition, end_of_file: u64) -> MockStep {
+    /// #     MockStep::expect(Command::Create)
+    /// #         .matching("disposition", move |r| {
+    /// #             r.content.as_create().is_ok_and(|c| c.create_disposition == disposition)
+    /// #         })
+    /// #         .respond(CreateResponse {
+    /// #             oplock_level: OplockLevel::None,
+    /// #             flags: CreateResponseFlags::new(),
+    /// #             create_action: CreateAction::Opened,
+    /// #             creation_time: FileTime::default(),
+    /// #             last_access_time: FileTime::default(),
+    /// #             last_write_time: FileTime::default(),
+    /// #             change_time: FileTime::default(),
+    /// #             allocation_size: 0,
+    /// #             endof_file: end_of_file,
+    /// #             file_attributes: FileAttributes::new().with_archive(true),
+    /// #             file_id: FileId { persistent: 1, volatile: 1 },
+    /// #             create_contexts: Default::default(),
+    /// #         })
+    /// # }
+    /// #
+    /// # fn close() -> MockStep {
+    /// #     MockStep::expect(Command::Close).respond(CloseResponse {
+    /// #         flags: CloseFlags::new(),
+    /// #         creation_time: FileTime::default(),
+    /// #         last_access_time: FileTime::default(),
+    /// #         last_write_time: FileTime::default(),
+    /// #         change_time: FileTime::default(),
+    /// #         allocation_size: 0,
+    /// #         endof_file: 0,
+    /// #         file_attributes: FileAttributes::new(),
+    /// #     })
+    /// # }
+    /// #
+    /// # fn connection_config() -> ConnectionConfig {
+    /// #     ConnectionConfig {
+    /// #         timeout: Some(Duration::from_secs(5)),
+    /// #         min_dialect: Some(Dialect::Smb021),
+    /// #         max_dialect: Some(Dialect::Smb021),
+    /// #         smb2_only_negotiate: true,
+    /// #         allow_unsigned_guest_access: true,
+    /// #         ..Default::default()
+    /// #     }
+    /// # }
+    /// #
+    /// # #[cfg(not(feature = "async"))] fn main() {}
+    /// # #[cfg(feature = "async")]
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let steps = [
+    /// #     create(CreateDisposition::Open, 6),
+    /// #     MockStep::expect(Command::Read).respond(ReadResponse { buffer: b"[smb]\n".to_vec() }),
+    /// #     close(),
+    /// #     create(CreateDisposition::Open, 4 << 30), close(),
+    /// # ];
+    /// let client = Client::new(ClientConfig {
+    ///     read_file_size_limit: Some(1 << 20),
+    /// #   connection: connection_config(),
+    ///     ..Default::default()
+    /// });
+    /// # let (transport, handle) = MockTransport::new(MockScript::from_iter(connect_steps()).steps(steps));
+    /// # client.connect_with_transport("127.0.0.1", Box::new(transport)).await?;
+    /// # let share = UncPath::from_str(r"\\127.0.0.1\share")?;
+    /// # client.share_connect_with(&share, Credentials::Anonymous).await?;
+    /// // The share is connected, see `Client::share_connect`.
+    /// let data = client.read_file(&share.clone().with_path("config.ini")).await?;
+    /// println!("{}", String::from_utf8_lossy(&data));
+    /// # assert_eq!(data, b"[smb]\n");
+    ///
+    /// // Files above the limit are not read.
+    /// let result = client.read_file(&share.with_path("disk.iso")).await;
+    /// assert!(matches!(result, Err(Error::FileTooLarge { .. })));
+    /// # handle.assert_done();
+    /// #   Ok(()) }
+    /// ```
+    pub async fn read_file(&self, path: &UncPath) -> crate::Result<Vec<u8>> {
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::standard_read());
+        let file = Self::_unwrap_file(self.create_file(path, &args).await?, path).await?;
+
+        let data = self._read_file(&file).await;
+        file.close().await?;
+        data
+    }
+
+    async fn _write_file(
+        file: &File,
+        data: &[u8],
+        options: &WriteFileOptions,
+    ) -> crate::Result<()> {
+        let offset = match options.mode {
+            WriteFileMode::Append => file.get_len().await?,
+            _ => 0,
+        };
+        file.write_all_at(data, offset).await?;
+
+        if let Some(last_write_time) = options.last_write_time {
+            // Zero times and attributes are left unchanged.
+            file.set_info(FileBasicInformation {
+                creation_time: FileTime::default(),
+                last_access_time: FileTime::default(),
+                last_write_time,
+                change_time: FileTime::default(),
+                file_attributes: FileAttributes::new(),
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn _read_file(&self, file: &File) -> crate::Result<Vec<u8>> {
+        let size = file.get_len().await?;
+        let limit = self.config().read_file_size_limit();
+        if size > limit {
+            return Err(Error::FileTooLarge { size, limit });
+        }
+
+        let mut data = vec![0; size as usize];
+        let read = file.read_all_at(&mut data, 0).await?;
+        // The file may have been truncated by another client in the meantime.
+        data.truncate(read);
+        Ok(data)
+    }
+
+    /// Returns the file of the resource, closing it and failing if it is not a file.
+    pub(super) async fn _unwrap_file(resource: Resource, path: &UncPath) -> crate::Result<File> {
+        match resource {
+            Resource::File(file) => Ok(file),
+            resource => {
+                resource.handle().close().await?;
+                Err(Error::InvalidArgument(format!("{path} is not a file")))
+            }
+        }
+    }
+}
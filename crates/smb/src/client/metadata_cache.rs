@@ -0,0 +1,363 @@
+//! Optional client-side cache of file metadata.
+//!
+//! The cache is disabled by default, and is enabled by setting [`ClientConfig::metadata_cache_ttl`][super::ClientConfig::metadata_cache_ttl].
+//! See [`Client::stat`] for more information.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use maybe_async::*;
+use smb_fscc::{DirAccessMask, FileIdBothDirectoryInformation};
+
+use super::{UncPath, WalkEntry};
+use crate::{Client, Error, FileCreateArgs, Resource};
+
+/// The metadata of a directory entry, as returned by [`Client::stat`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    /// The full path of the entry.
+    pub path: UncPath,
+    /// The information of the entry, as returned by listing its parent directory.
+    pub info: FileIdBothDirectoryInformation,
+}
+
+impl DirEntry {
+    /// Returns the name of the entry.
+    pub fn name(&self) -> String {
+        self.info.file_name.to_string()
+    }
+
+    /// Returns whether the entry is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.info.file_attributes.directory()
+    }
+}
+
+impl From<&WalkEntry> for DirEntry {
+    fn from(entry: &WalkEntry) -> Self {
+        Self {
+            path: entry.path.clone(),
+            info: entry.info.clone(),
+        }
+    }
+}
+
+/// Counters of a client metadata cache. See [`Client::metadata_cache_stats`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataCacheStats {
+    /// The number of lookups that were answered from the cache.
+    pub hits: u64,
+    /// The number of lookups that required a round trip to the server.
+    pub misses: u64,
+    /// The number of entries currently held by the cache, including expired ones.
+    pub entries: usize,
+}
+
+struct CachedMetadata {
+    cached_at: Instant,
+    /// `None` if the path is known not to exist.
+    entry: Option<DirEntry>,
+}
+
+/// A cache of path => [`DirEntry`], where each entry expires after a fixed time.
+///
+/// Keys are lowercased normalized paths, since paths may be case-insensitive on the server.
+pub(crate) struct MetadataCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CachedMetadata>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl MetadataCache {
+    /// When the cache grows beyond this number of entries, expired entries are dropped.
+    const PRUNE_THRESHOLD: usize = 0x4000;
+
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Default::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn key(path: &UncPath) -> String {
+        path.normalized()
+            .to_string()
+            .to_lowercase()
+            .trim_end_matches('\\')
+            .to_string()
+    }
+
+    /// Returns the cached metadata of the path, if it did not expire:
+    /// `Some(None)` means the path is known not to exist.
+    ///
+    /// Updates the hit/miss counters.
+    pub fn get(&self, path: &UncPath) -> Option<Option<DirEntry>> {
+        let key = Self::key(path);
+        let mut entries = self.entries.lock().unwrap();
+        let result = match entries.get(&key) {
+            Some(cached) if cached.cached_at.elapsed() < self.ttl => Some(cached.entry.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+        let counter = if result.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Caches the metadata of the path, or the fact that it does not exist, if `entry` is `None`.
+    pub fn insert(&self, path: &UncPath, entry: Option<DirEntry>) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= Self::PRUNE_THRESHOLD {
+            entries.retain(|_, cached| cached.cached_at.elapsed() < self.ttl);
+        }
+        entries.insert(
+            Self::key(path),
+            CachedMetadata {
+                cached_at: Instant::now(),
+                entry,
+            },
+        );
+    }
+
+    /// Drops the cached metadata of the path, and of all the paths under it.
+    pub fn invalidate(&self, path_prefix: &UncPath) {
+        let prefix = Self::key(path_prefix);
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|key, _| {
+            !(key == &prefix
+                || key
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.starts_with('\\')))
+        });
+    }
+
+    pub fn stats(&self) -> MetadataCacheStats {
+        MetadataCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+}
+
+#[maybe_async(AFIT)]
+impl Client {
+    /// Returns the metadata of the specified path, or `None` if it does not exist.
+    ///
+    /// The metadata is looked up by querying the parent directory for the single entry,
+    /// so the path itself is never opened: this does not update its last access time,
+    /// and does not conflict with its share mode.
+    ///
+    /// If the metadata cache is enabled (see [`ClientConfig::metadata_cache_ttl`][super::ClientConfig::metadata_cache_ttl]),
+    /// cached metadata is returned when available, and may be stale if the path was modified
+    /// by other clients, or directly through opened handles.
+    ///
+    /// ## Arguments
+    /// * `path` - The UNC path of the entry. The share must be connected using [`Client::share_connect`].
+    ///   This must not be the share root, which has no parent directory.
+    pub async fn stat(&self, path: &UncPath) -> crate::Result<Option<DirEntry>> {
+        let (parent, name) = Self::_split_parent(path).ok_or_else(|| {
+            Error::InvalidArgument(format!("{path} has no parent directory to query"))
+        })?;
+
+        if let Some(cached) = self.metadata_cache.as_ref().and_then(|c| c.get(path)) {
+            return Ok(cached);
+        }
+
+        let entry = self
+            ._query_parent_entry(&parent, name)
+            .await?
+            .map(|info| DirEntry {
+                path: path.clone(),
+                info,
+            });
+        if let Some(cache) = &self.metadata_cache {
+            cache.insert(path, entry.clone());
+        }
+        Ok(entry)
+    }
+
+    /// Returns whether the specified path exists.
+    ///
+    /// For paths other than the share root, this uses [`Client::stat`], so the path itself is never opened,
+    /// and the result may come from the metadata cache, if enabled.
+    pub async fn exists(&self, path: &UncPath) -> crate::Result<bool> {
+        if Self::_split_parent(path).is_none() {
+            return Ok(self.get_tree(path).await.is_ok());
+        }
+        Ok(self.stat(path).await?.is_some())
+    }
+
+    /// Drops the cached metadata of the specified path, and of all the paths under it.
+    ///
+    /// The client invalidates paths that it modifies, but this should be called when paths are modified
+    /// directly through opened handles (e.g. renaming or deleting a file using [`ResourceHandle::set_info`][crate::ResourceHandle::set_info]),
+    /// or by other clients.
+    ///
+    /// Does nothing if the metadata cache is disabled.
+    pub fn invalidate_metadata(&self, path_prefix: &UncPath) {
+        if let Some(cache) = &self.metadata_cache {
+            cache.invalidate(path_prefix);
+        }
+    }
+
+    /// Returns the counters of the metadata cache, or `None` if the cache is disabled.
+    pub fn metadata_cache_stats(&self) -> Option<MetadataCacheStats> {
+        self.metadata_cache.as_ref().map(MetadataCache::stats)
+    }
+
+    /// Caches the metadata of an entry that was listed by the client.
+    pub(crate) fn _cache_listed_entry(&self, entry: &WalkEntry) {
+        if let Some(cache) = &self.metadata_cache {
+            cache.insert(&entry.path, Some(entry.into()));
+        }
+    }
+
+    /// Splits a path into its parent directory and its name, or returns `None` for the share root.
+    fn _split_parent(path: &UncPath) -> Option<(UncPath, &str)> {
+        let relative = path.path()?.trim_matches('\\');
+        if relative.is_empty() {
+            return None;
+        }
+        Some(match relative.rsplit_once('\\') {
+            Some((parent, name)) => (path.clone().with_path(parent), name),
+            None => (path.clone().with_no_path(), relative),
+        })
+    }
+
+    /// Queries an entry from its parent directory, returning `None` if it does not exist.
+    async fn _query_parent_entry(
+        &self,
+        parent: &UncPath,
+        name: &str,
+    ) -> crate::Result<Option<FileIdBothDirectoryInformation>> {
+        let access = DirAccessMask::new()
+            .with_list_directory(true)
+            .with_synchronize(true);
+        let parent = match self
+            .create_file(parent, &FileCreateArgs::make_open_existing(access.into()))
+            .await
+        {
+            Ok(Resource::Directory(dir)) => dir,
+            // A path under a file does not exist.
+            Ok(resource) => {
+                resource.handle().close().await?;
+                return Ok(None);
+            }
+            Err(e) if e.is_not_found() => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let entry = parent
+            .query_entry::<FileIdBothDirectoryInformation>(name)
+            .await;
+        let closed = parent.close().await;
+        let entry = entry?;
+        closed?;
+        Ok(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use smb_fscc::FileIdBothDirectoryInformation;
+
+    use super::{DirEntry, MetadataCache, MetadataCacheStats};
+    use crate::UncPath;
+
+    fn make_entry(path: &str) -> DirEntry {
+        let path = UncPath::from_str(path).unwrap();
+        DirEntry {
+            info: FileIdBothDirectoryInformation {
+                file_index: 0,
+                creation_time: Default::default(),
+                last_access_time: Default::default(),
+                last_write_time: Default::default(),
+                change_time: Default::default(),
+                end_of_file: 0,
+                allocation_size: 0,
+                file_attributes: Default::default(),
+                ea_size: Some(0),
+                reparse_tag: None,
+                short_name_length: 0,
+                short_name: Default::default(),
+                file_id: 0,
+                file_name: path.path().unwrap().rsplit('\\').next().unwrap().into(),
+            },
+            path,
+        }
+    }
+
+    #[test]
+    fn test_cache_hits_and_expiry() {
+        let cache = MetadataCache::new(Duration::from_secs(60));
+        let entry = make_entry(r"\\server\share\dir\file.txt");
+        let missing = UncPath::from_str(r"\\server\share\missing").unwrap();
+
+        assert_eq!(cache.get(&entry.path), None);
+        cache.insert(&entry.path, Some(entry.clone()));
+        cache.insert(&missing, None);
+        // Lookups are case-insensitive.
+        let upper = UncPath::from_str(r"\\SERVER\share\DIR\File.txt").unwrap();
+        assert_eq!(cache.get(&upper), Some(Some(entry.clone())));
+        assert_eq!(cache.get(&missing), Some(None));
+        assert_eq!(
+            cache.stats(),
+            MetadataCacheStats {
+                hits: 2,
+                misses: 1,
+                entries: 2
+            }
+        );
+
+        let expired = MetadataCache::new(Duration::ZERO);
+        expired.insert(&entry.path, Some(entry.clone()));
+        assert_eq!(expired.get(&entry.path), None);
+        assert_eq!(expired.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_cache_invalidate_prefix() {
+        let cache = MetadataCache::new(Duration::from_secs(60));
+        let paths = [
+            r"\\server\share\dir",
+            r"\\server\share\dir\file.txt",
+            r"\\server\share\dir\sub\file.txt",
+            r"\\server\share\dir2",
+            r"\\server\other\dir",
+        ];
+        for path in paths {
+            let entry = make_entry(path);
+            cache.insert(&entry.path.clone(), Some(entry));
+        }
+
+        cache.invalidate(&UncPath::from_str(r"\\server\share\DIR\").unwrap());
+        let remaining = paths
+            .iter()
+            .filter(|p| {
+                cache
+                    .get(&UncPath::from_str(p).unwrap())
+                    .is_some_and(|e| e.is_some())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, [&r"\\server\share\dir2", &r"\\server\other\dir"]);
+
+        cache.invalidate(&UncPath::from_str(r"\\server\share").unwrap());
+        assert_eq!(cache.stats().entries, 1);
+    }
+}
@@ -0,0 +1,149 @@
+//! Selection of the network interfaces of the server to bind alternate channels to.
+//!
+//! See [`MultiChannelOptions`].
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+use smb_msg::NetworkInterfaceInfo;
+
+use super::config::{InterfaceSelectionPolicy, MultiChannelOptions};
+
+/// Returns the interfaces to bind alternate channels to, out of the interfaces reported by the server,
+/// in the order of preference of [`MultiChannelOptions::interface_policy`], and up to [`MultiChannelOptions::max_channels`].
+///
+/// The interface of the primary connection is the one that has its address, `primary_address`. Alternate channels are bound
+/// to other interfaces of the same address family, a single address for each. If the interface of the primary connection
+/// is not reported, which usually means the server is not on the local network of the client, no interface is returned.
+pub(crate) fn select_interfaces<'a>(
+    interfaces: &'a [NetworkInterfaceInfo],
+    primary_address: IpAddr,
+    rdma_only: bool,
+    options: &MultiChannelOptions,
+) -> Vec<&'a NetworkInterfaceInfo> {
+    let primary = match interfaces
+        .iter()
+        .find(|iface| iface.sockaddr.socket_addr().ip() == primary_address)
+    {
+        Some(primary) => primary,
+        None => return vec![],
+    };
+
+    let mut seen_indexes = HashSet::from([primary.if_index]);
+    let mut selected = interfaces
+        .iter()
+        .filter(|iface| iface.sockaddr.socket_addr().is_ipv4() == primary_address.is_ipv4())
+        .filter(|iface| !rdma_only || iface.capability.rdma())
+        .filter(|iface| seen_indexes.insert(iface.if_index))
+        .collect::<Vec<_>>();
+
+    // Stable, so that equally ranked interfaces keep the order of the server.
+    match options.interface_policy {
+        InterfaceSelectionPolicy::PreferRss => selected
+            .sort_by_key(|iface| (Reverse(iface.capability.rss()), Reverse(iface.link_speed))),
+        InterfaceSelectionPolicy::PreferLinkSpeed => selected
+            .sort_by_key(|iface| (Reverse(iface.link_speed), Reverse(iface.capability.rss()))),
+    }
+    selected.truncate(options.max_channels());
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use smb_msg::{NetworkInterfaceCapability, SocketAddrStorage, SocketAddrStorageV4};
+
+    use super::*;
+
+    fn interface(
+        if_index: u32,
+        address: [u8; 4],
+        rss: bool,
+        link_speed: u64,
+    ) -> NetworkInterfaceInfo {
+        NetworkInterfaceInfo {
+            if_index,
+            capability: NetworkInterfaceCapability::new().with_rss(rss),
+            link_speed,
+            sockaddr: SocketAddrStorage::V4(SocketAddrStorageV4 {
+                port: 0,
+                address: u32::from(Ipv4Addr::from(address)).to_be(),
+            }),
+        }
+    }
+
+    /// The interfaces of a server with a primary 1Gbps interface,
+    /// a 10Gbps interface with two addresses, an RSS-capable 1Gbps one and a 25Gbps one.
+    fn interfaces() -> Vec<NetworkInterfaceInfo> {
+        vec![
+            interface(1, [10, 0, 0, 1], false, 1_000_000_000),
+            interface(2, [10, 0, 1, 1], false, 10_000_000_000),
+            interface(2, [10, 0, 1, 2], false, 10_000_000_000),
+            interface(3, [10, 0, 2, 1], true, 1_000_000_000),
+            interface(4, [10, 0, 3, 1], false, 25_000_000_000),
+        ]
+    }
+
+    fn addresses(selected: &[&NetworkInterfaceInfo]) -> Vec<IpAddr> {
+        selected
+            .iter()
+            .map(|iface| iface.sockaddr.socket_addr().ip())
+            .collect()
+    }
+
+    fn ip(address: [u8; 4]) -> IpAddr {
+        IpAddr::from(address)
+    }
+
+    #[test]
+    fn test_prefer_rss() {
+        let interfaces = interfaces();
+        let selected = select_interfaces(
+            &interfaces,
+            ip([10, 0, 0, 1]),
+            false,
+            &MultiChannelOptions::default(),
+        );
+        assert_eq!(
+            addresses(&selected),
+            [ip([10, 0, 2, 1]), ip([10, 0, 3, 1]), ip([10, 0, 1, 1])]
+        );
+    }
+
+    #[test]
+    fn test_prefer_link_speed() {
+        let interfaces = interfaces();
+        let options = MultiChannelOptions {
+            max_channels: Some(2),
+            interface_policy: InterfaceSelectionPolicy::PreferLinkSpeed,
+        };
+        let selected = select_interfaces(&interfaces, ip([10, 0, 0, 1]), false, &options);
+        assert_eq!(addresses(&selected), [ip([10, 0, 3, 1]), ip([10, 0, 1, 1])]);
+    }
+
+    #[test]
+    fn test_excludes_primary_interface() {
+        let interfaces = interfaces();
+        // The primary connection goes to the second address of interface 2.
+        let selected = select_interfaces(
+            &interfaces,
+            ip([10, 0, 1, 2]),
+            false,
+            &MultiChannelOptions::default(),
+        );
+        assert_eq!(
+            selected.iter().map(|i| i.if_index).collect::<Vec<_>>(),
+            [3, 4, 1]
+        );
+    }
+
+    #[test]
+    fn test_no_interfaces_selected() {
+        let interfaces = interfaces();
+        let options = MultiChannelOptions::default();
+        assert!(select_interfaces(&interfaces, ip([192, 168, 0, 1]), false, &options).is_empty());
+        assert!(select_interfaces(&interfaces, ip([10, 0, 0, 1]), true, &options).is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+//! Gitignore-style filtering of walked and transferred paths.
+//!
+//! See [`PathFilter`] for more information.
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+#[cfg(feature = "std-fs-impls")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Error;
+
+/// A gitignore-style filter of remote paths, for excluding entries from [`Client::walk`][crate::Client::walk]
+/// and from downloads (see [`WalkOptions::filter`][super::WalkOptions::filter] and
+/// [`TransferOptions::filter`][crate::resource::TransferOptions::filter]).
+///
+/// Paths are matched relative to the root of the walk, with `/` separators, regardless of the
+/// `\` separators of SMB paths. The patterns follow the `.gitignore` syntax:
+/// * `*` and `?` match within a path component, and `**` matches any number of components.
+/// * A pattern without a `/` (other than a trailing one) matches the name of an entry at any depth, e.g. `*.tmp`.
+///   Other patterns are relative to the root, e.g. `build/*.o`. A leading `/` anchors a pattern to the root.
+/// * A trailing `/` only matches directories, e.g. `target/`.
+/// * A leading `!` re-includes entries that were excluded by a previous pattern.
+///   The last pattern that matches an entry decides whether it is excluded.
+/// * `\` is a path separator, as in SMB paths, and not an escape character.
+///
+/// Matching is case-insensitive, as most SMB servers are.
+///
+/// Excluded directories are not descended into, so their entries are never listed, and cannot be re-included.
+/// Directories whose entries are all excluded (e.g. by `**/node_modules/**`) are yielded, but not listed either,
+/// unless a later `!` pattern may re-include one of their entries.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    patterns: Vec<FilterPattern>,
+    /// The glob of each pattern, by the index of the pattern.
+    set: GlobSet,
+    /// The directory prefixes of patterns ending with `/**`, which match all the entries of a directory.
+    contents: GlobSet,
+    /// The index of the pattern of each glob of `contents`.
+    contents_patterns: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct FilterPattern {
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// How a [`PathFilter`] handles an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FilterDecision {
+    /// The entry is included, and it was re-included by the pattern at the index, if set.
+    Included(Option<usize>),
+    /// The entry is excluded by the pattern at the index.
+    Excluded(usize),
+    /// The entry is a directory that is included, but all of its entries are excluded by the pattern at the index.
+    Pruned(usize),
+}
+
+/// The number of entries a pattern of a [`PathFilter`] decided on, see [`TransferReport::filter_matches`][crate::resource::TransferReport::filter_matches].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PatternMatches {
+    /// The pattern, as it was provided.
+    pub pattern: String,
+    /// The number of entries the pattern excluded, or re-included if it is a `!` pattern.
+    ///
+    /// Entries of excluded or pruned directories are never listed, so a directory is counted once,
+    /// regardless of its content.
+    pub matches: u64,
+}
+
+impl PathFilter {
+    /// Creates a filter from gitignore-style patterns. Empty patterns, and patterns starting with `#`, are ignored.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if a pattern is not a valid glob.
+    pub fn new<I, S>(patterns: I) -> crate::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut filter_patterns = vec![];
+        let mut set = GlobSetBuilder::new();
+        let mut contents = GlobSetBuilder::new();
+        let mut contents_patterns = vec![];
+        for pattern in patterns {
+            let pattern = pattern.as_ref().trim_end();
+            if pattern.is_empty() || pattern.starts_with('#') {
+                continue;
+            }
+
+            let glob = pattern.replace('\\', "/");
+            let (negated, glob) = match glob.strip_prefix('!') {
+                Some(glob) => (true, glob),
+                None => (false, glob.as_str()),
+            };
+            let (dir_only, glob) = match glob.strip_suffix('/') {
+                Some(glob) => (true, glob),
+                None => (false, glob),
+            };
+            let glob = match glob.strip_prefix('/') {
+                Some(glob) => glob.to_string(),
+                None if !glob.contains('/') => format!("**/{glob}"),
+                None => glob.to_string(),
+            };
+            if glob.is_empty() || glob == "**/" {
+                return Err(Error::InvalidArgument(format!(
+                    "Invalid filter pattern {pattern}: no path to match"
+                )));
+            }
+
+            let index = filter_patterns.len();
+            set.add(Self::build_glob(pattern, &glob)?);
+            if let Some(prefix) = glob.strip_suffix("/**") {
+                contents.add(Self::build_glob(pattern, prefix)?);
+                contents_patterns.push(index);
+            }
+            filter_patterns.push(FilterPattern {
+                pattern: pattern.to_string(),
+                negated,
+                dir_only,
+            });
+        }
+
+        let build_error =
+            |e: globset::Error| Error::InvalidArgument(format!("Invalid filter patterns: {e}"));
+        Ok(Self {
+            patterns: filter_patterns,
+            set: set.build().map_err(build_error)?,
+            contents: contents.build().map_err(build_error)?,
+            contents_patterns,
+        })
+    }
+
+    /// Creates a filter from the lines of a local `.smbignore`-style file, in the `.gitignore` syntax.
+    ///
+    /// The file is read using blocking I/O, so it should be small.
+    pub fn from_ignore_file(path: &Path) -> crate::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::new(content.lines())
+    }
+
+    fn build_glob(pattern: &str, glob: &str) -> crate::Result<Glob> {
+        GlobBuilder::new(glob)
+            .literal_separator(true)
+            .backslash_escape(false)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| Error::InvalidArgument(format!("Invalid filter pattern {pattern}: {e}")))
+    }
+
+    /// Returns the patterns of the filter, in order, without the ignored ones.
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().map(|p| p.pattern.as_str())
+    }
+
+    /// Returns whether the entry at `path` is excluded by the filter.
+    ///
+    /// `path` is relative to the root of the walk, with either `/` or `\` separators.
+    pub fn is_excluded(&self, path: &str, is_dir: bool) -> bool {
+        matches!(
+            self.decide(&path.replace('\\', "/"), is_dir),
+            FilterDecision::Excluded(_)
+        )
+    }
+
+    /// Decides how to handle the entry at `path`, which is relative to the root of the walk, with `/` separators.
+    pub(crate) fn decide(&self, path: &str, is_dir: bool) -> FilterDecision {
+        let last_match = self
+            .set
+            .matches(path)
+            .into_iter()
+            .filter(|&index| is_dir || !self.patterns[index].dir_only)
+            .max();
+        match last_match {
+            Some(index) if !self.patterns[index].negated => return FilterDecision::Excluded(index),
+            _ if !is_dir => return FilterDecision::Included(last_match),
+            _ => {}
+        }
+
+        // Entries of the directory might be re-included by a later pattern.
+        let last_negated = self.patterns.iter().rposition(|p| p.negated);
+        let pruning = self
+            .contents
+            .matches(path)
+            .into_iter()
+            .map(|index| self.contents_patterns[index])
+            .filter(|&index| last_negated.is_none_or(|negated| index > negated))
+            .max();
+        match pruning {
+            Some(index) => FilterDecision::Pruned(index),
+            None => FilterDecision::Included(last_match),
+        }
+    }
+
+    /// Returns the index of the pattern that decided on an entry, if any.
+    pub(crate) fn decided_by(decision: FilterDecision) -> Option<usize> {
+        match decision {
+            FilterDecision::Included(index) => index,
+            FilterDecision::Excluded(index) | FilterDecision::Pruned(index) => Some(index),
+        }
+    }
+
+    /// Returns the number of patterns of the filter.
+    #[cfg(feature = "std-fs-impls")]
+    pub(crate) fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Pairs the patterns of the filter with the number of entries each of them decided on.
+    #[cfg(feature = "std-fs-impls")]
+    pub(crate) fn pattern_matches(&self, counts: &[AtomicU64]) -> Vec<PatternMatches> {
+        self.patterns
+            .iter()
+            .zip(counts)
+            .map(|(pattern, matches)| PatternMatches {
+                pattern: pattern.pattern.clone(),
+                matches: matches.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilterDecision, PathFilter};
+
+    #[test]
+    fn test_filter_pattern_syntax() {
+        let filter =
+            PathFilter::new(["*.tmp", "/build", "docs/*.pdf", "cache/", "# comment", ""]).unwrap();
+        assert_eq!(filter.len(), 4);
+
+        // Names match at any depth.
+        assert!(filter.is_excluded("a.tmp", false));
+        assert!(filter.is_excluded("dir/sub/A.TMP", false));
+        assert!(!filter.is_excluded("a.tmp.txt", false));
+        // Anchored patterns only match relative to the root.
+        assert!(filter.is_excluded("build", true));
+        assert!(!filter.is_excluded("src/build", true));
+        assert!(filter.is_excluded("docs/manual.pdf", false));
+        assert!(!filter.is_excluded("docs/old/manual.pdf", false));
+        assert!(!filter.is_excluded("other/docs/manual.pdf", false));
+        // Directory-only patterns.
+        assert!(filter.is_excluded("src/cache", true));
+        assert!(!filter.is_excluded("src/cache", false));
+        // Backslash separators are normalized.
+        assert!(filter.is_excluded("docs\\manual.pdf", false));
+
+        assert!(PathFilter::new(["a[b"]).is_err());
+        assert!(PathFilter::new(["/"]).is_err());
+    }
+
+    #[test]
+    fn test_filter_precedence() {
+        let filter = PathFilter::new(["*.log", "!important.log", "logs/**/important.log"]).unwrap();
+        assert_eq!(
+            filter.decide("debug.log", false),
+            FilterDecision::Excluded(0)
+        );
+        // The last matching pattern decides.
+        assert_eq!(
+            filter.decide("src/important.log", false),
+            FilterDecision::Included(Some(1))
+        );
+        assert_eq!(
+            filter.decide("logs/2024/important.log", false),
+            FilterDecision::Excluded(2)
+        );
+        assert_eq!(
+            filter.decide("a.txt", false),
+            FilterDecision::Included(None)
+        );
+    }
+
+    #[test]
+    fn test_filter_prunes_contents() {
+        let filter = PathFilter::new(["**/node_modules/**"]).unwrap();
+        // The directory itself is not excluded, but none of its entries may be included.
+        assert_eq!(
+            filter.decide("node_modules", true),
+            FilterDecision::Pruned(0)
+        );
+        assert_eq!(
+            filter.decide("a/b/node_modules", true),
+            FilterDecision::Pruned(0)
+        );
+        assert!(filter.is_excluded("a/node_modules/x/y.js", false));
+        assert_eq!(filter.decide("a/b", true), FilterDecision::Included(None));
+
+        // A later negation may re-include entries, so the directory must be listed.
+        let filter = PathFilter::new(["**/node_modules/**", "!**/node_modules/keep.txt"]).unwrap();
+        assert_eq!(
+            filter.decide("node_modules", true),
+            FilterDecision::Included(None)
+        );
+        assert!(!filter.is_excluded("node_modules/keep.txt", false));
+        assert!(filter.is_excluded("node_modules/other.txt", false));
+
+        // Unless the pruning pattern comes after it.
+        let filter = PathFilter::new(["!keep.txt", "**/node_modules/**"]).unwrap();
+        assert_eq!(
+            filter.decide("node_modules", true),
+            FilterDecision::Pruned(1)
+        );
+    }
+}
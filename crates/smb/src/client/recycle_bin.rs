@@ -0,0 +1,351 @@
+//! Recycle Bin (`$RECYCLE.BIN`) enumeration and restore utilities.
+//!
+//! Windows moves deleted files of a volume to `$RECYCLE.BIN\<SID>\`, where each deleted item
+//! is stored as a pair of files sharing the same random suffix:
+//! * `$I<suffix>` - Metadata: the original path, deletion time and size. See [`RecycleBinInfo`].
+//! * `$R<suffix>` - The actual data (a file or a directory) that was deleted.
+
+use crate::{
+    Client, Directory, Error, FileCreateArgs, GetLen, QueryDirectoryOptions, Resource, UncPath,
+};
+use maybe_async::*;
+use smb_dtyp::binrw_util::prelude::FileTime;
+use smb_fscc::{FileAccessMask, FileDirectoryInformation};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use binrw::prelude::*;
+
+/// The number of characters in the fixed-size path of a version 1 `$I` file.
+const V1_PATH_CHARS: u32 = 260;
+
+/// The contents of a `$I` Recycle Bin metadata file.
+///
+/// Both the version 1 (Windows Vista to 8.1) layout, which has a fixed-size path of 260 characters,
+/// and the version 2 (Windows 10 and later) layout, which has a length-prefixed path, are supported.
+#[binrw::binread]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[br(little)]
+pub struct RecycleBinInfo {
+    #[br(assert(version == 1 || version == 2, "Unsupported $I file version {}", version))]
+    pub version: u64,
+    /// The size of the deleted item, in bytes.
+    pub file_size: u64,
+    /// The time when the item was deleted.
+    pub deleted_at: FileTime,
+    #[br(temp, if(version == 2, V1_PATH_CHARS))]
+    path_length: u32,
+    /// The original path of the deleted item, on the server's file system (e.g. `C:\Shares\data\file.txt`).
+    #[br(count = path_length, try_map = |s: Vec<u16>| String::from_utf16(s.split(|&c| c == 0).next().unwrap_or_default()))]
+    pub original_path: String,
+}
+
+impl RecycleBinInfo {
+    /// Parses the contents of a `$I` file.
+    pub fn parse(data: &[u8]) -> crate::Result<Self> {
+        Ok(Self::read(&mut Cursor::new(data))?)
+    }
+
+    /// Returns the original name of the deleted item, without its parent directories.
+    pub fn original_name(&self) -> &str {
+        self.original_path
+            .rsplit('\\')
+            .next()
+            .unwrap_or(&self.original_path)
+    }
+}
+
+/// A deleted item in a Recycle Bin, as returned by [`Client::list_recycled`].
+#[derive(Debug, Clone)]
+pub struct RecycledEntry {
+    /// The SID of the user who deleted the item (the name of the Recycle Bin subdirectory).
+    pub sid: String,
+    /// The metadata of the deleted item.
+    pub info: RecycleBinInfo,
+    /// Whether the deleted item is a directory.
+    pub is_directory: bool,
+    /// The path of the `$I` metadata file.
+    pub info_path: UncPath,
+    /// The path of the `$R` data file or directory.
+    pub data_path: UncPath,
+}
+
+#[maybe_async(AFIT)]
+impl Client {
+    /// The name of the Recycle Bin directory in the root of a share.
+    pub const RECYCLE_BIN_DIR: &'static str = "$RECYCLE.BIN";
+
+    const RECYCLE_BIN_INFO_PREFIX: &'static str = "$I";
+    const RECYCLE_BIN_DATA_PREFIX: &'static str = "$R";
+
+    /// Lists the deleted items in the Recycle Bin of the specified share.
+    ///
+    /// ## Arguments
+    /// * `share` - The UNC path of the share. The share must be connected using [`Client::share_connect`].
+    /// * `sid` - The SID of the user whose deleted items to list. If `None`, all the per-user Recycle Bin
+    ///   directories are listed, and those that the current user may not access are skipped.
+    ///
+    /// ## Returns
+    /// The deleted items. `$I` files without a matching `$R` counterpart are skipped.
+    pub async fn list_recycled(
+        &self,
+        share: &UncPath,
+        sid: Option<&str>,
+    ) -> crate::Result<Vec<RecycledEntry>> {
+        let recycle_bin = share.clone().with_path(Self::RECYCLE_BIN_DIR);
+        let enumerate_all = sid.is_none();
+
+        let sids = match sid {
+            Some(sid) => vec![sid.to_string()],
+            None => {
+                let dir = self._open_recycle_bin_dir(&recycle_bin).await?;
+                let sids = Self::_list_recycle_bin_dir(&dir)
+                    .await?
+                    .into_iter()
+                    .filter(|item| item.file_attributes.directory())
+                    .map(|item| item.file_name.to_string())
+                    .filter(|name| name.starts_with("S-"))
+                    .collect::<Vec<_>>();
+                dir.close().await?;
+                sids
+            }
+        };
+
+        let mut result = vec![];
+        for sid in sids {
+            let sid_path = recycle_bin.clone().with_add_path(&sid);
+            let dir = match self._open_recycle_bin_dir(&sid_path).await {
+                Ok(dir) => dir,
+                Err(e) if enumerate_all && e.is_access_denied() => {
+                    log::debug!("Skipping Recycle Bin of {sid}: access denied");
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let items = Self::_list_recycle_bin_dir(&dir).await;
+            dir.close().await?;
+
+            // $R<suffix> => is directory
+            let data_items = items?
+                .into_iter()
+                .map(|item| (item.file_name.to_string(), item.file_attributes.directory()))
+                .collect::<HashMap<_, _>>();
+
+            for info_name in data_items
+                .keys()
+                .filter(|name| name.starts_with(Self::RECYCLE_BIN_INFO_PREFIX))
+            {
+                let suffix = &info_name[Self::RECYCLE_BIN_INFO_PREFIX.len()..];
+                let data_name = format!("{}{suffix}", Self::RECYCLE_BIN_DATA_PREFIX);
+                let is_directory = match data_items.get(&data_name) {
+                    Some(&is_directory) => is_directory,
+                    None => {
+                        log::debug!("Skipping {info_name} in {sid_path}: no matching {data_name}");
+                        continue;
+                    }
+                };
+
+                let info_path = sid_path.clone().with_add_path(info_name);
+                let info = match self._read_recycle_bin_info(&info_path).await {
+                    Ok(info) => info,
+                    Err(e) if e.is_access_denied() => {
+                        log::debug!("Skipping {info_path}: access denied");
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+
+                result.push(RecycledEntry {
+                    sid: sid.clone(),
+                    info,
+                    is_directory,
+                    info_path,
+                    data_path: sid_path.clone().with_add_path(&data_name),
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Restores a deleted item from the Recycle Bin, by renaming it on the server,
+    /// and removes its Recycle Bin metadata file.
+    ///
+    /// ## Arguments
+    /// * `entry` - The deleted item, as returned by [`Client::list_recycled`].
+    /// * `destination` - The path to restore the item to. Must be on the same share as the Recycle Bin,
+    ///   and must not exist. To restore to the original location, translate [`RecycleBinInfo::original_path`]
+    ///   to a path on the share.
+    pub async fn restore_recycled(
+        &self,
+        entry: &RecycledEntry,
+        destination: &UncPath,
+    ) -> crate::Result<()> {
+        if entry.data_path.clone().with_no_path() != destination.clone().with_no_path() {
+            return Err(Error::UnsupportedOperation(
+                "Restoring from the Recycle Bin to a different share is not supported".to_string(),
+            ));
+        }
+        let new_name = destination.path().unwrap_or_default();
+        if new_name.is_empty() {
+            return Err(Error::InvalidArgument(
+                "Restore destination must not be the share root".to_string(),
+            ));
+        }
+
+        let data = self
+            .create_file(
+                &entry.data_path,
+                &FileCreateArgs::make_open_existing(FileAccessMask::new().with_delete(true)),
+            )
+            .await?;
+        let data = data.handle();
+        let renamed = data.move_to(new_name, &Default::default()).await;
+        data.close().await?;
+        self.invalidate_metadata(destination);
+        renamed?;
+
+        log::debug!("Restored {} to {destination}", entry.data_path);
+
+        let info = self
+            .create_file(
+                &entry.info_path,
+                &FileCreateArgs::make_open_existing(FileAccessMask::new().with_delete(true)),
+            )
+            .await?;
+        let info = info.handle();
+        let deleted = info
+            .set_info(smb_fscc::FileDispositionInformation::default())
+            .await;
+        info.close().await?;
+        deleted
+    }
+
+    #[maybe_async]
+    async fn _open_recycle_bin_dir(&self, path: &UncPath) -> crate::Result<Arc<Directory>> {
+        let access = FileAccessMask::new()
+            .with_file_read_data(true)
+            .with_file_read_attributes(true);
+        match self
+            .create_file(path, &FileCreateArgs::make_open_existing(access))
+            .await?
+        {
+            Resource::Directory(dir) => Ok(Arc::new(dir)),
+            _ => Err(Error::InvalidState(format!("{path} is not a directory"))),
+        }
+    }
+
+    #[maybe_async]
+    async fn _list_recycle_bin_dir(
+        dir: &Arc<Directory>,
+    ) -> crate::Result<Vec<FileDirectoryInformation>> {
+        Directory::query_directory::<FileDirectoryInformation>(
+            dir,
+            "*",
+            &QueryDirectoryOptions::DEFAULT,
+        )
+        .await?
+        .collect_entries()
+        .await
+    }
+
+    #[maybe_async]
+    async fn _read_recycle_bin_info(&self, path: &UncPath) -> crate::Result<RecycleBinInfo> {
+        let file = match self
+            .create_file(
+                path,
+                &FileCreateArgs::make_open_existing(FileAccessMask::standard_read()),
+            )
+            .await?
+        {
+            Resource::File(file) => file,
+            _ => return Err(Error::InvalidState(format!("{path} is not a file"))),
+        };
+
+        let size = file.get_len().await?;
+        let mut data = vec![0; size as usize];
+        let mut pos = 0;
+        while pos < data.len() {
+            let read = file
+                .read_block(&mut data[pos..], pos as u64, None, false)
+                .await;
+            match read {
+                Ok(0) => break,
+                Ok(read) => pos += read,
+                Err(e) => {
+                    file.close().await?;
+                    return Err(e.into());
+                }
+            }
+        }
+        file.close().await?;
+
+        RecycleBinInfo::parse(&data[..pos])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecycleBinInfo, V1_PATH_CHARS};
+
+    const ORIGINAL_PATH: &str = r"C:\Shares\data\report.docx";
+    const DELETED_AT: u64 = 0x01d9c3a5e8f1b200;
+
+    #[test]
+    fn test_parse_info_v1() {
+        let mut data = vec![
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x34, 0x12, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0xb2, 0xf1, 0xe8, 0xa5, 0xc3, 0xd9, 0x01,
+        ];
+        let path = ORIGINAL_PATH
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        data.extend_from_slice(&path);
+        data.resize(24 + V1_PATH_CHARS as usize * 2, 0);
+        assert_eq!(data.len(), 544);
+
+        let info = RecycleBinInfo::parse(&data).unwrap();
+        assert_eq!(
+            info,
+            RecycleBinInfo {
+                version: 1,
+                file_size: 0x1234,
+                deleted_at: DELETED_AT.into(),
+                original_path: ORIGINAL_PATH.to_string(),
+            }
+        );
+        assert_eq!(info.original_name(), "report.docx");
+    }
+
+    #[test]
+    fn test_parse_info_v2() {
+        let data = [
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x34, 0x12, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0xb2, 0xf1, 0xe8, 0xa5, 0xc3, 0xd9, 0x01, 0x1b, 0x00, 0x00, 0x00,
+            0x43, 0x00, 0x3a, 0x00, 0x5c, 0x00, 0x53, 0x00, 0x68, 0x00, 0x61, 0x00, 0x72, 0x00,
+            0x65, 0x00, 0x73, 0x00, 0x5c, 0x00, 0x64, 0x00, 0x61, 0x00, 0x74, 0x00, 0x61, 0x00,
+            0x5c, 0x00, 0x72, 0x00, 0x65, 0x00, 0x70, 0x00, 0x6f, 0x00, 0x72, 0x00, 0x74, 0x00,
+            0x2e, 0x00, 0x64, 0x00, 0x6f, 0x00, 0x63, 0x00, 0x78, 0x00, 0x00, 0x00,
+        ];
+
+        let info = RecycleBinInfo::parse(&data).unwrap();
+        assert_eq!(
+            info,
+            RecycleBinInfo {
+                version: 2,
+                file_size: 0x1234,
+                deleted_at: DELETED_AT.into(),
+                original_path: ORIGINAL_PATH.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_info_unknown_version() {
+        let mut data = vec![0; 544];
+        data[0] = 3;
+        assert!(RecycleBinInfo::parse(&data).is_err());
+    }
+}
@@ -1,22 +1,42 @@
 use crate::ConnectionConfig;
-use crate::{Connection, Error, FileCreateArgs, Pipe, Resource, Session, Tree, sync_helpers::*};
+use crate::session::Credentials;
+use crate::{
+    Connection, DurableHandleTicket, Error, File, FileCreateArgs, Pipe, RenameOptions, Resource,
+    Session, Tree, sync_helpers::*,
+};
 use maybe_async::maybe_async;
-use smb_msg::{NetworkInterfaceInfo, ReferralEntry, ReferralEntryValue, Status};
-use smb_rpc::interface::{ShareInfo1, SrvSvc};
-use smb_transport::TransportConfig;
+use smb_fscc::{FileAccessMask, FileAllInformation};
+use smb_msg::{CreateDisposition, NetworkInterfaceInfo, Status};
+use smb_rpc::interface::{
+    DomainUser, Samr, ServerInfo, ServerInfoLevel, ShareInfo, ShareInfo1, ShareInfoLevel, SrvSvc,
+    WksSvc, WkstaInfo100,
+};
 use smb_transport::utils::TransportUtils;
-use sspi::{AuthIdentity, Secret};
+#[cfg(feature = "netbios-transport")]
+use smb_transport::{NetBiosName, NetBiosNameResolver};
+use smb_transport::{SmbTransport, TransportConfig};
+use std::collections::HashMap;
+use std::hash::Hash;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use std::{collections::HashMap, str::FromStr};
 
-use super::{config::ClientConfig, unc_path::UncPath};
+use super::{
+    config::ClientConfig,
+    dfs_referrals::{DfsReferral, DfsReferralCache},
+    metadata_cache::MetadataCache,
+    multichannel,
+    unc_path::UncPath,
+};
+use crate::tree::dir_path_prefixes;
 
 /*
     Note:
     - Most of the operations here are not especially high-performance critical,
         especially the ones tied to creating connection/sessions/trees - those are limited and slow anyway.
         Therefore, the wide use of Mutex/RwLock is acceptable here, for code simplicity.
+    - However, the locks of the client state are never held across network I/O, so that operations against
+        different servers and shares do not wait for each other. Connecting the same server or share
+        concurrently is serialized by a [`ConnectGates`] instead, so that the later connects reuse the first one.
 */
 
 /// This struct represents a high-level SMB client, and it is highly encouraged to use it
@@ -34,7 +54,8 @@ use super::{config::ClientConfig, unc_path::UncPath};
 /// to use the client to interact with them.
 ///
 /// To force a closure of all connections and their managed resources,
-/// use the [`Client::close`] method.
+/// use the [`Client::close`] method. Closing logs off the sessions, which also releases durable handles:
+/// to keep them for [`Client::reclaim_durable`], use [`Client::abandon`] instead.
 ///
 /// ## Example
 ///
@@ -54,7 +75,7 @@ use super::{config::ClientConfig, unc_path::UncPath};
 ///
 ///     // And open a file on the server
 ///     let file_to_open = target_path.with_path("file.txt");
-///     let file_open_args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_read(true));
+///     let file_open_args = FileCreateArgs::make_open_existing(FileAccessMask::standard_read());
 ///     let file = client.create_file(&file_to_open, &file_open_args).await?;
 ///     // now, you can do a bunch of operations against `file`, and close it at the end.
 ///     Ok(())
@@ -65,8 +86,19 @@ pub struct Client {
     /// Server Name + [RDMA|NONE] => [`ClientConnectionInfo`]
     // It's quite common to have one connection for RDMA, and one for TCP,
     connections: RwLock<HashMap<IpAddr, ClientConnectionInfo>>,
+    /// Serializes connecting to the same server address.
+    connection_gates: ConnectGates<IpAddr>,
     /// shares (trees) that are currently connected.
-    share_connects: Mutex<HashMap<UncPath, ClientConectedTree>>,
+    share_connects: RwLock<HashMap<UncPath, ClientConectedTree>>,
+    /// Serializes connecting (and disconnecting) the same share.
+    share_gates: ConnectGates<UncPath>,
+    /// See [`ClientConfig::metadata_cache_ttl`].
+    pub(super) metadata_cache: Option<MetadataCache>,
+    /// DFS referrals that were resolved, by the DFS path they cover.
+    dfs_referrals: DfsReferralCache,
+    /// Server names resolved using NetBIOS name queries, see [`ConnectionConfig::netbios_fallback`].
+    #[cfg(feature = "netbios-transport")]
+    netbios_names: Mutex<HashMap<String, IpAddr>>,
 }
 
 /// (Internal)
@@ -88,7 +120,7 @@ struct ClientSessionInfo {
 struct ClientConectedTree {
     session: Arc<Session>,
     tree: Arc<Tree>,
-    credentials: Option<AuthIdentity>,
+    credentials: Option<Credentials>,
 }
 
 #[derive(Clone)]
@@ -96,14 +128,48 @@ pub struct AltChannelInfo {
     connection: Arc<Connection>,
 }
 
+/// (Internal)
+///
+/// Holds a lock for each key (e.g. a server address or a share) that is being connected,
+/// so that concurrent connects of the same key run one after the other, and the later ones may reuse
+/// the result of the first, while connects of different keys do not wait for each other.
+struct ConnectGates<K> {
+    gates: Mutex<HashMap<K, Arc<Mutex<()>>>>,
+}
+
+impl<K> Default for ConnectGates<K> {
+    fn default() -> Self {
+        Self {
+            gates: Default::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> ConnectGates<K> {
+    /// Returns the gate of `key`, which the caller should lock for the duration of the connect.
+    #[maybe_async]
+    async fn get(&self, key: &K) -> crate::Result<Arc<Mutex<()>>> {
+        let mut gates = self.gates.lock().await?;
+        // Gates that are not held by anyone are no longer needed.
+        gates.retain(|_, gate| Arc::strong_count(gate) > 1);
+        Ok(gates.entry(key.clone()).or_default().clone())
+    }
+}
+
 #[maybe_async(AFIT)]
 impl Client {
     /// Creates a new `Client` instance with the given configuration.
     pub fn new(config: ClientConfig) -> Self {
         Client {
+            metadata_cache: config.metadata_cache_ttl.map(MetadataCache::new),
             config,
             connections: Default::default(),
+            connection_gates: Default::default(),
             share_connects: Default::default(),
+            share_gates: Default::default(),
+            dfs_referrals: Default::default(),
+            #[cfg(feature = "netbios-transport")]
+            netbios_names: Default::default(),
         }
     }
 
@@ -119,13 +185,12 @@ impl Client {
     /// See [Drop behavior][Client#drop-behavior] for more information.
     pub async fn close(&self) -> crate::Result<()> {
         // Close all opened shares
-        let mut trees = self.share_connects.lock().await?;
+        let trees = std::mem::take(&mut *self.share_connects.write().await?);
         for (_unc, connected_tree) in trees.iter() {
             connected_tree.tree.disconnect().await?;
         }
-        trees.clear();
 
-        let mut connections = self.connections.write().await?;
+        let mut connections = std::mem::take(&mut *self.connections.write().await?);
         // Close sessions
         for (_unc, conn) in connections.iter_mut() {
             for (_session_id, session) in conn.sessions.iter_mut() {
@@ -144,7 +209,32 @@ impl Client {
         for (_ip, conn) in connections.iter() {
             conn.connection.close().await.ok();
         }
-        connections.clear();
+
+        Ok(())
+    }
+
+    /// Shuts down all the connections of the client, without logging off sessions,
+    /// disconnecting trees, nor closing files first.
+    ///
+    /// To the server, this looks like a lost connection, so unlike [`Client::close`],
+    /// durable handles opened by the client may be reclaimed later using [`Client::reclaim_durable`].
+    /// Any resource held by the client will not be accessible after calling this method.
+    pub async fn abandon(&self) -> crate::Result<()> {
+        let trees = std::mem::take(&mut *self.share_connects.write().await?);
+        for connected_tree in trees.values() {
+            connected_tree.tree.abandon();
+        }
+
+        let connections = std::mem::take(&mut *self.connections.write().await?);
+        for conn in connections.values() {
+            for session in conn.sessions.values() {
+                session.session.abandon();
+                for alt_conn in session.session_alt_channels.iter().flat_map(|c| c.values()) {
+                    alt_conn.connection.close().await.ok();
+                }
+            }
+            conn.connection.close().await.ok();
+        }
 
         Ok(())
     }
@@ -160,6 +250,62 @@ impl Client {
         Ok(shares)
     }
 
+    /// Returns information about a share on the specified server, at the specified level.
+    ///
+    /// See [`SrvSvc::netr_share_get_info`] for the supported levels.
+    pub async fn share_info(
+        &self,
+        server: &str,
+        share: &str,
+        level: ShareInfoLevel,
+    ) -> crate::Result<ShareInfo> {
+        let srvsvc_pipe = self.open_pipe(server, "srvsvc").await?;
+
+        let mut srvsvc_pipe: SrvSvc<_> = srvsvc_pipe.bind().await?;
+        let info = srvsvc_pipe
+            .netr_share_get_info(server, share, level)
+            .await?;
+
+        Ok(info)
+    }
+
+    /// Returns information about the specified server, at the specified level.
+    pub async fn server_info(
+        &self,
+        server: &str,
+        level: ServerInfoLevel,
+    ) -> crate::Result<ServerInfo> {
+        let srvsvc_pipe = self.open_pipe(server, "srvsvc").await?;
+
+        let mut srvsvc_pipe: SrvSvc<_> = srvsvc_pipe.bind().await?;
+        let info = srvsvc_pipe.netr_server_get_info(server, level).await?;
+
+        Ok(info)
+    }
+
+    /// Returns the computer and domain names of the specified server (workstation info level 100).
+    pub async fn workstation_info(&self, server: &str) -> crate::Result<WkstaInfo100> {
+        let wkssvc_pipe = self.open_pipe(server, "wkssvc").await?;
+
+        let mut wkssvc_pipe: WksSvc<_> = wkssvc_pipe.bind().await?;
+        let info = wkssvc_pipe.netr_wksta_get_info(server).await?;
+
+        Ok(info)
+    }
+
+    /// Lists the normal user accounts of the domains of the specified server,
+    /// excluding the built-in domain.
+    ///
+    /// When connected to a domain controller, this returns the users of the domain.
+    pub async fn list_domain_users(&self, server: &str) -> crate::Result<Vec<DomainUser>> {
+        let samr_pipe = self.open_pipe(server, "samr").await?;
+
+        let mut samr_pipe: Samr<_> = samr_pipe.bind().await?;
+        let users = samr_pipe.list_domain_users().await?;
+
+        Ok(users)
+    }
+
     /// Connects to a share on the specified server.
     ///
     /// This method is the equivalent for executing a `net use` command on a local windows machine.
@@ -201,15 +347,29 @@ impl Client {
         user_name: &str,
         password: String,
     ) -> crate::Result<()> {
-        let identity = AuthIdentity {
-            username: sspi::Username::parse(user_name).map_err(|e| Error::SspiError(e.into()))?,
-            password: Secret::from(password),
-        };
+        self.share_connect_with(target, Credentials::password(user_name, password))
+            .await
+    }
 
-        self._share_connect(target, &identity).await?;
+    /// Connects to a share on a server, like [`Client::share_connect`], using the specified credentials.
+    ///
+    /// Use [`Credentials::Guest`] and [`Credentials::Anonymous`] for public shares, which do not require a user.
+    /// Guest and anonymous sessions are neither signed nor encrypted, so they require
+    /// [`ConnectionConfig::allow_unsigned_guest_access`] to be set in [`ClientConfig::connection`].
+    /// Multi-channel is not set up for such sessions, since alternate channels can only be bound to signed sessions.
+    ///
+    /// Connecting a share that is already connected (e.g. by another task, concurrently) reuses the existing connection.
+    pub async fn share_connect_with(
+        &self,
+        target: &UncPath,
+        credentials: Credentials,
+    ) -> crate::Result<()> {
+        if !self._share_connect(target, &credentials).await? {
+            return Ok(());
+        }
 
         // Establish an additional channel if multi-channel is enabled.
-        let mchannel_map = self._setup_multi_channel(target, &identity).await;
+        let mchannel_map = self._setup_multi_channel(target, &credentials).await;
         if let Ok(mchannel_map) = mchannel_map {
             let session = self.get_session(target).await?;
             log::debug!(
@@ -217,8 +377,8 @@ impl Client {
                 mchannel_map.as_ref().map(|m| m.len()).unwrap_or(0)
             );
 
-            let address = TransportUtils::parse_socket_address(target.server())?;
-            self._with_connection(address.ip(), |f| {
+            let address = self._resolve_server(target.server()).await?;
+            self._with_connection_mut(address.ip(), |f| {
                 let session_info = f
                     .sessions
                     .get(&session.session_id())
@@ -246,7 +406,13 @@ impl Client {
     ///
     /// Performs the actual share connection logic,
     /// without setting up multi-channel.
-    async fn _share_connect(&self, target: &UncPath, identity: &AuthIdentity) -> crate::Result<()> {
+    ///
+    /// Returns whether the share was connected by this call, rather than being already connected.
+    async fn _share_connect(
+        &self,
+        target: &UncPath,
+        credentials: &Credentials,
+    ) -> crate::Result<bool> {
         if target.share().is_none() {
             return Err(crate::Error::InvalidArgument(
                 "UNC path does not contain a share name.".to_string(),
@@ -255,28 +421,32 @@ impl Client {
 
         let target = target.clone().with_no_path();
 
+        // Concurrent connects of the same share wait here for the first one, and then reuse it.
+        let gate = self.share_gates.get(&target).await?;
+        let _connecting = gate.lock().await?;
         let already_connected = self._with_tree(&target, |_| Ok(())).await;
         if already_connected.is_ok() {
             log::debug!(
                 "Share {} is already connected. Ignoring duplicate connection attempt.",
                 target
             );
-            return Ok(());
+            return Ok(false);
         }
 
         let connection = self.connect(target.server()).await?;
+        let address = self._resolve_server(target.server()).await?;
 
         let session = {
-            let session = connection.authenticate(identity.clone()).await?;
+            let session = connection.authenticate(credentials.clone()).await?;
+            session.set_auto_reauth(self.config.auto_reauth);
             log::debug!(
-                "Successfully authenticated to {} as {}",
+                "Successfully authenticated to {} with {:?}",
                 target.server(),
-                identity.username.account_name()
+                credentials
             );
             let session = Arc::new(session);
 
-            let address = TransportUtils::parse_socket_address(target.server())?;
-            self._with_connection(address.ip(), |f| {
+            self._with_connection_mut(address.ip(), |f| {
                 f.sessions.insert(
                     session.session_id(),
                     ClientSessionInfo {
@@ -291,22 +461,20 @@ impl Client {
             session
         };
 
-        let tree = session.tree_connect(&target).await?;
-
-        let credentials = if tree.is_dfs_root()? {
-            Some(identity.to_owned())
-        } else {
-            None
-        };
-
-        let connect_share_info = ClientConectedTree {
-            session,
-            tree: Arc::new(tree),
-            credentials,
+        let connect_share_info = match Self::_tree_connect(&session, &target, credentials).await {
+            Ok(connect_share_info) => connect_share_info,
+            Err(e) => {
+                // The session was set up for this share only, so it must not outlive a failed connect.
+                log::debug!("Connecting to share {target} failed, logging off its session");
+                if let Err(logoff_error) = self._remove_session(address.ip(), &session).await {
+                    log::warn!("Failed to log off the session of {target}: {logoff_error}");
+                }
+                return Err(e);
+            }
         };
 
         self.share_connects
-            .lock()
+            .write()
             .await?
             .insert(target.clone(), connect_share_info);
 
@@ -315,10 +483,89 @@ impl Client {
             target.share().unwrap()
         );
 
+        Ok(true)
+    }
+
+    /// Connects the tree of `target`, in a session that was just set up for it.
+    async fn _tree_connect(
+        session: &Arc<Session>,
+        target: &UncPath,
+        credentials: &Credentials,
+    ) -> crate::Result<ClientConectedTree> {
+        let tree = session.tree_connect(target).await?;
+
+        let credentials = if tree.is_dfs_root()? {
+            Some(credentials.to_owned())
+        } else {
+            None
+        };
+
+        Ok(ClientConectedTree {
+            session: session.clone(),
+            tree: Arc::new(tree),
+            credentials,
+        })
+    }
+
+    /// Disconnects a share that was connected using [`Client::share_connect`].
+    ///
+    /// The tree is disconnected, and its session is logged off, unless other connected shares use it.
+    /// The connection to the server is kept, and reused by later calls to [`Client::share_connect`].
+    /// Resources opened on the share (e.g. files) can not be used once it is disconnected.
+    ///
+    /// ## Arguments
+    /// * `target` - The UNC path of the share to disconnect. Only the server and share components of the path are used.
+    ///
+    /// ## Returns
+    /// [`Error::NotFound`] if the share is not connected.
+    pub async fn share_disconnect(&self, target: &UncPath) -> crate::Result<()> {
+        let target = target.clone().with_no_path();
+        let gate = self.share_gates.get(&target).await?;
+        let _disconnecting = gate.lock().await?;
+        let connected = self
+            .share_connects
+            .write()
+            .await?
+            .remove(&target)
+            .ok_or_else(|| {
+                Error::NotFound(format!("No connected share found for path: {target}"))
+            })?;
+        self.invalidate_metadata(&target);
+
+        connected.tree.disconnect().await?;
+        log::debug!("Disconnected from share: {target}");
+
+        let session_in_use = self
+            .share_connects
+            .read()
+            .await?
+            .values()
+            .any(|tree| Arc::ptr_eq(&tree.session, &connected.session));
+        if !session_in_use {
+            let address = self._resolve_server(target.server()).await?;
+            self._remove_session(address.ip(), &connected.session)
+                .await?;
+        }
         Ok(())
     }
 
-    async fn _get_credentials(&self, target: &UncPath) -> crate::Result<AuthIdentity> {
+    /// Removes a session from its connection info, closing its alternate channels, and logs it off.
+    async fn _remove_session(&self, ip: IpAddr, session: &Session) -> crate::Result<()> {
+        let session_info = self
+            ._with_connection_mut(ip, |c| Ok(c.sessions.remove(&session.session_id())))
+            .await?;
+        let alt_channels = session_info
+            .and_then(|info| info.session_alt_channels)
+            .unwrap_or_default();
+        for alt_conn in alt_channels.into_values() {
+            alt_conn.connection.close().await.ok();
+        }
+        session.logoff().await?;
+        log::debug!("Logged off session {:#x}", session.session_id());
+        Ok(())
+    }
+
+    async fn _get_credentials(&self, target: &UncPath) -> crate::Result<Credentials> {
         self._with_tree(target, |tree| {
             tree.credentials.as_ref().cloned().ok_or_else(|| {
                 Error::InvalidArgument(format!(
@@ -350,8 +597,70 @@ impl Client {
     /// ## Returns
     /// The connected connection, if succeeded. Error if failed to make the connection,
     pub async fn connect(&self, server: &str) -> crate::Result<Arc<Connection>> {
-        let server_address = TransportUtils::parse_socket_address(server)?;
-        self.connect_to_address(server, server_address).await
+        let server_addresses = self._resolve_server_addresses(server).await?;
+        self._connect_transport_to_addresses(server, server_addresses, None)
+            .await
+    }
+
+    /// Resolves the address of `server`, which identifies its connection.
+    /// See [`Client::_resolve_server_addresses`].
+    #[maybe_async]
+    async fn _resolve_server(&self, server: &str) -> crate::Result<SocketAddr> {
+        Ok(self._resolve_server_addresses(server).await?[0])
+    }
+
+    /// Resolves all the addresses of `server` using DNS, falling back to a NetBIOS name query for names
+    /// that DNS can't resolve, if enabled by [`ConnectionConfig::netbios_fallback`] or the NetBIOS transport.
+    #[maybe_async]
+    async fn _resolve_server_addresses(&self, server: &str) -> crate::Result<Vec<SocketAddr>> {
+        let error = match TransportUtils::resolve_socket_addresses(server) {
+            Ok(addresses) => return Ok(addresses),
+            Err(e) => e,
+        };
+        match self._resolve_netbios_name(server).await? {
+            Some(address) => Ok(vec![address]),
+            None => Err(error.into()),
+        }
+    }
+
+    /// Returns the address of the NetBIOS name of `server`, if it is a single-label name, and NetBIOS is enabled.
+    /// Resolved names are cached for the lifetime of the client.
+    #[cfg(feature = "netbios-transport")]
+    #[maybe_async]
+    async fn _resolve_netbios_name(&self, server: &str) -> crate::Result<Option<SocketAddr>> {
+        let config = &self.config.connection;
+        if !config.netbios_fallback && config.transport != TransportConfig::NetBios {
+            return Ok(None);
+        }
+        let name = NetBiosName::for_server(server);
+        if server.contains('.') || name.name() == NetBiosName::ANY_SERVER {
+            return Ok(None);
+        }
+
+        if let Some(ip) = self.netbios_names.lock().await?.get(name.name()) {
+            return Ok(Some(SocketAddr::new(*ip, 0)));
+        }
+        let resolver = NetBiosNameResolver::broadcast(config.timeout());
+        match resolver.resolve(&name).await {
+            Ok(ip) => {
+                log::debug!("Resolved NetBIOS name {name} to {ip}");
+                self.netbios_names
+                    .lock()
+                    .await?
+                    .insert(name.name().to_string(), ip.into());
+                Ok(Some(SocketAddr::new(ip.into(), 0)))
+            }
+            Err(e) => {
+                log::debug!("Failed to resolve NetBIOS name {name}: {e}");
+                Ok(None)
+            }
+        }
+    }
+
+    #[cfg(not(feature = "netbios-transport"))]
+    #[maybe_async]
+    async fn _resolve_netbios_name(&self, _server: &str) -> crate::Result<Option<SocketAddr>> {
+        Ok(None)
     }
 
     /// Makes a connection to the specified server and address.
@@ -376,7 +685,7 @@ impl Client {
         server: &str,
         server_address: SocketAddr,
     ) -> crate::Result<Arc<Connection>> {
-        self._connect_transport_to_address(server, server_address, None)
+        self._connect_transport_to_addresses(server, vec![server_address], None)
             .await
     }
 
@@ -387,16 +696,61 @@ impl Client {
         server_address: SocketAddr,
         transport: TransportConfig,
     ) -> crate::Result<Arc<Connection>> {
-        self._connect_transport_to_address(server, server_address, Some(transport))
+        self._connect_transport_to_addresses(server, vec![server_address], Some(transport))
             .await
     }
 
-    async fn _connect_transport_to_address(
+    /// Makes a connection to the specified server over an existing transport, e.g. a custom one.
+    /// See [`Connection::from_transport`].
+    ///
+    /// The connection is identified by the IP address of [`SmbTransport::remote_address`], so that
+    /// [`Client::share_connect`] uses it for shares of a server at that address, e.g. `\\127.0.0.1\share`.
+    ///
+    /// ## Returns
+    /// The connected connection, if succeeded. Error if a connection to the address already exists,
+    /// or if negotiating over the transport failed.
+    pub async fn connect_with_transport(
         &self,
         server: &str,
-        server_address: SocketAddr,
+        transport: Box<dyn SmbTransport>,
+    ) -> crate::Result<Arc<Connection>> {
+        let server_address = transport.remote_address()?;
+        let gate = self.connection_gates.get(&server_address.ip()).await?;
+        let _connecting = gate.lock().await?;
+
+        let conn = Connection::from_transport(
+            transport,
+            server,
+            self.config.client_guid,
+            self.config.connection.clone(),
+        )
+        .await?;
+        let conn = Arc::new(conn);
+        self._add_connection(conn.clone(), &server_address.ip())
+            .await?;
+
+        log::debug!("Successfully connected to {server} over a custom transport");
+
+        Ok(conn)
+    }
+
+    /// Makes a connection to the specified server, at the first of `server_addresses` that accepts it.
+    /// The connection is identified by the first address.
+    async fn _connect_transport_to_addresses(
+        &self,
+        server: &str,
+        server_addresses: Vec<SocketAddr>,
         transport: Option<TransportConfig>,
     ) -> crate::Result<Arc<Connection>> {
+        let server_address = server_addresses[0];
+        // Concurrent connects to the same address wait here for the first one, and then reuse it.
+        let gate = self.connection_gates.get(&server_address.ip()).await?;
+        let _connecting = gate.lock().await?;
+        if let Ok(c) = self.get_connection_ip_channel(server_address.ip()).await {
+            log::debug!("Reusing existing connection to {server}",);
+            return Ok(c);
+        }
+
         log::debug!("Creating new connection to {server}",);
 
         let config = if let Some(transport) = transport {
@@ -408,15 +762,14 @@ impl Client {
             self.config.connection.clone()
         };
 
-        let conn = Connection::build(server, server_address, self.config.client_guid, config)?;
+        let conn = Connection::build_with_addresses(
+            server,
+            server_addresses,
+            self.config.client_guid,
+            config,
+        )?;
 
         let conn = Arc::new(conn);
-
-        // TODO: This is a bit racy
-        if let Ok(c) = self.get_connection_ip_channel(server_address.ip()).await {
-            log::debug!("Reusing existing connection to {server}",);
-            return Ok(c);
-        }
         self._add_connection(conn.clone(), &server_address.ip())
             .await?;
 
@@ -454,7 +807,7 @@ impl Client {
     /// Returns the underlying [`Connection`] for the specified server,
     /// after a successful call to [`Client::connect`] or [`Client::share_connect`].
     pub async fn get_connection(&self, server: &str) -> crate::Result<Arc<Connection>> {
-        let addr = TransportUtils::parse_socket_address(server)?;
+        let addr = self._resolve_server(server).await?;
         self.get_connection_ip(addr.ip()).await
     }
 
@@ -478,7 +831,7 @@ impl Client {
         path: &UncPath,
     ) -> crate::Result<HashMap<u32, AltChannelInfo>> {
         let session = self.get_session(path).await?;
-        let address = TransportUtils::parse_socket_address(path.server())?;
+        let address = self._resolve_server(path.server()).await?;
         let channels = self
             ._with_connection(address.ip(), |c| {
                 let session_info = c.sessions.get(&session.session_id());
@@ -523,6 +876,19 @@ impl Client {
 
     #[maybe_async]
     async fn _with_connection<F, R>(&self, ip: IpAddr, f: F) -> crate::Result<R>
+    where
+        F: FnOnce(&ClientConnectionInfo) -> crate::Result<R>,
+    {
+        let connections = self.connections.read().await?;
+        let conn = connections
+            .get(&ip)
+            .ok_or_else(|| Error::NotFound(format!("No connection found for server: {ip:?}")))?;
+        f(conn)
+    }
+
+    /// Just like [`Client::_with_connection`], but locks the connections for writing.
+    #[maybe_async]
+    async fn _with_connection_mut<F, R>(&self, ip: IpAddr, f: F) -> crate::Result<R>
     where
         F: FnOnce(&mut ClientConnectionInfo) -> crate::Result<R>,
     {
@@ -533,16 +899,16 @@ impl Client {
         f(conn)
     }
 
-    /// Locks `share_connects`, locates the tree for the specified path,
+    /// Locks `share_connects` for reading, locates the tree for the specified path,
     /// and calls the specified closure with the tree.
     #[maybe_async]
     async fn _with_tree<F, R>(&self, path: &UncPath, f: F) -> crate::Result<R>
     where
-        F: FnOnce(&mut ClientConectedTree) -> crate::Result<R>,
+        F: FnOnce(&ClientConectedTree) -> crate::Result<R>,
     {
         let tree_path = path.clone().with_no_path();
-        let mut sc = self.share_connects.lock().await?;
-        let sc = sc.get_mut(&tree_path).ok_or_else(|| {
+        let sc = self.share_connects.read().await?;
+        let sc = sc.get(&tree_path).ok_or_else(|| {
             Error::NotFound(format!("No connected share found for path: {path}",))
         })?;
         f(sc)
@@ -553,6 +919,7 @@ impl Client {
     /// See [`FileCreateArgs`] for detailed information regarding the file open options.
     ///
     /// The function also handles DFS resolution if it is enabled in the client configuration.
+    /// Resolved DFS referrals are cached for their time-to-live, and paths they cover are opened on their targets directly.
     ///
     /// ## Arguments
     /// * `path` - The UNC path of the file to create or open.
@@ -565,25 +932,178 @@ impl Client {
         path: &UncPath,
         args: &FileCreateArgs,
     ) -> crate::Result<Resource> {
-        let file_result = self._create_file(path, args).await;
-
-        let resource = match file_result {
-            Ok(file) => Ok(file),
-            Err(Error::ReceivedErrorMessage(Status::U32_PATH_NOT_COVERED, _)) => {
-                if self.config.dfs {
-                    DfsResolver::new(self).resolve_to_dfs_file(path, args).await
-                } else {
-                    Err(Error::UnsupportedOperation(
-                        "DFS is not enabled, but the server returned path not covered (dfs must be enabled in config to resolve the path!).".to_string(),
-                    ))
-                }
+        let cached_referral = match self.config.dfs {
+            true => self.dfs_referrals.get(&path.to_string()),
+            false => None,
+        };
+        let resource = match cached_referral {
+            Some(referral) => {
+                DfsResolver::new(self)
+                    .create_on_targets(path, &referral, args)
+                    .await
             }
-            x => x,
+            None => match self._create_file(path, args).await {
+                Ok(file) => Ok(file),
+                Err(Error::ServerError(Status::PathNotCovered, _)) => {
+                    if self.config.dfs {
+                        DfsResolver::new(self).resolve_to_dfs_file(path, args).await
+                    } else {
+                        Err(Error::UnsupportedOperation(
+                            "DFS is not enabled, but the server returned path not covered (dfs must be enabled in config to resolve the path!).".to_string(),
+                        ))
+                    }
+                }
+                x => x,
+            },
         }?;
 
+        if Self::_may_modify(args) {
+            self.invalidate_metadata(path);
+        }
+
         Ok(resource)
     }
 
+    /// Reopens a durable handle, after the connection it was opened on was lost.
+    ///
+    /// The ticket is exported from the durable file using [`File::export_durable_ticket`],
+    /// possibly by another [`Client`] instance or process (see the `serde` feature).
+    /// The share of the ticket must be connected first using [`Client::share_connect`],
+    /// with the same user that opened the handle.
+    ///
+    /// The server keeps the handle only for the timeout of the ticket after the connection is lost,
+    /// and only if the sessions of the handle were not logged off:
+    /// use [`Client::abandon`] rather than [`Client::close`] to preserve durable handles.
+    /// If the server no longer holds the handle, this fails with [`Error::DurableReclaimFailed`].
+    pub async fn reclaim_durable(&self, ticket: &DurableHandleTicket) -> crate::Result<File> {
+        let share = UncPath::new(&ticket.server)?.with_share(&ticket.share)?;
+        let tree = self.get_tree(&share).await?;
+        tree.reclaim_durable(ticket).await
+    }
+
+    /// Deletes the specified file or directory. Directories must be empty,
+    /// otherwise this fails with [`Error::DirectoryNotEmpty`].
+    ///
+    /// See [`Client::remove_file`] and [`Client::remove_dir`] to delete paths of a specific type.
+    ///
+    /// ## Arguments
+    /// * `path` - The UNC path of the file or directory to delete.
+    pub async fn delete(&self, path: &UncPath) -> crate::Result<()> {
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_delete(true));
+        self._delete(path, &args).await
+    }
+
+    /// Returns the metadata of the specified file or directory. See [`Tree::metadata`].
+    pub async fn metadata(&self, path: &UncPath) -> crate::Result<FileAllInformation> {
+        self.create_file(path, &FileCreateArgs::make_read_attributes())
+            .await?
+            .query_all_and_close()
+            .await
+    }
+
+    /// Deletes the specified file. Fails if the path is a directory. See [`Tree::remove_file`].
+    pub async fn remove_file(&self, path: &UncPath) -> crate::Result<()> {
+        self._delete(path, &FileCreateArgs::make_remove(false))
+            .await
+    }
+
+    /// Deletes the specified empty directory. See [`Tree::remove_dir`].
+    ///
+    /// Fails with [`Error::DirectoryNotEmpty`] if the directory is not empty.
+    pub async fn remove_dir(&self, path: &UncPath) -> crate::Result<()> {
+        self._delete(path, &FileCreateArgs::make_remove(true)).await
+    }
+
+    /// Creates the specified directory. Its parent directory must exist. See [`Tree::create_dir`].
+    pub async fn create_dir(&self, path: &UncPath) -> crate::Result<()> {
+        self.create_file(path, &FileCreateArgs::make_create_dir(false))
+            .await?
+            .close_handle()
+            .await
+    }
+
+    /// Creates the specified directory, and all of its missing parent directories.
+    /// See [`Tree::create_dir_all`].
+    pub async fn create_dir_all(&self, path: &UncPath) -> crate::Result<()> {
+        let share = path.clone().with_no_path();
+        for dir in dir_path_prefixes(path.path().unwrap_or_default()) {
+            self.create_file(
+                &share.clone().with_path(dir),
+                &FileCreateArgs::make_create_dir(true),
+            )
+            .await?
+            .close_handle()
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn _delete(&self, path: &UncPath, args: &FileCreateArgs) -> crate::Result<()> {
+        let resource = self.create_file(path, args).await?;
+        let deleted = resource.delete_and_close(&path.to_string()).await;
+        self.invalidate_metadata(path);
+        deleted
+    }
+
+    /// Renames (or moves) the specified file or directory.
+    ///
+    /// ## Arguments
+    /// * `from` - The UNC path of the file or directory to rename.
+    /// * `to` - The new UNC path. Must be on the same share as `from`.
+    /// * `replace_if_exists` - Whether to replace an existing file at `to`.
+    ///   If not set, and such a file exists, fails with [`Error::ObjectNameCollision`].
+    ///
+    /// See [`ResourceHandle::move_to`][crate::ResourceHandle::move_to] for renaming already opened resources.
+    pub async fn rename(
+        &self,
+        from: &UncPath,
+        to: &UncPath,
+        replace_if_exists: bool,
+    ) -> crate::Result<()> {
+        if from.clone().with_no_path() != to.clone().with_no_path() {
+            return Err(Error::UnsupportedOperation(
+                "Renaming to a different share is not supported".to_string(),
+            ));
+        }
+        let new_name = to.path().unwrap_or_default();
+        if new_name.is_empty() {
+            return Err(Error::InvalidArgument(
+                "Rename destination must not be the share root".to_string(),
+            ));
+        }
+
+        let resource = self
+            .create_file(
+                from,
+                &FileCreateArgs::make_open_existing(FileAccessMask::new().with_delete(true)),
+            )
+            .await?;
+        let handle = resource.handle();
+        let options = RenameOptions {
+            replace_if_exists,
+            ..Default::default()
+        };
+        let renamed = handle.move_to(new_name, &options).await;
+        handle.close().await?;
+        self.invalidate_metadata(from);
+        self.invalidate_metadata(to);
+        renamed
+    }
+
+    /// Returns whether opening a file with the specified arguments may modify its metadata:
+    /// creating, overwriting, writing, or deleting and renaming it using the opened handle.
+    fn _may_modify(args: &FileCreateArgs) -> bool {
+        let access = args.desired_access;
+        args.disposition != CreateDisposition::Open
+            || access.file_write_data()
+            || access.file_append_data()
+            || access.file_write_attributes()
+            || access.delete()
+            || access.generic_write()
+            || access.generic_all()
+            || access.maximum_allowed()
+    }
+
     /// Similar [`Client::share_connect`], but connects to the SMB pipes share (IPC$).
     ///
     /// After calling this method, the [`Client::open_pipe`] method can be used to open named pipes.
@@ -593,17 +1113,14 @@ impl Client {
         username: &str,
         password: String,
     ) -> crate::Result<()> {
-        let ipc_share = UncPath::ipc_share(server)?;
-        let identity = AuthIdentity {
-            username: sspi::Username::parse(username).map_err(|e| Error::SspiError(e.into()))?,
-            password: Secret::from(password),
-        };
-        self._share_connect(&ipc_share, &identity).await
+        self._ipc_connect(server, &Credentials::password(username, password))
+            .await
     }
 
-    pub async fn _ipc_connect(&self, server: &str, identity: &AuthIdentity) -> crate::Result<()> {
+    pub async fn _ipc_connect(&self, server: &str, credentials: &Credentials) -> crate::Result<()> {
         let ipc_share = UncPath::ipc_share(server)?;
-        self._share_connect(&ipc_share, identity).await
+        self._share_connect(&ipc_share, credentials).await?;
+        Ok(())
     }
 
     /// Opens a named pipe on the specified server.
@@ -644,7 +1161,7 @@ impl Client {
     async fn _setup_multi_channel(
         &self,
         unc: &UncPath,
-        identity: &AuthIdentity,
+        credentials: &Credentials,
     ) -> crate::Result<Option<HashMap<u32, AltChannelInfo>>> {
         if unc.is_ipc_share() {
             return Err(Error::InvalidArgument(
@@ -657,6 +1174,13 @@ impl Client {
             return Ok(None);
         }
 
+        if !matches!(credentials, Credentials::Password { .. }) {
+            log::debug!(
+                "Multi-channel is not supported for guest or anonymous sessions. Skipping setup."
+            );
+            return Ok(None);
+        }
+
         let primary_conn_info = {
             let opened_conn_info = self.get_connection(unc.server()).await?;
             opened_conn_info
@@ -676,7 +1200,7 @@ impl Client {
 
         // Connect IPC and query network interfaces.
         let ipc_share = UncPath::ipc_share(unc.server())?;
-        self._ipc_connect(ipc_share.server(), identity).await?;
+        self._ipc_connect(ipc_share.server(), credentials).await?;
         let ipc_tree = self.get_tree(&ipc_share).await?;
         let network_interfaces = ipc_tree
             .as_ipc_tree()
@@ -686,16 +1210,16 @@ impl Client {
 
         let mut result = HashMap::new();
 
-        // Bind to other, non-rdma network interfaces.
-        let other_interfaces = MultiChannelUtils::get_alt_interface_addresses(
+        let other_interfaces = multichannel::select_interfaces(
             &network_interfaces,
             primary_conn_info.server_address.ip(),
             self.config.connection.multichannel.is_rdma_only(),
-        )?;
+            &self.config.multichannel,
+        );
 
         if other_interfaces.is_empty() {
             log::warn!(
-                "Multi-channel setup failed: unable to determine the current primary network interface.
+                "Multi-channel setup failed: no alternate network interface of the server was found.
                 This usually means the SMB server is not on the same local network as the client, and multi-channel cannot be used.
                 Available interfaces: {network_interfaces:?}",
             );
@@ -703,41 +1227,74 @@ impl Client {
         }
 
         let session = self.get_session(unc).await?;
-        for (if_index, &interface) in other_interfaces.iter() {
+        for interface in other_interfaces {
             let address = interface.sockaddr.socket_addr();
-            log::debug!("Found alternate interface for multi-channel: {if_index} => {address}");
-
-            let (connection, channel) = {
-                let connection = if interface.capability.rdma() && cfg!(feature = "rdma") {
-                    self._connect_transport_to_address(
-                        unc.server(),
-                        address,
-                        #[cfg(feature = "rdma")]
-                        Some(TransportConfig::Rdma(crate::transport::RdmaConfig {
-                            rdma_type: self.config.rdma_type.ok_or_else(|| {
-                                Error::InvalidConfiguration(
-                                    "RDMA transport type is not specified in client configuration."
-                                        .to_string(),
-                                )
-                            })?,
-                        })),
-                        #[cfg(not(feature = "rdma"))]
-                        None,
-                    )
-                    .await?
-                } else {
-                    self.connect_to_address(unc.server(), address).await?
-                };
+            log::debug!(
+                "Found alternate interface for multi-channel: {} => {address}",
+                interface.if_index
+            );
 
-                let channel = connection.bind_session(&session, identity.clone()).await?;
+            // A channel that can't be bound is skipped: the session keeps working over the others.
+            match self
+                ._bind_alt_channel(unc, &session, credentials, interface)
+                .await
+            {
+                Ok((connection, channel)) => {
+                    result.insert(channel, AltChannelInfo { connection });
+                }
+                Err(e) => {
+                    log::warn!("Failed to bind alternate channel over {address}: {e}");
+                }
+            }
+        }
 
-                (connection, channel)
-            };
-            result.insert(channel, AltChannelInfo { connection });
+        if result.is_empty() {
+            return Err(Error::InvalidState(
+                "Failed to bind any alternate channel.".to_string(),
+            ));
         }
 
         Ok(Some(result))
     }
+
+    /// Connects to an alternate interface of the server, and binds the session to the connection.
+    ///
+    /// Returns the connection and the ID of the new channel of the session.
+    #[maybe_async]
+    async fn _bind_alt_channel(
+        &self,
+        unc: &UncPath,
+        session: &Session,
+        credentials: &Credentials,
+        interface: &NetworkInterfaceInfo,
+    ) -> crate::Result<(Arc<Connection>, u32)> {
+        let address = interface.sockaddr.socket_addr();
+        let connection = if interface.capability.rdma() && cfg!(feature = "rdma") {
+            self._connect_transport_to_addresses(
+                unc.server(),
+                vec![address],
+                #[cfg(feature = "rdma")]
+                Some(TransportConfig::Rdma(crate::transport::RdmaConfig {
+                    rdma_type: self.config.rdma_type.ok_or_else(|| {
+                        Error::InvalidConfiguration(
+                            "RDMA transport type is not specified in client configuration."
+                                .to_string(),
+                        )
+                    })?,
+                })),
+                #[cfg(not(feature = "rdma"))]
+                None,
+            )
+            .await?
+        } else {
+            self.connect_to_address(unc.server(), address).await?
+        };
+
+        let channel = connection
+            .bind_session(session, credentials.clone())
+            .await?;
+        Ok((connection, channel))
+    }
 }
 
 impl Default for Client {
@@ -758,19 +1315,34 @@ impl<'a> DfsResolver<'a> {
     }
 
     /// Resolves the DFS referral for the given UNC path and re-creates a file on the resolved path.
+    ///
+    /// The referral is cached, see [`Client::create_file`].
     #[maybe_async]
     async fn resolve_to_dfs_file(
         &self,
         dfs_path: &UncPath,
         args: &FileCreateArgs,
     ) -> crate::Result<Resource> {
-        let dfs_ref_paths = self.get_dfs_refs(dfs_path).await?;
+        let referral = self.get_dfs_referral(dfs_path).await?;
+        self.client.dfs_referrals.insert(referral.clone());
+        self.create_on_targets(dfs_path, &referral, args).await
+    }
+
+    /// Creates a file on the given path under the first target of the referral that may be connected.
+    #[maybe_async]
+    async fn create_on_targets(
+        &self,
+        dfs_path: &UncPath,
+        referral: &DfsReferral,
+        args: &FileCreateArgs,
+    ) -> crate::Result<Resource> {
+        let dfs_ref_paths = referral.targets_for(&dfs_path.to_string())?;
 
         // Re-use the same credentials for the DFS referral.
         let dfs_creds = self.client._get_credentials(dfs_path).await?;
 
         // Open the next DFS referral. Try each referral path, since some may be down.
-        for ref_unc_path in dfs_ref_paths.iter() {
+        for (target, ref_unc_path) in dfs_ref_paths.iter() {
             // Try opening the share. Log failure, and try next ref.
             if let Err(e) = self.client._share_connect(ref_unc_path, &dfs_creds).await {
                 log::error!("Failed to open DFS referral: {e}",);
@@ -786,132 +1358,35 @@ impl<'a> DfsResolver<'a> {
                     e
                 })?;
             log::info!("Successfully created file on DFS referral: {ref_unc_path}",);
+            self.client.dfs_referrals.set_hint(referral, *target);
             return Ok(resource);
         }
+        // None of the targets is available: query the referral again next time.
+        self.client.dfs_referrals.remove(referral);
         Err(Error::DfsError(dfs_path.clone()))
     }
 
-    /// Returns a list of DFS referral paths for the given input UNC path.
+    /// Queries the DFS referral of the given UNC path from its DFS root,
+    /// with the site name of [`ClientConfig::dfs_site_name`], if configured.
     #[maybe_async]
-    async fn get_dfs_refs(&self, unc: &UncPath) -> crate::Result<Vec<UncPath>> {
+    async fn get_dfs_referral(&self, unc: &UncPath) -> crate::Result<DfsReferral> {
         log::debug!("Resolving DFS referral for {unc}");
         let dfs_path_string = unc.to_string();
 
         let dfs_refs = {
             let dfs_root = &self.client.get_tree(unc).await?;
-            dfs_root
-                .as_dfs_tree()?
-                .dfs_get_referrals(&dfs_path_string)
-                .await?
-        };
-        if !dfs_refs.referral_header_flags.storage_servers() {
-            return Err(Error::InvalidMessage(
-                "DFS referral does not contain storage servers".to_string(),
-            ));
-        }
-
-        let mut paths = vec![];
-        // Resolve the DFS referral entries.
-        for (indx, curr_referral) in dfs_refs.referral_entries.iter().enumerate() {
-            let is_first = indx == 0;
-            paths.push(self.ref_entry_to_dfs_target(
-                curr_referral,
-                dfs_refs.path_consumed as usize,
-                &dfs_path_string,
-                is_first,
-            )?);
-        }
-        Ok(paths)
-    }
-
-    /// Given a [`ReferralEntry`] result from a DFS referral query, returns a ready UNC path for the DFS target.
-    fn ref_entry_to_dfs_target(
-        &self,
-        entry: &ReferralEntry,
-        path_consumed: usize,
-        dfs_path_string: &str,
-        is_first: bool,
-    ) -> crate::Result<UncPath> {
-        match &entry.value {
-            ReferralEntryValue::V4(v4) => {
-                // First? verify flags.
-                if v4.referral_entry_flags == 0 && is_first {
-                    return Err(Error::InvalidMessage(
-                        "First DFS Referral is not primary one, invalid message!".to_string(),
-                    ));
+            let dfs_root = dfs_root.as_dfs_tree()?;
+            match self.client.config.dfs_site_name.as_deref() {
+                Some(site_name) => {
+                    dfs_root
+                        .dfs_get_referrals_ex(&dfs_path_string, Some(site_name))
+                        .await?
                 }
-                // The path consumed is a wstring index.
-                let index_end_of_match = path_consumed / std::mem::size_of::<u16>();
-
-                if index_end_of_match > dfs_path_string.len() {
-                    return Err(Error::InvalidMessage(
-                        "DFS path consumed is out of bounds".to_string(),
-                    ));
-                }
-
-                let suffix = if index_end_of_match < dfs_path_string.len() {
-                    dfs_path_string
-                        .char_indices()
-                        .nth(index_end_of_match)
-                        .ok_or_else(|| {
-                            Error::InvalidMessage("DFS path consumed is out of bounds".to_string())
-                        })?
-                        .0
-                } else {
-                    // Empty -- exact cover.
-                    dfs_path_string.len()
-                };
-
-                let unc_str_dest = "\\".to_string()
-                    + &v4.refs.network_address.to_string()
-                    + &dfs_path_string[suffix..];
-                let unc_path = UncPath::from_str(&unc_str_dest)?;
-                log::debug!("Resolved DFS referral to {unc_path}",);
-                Ok(unc_path)
+                None => dfs_root.dfs_get_referrals(&dfs_path_string).await?,
             }
-            _ => Err(Error::UnsupportedOperation(
-                "Unsupported DFS referral entry type".to_string(),
-            )),
-        }
-    }
-}
-
-struct MultiChannelUtils;
-impl MultiChannelUtils {
-    /// Given the list of network interfaces on the client machine,
-    /// this returns a map of relevant interface indexes to their IP addresses,
-    /// which are relevant for multi-channel connections.
-    fn get_alt_interface_addresses(
-        network_interfaces: &[NetworkInterfaceInfo],
-        current_server_address: IpAddr,
-        rdma_only: bool,
-    ) -> crate::Result<HashMap<u32, &NetworkInterfaceInfo>> {
-        let current_primary_interface = network_interfaces
-            .iter()
-            .find(|iface| iface.sockaddr.socket_addr().ip() == current_server_address);
-
-        if current_primary_interface.is_none() {
-            return Ok(HashMap::new());
-        }
-
-        let current_primary_interface = current_primary_interface.unwrap();
-
-        let index_to_address = network_interfaces
-            .iter()
-            .filter(|iface| {
-                iface.sockaddr.socket_addr().is_ipv4()
-                    && iface.if_index != current_primary_interface.if_index
-            }) // TODO: IPv6; RDMA
-            .filter(|iface| {
-                if rdma_only {
-                    iface.capability.rdma()
-                } else {
-                    true
-                }
-            })
-            .map(|iface| (iface.if_index, iface))
-            .collect();
-
-        Ok(index_to_address)
+        };
+        let referral = DfsReferral::from_response(&dfs_path_string, &dfs_refs)?;
+        log::debug!("Resolved DFS referral of {}", referral.prefix());
+        Ok(referral)
     }
 }
@@ -0,0 +1,240 @@
+//! Snapshot (previous versions) helpers: [`Client::list_snapshots`] and [`Client::download_snapshot_tree`].
+//!
+//! Servers expose snapshots of shares, such as Volume Shadow Copies on Windows, or `shadow_copy2` snapshots on Samba.
+//! Files and directories are opened as of a snapshot by setting [`FileCreateArgs::snapshot`],
+//! and trees are walked as of a snapshot by setting [`WalkOptions::snapshot`][super::WalkOptions::snapshot].
+
+use maybe_async::*;
+use smb_dtyp::binrw_util::prelude::FileTime;
+use smb_fscc::FileAccessMask;
+
+use super::UncPath;
+use crate::{Client, Error, FileCreateArgs, Resource};
+
+#[cfg(feature = "std-fs-impls")]
+use {
+    super::download_dir::{local_names, set_local_times},
+    super::{WalkEntry, WalkOptions, WalkOrder},
+    crate::resource::{TransferOptions, TransferReport, resolve_local_names_with_existing},
+    std::collections::HashMap,
+    std::path::Path,
+};
+
+#[cfg(all(feature = "std-fs-impls", not(feature = "async")))]
+use std::fs;
+#[cfg(all(feature = "std-fs-impls", feature = "glob"))]
+use {
+    super::PathFilter,
+    std::sync::{Arc, atomic::AtomicU64},
+};
+#[cfg(all(feature = "std-fs-impls", feature = "async"))]
+use {futures_util::TryStreamExt, tokio::fs};
+
+#[maybe_async(AFIT)]
+impl Client {
+    /// Lists the snapshots of a remote directory. See [`Directory::enumerate_snapshots`][crate::Directory::enumerate_snapshots].
+    ///
+    /// ## Arguments
+    /// * `path` - The UNC path of the directory, usually the root of the share.
+    ///   The share must be connected using [`Client::share_connect`].
+    ///
+    /// ## Returns
+    /// The times of the snapshots, to be used with [`Client::download_snapshot_tree`] or [`FileCreateArgs::snapshot`].
+    pub async fn list_snapshots(&self, path: &UncPath) -> crate::Result<Vec<FileTime>> {
+        let access = FileAccessMask::new()
+            .with_file_read_data(true)
+            .with_file_read_attributes(true)
+            .with_synchronize(true);
+        let dir = match self
+            .create_file(path, &FileCreateArgs::make_open_existing(access))
+            .await?
+        {
+            Resource::Directory(dir) => dir,
+            resource => {
+                resource.handle().close().await?;
+                return Err(Error::InvalidArgument(format!("{path} is not a directory")));
+            }
+        };
+
+        let snapshots = dir.enumerate_snapshots().await;
+        dir.close().await?;
+        snapshots
+    }
+
+    /// Downloads a remote directory tree as of a snapshot, for restoring files that were deleted or modified since.
+    ///
+    /// The tree is walked using [`Client::walk`], and every directory and file is opened as of the snapshot.
+    /// The tree is merged into the existing content of `local_dest`: files that exist both locally and in the snapshot
+    /// are overwritten, and other local files are kept.
+    /// Remote names that collide with each other, or with existing local names, on case- or normalization-insensitive
+    /// file systems are handled according to [`TransferOptions::name_collision_policy`].
+    ///
+    /// The last write and last access times of the downloaded files and directories are preserved, where possible.
+    /// Directories that are reparse points are skipped.
+    ///
+    /// ## Arguments
+    /// * `share` - The UNC path of the share. The share must be connected using [`Client::share_connect`].
+    /// * `snapshot` - The time of the snapshot, as returned by [`Client::list_snapshots`].
+    /// * `remote_subpath` - The path of the directory to download, relative to the share.
+    ///   An empty path downloads the whole share.
+    /// * `local_dest` - The local directory to download into. It is created if it does not exist.
+    /// * `options` - See [`TransferOptions`]. Files are downloaded one chunk at a time, in [`TransferOptions::chunk_size`] chunks.
+    ///
+    /// ## Returns
+    /// A report of the download, see [`TransferReport`].
+    /// The download stops at the first error, keeping everything that was downloaded so far.
+    #[cfg(feature = "std-fs-impls")]
+    pub async fn download_snapshot_tree(
+        &self,
+        share: &UncPath,
+        snapshot: FileTime,
+        remote_subpath: &str,
+        local_dest: &Path,
+        options: &TransferOptions,
+    ) -> crate::Result<TransferReport> {
+        let root = share.clone().with_path(remote_subpath);
+        let walk_options = WalkOptions {
+            order: WalkOrder::DepthFirst,
+            snapshot: Some(snapshot),
+            #[cfg(feature = "glob")]
+            filter: options.filter.clone(),
+            ..Default::default()
+        };
+        let mut report = TransferReport::default();
+        let entries = self
+            ._collect_walk(&root, &walk_options, &mut report)
+            .await?;
+        let mut children = group_by_parent(entries, &root);
+
+        fs::create_dir_all(local_dest).await?;
+        let mut pending = vec![(root, local_dest.to_path_buf())];
+        let mut dirs = vec![];
+        while let Some((remote_dir, local_dir)) = pending.pop() {
+            let entries = children.remove(&remote_dir).unwrap_or_default();
+            let names = entries.iter().map(WalkEntry::name).collect::<Vec<_>>();
+            let existing = local_names(&local_dir).await?;
+            let local_names = resolve_local_names_with_existing(
+                &names,
+                &existing,
+                options.name_collision_policy,
+                &mut report,
+            )?;
+
+            for (entry, local_name) in entries.into_iter().zip(local_names) {
+                let Some(local_name) = local_name else {
+                    continue;
+                };
+                let local_path = local_dir.join(local_name);
+                if !entry.is_directory() {
+                    log::debug!("Restoring {} to {}", entry.path, local_path.display());
+                    self._download_snapshot_file(&entry.path, snapshot, &local_path, options)
+                        .await?;
+                    set_local_times(&local_path, &entry.info);
+                } else if entry.info.file_attributes.reparse_point() {
+                    log::warn!(
+                        "Skipping {}: directory reparse points are not restored",
+                        entry.path
+                    );
+                } else {
+                    fs::create_dir_all(&local_path).await?;
+                    dirs.push((local_path.clone(), entry.info));
+                    pending.push((entry.path, local_path));
+                }
+            }
+        }
+        // Creating entries updates the times of their directory, so these are set last, innermost first.
+        for (local_path, info) in dirs.iter().rev() {
+            set_local_times(local_path, info);
+        }
+        Ok(report)
+    }
+
+    #[cfg(feature = "std-fs-impls")]
+    async fn _download_snapshot_file(
+        &self,
+        path: &UncPath,
+        snapshot: FileTime,
+        local_path: &Path,
+        options: &TransferOptions,
+    ) -> crate::Result<()> {
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::standard_read())
+            .with_snapshot(Some(snapshot));
+        let file = Self::_unwrap_file(self.create_file(path, &args).await?, path).await?;
+
+        let downloaded = Self::_download_to(
+            &file,
+            local_path,
+            options.chunk_size,
+            options.integrity.as_ref(),
+        )
+        .await;
+        file.close().await?;
+        downloaded
+    }
+
+    /// Collects a walk, recording the matches of [`WalkOptions::filter`] in the `report`.
+    #[cfg(feature = "std-fs-impls")]
+    #[async_impl]
+    async fn _collect_walk(
+        &self,
+        root: &UncPath,
+        options: &WalkOptions,
+        #[allow(unused_variables)] report: &mut TransferReport,
+    ) -> crate::Result<Vec<WalkEntry>> {
+        #[cfg(feature = "glob")]
+        if let Some(filter) = &options.filter {
+            let matches = new_filter_matches(filter);
+            let entries = self
+                .walk_counting_matches(root, options, matches.clone())
+                .try_collect()
+                .await;
+            report.filter_matches = filter.pattern_matches(&matches);
+            return entries;
+        }
+        self.walk(root, options).try_collect().await
+    }
+
+    /// Collects a walk, recording the matches of [`WalkOptions::filter`] in the `report`.
+    #[cfg(feature = "std-fs-impls")]
+    #[sync_impl]
+    fn _collect_walk(
+        &self,
+        root: &UncPath,
+        options: &WalkOptions,
+        #[allow(unused_variables)] report: &mut TransferReport,
+    ) -> crate::Result<Vec<WalkEntry>> {
+        #[cfg(feature = "glob")]
+        if let Some(filter) = &options.filter {
+            let matches = new_filter_matches(filter);
+            let entries = self
+                .walk_counting_matches(root, options, matches.clone())
+                .collect();
+            report.filter_matches = filter.pattern_matches(&matches);
+            return entries;
+        }
+        self.walk(root, options).collect()
+    }
+}
+
+/// Returns a zeroed match count for each pattern of the `filter`.
+#[cfg(all(feature = "std-fs-impls", feature = "glob"))]
+fn new_filter_matches(filter: &PathFilter) -> Arc<[AtomicU64]> {
+    (0..filter.len()).map(|_| AtomicU64::new(0)).collect()
+}
+
+/// Groups the entries of a depth-first walk of `root` by their parent directory.
+#[cfg(feature = "std-fs-impls")]
+fn group_by_parent(entries: Vec<WalkEntry>, root: &UncPath) -> HashMap<UncPath, Vec<WalkEntry>> {
+    // The directories from the root to the current entry: the parent of an entry at depth `n` is at index `n`.
+    let mut parents = vec![root.clone()];
+    let mut children: HashMap<UncPath, Vec<WalkEntry>> = HashMap::new();
+    for entry in entries {
+        parents.truncate(entry.depth + 1);
+        let parent = parents[entry.depth].clone();
+        if entry.is_directory() {
+            parents.push(entry.path.clone());
+        }
+        children.entry(parent).or_default().push(entry);
+    }
+    children
+}
@@ -0,0 +1,310 @@
+//! Querying the metadata of many paths at once.
+//!
+//! See [`Client::stat_many`] for more information.
+
+#[cfg(feature = "async")]
+use futures_core::Stream;
+#[cfg(feature = "async")]
+use futures_util::{
+    StreamExt,
+    future::BoxFuture,
+    stream::{self, FuturesUnordered},
+};
+use maybe_async::*;
+use smb_fscc::{
+    FileAccessMask, FileAllInformation, FileAttributeTagInformation, FileIdBothDirectoryInformation,
+};
+use smb_msg::Status;
+use std::collections::{HashMap, VecDeque};
+
+use super::{DirEntry, UncPath};
+use crate::tree::PathInfo;
+use crate::{Client, Error, FileCreateArgs};
+
+/// Options for [`Client::stat_many`].
+#[derive(Debug, Clone)]
+pub struct StatManyOptions {
+    /// The maximum number of paths queried at the same time, per connection.
+    ///
+    /// Each path is queried using a single compound request, so this bounds the number of compounds
+    /// in flight on each connection. Good values depend on the latency to the server, and on the credits
+    /// it grants; defaults to [`StatManyOptions::DEFAULT_MAX_IN_FLIGHT`].
+    ///
+    /// This is ignored in sync builds, where paths are always queried one by one.
+    pub max_in_flight: usize,
+}
+
+impl StatManyOptions {
+    pub const DEFAULT_MAX_IN_FLIGHT: usize = 32;
+}
+
+impl Default for StatManyOptions {
+    fn default() -> Self {
+        Self {
+            max_in_flight: Self::DEFAULT_MAX_IN_FLIGHT,
+        }
+    }
+}
+
+impl Client {
+    /// Returns the metadata of many paths, possibly spanning multiple shares and servers.
+    ///
+    /// Unlike [`Client::stat`], each path is opened: the open, the metadata queries and the close
+    /// are sent in a single compound request, and compounds are pipelined on each connection
+    /// (see [`StatManyOptions::max_in_flight`]).
+    /// Paths are grouped by their tree, which must be connected using [`Client::share_connect`].
+    /// Paths of DFS shares, and of connections that do not support multi-credit requests, are queried
+    /// one request at a time.
+    ///
+    /// Since the entries are not listed from their parent directory, [`FileIdBothDirectoryInformation::file_index`]
+    /// is always 0, and the entries have no short names.
+    /// The metadata cache is neither used nor updated.
+    ///
+    /// ## Arguments
+    /// * `paths` - The UNC paths to query.
+    /// * `options` - See [`StatManyOptions`].
+    ///
+    /// ## Returns
+    /// A stream of the paths, each with its metadata, in no particular order. The stream must be pinned before it is polled.
+    /// A path that fails to be queried (e.g. it does not exist, or access is denied) is yielded with its error,
+    /// and the rest of the paths are still queried.
+    #[cfg(feature = "async")]
+    pub fn stat_many<'a>(
+        &'a self,
+        paths: impl IntoIterator<Item = UncPath>,
+        options: &StatManyOptions,
+    ) -> impl Stream<Item = (UncPath, crate::Result<DirEntry>)> + 'a {
+        type Query<'b> = BoxFuture<'b, (usize, UncPath, crate::Result<DirEntry>)>;
+
+        let max_in_flight = options.max_in_flight.max(1);
+        let queues = StatQueues::new(paths);
+        let in_flight: FuturesUnordered<Query<'a>> = FuturesUnordered::new();
+        stream::unfold(
+            (queues, in_flight),
+            move |(mut queues, mut in_flight)| async move {
+                while let Some((connection, path)) = queues.next_launch(max_in_flight) {
+                    in_flight.push(Box::pin(async move {
+                        let result = self._stat_path(&path).await;
+                        (connection, path, result)
+                    }));
+                }
+                let (connection, path, result) = in_flight.next().await?;
+                queues.complete(connection);
+                Some(((path, result), (queues, in_flight)))
+            },
+        )
+    }
+
+    /// Returns the metadata of many paths, possibly spanning multiple shares and servers.
+    ///
+    /// Unlike [`Client::stat`], each path is opened: the open, the metadata queries and the close
+    /// are sent in a single compound request.
+    /// Paths are grouped by their tree, which must be connected using [`Client::share_connect`].
+    /// Paths of DFS shares, and of connections that do not support multi-credit requests, are queried
+    /// one request at a time.
+    ///
+    /// Since the entries are not listed from their parent directory, [`FileIdBothDirectoryInformation::file_index`]
+    /// is always 0, and the entries have no short names.
+    /// The metadata cache is neither used nor updated.
+    ///
+    /// ## Arguments
+    /// * `paths` - The UNC paths to query.
+    /// * `options` - See [`StatManyOptions`].
+    ///
+    /// ## Returns
+    /// An iterator over the paths, each with its metadata, grouped by their tree.
+    /// A path that fails to be queried (e.g. it does not exist, or access is denied) is yielded with its error,
+    /// and the rest of the paths are still queried.
+    #[cfg(not(feature = "async"))]
+    pub fn stat_many<'a>(
+        &'a self,
+        paths: impl IntoIterator<Item = UncPath>,
+        _options: &StatManyOptions,
+    ) -> impl Iterator<Item = (UncPath, crate::Result<DirEntry>)> + 'a {
+        let mut queues = StatQueues::new(paths);
+        std::iter::from_fn(move || {
+            let (connection, path) = queues.next_launch(1)?;
+            let result = self._stat_path(&path);
+            queues.complete(connection);
+            Some((path, result))
+        })
+    }
+
+    /// Queries the metadata of a single path of [`Client::stat_many`].
+    #[maybe_async]
+    async fn _stat_path(&self, path: &UncPath) -> crate::Result<DirEntry> {
+        let tree = self.get_tree(path).await?;
+        let info = if tree.can_compound_opens()? {
+            let name = path.path().unwrap_or_default().trim_start_matches('\\');
+            let pending = tree.send_stat_compound(name).await?;
+            match tree.receive_stat_compound(pending).await {
+                Err(Error::ServerError(Status::PathNotCovered, _)) if self.config().dfs => {
+                    self._stat_opened(path).await?
+                }
+                result => result?,
+            }
+        } else {
+            self._stat_opened(path).await?
+        };
+        Ok(Self::_path_info_entry(path, info))
+    }
+
+    /// Queries the metadata of a path one request at a time, resolving DFS paths if required.
+    #[maybe_async]
+    async fn _stat_opened(&self, path: &UncPath) -> crate::Result<PathInfo> {
+        let mut args = FileCreateArgs::make_open_existing(
+            FileAccessMask::new().with_file_read_attributes(true),
+        );
+        args.options = args.options.with_open_reparse_point(true);
+        let resource = self.create_file(path, &args).await?;
+        let handle = resource.handle();
+
+        let info = match handle.query_info::<FileAllInformation>().await {
+            Ok(all) => handle
+                .query_info::<FileAttributeTagInformation>()
+                .await
+                .map(|tag| PathInfo { all, tag }),
+            Err(e) => Err(e),
+        };
+        handle.close().await?;
+        info
+    }
+
+    /// Converts the queried metadata of a path to a [`DirEntry`].
+    fn _path_info_entry(path: &UncPath, info: PathInfo) -> DirEntry {
+        let PathInfo { all, tag } = info;
+        let name = path
+            .path()
+            .unwrap_or_default()
+            .trim_matches('\\')
+            .rsplit('\\')
+            .next()
+            .unwrap_or_default();
+        let reparse_point = all.basic.file_attributes.reparse_point();
+        DirEntry {
+            path: path.clone(),
+            info: FileIdBothDirectoryInformation {
+                file_index: 0,
+                creation_time: all.basic.creation_time,
+                last_access_time: all.basic.last_access_time,
+                last_write_time: all.basic.last_write_time,
+                change_time: all.basic.change_time,
+                end_of_file: all.standard.end_of_file,
+                allocation_size: all.standard.allocation_size,
+                file_attributes: all.basic.file_attributes,
+                ea_size: (!reparse_point).then_some(all.ea.ea_size),
+                reparse_tag: reparse_point.then_some(tag.reparse_tag),
+                short_name_length: 0,
+                short_name: Default::default(),
+                file_id: all.internal.index_number,
+                file_name: name.into(),
+            },
+        }
+    }
+}
+
+/// The paths of a [`Client::stat_many`] call that are yet to be queried,
+/// grouped by their connection, and then by their tree.
+struct StatQueues {
+    connections: Vec<ConnectionQueue>,
+}
+
+struct ConnectionQueue {
+    paths: VecDeque<UncPath>,
+    in_flight: usize,
+}
+
+impl StatQueues {
+    fn new(paths: impl IntoIterator<Item = UncPath>) -> Self {
+        // server => (share => paths), keeping the order in which servers and shares first appear.
+        let mut servers: Vec<Vec<Vec<UncPath>>> = vec![];
+        let mut server_index = HashMap::new();
+        let mut share_index = HashMap::new();
+        for path in paths {
+            let tree = path.clone().with_no_path().normalized();
+            let server = *server_index
+                .entry(tree.server().to_string())
+                .or_insert_with(|| {
+                    servers.push(vec![]);
+                    servers.len() - 1
+                });
+            let shares = &mut servers[server];
+            let share = *share_index.entry(tree).or_insert_with(|| {
+                shares.push(vec![]);
+                shares.len() - 1
+            });
+            shares[share].push(path);
+        }
+
+        Self {
+            connections: servers
+                .into_iter()
+                .map(|shares| ConnectionQueue {
+                    paths: shares.into_iter().flatten().collect(),
+                    in_flight: 0,
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the next path to query, and the index of its connection,
+    /// if any connection has less than `max_in_flight` queries in flight.
+    fn next_launch(&mut self, max_in_flight: usize) -> Option<(usize, UncPath)> {
+        self.connections
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, queue)| queue.in_flight < max_in_flight)
+            .find_map(|(index, queue)| {
+                let path = queue.paths.pop_front()?;
+                queue.in_flight += 1;
+                Some((index, path))
+            })
+    }
+
+    /// Marks a query of the connection launched by [`StatQueues::next_launch`] as completed.
+    fn complete(&mut self, connection: usize) {
+        self.connections[connection].in_flight -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::StatQueues;
+    use crate::UncPath;
+
+    #[test]
+    fn test_stat_queues_grouping() {
+        let paths = [
+            r"\\server1\share\a",
+            r"\\server2\share\b",
+            r"\\server1\other\c",
+            r"\\SERVER1\Share\d",
+            r"\\server2\share\e",
+        ]
+        .map(|p| UncPath::from_str(p).unwrap());
+        let mut queues = StatQueues::new(paths.clone());
+
+        // Up to 2 queries per connection, with the paths of each connection grouped by tree.
+        let mut launched = vec![];
+        while let Some((connection, path)) = queues.next_launch(2) {
+            launched.push((connection, path));
+        }
+        assert_eq!(
+            launched,
+            [
+                (0, paths[0].clone()),
+                (0, paths[3].clone()),
+                (1, paths[1].clone()),
+                (1, paths[4].clone()),
+            ]
+        );
+
+        queues.complete(1);
+        assert_eq!(queues.next_launch(2), None);
+        queues.complete(0);
+        assert_eq!(queues.next_launch(2), Some((0, paths[2].clone())));
+        assert_eq!(queues.next_launch(2), None);
+    }
+}
@@ -0,0 +1,891 @@
+//! Recursive directory traversal.
+//!
+//! See [`Client::walk`] for more information.
+
+use crate::{Client, Directory, Error, FileCreateArgs, QueryDirectoryOptions, Resource, UncPath};
+#[cfg(feature = "async")]
+use futures_core::Stream;
+#[cfg(feature = "async")]
+use futures_util::{
+    FutureExt, StreamExt,
+    future::BoxFuture,
+    stream::{self, FuturesUnordered},
+};
+use maybe_async::*;
+use smb_dtyp::binrw_util::prelude::FileTime;
+use smb_fscc::{FileAccessMask, FileIdBothDirectoryInformation};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+#[cfg(feature = "glob")]
+use {
+    super::path_filter::{FilterDecision, PathFilter},
+    std::sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The order in which [`Client::walk`] yields entries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WalkOrder {
+    /// Entries are yielded as soon as their directory is listed.
+    /// All entries are yielded, but the order between directories is arbitrary when listing in parallel.
+    ///
+    /// When listing one directory at a time, this is breadth-first order.
+    #[default]
+    Unordered,
+    /// Strict depth-first (pre-order) order: each directory entry is immediately followed by
+    /// the entries of that directory. This is deterministic regardless of the parallelism level,
+    /// but may have to wait for a specific listing to complete, while others are already available.
+    DepthFirst,
+}
+
+/// Options for [`Client::walk`].
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    /// The maximum number of directory listings in flight at the same time.
+    ///
+    /// Each listing requires an open handle of its directory, so this also bounds
+    /// the number of handles opened by the walk.
+    /// Good values depend on the latency to the server; defaults to 1 (a single directory at a time).
+    ///
+    /// This is ignored in sync builds, where directories are always listed one by one.
+    pub parallelism: usize,
+
+    /// The order of the yielded entries. See [`WalkOrder`].
+    pub order: WalkOrder,
+
+    /// The maximum depth to descend into, where entries of the root directory are at depth 0.
+    /// `Some(0)` only lists the root directory. If unset, the whole tree is walked.
+    pub max_depth: Option<usize>,
+
+    /// If set, walks the tree as of this snapshot time, instead of its current state.
+    /// Every directory of the walk is opened as of the snapshot.
+    /// See [`Client::list_snapshots`].
+    pub snapshot: Option<FileTime>,
+
+    /// If set, entries excluded by the filter are not yielded, and excluded directories are not listed.
+    /// See [`PathFilter`].
+    #[cfg(feature = "glob")]
+    pub filter: Option<Arc<PathFilter>>,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            parallelism: 1,
+            order: WalkOrder::default(),
+            max_depth: None,
+            snapshot: None,
+            #[cfg(feature = "glob")]
+            filter: None,
+        }
+    }
+}
+
+/// An entry yielded by [`Client::walk`].
+#[derive(Debug)]
+pub struct WalkEntry {
+    /// The full path of the entry.
+    pub path: UncPath,
+    /// The depth of the entry, where entries of the root directory are at depth 0.
+    pub depth: usize,
+    /// The information of the entry, as returned by listing its parent directory.
+    pub info: FileIdBothDirectoryInformation,
+}
+
+impl WalkEntry {
+    /// Returns the name of the entry.
+    pub fn name(&self) -> String {
+        self.info.file_name.to_string()
+    }
+
+    /// Returns whether the entry is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.info.file_attributes.directory()
+    }
+}
+
+impl Client {
+    /// Recursively walks the directory tree under the specified path.
+    ///
+    /// The `.` and `..` entries are never yielded. Directories that are reparse points
+    /// (e.g. junctions and directory symbolic links) are yielded, but not descended into.
+    ///
+    /// ## Arguments
+    /// * `root` - The UNC path of the directory to walk. The share must be connected using [`Client::share_connect`].
+    /// * `options` - See [`WalkOptions`].
+    ///
+    /// ## Returns
+    /// A stream of the entries under `root`. The stream must be pinned before it is polled.
+    /// If a directory fails to be listed, an error is yielded in place of its entries, and the walk continues.
+    ///
+    /// Dropping the stream cancels the walk, and closes the directory handles that are still open.
+    #[cfg(feature = "async")]
+    pub fn walk<'a>(
+        &'a self,
+        root: &UncPath,
+        options: &WalkOptions,
+    ) -> impl Stream<Item = crate::Result<WalkEntry>> + 'a {
+        self._walk(WalkState::new(root.clone(), options), options.snapshot)
+    }
+
+    #[cfg(feature = "async")]
+    fn _walk<'a>(
+        &'a self,
+        state: WalkState<FileIdBothDirectoryInformation>,
+        snapshot: Option<FileTime>,
+    ) -> impl Stream<Item = crate::Result<WalkEntry>> + 'a {
+        type Listing<'b> = BoxFuture<
+            'b,
+            (
+                PendingDir,
+                crate::Result<Vec<FileIdBothDirectoryInformation>>,
+            ),
+        >;
+
+        let in_flight: FuturesUnordered<Listing<'a>> = FuturesUnordered::new();
+        stream::unfold(
+            (state, in_flight),
+            move |(mut state, mut in_flight)| async move {
+                loop {
+                    while let Some(dir) = state.next_launch() {
+                        let path = dir.path.clone();
+                        in_flight.push(Box::pin(async move {
+                            (dir, self._list_walked_dir(path, snapshot).await)
+                        }));
+                    }
+                    // Keep the listings in flight progressing, while the caller consumes the entries.
+                    while let Some(Some((dir, listing))) = in_flight.next().now_or_never() {
+                        state.complete(dir, listing);
+                    }
+
+                    match state.next_output() {
+                        WalkStep::Item(item) => {
+                            return Some((item.map(|e| self._walked(e)), (state, in_flight)));
+                        }
+                        WalkStep::Done => return None,
+                        WalkStep::Wait => {
+                            let (dir, listing) = in_flight
+                                .next()
+                                .await
+                                .expect("Walk is waiting, but no listing is in flight");
+                            state.complete(dir, listing);
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    /// Recursively walks the directory tree under the specified path.
+    ///
+    /// The `.` and `..` entries are never yielded. Directories that are reparse points
+    /// (e.g. junctions and directory symbolic links) are yielded, but not descended into.
+    ///
+    /// ## Arguments
+    /// * `root` - The UNC path of the directory to walk. The share must be connected using [`Client::share_connect`].
+    /// * `options` - See [`WalkOptions`].
+    ///
+    /// ## Returns
+    /// An iterator over the entries under `root`.
+    /// If a directory fails to be listed, an error is yielded in place of its entries, and the walk continues.
+    #[cfg(not(feature = "async"))]
+    pub fn walk<'a>(
+        &'a self,
+        root: &UncPath,
+        options: &WalkOptions,
+    ) -> impl Iterator<Item = crate::Result<WalkEntry>> + 'a {
+        let options = WalkOptions {
+            parallelism: 1,
+            ..options.clone()
+        };
+        self._walk(WalkState::new(root.clone(), &options), options.snapshot)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn _walk<'a>(
+        &'a self,
+        mut state: WalkState<FileIdBothDirectoryInformation>,
+        snapshot: Option<FileTime>,
+    ) -> impl Iterator<Item = crate::Result<WalkEntry>> + 'a {
+        std::iter::from_fn(move || {
+            loop {
+                match state.next_output() {
+                    WalkStep::Item(item) => return Some(item.map(|e| self._walked(e))),
+                    WalkStep::Done => return None,
+                    WalkStep::Wait => {
+                        let dir = state
+                            .next_launch()
+                            .expect("Walk is waiting, but no listing may be started");
+                        let listing = self._list_walked_dir(dir.path.clone(), snapshot);
+                        state.complete(dir, listing);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Walks the directory tree under `root`, like [`Client::walk`], counting the entries each pattern of
+    /// [`WalkOptions::filter`] decided on into `filter_matches`, by the index of the pattern.
+    #[cfg(all(feature = "glob", feature = "std-fs-impls", feature = "async"))]
+    pub(crate) fn walk_counting_matches<'a>(
+        &'a self,
+        root: &UncPath,
+        options: &WalkOptions,
+        filter_matches: Arc<[AtomicU64]>,
+    ) -> impl Stream<Item = crate::Result<WalkEntry>> + 'a {
+        let state = WalkState::new(root.clone(), options).with_filter_matches(filter_matches);
+        self._walk(state, options.snapshot)
+    }
+
+    /// Walks the directory tree under `root`, like [`Client::walk`], counting the entries each pattern of
+    /// [`WalkOptions::filter`] decided on into `filter_matches`, by the index of the pattern.
+    #[cfg(all(feature = "glob", feature = "std-fs-impls", not(feature = "async")))]
+    pub(crate) fn walk_counting_matches<'a>(
+        &'a self,
+        root: &UncPath,
+        options: &WalkOptions,
+        filter_matches: Arc<[AtomicU64]>,
+    ) -> impl Iterator<Item = crate::Result<WalkEntry>> + 'a {
+        let options = WalkOptions {
+            parallelism: 1,
+            ..options.clone()
+        };
+        let state = WalkState::new(root.clone(), &options).with_filter_matches(filter_matches);
+        self._walk(state, options.snapshot)
+    }
+
+    /// Converts a walked entry, adding it to the metadata cache.
+    fn _walked(&self, entry: Entry<FileIdBothDirectoryInformation>) -> WalkEntry {
+        let entry = entry.into();
+        self._cache_listed_entry(&entry);
+        entry
+    }
+
+    /// Lists a directory of a walk, closing it once done.
+    #[maybe_async]
+    pub(super) async fn _list_walked_dir(
+        &self,
+        path: UncPath,
+        snapshot: Option<FileTime>,
+    ) -> crate::Result<Vec<FileIdBothDirectoryInformation>> {
+        let access = FileAccessMask::new()
+            .with_file_read_data(true)
+            .with_file_read_attributes(true)
+            .with_synchronize(true);
+        let dir = match self
+            .create_file(
+                &path,
+                &FileCreateArgs::make_open_existing(access).with_snapshot(snapshot),
+            )
+            .await?
+        {
+            Resource::Directory(dir) => Arc::new(dir),
+            _ => return Err(Error::InvalidState(format!("{path} is not a directory"))),
+        };
+
+        let listing = Self::_query_walked_dir(&dir).await;
+        let closed = dir.close().await;
+        let listing = listing?;
+        closed?;
+        Ok(listing)
+    }
+
+    #[maybe_async]
+    async fn _query_walked_dir(
+        dir: &Arc<Directory>,
+    ) -> crate::Result<Vec<FileIdBothDirectoryInformation>> {
+        Directory::query_directory::<FileIdBothDirectoryInformation>(
+            dir,
+            "*",
+            &QueryDirectoryOptions::DEFAULT,
+        )
+        .await?
+        .collect_entries()
+        .await
+    }
+}
+
+/// The information the walk requires about each listed entry.
+trait WalkInfo {
+    fn name(&self) -> String;
+    #[cfg(feature = "glob")]
+    fn is_dir(&self) -> bool;
+    /// Whether the entry is a directory that should be descended into.
+    fn is_traversable_dir(&self) -> bool;
+}
+
+impl WalkInfo for FileIdBothDirectoryInformation {
+    fn name(&self) -> String {
+        self.file_name.to_string()
+    }
+
+    #[cfg(feature = "glob")]
+    fn is_dir(&self) -> bool {
+        self.file_attributes.directory()
+    }
+
+    fn is_traversable_dir(&self) -> bool {
+        self.file_attributes.directory() && !self.file_attributes.reparse_point()
+    }
+}
+
+/// An entry of a walk, generic over the listing information for testing.
+struct Entry<T> {
+    path: UncPath,
+    depth: usize,
+    info: T,
+}
+
+impl From<Entry<FileIdBothDirectoryInformation>> for WalkEntry {
+    fn from(entry: Entry<FileIdBothDirectoryInformation>) -> Self {
+        Self {
+            path: entry.path,
+            depth: entry.depth,
+            info: entry.info,
+        }
+    }
+}
+
+/// A directory to be listed. Its entries are at `depth`.
+struct PendingDir {
+    id: usize,
+    path: UncPath,
+    depth: usize,
+}
+
+/// A listed directory, whose entries are being yielded in depth-first order.
+struct Frame<T> {
+    /// The remaining entries, and the listing id of each entry that is a directory to descend into.
+    entries: VecDeque<(Entry<T>, Option<usize>)>,
+    /// The directories of `entries` whose listing has not started yet, in order.
+    unlaunched: VecDeque<PendingDir>,
+}
+
+enum WalkStep<T> {
+    /// The next item to yield.
+    Item(crate::Result<T>),
+    /// The next item depends on a listing in flight.
+    Wait,
+    /// The walk is complete.
+    Done,
+}
+
+/// The scheduling state of a walk.
+///
+/// This is independent of the actual I/O: the driver starts the listings returned by [`WalkState::next_launch`],
+/// reports their results using [`WalkState::complete`], and yields the items returned by [`WalkState::next_output`].
+struct WalkState<T> {
+    parallelism: usize,
+    order: WalkOrder,
+    max_depth: Option<usize>,
+    next_id: usize,
+
+    /// The number of listings in flight, which is the number of directory handles in use.
+    in_flight: usize,
+    /// Entries ready to be yielded.
+    output: VecDeque<crate::Result<Entry<T>>>,
+
+    /// [`WalkOrder::Unordered`]: directories waiting to be listed.
+    queue: VecDeque<PendingDir>,
+
+    /// [`WalkOrder::DepthFirst`]: the stack of directories being yielded.
+    frames: Vec<Frame<T>>,
+    /// [`WalkOrder::DepthFirst`]: completed listings, which were not yielded yet.
+    ready: HashMap<usize, (PendingDir, crate::Result<Vec<T>>)>,
+    /// [`WalkOrder::DepthFirst`]: the listing that must be yielded next, and the directory
+    /// to list, if its listing has not started yet.
+    awaiting: Option<(usize, Option<PendingDir>)>,
+
+    /// The filter of the walk, and the root path, which filtered paths are relative to.
+    #[cfg(feature = "glob")]
+    filter: Option<(Arc<PathFilter>, String)>,
+    /// The number of entries each pattern of the filter decided on.
+    #[cfg(feature = "glob")]
+    filter_matches: Option<Arc<[AtomicU64]>>,
+}
+
+impl<T: WalkInfo> WalkState<T> {
+    fn new(root: UncPath, options: &WalkOptions) -> Self {
+        #[cfg(feature = "glob")]
+        let filter = options
+            .filter
+            .as_ref()
+            .map(|filter| (filter.clone(), root.to_string()));
+        let root = PendingDir {
+            id: 0,
+            path: root,
+            depth: 0,
+        };
+        let mut state = Self {
+            parallelism: options.parallelism.max(1),
+            order: options.order,
+            max_depth: options.max_depth,
+            next_id: 1,
+            in_flight: 0,
+            output: VecDeque::new(),
+            queue: VecDeque::new(),
+            frames: Vec::new(),
+            ready: HashMap::new(),
+            awaiting: None,
+            #[cfg(feature = "glob")]
+            filter,
+            #[cfg(feature = "glob")]
+            filter_matches: None,
+        };
+        match state.order {
+            WalkOrder::Unordered => state.queue.push_back(root),
+            WalkOrder::DepthFirst => state.awaiting = Some((0, Some(root))),
+        }
+        state
+    }
+
+    #[cfg(all(feature = "glob", any(feature = "std-fs-impls", test)))]
+    fn with_filter_matches(mut self, filter_matches: Arc<[AtomicU64]>) -> Self {
+        self.filter_matches = Some(filter_matches);
+        self
+    }
+
+    /// Returns the next directory to start listing, if the parallelism level allows it.
+    fn next_launch(&mut self) -> Option<PendingDir> {
+        if self.in_flight >= self.parallelism {
+            return None;
+        }
+        let next = match self.order {
+            WalkOrder::Unordered => self.queue.pop_front(),
+            WalkOrder::DepthFirst => match &mut self.awaiting {
+                // The listing that blocks the walk comes first.
+                Some((_, pending @ Some(_))) => pending.take(),
+                // Prefetch the next directories, in the order they are going to be yielded.
+                // Completed listings are counted as well, to bound the memory used by them.
+                _ if self.in_flight + self.ready.len() < self.parallelism => self
+                    .frames
+                    .iter_mut()
+                    .rev()
+                    .find_map(|frame| frame.unlaunched.pop_front()),
+                _ => None,
+            },
+        }?;
+        self.in_flight += 1;
+        Some(next)
+    }
+
+    /// Reports the result of a listing started by [`WalkState::next_launch`].
+    fn complete(&mut self, dir: PendingDir, listing: crate::Result<Vec<T>>) {
+        debug_assert!(self.in_flight > 0);
+        self.in_flight -= 1;
+        match self.order {
+            WalkOrder::Unordered => match listing {
+                Ok(listing) => {
+                    for (entry, subdir) in self.make_entries(&dir, listing) {
+                        if let Some(subdir) = subdir {
+                            self.queue.push_back(subdir);
+                        }
+                        self.output.push_back(Ok(entry));
+                    }
+                }
+                Err(e) => self.output.push_back(Err(e)),
+            },
+            WalkOrder::DepthFirst => {
+                self.ready.insert(dir.id, (dir, listing));
+            }
+        }
+    }
+
+    /// Returns the next item to yield.
+    fn next_output(&mut self) -> WalkStep<Entry<T>> {
+        loop {
+            if let Some(item) = self.output.pop_front() {
+                return WalkStep::Item(item);
+            }
+            match self.order {
+                WalkOrder::Unordered => {
+                    return if self.queue.is_empty() && self.in_flight == 0 {
+                        WalkStep::Done
+                    } else {
+                        WalkStep::Wait
+                    };
+                }
+                WalkOrder::DepthFirst => {
+                    // Descend into the directory that was just yielded.
+                    if let Some((id, _)) = self.awaiting {
+                        let Some((dir, listing)) = self.ready.remove(&id) else {
+                            return WalkStep::Wait;
+                        };
+                        self.awaiting = None;
+                        match listing {
+                            Ok(listing) => {
+                                let mut frame = Frame {
+                                    entries: VecDeque::new(),
+                                    unlaunched: VecDeque::new(),
+                                };
+                                for (entry, subdir) in self.make_entries(&dir, listing) {
+                                    frame
+                                        .entries
+                                        .push_back((entry, subdir.as_ref().map(|s| s.id)));
+                                    frame.unlaunched.extend(subdir);
+                                }
+                                self.frames.push(frame);
+                            }
+                            Err(e) => self.output.push_back(Err(e)),
+                        }
+                        continue;
+                    }
+
+                    let Some(frame) = self.frames.last_mut() else {
+                        return WalkStep::Done;
+                    };
+                    let Some((entry, subdir)) = frame.entries.pop_front() else {
+                        self.frames.pop();
+                        continue;
+                    };
+                    if let Some(subdir_id) = subdir {
+                        // Unless prefetched, the directory is the next one to be listed in this frame.
+                        let pending = match frame.unlaunched.front() {
+                            Some(pending) if pending.id == subdir_id => {
+                                frame.unlaunched.pop_front()
+                            }
+                            _ => None,
+                        };
+                        self.awaiting = Some((subdir_id, pending));
+                    }
+                    return WalkStep::Item(Ok(entry));
+                }
+            }
+        }
+    }
+
+    /// Converts a listing of a directory into entries, along with the directories to descend into.
+    fn make_entries(
+        &mut self,
+        dir: &PendingDir,
+        listing: Vec<T>,
+    ) -> Vec<(Entry<T>, Option<PendingDir>)> {
+        let depth = dir.depth;
+        let descend = self.max_depth.is_none_or(|max_depth| depth < max_depth);
+        listing
+            .into_iter()
+            .filter_map(|info| {
+                let name = info.name();
+                // Never descend into these, even if the listing includes them.
+                if name == "." || name == ".." {
+                    return None;
+                }
+                let entry = Entry {
+                    path: dir.path.clone().with_add_path(&name),
+                    depth,
+                    info,
+                };
+                #[cfg(feature = "glob")]
+                let descend = match self.filter_entry(&entry) {
+                    FilterDecision::Included(_) => descend,
+                    FilterDecision::Excluded(_) => return None,
+                    FilterDecision::Pruned(_) => false,
+                };
+                let subdir = (descend && entry.info.is_traversable_dir()).then(|| {
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    PendingDir {
+                        id,
+                        path: entry.path.clone(),
+                        depth: depth + 1,
+                    }
+                });
+                Some((entry, subdir))
+            })
+            .collect()
+    }
+
+    /// Decides whether to yield an entry, and whether to descend into it, counting the deciding pattern.
+    #[cfg(feature = "glob")]
+    fn filter_entry(&self, entry: &Entry<T>) -> FilterDecision {
+        let Some((filter, root)) = &self.filter else {
+            return FilterDecision::Included(None);
+        };
+        let path = entry.path.to_string();
+        let relative_path = path
+            .strip_prefix(root.as_str())
+            .unwrap_or(&path)
+            .trim_start_matches('\\')
+            .replace('\\', "/");
+        let decision = filter.decide(&relative_path, entry.info.is_dir());
+        if let (Some(index), Some(matches)) =
+            (PathFilter::decided_by(decision), &self.filter_matches)
+        {
+            matches[index].fetch_add(1, Ordering::Relaxed);
+        }
+        decision
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PendingDir, WalkInfo, WalkOptions, WalkOrder, WalkState, WalkStep};
+    use crate::{Error, UncPath};
+    use std::collections::HashMap;
+
+    struct TestInfo {
+        name: String,
+        is_dir: bool,
+    }
+
+    impl WalkInfo for TestInfo {
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        #[cfg(feature = "glob")]
+        fn is_dir(&self) -> bool {
+            self.is_dir
+        }
+
+        fn is_traversable_dir(&self) -> bool {
+            self.is_dir
+        }
+    }
+
+    fn root() -> UncPath {
+        UncPath::new("server").unwrap().with_share("share").unwrap()
+    }
+
+    /// Builds a tree of `width` subdirectories and one file per directory, `depth` levels deep.
+    fn make_tree(
+        tree: &mut HashMap<String, Vec<(String, bool)>>,
+        path: &UncPath,
+        width: usize,
+        depth: usize,
+    ) {
+        let mut entries = vec![(".".to_string(), true), ("..".to_string(), true)];
+        entries.push(("file.txt".to_string(), false));
+        if depth > 0 {
+            for i in 0..width {
+                let name = format!("dir{i}");
+                make_tree(tree, &path.clone().with_add_path(&name), width, depth - 1);
+                entries.push((name, true));
+            }
+        }
+        tree.insert(path.to_string(), entries);
+    }
+
+    fn list(
+        tree: &HashMap<String, Vec<(String, bool)>>,
+        path: &UncPath,
+    ) -> crate::Result<Vec<TestInfo>> {
+        match tree.get(&path.to_string()) {
+            Some(entries) => Ok(entries
+                .iter()
+                .map(|(name, is_dir)| TestInfo {
+                    name: name.clone(),
+                    is_dir: *is_dir,
+                })
+                .collect()),
+            None => Err(Error::Other("No such directory")),
+        }
+    }
+
+    /// Runs a walk, completing the listings in flight in a pseudo-random order.
+    /// Returns the yielded paths (or `None` for errors), and the maximum number of listings in flight.
+    fn run_walk(
+        tree: &HashMap<String, Vec<(String, bool)>>,
+        options: &WalkOptions,
+    ) -> (Vec<Option<String>>, usize) {
+        let (result, max_in_flight, _) = run_walk_state(tree, WalkState::new(root(), options));
+        (result, max_in_flight)
+    }
+
+    /// Runs a walk like [`run_walk`], also returning the listed directories.
+    fn run_walk_state(
+        tree: &HashMap<String, Vec<(String, bool)>>,
+        mut state: WalkState<TestInfo>,
+    ) -> (Vec<Option<String>>, usize, Vec<String>) {
+        let mut listed = vec![];
+        let mut in_flight: Vec<PendingDir> = vec![];
+        let mut max_in_flight = 0;
+        let mut seed = 17usize;
+        let mut result = vec![];
+        loop {
+            while let Some(dir) = state.next_launch() {
+                in_flight.push(dir);
+            }
+            max_in_flight = max_in_flight.max(in_flight.len());
+            assert_eq!(in_flight.len(), state.in_flight);
+
+            match state.next_output() {
+                WalkStep::Item(item) => result.push(item.ok().map(|e| e.path.to_string())),
+                WalkStep::Done => break,
+                WalkStep::Wait => {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    let dir = in_flight.swap_remove((seed >> 8) % in_flight.len());
+                    listed.push(dir.path.to_string());
+                    let listing = list(tree, &dir.path);
+                    state.complete(dir, listing);
+                }
+            }
+        }
+        assert!(in_flight.is_empty());
+        (result, max_in_flight, listed)
+    }
+
+    fn dfs(
+        tree: &HashMap<String, Vec<(String, bool)>>,
+        path: &UncPath,
+        result: &mut Vec<Option<String>>,
+    ) {
+        for (name, is_dir) in &tree[&path.to_string()] {
+            if name == "." || name == ".." {
+                continue;
+            }
+            let child = path.clone().with_add_path(name);
+            result.push(Some(child.to_string()));
+            if *is_dir {
+                dfs(tree, &child, result);
+            }
+        }
+    }
+
+    #[test]
+    fn test_walk_depth_first_order() {
+        let mut tree = HashMap::new();
+        make_tree(&mut tree, &root(), 3, 3);
+        let mut expected = vec![];
+        dfs(&tree, &root(), &mut expected);
+
+        for parallelism in [1, 2, 4, 16] {
+            let options = WalkOptions {
+                parallelism,
+                order: WalkOrder::DepthFirst,
+                max_depth: None,
+                ..Default::default()
+            };
+            let (result, max_in_flight) = run_walk(&tree, &options);
+            assert_eq!(result, expected, "parallelism {parallelism}");
+            assert!(max_in_flight <= parallelism);
+            // Sibling directories are listed in parallel.
+            assert_eq!(max_in_flight > 1, parallelism > 1);
+        }
+    }
+
+    #[test]
+    fn test_walk_unordered_complete() {
+        let mut tree = HashMap::new();
+        make_tree(&mut tree, &root(), 4, 3);
+        let mut expected = vec![];
+        dfs(&tree, &root(), &mut expected);
+        expected.sort();
+
+        for parallelism in [1, 3, 8] {
+            let options = WalkOptions {
+                parallelism,
+                ..Default::default()
+            };
+            let (mut result, max_in_flight) = run_walk(&tree, &options);
+            assert!(max_in_flight <= parallelism);
+            result.sort();
+            assert_eq!(result, expected, "parallelism {parallelism}");
+        }
+    }
+
+    #[test]
+    fn test_walk_max_depth() {
+        let mut tree = HashMap::new();
+        make_tree(&mut tree, &root(), 2, 3);
+        let options = WalkOptions {
+            parallelism: 2,
+            order: WalkOrder::DepthFirst,
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let (result, _) = run_walk(&tree, &options);
+        // Root: file + 2 dirs; each dir: file + 2 dirs, which are not descended into.
+        assert_eq!(result.len(), 3 + 2 * 3);
+    }
+
+    #[test]
+    fn test_walk_listing_error() {
+        let mut tree = HashMap::new();
+        make_tree(&mut tree, &root(), 2, 2);
+        tree.remove(&root().with_add_path("dir0").to_string());
+
+        for order in [WalkOrder::Unordered, WalkOrder::DepthFirst] {
+            let options = WalkOptions {
+                parallelism: 2,
+                order,
+                max_depth: None,
+                ..Default::default()
+            };
+            let (result, _) = run_walk(&tree, &options);
+            assert_eq!(result.iter().filter(|r| r.is_none()).count(), 1);
+            // Root: 3 entries, dir1: 3 entries, dir1's subdirectories: 1 entry each.
+            assert_eq!(result.iter().filter(|r| r.is_some()).count(), 3 + 3 + 2);
+        }
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn test_walk_filter_prunes_directories() {
+        use super::PathFilter;
+        use std::sync::{
+            Arc,
+            atomic::{AtomicU64, Ordering},
+        };
+
+        let mut tree = HashMap::new();
+        make_tree(&mut tree, &root(), 2, 4);
+        let deep = root().with_add_path("dir0").with_add_path("dir1");
+        let filter = PathFilter::new([
+            "/dir0/dir1/",
+            "*.txt",
+            "!/dir1/file.txt",
+            // After the negation, so it may prune.
+            "**/dir1/dir0/**",
+        ])
+        .unwrap();
+
+        for order in [WalkOrder::Unordered, WalkOrder::DepthFirst] {
+            let options = WalkOptions {
+                parallelism: 4,
+                order,
+                filter: Some(Arc::new(filter.clone())),
+                ..Default::default()
+            };
+            let matches: Arc<[AtomicU64]> = (0..4).map(|_| AtomicU64::new(0)).collect();
+            let state =
+                WalkState::<TestInfo>::new(root(), &options).with_filter_matches(matches.clone());
+            let (result, _, listed) = run_walk_state(&tree, state);
+            let result = result.into_iter().map(Option::unwrap).collect::<Vec<_>>();
+
+            // The excluded directory is neither yielded nor listed, and neither are its subdirectories.
+            let deep = deep.to_string();
+            assert!(result.iter().all(|path| !path.starts_with(&deep)));
+            assert!(listed.iter().all(|path| !path.starts_with(&deep)));
+            // Pruned directories are yielded, but not listed.
+            let pruned = root()
+                .with_add_path("dir1")
+                .with_add_path("dir1")
+                .with_add_path("dir0")
+                .to_string();
+            assert!(result.contains(&pruned));
+            assert!(!listed.contains(&pruned));
+            assert!(
+                listed
+                    .iter()
+                    .all(|path| !path.starts_with(&format!("{pruned}\\")))
+            );
+            // Files are excluded, unless re-included by a later pattern.
+            let kept = root().with_add_path("dir1").with_add_path("file.txt");
+            assert!(result.contains(&kept.to_string()));
+            assert_eq!(
+                result.iter().filter(|path| path.ends_with(".txt")).count(),
+                1
+            );
+
+            // Without the filter, every directory of the tree is listed.
+            assert!(listed.len() < tree.len());
+            let matches = matches
+                .iter()
+                .map(|m| m.load(Ordering::Relaxed))
+                .collect::<Vec<_>>();
+            assert_eq!(matches[0], 1);
+            assert_eq!(matches[2], 1);
+            assert!(matches[1] > 0 && matches[3] > 0);
+        }
+    }
+}
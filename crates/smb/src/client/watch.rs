@@ -0,0 +1,829 @@
+//! Watching a path for changes, across re-creations of the watched directory.
+//!
+//! See [`Client::watch_path`] for more information.
+
+use crate::{Client, Directory, Error, FileCreateArgs, Resource, UncPath};
+use maybe_async::*;
+use smb_fscc::{DirAccessMask, FileNotifyInformation, NotifyAction};
+use smb_msg::{NotifyFilter, Status};
+use std::sync::Arc;
+#[cfg(not(feature = "async"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// A change of the path watched by [`Client::watch_path`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// A file or directory was created, or moved into the watched directory.
+    Added(UncPath),
+    /// A file or directory was deleted, or moved out of the watched directory.
+    Removed(UncPath),
+    /// The data, attributes or streams of a file or directory were changed.
+    Modified(UncPath),
+    /// A file or directory was renamed within its directory.
+    Renamed { from: UncPath, to: UncPath },
+    /// Changes may have been missed, e.g. since the watched directory was deleted and re-created,
+    /// or since there were more changes than the server could report.
+    /// Consumers should re-scan the watched path to catch up.
+    Rescan,
+}
+
+/// Options for [`Client::watch_path_with_options`].
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// The number of attempts to re-open the watched directory, once its handle was lost,
+    /// e.g. since the directory was deleted. The watch fails with the error of the last attempt.
+    pub reopen_attempts: usize,
+    /// The delay before the second attempt to re-open the watched directory.
+    /// The delay is doubled after each failed attempt, up to [`max_reopen_backoff`][Self::max_reopen_backoff].
+    pub reopen_backoff: Duration,
+    /// The maximum delay between attempts to re-open the watched directory.
+    pub max_reopen_backoff: Duration,
+}
+
+impl WatchOptions {
+    pub const DEFAULT_REOPEN_ATTEMPTS: usize = 5;
+    pub const DEFAULT_REOPEN_BACKOFF: Duration = Duration::from_millis(500);
+    pub const DEFAULT_MAX_REOPEN_BACKOFF: Duration = Duration::from_secs(8);
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            reopen_attempts: Self::DEFAULT_REOPEN_ATTEMPTS,
+            reopen_backoff: Self::DEFAULT_REOPEN_BACKOFF,
+            max_reopen_backoff: Self::DEFAULT_MAX_REOPEN_BACKOFF,
+        }
+    }
+}
+
+impl Client {
+    /// Watches the directory at `path` for changes, using the default [`WatchOptions`].
+    ///
+    /// See [`Client::watch_path_with_options`] for more information.
+    #[maybe_async]
+    pub async fn watch_path(
+        &self,
+        path: &UncPath,
+        filter: NotifyFilter,
+        recursive: bool,
+    ) -> crate::Result<WatchHandle> {
+        self.watch_path_with_options(path, filter, recursive, &Default::default())
+            .await
+    }
+
+    /// Watches the directory at `path` for changes.
+    ///
+    /// Unlike [`Directory::watch_stream`], the watch survives the loss of the directory handle:
+    /// once the server fails the watch since the directory was deleted (`STATUS_DELETE_PENDING`),
+    /// or its handle was closed (`STATUS_FILE_CLOSED`, `STATUS_NOTIFY_CLEANUP`), the directory is
+    /// re-opened, as configured by `options`, and [`WatchEvent::Rescan`] is yielded,
+    /// since changes may have been missed in the meantime. [`WatchEvent::Rescan`] is also yielded
+    /// when the server had more changes than it could report (`STATUS_NOTIFY_ENUM_DIR`).
+    ///
+    /// ## Arguments
+    /// * `path` - The UNC path of the directory to watch. The share must be connected using [`Client::share_connect`].
+    /// * `filter` - The changes to watch for.
+    /// * `recursive` - Whether to watch the whole tree under `path`, or only its direct entries.
+    /// * `options` - See [`WatchOptions`].
+    ///
+    /// ## Returns
+    /// A [`WatchHandle`] of the events. Other errors end the watch, after being yielded.
+    /// The directory is re-opened on the tree it was first opened on, so the watch also ends
+    /// once that tree is disconnected.
+    #[maybe_async]
+    pub async fn watch_path_with_options(
+        &self,
+        path: &UncPath,
+        filter: NotifyFilter,
+        recursive: bool,
+        options: &WatchOptions,
+    ) -> crate::Result<WatchHandle> {
+        let directory = match self.create_file(path, &Watcher::open_args()).await? {
+            Resource::Directory(directory) => Arc::new(directory),
+            _ => return Err(Error::InvalidState(format!("{path} is not a directory"))),
+        };
+        let watcher = Watcher {
+            directory,
+            converter: EventConverter::new(path.clone()),
+            filter,
+            recursive,
+            options: options.clone(),
+            stop: Default::default(),
+        };
+        Ok(WatchHandle::start(watcher))
+    }
+}
+
+/// Stops a watch, from its handle.
+#[derive(Default)]
+struct WatchStop {
+    #[cfg(feature = "async")]
+    token: tokio_util::sync::CancellationToken,
+    #[cfg(not(feature = "async"))]
+    stopped: AtomicBool,
+    /// Cancels the pending change notification of the running watch.
+    #[cfg(feature = "multi_threaded")]
+    canceller: std::sync::Mutex<Option<crate::resource::NotifyDirectoryIteratorCanceller>>,
+}
+
+impl WatchStop {
+    fn stop(&self) {
+        #[cfg(feature = "async")]
+        self.token.cancel();
+        #[cfg(not(feature = "async"))]
+        self.stopped.store(true, Ordering::SeqCst);
+        #[cfg(feature = "multi_threaded")]
+        if let Some(canceller) = self.canceller.lock().unwrap().as_ref() {
+            canceller.cancel();
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        #[cfg(feature = "async")]
+        return self.token.is_cancelled();
+        #[cfg(not(feature = "async"))]
+        return self.stopped.load(Ordering::SeqCst);
+    }
+
+    /// Sets the canceller of the running watch, if any, and cancels it if the watch was already stopped.
+    #[cfg(feature = "multi_threaded")]
+    fn set_canceller(&self, canceller: Option<&crate::resource::NotifyDirectoryIteratorCanceller>) {
+        *self.canceller.lock().unwrap() = canceller.cloned();
+        if let Some(canceller) = canceller.filter(|_| self.is_stopped()) {
+            canceller.cancel();
+        }
+    }
+}
+
+/// Converts the notifications of a watched directory into [`WatchEvent`]s.
+struct EventConverter {
+    root: UncPath,
+    /// The old name of a rename, until its new name is notified.
+    renamed_from: Option<UncPath>,
+}
+
+impl EventConverter {
+    fn new(root: UncPath) -> Self {
+        Self {
+            root,
+            renamed_from: None,
+        }
+    }
+
+    /// Converts a notification into the events it completes.
+    fn convert(&mut self, info: FileNotifyInformation) -> Vec<WatchEvent> {
+        let path = self.root.join(&info.file_name.to_string());
+        let mut events: Vec<_> = self.take_rename().into_iter().collect();
+        match info.action {
+            NotifyAction::Added => events.push(WatchEvent::Added(path)),
+            NotifyAction::Removed => events.push(WatchEvent::Removed(path)),
+            NotifyAction::Modified
+            | NotifyAction::AddedStream
+            | NotifyAction::RemovedStream
+            | NotifyAction::ModifiedStream => events.push(WatchEvent::Modified(path)),
+            NotifyAction::RenamedOldName => self.renamed_from = Some(path),
+            NotifyAction::RenamedNewName => match events.pop() {
+                Some(WatchEvent::Removed(from)) => {
+                    events.push(WatchEvent::Renamed { from, to: path })
+                }
+                _ => events.push(WatchEvent::Added(path)),
+            },
+            action => log::debug!("Ignoring {action:?} notification of {path}"),
+        }
+        events
+    }
+
+    /// Returns the pending old name of a rename, as a removal, since its new name was not notified.
+    fn take_rename(&mut self) -> Option<WatchEvent> {
+        self.renamed_from.take().map(WatchEvent::Removed)
+    }
+}
+
+/// The state of a watch of [`Client::watch_path`].
+struct Watcher {
+    directory: Arc<Directory>,
+    converter: EventConverter,
+    filter: NotifyFilter,
+    recursive: bool,
+    options: WatchOptions,
+    stop: Arc<WatchStop>,
+}
+
+#[maybe_async(AFIT)]
+impl Watcher {
+    fn open_args() -> FileCreateArgs {
+        FileCreateArgs::make_open_existing(
+            DirAccessMask::new()
+                .with_list_directory(true)
+                .with_synchronize(true)
+                .into(),
+        )
+    }
+
+    fn root(&self) -> &UncPath {
+        &self.converter.root
+    }
+
+    /// Whether the watch failed since the handle of the watched directory was lost.
+    fn is_handle_lost(error: &Error) -> bool {
+        matches!(
+            error.raw_status(),
+            Some(Status::U32_DELETE_PENDING | Status::U32_FILE_CLOSED)
+        )
+    }
+
+    /// Handles the end of the change notifications of the watched directory, whether they ended by
+    /// `error`, or by the server cleaning up the watch.
+    ///
+    /// Returns the events to yield, and whether to watch the directory again.
+    async fn watch_ended(
+        &mut self,
+        error: Option<Error>,
+    ) -> (Vec<crate::Result<WatchEvent>>, bool) {
+        let mut events: Vec<_> = self.converter.take_rename().map(Ok).into_iter().collect();
+        if self.stop.is_stopped() {
+            return (events, false);
+        }
+
+        let result = match error {
+            Some(Error::BufferTooSmall {
+                data_type: "FileNotifyInformation",
+                ..
+            }) => {
+                log::debug!("Changes of {} were not reported by the server", self.root());
+                Ok(true)
+            }
+            Some(e) if !Self::is_handle_lost(&e) => Err(e),
+            error => {
+                log::debug!(
+                    "The watch of {} ended ({}), re-opening it",
+                    self.root(),
+                    error.map_or("cleaned up".to_string(), |e| e.to_string())
+                );
+                self.reopen().await
+            }
+        };
+        match result {
+            Ok(true) => {
+                events.push(Ok(WatchEvent::Rescan));
+                (events, true)
+            }
+            Ok(false) => (events, false),
+            Err(e) => {
+                events.push(Err(e));
+                (events, false)
+            }
+        }
+    }
+
+    /// Re-opens the watched directory, as configured by [`WatchOptions`].
+    ///
+    /// Returns whether the directory was re-opened, or the watch was stopped in the meantime.
+    async fn reopen(&mut self) -> crate::Result<bool> {
+        // The lost handle must be closed, so that a deleted directory can be re-created.
+        if let Err(e) = self.directory.close().await {
+            log::debug!("Failed to close the lost handle of {}: {e}", self.root());
+        }
+
+        let mut delay = self.options.reopen_backoff;
+        let mut attempt = 1;
+        loop {
+            match self.directory.reopen(&Self::open_args()).await {
+                Ok(Resource::Directory(directory)) => {
+                    self.directory = Arc::new(directory);
+                    return Ok(true);
+                }
+                Ok(_) => {
+                    return Err(Error::InvalidState(format!(
+                        "{} is no longer a directory",
+                        self.root()
+                    )));
+                }
+                Err(e) if attempt >= self.options.reopen_attempts => return Err(e),
+                Err(e) => log::debug!("Failed to re-open {} (attempt {attempt}): {e}", self.root()),
+            }
+            if !backoff(&self.stop, delay).await {
+                return Ok(false);
+            }
+            delay = (delay * 2).min(self.options.max_reopen_backoff);
+            attempt += 1;
+        }
+    }
+
+    async fn close(&self) {
+        if let Err(e) = self.directory.close().await {
+            log::debug!("Failed to close the watched directory {}: {e}", self.root());
+        }
+    }
+}
+
+/// Waits for `delay`, unless the watch is stopped first. Returns whether the watch is still running.
+#[cfg(feature = "async")]
+async fn backoff(stop: &WatchStop, delay: Duration) -> bool {
+    tokio::select! {
+        _ = stop.token.cancelled() => false,
+        _ = tokio::time::sleep(delay) => true,
+    }
+}
+
+/// How often [`backoff`] checks whether the watch was stopped, in sync builds.
+#[cfg(not(feature = "async"))]
+const BACKOFF_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Waits for `delay`, unless the watch is stopped first. Returns whether the watch is still running.
+#[cfg(not(feature = "async"))]
+fn backoff(stop: &WatchStop, delay: Duration) -> bool {
+    let deadline = std::time::Instant::now() + delay;
+    while !stop.is_stopped() {
+        let now = std::time::Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        std::thread::sleep((deadline - now).min(BACKOFF_POLL_INTERVAL));
+    }
+    false
+}
+
+/// The events of a watch of [`Client::watch_path`], as a [`Stream`][futures_core::Stream].
+///
+/// The watch runs in a spawned task. Dropping the handle stops the watch.
+#[cfg(feature = "async")]
+pub struct WatchHandle {
+    events: tokio_stream::wrappers::ReceiverStream<crate::Result<WatchEvent>>,
+    stop: Arc<WatchStop>,
+}
+
+#[cfg(feature = "async")]
+impl WatchHandle {
+    fn start(watcher: Watcher) -> Self {
+        let (sender, receiver) = tokio::sync::mpsc::channel(1024);
+        let stop = watcher.stop.clone();
+        tokio::spawn(watcher.run(sender));
+        Self {
+            events: receiver.into(),
+            stop,
+        }
+    }
+
+    /// Stops the watch: the pending change notification is cancelled, the directory is closed,
+    /// and the stream ends once the events that were already received are yielded.
+    pub fn stop(&self) {
+        self.stop.stop();
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for WatchHandle {
+    type Item = crate::Result<WatchEvent>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        std::pin::Pin::new(&mut self.get_mut().events).poll_next(cx)
+    }
+}
+
+#[cfg(feature = "async")]
+impl Watcher {
+    async fn run(mut self, sender: tokio::sync::mpsc::Sender<crate::Result<WatchEvent>>) {
+        use futures_util::StreamExt;
+
+        loop {
+            let mut error = None;
+            match Directory::watch_stream_cancellable(
+                &self.directory,
+                self.filter,
+                self.recursive,
+                self.stop.token.child_token(),
+            ) {
+                Ok(notifications) => {
+                    let mut notifications = std::pin::pin!(notifications);
+                    while let Some(notification) = notifications.next().await {
+                        match notification {
+                            Ok(info) => {
+                                for event in self.converter.convert(info) {
+                                    if sender.send(Ok(event)).await.is_err() {
+                                        self.stop.stop();
+                                    }
+                                }
+                            }
+                            Err(e) => error = Some(e),
+                        }
+                    }
+                }
+                Err(e) => error = Some(e),
+            }
+
+            let (events, again) = self.watch_ended(error).await;
+            for event in events {
+                sender.send(event).await.ok();
+            }
+            if !again {
+                break;
+            }
+        }
+        self.close().await;
+    }
+}
+
+/// The events of a watch of [`Client::watch_path`], as an [`Iterator`].
+///
+/// The watch runs in a worker thread. Dropping the handle stops the watch.
+#[cfg(feature = "multi_threaded")]
+pub struct WatchHandle {
+    events: std::sync::mpsc::Receiver<crate::Result<WatchEvent>>,
+    stop: Arc<WatchStop>,
+}
+
+#[cfg(feature = "multi_threaded")]
+impl WatchHandle {
+    fn start(watcher: Watcher) -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let stop = watcher.stop.clone();
+        std::thread::spawn(move || watcher.run(sender));
+        Self {
+            events: receiver,
+            stop,
+        }
+    }
+
+    /// Stops the watch: the pending change notification is cancelled, the directory is closed,
+    /// and the iterator ends once the events that were already received are yielded.
+    pub fn stop(&self) {
+        self.stop.stop();
+    }
+}
+
+#[cfg(feature = "multi_threaded")]
+impl Iterator for WatchHandle {
+    type Item = crate::Result<WatchEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.recv().ok()
+    }
+}
+
+#[cfg(feature = "multi_threaded")]
+impl Watcher {
+    fn run(mut self, sender: std::sync::mpsc::Sender<crate::Result<WatchEvent>>) {
+        use crate::resource::NotifyDirectoryIteratorCancellable;
+
+        loop {
+            let mut error = None;
+            match Directory::watch_stream(&self.directory, self.filter, self.recursive) {
+                Ok(notifications) => {
+                    self.stop.set_canceller(Some(notifications.get_canceller()));
+                    for notification in notifications {
+                        match notification {
+                            Ok(info) => {
+                                for event in self.converter.convert(info) {
+                                    if sender.send(Ok(event)).is_err() {
+                                        self.stop.stop();
+                                    }
+                                }
+                            }
+                            Err(e) => error = Some(e),
+                        }
+                    }
+                    self.stop.set_canceller(None);
+                }
+                Err(e) => error = Some(e),
+            }
+
+            let (events, again) = self.watch_ended(error);
+            for event in events {
+                sender.send(event).ok();
+            }
+            if !again {
+                break;
+            }
+        }
+        self.close();
+    }
+}
+
+/// The events of a watch of [`Client::watch_path`], as an [`Iterator`].
+///
+/// Single-threaded builds send each change notification request from [`Iterator::next`],
+/// and can not cancel a pending one, so [`WatchHandle::stop`] takes effect once it completes.
+/// Dropping the handle closes the directory.
+#[cfg(feature = "single_threaded")]
+pub struct WatchHandle {
+    watcher: Watcher,
+    events: std::collections::VecDeque<crate::Result<WatchEvent>>,
+    done: bool,
+}
+
+#[cfg(feature = "single_threaded")]
+impl WatchHandle {
+    fn start(watcher: Watcher) -> Self {
+        Self {
+            watcher,
+            events: Default::default(),
+            done: false,
+        }
+    }
+
+    /// Stops the watch: the directory is closed, and the iterator ends
+    /// once the events that were already received are yielded.
+    pub fn stop(&self) {
+        self.watcher.stop.stop();
+    }
+}
+
+#[cfg(feature = "single_threaded")]
+impl Iterator for WatchHandle {
+    type Item = crate::Result<WatchEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use crate::msg_handler::ReceiveOptions;
+        use crate::resource::DirectoryWatchResult;
+
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(event);
+            }
+            if self.done {
+                return None;
+            }
+            if self.watcher.stop.is_stopped() {
+                self.done = true;
+                self.watcher.close();
+                continue;
+            }
+
+            let watcher = &mut self.watcher;
+            let error = match watcher.directory._watch_options(
+                watcher.filter,
+                watcher.recursive,
+                ReceiveOptions::new().without_timeout(),
+            ) {
+                DirectoryWatchResult::Notifications(notifications) => {
+                    for info in notifications {
+                        let events = watcher.converter.convert(info);
+                        self.events.extend(events.into_iter().map(Ok));
+                    }
+                    continue;
+                }
+                DirectoryWatchResult::Cleanup => None,
+                result => {
+                    let result: crate::Result<_> = result.into();
+                    result.err()
+                }
+            };
+            let (events, again) = watcher.watch_ended(error);
+            self.events.extend(events);
+            if !again {
+                self.done = true;
+                self.watcher.close();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "single_threaded")]
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        if !self.done {
+            self.watcher.close();
+        }
+    }
+}
+
+#[cfg(not(feature = "single_threaded"))]
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.stop.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventConverter, WatchEvent, WatchOptions, Watcher};
+    use crate::test_util::*;
+    use crate::{Error, UncPath};
+    use maybe_async::maybe_async;
+    use smb_fscc::{FileAttributes, FileNotifyInformation, NotifyAction};
+    use smb_msg::{Command, FileId, NotifyFilter, ShareFlags, Status};
+    use smb_transport::mock::MockStep;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const LOST_ID: FileId = FileId {
+        persistent: 0x31,
+        volatile: 0x32,
+    };
+    const REOPENED_ID: FileId = FileId {
+        persistent: 0x41,
+        volatile: 0x42,
+    };
+
+    fn root() -> UncPath {
+        format!(r"{SHARE}\dir").parse().unwrap()
+    }
+
+    fn notification(action: NotifyAction, file_name: &str) -> FileNotifyInformation {
+        FileNotifyInformation {
+            action,
+            file_name: file_name.into(),
+        }
+    }
+
+    fn open_dir_step(file_id: FileId) -> MockStep {
+        MockStep::expect(Command::Create)
+            .matching("opens dir", |r| {
+                r.content
+                    .as_create()
+                    .is_ok_and(|create| create.name == "dir")
+            })
+            .respond(create_response(
+                file_id,
+                FileAttributes::new().with_directory(true),
+            ))
+    }
+
+    fn close_step(file_id: FileId) -> MockStep {
+        MockStep::expect(Command::Close)
+            .matching("closes dir", move |r| {
+                r.content
+                    .as_close()
+                    .is_ok_and(|close| close.file_id == file_id)
+            })
+            .respond(close_response())
+    }
+
+    /// Opens the `dir` directory over a mock transport, that then follows `steps`, and watches it.
+    #[maybe_async]
+    async fn mock_watcher(steps: impl IntoIterator<Item = MockStep>) -> (MockTree, Watcher) {
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            std::iter::once(open_dir_step(LOST_ID)).chain(steps),
+        )
+        .await;
+        let directory = mock
+            .tree
+            .open_existing("dir", Watcher::open_args().desired_access)
+            .await
+            .unwrap()
+            .unwrap_dir();
+        let watcher = Watcher {
+            directory: Arc::new(directory),
+            converter: EventConverter::new(root()),
+            filter: NotifyFilter::new().with_file_name(true),
+            recursive: false,
+            options: WatchOptions {
+                reopen_attempts: 2,
+                reopen_backoff: Duration::from_millis(1),
+                ..Default::default()
+            },
+            stop: Default::default(),
+        };
+        (mock, watcher)
+    }
+
+    #[test]
+    fn test_convert_notifications() {
+        let mut converter = EventConverter::new(root());
+        let path = |name: &str| root().join(name);
+
+        assert_eq!(
+            converter.convert(notification(NotifyAction::Added, "a.txt")),
+            [WatchEvent::Added(path("a.txt"))]
+        );
+        assert_eq!(
+            converter.convert(notification(NotifyAction::ModifiedStream, r"sub\b.txt")),
+            [WatchEvent::Modified(path(r"sub\b.txt"))]
+        );
+
+        // A rename is reported once both of its names are notified.
+        assert!(
+            converter
+                .convert(notification(NotifyAction::RenamedOldName, "a.txt"))
+                .is_empty()
+        );
+        assert_eq!(
+            converter.convert(notification(NotifyAction::RenamedNewName, "c.txt")),
+            [WatchEvent::Renamed {
+                from: path("a.txt"),
+                to: path("c.txt")
+            }]
+        );
+
+        // Unpaired names of renames are reported as removals and additions.
+        converter.convert(notification(NotifyAction::RenamedOldName, "c.txt"));
+        assert_eq!(
+            converter.convert(notification(NotifyAction::Removed, "d.txt")),
+            [
+                WatchEvent::Removed(path("c.txt")),
+                WatchEvent::Removed(path("d.txt"))
+            ]
+        );
+        assert_eq!(
+            converter.convert(notification(NotifyAction::RenamedNewName, "e.txt")),
+            [WatchEvent::Added(path("e.txt"))]
+        );
+        converter.convert(notification(NotifyAction::RenamedOldName, "e.txt"));
+        assert_eq!(
+            converter.take_rename(),
+            Some(WatchEvent::Removed(path("e.txt")))
+        );
+        assert_eq!(converter.take_rename(), None);
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_watch_reopens_lost_directory() {
+        let (mock, mut watcher) = mock_watcher([
+            close_step(LOST_ID),
+            // The directory is not re-created yet.
+            MockStep::expect(Command::Create).respond_error(Status::ObjectNameNotFound),
+            open_dir_step(REOPENED_ID),
+            close_step(REOPENED_ID),
+        ])
+        .await;
+
+        watcher
+            .converter
+            .convert(notification(NotifyAction::RenamedOldName, "a.txt"));
+        let (events, again) = watcher
+            .watch_ended(Some(Error::server_status(
+                Status::DeletePending as u32,
+                None,
+            )))
+            .await;
+        assert!(again);
+        let events = events
+            .into_iter()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            [
+                WatchEvent::Removed(root().join("a.txt")),
+                WatchEvent::Rescan
+            ]
+        );
+
+        watcher.close().await;
+        mock.handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_watch_ended() {
+        let (mock, mut watcher) = mock_watcher([
+            close_step(LOST_ID),
+            MockStep::expect(Command::Create).respond_error(Status::ObjectNameNotFound),
+            MockStep::expect(Command::Create).respond_error(Status::ObjectNameNotFound),
+        ])
+        .await;
+
+        // Unreported changes require a rescan, but the handle is still valid.
+        let (events, again) = watcher
+            .watch_ended(Some(Error::BufferTooSmall {
+                data_type: "FileNotifyInformation",
+                required: None,
+                provided: 0x10000,
+            }))
+            .await;
+        assert!(again);
+        assert!(matches!(events[..], [Ok(WatchEvent::Rescan)]));
+
+        // Other errors end the watch.
+        let (events, again) = watcher
+            .watch_ended(Some(Error::server_status(
+                Status::AccessDenied as u32,
+                None,
+            )))
+            .await;
+        assert!(!again);
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].as_ref().unwrap_err().status(),
+            Some(Status::AccessDenied)
+        );
+
+        // Once the server cleaned up the watch, the directory is re-opened, until the attempts run out.
+        let (events, again) = watcher.watch_ended(None).await;
+        assert!(!again);
+        assert_eq!(
+            events[0].as_ref().unwrap_err().status(),
+            Some(Status::ObjectNameNotFound)
+        );
+        mock.handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_watch_ended_stopped() {
+        let (mock, mut watcher) = mock_watcher([close_step(LOST_ID)]).await;
+        watcher.stop.stop();
+        let (events, again) = watcher
+            .watch_ended(Some(Error::server_status(Status::FileClosed as u32, None)))
+            .await;
+        assert!(!again);
+        assert!(events.is_empty());
+
+        watcher.close().await;
+        mock.handle.assert_done();
+    }
+}
@@ -22,6 +22,20 @@ impl Decompressor {
         &self,
         original: &CompressedMessage,
     ) -> Result<(Response, Vec<u8>), CompressionError> {
+        let bytes = self.decompress_raw(original)?;
+        let mut cursor = std::io::Cursor::new(&bytes);
+        Ok((
+            Response::read(&mut cursor)
+                .map_err(|_| CompressionError::InvalidDecompressedMessage)?,
+            bytes,
+        ))
+    }
+
+    /// Decompresses the message, returning the raw decompressed message, without parsing it.
+    pub fn decompress_raw(
+        &self,
+        original: &CompressedMessage,
+    ) -> Result<Vec<u8>, CompressionError> {
         let method: Box<dyn CompressionMethod> = match original {
             CompressedMessage::Unchained(_) => Box::new(UnchainedCompression),
             CompressedMessage::Chained(_) => {
@@ -32,16 +46,14 @@ impl Decompressor {
                 }
             }
         };
-        let bytes = method.decompress(original)?;
-        let mut cursor = std::io::Cursor::new(&bytes);
-        Ok((
-            Response::read(&mut cursor)
-                .map_err(|_| CompressionError::InvalidDecompressedMessage)?,
-            bytes,
-        ))
+        method.decompress(original)
     }
 }
 
+/// Use this struct to compress an outgoing message, according to the negotiated compression capabilities.
+///
+/// Messages are compressed as chained messages if the server supports it (MS-SMB2 3.1.4.4),
+/// and as unchained messages using LZ4 otherwise.
 #[derive(Debug)]
 pub struct Compressor {
     caps: CompressionCapabilities,
@@ -52,10 +64,50 @@ impl Compressor {
         Compressor { caps: caps.clone() }
     }
 
+    /// Returns whether messages can be compressed using the negotiated algorithms.
+    ///
+    /// Unchained messages can't be compressed using Pattern_V1, for example.
+    pub fn can_compress(&self) -> bool {
+        let algorithms = &self.caps.compression_algorithms;
+        let usable = |algo: &CompressionAlgorithm| {
+            algorithms.contains(algo) && SUPPORTED_ALGORITHMS.contains(algo)
+        };
+        match self.caps.flags.chained() {
+            true => {
+                ChainedCompression::ALGORITHM_PRIORITY.iter().any(usable)
+                    || usable(&CompressionAlgorithm::PatternV1)
+            }
+            false => UnchainedCompression::ALGORITHM_PRIORITY.iter().any(usable),
+        }
+    }
+
     pub fn compress(&self, bytes: &[u8]) -> crate::Result<CompressedMessage> {
-        // TODO: Add Chained.
-        Ok(UnchainedCompression.compress(bytes, &self.caps.compression_algorithms)?)
+        self.compress_segments(&[bytes])
     }
+
+    /// Compresses a message, made of the concatenation of `segments`.
+    ///
+    /// In chained compression, each segment is compressed on its own, so segments should be split
+    /// where the data changes, for example between the header and the payload of a write request.
+    /// The result may be larger than the original message, for data that does not compress well.
+    pub fn compress_segments(&self, segments: &[&[u8]]) -> crate::Result<CompressedMessage> {
+        let method: &dyn CompressionMethod = match self.caps.flags.chained() {
+            true => &ChainedCompression,
+            false => &UnchainedCompression,
+        };
+        Ok(method.compress(segments, &self.caps.compression_algorithms)?)
+    }
+}
+
+/// Counters of the compressed messages sent on a connection. See [`Connection::compression_stats`][crate::Connection::compression_stats].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// The number of messages sent compressed.
+    pub messages_compressed: u64,
+    /// The total size of the compressed messages, before compression.
+    pub original_bytes: u64,
+    /// The total size of the compressed messages, as sent (before encryption, if any).
+    pub compressed_bytes: u64,
 }
 
 /// This trait describes a (de)compression method, not a specific algorithm.
@@ -68,7 +120,7 @@ trait CompressionMethod {
 
     fn compress(
         &self,
-        data: &[u8],
+        segments: &[&[u8]],
         algorithms: &[CompressionAlgorithm],
     ) -> Result<CompressedMessage, CompressionError>;
 
@@ -82,6 +134,8 @@ trait CompressionMethod {
             CompressionAlgorithm::PatternV1 => Box::new(PatternV1Compression),
             #[cfg(feature = "compress_lz4")]
             CompressionAlgorithm::LZ4 => Box::new(Lz4Compression),
+            #[cfg(feature = "compress_lznt1")]
+            CompressionAlgorithm::LZNT1 => Box::new(Lznt1Compression),
             _ => Err(CompressionError::UnsupportedAlgorithm(algo))?,
         })
     }
@@ -102,14 +156,24 @@ impl CompressionMethod for UnchainedCompression {
         let mut data: Vec<u8> = Vec::<u8>::with_capacity(compressed.original_size as usize);
         self.get_compression_algorithm(compressed.compression_algorithm)?
             .decompress(&compressed.data, Some(compressed.original_size), &mut data)?;
+        if data.len() != compressed.original_size as usize {
+            return Err(CompressionError::DecompressedSizeMismatch {
+                expected: compressed.original_size as usize,
+                actual: data.len(),
+            });
+        }
         Ok(data)
     }
 
     fn compress(
         &self,
-        data: &[u8],
+        segments: &[&[u8]],
         allowed_algorithms: &[CompressionAlgorithm],
     ) -> Result<CompressedMessage, CompressionError> {
+        let data = match segments {
+            [segment] => std::borrow::Cow::Borrowed(*segment),
+            _ => std::borrow::Cow::Owned(segments.concat()),
+        };
         // Check what algos are supported.
         for algo in Self::ALGORITHM_PRIORITY.iter() {
             if !allowed_algorithms.contains(algo) {
@@ -117,7 +181,7 @@ impl CompressionMethod for UnchainedCompression {
             }
 
             let algo_impl = self.get_compression_algorithm(*algo)?;
-            let compressed = algo_impl.compress(data)?;
+            let compressed = algo_impl.compress(&data)?;
             return Ok(CompressedMessage::Unchained(CompressedUnchainedMessage {
                 compression_algorithm: *algo,
                 data: compressed,
@@ -131,6 +195,93 @@ impl CompressionMethod for UnchainedCompression {
 
 struct ChainedCompression;
 
+impl ChainedCompression {
+    /// The general-purpose algorithms, by priority. Pattern_V1 only applies to runs of a repeated byte.
+    pub const ALGORITHM_PRIORITY: [CompressionAlgorithm; 1] = [CompressionAlgorithm::LZ4];
+
+    /// The minimal number of repetitions of a byte at the start or the end of a segment,
+    /// to compress them using Pattern_V1, rather than with the rest of the segment.
+    pub const PATTERN_V1_MIN_REPETITIONS: usize = 64;
+
+    /// Compresses a segment of the message into `items`: repetitions of a byte at its start and end
+    /// are compressed using Pattern_V1 (if allowed), and the rest using `algorithm`,
+    /// or not at all, if that does not make it smaller.
+    fn compress_segment(
+        &self,
+        segment: &[u8],
+        algorithm: Option<CompressionAlgorithm>,
+        pattern_v1: bool,
+        items: &mut Vec<CompressedChainedItem>,
+    ) -> Result<(), CompressionError> {
+        let (head, rest) = match pattern_v1 {
+            true => segment.split_at(Self::repetitions(segment.iter())),
+            false => (&segment[..0], segment),
+        };
+        let (middle, tail) = match pattern_v1 {
+            true => rest.split_at(rest.len() - Self::repetitions(rest.iter().rev())),
+            false => (rest, &rest[..0]),
+        };
+
+        if !head.is_empty() {
+            items.push(self.pattern_v1_item(head)?);
+        }
+        if !middle.is_empty() {
+            items.push(self.compress_item(middle, algorithm)?);
+        }
+        if !tail.is_empty() {
+            items.push(self.pattern_v1_item(tail)?);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of repetitions of the first byte,
+    /// or 0 if there are less than [`Self::PATTERN_V1_MIN_REPETITIONS`].
+    fn repetitions<'a>(mut bytes: impl Iterator<Item = &'a u8>) -> usize {
+        let count = match bytes.next() {
+            Some(first) => 1 + bytes.take_while(|b| *b == first).count(),
+            None => 0,
+        };
+        match count >= Self::PATTERN_V1_MIN_REPETITIONS {
+            true => count,
+            false => 0,
+        }
+    }
+
+    fn pattern_v1_item(&self, data: &[u8]) -> Result<CompressedChainedItem, CompressionError> {
+        let algorithm = CompressionAlgorithm::PatternV1;
+        Ok(CompressedChainedItem {
+            compression_algorithm: algorithm,
+            flags: 0,
+            original_size: None,
+            payload_data: self.get_compression_algorithm(algorithm)?.compress(data)?,
+        })
+    }
+
+    fn compress_item(
+        &self,
+        data: &[u8],
+        algorithm: Option<CompressionAlgorithm>,
+    ) -> Result<CompressedChainedItem, CompressionError> {
+        if let Some(algorithm) = algorithm {
+            let compressed = self.get_compression_algorithm(algorithm)?.compress(data)?;
+            if compressed.len() + std::mem::size_of::<u32>() < data.len() {
+                return Ok(CompressedChainedItem {
+                    compression_algorithm: algorithm,
+                    flags: 0,
+                    original_size: Some(data.len() as u32),
+                    payload_data: compressed,
+                });
+            }
+        }
+        Ok(CompressedChainedItem {
+            compression_algorithm: CompressionAlgorithm::None,
+            flags: 0,
+            original_size: None,
+            payload_data: data.to_vec(),
+        })
+    }
+}
+
 impl CompressionMethod for ChainedCompression {
     fn decompress(&self, compressed: &CompressedMessage) -> Result<Vec<u8>, CompressionError> {
         let compressed = match compressed {
@@ -146,29 +297,33 @@ impl CompressionMethod for ChainedCompression {
         // size-limited vector.
         let mut data = Vec::with_capacity(compressed.original_size as usize);
 
+        // Each item is decompressed in order, and appended to the data of the previous ones.
         for item in compressed.items.iter() {
             let len_before = data.len();
             self.get_compression_algorithm(item.compression_algorithm)?
                 .decompress(&item.payload_data, item.original_size, &mut data)?;
             let len_after = data.len();
             if len_after > compressed.original_size as usize {
-                return Err(CompressionError::ChainedCompressionFailed(
-                    "Decompressed size exceeds the expected size".to_string(),
-                ))?;
+                return Err(CompressionError::DecompressedSizeMismatch {
+                    expected: compressed.original_size as usize,
+                    actual: len_after,
+                });
             }
             if let Some(original_size) = item.original_size {
                 if len_after - len_before != original_size as usize {
-                    Err(CompressionError::ChainedCompressionFailed(
-                        "Decompressed size does not match the item expected size".to_string(),
-                    ))?;
+                    return Err(CompressionError::DecompressedSizeMismatch {
+                        expected: original_size as usize,
+                        actual: len_after - len_before,
+                    });
                 }
             }
         }
 
         if data.len() != compressed.original_size as usize {
-            Err(CompressionError::ChainedCompressionFailed(
-                "Decompressed size does not match the expected size".to_string(),
-            ))?;
+            return Err(CompressionError::DecompressedSizeMismatch {
+                expected: compressed.original_size as usize,
+                actual: data.len(),
+            });
         }
 
         Ok(data)
@@ -176,10 +331,35 @@ impl CompressionMethod for ChainedCompression {
 
     fn compress(
         &self,
-        _data: &[u8],
-        _algorithms: &[CompressionAlgorithm],
+        segments: &[&[u8]],
+        allowed_algorithms: &[CompressionAlgorithm],
     ) -> Result<CompressedMessage, CompressionError> {
-        todo!()
+        let algorithm = Self::ALGORITHM_PRIORITY
+            .into_iter()
+            .find(|algo| allowed_algorithms.contains(algo) && SUPPORTED_ALGORITHMS.contains(algo));
+        let pattern_v1 = allowed_algorithms.contains(&CompressionAlgorithm::PatternV1)
+            && SUPPORTED_ALGORITHMS.contains(&CompressionAlgorithm::PatternV1);
+        if algorithm.is_none() && !pattern_v1 {
+            return Err(CompressionError::NoSupportedCompressionAlgorithm);
+        }
+
+        let mut items = Vec::with_capacity(segments.len() * 3);
+        for segment in segments {
+            self.compress_segment(segment, algorithm, pattern_v1, &mut items)?;
+        }
+        let original_size = segments.iter().map(|s| s.len()).sum::<usize>();
+        let original_size = u32::try_from(original_size).map_err(|_| {
+            CompressionError::ChainedCompressionFailed("Message is too large".to_string())
+        })?;
+        match items.first_mut() {
+            Some(first) => first.flags = CompressedChainedItem::FLAG_CHAINED,
+            None => return Err(CompressionError::InvalidCompressedMessage),
+        }
+
+        Ok(CompressedMessage::Chained(CompressedChainedMessage {
+            original_size,
+            items,
+        }))
     }
 }
 
@@ -206,6 +386,8 @@ pub const SUPPORTED_ALGORITHMS: &[CompressionAlgorithm] = &[
     CompressionAlgorithm::PatternV1,
     #[cfg(feature = "compress_lz4")]
     CompressionAlgorithm::LZ4,
+    #[cfg(feature = "compress_lznt1")]
+    CompressionAlgorithm::LZNT1,
 ];
 
 struct NoneCompression;
@@ -246,6 +428,11 @@ pub struct PatternV1Payload {
     repetitions: u32,
 }
 
+#[cfg(feature = "compress_pattern_v1")]
+impl PatternV1Compression {
+    const PAYLOAD_SIZE: usize = 8;
+}
+
 #[cfg(feature = "compress_pattern_v1")]
 impl CompressionAlgorithmImpl for PatternV1Compression {
     fn decompress(
@@ -255,7 +442,9 @@ impl CompressionAlgorithmImpl for PatternV1Compression {
         out: &mut Vec<u8>,
     ) -> Result<(), CompressionError> {
         debug_assert!(original_size.is_none());
-        assert!(compressed.len() == 8);
+        if compressed.len() != Self::PAYLOAD_SIZE {
+            return Err(CompressionError::InvalidCompressedMessage);
+        }
         let mut cursor = Cursor::new(&compressed);
 
         let parsed_payload = match PatternV1Payload::read(&mut cursor) {
@@ -270,8 +459,23 @@ impl CompressionAlgorithmImpl for PatternV1Compression {
         Ok(())
     }
 
-    fn compress(&self, _data: &[u8]) -> Result<Vec<u8>, CompressionError> {
-        todo!()
+    /// Compresses data that consists of a single repeated byte.
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let pattern = match data.first() {
+            Some(pattern) if data.iter().all(|b| b == pattern) => *pattern,
+            _ => return Err(CompressionError::PatternV1InvalidPattern),
+        };
+        let repetitions =
+            u32::try_from(data.len()).map_err(|_| CompressionError::PatternV1InvalidPattern)?;
+
+        let mut cursor = Cursor::new(Vec::with_capacity(8));
+        PatternV1Payload {
+            pattern,
+            repetitions,
+        }
+        .write(&mut cursor)
+        .map_err(CompressionError::PatternV1InvalidPayload)?;
+        Ok(cursor.into_inner())
     }
 }
 
@@ -292,7 +496,10 @@ impl CompressionAlgorithmImpl for Lz4Compression {
         let size = lz4_flex::decompress_into(compressed, &mut out[start_index..])?;
 
         if size != original_size.unwrap() as usize {
-            Err(CompressionError::PatternV1InvalidDecompressedSize)?;
+            return Err(CompressionError::DecompressedSizeMismatch {
+                expected: original_size.unwrap() as usize,
+                actual: size,
+            });
         }
         Ok(())
     }
@@ -302,6 +509,116 @@ impl CompressionAlgorithmImpl for Lz4Compression {
     }
 }
 
+/// LZNT1 (MS-XCA 2.5). Only decompression is supported.
+#[cfg(feature = "compress_lznt1")]
+struct Lznt1Compression;
+
+#[cfg(feature = "compress_lznt1")]
+impl Lznt1Compression {
+    /// The maximal size of the decompressed data of a single chunk.
+    const CHUNK_SIZE: usize = 0x1000;
+    const CHUNK_HEADER_SIZE: usize = 2;
+    const CHUNK_SIZE_MASK: u16 = 0x0fff;
+    const CHUNK_SIGNATURE_MASK: u16 = 0x7000;
+    const CHUNK_SIGNATURE: u16 = 0x3000;
+    const CHUNK_COMPRESSED: u16 = 0x8000;
+
+    /// Decompresses the data of a single compressed chunk, appending up to [`Self::CHUNK_SIZE`] bytes to `out`.
+    fn decompress_chunk(chunk: &[u8], out: &mut Vec<u8>) -> Result<(), CompressionError> {
+        let chunk_start = out.len();
+        let mut input = chunk.iter().copied();
+        while let Some(flags) = input.next() {
+            for bit in 0..8 {
+                if flags & (1 << bit) == 0 {
+                    match input.next() {
+                        Some(literal) => out.push(literal),
+                        None => return Ok(()),
+                    }
+                    continue;
+                }
+
+                let token = match (input.next(), input.next()) {
+                    (Some(low), Some(high)) => u16::from_le_bytes([low, high]),
+                    _ => return Err(CompressionError::Lznt1InvalidData("truncated copy token")),
+                };
+                // The split between the offset and length bits of a token depends on the position in the chunk.
+                let position = out.len() - chunk_start;
+                if position == 0 {
+                    return Err(CompressionError::Lznt1InvalidData(
+                        "copy token at the start of a chunk",
+                    ));
+                }
+                let mut offset_bits = 4;
+                while (1usize << offset_bits) < position {
+                    offset_bits += 1;
+                }
+                let length_bits = 16 - offset_bits;
+                let offset = (token >> length_bits) as usize + 1;
+                let length = (token & ((1 << length_bits) - 1)) as usize + 3;
+                if offset > position || position + length > Self::CHUNK_SIZE {
+                    return Err(CompressionError::Lznt1InvalidData(
+                        "copy token out of the chunk bounds",
+                    ));
+                }
+                // The copied range may overlap with the copied bytes, so bytes are copied one by one.
+                let copy_start = out.len() - offset;
+                for i in 0..length {
+                    out.push(out[copy_start + i]);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "compress_lznt1")]
+impl CompressionAlgorithmImpl for Lznt1Compression {
+    fn decompress(
+        &self,
+        compressed: &[u8],
+        original_size: Option<u32>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), CompressionError> {
+        let start_index = out.len();
+        let limit = start_index + original_size.unwrap_or(u32::MAX) as usize;
+        let mut remaining = compressed;
+        while remaining.len() >= Self::CHUNK_HEADER_SIZE {
+            let header = u16::from_le_bytes([remaining[0], remaining[1]]);
+            if header == 0 {
+                // End of the compressed buffer.
+                break;
+            }
+            if header & Self::CHUNK_SIGNATURE_MASK != Self::CHUNK_SIGNATURE {
+                return Err(CompressionError::Lznt1InvalidData(
+                    "invalid chunk signature",
+                ));
+            }
+            let chunk_size = (header & Self::CHUNK_SIZE_MASK) as usize + 1;
+            let chunk = remaining
+                .get(Self::CHUNK_HEADER_SIZE..Self::CHUNK_HEADER_SIZE + chunk_size)
+                .ok_or(CompressionError::Lznt1InvalidData("truncated chunk"))?;
+            match header & Self::CHUNK_COMPRESSED != 0 {
+                true => Self::decompress_chunk(chunk, out)?,
+                false => out.extend_from_slice(chunk),
+            }
+            if out.len() > limit {
+                return Err(CompressionError::DecompressedSizeMismatch {
+                    expected: limit - start_index,
+                    actual: out.len() - start_index,
+                });
+            }
+            remaining = &remaining[Self::CHUNK_HEADER_SIZE + chunk_size..];
+        }
+        Ok(())
+    }
+
+    fn compress(&self, _data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        Err(CompressionError::UnsupportedAlgorithm(
+            CompressionAlgorithm::LZNT1,
+        ))
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum CompressionError {
     // --- General
@@ -317,6 +634,8 @@ pub enum CompressionError {
     UnsupportedCompressionMethod,
     #[error("There is no supported compression algorithm available.")]
     NoSupportedCompressionAlgorithm,
+    #[error("Decompressed size {actual} does not match the expected size {expected}")]
+    DecompressedSizeMismatch { expected: usize, actual: usize },
 
     // --- LZ4
     #[cfg(feature = "compress_lz4")]
@@ -331,8 +650,13 @@ pub enum CompressionError {
     #[error("PatternV1 invalid compressed payload")]
     PatternV1InvalidPayload(binrw::Error),
     #[cfg(feature = "compress_pattern_v1")]
-    #[error("PatternV1 invalid decompressed size")]
-    PatternV1InvalidDecompressedSize,
+    #[error("PatternV1 can only compress a non-empty run of a single repeated byte")]
+    PatternV1InvalidPattern,
+
+    // --- LZNT1
+    #[cfg(feature = "compress_lznt1")]
+    #[error("LZNT1 invalid compressed data: {0}")]
+    Lznt1InvalidData(&'static str),
 }
 
 #[cfg(test)]
@@ -353,13 +677,121 @@ mod tests {
     #[cfg(feature = "compress_pattern_v1")]
     #[test]
     pub fn test_pattern_v1_algorithm_decompression() {
-        let pattern_v1_payload_buffer = vec!['h' as u8, 0x0, 0x0, 0x0, 0xee, 0x1, 0x0, 0x0];
+        let pattern_v1_payload_buffer = vec![b'h', 0x0, 0x0, 0x0, 0xee, 0x1, 0x0, 0x0];
         let mut out = vec![];
         super::PatternV1Compression
             .decompress(&pattern_v1_payload_buffer, None, &mut out)
             .unwrap();
     }
 
+    #[cfg(any(feature = "compress_pattern_v1", feature = "compress_lz4"))]
+    fn caps(chained: bool, algorithms: &[CompressionAlgorithm]) -> CompressionCapabilities {
+        CompressionCapabilities {
+            flags: CompressionCapsFlags::new().with_chained(chained),
+            compression_algorithms: algorithms.to_vec(),
+        }
+    }
+
+    /// Writes the compressed message, parses it back, and decompresses it.
+    fn write_and_decompress(
+        caps: &CompressionCapabilities,
+        message: &CompressedMessage,
+    ) -> Vec<u8> {
+        let mut cursor = Cursor::new(vec![]);
+        message.write(&mut cursor).unwrap();
+        let written = cursor.into_inner();
+        assert_eq!(written.len(), message.total_size());
+
+        let parsed = CompressedMessage::read(&mut Cursor::new(&written)).unwrap();
+        Decompressor::new(caps).decompress_raw(&parsed).unwrap()
+    }
+
+    #[cfg(feature = "compress_pattern_v1")]
+    #[test]
+    pub fn test_pattern_v1_algorithm_compression() {
+        let compressed = PatternV1Compression.compress(&[b'h'; 0x1ee]).unwrap();
+        assert_eq!(compressed, [b'h', 0x0, 0x0, 0x0, 0xee, 0x1, 0x0, 0x0]);
+        assert!(PatternV1Compression.compress(b"hh h").is_err());
+        assert!(PatternV1Compression.compress(&[]).is_err());
+    }
+
+    #[cfg(all(feature = "compress_pattern_v1", feature = "compress_lz4"))]
+    #[test]
+    pub fn test_chained_compression_round_trip() {
+        let caps = caps(
+            true,
+            &[
+                CompressionAlgorithm::LZ4,
+                CompressionAlgorithm::PatternV1,
+                CompressionAlgorithm::None,
+            ],
+        );
+        let header = (0..112u8).collect::<Vec<_>>();
+        let payload = [
+            vec![0u8; 0x1000],
+            b"compressible text, ".repeat(100),
+            vec![0xffu8; 0x300],
+        ]
+        .concat();
+
+        let compressor = Compressor::new(&caps);
+        assert!(compressor.can_compress());
+        let message = compressor.compress_segments(&[&header, &payload]).unwrap();
+        let items = match &message {
+            CompressedMessage::Chained(chained) => &chained.items,
+            _ => panic!("Expected a chained message"),
+        };
+        let algorithms = items
+            .iter()
+            .map(|item| item.compression_algorithm)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            algorithms,
+            [
+                CompressionAlgorithm::None,
+                CompressionAlgorithm::PatternV1,
+                CompressionAlgorithm::LZ4,
+                CompressionAlgorithm::PatternV1,
+            ]
+        );
+        assert_eq!(items[0].flags, CompressedChainedItem::FLAG_CHAINED);
+        assert!(message.total_size() < header.len() + payload.len());
+
+        assert_eq!(
+            write_and_decompress(&caps, &message),
+            [header, payload].concat()
+        );
+    }
+
+    #[cfg(feature = "compress_pattern_v1")]
+    #[test]
+    pub fn test_chained_compression_pattern_v1_only() {
+        let caps = caps(true, &[CompressionAlgorithm::PatternV1]);
+        let data = [b"short".to_vec(), vec![7u8; 0x2000]].concat();
+
+        let message = Compressor::new(&caps).compress(&data).unwrap();
+        assert_eq!(write_and_decompress(&caps, &message), data);
+    }
+
+    #[cfg(feature = "compress_lz4")]
+    #[test]
+    pub fn test_unchained_compression_round_trip() {
+        let caps = caps(
+            false,
+            &[CompressionAlgorithm::LZ4, CompressionAlgorithm::PatternV1],
+        );
+        let data = b"compressible text, ".repeat(200);
+
+        let message = Compressor::new(&caps).compress(&data).unwrap();
+        assert!(matches!(message, CompressedMessage::Unchained(_)));
+        assert_eq!(write_and_decompress(&caps, &message), data);
+
+        // Pattern_V1 only applies to chained messages.
+        assert!(
+            !Compressor::new(&self::caps(false, &[CompressionAlgorithm::PatternV1])).can_compress()
+        );
+    }
+
     #[cfg(feature = "compress_pattern_v1")]
     #[test]
     pub fn test_chained_decompression() {
@@ -440,4 +872,115 @@ mod tests {
             }
         )
     }
+
+    #[cfg(feature = "compress_pattern_v1")]
+    #[test]
+    pub fn test_pattern_v1_invalid_payload_size() {
+        let mut out = vec![];
+        let result = super::PatternV1Compression.decompress(&[b'h', 0, 0, 0, 0xee], None, &mut out);
+        assert!(matches!(
+            result,
+            Err(CompressionError::InvalidCompressedMessage)
+        ));
+    }
+
+    #[cfg(feature = "compress_pattern_v1")]
+    #[test]
+    pub fn test_chained_decompression_size_mismatch() {
+        let caps = caps(true, &[CompressionAlgorithm::PatternV1]);
+        let message = |original_size| {
+            CompressedMessage::Chained(CompressedChainedMessage {
+                original_size,
+                items: vec![
+                    CompressedChainedItem {
+                        compression_algorithm: CompressionAlgorithm::None,
+                        flags: CompressedChainedItem::FLAG_CHAINED,
+                        original_size: None,
+                        payload_data: vec![0xfe; Header::STRUCT_SIZE],
+                    },
+                    CompressedChainedItem {
+                        compression_algorithm: CompressionAlgorithm::PatternV1,
+                        flags: 0,
+                        original_size: None,
+                        payload_data: vec![0, 0, 0, 0, 0x10, 0, 0, 0],
+                    },
+                ],
+            })
+        };
+        let decompressor = Decompressor::new(&caps);
+        assert_eq!(
+            decompressor.decompress_raw(&message(0x50)).unwrap(),
+            [[0xfe; Header::STRUCT_SIZE].as_slice(), &[0; 0x10]].concat()
+        );
+        for original_size in [0x48, 0x60] {
+            let result = decompressor.decompress_raw(&message(original_size));
+            assert!(matches!(
+                result,
+                Err(CompressionError::DecompressedSizeMismatch { .. })
+            ));
+        }
+    }
+
+    #[cfg(feature = "compress_lznt1")]
+    #[test]
+    pub fn test_lznt1_algorithm_decompression() {
+        let mut compressed = vec![];
+        // A compressed chunk: "abc", and a copy token of 9 bytes, 3 bytes back.
+        compressed.extend_from_slice(&[0x05, 0xb0, 0x08, b'a', b'b', b'c', 0x06, 0x20]);
+        // A compressed chunk: 32 literals, and a copy token of 10 bytes, 32 bytes back.
+        let literals = b"0123456789abcdefghijklmnopqrstuv";
+        compressed.extend_from_slice(&[0x26, 0xb0]);
+        for group in literals.chunks(8) {
+            compressed.push(0x00);
+            compressed.extend_from_slice(group);
+        }
+        compressed.extend_from_slice(&[0x01, 0x07, 0xf8]);
+        // An uncompressed chunk, and the end of the buffer.
+        compressed.extend_from_slice(&[0x02, 0x30, b'x', b'y', b'z', 0x00, 0x00]);
+
+        let expected = [
+            b"abcabcabcabc".as_slice(),
+            literals,
+            &literals[..10],
+            b"xyz",
+        ]
+        .concat();
+        let mut out = b"prefix".to_vec();
+        super::Lznt1Compression
+            .decompress(&compressed, Some(expected.len() as u32), &mut out)
+            .unwrap();
+        assert_eq!(out, [b"prefix".as_slice(), &expected].concat());
+
+        // The output is bound by the original size.
+        let result = super::Lznt1Compression.decompress(
+            &compressed,
+            Some(expected.len() as u32 - 1),
+            &mut vec![],
+        );
+        assert!(matches!(
+            result,
+            Err(CompressionError::DecompressedSizeMismatch { .. })
+        ));
+    }
+
+    #[cfg(feature = "compress_lznt1")]
+    #[test]
+    pub fn test_lznt1_invalid_data() {
+        for compressed in [
+            // A copy token at the start of a chunk.
+            [0x02, 0xb0, 0x01, 0x00, 0x00].as_slice(),
+            // A copy token that points before the start of the chunk.
+            &[0x02, 0xb0, 0x02, b'a', 0x00, 0x20],
+            // A chunk that is longer than the buffer.
+            &[0x10, 0x30, b'a'],
+            // An invalid chunk signature.
+            &[0x00, 0xc0, b'a'],
+        ] {
+            let result = super::Lznt1Compression.decompress(compressed, Some(0x1000), &mut vec![]);
+            assert!(
+                matches!(result, Err(CompressionError::Lznt1InvalidData(_))),
+                "{compressed:x?}: {result:?}"
+            );
+        }
+    }
 }
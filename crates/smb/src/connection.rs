@@ -1,23 +1,41 @@
 pub mod config;
 pub mod connection_info;
+pub mod metrics;
+mod negotiate_validation;
+pub mod oplock_break;
 pub mod preauth_hash;
+pub mod quirks;
+#[cfg(feature = "unstable-raw")]
+pub mod raw;
 pub mod transformer;
 pub mod worker;
 
-use crate::compression;
+use crate::compression::CompressionStats;
 use crate::connection::preauth_hash::PreauthHashState;
 use crate::dialects::DialectImpl;
-use crate::session::ChannelMessageHandler;
+use crate::session::{ChannelMessageHandler, Credentials};
 use crate::sync_helpers::*;
-use crate::{Error, crypto, msg_handler::*, session::Session};
+use crate::{Error, crypto, error::TimedOutTask, msg_handler::*, session::Session};
 use binrw::prelude::*;
 pub use config::*;
-use connection_info::{ConnectionInfo, NegotiatedProperties};
+pub use connection_info::NegotiateInfoSnapshot;
+use connection_info::{ConnectionInfo, NegotiatedProperties, PreauthIntegrityInfo};
 use maybe_async::*;
+pub use metrics::{ClientMetricsSink, ConnectionMetrics, ConnectionStats, MetricsHook};
+use metrics::{ConnectionMetricsState, CreditWindow, DataDirection};
+pub use oplock_break::BreakNotification;
+use oplock_break::OplockBreaks;
+pub use quirks::{Quirk, ServerQuirks, ServerQuirksOverrides};
+use quirks::{QuirksRegistry, ServerFingerprint};
 use rand::RngCore;
 use rand::rngs::OsRng;
 use smb_dtyp::*;
-use smb_msg::{Command, Response, negotiate::*, plain::*, smb1::SMB1NegotiateMessage};
+use smb_msg::{
+    Command, EchoRequest, Response, ValidateNegotiateInfoRequest,
+    negotiate::*,
+    plain::*,
+    smb1::{SMB1_PROTOCOL_ID, SMB1NegotiateMessage, SMB1NegotiateResponse},
+};
 use smb_transport::*;
 use std::cmp::max;
 use std::collections::HashMap;
@@ -25,6 +43,7 @@ use std::net::SocketAddr;
 #[cfg(feature = "multi_threaded")]
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::{AtomicU16, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 pub use transformer::TransformError;
 use worker::{Worker, WorkerImpl};
 
@@ -37,28 +56,74 @@ pub struct Connection {
     config: ConnectionConfig,
 
     server_name: String,
-    server_address: SocketAddr,
+    server_addresses: Vec<SocketAddr>,
+}
+
+/// The highest protocol family a server supports, as detected by [`Connection::probe_dialects`].
+///
+/// Only the dialects allowed by the configuration are offered, so a server that supports later dialects
+/// than [`ConnectionConfig::max_dialect`] is detected by the latest offered one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolFamily {
+    /// The server only supports SMBv1, which is not supported by this crate.
+    Smb1,
+    /// The server selected `SMB 2.002`: it supports SMB 2.0.2, but not SMB 2.1 or later, or those were not offered.
+    Smb202,
+    /// The server selected `SMB 2.???`: it supports SMB 2.1 or later.
+    /// The exact dialect is only known once the SMB2 negotiation completes, see [`Connection::connect`].
+    Smb2,
+}
+
+/// The response of the server to the multi-protocol SMBv1 negotiate request.
+enum MultiProtocolResponse {
+    Smb1(SMB1NegotiateResponse),
+    Smb2(PlainResponse),
 }
 
 #[maybe_async(AFIT)]
 impl Connection {
     /// Creates a new SMB connection, specifying a server configuration, without connecting to a server.
     /// Use the [`connect`](Connection::connect) method to establish a connection.
+    ///
+    /// `server_name` is the name of the server, used for authentication (e.g. the Kerberos SPN),
+    /// and `server_address` is the address to connect to. If its port is 0, the port of
+    /// [`ConnectionConfig::port`], or the default port of the transport, is used.
     pub fn build(
         server_name: &str,
         server_address: SocketAddr,
         client_guid: Guid,
         config: ConnectionConfig,
+    ) -> crate::Result<Self> {
+        Self::build_with_addresses(server_name, vec![server_address], client_guid, config)
+    }
+
+    /// Creates a new SMB connection, just like [`Connection::build`], to a server that has multiple addresses,
+    /// such as both IPv6 and IPv4 addresses.
+    ///
+    /// When connecting, the transport connects to the first of the addresses that accepts the connection.
+    /// TCP connections are attempted concurrently, starting the next attempt after
+    /// [`ConnectionConfig::happy_eyeballs_delay`], as in the Happy Eyeballs algorithm (RFC 8305).
+    pub fn build_with_addresses(
+        server_name: &str,
+        server_addresses: Vec<SocketAddr>,
+        client_guid: Guid,
+        config: ConnectionConfig,
     ) -> crate::Result<Self> {
         config.validate()?;
+        if server_addresses.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "No addresses to connect to {server_name}"
+            )));
+        }
         Ok(Connection {
             handler: HandlerReference::new(ConnectionMessageHandler::new(
                 client_guid,
-                config.credits_backlog,
+                config.credits_backlog(),
+                config.metrics.as_ref(),
             )),
             config,
             server_name: server_name.to_string(),
-            server_address,
+            server_addresses,
         })
     }
 
@@ -69,7 +134,7 @@ impl Connection {
     pub async fn bind_session(
         &self,
         primary_session: &Session,
-        identity: sspi::AuthIdentity,
+        credentials: impl Into<Credentials>,
     ) -> crate::Result<u32> {
         log::debug!("Binding alternate session to new connection");
 
@@ -94,7 +159,7 @@ impl Connection {
 
         primary_session
             .bind(
-                identity,
+                credentials.into(),
                 &self.handler,
                 self.handler.conn_info.get().unwrap(),
             )
@@ -107,22 +172,7 @@ impl Connection {
             return Err(Error::InvalidState("Already connected".into()));
         }
 
-        let mut transport = make_transport(&self.config.transport, self.config.timeout())?;
-
-        let mut actual_connect_address = self.server_address;
-        if actual_connect_address.port() == 0 {
-            actual_connect_address
-                .set_port(self.config.port.unwrap_or_else(|| transport.default_port()));
-        }
-
-        log::info!(
-            "Connecting to {} (at {actual_connect_address})...",
-            &self.server_name,
-        );
-        transport
-            .connect(&self.server_name, actual_connect_address)
-            .await?;
-
+        let transport = self._connect_transport().await?;
         log::info!("Connected to {}. Negotiating.", &self.server_name);
         self._negotiate(transport, self.config.smb2_only_negotiate)
             .await?;
@@ -169,6 +219,96 @@ impl Connection {
         Ok(conn)
     }
 
+    /// Detects the highest protocol family the server supports, without connecting to it.
+    ///
+    /// This sends the multi-protocol SMBv1 negotiate request, offering `NT LM 0.12` and the SMB2 dialects
+    /// in the configured range (see [`ConnectionConfig::min_dialect`] and [`ConnectionConfig::max_dialect`]),
+    /// and disconnects once the server responds, with either an SMBv1 or an SMB2 negotiate response.
+    /// Unlike [`Connection::connect`], this never sends an SMB2-only negotiate request, which some
+    /// legacy servers reset the connection on, regardless of [`ConnectionConfig::smb2_only_negotiate`].
+    ///
+    /// The connection remains unconnected, and may be connected afterwards.
+    pub async fn probe_dialects(&self) -> crate::Result<ProtocolFamily> {
+        if self.handler.worker().is_some() {
+            return Err(Error::InvalidState("Already connected".into()));
+        }
+
+        let mut transport = self._connect_transport().await?;
+        log::info!("Connected to {}. Probing dialects.", &self.server_name);
+        match self._multi_protocol_negotiate(&mut transport).await? {
+            MultiProtocolResponse::Smb1(response)
+                if response.dialect_index == SMB1NegotiateResponse::NO_DIALECT =>
+            {
+                Err(Error::NegotiationError(
+                    "The server supports none of the offered dialects".to_string(),
+                ))
+            }
+            MultiProtocolResponse::Smb1(_) => Ok(ProtocolFamily::Smb1),
+            MultiProtocolResponse::Smb2(message) => {
+                match message.content.to_negotiate()?.dialect_revision {
+                    NegotiateDialect::Smb02Wildcard => Ok(ProtocolFamily::Smb2),
+                    NegotiateDialect::Smb0202 => Ok(ProtocolFamily::Smb202),
+                    _ => Err(Error::InvalidMessage(
+                        "Expected SMB2 wildcard or SMB 2.0.2 dialect".to_string(),
+                    )),
+                }
+            }
+        }
+    }
+
+    /// Creates the configured transport, and connects it to the server.
+    ///
+    /// Falls back to the NetBIOS transport if the TCP connection is refused,
+    /// and [`ConnectionConfig::netbios_fallback`] is set.
+    #[maybe_async]
+    async fn _connect_transport(&self) -> crate::Result<Box<dyn SmbTransport>> {
+        let error = match self._connect_transport_with(&self.config.transport).await {
+            Err(Error::TransportError(e)) => e,
+            result => return result,
+        };
+        match netbios_fallback(&self.config, &self.server_addresses, &error) {
+            Some(fallback) => {
+                log::info!(
+                    "Connecting to {} failed ({error}), falling back to NetBIOS.",
+                    &self.server_name
+                );
+                self._connect_transport_with(&fallback).await
+            }
+            None => Err(error.into()),
+        }
+    }
+
+    #[maybe_async]
+    async fn _connect_transport_with(
+        &self,
+        transport: &TransportConfig,
+    ) -> crate::Result<Box<dyn SmbTransport>> {
+        let mut transport = make_transport_with_options(
+            transport,
+            self.config.timeout(),
+            self.config.tcp_options(),
+        )?;
+
+        let port = self.config.port.unwrap_or_else(|| transport.default_port());
+        let actual_connect_addresses = self
+            .server_addresses
+            .iter()
+            .map(|address| match address.port() {
+                0 => SocketAddr::new(address.ip(), port),
+                _ => *address,
+            })
+            .collect::<Vec<_>>();
+
+        log::info!(
+            "Connecting to {} (at {actual_connect_addresses:?})...",
+            &self.server_name,
+        );
+        transport
+            .connect_any(&self.server_name, &actual_connect_addresses)
+            .await?;
+        Ok(transport)
+    }
+
     /// Closes the connection, and all of it's managed resources.
     ///
     /// Any session, tree, or file handles associated with the connection will be unusable after
@@ -184,53 +324,93 @@ impl Connection {
 
     /// Switches the protocol to SMB2 against the server if required,
     /// and wraps the transport in a SMB2 worker.
+    ///
+    /// Returns the SMB2 negotiate response of the server, if it selected SMB 2.0.2
+    /// in response to the multi-protocol negotiate request.
     #[maybe_async]
     async fn _negotiate_switch_to_smb2(
         &self,
         mut transport: Box<dyn SmbTransport>,
         smb2_only_neg: bool,
-    ) -> crate::Result<Arc<WorkerImpl>> {
+    ) -> crate::Result<(Arc<WorkerImpl>, Option<NegotiateResponse>)> {
+        let mut direct_response = None;
         // Multi-protocol negotiation: Begin with SMB1, expect SMB2.
         if !smb2_only_neg {
-            log::debug!("Negotiating multi-protocol: Sending SMB1");
-            // 1. Send SMB1 negotiate request
-            let msg_bytes: Vec<u8> = SMB1NegotiateMessage::default().try_into()?;
-            transport.send(&IoVec::from(msg_bytes)).await?;
-
-            log::debug!("Sent SMB1 negotiate request, Receieving SMB2 response");
-            // 2. Expect SMB2 negotiate response
-            let recieved_bytes = transport.receive().await?;
-            let response = Response::try_from(recieved_bytes.as_ref())?;
-            let message = match response {
-                Response::Plain(m) => m,
-                _ => {
-                    return Err(Error::InvalidMessage(
-                        "Expected SMB2 negotiate response, got SMB1".to_string(),
-                    ));
-                }
+            // 1. Send SMB1 negotiate request, expect SMB2 negotiate response.
+            let message = match self._multi_protocol_negotiate(&mut transport).await? {
+                MultiProtocolResponse::Smb1(_) => return Err(Error::Smb1OnlyServer),
+                MultiProtocolResponse::Smb2(message) => message,
             };
 
-            let smb2_negotiate_response = message.content.to_negotiate()?;
-
-            // 3. Make sure dialect is smb2*, message ID is 0.
-            if smb2_negotiate_response.dialect_revision != NegotiateDialect::Smb02Wildcard {
-                return Err(Error::InvalidMessage(
-                    "Expected SMB2 wildcard dialect".to_string(),
-                ));
-            }
+            // 2. Make sure dialect is smb2*, message ID is 0.
             if message.header.message_id != 0 {
                 return Err(Error::InvalidMessage("Expected message ID 0".to_string()));
             }
-            if message.header.credit_charge != 0 || message.header.credit_request != 1 {
-                return Err(Error::InvalidMessage(
-                    "Expected credit charge 0 and request 1 for initial message.".to_string(),
-                ));
+            let smb2_negotiate_response = message.content.to_negotiate()?;
+            match smb2_negotiate_response.dialect_revision {
+                NegotiateDialect::Smb02Wildcard => {
+                    if message.header.credit_charge != 0 || message.header.credit_request != 1 {
+                        return Err(Error::InvalidMessage(
+                            "Expected credit charge 0 and request 1 for initial message."
+                                .to_string(),
+                        ));
+                    }
+                }
+                NegotiateDialect::Smb0202 => {
+                    log::debug!("Server selected SMB 2.0.2 in multi-protocol negotiation");
+                    direct_response = Some(smb2_negotiate_response);
+                }
+                _ => {
+                    return Err(Error::InvalidMessage(
+                        "Expected SMB2 wildcard or SMB 2.0.2 dialect".to_string(),
+                    ));
+                }
             }
             // Increase sequence number.
             self.handler.curr_msg_id.fetch_add(1, Ordering::SeqCst);
         }
 
-        WorkerImpl::start(transport, self.config.timeout()).await
+        let worker = WorkerImpl::start(transport, self.config.request_timeout()).await?;
+        Ok((worker, direct_response))
+    }
+
+    /// Sends the multi-protocol SMBv1 negotiate request, offering the SMB2 dialects in the configured range,
+    /// and receives the response of the server, which is either an SMBv1 or an SMB2 negotiate response.
+    #[maybe_async]
+    async fn _multi_protocol_negotiate(
+        &self,
+        transport: &mut Box<dyn SmbTransport>,
+    ) -> crate::Result<MultiProtocolResponse> {
+        log::debug!("Negotiating multi-protocol: Sending SMB1");
+        let (min_dialect, max_dialect) = self._dialect_range();
+        let msg_bytes: Vec<u8> = SMB1NegotiateMessage::new(
+            min_dialect <= Dialect::Smb0202,
+            max_dialect > Dialect::Smb0202,
+        )
+        .try_into()?;
+        transport.send(&IoVec::from(msg_bytes)).await?;
+
+        log::debug!("Sent SMB1 negotiate request, Receieving response");
+        let recieved_bytes = transport.receive().await?;
+        if recieved_bytes.starts_with(SMB1_PROTOCOL_ID) {
+            let response = SMB1NegotiateResponse::read(&mut std::io::Cursor::new(&recieved_bytes))?;
+            log::debug!("Server responded with SMB1 negotiate response: {response:?}");
+            return Ok(MultiProtocolResponse::Smb1(response));
+        }
+        match Response::try_from(recieved_bytes.as_ref())? {
+            Response::Plain(message) => Ok(MultiProtocolResponse::Smb2(message)),
+            _ => Err(Error::InvalidMessage(
+                "Expected plain SMB2 negotiate response".to_string(),
+            )),
+        }
+    }
+
+    /// Returns the range of dialects to negotiate, as configured.
+    fn _dialect_range(&self) -> (Dialect, Dialect) {
+        (
+            self.config.min_dialect.unwrap_or(Dialect::MIN),
+            self.config.max_dialect.unwrap_or(Dialect::MAX),
+        )
     }
 
     /// This method perofrms the SMB2 negotiation.
@@ -238,6 +418,7 @@ impl Connection {
     async fn _negotiate_smb2(
         &self,
         server_address: std::net::SocketAddr,
+        transport: &'static str,
     ) -> crate::Result<ConnectionInfo> {
         // Confirm that we're not already negotiated.
         if self.handler.conn_info.get().is_some() {
@@ -247,8 +428,7 @@ impl Connection {
         log::debug!("Negotiating SMB2");
 
         // List possible versions to run with.
-        let min_dialect = self.config.min_dialect.unwrap_or(Dialect::MIN);
-        let max_dialect = self.config.max_dialect.unwrap_or(Dialect::MAX);
+        let (min_dialect, max_dialect) = self._dialect_range();
         let dialects: Vec<Dialect> = Dialect::ALL
             .iter()
             .filter(|dialect| **dialect >= min_dialect && **dialect <= max_dialect)
@@ -267,25 +447,57 @@ impl Connection {
             vec![]
         };
 
+        let mut preauth_salt = vec![0u8; self.config.preauth_salt_length() as usize];
+        OsRng.fill_bytes(&mut preauth_salt);
+
+        let request = self._make_smb2_neg_request(
+            dialects,
+            self.config.allowed_signing_algorithms().to_vec(),
+            encryption_algos,
+            self.config.compression_algorithms().to_vec(),
+            self.config.preauth_hash_algorithms().to_vec(),
+            preauth_salt.clone(),
+        );
+        let client_negotiate = ValidateNegotiateInfoRequest {
+            capabilities: u32::from_le_bytes(request.capabilities.into_bytes()),
+            guid: request.client_guid,
+            security_mode: request.security_mode,
+            dialects: request.dialects.clone(),
+        };
+
         // Send SMB2 negotiate request
         let (request_status, response) = self
             .handler
-            .sendor_recv(
-                OutgoingMessage::new(
-                    self._make_smb2_neg_request(
-                        dialects,
-                        crypto::SIGNING_ALGOS.to_vec(),
-                        encryption_algos,
-                        compression::SUPPORTED_ALGORITHMS.to_vec(),
-                    )
-                    .into(),
-                )
-                .with_return_raw_data(true),
-            )
+            .sendor_recv(OutgoingMessage::new(request.into()).with_return_raw_data(true))
             .await?;
 
         let smb2_negotiate_response = response.message.content.to_negotiate()?;
+        self._process_negotiate_response(
+            &smb2_negotiate_response,
+            Some(client_negotiate),
+            preauth_salt,
+            request_status.raw.as_ref().map(|raw| (raw, &response.raw)),
+            server_address,
+            transport,
+        )
+    }
 
+    /// Processes the SMB2 negotiate response of the server, returning the connection information.
+    ///
+    /// `client_negotiate` holds the parameters of the SMB2 negotiate request of the client, if one was sent.
+    /// `preauth_messages` are the raw negotiate request and response, required for dialects that support
+    /// the preauth integrity hash. They are unavailable if the server selected SMB 2.0.2 in response to
+    /// the multi-protocol negotiate request.
+    fn _process_negotiate_response(
+        &self,
+        smb2_negotiate_response: &NegotiateResponse,
+        client_negotiate: Option<ValidateNegotiateInfoRequest>,
+        preauth_salt: Vec<u8>,
+        preauth_messages: Option<(&IoVec, &IoVec)>,
+        server_address: std::net::SocketAddr,
+        transport: &'static str,
+    ) -> crate::Result<ConnectionInfo> {
+        let (min_dialect, max_dialect) = self._dialect_range();
         // well, only 3.1 is supported for starters.
         let dialect_rev = smb2_negotiate_response.dialect_revision.try_into()?;
         if dialect_rev > max_dialect || dialect_rev < min_dialect {
@@ -298,6 +510,7 @@ impl Connection {
         let mut negotiation = NegotiatedProperties {
             server_guid: smb2_negotiate_response.server_guid,
             caps: smb2_negotiate_response.capabilities,
+            security_mode: smb2_negotiate_response.security_mode,
             max_transact_size: smb2_negotiate_response.max_transact_size,
             max_read_size: smb2_negotiate_response.max_read_size,
             max_write_size: smb2_negotiate_response.max_write_size,
@@ -305,11 +518,12 @@ impl Connection {
             signing_algo: None,
             encryption_cipher: None,
             compression: None,
+            preauth_integrity: None,
             dialect_rev,
         };
 
         dialect_impl.process_negotiate_request(
-            &smb2_negotiate_response,
+            smb2_negotiate_response,
             &mut negotiation,
             &self.config,
         )?;
@@ -328,26 +542,56 @@ impl Connection {
             &negotiation
         );
 
+        negotiation.preauth_integrity =
+            smb2_negotiate_response
+                .get_ctx_integrity()
+                .and_then(|caps| {
+                    Some(PreauthIntegrityInfo {
+                        algorithm: caps.hash_algorithms.first().copied()?,
+                        client_salt: preauth_salt,
+                        server_salt: caps.salt.clone(),
+                    })
+                });
+
         let preauth_hash = if dialect_impl.preauth_hash_supported() {
-            PreauthHashState::begin()
-                .next(
-                    &request_status
-                        .raw
-                        .expect("Preauth hash must be calculated for supported dialect!"),
+            // SHA-512 is the only algorithm defined by the spec, so assume it if not specified by the server.
+            let preauth_algo = negotiation
+                .preauth_integrity
+                .as_ref()
+                .map_or(HashAlgorithm::Sha512, |p| p.algorithm);
+            let (request, response) = preauth_messages.ok_or_else(|| {
+                Error::InvalidState(
+                    "Preauth hash must be calculated for supported dialect!".to_string(),
                 )
-                .next(&response.raw)
+            })?;
+            PreauthHashState::begin(preauth_algo)?
+                .next(request)
+                .next(response)
         } else {
             PreauthHashState::unsupported()
         };
 
+        let quirks = QuirksRegistry::new(
+            &ServerFingerprint {
+                server_guid: negotiation.server_guid,
+                dialect: dialect_rev,
+                auth_buffer: &negotiation.auth_buffer,
+            },
+            self.config.quirks,
+        );
+
         Ok(ConnectionInfo {
             negotiation,
+            quirks: Arc::new(quirks),
             dialect: dialect_impl,
             config: self.config.clone(),
             server_name: self.server_name.clone(),
             preauth_hash,
             client_guid: self.handler.client_guid,
+            client_negotiate,
+            negotiate_validated: Default::default(),
             server_address,
+            transport,
         })
     }
 
@@ -358,6 +602,8 @@ impl Connection {
         signing_algorithms: Vec<SigningAlgorithmId>,
         encrypting_algorithms: Vec<EncryptionCipher>,
         compression_algorithms: Vec<CompressionAlgorithm>,
+        preauth_hash_algorithms: Vec<HashAlgorithm>,
+        preauth_salt: Vec<u8>,
     ) -> NegotiateRequest {
         let client_guid = self.handler.client_guid;
         let client_netname = self
@@ -370,12 +616,10 @@ impl Connection {
 
         // Context list supported on SMB3.1.1+
         let ctx_list = if supported_dialects.contains(&Dialect::Smb0311) {
-            let mut preauth_integrity_hash = [0u8; 32];
-            OsRng.fill_bytes(&mut preauth_integrity_hash);
             let mut ctx_list = vec![
                 PreauthIntegrityCapabilities {
-                    hash_algorithms: vec![HashAlgorithm::Sha512],
-                    salt: preauth_integrity_hash.to_vec(),
+                    hash_algorithms: preauth_hash_algorithms,
+                    salt: preauth_salt,
                 }
                 .into(),
                 NetnameNegotiateContextId {
@@ -469,15 +713,26 @@ impl Connection {
         }
 
         let server_address = transport.remote_address()?;
+        let transport_name = transport.name();
         // Negotiate SMB1, Switch to SMB2
-        let worker = self
+        let (worker, direct_response) = self
             ._negotiate_switch_to_smb2(transport, smb2_only_neg)
             .await?;
 
         self.handler.worker.set(worker).unwrap();
 
-        // Negotiate SMB2
-        let info = self._negotiate_smb2(server_address).await?;
+        // Negotiate SMB2, unless the server already selected SMB 2.0.2.
+        let info = match direct_response {
+            Some(response) => self._process_negotiate_response(
+                &response,
+                None,
+                vec![],
+                None,
+                server_address,
+                transport_name,
+            )?,
+            None => self._negotiate_smb2(server_address, transport_name).await?,
+        };
 
         self.handler
             .worker
@@ -496,25 +751,35 @@ impl Connection {
 
         self.handler.conn_info.set(Arc::new(info)).unwrap();
 
+        #[cfg(not(feature = "single_threaded"))]
+        if let Some(interval) = self.config.keepalive_interval {
+            log::debug!("Starting keepalive job, every {interval:?} of idle time.");
+            self.handler.handler.start_keepalive(interval);
+        }
+
         log::debug!("Negotiation successful");
         Ok(())
     }
 
     /// Starts a new session for the current connection, and authenticates it
-    /// using the provided user name and password.
+    /// using the provided credentials.
     ///
     /// ## Arguments
-    /// * `user_name` - The user to authenticate with.
-    /// * `password` - The password for the user.
+    /// * `credentials` - The credentials to authenticate with: a user name and a password, a guest or an anonymous session.
+    ///   See [`Credentials`]. An [`sspi::AuthIdentity`] may be passed as well.
     ///
     /// ## Returns
     /// A [`Session`] object representing the authenticated session.
     ///
     /// ## Notes:
     /// * Use the [`ConnectionConfig`] to configure authentication options.
-    pub async fn authenticate(&self, identity: sspi::AuthIdentity) -> crate::Result<Session> {
+    /// * Guest and anonymous sessions require [`ConnectionConfig::allow_unsigned_guest_access`].
+    pub async fn authenticate(
+        &self,
+        credentials: impl Into<Credentials>,
+    ) -> crate::Result<Session> {
         let session = Session::create(
-            identity,
+            credentials.into(),
             &self.handler,
             self.handler.conn_info.get().unwrap(),
         )
@@ -533,6 +798,78 @@ impl Connection {
     pub fn conn_info(&self) -> Option<&Arc<ConnectionInfo>> {
         self.handler.conn_info.get()
     }
+
+    /// Returns a snapshot of the negotiated properties of the connection, such as the dialect,
+    /// the algorithms and the buffer size limits, or `None` if the connection has not been negotiated.
+    pub fn negotiate_info(&self) -> Option<NegotiateInfoSnapshot> {
+        self.conn_info().map(|info| info.as_ref().into())
+    }
+
+    /// Returns the counters of the compressed messages sent on the connection,
+    /// or `None` if the connection is not connected.
+    ///
+    /// Messages are only compressed if [`ConnectionConfig::compression_enabled`] is set, and the server supports compression.
+    pub fn compression_stats(&self) -> Option<CompressionStats> {
+        self.handler
+            .worker()
+            .map(|worker| worker.transformer().compression_stats())
+    }
+
+    /// Returns the counters of the requests sent on the connection, or `None` if no metrics are collected.
+    ///
+    /// Counters are collected if [`ConnectionConfig::metrics`] is set to [`MetricsHook::aggregate`],
+    /// or to a sink that aggregates them (see [`ClientMetricsSink::stats`]).
+    pub fn stats(&self) -> Option<ConnectionStats> {
+        self.handler.metrics.as_ref()?.sink.stats()
+    }
+
+    /// Sends an SMB2 ECHO request to the server, and waits for its response,
+    /// to check whether the connection is still alive.
+    ///
+    /// Fails with [`Error::TransportError`] or [`Error::Timeout`] if the connection is broken.
+    /// See also [`ConnectionConfig::keepalive_interval`], to probe idle connections periodically.
+    pub async fn echo(&self) -> crate::Result<()> {
+        self.handler.handler.echo(None).await
+    }
+
+    /// Sets a callback, invoked when the server breaks an oplock or a lease held by an open of the connection,
+    /// replacing the previously set one.
+    ///
+    /// Breaks are acknowledged automatically, once the callback completes, so applications that cache data
+    /// of the open locally should write it back (or discard it) from within the callback.
+    /// Breaks are not processed by single-threaded builds, which do not handle server notifications.
+    #[cfg(feature = "async")]
+    pub fn on_lease_break<F, Fut>(&self, callback: F) -> crate::Result<()>
+    where
+        F: Fn(BreakNotification) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.handler
+            .oplock_breaks
+            .set_callback(Arc::new(move |notification| {
+                Box::pin(callback(notification))
+            }))
+    }
+
+    /// Sets a callback, invoked when the server breaks an oplock or a lease held by an open of the connection,
+    /// replacing the previously set one.
+    ///
+    /// Breaks are acknowledged automatically, once the callback returns, so applications that cache data
+    /// of the open locally should write it back (or discard it) from within the callback.
+    /// Breaks are not processed by single-threaded builds, which do not handle server notifications.
+    #[cfg(not(feature = "async"))]
+    pub fn on_lease_break<F>(&self, callback: F) -> crate::Result<()>
+    where
+        F: Fn(BreakNotification) + Send + Sync + 'static,
+    {
+        self.handler.oplock_breaks.set_callback(Arc::new(callback))
+    }
+
+    /// Returns the worker of the connection, once it is connected.
+    #[cfg(test)]
+    pub(crate) fn worker(&self) -> Option<&Arc<WorkerImpl>> {
+        self.handler.worker()
+    }
 }
 
 /// This struct is the internal message handler for the SMB client.
@@ -546,14 +883,21 @@ pub(crate) struct ConnectionMessageHandler {
     worker: OnceCell<Arc<WorkerImpl>>,
 
     #[cfg(feature = "async")]
-    /// Cancellation token for stopping notifications.
+    /// Cancellation token for stopping notifications and keepalive requests.
     stop_notifications: CancellationToken,
     #[cfg(feature = "multi_threaded")]
-    /// Flag to stop notifications.
+    /// Flag to stop notifications and keepalive requests.
     stop_notifications: Arc<AtomicBool>,
 
+    /// The time the handler was created, which [`last_received`][Self::last_received] is relative to.
+    created: Instant,
+    /// The time a message was last received from the server, in milliseconds since [`created`][Self::created].
+    last_received: AtomicU64,
+
     /// Holds the sessions created by this connection.
     sessions: Mutex<HashMap<u64, Weak<ChannelMessageHandler>>>,
+    /// Holds the oplocks and leases of the opens of this connection.
+    oplock_breaks: OplockBreaks,
 
     // Negotiation-related state.
     conn_info: OnceCell<Arc<ConnectionInfo>>,
@@ -565,21 +909,32 @@ pub(crate) struct ConnectionMessageHandler {
     /// The number of credits granted to the client by the server, including the being-used ones.
     /// This field is used ONLY when large MTU is enabled.
     credit_pool: AtomicU16,
+
+    /// The metrics of the connection, if configured.
+    metrics: Option<ConnectionMetricsState>,
 }
 
 impl ConnectionMessageHandler {
-    fn new(client_guid: Guid, credits_backlog: Option<u16>) -> ConnectionMessageHandler {
+    fn new(
+        client_guid: Guid,
+        credits_backlog: u16,
+        metrics: Option<&MetricsHook>,
+    ) -> ConnectionMessageHandler {
         ConnectionMessageHandler {
             client_guid,
             worker: OnceCell::new(),
             conn_info: OnceCell::new(),
-            credits_backlog: credits_backlog.unwrap_or(128),
+            credits_backlog,
             curr_credits: Semaphore::new(1),
             curr_msg_id: AtomicU64::new(0),
             credit_pool: AtomicU16::new(1),
             #[cfg(not(feature = "single_threaded"))]
             stop_notifications: Default::default(),
+            created: Instant::now(),
+            last_received: AtomicU64::new(0),
             sessions: Mutex::new(HashMap::with_capacity(1)),
+            oplock_breaks: Default::default(),
+            metrics: metrics.map(ConnectionMetricsState::new),
         }
     }
 
@@ -587,6 +942,11 @@ impl ConnectionMessageHandler {
         self.worker.get()
     }
 
+    /// Returns the oplocks and leases held by the opens of the connection.
+    pub fn oplock_breaks(&self) -> &OplockBreaks {
+        &self.oplock_breaks
+    }
+
     const SET_CREDIT_CHARGE_CMDS: &'static [Command] = &[
         Command::Read,
         Command::Write,
@@ -594,40 +954,55 @@ impl ConnectionMessageHandler {
         Command::QueryDirectory,
     ];
 
-    const CREDIT_CALC_RATIO: u32 = 65536;
+    /// The payload size covered by a single credit (MS-SMB2 3.1.5.2).
+    pub(crate) const CREDIT_CALC_RATIO: u32 = 65536;
     const CREDITS_PER_MSG_NO_LARGE_MTU: u32 = 1;
 
+    /// Returns the credit charge of a message, when large MTU is supported.
+    fn credit_charge(msg: &OutgoingMessage) -> u16 {
+        if Self::SET_CREDIT_CHARGE_CMDS.contains(&msg.message.header.command) {
+            // Raw request bodies have no known payload size, and are charged a single credit,
+            // unless the payload size is provided.
+            let payload_size = msg.credit_payload_size.unwrap_or_else(|| {
+                max(
+                    msg.message.content.req_payload_size(),
+                    msg.message.content.expected_resp_size(),
+                )
+            });
+            (1 + payload_size.saturating_sub(1) / Self::CREDIT_CALC_RATIO)
+                .try_into()
+                .unwrap()
+        } else {
+            1
+        }
+    }
+
+    /// Sets the credit charge, credit request and message ID of a message, when large MTU is supported.
+    /// The charged credits must already be acquired.
+    fn assign_sequence(&self, msg: &mut OutgoingMessage, cost: u16) {
+        let mut request = cost;
+        // Request additional credits if required: if balance < extra, add to request the diff:
+        let current_pool_size = self.credit_pool.load(Ordering::SeqCst);
+        if current_pool_size < self.credits_backlog {
+            request += self.credits_backlog - current_pool_size;
+        }
+
+        msg.message.header.credit_charge = cost;
+        msg.message.header.credit_request = request;
+        msg.message.header.message_id = self.curr_msg_id.fetch_add(cost as u64, Ordering::SeqCst);
+    }
+
     #[maybe_async]
     async fn process_sequence_outgoing(&self, msg: &mut OutgoingMessage) -> crate::Result<()> {
         if let Some(neg) = self.conn_info.get() {
             if neg.negotiation.caps.large_mtu() {
                 // Calculate the cost of the message (charge).
-                let cost = if Self::SET_CREDIT_CHARGE_CMDS.contains(&msg.message.header.command) {
-                    let send_payload_size = msg.message.content.req_payload_size();
-                    let expected_response_payload_size = msg.message.content.expected_resp_size();
-                    (1 + (max(send_payload_size, expected_response_payload_size) - 1)
-                        / Self::CREDIT_CALC_RATIO)
-                        .try_into()
-                        .unwrap()
-                } else {
-                    1
-                };
+                let cost = Self::credit_charge(msg);
 
                 // First, acquire credits from the semaphore, and forget them.
                 // They may be returned via the response message, at `process_sequence_incoming` below.
                 self.curr_credits.acquire_many(cost as u32).await?.forget();
-
-                let mut request = cost;
-                // Request additional credits if required: if balance < extra, add to request the diff:
-                let current_pool_size = self.credit_pool.load(Ordering::SeqCst);
-                if current_pool_size < self.credits_backlog {
-                    request += self.credits_backlog - current_pool_size;
-                }
-
-                msg.message.header.credit_charge = cost;
-                msg.message.header.credit_request = request;
-                msg.message.header.message_id =
-                    self.curr_msg_id.fetch_add(cost as u64, Ordering::SeqCst);
+                self.assign_sequence(msg, cost);
 
                 return Ok(());
             } else {
@@ -655,6 +1030,35 @@ impl ConnectionMessageHandler {
         Ok(())
     }
 
+    /// Like [`Self::process_sequence_outgoing`], for the messages of a compound.
+    ///
+    /// The credits of all the messages are acquired at once, so concurrent compounds can't hold
+    /// part of the credits each, waiting for each other.
+    /// Compounding requires large MTU: otherwise, only a single request is outstanding at a time.
+    #[maybe_async]
+    async fn process_sequence_outgoing_compound(
+        &self,
+        msgs: &mut [OutgoingMessage],
+    ) -> crate::Result<()> {
+        if !self
+            .conn_info
+            .get()
+            .is_some_and(|neg| neg.negotiation.caps.large_mtu())
+        {
+            return Err(Error::UnsupportedOperation(
+                "Compounding requires a connection that supports multi-credit requests".to_string(),
+            ));
+        }
+
+        let costs = msgs.iter().map(Self::credit_charge).collect::<Vec<_>>();
+        let total_cost = costs.iter().map(|&cost| cost as u32).sum();
+        self.curr_credits.acquire_many(total_cost).await?.forget();
+        for (msg, cost) in msgs.iter_mut().zip(costs) {
+            self.assign_sequence(msg, cost);
+        }
+        Ok(())
+    }
+
     #[maybe_async]
     async fn process_sequence_incoming(&self, msg: &IncomingMessage) -> crate::Result<()> {
         if let Some(neg) = self.conn_info.get() {
@@ -673,6 +1077,9 @@ impl ConnectionMessageHandler {
 
                 // Return the credits to the pool.
                 self.curr_credits.add_permits(granted_credits as usize);
+                if let Some(metrics) = &self.metrics {
+                    self.report_credit_window(metrics);
+                }
                 return Ok(());
             }
         }
@@ -686,9 +1093,54 @@ impl ConnectionMessageHandler {
             Self::CREDITS_PER_MSG_NO_LARGE_MTU,
             self.curr_credits.available_permits()
         );
+        if let Some(metrics) = &self.metrics {
+            self.report_credit_window(metrics);
+        }
         Ok(())
     }
 
+    /// Reports the current credits of the connection to the metrics sink.
+    fn report_credit_window(&self, metrics: &ConnectionMetricsState) {
+        let granted = match self.conn_info.get() {
+            Some(neg) if neg.negotiation.caps.large_mtu() => {
+                self.credit_pool.load(Ordering::SeqCst)
+            }
+            _ => Self::CREDITS_PER_MSG_NO_LARGE_MTU as u16,
+        };
+        metrics.sink.on_credit_window(CreditWindow {
+            granted,
+            available: self.curr_credits.available_permits() as u32,
+        });
+    }
+
+    /// Records an outgoing message for the metrics, right before it is sent.
+    fn record_outgoing(&self, metrics: &ConnectionMetricsState, msg: &OutgoingMessage) {
+        let command = msg.message.header.command;
+        if msg.is_retry {
+            metrics.sink.on_retry(command);
+        }
+        if let RequestContent::Write(write) = &msg.message.content {
+            metrics
+                .sink
+                .on_bytes(DataDirection::Written, write.length as u64);
+        }
+        if msg.has_response {
+            metrics.sent(msg.message.header.message_id, command);
+        }
+    }
+
+    /// Sets the priority of an outgoing message, if supported by the dialect.
+    fn set_priority(&self, msg: &mut OutgoingMessage) {
+        let priority_value = match self.conn_info.get() {
+            Some(neg_info) => match neg_info.negotiation.dialect_rev {
+                Dialect::Smb0311 => 1,
+                _ => 0,
+            },
+            None => 0,
+        };
+        msg.message.header.flags = msg.message.header.flags.with_priority_mask(priority_value);
+    }
+
     #[cfg(feature = "async")]
     async fn start_notify(self: &Arc<Self>) -> crate::Result<()> {
         let worker = self.worker.get().unwrap();
@@ -745,6 +1197,112 @@ impl ConnectionMessageHandler {
         Ok(())
     }
 
+    /// Returns the time since a message was last received from the server.
+    #[cfg(not(feature = "single_threaded"))]
+    fn idle_time(&self) -> Duration {
+        let last_received = Duration::from_millis(self.last_received.load(Ordering::Relaxed));
+        self.created.elapsed().saturating_sub(last_received)
+    }
+
+    /// Sends an echo request, and waits for its response.
+    ///
+    /// If `timeout` is not set, the connection's timeout is used.
+    #[maybe_async]
+    async fn echo(&self, timeout: Option<Duration>) -> crate::Result<()> {
+        let mut options = ReceiveOptions::new().with_cmd(Some(Command::Echo));
+        if let Some(timeout) = timeout {
+            options = options.with_timeout(timeout);
+        }
+        let response = self
+            .send_recvo(EchoRequest::default().into(), options)
+            .await?;
+        response.message.content.to_echo()?;
+        Ok(())
+    }
+
+    /// Sends a keepalive echo request, if the connection has been idle for `interval`.
+    ///
+    /// Returns the time to wait before the next check, or `None` if the keepalive job should stop.
+    /// If the echo fails, the connection is stopped, failing the pending and future requests.
+    #[cfg(not(feature = "single_threaded"))]
+    #[maybe_async]
+    async fn keepalive(&self, interval: Duration) -> Option<Duration> {
+        let worker = self.worker.get()?;
+        if worker.stopped() {
+            return None;
+        }
+        let idle_time = self.idle_time();
+        if idle_time < interval {
+            return Some(interval - idle_time);
+        }
+
+        log::trace!("Connection idle for {idle_time:?}, sending keepalive echo.");
+        let timeout = match self.conn_info.get()?.config.request_timeout() {
+            Duration::ZERO => interval,
+            timeout => timeout,
+        };
+        match self.echo(Some(timeout)).await {
+            Ok(()) => Some(interval),
+            Err(Error::ConnectionStopped) => None,
+            Err(e) => {
+                log::error!("Keepalive echo failed, stopping the connection: {e}");
+                worker.stop().await.unwrap_or_else(|e| {
+                    log::debug!("Failed to stop the worker after a keepalive failure: {e}");
+                });
+                None
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn start_keepalive(self: &Arc<Self>, interval: Duration) {
+        let handler = Arc::downgrade(self);
+        let stop_keepalive = self.stop_notifications.clone();
+        tokio::spawn(async move {
+            let mut wait = interval;
+            loop {
+                select! {
+                    _ = stop_keepalive.cancelled() => break,
+                    _ = tokio::time::sleep(wait) => {}
+                }
+                let next_wait = match handler.upgrade() {
+                    Some(handler) => handler.keepalive(interval).await,
+                    None => None,
+                };
+                match next_wait {
+                    Some(next_wait) => wait = next_wait,
+                    None => break,
+                }
+            }
+            log::info!("Keepalive job stopped.");
+        });
+    }
+
+    #[cfg(feature = "multi_threaded")]
+    fn start_keepalive(self: &Arc<Self>, interval: Duration) {
+        const POLLING_INTERVAL: Duration = Duration::from_millis(100);
+        let handler = Arc::downgrade(self);
+        let stopped_ref = self.stop_notifications.clone();
+        std::thread::spawn(move || {
+            let mut next_check = Instant::now() + interval;
+            while !stopped_ref.load(Ordering::SeqCst) {
+                let now = Instant::now();
+                if now < next_check {
+                    std::thread::sleep((next_check - now).min(POLLING_INTERVAL));
+                    continue;
+                }
+                match handler
+                    .upgrade()
+                    .and_then(|handler| handler.keepalive(interval))
+                {
+                    Some(wait) => next_check = Instant::now() + wait,
+                    None => break,
+                }
+            }
+            log::info!("Keepalive job stopped.");
+        });
+    }
+
     #[cfg(not(feature = "single_threaded"))]
     pub fn stop_notify(&self) {
         #[cfg(feature = "async")]
@@ -755,17 +1313,58 @@ impl ConnectionMessageHandler {
     }
 }
 
+/// A response being awaited from the worker.
+///
+/// Unless disarmed once received, the response is abandoned when dropped, see [`Worker::abandon`].
+struct AwaitedResponse {
+    worker: Arc<WorkerImpl>,
+    msg_id: u64,
+    armed: bool,
+}
+
+impl AwaitedResponse {
+    #[maybe_async]
+    async fn abandon(mut self) -> crate::Result<()> {
+        self.armed = false;
+        self.worker.abandon(self.msg_id).await
+    }
+
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for AwaitedResponse {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let (worker, msg_id) = (self.worker.clone(), self.msg_id);
+        tokio::task::spawn(async move {
+            if let Err(e) = worker.abandon(msg_id).await {
+                log::debug!("Failed to abandon response {msg_id}: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl Drop for AwaitedResponse {
+    fn drop(&mut self) {
+        // Blocking receives are not dropped while waiting, unless unwinding.
+        if self.armed {
+            self.worker.abandon(self.msg_id).unwrap_or_else(|e| {
+                log::debug!("Failed to abandon response {}: {e}", self.msg_id);
+            });
+        }
+    }
+}
+
 impl MessageHandler for ConnectionMessageHandler {
     #[maybe_async]
     async fn sendo(&self, mut msg: OutgoingMessage) -> crate::Result<SendMessageResult> {
-        let priority_value = match self.conn_info.get() {
-            Some(neg_info) => match neg_info.negotiation.dialect_rev {
-                Dialect::Smb0311 => 1,
-                _ => 0,
-            },
-            None => 0,
-        };
-        msg.message.header.flags = msg.message.header.flags.with_priority_mask(priority_value);
+        self.set_priority(&mut msg);
 
         let is_cancel = msg.message.content.as_cancel().is_ok();
         if !is_cancel {
@@ -775,6 +1374,12 @@ impl MessageHandler for ConnectionMessageHandler {
                 "Cancel message must have a valid message ID".into(),
             ));
         }
+        if let Some(metrics) = &self.metrics {
+            if !is_cancel {
+                self.report_credit_window(metrics);
+                self.record_outgoing(metrics, &msg);
+            }
+        }
 
         self.worker
             .get()
@@ -783,9 +1388,77 @@ impl MessageHandler for ConnectionMessageHandler {
             .await
     }
 
+    #[maybe_async]
+    async fn sendo_compound(
+        &self,
+        mut msgs: Vec<OutgoingMessage>,
+    ) -> crate::Result<Vec<SendMessageResult>> {
+        if msgs
+            .iter()
+            .any(|msg| msg.message.content.as_cancel().is_ok())
+        {
+            return Err(Error::InvalidArgument(
+                "Cancel messages can't be compounded".into(),
+            ));
+        }
+        msgs.iter_mut().for_each(|msg| self.set_priority(msg));
+        self.process_sequence_outgoing_compound(&mut msgs).await?;
+        if let Some(metrics) = &self.metrics {
+            self.report_credit_window(metrics);
+            msgs.iter()
+                .for_each(|msg| self.record_outgoing(metrics, msg));
+        }
+
+        self.worker
+            .get()
+            .ok_or(Error::InvalidState("Worker is uninitialized".into()))?
+            .send_compound(msgs)
+            .await
+    }
+
     #[maybe_async]
     async fn recvo(&self, options: ReceiveOptions<'_>) -> crate::Result<IncomingMessage> {
-        let msg = self.worker.get().unwrap().receive(&options).await?;
+        let worker = self.worker.get().unwrap();
+        let awaiting = AwaitedResponse {
+            worker: worker.clone(),
+            msg_id: options.msg_id,
+            armed: true,
+        };
+        let msg = match (worker.receive(&options).await, options.cmd) {
+            // Tell which request timed out, if known.
+            (Err(Error::OperationTimeout(TimedOutTask::ReceiveNextMessage, _)), Some(command)) => {
+                Err(Error::Timeout(command, options.msg_id))
+            }
+            (msg, _) => msg,
+        };
+        match &msg {
+            // The response may still arrive later; it should not be stored until awaited.
+            Err(Error::OperationTimeout(..) | Error::Timeout(..) | Error::Cancelled(_)) => {
+                awaiting.abandon().await?
+            }
+            _ => awaiting.disarm(),
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.received(
+                options.msg_id,
+                msg.as_ref().ok().map(|msg| msg.message.header.status),
+            );
+            if let Some(ResponseContent::Read(read)) = msg.as_ref().ok().map(|m| &m.message.content)
+            {
+                metrics
+                    .sink
+                    .on_bytes(DataDirection::Read, read.buffer.len() as u64);
+            }
+        }
+        let msg = msg?;
+        self.last_received
+            .store(self.created.elapsed().as_millis() as u64, Ordering::Relaxed);
+
+        // Unchecked messages are returned as is, once their credits are accounted for.
+        if options.unchecked {
+            self.process_sequence_incoming(&msg).await?;
+            return Ok(msg);
+        }
 
         // Command matching (if needed).
         if let Some(cmd) = options.cmd {
@@ -794,6 +1467,11 @@ impl MessageHandler for ConnectionMessageHandler {
             }
         }
 
+        // A response of an unknown command is never expected.
+        if let ResponseContent::Unknown { command, .. } = msg.message.content {
+            return Err(Error::UnexpectedMessageCommand(Command::Unknown(command)));
+        }
+
         // Direction matching.
         if !msg.message.header.flags.server_to_redir() {
             return Err(Error::InvalidMessage(
@@ -809,13 +1487,11 @@ impl MessageHandler for ConnectionMessageHandler {
             .iter()
             .any(|s| msg.message.header.status == *s as u32)
         {
-            if let ResponseContent::Error(error_res) = msg.message.content {
-                return Err(Error::ReceivedErrorMessage(
-                    msg.message.header.status,
-                    error_res,
-                ));
-            }
-            return Err(Error::UnexpectedMessageStatus(msg.message.header.status));
+            let error_res = match msg.message.content {
+                ResponseContent::Error(error_res) => Some(error_res),
+                _ => None,
+            };
+            return Err(Error::server_status(msg.message.header.status, error_res));
         }
 
         Ok(msg)
@@ -823,6 +1499,30 @@ impl MessageHandler for ConnectionMessageHandler {
 
     #[maybe_async]
     async fn notify(&self, msg: IncomingMessage) -> crate::Result<()> {
+        // A response of an abandoned request: only its credits are relevant.
+        if msg.message.header.message_id != u64::MAX {
+            log::debug!(
+                "Dropping response of abandoned request {} ({:#x}).",
+                msg.message.header.message_id,
+                msg.message.header.status
+            );
+            return self.process_sequence_incoming(&msg).await;
+        }
+
+        // Breaks are handled by the connection, since the server does not have to set their session ID.
+        let notification = match &msg.message.content {
+            ResponseContent::OplockBreakNotify(notify) => {
+                Some(BreakNotification::Oplock(notify.clone()))
+            }
+            ResponseContent::LeaseBreakNotify(notify) => {
+                Some(BreakNotification::Lease(notify.clone()))
+            }
+            _ => None,
+        };
+        if let Some(notification) = notification {
+            return self.oplock_breaks.handle(notification).await;
+        }
+
         if msg.message.header.session_id == 0 {
             log::warn!("Received notification without session ID: {msg:?}");
             return Ok(());
@@ -882,3 +1582,511 @@ impl Drop for ConnectionMessageHandler {
         });
     }
 }
+
+/// Returns the transport to retry connecting with, after connecting to `address` failed with `error`.
+///
+/// Only refused TCP connections to the default port fall back to NetBIOS (port 139),
+/// since other errors (e.g. timeouts) are just as likely to happen when connecting to port 139.
+#[cfg(feature = "netbios-transport")]
+fn netbios_fallback(
+    config: &ConnectionConfig,
+    addresses: &[SocketAddr],
+    error: &TransportError,
+) -> Option<TransportConfig> {
+    fn is_refused(error: &TransportError) -> bool {
+        match error {
+            TransportError::IoError(e) => e.kind() == std::io::ErrorKind::ConnectionRefused,
+            TransportError::ConnectFailed(errors) => errors.iter().all(|(_, e)| is_refused(e)),
+            _ => false,
+        }
+    }
+    let is_refused = is_refused(error);
+    let is_default_port =
+        config.port.is_none() && addresses.iter().all(|address| address.port() == 0);
+    (config.netbios_fallback
+        && config.transport == TransportConfig::Tcp
+        && is_default_port
+        && is_refused)
+        .then_some(TransportConfig::NetBios)
+}
+
+#[cfg(not(feature = "netbios-transport"))]
+fn netbios_fallback(
+    _config: &ConnectionConfig,
+    _addresses: &[SocketAddr],
+    _error: &TransportError,
+) -> Option<TransportConfig> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Connection, ConnectionConfig, Error, ProtocolFamily};
+    use maybe_async::maybe_async;
+    use smb_dtyp::Guid;
+    use smb_msg::{
+        Dialect, NegotiateDialect,
+        smb1::{SMB1_PROTOCOL_ID, SMB1NegotiateMessage},
+    };
+    use smb_transport::{SmbTransport, TcpTransport};
+    use std::io::{Read, Write};
+    use std::net::SocketAddr;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Starts a fake server, replying each request with the next of `replies`.
+    ///
+    /// The received requests are sent to the returned channel once all the replies were sent,
+    /// and the connection is then kept open until the client closes it.
+    fn start_fake_server(replies: Vec<Vec<u8>>) -> (SocketAddr, mpsc::Receiver<Vec<Vec<u8>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut requests = vec![];
+            for reply in replies {
+                requests.push(read_frame(&mut stream));
+                let mut frame = (reply.len() as u32).to_be_bytes().to_vec();
+                frame.extend(reply);
+                stream.write_all(&frame).unwrap();
+            }
+            sender.send(requests).unwrap();
+            while stream.read(&mut [0; 1024]).is_ok_and(|read| read > 0) {}
+        });
+        (address, receiver)
+    }
+
+    fn read_frame(stream: &mut TcpStream) -> Vec<u8> {
+        let mut length = [0; 4];
+        stream.read_exact(&mut length).unwrap();
+        let mut frame = vec![0; u32::from_be_bytes(length) as usize];
+        stream.read_exact(&mut frame).unwrap();
+        frame
+    }
+
+    /// Returns an SMB2 negotiate response, selecting the specified dialect, with no security buffer.
+    fn negotiate_response(dialect: u16, message_id: u64) -> Vec<u8> {
+        let mut response = vec![];
+        // Header: credit charge 0, status 0, negotiate, 1 credit, server-to-redir.
+        response.extend(b"\xfeSMB\x40\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x01\x00\x00\x00");
+        response.extend([0; 4]);
+        response.extend(message_id.to_le_bytes());
+        response.extend([0; 32]);
+        // Negotiate response, signing enabled.
+        response.extend(b"\x41\x00\x01\x00");
+        response.extend(dialect.to_le_bytes());
+        response.extend([0; 2]);
+        response.extend([0x11; 16]);
+        response.extend([0; 4]);
+        for max_size in [0x10000u32; 3] {
+            response.extend(max_size.to_le_bytes());
+        }
+        response.extend([0; 16]);
+        response.extend(b"\x80\x00\x00\x00\x00\x00\x00\x00\x00");
+        response
+    }
+
+    #[maybe_async]
+    async fn negotiate(
+        server_address: SocketAddr,
+        config: ConnectionConfig,
+    ) -> crate::Result<Connection> {
+        let mut transport = TcpTransport::new(TIMEOUT);
+        transport.connect("server", server_address).await?;
+        Connection::from_transport(
+            Box::new(transport),
+            "server",
+            Guid::generate(),
+            ConnectionConfig {
+                timeout: config.timeout.or(Some(TIMEOUT)),
+                ..config
+            },
+        )
+        .await
+    }
+
+    /// Returns an SMB2 echo response, with 1 credit.
+    fn echo_response(message_id: u64) -> Vec<u8> {
+        let mut response = vec![];
+        response.extend(b"\xfeSMB\x40\x00\x00\x00\x00\x00\x00\x00\x0d\x00\x01\x00\x01\x00\x00\x00");
+        response.extend([0; 4]);
+        response.extend(message_id.to_le_bytes());
+        response.extend([0; 32]);
+        response.extend(b"\x04\x00\x00\x00");
+        response
+    }
+
+    /// The replies of the fake server to negotiate SMB 2.1, followed by `replies`.
+    fn negotiate_smb021_then(replies: impl IntoIterator<Item = Vec<u8>>) -> Vec<Vec<u8>> {
+        let mut all_replies = vec![
+            negotiate_response(NegotiateDialect::Smb02Wildcard as u16, 0),
+            negotiate_response(Dialect::Smb021 as u16, 1),
+        ];
+        all_replies.extend(replies);
+        all_replies
+    }
+
+    fn is_echo_request(request: &[u8]) -> bool {
+        request.starts_with(b"\xfeSMB") && request[12..14] == [0x0d, 0x00]
+    }
+
+    fn negotiated_dialect(connection: &Connection) -> Dialect {
+        connection.conn_info().unwrap().negotiation.dialect_rev
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_multi_protocol_smb1_only_server() {
+        // NT LM 0.12 selected.
+        let (address, requests) = start_fake_server(vec![
+            smb_tests::hex_to_u8_array! {"ff534d4272000000009853c8000000000000000000000000fffffeff000000001100000332000100041100000000010000000000fde30080"},
+        ]);
+        let result = negotiate(address, Default::default()).await;
+        assert!(matches!(result, Err(Error::Smb1OnlyServer)));
+
+        let requests = requests.recv_timeout(TIMEOUT).unwrap();
+        assert!(requests[0].starts_with(SMB1_PROTOCOL_ID));
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_multi_protocol_wildcard() {
+        let (address, requests) = start_fake_server(vec![
+            negotiate_response(NegotiateDialect::Smb02Wildcard as u16, 0),
+            negotiate_response(Dialect::Smb021 as u16, 1),
+        ]);
+        let connection = negotiate(
+            address,
+            ConnectionConfig {
+                max_dialect: Some(Dialect::Smb021),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(negotiated_dialect(&connection), Dialect::Smb021);
+
+        let requests = requests.recv_timeout(TIMEOUT).unwrap();
+        let smb1_request: Vec<u8> = SMB1NegotiateMessage::new(true, true).try_into().unwrap();
+        assert_eq!(requests[0], smb1_request);
+        assert!(requests[1].starts_with(b"\xfeSMB"));
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_negotiate_info() {
+        let (address, _requests) = start_fake_server(negotiate_smb021_then([]));
+        let connection = negotiate(
+            address,
+            ConnectionConfig {
+                max_dialect: Some(Dialect::Smb021),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let info = connection.negotiate_info().unwrap();
+        assert_eq!(info.server_address, address);
+        assert_eq!(info.transport, "tcp");
+        assert_eq!(info.dialect, Dialect::Smb021);
+        assert_eq!(info.server_guid, Guid::from([0x11; 16]));
+        assert!(!info.signing_required);
+        assert_eq!(
+            info.signing_algorithm,
+            smb_msg::SigningAlgorithmId::HmacSha256
+        );
+        assert_eq!(info.encryption_cipher, None);
+        assert!(info.compression_algorithms.is_empty());
+        assert_eq!(info.preauth_hash_algorithm, None);
+        assert_eq!(
+            (
+                info.max_transact_size,
+                info.max_read_size,
+                info.max_write_size
+            ),
+            (0x10000, 0x10000, 0x10000)
+        );
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_multi_protocol_smb2_002_selected() {
+        let (address, requests) =
+            start_fake_server(vec![negotiate_response(Dialect::Smb0202 as u16, 0)]);
+        let connection = negotiate(
+            address,
+            ConnectionConfig {
+                max_dialect: Some(Dialect::Smb0202),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(negotiated_dialect(&connection), Dialect::Smb0202);
+
+        // Only SMB 2.002 is offered, and no SMB2 negotiate request is sent.
+        let requests = requests.recv_timeout(TIMEOUT).unwrap();
+        let smb1_request: Vec<u8> = SMB1NegotiateMessage::new(true, false).try_into().unwrap();
+        assert_eq!(requests, [smb1_request]);
+    }
+
+    /// Returns the signing capabilities of the SMB2 negotiate request.
+    #[cfg(all(feature = "sign_hmac", feature = "sign_cmac"))]
+    fn offered_signing_algorithms(request: &[u8]) -> Option<Vec<smb_msg::SigningAlgorithmId>> {
+        use binrw::BinRead;
+        use smb_msg::{NegotiateContextValue, NegotiateRequest};
+        use std::io::Cursor;
+
+        // Context offsets are relative to the start of the SMB2 header.
+        let mut cursor = Cursor::new(request);
+        cursor.set_position(64);
+        let request = NegotiateRequest::read_le(&mut cursor).unwrap();
+        request
+            .negotiate_context_list?
+            .into_iter()
+            .find_map(|context| match context.data {
+                NegotiateContextValue::SigningCapabilities(caps) => Some(caps.signing_algorithms),
+                _ => None,
+            })
+    }
+
+    #[cfg(all(feature = "sign_hmac", feature = "sign_cmac"))]
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_negotiate_signing_algorithms() {
+        use smb_msg::SigningAlgorithmId;
+
+        let restricted = vec![SigningAlgorithmId::HmacSha256, SigningAlgorithmId::AesCmac];
+        for (allowed, expected) in [
+            (None, crate::crypto::SIGNING_ALGOS.to_vec()),
+            (Some(restricted.clone()), restricted.clone()),
+            (
+                Some(vec![SigningAlgorithmId::AesCmac]),
+                vec![SigningAlgorithmId::AesCmac],
+            ),
+        ] {
+            let (address, requests) =
+                start_fake_server(vec![negotiate_response(Dialect::Smb021 as u16, 0)]);
+            negotiate(
+                address,
+                ConnectionConfig {
+                    smb2_only_negotiate: true,
+                    allowed_signing_algorithms: allowed,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+            let requests = requests.recv_timeout(TIMEOUT).unwrap();
+            assert_eq!(offered_signing_algorithms(&requests[0]), Some(expected));
+        }
+    }
+
+    #[maybe_async]
+    async fn probe(
+        server_address: SocketAddr,
+        config: ConnectionConfig,
+    ) -> crate::Result<ProtocolFamily> {
+        let connection = Connection::build(
+            "server",
+            server_address,
+            Guid::generate(),
+            ConnectionConfig {
+                timeout: Some(TIMEOUT),
+                ..config
+            },
+        )?;
+        connection.probe_dialects().await
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_probe_dialects() {
+        for (reply, max_dialect, expected) in [
+            (
+                smb_tests::hex_to_u8_array! {"ff534d4272000000009853c8000000000000000000000000fffffeff000000001100000332000100041100000000010000000000fde30080"},
+                None,
+                ProtocolFamily::Smb1,
+            ),
+            (
+                negotiate_response(NegotiateDialect::Smb02Wildcard as u16, 0),
+                None,
+                ProtocolFamily::Smb2,
+            ),
+            (
+                negotiate_response(Dialect::Smb0202 as u16, 0),
+                Some(Dialect::Smb0202),
+                ProtocolFamily::Smb202,
+            ),
+        ] {
+            let (address, requests) = start_fake_server(vec![reply]);
+            let config = ConnectionConfig {
+                max_dialect,
+                ..Default::default()
+            };
+            let family = probe(address, config).await.unwrap();
+            assert_eq!(family, expected);
+
+            // Only the multi-protocol negotiate request is sent.
+            let requests = requests.recv_timeout(TIMEOUT).unwrap();
+            assert_eq!(requests.len(), 1);
+            assert!(requests[0].starts_with(SMB1_PROTOCOL_ID));
+        }
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_probe_dialects_none_supported() {
+        // SMBv1 response, with no dialect selected.
+        let (address, _requests) = start_fake_server(vec![smb_tests::hex_to_u8_array! {
+            "ff534d4272000000009853c8000000000000000000000000fffffeff0000000001ffff0000"
+        }]);
+        let result = probe(address, Default::default()).await;
+        assert!(matches!(result, Err(Error::NegotiationError(_))));
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_echo() {
+        let (address, requests) = start_fake_server(negotiate_smb021_then([echo_response(2)]));
+        let connection = negotiate(
+            address,
+            ConnectionConfig {
+                max_dialect: Some(Dialect::Smb021),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        connection.echo().await.unwrap();
+
+        let requests = requests.recv_timeout(TIMEOUT).unwrap();
+        assert!(is_echo_request(&requests[2]));
+    }
+
+    /// A response of an unknown command fails its request, and the connection remains usable.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_unknown_response_command() {
+        use crate::test_util::*;
+        use smb_msg::{Command, EchoResponse, ResponseContent, ShareFlags};
+        use smb_transport::mock::MockStep;
+
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                MockStep::expect(Command::Echo).respond(ResponseContent::Unknown {
+                    command: 0x7f,
+                    body: vec![0x08, 0x00, 0xaa, 0xbb],
+                }),
+                MockStep::expect(Command::Echo).respond(EchoResponse::default()),
+            ],
+        )
+        .await;
+        let result = mock.connection.echo().await;
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedMessageCommand(Command::Unknown(0x7f)))
+        ));
+        mock.connection.echo().await.unwrap();
+        assert!(!mock.handle.is_disconnected());
+        mock.handle.assert_done();
+    }
+
+    #[cfg(not(feature = "single_threaded"))]
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_keepalive() {
+        #[cfg(feature = "async")]
+        async fn sleep(duration: Duration) {
+            tokio::time::sleep(duration).await;
+        }
+        #[cfg(not(feature = "async"))]
+        fn sleep(duration: Duration) {
+            std::thread::sleep(duration);
+        }
+
+        // The first keepalive echo is answered, and the next one is not.
+        let (address, requests) = start_fake_server(negotiate_smb021_then([echo_response(2)]));
+        let connection = negotiate(
+            address,
+            ConnectionConfig {
+                max_dialect: Some(Dialect::Smb021),
+                keepalive_interval: Some(Duration::from_millis(100)),
+                timeout: Some(Duration::from_millis(300)),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        sleep(Duration::from_millis(1500)).await;
+
+        let requests = requests.try_recv().unwrap();
+        assert!(is_echo_request(&requests[2]));
+        // The unanswered echo stops the connection.
+        assert!(connection.handler.worker().unwrap().stopped());
+    }
+
+    #[cfg(feature = "netbios-transport")]
+    #[test]
+    fn test_netbios_fallback() {
+        use super::netbios_fallback;
+        use smb_transport::{TransportConfig, TransportError};
+        use std::io::ErrorKind;
+
+        let refused = || TransportError::IoError(ErrorKind::ConnectionRefused.into());
+        let default_port: [SocketAddr; 1] = ["10.0.0.1:0".parse().unwrap()];
+        let config = ConnectionConfig {
+            netbios_fallback: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            netbios_fallback(&config, &default_port, &refused()),
+            Some(TransportConfig::NetBios)
+        );
+
+        // Disabled, or not a refused connection.
+        assert_eq!(
+            netbios_fallback(&ConnectionConfig::default(), &default_port, &refused()),
+            None
+        );
+        for error in [
+            TransportError::Timeout(Duration::from_secs(1)),
+            TransportError::IoError(ErrorKind::ConnectionReset.into()),
+        ] {
+            assert_eq!(netbios_fallback(&config, &default_port, &error), None);
+        }
+
+        // Multiple addresses fall back only if all of them refused the connection.
+        let addresses: [SocketAddr; 2] = [
+            "10.0.0.1:0".parse().unwrap(),
+            "[fd00::1]:0".parse().unwrap(),
+        ];
+        let all_refused = TransportError::ConnectFailed(addresses.map(|a| (a, refused())).into());
+        assert_eq!(
+            netbios_fallback(&config, &addresses, &all_refused),
+            Some(TransportConfig::NetBios)
+        );
+        let one_timed_out = TransportError::ConnectFailed(vec![
+            (addresses[0], refused()),
+            (
+                addresses[1],
+                TransportError::Timeout(Duration::from_secs(1)),
+            ),
+        ]);
+        assert_eq!(netbios_fallback(&config, &addresses, &one_timed_out), None);
+
+        // An explicitly configured port, or a transport other than TCP.
+        let explicit_port: [SocketAddr; 1] = ["10.0.0.1:10445".parse().unwrap()];
+        assert_eq!(netbios_fallback(&config, &explicit_port, &refused()), None);
+        let with_port = ConnectionConfig {
+            port: Some(445),
+            ..config.clone()
+        };
+        assert_eq!(
+            netbios_fallback(&with_port, &default_port, &refused()),
+            None
+        );
+        let netbios = ConnectionConfig {
+            transport: TransportConfig::NetBios,
+            ..config
+        };
+        assert_eq!(netbios_fallback(&netbios, &default_port, &refused()), None);
+    }
+}
@@ -1,10 +1,16 @@
 //! Connection configuration settings.
 
+use std::net::IpAddr;
 use std::time::Duration;
 
-use smb_msg::Dialect;
+use smb_msg::{Command, CompressionAlgorithm, Dialect, HashAlgorithm, SigningAlgorithmId};
 use smb_transport::config::*;
 
+use super::metrics::MetricsHook;
+use super::preauth_hash;
+use super::quirks::ServerQuirksOverrides;
+use crate::session::AuthTokenHook;
+
 /// Specifies the encryption mode for the connection.
 /// Use this as part of the [ConnectionConfig] to specify the encryption mode for the connection.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -62,6 +68,92 @@ impl EncryptionMode {
     }
 }
 
+/// Specifies which messages are signed and/or encrypted by the client.
+/// Use this as part of the [ConnectionConfig] to relax message protection for bulk data transfers.
+///
+/// # Security
+/// **Any policy other than the default one weakens the security of the connection!**
+///
+/// Unsigned messages may be tampered with by anyone on the network path, and unencrypted
+/// messages may be read by anyone on it. Only relax the policy on isolated, trusted networks,
+/// where line-rate throughput is more important than integrity and confidentiality of file data.
+///
+/// The policy never overrides a requirement of the server: if the server requires
+/// signing or encryption for a message that the policy would leave unprotected, sending
+/// that message fails with [`Error::InvalidConfiguration`][crate::Error::InvalidConfiguration].
+/// For the same reason, a relaxed policy may not be combined with [`EncryptionMode::Required`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MessageProtectionPolicy {
+    /// All messages are protected as negotiated: they are signed, and encrypted whenever
+    /// the session, the share or the [`EncryptionMode`] require it.
+    #[default]
+    SignAndEncryptEverything,
+    /// All messages are signed, but none are encrypted.
+    SignAllEncryptNone,
+    /// Control messages are protected as negotiated, but bulk data messages
+    /// (`READ` and `WRITE`) are neither signed nor encrypted.
+    ControlOnly,
+}
+
+impl MessageProtectionPolicy {
+    /// Returns whether this is the default, non-relaxed policy.
+    pub fn is_default(&self) -> bool {
+        matches!(self, Self::SignAndEncryptEverything)
+    }
+
+    /// Returns whether messages of the specified command may be encrypted under this policy.
+    pub fn allows_encryption(&self, command: Command) -> bool {
+        match self {
+            Self::SignAndEncryptEverything => true,
+            Self::SignAllEncryptNone => false,
+            Self::ControlOnly => !Self::is_bulk(command),
+        }
+    }
+
+    /// Returns whether messages of the specified command may be signed under this policy.
+    pub fn allows_signing(&self, command: Command) -> bool {
+        match self {
+            Self::SignAndEncryptEverything | Self::SignAllEncryptNone => true,
+            Self::ControlOnly => !Self::is_bulk(command),
+        }
+    }
+
+    fn is_bulk(command: Command) -> bool {
+        matches!(command, Command::Read | Command::Write)
+    }
+}
+
+/// Specifies how buffer sizes that exceed the limits negotiated with the server are handled.
+///
+/// This applies to buffer sizes passed to operations such as [`Directory::query_with_options`][crate::Directory::query_with_options],
+/// [`ResourceHandle::ioctl`][crate::ResourceHandle::ioctl], the `*_with_options` query methods,
+/// [`File::read_block`][crate::File::read_block] and [`File::write_block`][crate::File::write_block].
+/// See [`ConnectionInfo::clamp_or_error`][crate::connection::connection_info::ConnectionInfo::clamp_or_error].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BufferSizePolicy {
+    /// Oversized buffers are clamped to the negotiated limit, and a warning is logged.
+    #[default]
+    Clamp,
+    /// Oversized buffers fail the operation with [`Error::InvalidArgument`][crate::Error::InvalidArgument],
+    /// before any request is sent.
+    Error,
+}
+
+/// Specifies how incoming messages with an invalid signature are handled.
+///
+/// **Security notes:** a message with an invalid signature may have been tampered with on the network path,
+/// or injected by an attacker. [`SignatureFailurePolicy::Warn`] accepts such messages as if their signature was valid,
+/// so it must only be used to diagnose servers that sign messages incorrectly, on isolated, trusted networks.
+/// Never use it in production.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureFailurePolicy {
+    /// Messages with an invalid signature fail with [`Error::SignatureVerificationFailed`][crate::Error::SignatureVerificationFailed].
+    #[default]
+    Fail,
+    /// **Insecure!** Messages with an invalid signature are logged as a warning, and processed as if their signature was valid.
+    Warn,
+}
+
 /// Specifies the authentication methods (SSPs) to be used for the connection.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AuthMethodsConfig {
@@ -72,6 +164,11 @@ pub struct AuthMethodsConfig {
     /// Whether to try using Kerberos authentication.
     /// This is supported only if the `kerberos` feature is enabled,
     /// and if so, enabled by default.
+    ///
+    /// Tickets are requested for the SPN of the server, see [`UncPath::kerberos_spn`][crate::UncPath::kerberos_spn],
+    /// using the password of [`Credentials::Password`][crate::Credentials::Password].
+    /// Existing ticket caches (ccache, e.g. `KRB5CCNAME`) and keytabs are not supported,
+    /// since the underlying `sspi` library can't use them.
     pub kerberos: bool,
 }
 
@@ -91,12 +188,45 @@ pub struct ConnectionConfig {
     /// If unset, defaults to the default port for the selected transport protocol.
     pub port: Option<u16>,
 
-    /// Specifies the timeout for the connection.
+    /// Specifies the timeout for connecting to the server.
     /// If unset, defaults to [`ConnectionConfig::DEFAULT_TIMEOUT`].
     /// 0 means wait forever.
     /// Access the timeout using the [`ConnectionConfig::timeout()`] method.
     pub timeout: Option<Duration>,
 
+    /// Specifies how long to wait for the response of each request, once the connection is established.
+    /// If unset, defaults to [`timeout`][Self::timeout]. 0 means wait forever.
+    ///
+    /// A request that times out fails with [`Error::Timeout`][crate::Error::Timeout], holding its command
+    /// and message ID, and its response is discarded if it arrives later. Requests of files, directories and pipes are
+    /// cancelled on the server as well. Requests that may wait for long by design, such as
+    /// [`Directory::watch`][crate::resource::Directory::watch], override this timeout
+    /// (see [`ReceiveOptions::timeout`][crate::msg_handler::ReceiveOptions::timeout]).
+    /// Access the timeout using the [`ConnectionConfig::request_timeout()`] method.
+    pub request_timeout: Option<Duration>,
+
+    /// Specifies the timeout for the whole session setup (authentication) exchange,
+    /// regardless of the per-message [`timeout`][Self::timeout].
+    /// If unset, defaults to [`ConnectionConfig::DEFAULT_SESSION_SETUP_TIMEOUT`].
+    /// 0 means wait forever.
+    pub session_setup_timeout: Option<Duration>,
+
+    /// Specifies the timeout for connecting to a tree (share).
+    /// If unset, defaults to [`ConnectionConfig::DEFAULT_TREE_CONNECT_TIMEOUT`].
+    /// 0 means wait forever.
+    pub tree_connect_timeout: Option<Duration>,
+
+    /// Sends an SMB2 ECHO request to the server once the connection is idle for this long,
+    /// so that network gateways don't drop idle connections, and broken connections are detected early.
+    /// If unset, no keepalive requests are sent.
+    ///
+    /// If the server does not respond within [`timeout`][Self::timeout] (or within the keepalive interval,
+    /// if the timeout is 0), the connection is stopped: pending and future requests fail with
+    /// [`Error::ConnectionStopped`][crate::Error::ConnectionStopped].
+    /// Keepalive requests are not sent by single-threaded builds, which have no background tasks.
+    /// See also [`Connection::echo`][crate::Connection::echo].
+    pub keepalive_interval: Option<Duration>,
+
     /// Specifies the minimum and maximum dialects to be used in the connection.
     ///
     /// Note, that if set, the minimum dialect must be less than or equal to the maximum dialect.
@@ -111,15 +241,42 @@ pub struct ConnectionConfig {
     /// See [EncryptionMode] for more information.
     pub encryption_mode: EncryptionMode,
 
+    /// Sets which messages are signed and/or encrypted.
+    /// See [MessageProtectionPolicy] for more information, and **read the security notes there before changing it.**
+    pub protection_policy: MessageProtectionPolicy,
+
     /// Sets whether signing may be skipped for guest or anonymous access.
     pub allow_unsigned_guest_access: bool,
 
+    /// Whether to fail the session setup, with [`Error::GuestFallbackRefused`][crate::Error::GuestFallbackRefused],
+    /// when the server logs in a user that supplied a password as a guest.
+    ///
+    /// Some servers (e.g. Samba with `map to guest = bad password`) log in users with unknown names or wrong
+    /// passwords as guests, instead of failing the login, so setting this prevents a silent downgrade to guest access.
+    /// It does not affect [`Credentials::Guest`][crate::session::Credentials::Guest] and
+    /// [`Credentials::Anonymous`][crate::session::Credentials::Anonymous].
+    pub refuse_guest_fallback: bool,
+
     /// Whether to enable compression, if supported by the server and specified connection dialects.
     ///
     /// Note: you must also have compression features enabled when building the crate, otherwise compression
     /// would not be available. *The compression feature is enabled by default.*
     pub compression_enabled: bool,
 
+    /// Write requests larger than this, in bytes, are sent compressed, if compression is enabled and negotiated.
+    /// If unset, defaults to [`ConnectionConfig::DEFAULT_COMPRESSION_THRESHOLD`].
+    ///
+    /// Requests are sent uncompressed when compressing them does not make them smaller.
+    pub compression_threshold: Option<u32>,
+
+    /// The compression algorithms to offer to the server, in order of preference (SMB 3.1.1 only).
+    /// If unset, defaults to [`ConnectionConfig::DEFAULT_COMPRESSION_ALGORITHMS`].
+    /// All algorithms must be supported by the crate.
+    ///
+    /// Chained compression is offered along with the algorithms, so that runs of a repeated byte
+    /// can be compressed using Pattern_V1, and the rest of the message using LZ4.
+    pub compression_algorithms: Option<Vec<CompressionAlgorithm>>,
+
     /// Multi-channel configuration
     pub multichannel: MultiChannelConfig,
 
@@ -133,17 +290,83 @@ pub struct ConnectionConfig {
     /// Whether to avoid multi-protocol negotiation,
     /// and perform smb2-only negotiation. This results in a
     /// faster negotiation process, but it might fail with some servers,
+    /// that expect an SMBv1 negotiate request first.
+    ///
+    /// When unset, an SMBv1 negotiate request is sent first, offering the SMB2 dialects in the
+    /// configured range. Servers that only support SMBv1 fail the negotiation with
+    /// [`Error::Smb1OnlyServer`][crate::Error::Smb1OnlyServer].
     pub smb2_only_negotiate: bool,
 
+    /// The preauth integrity hash algorithms to offer to the server, in order of preference (SMB 3.1.1 only).
+    /// If unset, defaults to [`ConnectionConfig::DEFAULT_PREAUTH_HASH_ALGORITHMS`].
+    /// All algorithms must be supported by the crate.
+    pub preauth_hash_algorithms: Option<Vec<HashAlgorithm>>,
+
+    /// The signing algorithms to offer to the server, in order of preference (SMB 3.1.1 only).
+    /// If unset, defaults to [`ConnectionConfig::DEFAULT_SIGNING_ALGORITHMS`].
+    /// All algorithms must be supported by the crate.
+    ///
+    /// Negotiation fails with [`Error::NegotiationError`][crate::Error::NegotiationError] if the server selects
+    /// an algorithm that was not offered. A server that does not select any algorithm implies AES-CMAC,
+    /// so that must be offered as well in that case.
+    pub allowed_signing_algorithms: Option<Vec<SigningAlgorithmId>>,
+
+    /// How incoming messages with an invalid signature are handled.
+    /// See [`SignatureFailurePolicy`] for more information, and **read the security notes there before changing it.**
+    pub signature_failure_policy: SignatureFailurePolicy,
+
+    /// The length, in bytes, of the random salt sent in the preauth integrity negotiate context (SMB 3.1.1 only).
+    /// If unset, defaults to [`ConnectionConfig::DEFAULT_PREAUTH_SALT_LENGTH`].
+    pub preauth_salt_length: Option<u16>,
+
     /// Specifies the transport protocol to be used for the connection.
     pub transport: TransportConfig,
 
+    /// The local address to bind TCP connections to, to select the network interface that connects to the server.
+    /// If unset, the operating system selects the local address.
+    ///
+    /// Applies to the TCP and NetBIOS transports. Only server addresses of the same family (IPv4 or IPv6)
+    /// as the bind address are connected to.
+    pub bind_address: Option<IpAddr>,
+
+    /// The delay before connecting to the next address of a server that has multiple addresses
+    /// (e.g. both IPv6 and IPv4 addresses), while connecting to the previous ones is still in progress,
+    /// as in the Happy Eyeballs algorithm (RFC 8305).
+    /// If unset, defaults to [`ConnectionConfig::DEFAULT_HAPPY_EYEBALLS_DELAY`].
+    ///
+    /// Applies to the TCP and NetBIOS transports. See also [`Connection::build_with_addresses`][crate::Connection::build_with_addresses].
+    pub happy_eyeballs_delay: Option<Duration>,
+
+    /// Whether to connect using the NetBIOS transport (port 139), when the server refuses the TCP connection
+    /// to port 445. Old devices may only listen on port 139.
+    ///
+    /// Applies only to the TCP transport, when no [`port`][Self::port] is specified.
+    /// If set, servers whose names DNS can't resolve are also looked up using NetBIOS name queries,
+    /// broadcast on the local network. Requires the `netbios-transport` feature.
+    pub netbios_fallback: bool,
+
     /// Configures valid authentication methods (SSPs) for the connection.
     /// See [`AuthMethodsConfig`] for more information.
     pub auth_methods: AuthMethodsConfig,
 
+    /// An opt-in hook, invoked for each security token exchanged during authentication.
+    /// Use [`AuthTokenHook::log`] to log the tokens instead of providing a callback.
+    ///
+    /// **The tokens contain sensitive material.** See [`crate::session::auth_tokens`] for more information.
+    pub auth_token_hook: Option<AuthTokenHook>,
+
+    /// An opt-in hook, notified of the latency of each request, the file data transferred and the credits of the connection.
+    /// Use [`MetricsHook::aggregate`] to read the counters of the connection with [`Connection::stats`][crate::Connection::stats].
+    ///
+    /// See [`crate::connection::metrics`] for more information.
+    pub metrics: Option<MetricsHook>,
+
+    /// Forces server quirks on or off. By default, quirks are detected automatically.
+    /// See [`crate::connection::quirks`] for more information.
+    pub quirks: ServerQuirksOverrides,
+
     /// The number of SMB2 credits to request for the connection.
-    /// If not configured, uses a default value.
+    /// If not configured, uses [`DEFAULT_CREDITS_BACKLOG`][Self::DEFAULT_CREDITS_BACKLOG].
     ///
     /// The higher number of credits, the more concurrent requests can be sent on the connection.
     /// However, some servers may not issue such high number of credits.
@@ -152,12 +375,39 @@ pub struct ConnectionConfig {
     /// parameter in the `Set-SmbServerConfiguration` PowerShell cmdlet, but from the client's side.
     pub credits_backlog: Option<u16>,
 
+    /// The number of read or write requests that [`File::read_all_at`][crate::File::read_all_at]
+    /// and [`File::write_all_at`][crate::File::write_all_at] keep in flight.
+    /// If not configured, uses [`DEFAULT_IO_PIPELINE_DEPTH`][Self::DEFAULT_IO_PIPELINE_DEPTH].
+    ///
+    /// Requests are only pipelined on connections that support multi-credit requests, and in builds
+    /// with the `async` or `multi_threaded` features. The requests in flight also share the credits of the connection
+    /// (see [`credits_backlog`][Self::credits_backlog]) with any other request.
+    pub io_pipeline_depth: Option<u16>,
+
     /// The default size, in bytes, of the buffer that can be used for
     /// [`ResourceHandle::query_info`][crate::ResourceHandle::query_info], [`ResourceHandle::query_fs_info`][crate::ResourceHandle::query_fs_info],
     /// [`ResourceHandle::query_security_info`][crate::ResourceHandle::query_security_info], [`Directory::query_quota_info`][crate::Directory::query_quota_info],
     /// their respective `set_*_info` counterparts (such as [`ResourceHandle::set_info`][crate::ResourceHandle::set_info]),
     /// [`Directory::query`][crate::Directory::query] and [`Directory::watch`][crate::Directory::watch] operations.
     pub default_transaction_size: Option<u32>,
+
+    /// The number of times the remainder of a short write is retried, before failing with [`Error::ShortWrite`][crate::Error::ShortWrite].
+    /// If not configured, uses [`DEFAULT_SHORT_WRITE_RETRIES`][Self::DEFAULT_SHORT_WRITE_RETRIES].
+    ///
+    /// Servers may write less data than requested, for example when the disk or the quota is almost full.
+    pub short_write_retries: Option<u32>,
+
+    /// The number of messages that may be encrypted with the keys of a single session.
+    /// If not configured, uses [`DEFAULT_ENCRYPTION_NONCE_LIMIT`][Self::DEFAULT_ENCRYPTION_NONCE_LIMIT].
+    ///
+    /// Encryption nonces are derived from a per-session counter, and a nonce must never be reused with the same key.
+    /// Once the limit is reached, encrypting further messages fails with [`Error::NonceExhaustion`][crate::Error::NonceExhaustion],
+    /// and a new session must be set up. See [`Session::stats`][crate::Session::stats].
+    pub encryption_nonce_limit: Option<u64>,
+
+    /// How buffer sizes that exceed the negotiated maximum transact, read or write sizes are handled.
+    /// See [`BufferSizePolicy`].
+    pub buffer_size_policy: BufferSizePolicy,
 }
 
 impl ConnectionConfig {
@@ -183,6 +433,76 @@ impl ConnectionConfig {
             }
         }
 
+        // A relaxed protection policy contradicts required encryption.
+        if !self.protection_policy.is_default() && self.encryption_mode.is_required() {
+            return Err(crate::Error::InvalidConfiguration(
+                "Encryption is required, but the protection policy does not allow encrypting all messages".to_string(),
+            ));
+        }
+
+        if self.keepalive_interval == Some(Duration::ZERO) {
+            return Err(crate::Error::InvalidConfiguration(
+                "Keepalive interval must be greater than zero".to_string(),
+            ));
+        }
+
+        if self.io_pipeline_depth == Some(0) {
+            return Err(crate::Error::InvalidConfiguration(
+                "I/O pipeline depth must be greater than zero".to_string(),
+            ));
+        }
+
+        if self.encryption_nonce_limit == Some(0) {
+            return Err(crate::Error::InvalidConfiguration(
+                "Encryption nonce limit must be greater than zero".to_string(),
+            ));
+        }
+
+        let preauth_hash_algorithms = self.preauth_hash_algorithms();
+        if preauth_hash_algorithms.is_empty() {
+            return Err(crate::Error::InvalidConfiguration(
+                "At least one preauth integrity algorithm must be offered".to_string(),
+            ));
+        }
+        if let Some(algo) = preauth_hash_algorithms
+            .iter()
+            .find(|algo| !preauth_hash::SUPPORTED_ALGOS.contains(algo))
+        {
+            return Err(crate::Error::InvalidConfiguration(format!(
+                "Preauth integrity algorithm {algo:?} is not supported"
+            )));
+        }
+
+        let signing_algorithms = self.allowed_signing_algorithms();
+        if signing_algorithms.is_empty() {
+            return Err(crate::Error::InvalidConfiguration(
+                "At least one signing algorithm must be offered".to_string(),
+            ));
+        }
+        if let Some(algo) = signing_algorithms
+            .iter()
+            .find(|algo| !crate::crypto::SIGNING_ALGOS.contains(algo))
+        {
+            return Err(crate::Error::InvalidConfiguration(format!(
+                "Signing algorithm {algo:?} is not supported"
+            )));
+        }
+
+        let compression_algorithms = self.compression_algorithms();
+        if compression_algorithms.is_empty() {
+            return Err(crate::Error::InvalidConfiguration(
+                "At least one compression algorithm must be offered".to_string(),
+            ));
+        }
+        if let Some(algo) = compression_algorithms
+            .iter()
+            .find(|algo| !crate::compression::SUPPORTED_ALGORITHMS.contains(algo))
+        {
+            return Err(crate::Error::InvalidConfiguration(format!(
+                "Compression algorithm {algo} is not supported"
+            )));
+        }
+
         if let Some(default_transaction_size) = self.default_transaction_size {
             if default_transaction_size == 0 {
                 return Err(crate::Error::InvalidConfiguration(
@@ -198,6 +518,90 @@ impl ConnectionConfig {
         self.timeout.unwrap_or(Self::DEFAULT_TIMEOUT)
     }
 
+    /// Returns the effective timeout of requests, which is [`timeout()`][Self::timeout()] if
+    /// [`request_timeout`][`Self::request_timeout`] is not set.
+    pub fn request_timeout(&self) -> Duration {
+        self.request_timeout.unwrap_or_else(|| self.timeout())
+    }
+
+    pub const DEFAULT_HAPPY_EYEBALLS_DELAY: Duration = TcpConnectOptions::DEFAULT_ATTEMPT_DELAY;
+
+    /// Returns the effective delay to be used if [`happy_eyeballs_delay`][`Self::happy_eyeballs_delay`] is not set.
+    pub fn happy_eyeballs_delay(&self) -> Duration {
+        self.happy_eyeballs_delay
+            .unwrap_or(Self::DEFAULT_HAPPY_EYEBALLS_DELAY)
+    }
+
+    /// Returns the options of TCP connections, according to the configuration.
+    pub(crate) fn tcp_options(&self) -> TcpConnectOptions {
+        TcpConnectOptions {
+            bind_address: self.bind_address,
+            attempt_delay: self.happy_eyeballs_delay(),
+        }
+    }
+
+    pub const DEFAULT_SESSION_SETUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Returns the effective timeout to be used if [`session_setup_timeout`][`Self::session_setup_timeout`] is not set.
+    pub fn session_setup_timeout(&self) -> Duration {
+        self.session_setup_timeout
+            .unwrap_or(Self::DEFAULT_SESSION_SETUP_TIMEOUT)
+    }
+
+    pub const DEFAULT_TREE_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Returns the effective timeout to be used if [`tree_connect_timeout`][`Self::tree_connect_timeout`] is not set.
+    pub fn tree_connect_timeout(&self) -> Duration {
+        self.tree_connect_timeout
+            .unwrap_or(Self::DEFAULT_TREE_CONNECT_TIMEOUT)
+    }
+
+    pub const DEFAULT_PREAUTH_HASH_ALGORITHMS: &[HashAlgorithm] = preauth_hash::SUPPORTED_ALGOS;
+
+    /// Returns the effective algorithms to be offered if [`preauth_hash_algorithms`][`Self::preauth_hash_algorithms`] is not set.
+    pub fn preauth_hash_algorithms(&self) -> &[HashAlgorithm] {
+        self.preauth_hash_algorithms
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_PREAUTH_HASH_ALGORITHMS)
+    }
+
+    /// All the signing algorithms supported by the crate, according to the enabled features.
+    pub const DEFAULT_SIGNING_ALGORITHMS: &[SigningAlgorithmId] = crate::crypto::SIGNING_ALGOS;
+
+    /// Returns the effective algorithms to be offered if [`allowed_signing_algorithms`][`Self::allowed_signing_algorithms`] is not set.
+    pub fn allowed_signing_algorithms(&self) -> &[SigningAlgorithmId] {
+        self.allowed_signing_algorithms
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_SIGNING_ALGORITHMS)
+    }
+
+    pub const DEFAULT_COMPRESSION_THRESHOLD: u32 = 1024;
+
+    /// Returns the effective threshold to be used if [`compression_threshold`][`Self::compression_threshold`] is not set.
+    pub fn compression_threshold(&self) -> u32 {
+        self.compression_threshold
+            .unwrap_or(Self::DEFAULT_COMPRESSION_THRESHOLD)
+    }
+
+    /// All the compression algorithms supported by the crate, according to the enabled features.
+    pub const DEFAULT_COMPRESSION_ALGORITHMS: &[CompressionAlgorithm] =
+        crate::compression::SUPPORTED_ALGORITHMS;
+
+    /// Returns the effective algorithms to be offered if [`compression_algorithms`][`Self::compression_algorithms`] is not set.
+    pub fn compression_algorithms(&self) -> &[CompressionAlgorithm] {
+        self.compression_algorithms
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_COMPRESSION_ALGORITHMS)
+    }
+
+    pub const DEFAULT_PREAUTH_SALT_LENGTH: u16 = 32;
+
+    /// Returns the effective salt length to be used if [`preauth_salt_length`][`Self::preauth_salt_length`] is not set.
+    pub fn preauth_salt_length(&self) -> u16 {
+        self.preauth_salt_length
+            .unwrap_or(Self::DEFAULT_PREAUTH_SALT_LENGTH)
+    }
+
     pub const DEFAULT_TRANSACTION_SIZE: u32 = 0x10_000;
 
     /// Returns the effective value to be used if [`default_transaction_size`][`Self::default_transaction_size`] is not set.
@@ -205,4 +609,126 @@ impl ConnectionConfig {
         self.default_transaction_size
             .unwrap_or(Self::DEFAULT_TRANSACTION_SIZE)
     }
+
+    pub const DEFAULT_CREDITS_BACKLOG: u16 = 128;
+
+    /// Returns the effective value to be used if [`credits_backlog`][`Self::credits_backlog`] is not set.
+    pub fn credits_backlog(&self) -> u16 {
+        self.credits_backlog
+            .unwrap_or(Self::DEFAULT_CREDITS_BACKLOG)
+    }
+
+    pub const DEFAULT_IO_PIPELINE_DEPTH: u16 = 4;
+
+    /// Returns the effective value to be used if [`io_pipeline_depth`][`Self::io_pipeline_depth`] is not set.
+    pub fn io_pipeline_depth(&self) -> u16 {
+        self.io_pipeline_depth
+            .unwrap_or(Self::DEFAULT_IO_PIPELINE_DEPTH)
+    }
+
+    pub const DEFAULT_SHORT_WRITE_RETRIES: u32 = 3;
+
+    /// Returns the effective value to be used if [`short_write_retries`][`Self::short_write_retries`] is not set.
+    pub fn short_write_retries(&self) -> u32 {
+        self.short_write_retries
+            .unwrap_or(Self::DEFAULT_SHORT_WRITE_RETRIES)
+    }
+
+    /// The default encryption nonce limit: well below the wrap point of the 64-bit nonce counter.
+    pub const DEFAULT_ENCRYPTION_NONCE_LIMIT: u64 = 1 << 56;
+
+    /// Returns the effective value to be used if [`encryption_nonce_limit`][`Self::encryption_nonce_limit`] is not set.
+    pub fn encryption_nonce_limit(&self) -> u64 {
+        self.encryption_nonce_limit
+            .unwrap_or(Self::DEFAULT_ENCRYPTION_NONCE_LIMIT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protection_policy_commands() {
+        let policy = MessageProtectionPolicy::ControlOnly;
+        assert!(!policy.allows_signing(Command::Read));
+        assert!(!policy.allows_encryption(Command::Write));
+        assert!(policy.allows_signing(Command::Create));
+        assert!(policy.allows_encryption(Command::Ioctl));
+
+        let policy = MessageProtectionPolicy::SignAllEncryptNone;
+        assert!(policy.allows_signing(Command::Read));
+        assert!(!policy.allows_encryption(Command::Create));
+    }
+
+    #[test]
+    fn test_relaxed_protection_policy_rejects_required_encryption() {
+        let config = ConnectionConfig {
+            encryption_mode: EncryptionMode::Required,
+            protection_policy: MessageProtectionPolicy::ControlOnly,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_signing_algorithms_validation() {
+        assert_eq!(
+            ConnectionConfig::default().allowed_signing_algorithms(),
+            crate::crypto::SIGNING_ALGOS
+        );
+
+        let config = ConnectionConfig {
+            allowed_signing_algorithms: Some(vec![]),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        #[cfg(feature = "sign_cmac")]
+        {
+            let config = ConnectionConfig {
+                allowed_signing_algorithms: Some(vec![SigningAlgorithmId::AesCmac]),
+                ..Default::default()
+            };
+            assert!(config.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_preauth_hash_algorithms_validation() {
+        assert_eq!(
+            ConnectionConfig::default().preauth_hash_algorithms(),
+            &[HashAlgorithm::Sha512]
+        );
+
+        let config = ConnectionConfig {
+            preauth_hash_algorithms: Some(vec![]),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = ConnectionConfig {
+            preauth_hash_algorithms: Some(vec![
+                HashAlgorithm::Sha512,
+                HashAlgorithm::Unknown(0x02),
+            ]),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_keepalive_interval_validation() {
+        let config = ConnectionConfig {
+            keepalive_interval: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = ConnectionConfig {
+            keepalive_interval: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+    }
 }
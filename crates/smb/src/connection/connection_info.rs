@@ -1,11 +1,13 @@
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
 use crate::{connection::preauth_hash::PreauthHashState, dialects::DialectImpl};
 use binrw::prelude::*;
 use smb_dtyp::Guid;
 use smb_msg::*;
 
-use super::ConnectionConfig;
+use super::quirks::QuirksRegistry;
+use super::{BufferSizePolicy, ConnectionConfig};
 
 /// Contains important information from the negotiation process,
 /// to be used during connection operations.
@@ -17,6 +19,9 @@ pub struct NegotiatedProperties {
     /// From the server's negotiation response.
     pub caps: GlobalCapabilities,
 
+    /// From the server's negotiation response.
+    pub security_mode: NegotiateSecurityMode,
+
     /// From the server's negotiation response.
     pub max_transact_size: u32,
     /// From the server's negotiation response.
@@ -41,11 +46,59 @@ pub struct NegotiatedProperties {
     /// using negotiation context.
     pub compression: Option<CompressionCapabilities>,
 
+    /// Preauth integrity properties, for dialect 3.1.1.
+    pub preauth_integrity: Option<PreauthIntegrityInfo>,
+
     /// The selected dialect revision for the connection.
     /// Use [ConnectionInfo::dialect] to get the implementation of the selected dialect.
     pub dialect_rev: Dialect,
 }
 
+impl NegotiatedProperties {
+    /// Returns the negotiated limit of the specified kind, in bytes.
+    pub fn limit(&self, which: BufferLimit) -> u32 {
+        match which {
+            BufferLimit::Transact => self.max_transact_size,
+            BufferLimit::Read => self.max_read_size,
+            BufferLimit::Write => self.max_write_size,
+        }
+    }
+}
+
+/// The kinds of buffer size limits negotiated with the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferLimit {
+    /// [`NegotiatedProperties::max_transact_size`], which limits query, set, IOCTL and change notify buffers.
+    Transact,
+    /// [`NegotiatedProperties::max_read_size`], which limits the length of a single read.
+    Read,
+    /// [`NegotiatedProperties::max_write_size`], which limits the length of a single write.
+    Write,
+}
+
+impl std::fmt::Display for BufferLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BufferLimit::Transact => write!(f, "max transact size"),
+            BufferLimit::Read => write!(f, "max read size"),
+            BufferLimit::Write => write!(f, "max write size"),
+        }
+    }
+}
+
+/// The preauth integrity properties of a connection, as negotiated.
+///
+/// These are exposed for diagnostic purposes.
+#[derive(Debug, Clone)]
+pub struct PreauthIntegrityInfo {
+    /// The hash algorithm selected by the server.
+    pub algorithm: HashAlgorithm,
+    /// The salt sent by the client.
+    pub client_salt: Vec<u8>,
+    /// The salt sent by the server.
+    pub server_salt: Vec<u8>,
+}
+
 /// This struct is initalized once a connection is established and negotiated.
 /// It contains all the information about the connection.
 #[derive(Debug)]
@@ -54,6 +107,8 @@ pub struct ConnectionInfo {
     pub server_name: String,
     /// The server address used for the connection.
     pub server_address: std::net::SocketAddr,
+    /// The name of the transport protocol of the connection (see [`SmbTransport::name`][smb_transport::SmbTransport::name]).
+    pub transport: &'static str,
 
     /// Contains negotiated properties of the connection.
     pub negotiation: NegotiatedProperties,
@@ -65,4 +120,203 @@ pub struct ConnectionInfo {
     pub preauth_hash: PreauthHashState,
     /// The client GUID used for the connection.
     pub client_guid: Guid,
+    /// The parameters of the SMB2 negotiate request of the client, which the server confirms when the
+    /// negotiation is validated, after a tree connect (MS-SMB2 3.2.5.5).
+    /// `None` if the server selected SMB 2.0.2 in response to the multi-protocol negotiate request.
+    pub client_negotiate: Option<ValidateNegotiateInfoRequest>,
+    /// Whether the negotiation was validated. See [`ConnectionInfo::needs_negotiate_validation`].
+    pub(crate) negotiate_validated: AtomicBool,
+    /// The known quirks of the server. See [`crate::connection::quirks`] for more information.
+    pub quirks: Arc<QuirksRegistry>,
+}
+
+impl ConnectionInfo {
+    /// Validates a requested buffer size against the negotiated limit of the specified kind.
+    ///
+    /// If the requested size exceeds the limit, it is either clamped to the limit with a warning,
+    /// or rejected with [`Error::InvalidArgument`][crate::Error::InvalidArgument], according to `policy`.
+    /// Usually, `policy` is [`ConnectionConfig::buffer_size_policy`].
+    /// # Returns
+    /// The buffer size to use.
+    pub fn clamp_or_error(
+        &self,
+        requested: usize,
+        which: BufferLimit,
+        policy: BufferSizePolicy,
+    ) -> crate::Result<u32> {
+        clamp_or_error(requested, which, self.negotiation.limit(which), policy)
+    }
+}
+
+/// A snapshot of the negotiated properties of a connection, for diagnostic purposes.
+///
+/// Returned by [`Connection::negotiate_info`][crate::Connection::negotiate_info].
+/// Algorithms the server did not select explicitly are reported as the defaults of the negotiated dialect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NegotiateInfoSnapshot {
+    /// The server name used for the connection.
+    pub server_name: String,
+    /// The address of the server.
+    pub server_address: std::net::SocketAddr,
+    /// The name of the transport protocol (e.g. `"tcp"`, `"netbios"` or `"quic"`).
+    pub transport: &'static str,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serde_fmt::debug"))]
+    pub dialect: Dialect,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serde_fmt::display"))]
+    pub server_guid: Guid,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serde_fmt::display"))]
+    pub client_guid: Guid,
+    /// The capabilities of the server, from its negotiate response.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serde_fmt::capabilities"))]
+    pub server_capabilities: GlobalCapabilities,
+    /// Whether the server requires messages to be signed.
+    pub signing_required: bool,
+    /// The algorithm messages are signed with.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serde_fmt::debug"))]
+    pub signing_algorithm: SigningAlgorithmId,
+    /// The cipher messages are encrypted with, or `None` if the connection does not support encryption.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serde_fmt::debug_option"))]
+    pub encryption_cipher: Option<EncryptionCipher>,
+    /// The compression algorithms supported by both sides, or empty if compression is not in use.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serde_fmt::debug_seq"))]
+    pub compression_algorithms: Vec<CompressionAlgorithm>,
+    /// The preauth integrity hash algorithm, for dialect 3.1.1.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serde_fmt::debug_option"))]
+    pub preauth_hash_algorithm: Option<HashAlgorithm>,
+    pub max_transact_size: u32,
+    pub max_read_size: u32,
+    pub max_write_size: u32,
+}
+
+impl From<&ConnectionInfo> for NegotiateInfoSnapshot {
+    fn from(info: &ConnectionInfo) -> Self {
+        let negotiation = &info.negotiation;
+        let encryption_cipher = match negotiation.dialect_rev {
+            Dialect::Smb0311 => negotiation.encryption_cipher,
+            // Older SMB3 dialects always use AES-128-CCM, if the server supports encryption.
+            dialect if dialect.is_smb3() && negotiation.caps.encryption() => {
+                Some(EncryptionCipher::Aes128Ccm)
+            }
+            _ => None,
+        };
+        Self {
+            server_name: info.server_name.clone(),
+            server_address: info.server_address,
+            transport: info.transport,
+            dialect: negotiation.dialect_rev,
+            server_guid: negotiation.server_guid,
+            client_guid: info.client_guid,
+            server_capabilities: negotiation.caps,
+            signing_required: negotiation.security_mode.signing_required(),
+            signing_algorithm: negotiation
+                .signing_algo
+                .unwrap_or_else(|| info.dialect.default_signing_algo()),
+            encryption_cipher,
+            compression_algorithms: negotiation
+                .compression
+                .as_ref()
+                .map(|c| c.compression_algorithms.clone())
+                .unwrap_or_default(),
+            preauth_hash_algorithm: negotiation.preauth_integrity.as_ref().map(|p| p.algorithm),
+            max_transact_size: negotiation.max_transact_size,
+            max_read_size: negotiation.max_read_size,
+            max_write_size: negotiation.max_write_size,
+        }
+    }
+}
+
+/// Serializes protocol types without a serde representation, by their string forms.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_fmt {
+    use serde::Serializer;
+    use smb_msg::GlobalCapabilities;
+    use std::fmt::{Debug, Display};
+
+    pub fn display<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn debug<T: Debug, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{value:?}"))
+    }
+
+    pub fn debug_option<T: Debug, S: Serializer>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&format!("{value:?}")),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn debug_seq<T: Debug, S: Serializer>(
+        values: &[T],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|value| format!("{value:?}")))
+    }
+
+    /// Serializes the capabilities as their numeric value.
+    pub fn capabilities<S: Serializer>(
+        caps: &GlobalCapabilities,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(u32::from_le_bytes(caps.into_bytes()))
+    }
+}
+
+fn clamp_or_error(
+    requested: usize,
+    which: BufferLimit,
+    limit: u32,
+    policy: BufferSizePolicy,
+) -> crate::Result<u32> {
+    if requested <= limit as usize {
+        return Ok(requested as u32);
+    }
+    match policy {
+        BufferSizePolicy::Clamp => {
+            log::warn!("Buffer size 0x{requested:x} exceeds {which} 0x{limit:x}, clamping");
+            Ok(limit)
+        }
+        BufferSizePolicy::Error => Err(crate::Error::InvalidArgument(format!(
+            "Buffer size 0x{requested:x} exceeds {which} 0x{limit:x}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clamp_or_error() {
+        const LIMIT: u32 = 0x10000;
+        #[rustfmt::skip]
+        let cases = [
+            (0, BufferSizePolicy::Clamp, Some(0)),
+            (0x1000, BufferSizePolicy::Clamp, Some(0x1000)),
+            (0x1000, BufferSizePolicy::Error, Some(0x1000)),
+            (LIMIT as usize, BufferSizePolicy::Clamp, Some(LIMIT)),
+            (LIMIT as usize, BufferSizePolicy::Error, Some(LIMIT)),
+            (LIMIT as usize + 1, BufferSizePolicy::Clamp, Some(LIMIT)),
+            (LIMIT as usize + 1, BufferSizePolicy::Error, None),
+            (usize::MAX, BufferSizePolicy::Clamp, Some(LIMIT)),
+            (usize::MAX, BufferSizePolicy::Error, None),
+        ];
+        for which in [BufferLimit::Transact, BufferLimit::Read, BufferLimit::Write] {
+            for (requested, policy, expected) in cases {
+                let result = clamp_or_error(requested, which, LIMIT, policy);
+                match expected {
+                    Some(size) => assert_eq!(result.unwrap(), size, "{requested:x} {policy:?}"),
+                    None => assert!(
+                        matches!(result, Err(crate::Error::InvalidArgument(_))),
+                        "{requested:x} {policy:?}"
+                    ),
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,472 @@
+//! Observability hooks for the requests sent on a connection.
+//!
+//! Set [`ConnectionConfig::metrics`][crate::ConnectionConfig::metrics] to be notified of each completed request,
+//! of the file data read and written, and of the credits of the connection.
+//! [`MetricsHook::aggregate`] collects these into counters of each connection, which are returned by
+//! [`Connection::stats`][crate::Connection::stats]; [`MetricsHook::new`] forwards them to a custom [`ClientMetricsSink`].
+//!
+//! The hooks are invoked from the send and receive paths of the connection, so sinks should return quickly.
+//! When no hook is configured, requests are not timed at all.
+
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use smb_msg::{Command, Status};
+
+/// The direction of file data, see [`ClientMetricsSink::on_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDirection {
+    /// Data returned by the server, in a read response.
+    Read,
+    /// Data sent to the server, in a write request.
+    Written,
+}
+
+/// The credits of a connection, at a point in time. See [`ClientMetricsSink::on_credit_window`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CreditWindow {
+    /// The number of credits granted to the client by the server, including those in use.
+    ///
+    /// This is always 1 on connections that do not support multi-credit requests.
+    pub granted: u16,
+    /// The number of credits available for the next requests.
+    pub available: u32,
+}
+
+impl CreditWindow {
+    /// Returns the number of credits charged by requests that were not responded yet.
+    pub fn in_flight(&self) -> u32 {
+        (self.granted as u32).saturating_sub(self.available)
+    }
+}
+
+/// A caller-provided sink of connection metrics.
+///
+/// All the methods are no-ops by default, so implementations only override the ones they need.
+/// See the [module documentation][self] for more information.
+pub trait ClientMetricsSink: Send + Sync {
+    /// Invoked once the response of a request is received: `latency` is the time since the request was sent,
+    /// and `status` is the status code of the (final) response.
+    ///
+    /// Requests that are abandoned, e.g. since they time out, are not reported.
+    fn on_request(&self, _command: Command, _latency: Duration, _status: u32) {}
+
+    /// Invoked when a request is sent again, e.g. after its session expired
+    /// (see [`Session::set_auto_reauth`][crate::Session::set_auto_reauth]).
+    fn on_retry(&self, _command: Command) {}
+
+    /// Invoked for the file data of each read response and write request.
+    fn on_bytes(&self, _direction: DataDirection, _bytes: u64) {}
+
+    /// Invoked once credits are charged by an outgoing request, or granted by an incoming response.
+    fn on_credit_window(&self, _window: CreditWindow) {}
+
+    /// Returns the counters aggregated by the sink, if it aggregates any.
+    /// This is what [`Connection::stats`][crate::Connection::stats] returns.
+    fn stats(&self) -> Option<ConnectionStats> {
+        None
+    }
+}
+
+/// The counters of the requests of a single command. See [`ConnectionStats::commands`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CommandStats {
+    /// The number of responses received.
+    pub count: u64,
+    /// The number of responses with an error status.
+    pub errors: u64,
+    /// The total latency of the requests.
+    pub total_latency: Duration,
+    /// The highest latency of a single request.
+    pub max_latency: Duration,
+}
+
+impl CommandStats {
+    /// Returns the average latency of the requests, or zero if none were responded.
+    pub fn mean_latency(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => self.total_latency / count.min(u32::MAX as u64) as u32,
+        }
+    }
+}
+
+/// The counters of a connection, as aggregated by [`ConnectionMetrics`]. See [`Connection::stats`][crate::Connection::stats].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// The number of responses received.
+    pub requests: u64,
+    /// The number of responses with an error status.
+    /// `STATUS_MORE_PROCESSING_REQUIRED` is not counted as an error.
+    pub error_responses: u64,
+    /// The number of requests sent again. See [`ClientMetricsSink::on_retry`].
+    pub retries: u64,
+    /// The total size of the file data read.
+    pub bytes_read: u64,
+    /// The total size of the file data written.
+    pub bytes_written: u64,
+    /// The most recent credit window of the connection.
+    pub credits: CreditWindow,
+    /// The highest number of credits in flight at once.
+    pub max_credits_in_flight: u32,
+    /// The counters of each command that was responded.
+    pub commands: HashMap<Command, CommandStats>,
+}
+
+impl ConnectionStats {
+    /// Returns whether the status is an error status (NTSTATUS severity `STATUS_SEVERITY_ERROR`),
+    /// other than `STATUS_MORE_PROCESSING_REQUIRED`, which is expected during session setup.
+    fn is_error(status: u32) -> bool {
+        status >> 30 == 0b11 && status != Status::MoreProcessingRequired as u32
+    }
+}
+
+/// A [`ClientMetricsSink`] that aggregates the metrics into [`ConnectionStats`].
+#[derive(Debug, Default)]
+pub struct ConnectionMetrics {
+    stats: Mutex<ConnectionStats>,
+}
+
+impl ConnectionMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ConnectionStats)) {
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut stats);
+    }
+}
+
+impl ClientMetricsSink for ConnectionMetrics {
+    fn on_request(&self, command: Command, latency: Duration, status: u32) {
+        let is_error = ConnectionStats::is_error(status);
+        self.update(|stats| {
+            stats.requests += 1;
+            stats.error_responses += is_error as u64;
+            let command = stats.commands.entry(command).or_default();
+            command.count += 1;
+            command.errors += is_error as u64;
+            command.total_latency += latency;
+            command.max_latency = command.max_latency.max(latency);
+        });
+    }
+
+    fn on_retry(&self, _command: Command) {
+        self.update(|stats| stats.retries += 1);
+    }
+
+    fn on_bytes(&self, direction: DataDirection, bytes: u64) {
+        self.update(|stats| match direction {
+            DataDirection::Read => stats.bytes_read += bytes,
+            DataDirection::Written => stats.bytes_written += bytes,
+        });
+    }
+
+    fn on_credit_window(&self, window: CreditWindow) {
+        self.update(|stats| {
+            stats.credits = window;
+            stats.max_credits_in_flight = stats.max_credits_in_flight.max(window.in_flight());
+        });
+    }
+
+    fn stats(&self) -> Option<ConnectionStats> {
+        Some(self.stats.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+#[derive(Clone)]
+enum MetricsHookKind {
+    Aggregate,
+    Sink(Arc<dyn ClientMetricsSink>),
+}
+
+/// The metrics to collect for a connection. See [`ConnectionConfig::metrics`][crate::ConnectionConfig::metrics].
+#[derive(Clone)]
+pub struct MetricsHook(MetricsHookKind);
+
+impl MetricsHook {
+    /// Aggregates the metrics of each connection into its own [`ConnectionMetrics`],
+    /// so [`Connection::stats`][crate::Connection::stats] returns the counters of that connection.
+    pub fn aggregate() -> Self {
+        Self(MetricsHookKind::Aggregate)
+    }
+
+    /// Forwards the metrics to the specified sink.
+    ///
+    /// The sink is shared by all the connections created with the configuration,
+    /// e.g. all the connections of a [`Client`][crate::Client].
+    pub fn new(sink: Arc<dyn ClientMetricsSink>) -> Self {
+        Self(MetricsHookKind::Sink(sink))
+    }
+
+    /// Returns the sink of a new connection.
+    pub(crate) fn make_sink(&self) -> Arc<dyn ClientMetricsSink> {
+        match &self.0 {
+            MetricsHookKind::Aggregate => Arc::new(ConnectionMetrics::new()),
+            MetricsHookKind::Sink(sink) => sink.clone(),
+        }
+    }
+}
+
+impl std::fmt::Debug for MetricsHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            MetricsHookKind::Aggregate => f.write_str("MetricsHook::Aggregate"),
+            MetricsHookKind::Sink(_) => f.debug_tuple("MetricsHook").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl PartialEq for MetricsHook {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (MetricsHookKind::Aggregate, MetricsHookKind::Aggregate) => true,
+            (MetricsHookKind::Sink(a), MetricsHookKind::Sink(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for MetricsHook {}
+
+/// The metrics state of a connection message handler, which only exists when metrics are configured.
+pub(crate) struct ConnectionMetricsState {
+    pub sink: Arc<dyn ClientMetricsSink>,
+    /// The command and send time of each request awaiting a response, by message ID.
+    sent: Mutex<HashMap<u64, (Command, Instant)>>,
+}
+
+impl ConnectionMetricsState {
+    pub fn new(hook: &MetricsHook) -> Self {
+        Self {
+            sink: hook.make_sink(),
+            sent: Default::default(),
+        }
+    }
+
+    /// Records that a request is sent now.
+    pub fn sent(&self, msg_id: u64, command: Command) {
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(msg_id, (command, Instant::now()));
+    }
+
+    /// Reports the response of a request, if it was recorded as sent.
+    /// `status` is `None` if the response was not received, in which case the request is only forgotten.
+    pub fn received(&self, msg_id: u64, status: Option<u32>) {
+        let sent = self
+            .sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&msg_id);
+        if let (Some((command, sent_at)), Some(status)) = (sent, status) {
+            self.sink.on_request(command, sent_at.elapsed(), status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_metrics() {
+        let metrics = ConnectionMetrics::new();
+        metrics.on_request(Command::Read, Duration::from_millis(4), 0);
+        metrics.on_request(Command::Read, Duration::from_millis(2), 0xC0000034);
+        metrics.on_request(Command::Create, Duration::from_millis(1), 0x80000005);
+        metrics.on_request(Command::SessionSetup, Duration::ZERO, 0xC0000016);
+        metrics.on_bytes(DataDirection::Read, 10);
+        metrics.on_bytes(DataDirection::Written, 3);
+        metrics.on_retry(Command::Write);
+        metrics.on_credit_window(CreditWindow {
+            granted: 8,
+            available: 2,
+        });
+        metrics.on_credit_window(CreditWindow {
+            granted: 8,
+            available: 7,
+        });
+
+        let stats = metrics.stats().unwrap();
+        assert_eq!(stats.requests, 4);
+        // STATUS_BUFFER_OVERFLOW is a warning, and STATUS_MORE_PROCESSING_REQUIRED is expected.
+        assert_eq!(stats.error_responses, 1);
+        assert_eq!(stats.retries, 1);
+        assert_eq!((stats.bytes_read, stats.bytes_written), (10, 3));
+        assert_eq!(stats.credits.in_flight(), 1);
+        assert_eq!(stats.max_credits_in_flight, 6);
+        assert_eq!(
+            stats.commands[&Command::Read],
+            CommandStats {
+                count: 2,
+                errors: 1,
+                total_latency: Duration::from_millis(6),
+                max_latency: Duration::from_millis(4),
+            }
+        );
+        assert_eq!(
+            stats.commands[&Command::Read].mean_latency(),
+            Duration::from_millis(3)
+        );
+        assert_eq!(stats.commands[&Command::Create].errors, 0);
+    }
+
+    #[test]
+    fn test_metrics_hook_makes_sink_per_connection() {
+        let hook = MetricsHook::aggregate();
+        let (a, b) = (hook.make_sink(), hook.make_sink());
+        a.on_retry(Command::Read);
+        assert_eq!(a.stats().unwrap().retries, 1);
+        assert_eq!(b.stats().unwrap().retries, 0);
+
+        let shared: Arc<dyn ClientMetricsSink> = Arc::new(ConnectionMetrics::new());
+        let hook = MetricsHook::new(shared.clone());
+        assert!(Arc::ptr_eq(&hook.make_sink(), &shared));
+        assert_eq!(hook, hook.clone());
+        assert_ne!(hook, MetricsHook::aggregate());
+    }
+
+    /// The steps of a file that is created, written, read and closed, after the connection is set up.
+    fn file_steps() -> Vec<smb_transport::mock::MockStep> {
+        use crate::test_util::*;
+        use smb_fscc::FileAttributes;
+        use smb_msg::{CreateResponse, FileId, ReadResponse, WriteResponse};
+        use smb_transport::mock::MockStep;
+
+        const FILE_ID: FileId = FileId {
+            persistent: 0x50,
+            volatile: 0x60,
+        };
+        vec![
+            MockStep::expect(Command::Create).respond(CreateResponse {
+                endof_file: 0x10,
+                ..create_response(FILE_ID, FileAttributes::new())
+            }),
+            MockStep::expect(Command::Write).respond(WriteResponse { count: 8 }),
+            MockStep::expect(Command::Read).respond(ReadResponse {
+                buffer: vec![0x11; 0x10],
+            }),
+            MockStep::expect(Command::Close).respond(close_response()),
+        ]
+    }
+
+    #[maybe_async::maybe_async]
+    async fn create_write_read_close(tree: &crate::Tree) {
+        use smb_fscc::FileAccessMask;
+
+        let args = crate::FileCreateArgs::make_open_existing(FileAccessMask::standard_rw());
+        let file = tree.create("file", &args).await.unwrap().unwrap_file();
+        let written = file.write_block(&[0x22; 8], 0, None).await.unwrap();
+        assert_eq!(written, 8);
+        let mut buf = [0; 0x10];
+        let read = file.read_block(&mut buf, 0, None, false).await.unwrap();
+        assert_eq!(read, 0x10);
+        file.close().await.unwrap();
+    }
+
+    /// A custom sink is notified of each request, with its status, and of the data transferred.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_sink_notified_of_requests() {
+        use crate::test_util::*;
+        use smb_msg::ShareFlags;
+
+        #[derive(Default)]
+        struct RecordingSink {
+            requests: Mutex<Vec<(Command, u32)>>,
+            bytes: Mutex<Vec<(DataDirection, u64)>>,
+            credit_windows: std::sync::atomic::AtomicUsize,
+        }
+        impl ClientMetricsSink for RecordingSink {
+            fn on_request(&self, command: Command, _latency: Duration, status: u32) {
+                self.requests.lock().unwrap().push((command, status));
+            }
+            fn on_bytes(&self, direction: DataDirection, bytes: u64) {
+                self.bytes.lock().unwrap().push((direction, bytes));
+            }
+            fn on_credit_window(&self, _window: CreditWindow) {
+                self.credit_windows
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+
+        let sink = Arc::new(RecordingSink::default());
+        let config = crate::ConnectionConfig {
+            metrics: Some(MetricsHook::new(sink.clone())),
+            ..mock_config()
+        };
+        let mock = connect_mock_tree_with_config(ShareFlags::new(), config, file_steps()).await;
+        create_write_read_close(&mock.tree).await;
+        mock.handle.assert_done();
+
+        // A sink that does not aggregate has no stats.
+        assert_eq!(mock.connection.stats(), None);
+        let requests = sink.requests.lock().unwrap().clone();
+        assert_eq!(
+            requests,
+            [
+                (Command::Negotiate, 0),
+                (Command::SessionSetup, 0xC0000016),
+                (Command::SessionSetup, 0),
+                (Command::TreeConnect, 0),
+                (Command::Create, 0),
+                (Command::Write, 0),
+                (Command::Read, 0),
+                (Command::Close, 0),
+            ]
+        );
+        let bytes = sink.bytes.lock().unwrap().clone();
+        assert_eq!(
+            bytes,
+            [(DataDirection::Written, 8), (DataDirection::Read, 0x10)]
+        );
+        // Credits are reported when sending and receiving each request.
+        let credit_windows = sink
+            .credit_windows
+            .load(std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(credit_windows, 2 * requests.len());
+    }
+
+    /// The aggregated counters of the connection are returned by [`Connection::stats`][crate::Connection::stats],
+    /// and none are collected unless configured.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_connection_stats() {
+        use crate::test_util::*;
+        use smb_msg::ShareFlags;
+
+        let config = crate::ConnectionConfig {
+            metrics: Some(MetricsHook::aggregate()),
+            ..mock_config()
+        };
+        let mock = connect_mock_tree_with_config(ShareFlags::new(), config, file_steps()).await;
+        create_write_read_close(&mock.tree).await;
+        mock.handle.assert_done();
+
+        let stats = mock.connection.stats().unwrap();
+        assert_eq!(stats.requests, 8);
+        // STATUS_MORE_PROCESSING_REQUIRED is not an error.
+        assert_eq!(stats.error_responses, 0);
+        assert_eq!((stats.bytes_read, stats.bytes_written), (0x10, 8));
+        assert_eq!(stats.commands[&Command::SessionSetup].count, 2);
+        for command in [
+            Command::Create,
+            Command::Write,
+            Command::Read,
+            Command::Close,
+        ] {
+            assert_eq!(stats.commands[&command].count, 1);
+        }
+        // All the requests were responded.
+        assert_eq!(stats.credits.in_flight(), 0);
+        assert!(stats.max_credits_in_flight >= 1);
+
+        let mock = connect_mock_tree(ShareFlags::new(), file_steps()).await;
+        create_write_read_close(&mock.tree).await;
+        assert!(mock.connection.handler.metrics.is_none());
+        assert_eq!(mock.connection.stats(), None);
+    }
+}
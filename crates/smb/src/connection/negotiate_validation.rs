@@ -0,0 +1,227 @@
+//! Validation of the negotiation of SMB 3.0 and 3.0.2 connections (MS-SMB2 3.2.5.5).
+//!
+//! Unlike SMB 3.1.1, these dialects do not protect the negotiate messages by the preauth integrity hash,
+//! so an attacker may tamper with them, e.g. to downgrade the dialect, or to disable signing.
+//! Once a signed session connects to a tree, the client sends FSCTL_VALIDATE_NEGOTIATE_INFO with the
+//! parameters of its negotiate request, and the server responds with the parameters of its negotiate response.
+//! Both messages are signed, so comparing the response with the negotiated properties detects tampering.
+
+use std::fmt::Debug;
+use std::sync::atomic::Ordering;
+
+use smb_msg::{Dialect, ValidateNegotiateInfoResponse};
+
+use super::connection_info::ConnectionInfo;
+use crate::Error;
+
+impl ConnectionInfo {
+    /// Whether the negotiation should be validated after the next tree connect of a signed session.
+    ///
+    /// This is the case for SMB 3.0 and 3.0.2 connections, until the negotiation is validated once.
+    pub(crate) fn needs_negotiate_validation(&self) -> bool {
+        matches!(
+            self.negotiation.dialect_rev,
+            Dialect::Smb030 | Dialect::Smb0302
+        ) && self.client_negotiate.is_some()
+            && !self.negotiate_validated.load(Ordering::SeqCst)
+    }
+
+    /// Marks the negotiation as validated, so that further tree connects do not validate it again.
+    pub(crate) fn set_negotiate_validated(&self) {
+        self.negotiate_validated.store(true, Ordering::SeqCst);
+    }
+
+    /// Compares the response of the server to FSCTL_VALIDATE_NEGOTIATE_INFO with the negotiated properties,
+    /// failing with [`Error::NegotiateValidationFailed`] on the first mismatch.
+    pub(crate) fn check_negotiate_validation(
+        &self,
+        response: &ValidateNegotiateInfoResponse,
+    ) -> crate::Result<()> {
+        let negotiation = &self.negotiation;
+        Self::check_validated(
+            "capabilities",
+            u32::from_le_bytes(negotiation.caps.into_bytes()),
+            response.capabilities,
+        )?;
+        Self::check_validated("server GUID", negotiation.server_guid, response.guid)?;
+        Self::check_validated(
+            "security mode",
+            negotiation.security_mode,
+            response.security_mode,
+        )?;
+        Self::check_validated("dialect", negotiation.dialect_rev, response.dialect)
+    }
+
+    fn check_validated<T: PartialEq + Debug>(
+        field: &'static str,
+        negotiated: T,
+        validated: T,
+    ) -> crate::Result<()> {
+        if negotiated != validated {
+            return Err(Error::NegotiateValidationFailed {
+                field,
+                negotiated: format!("{negotiated:?}"),
+                validated: format!("{validated:?}"),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use smb_dtyp::Guid;
+    use smb_msg::{GlobalCapabilities, NegotiateSecurityMode, ValidateNegotiateInfoRequest};
+
+    use super::*;
+    use crate::ConnectionConfig;
+    use crate::connection::connection_info::NegotiatedProperties;
+    use crate::connection::preauth_hash::PreauthHashState;
+    use crate::connection::quirks::{QuirksRegistry, ServerFingerprint};
+    use crate::dialects::DialectImpl;
+
+    fn server_guid() -> Guid {
+        Guid::from_str("a1b2c3d4-0102-0304-0506-0708090a0b0c").unwrap()
+    }
+
+    fn server_caps() -> GlobalCapabilities {
+        GlobalCapabilities::new()
+            .with_dfs(true)
+            .with_leasing(true)
+            .with_large_mtu(true)
+            .with_encryption(true)
+    }
+
+    /// The information of a connection that negotiated `dialect`.
+    fn connection_info(dialect: Dialect) -> ConnectionInfo {
+        let negotiation = NegotiatedProperties {
+            server_guid: server_guid(),
+            caps: server_caps(),
+            security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+            max_transact_size: 0x10000,
+            max_read_size: 0x10000,
+            max_write_size: 0x10000,
+            auth_buffer: vec![],
+            signing_algo: None,
+            encryption_cipher: None,
+            compression: None,
+            preauth_integrity: None,
+            dialect_rev: dialect,
+        };
+        let config = ConnectionConfig::default();
+        let quirks = QuirksRegistry::new(
+            &ServerFingerprint {
+                server_guid: negotiation.server_guid,
+                dialect,
+                auth_buffer: &negotiation.auth_buffer,
+            },
+            config.quirks,
+        );
+        ConnectionInfo {
+            server_name: "server".to_string(),
+            server_address: "127.0.0.1:445".parse().unwrap(),
+            transport: "tcp",
+            negotiation,
+            dialect: DialectImpl::new(dialect),
+            config,
+            preauth_hash: PreauthHashState::unsupported(),
+            client_guid: Guid::ZERO,
+            client_negotiate: Some(ValidateNegotiateInfoRequest {
+                capabilities: 0,
+                guid: Guid::ZERO,
+                security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+                dialects: vec![Dialect::Smb021, Dialect::Smb030, Dialect::Smb0302],
+            }),
+            negotiate_validated: Default::default(),
+            quirks: Arc::new(quirks),
+        }
+    }
+
+    /// The response of a server that confirms the negotiation of [`connection_info`].
+    fn validated_response() -> ValidateNegotiateInfoResponse {
+        ValidateNegotiateInfoResponse {
+            capabilities: u32::from_le_bytes(server_caps().into_bytes()),
+            guid: server_guid(),
+            security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+            dialect: Dialect::Smb0302,
+        }
+    }
+
+    #[test]
+    fn test_needs_negotiate_validation() {
+        for dialect in [Dialect::Smb030, Dialect::Smb0302] {
+            assert!(connection_info(dialect).needs_negotiate_validation());
+        }
+        // The negotiation of 3.1.1 is protected by the preauth integrity hash.
+        for dialect in [Dialect::Smb0202, Dialect::Smb021, Dialect::Smb0311] {
+            assert!(!connection_info(dialect).needs_negotiate_validation());
+        }
+
+        let info = connection_info(Dialect::Smb0302);
+        info.set_negotiate_validated();
+        assert!(!info.needs_negotiate_validation());
+
+        let mut info = connection_info(Dialect::Smb030);
+        info.client_negotiate = None;
+        assert!(!info.needs_negotiate_validation());
+    }
+
+    #[test]
+    fn test_negotiate_validated() {
+        let info = connection_info(Dialect::Smb0302);
+        info.check_negotiate_validation(&validated_response())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_negotiate_validation_mismatch() {
+        let info = connection_info(Dialect::Smb0302);
+        let mismatches = [
+            (
+                "dialect",
+                ValidateNegotiateInfoResponse {
+                    dialect: Dialect::Smb030,
+                    ..validated_response()
+                },
+            ),
+            (
+                "security mode",
+                ValidateNegotiateInfoResponse {
+                    security_mode: NegotiateSecurityMode::new()
+                        .with_signing_enabled(true)
+                        .with_signing_required(true),
+                    ..validated_response()
+                },
+            ),
+            (
+                "server GUID",
+                ValidateNegotiateInfoResponse {
+                    guid: Guid::ZERO,
+                    ..validated_response()
+                },
+            ),
+            (
+                "capabilities",
+                ValidateNegotiateInfoResponse {
+                    capabilities: u32::from_le_bytes(
+                        server_caps().with_encryption(false).into_bytes(),
+                    ),
+                    ..validated_response()
+                },
+            ),
+        ];
+        for (expected_field, response) in mismatches {
+            let result = info.check_negotiate_validation(&response);
+            assert!(
+                matches!(
+                    &result,
+                    Err(Error::NegotiateValidationFailed { field, .. }) if *field == expected_field
+                ),
+                "{expected_field}: {result:?}"
+            );
+        }
+    }
+}
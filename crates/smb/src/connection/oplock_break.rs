@@ -0,0 +1,249 @@
+//! Handling of oplock and lease breaks, notified by the server (MS-SMB2 3.2.5.19).
+//!
+//! Opens that hold an oplock or a lease are registered with their connection when they are created.
+//! When the server breaks one of them, the callback set by [`Connection::on_lease_break`][crate::Connection::on_lease_break]
+//! is invoked, and the break is then acknowledged with the state the server broke the open to.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+
+use maybe_async::*;
+use smb_msg::{
+    FileId, LeaseBreakAck, LeaseBreakNotify, LeaseState, OplockBreakAck, OplockBreakNotify,
+    OplockLevel, RequestContent,
+};
+
+use crate::msg_handler::MessageHandler;
+use crate::resource::ResourceMessageHandle;
+
+/// An oplock or lease break, notified by the server.
+///
+/// See [`Connection::on_lease_break`][crate::Connection::on_lease_break].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakNotification {
+    /// The oplock of an open is broken to a lower level.
+    Oplock(OplockBreakNotify),
+    /// A lease is broken to a lower state.
+    Lease(LeaseBreakNotify),
+}
+
+impl BreakNotification {
+    fn key(&self) -> BreakKey {
+        match self {
+            BreakNotification::Oplock(notify) => BreakKey::Oplock(notify.file_id),
+            BreakNotification::Lease(notify) => BreakKey::Lease(notify.lease_key.as_u128()),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+pub(crate) type BreakCallback =
+    dyn Fn(BreakNotification) -> futures_core::future::BoxFuture<'static, ()> + Send + Sync;
+#[cfg(not(feature = "async"))]
+pub(crate) type BreakCallback = dyn Fn(BreakNotification) + Send + Sync;
+
+/// Identifies a held oplock or lease, the way the server breaks it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum BreakKey {
+    Oplock(FileId),
+    Lease(u128),
+}
+
+struct HeldOplock {
+    /// The handler of the open, which acknowledges the breaks.
+    handler: Weak<ResourceMessageHandle>,
+    level: OplockLevel,
+}
+
+/// The oplocks and leases held by the opens of a connection.
+#[derive(Default)]
+pub(crate) struct OplockBreaks {
+    held: Mutex<HashMap<BreakKey, HeldOplock>>,
+    callback: RwLock<Option<Arc<BreakCallback>>>,
+}
+
+#[maybe_async(AFIT)]
+impl OplockBreaks {
+    /// Sets the callback invoked before acknowledging each break.
+    pub fn set_callback(&self, callback: Arc<BreakCallback>) -> crate::Result<()> {
+        *self.callback.write()? = Some(callback);
+        Ok(())
+    }
+
+    /// Registers an open, if it holds an oplock or a lease, so its breaks are acknowledged.
+    ///
+    /// The open is unregistered once its handler is dropped.
+    pub fn register(
+        &self,
+        file_id: FileId,
+        level: OplockLevel,
+        lease_key: Option<u128>,
+        handler: Weak<ResourceMessageHandle>,
+    ) -> crate::Result<()> {
+        let key = match (level, lease_key) {
+            (OplockLevel::None, _) => return Ok(()),
+            (OplockLevel::Lease, Some(lease_key)) => BreakKey::Lease(lease_key),
+            (OplockLevel::Lease, None) => {
+                log::warn!("Open {file_id:?} was granted a lease, without a lease context.");
+                return Ok(());
+            }
+            _ => BreakKey::Oplock(file_id),
+        };
+        let mut held = self.held.lock()?;
+        held.retain(|_, held| held.handler.strong_count() > 0);
+        held.insert(key, HeldOplock { handler, level });
+        Ok(())
+    }
+
+    /// Handles a break notified by the server: invokes the callback,
+    /// and then acknowledges the break, if required.
+    pub async fn handle(&self, notification: BreakNotification) -> crate::Result<()> {
+        let key = notification.key();
+        let held = {
+            let held = self.held.lock()?;
+            held.get(&key)
+                .and_then(|held| Some((held.handler.upgrade()?, held.level)))
+        };
+        let Some((handler, level)) = held else {
+            log::warn!("Received a break of an unknown open: {notification:?}");
+            return Ok(());
+        };
+        log::debug!("Received a break: {notification:?}");
+
+        let callback = self.callback.read()?.clone();
+        if let Some(callback) = callback {
+            #[cfg(feature = "async")]
+            callback(notification.clone()).await;
+            #[cfg(not(feature = "async"))]
+            callback(notification.clone());
+        }
+
+        let ack = Self::acknowledgement(&notification, level);
+        {
+            let mut held = self.held.lock()?;
+            match &notification {
+                BreakNotification::Oplock(notify) if notify.oplock_level == OplockLevel::None => {
+                    held.remove(&key);
+                }
+                BreakNotification::Oplock(notify) => {
+                    if let Some(held) = held.get_mut(&key) {
+                        held.level = notify.oplock_level;
+                    }
+                }
+                BreakNotification::Lease(notify) => {
+                    if notify.new_lease_state == LeaseState::new() {
+                        held.remove(&key);
+                    }
+                }
+            }
+        }
+        if let Some(ack) = ack {
+            handler.send_recv(ack).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the acknowledgement of a break of an open that held `level`, if the server expects one.
+    fn acknowledgement(
+        notification: &BreakNotification,
+        level: OplockLevel,
+    ) -> Option<RequestContent> {
+        match notification {
+            // Breaks of level II oplocks are not acknowledged.
+            BreakNotification::Oplock(_) if level == OplockLevel::II => None,
+            BreakNotification::Oplock(notify) => {
+                Some(RequestContent::OplockBreakAck(OplockBreakAck {
+                    oplock_level: notify.oplock_level,
+                    file_id: notify.file_id,
+                }))
+            }
+            BreakNotification::Lease(notify) => notify.flags.ack_required().then(|| {
+                RequestContent::LeaseBreakAck(LeaseBreakAck {
+                    lease_key: notify.lease_key,
+                    lease_state: notify.new_lease_state,
+                })
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smb_dtyp::Guid;
+    use smb_msg::{
+        FileId, LeaseBreakNotify, LeaseBreakNotifyFlags, LeaseState, OplockBreakNotify,
+        OplockLevel, RequestContent,
+    };
+
+    use super::{BreakKey, BreakNotification, OplockBreaks};
+
+    const FILE_ID: FileId = FileId {
+        persistent: 1,
+        volatile: 2,
+    };
+
+    #[test]
+    fn test_register() {
+        let breaks = OplockBreaks::default();
+        breaks
+            .register(FILE_ID, OplockLevel::None, None, Default::default())
+            .unwrap();
+        breaks
+            .register(FILE_ID, OplockLevel::Lease, None, Default::default())
+            .unwrap();
+        assert!(breaks.held.lock().unwrap().is_empty());
+
+        breaks
+            .register(FILE_ID, OplockLevel::Batch, None, Default::default())
+            .unwrap();
+        breaks
+            .register(
+                FILE_ID,
+                OplockLevel::Lease,
+                Some(0x1234),
+                Default::default(),
+            )
+            .unwrap();
+        // Opens with dropped handlers are removed once another open is registered.
+        let held = breaks.held.lock().unwrap();
+        assert_eq!(held.len(), 1);
+        assert!(held.contains_key(&BreakKey::Lease(0x1234)));
+    }
+
+    #[test]
+    fn test_oplock_break_acknowledgement() {
+        let notification = BreakNotification::Oplock(OplockBreakNotify {
+            oplock_level: OplockLevel::II,
+            file_id: FILE_ID,
+        });
+        let ack = OplockBreaks::acknowledgement(&notification, OplockLevel::Batch);
+        assert!(matches!(
+            ack,
+            Some(RequestContent::OplockBreakAck(ack))
+                if ack.oplock_level == OplockLevel::II && ack.file_id == FILE_ID
+        ));
+        assert!(OplockBreaks::acknowledgement(&notification, OplockLevel::II).is_none());
+    }
+
+    #[test]
+    fn test_lease_break_acknowledgement() {
+        let lease_key = Guid::generate();
+        let new_lease_state = LeaseState::new().with_read_caching(true);
+        let notify = |ack_required| {
+            BreakNotification::Lease(LeaseBreakNotify {
+                new_epoch: 2,
+                flags: LeaseBreakNotifyFlags::new().with_ack_required(ack_required),
+                lease_key,
+                current_lease_state: new_lease_state.with_write_caching(true),
+                new_lease_state,
+            })
+        };
+        let ack = OplockBreaks::acknowledgement(&notify(true), OplockLevel::Lease);
+        assert!(matches!(
+            ack,
+            Some(RequestContent::LeaseBreakAck(ack))
+                if ack.lease_key == lease_key && ack.lease_state == new_lease_state
+        ));
+        assert!(OplockBreaks::acknowledgement(&notify(false), OplockLevel::Lease).is_none());
+    }
+}
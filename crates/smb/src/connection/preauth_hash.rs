@@ -1,24 +1,65 @@
-use sha2::{Digest, Sha512};
+//! Preauthentication integrity hash (SMB 3.1.1).
+//!
+//! The hash algorithm is selected by the server during negotiation, from the algorithms
+//! offered by the client (see [`ConnectionConfig::preauth_hash_algorithms`][crate::ConnectionConfig::preauth_hash_algorithms]).
+//! To support a new algorithm, add its [`HashAlgorithm`] variant, and return its digest implementation
+//! from [`make_digest`].
+
+use sha2::Sha512;
+use sha2::digest::DynDigest;
 
 use smb_msg::HashAlgorithm;
 use smb_transport::IoVec;
 
-pub type PreauthHashValue = [u8; 64];
+use crate::Error;
+
+pub type PreauthHashValue = Vec<u8>;
 
+/// Preauth integrity hash algorithms supported by this crate.
 pub const SUPPORTED_ALGOS: &[HashAlgorithm] = &[HashAlgorithm::Sha512];
 
+/// Returns the digest implementation of the specified algorithm, if supported.
+fn make_digest(algorithm: HashAlgorithm) -> Option<Box<dyn DynDigest>> {
+    match algorithm {
+        HashAlgorithm::Sha512 => Some(Box::new(Sha512::default())),
+        HashAlgorithm::Unknown(_) => None,
+    }
+}
+
+/// Makes sure the algorithm selected by the server is supported, and was offered by the client.
+pub fn validate_selected_algo(
+    selected: HashAlgorithm,
+    offered: &[HashAlgorithm],
+) -> crate::Result<()> {
+    if !SUPPORTED_ALGOS.contains(&selected) || !offered.contains(&selected) {
+        return Err(Error::NegotiationError(format!(
+            "Unsupported preauth integrity algorithm selected by the server: {selected:?}"
+        )));
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub enum PreauthHashState {
     /// This state always transitions to itself, and calling `unwrap_final_hash` returns `None`.
     Unsupported,
 
-    InProgress(PreauthHashValue),
+    InProgress(HashAlgorithm, PreauthHashValue),
     Finished(PreauthHashValue),
 }
 
 impl PreauthHashState {
-    pub fn begin() -> PreauthHashState {
-        PreauthHashState::InProgress([0; 64])
+    /// Starts a new preauth hash, using the specified algorithm.
+    pub fn begin(algorithm: HashAlgorithm) -> crate::Result<PreauthHashState> {
+        let digest = make_digest(algorithm).ok_or_else(|| {
+            Error::NegotiationError(format!(
+                "Unsupported preauth integrity algorithm: {algorithm:?}"
+            ))
+        })?;
+        Ok(PreauthHashState::InProgress(
+            algorithm,
+            vec![0; digest.output_size()],
+        ))
     }
 
     pub fn unsupported() -> PreauthHashState {
@@ -27,15 +68,16 @@ impl PreauthHashState {
 
     pub fn next(self, data: &IoVec) -> PreauthHashState {
         match self {
-            PreauthHashState::InProgress(hash) => {
-                let mut hasher = Sha512::new();
-                hasher.update(hash);
+            PreauthHashState::InProgress(algorithm, hash) => {
+                let mut hasher = make_digest(algorithm)
+                    .expect("Preauth hash algorithm is validated when the hash begins");
+                hasher.update(&hash);
 
                 for data in data.iter() {
                     hasher.update(data.as_ref());
                 }
 
-                PreauthHashState::InProgress(hasher.finalize().into())
+                PreauthHashState::InProgress(algorithm, hasher.finalize().into_vec())
             }
             PreauthHashState::Unsupported => PreauthHashState::Unsupported,
             _ => panic!("Preauth hash not started/already finished."),
@@ -44,7 +86,7 @@ impl PreauthHashState {
 
     pub fn finish(self) -> PreauthHashState {
         match self {
-            PreauthHashState::InProgress(hash) => PreauthHashState::Finished(hash),
+            PreauthHashState::InProgress(_, hash) => PreauthHashState::Finished(hash),
             PreauthHashState::Unsupported => PreauthHashState::Unsupported,
             _ => panic!("Preauth hash not started"),
         }
@@ -58,3 +100,41 @@ impl PreauthHashState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use smb_msg::HashAlgorithm;
+    use smb_transport::IoVec;
+
+    use super::{PreauthHashState, validate_selected_algo};
+    use crate::Error;
+
+    #[test]
+    fn test_sha512_preauth_hash() {
+        let hash = PreauthHashState::begin(HashAlgorithm::Sha512)
+            .unwrap()
+            .next(&IoVec::from(b"abc".to_vec()))
+            .finish();
+        let hash = hash.unwrap_final_hash().unwrap();
+        // SHA-512 of 64 zero bytes followed by "abc".
+        assert_eq!(hash.len(), 64);
+        assert_eq!(
+            hash[..8],
+            [0x76, 0x82, 0xb6, 0xb8, 0x4c, 0x6f, 0x69, 0x25][..]
+        );
+    }
+
+    #[test]
+    fn test_unknown_selected_algo() {
+        let offered = [HashAlgorithm::Sha512];
+        assert!(validate_selected_algo(HashAlgorithm::Sha512, &offered).is_ok());
+        assert!(matches!(
+            validate_selected_algo(HashAlgorithm::Unknown(0x02), &offered),
+            Err(Error::NegotiationError(_))
+        ));
+        assert!(matches!(
+            PreauthHashState::begin(HashAlgorithm::Unknown(0x02)),
+            Err(Error::NegotiationError(_))
+        ));
+    }
+}
@@ -0,0 +1,300 @@
+//! Server quirks: known deviations of server implementations from the specification.
+//!
+//! The quirks of a server are first guessed from its negotiate response (see [`ServerFingerprint`]),
+//! and are then updated when the connection observes a failure pattern that matches a quirk.
+//! Each quirk may be forced on or off using [`ConnectionConfig::quirks`][crate::ConnectionConfig::quirks].
+//!
+//! Whenever a quirk changes the behavior of the client, a trace message is logged under the
+//! [`LOG_TARGET`] target.
+
+use std::fmt::Display;
+use std::sync::RwLock;
+
+use smb_dtyp::Guid;
+use smb_msg::Dialect;
+
+/// The log target used for quirk-related messages.
+pub const LOG_TARGET: &str = "smb::quirks";
+
+/// A single server quirk. See [`ServerQuirks`] for the meaning of each quirk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quirk {
+    LenientReservedFields,
+    NoQueryOnDiskId,
+    EmptySuccessEndsEnumeration,
+    NeedsDfsFlagOff,
+}
+
+impl Quirk {
+    pub const ALL: [Quirk; 4] = [
+        Quirk::LenientReservedFields,
+        Quirk::NoQueryOnDiskId,
+        Quirk::EmptySuccessEndsEnumeration,
+        Quirk::NeedsDfsFlagOff,
+    ];
+}
+
+impl Display for Quirk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Quirk::LenientReservedFields => "lenient_reserved_fields",
+            Quirk::NoQueryOnDiskId => "no_query_on_disk_id",
+            Quirk::EmptySuccessEndsEnumeration => "empty_success_ends_enumeration",
+            Quirk::NeedsDfsFlagOff => "needs_dfs_flag_off",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The set of quirks of a server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ServerQuirks {
+    /// The server sends responses with non-zero reserved fields.
+    /// Such fields are ignored when parsing plain (unencrypted and uncompressed) responses.
+    pub lenient_reserved_fields: bool,
+    /// The server fails create requests that contain the `QFid` (query on-disk ID) create context,
+    /// so it is not sent.
+    pub no_query_on_disk_id: bool,
+    /// The server ends directory enumerations by returning an empty, successful query directory response,
+    /// instead of `STATUS_NO_MORE_FILES`.
+    pub empty_success_ends_enumeration: bool,
+    /// The server fails requests to DFS shares that have the DFS operation header flag set,
+    /// so the flag is never set.
+    pub needs_dfs_flag_off: bool,
+}
+
+impl ServerQuirks {
+    /// Returns the quirks that are expected for the specified server fingerprint.
+    pub fn from_fingerprint(fingerprint: &ServerFingerprint) -> ServerQuirks {
+        let mut quirks = ServerQuirks::default();
+
+        // Servers that do not generate a GUID are usually minimal implementations,
+        // which are not strict about zeroing reserved fields.
+        if fingerprint.server_guid == Guid::ZERO {
+            log::trace!(target: LOG_TARGET, "Server GUID is zero: expecting non-zero reserved fields");
+            quirks.lenient_reserved_fields = true;
+        }
+        if fingerprint.auth_buffer.is_empty() {
+            log::trace!(target: LOG_TARGET, "Server sent no security blob hint: expecting non-zero reserved fields");
+            quirks.lenient_reserved_fields = true;
+        }
+
+        // QFid is unknown to SMB 2.0.2-only servers.
+        if fingerprint.dialect == Dialect::Smb0202 {
+            log::trace!(target: LOG_TARGET, "Dialect is SMB 2.0.2: not querying on-disk IDs");
+            quirks.no_query_on_disk_id = true;
+        }
+
+        quirks
+    }
+
+    /// Returns whether the specified quirk is set.
+    pub fn get(&self, quirk: Quirk) -> bool {
+        match quirk {
+            Quirk::LenientReservedFields => self.lenient_reserved_fields,
+            Quirk::NoQueryOnDiskId => self.no_query_on_disk_id,
+            Quirk::EmptySuccessEndsEnumeration => self.empty_success_ends_enumeration,
+            Quirk::NeedsDfsFlagOff => self.needs_dfs_flag_off,
+        }
+    }
+
+    /// Sets the specified quirk.
+    pub fn set(&mut self, quirk: Quirk, value: bool) {
+        match quirk {
+            Quirk::LenientReservedFields => self.lenient_reserved_fields = value,
+            Quirk::NoQueryOnDiskId => self.no_query_on_disk_id = value,
+            Quirk::EmptySuccessEndsEnumeration => self.empty_success_ends_enumeration = value,
+            Quirk::NeedsDfsFlagOff => self.needs_dfs_flag_off = value,
+        }
+    }
+}
+
+/// Forces server quirks on or off, regardless of the server fingerprint and of runtime detection.
+///
+/// Unset quirks are detected automatically.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ServerQuirksOverrides {
+    /// See [`ServerQuirks::lenient_reserved_fields`].
+    ///
+    /// Forcing this quirk off makes responses with non-zero reserved fields fail to parse.
+    pub lenient_reserved_fields: Option<bool>,
+    /// See [`ServerQuirks::no_query_on_disk_id`].
+    pub no_query_on_disk_id: Option<bool>,
+    /// See [`ServerQuirks::empty_success_ends_enumeration`].
+    ///
+    /// Forcing this quirk off makes an empty, successful query directory response an error.
+    pub empty_success_ends_enumeration: Option<bool>,
+    /// See [`ServerQuirks::needs_dfs_flag_off`]. This quirk is never detected automatically.
+    pub needs_dfs_flag_off: Option<bool>,
+}
+
+impl ServerQuirksOverrides {
+    /// Returns the override of the specified quirk, if any.
+    pub fn get(&self, quirk: Quirk) -> Option<bool> {
+        match quirk {
+            Quirk::LenientReservedFields => self.lenient_reserved_fields,
+            Quirk::NoQueryOnDiskId => self.no_query_on_disk_id,
+            Quirk::EmptySuccessEndsEnumeration => self.empty_success_ends_enumeration,
+            Quirk::NeedsDfsFlagOff => self.needs_dfs_flag_off,
+        }
+    }
+
+    /// Applies the overrides to the specified quirks.
+    pub fn apply(&self, mut quirks: ServerQuirks) -> ServerQuirks {
+        for quirk in Quirk::ALL {
+            if let Some(value) = self.get(quirk) {
+                quirks.set(quirk, value);
+            }
+        }
+        quirks
+    }
+}
+
+/// The properties of a server that are used to guess its quirks,
+/// taken from the negotiate response.
+#[derive(Debug, Clone, Copy)]
+pub struct ServerFingerprint<'a> {
+    pub server_guid: Guid,
+    pub dialect: Dialect,
+    /// The security blob of the negotiate response.
+    pub auth_buffer: &'a [u8],
+}
+
+/// Holds the quirks of the server of a connection.
+///
+/// The quirks are initialized from the [`ServerFingerprint`] when the connection is negotiated,
+/// and are updated when a quirk is detected at runtime. Overrides always take precedence.
+#[derive(Debug)]
+pub struct QuirksRegistry {
+    quirks: RwLock<ServerQuirks>,
+    overrides: ServerQuirksOverrides,
+}
+
+impl QuirksRegistry {
+    pub fn new(fingerprint: &ServerFingerprint, overrides: ServerQuirksOverrides) -> Self {
+        let quirks = overrides.apply(ServerQuirks::from_fingerprint(fingerprint));
+        log::debug!(target: LOG_TARGET, "Server quirks: {quirks:?}");
+        Self {
+            quirks: RwLock::new(quirks),
+            overrides,
+        }
+    }
+
+    /// Returns the current quirks of the server.
+    pub fn get(&self) -> ServerQuirks {
+        *self.quirks.read().unwrap()
+    }
+
+    /// Returns whether the specified quirk is currently set.
+    pub fn is_set(&self, quirk: Quirk) -> bool {
+        self.get().get(quirk)
+    }
+
+    /// Returns whether the specified quirk is forced on or off by the configuration.
+    pub fn is_overridden(&self, quirk: Quirk) -> bool {
+        self.overrides.get(quirk).is_some()
+    }
+
+    /// Sets a quirk that was detected at runtime, unless it is forced off by the configuration.
+    ///
+    /// Returns whether the quirk is set.
+    pub(crate) fn detected(&self, quirk: Quirk, reason: &str) -> bool {
+        if let Some(value) = self.overrides.get(quirk) {
+            return value;
+        }
+        let mut quirks = self.quirks.write().unwrap();
+        if !quirks.get(quirk) {
+            log::debug!(target: LOG_TARGET, "Detected server quirk {quirk}: {reason}");
+            quirks.set(quirk, true);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smb_dtyp::Guid;
+    use smb_msg::Dialect;
+
+    use super::{Quirk, QuirksRegistry, ServerFingerprint, ServerQuirks, ServerQuirksOverrides};
+
+    const SERVER_GUID: Guid = Guid::MAX;
+    const AUTH_BUFFER: &[u8] = &[0x60, 0x00];
+
+    #[test]
+    fn test_fingerprint_mapping() {
+        let spec_compliant = ServerFingerprint {
+            server_guid: SERVER_GUID,
+            dialect: Dialect::Smb0311,
+            auth_buffer: AUTH_BUFFER,
+        };
+        assert_eq!(
+            ServerQuirks::from_fingerprint(&spec_compliant),
+            ServerQuirks::default()
+        );
+
+        let zero_guid = ServerFingerprint {
+            server_guid: Guid::ZERO,
+            ..spec_compliant
+        };
+        assert_eq!(
+            ServerQuirks::from_fingerprint(&zero_guid),
+            ServerQuirks {
+                lenient_reserved_fields: true,
+                ..Default::default()
+            }
+        );
+
+        let no_hint = ServerFingerprint {
+            auth_buffer: &[],
+            ..spec_compliant
+        };
+        assert!(ServerQuirks::from_fingerprint(&no_hint).lenient_reserved_fields);
+
+        let smb2 = ServerFingerprint {
+            dialect: Dialect::Smb0202,
+            ..spec_compliant
+        };
+        assert_eq!(
+            ServerQuirks::from_fingerprint(&smb2),
+            ServerQuirks {
+                no_query_on_disk_id: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_overrides() {
+        let fingerprint = ServerFingerprint {
+            server_guid: Guid::ZERO,
+            dialect: Dialect::Smb0311,
+            auth_buffer: AUTH_BUFFER,
+        };
+        let registry = QuirksRegistry::new(
+            &fingerprint,
+            ServerQuirksOverrides {
+                lenient_reserved_fields: Some(false),
+                needs_dfs_flag_off: Some(true),
+                empty_success_ends_enumeration: Some(false),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            registry.get(),
+            ServerQuirks {
+                needs_dfs_flag_off: true,
+                ..Default::default()
+            }
+        );
+
+        // Forced off quirks are never detected.
+        assert!(!registry.detected(Quirk::EmptySuccessEndsEnumeration, "test"));
+        assert!(!registry.is_set(Quirk::EmptySuccessEndsEnumeration));
+
+        // Other quirks are.
+        assert!(registry.detected(Quirk::NoQueryOnDiskId, "test"));
+        assert!(registry.is_set(Quirk::NoQueryOnDiskId));
+        assert!(!registry.is_overridden(Quirk::NoQueryOnDiskId));
+    }
+}
@@ -0,0 +1,180 @@
+//! Sending hand-built messages over a connection, bypassing the typed request and response contents.
+//!
+//! This is meant for protocol research and testing servers, e.g. sending deliberately malformed messages.
+//! Raw messages still go through the same send and receive path as any other message:
+//! the connection allocates their message IDs and credits, signs or encrypts them,
+//! and matches their responses.
+//!
+//! **Unstable**: this module is only available with the `unstable-raw` crate feature,
+//! and is not covered by semver guarantees. It may change or be removed in any release.
+
+use std::time::Duration;
+
+use maybe_async::*;
+use smb_msg::{Command, Header, HeaderFlags, PlainRequest, RequestContent};
+
+use super::Connection;
+use crate::{
+    Error,
+    msg_handler::{MessageForm, MessageHandler, OutgoingMessage, ReceiveOptions},
+};
+
+/// The content of a raw request.
+#[derive(Debug)]
+pub enum RawContent {
+    /// A typed request content.
+    Typed(RequestContent),
+    /// A hand-built request body, sent as is after the header of `command`.
+    Bytes { command: Command, body: Vec<u8> },
+}
+
+impl RawContent {
+    /// Returns a hand-built request body of `command`.
+    pub fn bytes(command: Command, body: impl Into<Vec<u8>>) -> Self {
+        RawContent::Bytes {
+            command,
+            body: body.into(),
+        }
+    }
+
+    fn into_message(self) -> PlainRequest {
+        match self {
+            RawContent::Typed(content) => PlainRequest::new(content),
+            RawContent::Bytes { command, body } => PlainRequest::new_with_command(
+                RequestContent::Unknown {
+                    command: command.code(),
+                    body,
+                },
+                command,
+            ),
+        }
+    }
+}
+
+impl From<RequestContent> for RawContent {
+    fn from(content: RequestContent) -> Self {
+        RawContent::Typed(content)
+    }
+}
+
+/// Options for sending a raw message, see [`Connection::send_raw_content`].
+#[derive(Debug, Clone, Default)]
+pub struct SendOptions {
+    /// The session ID to set in the header.
+    ///
+    /// Signing and encryption use the keys of this session, so it must be set up on this connection.
+    pub session_id: u64,
+    /// The tree ID to set in the header.
+    pub tree_id: u32,
+    /// Header flags to set. The connection sets the priority, and the signed flag according to [`SendOptions::sign`].
+    pub flags: HeaderFlags,
+    /// Whether to sign the message.
+    pub sign: bool,
+    /// Whether to encrypt the message. Takes precedence over [`SendOptions::sign`].
+    pub encrypt: bool,
+    /// Whether to compress the message, if compression is negotiated.
+    pub compress: bool,
+    /// A timeout for receiving the response.
+    /// If not set, the default timeout of the connection is used.
+    pub timeout: Option<Duration>,
+}
+
+/// A raw response, see [`Connection::receive_raw_content`].
+#[derive(Debug)]
+pub struct RawResponse {
+    /// The header of the response.
+    pub header: Header,
+    /// The body of the response, following the header, as received.
+    pub body: Vec<u8>,
+    /// Whether the response was signed, encrypted and compressed.
+    pub form: MessageForm,
+}
+
+#[maybe_async(AFIT)]
+impl Connection {
+    /// Sends a raw message, without waiting for its response.
+    ///
+    /// Returns the message ID of the sent message, to receive its response with [`Connection::receive_raw_content`].
+    ///
+    /// Requests of [`RawContent::Bytes`] are charged a single credit.
+    /// Sending a cancel request this way is not supported, since it requires the message ID of the cancelled request.
+    ///
+    /// **Unstable**: not covered by semver guarantees, see the [module documentation][self].
+    pub async fn send_raw_content(
+        &self,
+        content: impl Into<RawContent>,
+        options: &SendOptions,
+    ) -> crate::Result<u64> {
+        let mut message = content.into().into_message();
+        message.header.session_id = options.session_id;
+        message.header.tree_id = Some(options.tree_id);
+        message.header.flags = options.flags.with_signed(options.sign && !options.encrypt);
+
+        let mut msg = OutgoingMessage::from_message(message).with_encrypt(options.encrypt);
+        msg.compress = options.compress;
+        Ok(self.handler.sendo(msg).await?.msg_id)
+    }
+
+    /// Receives the response of a raw message, sent with [`Connection::send_raw_content`].
+    ///
+    /// The response is returned whatever its status and command are. Interim async responses are skipped.
+    /// A response that has a valid header but fails to parse fails with [`Error::ResponseParseError`],
+    /// which also holds its body.
+    ///
+    /// **Unstable**: not covered by semver guarantees, see the [module documentation][self].
+    pub async fn receive_raw_content(
+        &self,
+        msg_id: u64,
+        options: &SendOptions,
+    ) -> crate::Result<RawResponse> {
+        if self.handler.worker().is_none() {
+            return Err(Error::InvalidState("Connection is not connected".into()));
+        }
+
+        let mut receive_options = ReceiveOptions::new()
+            .with_msg_id_filter(msg_id)
+            .with_unchecked(true);
+        receive_options.timeout = options.timeout;
+
+        let msg = self.handler.recvo(receive_options).await?;
+        let mut raw = msg.raw;
+        let body = raw.consolidate().split_off(Header::STRUCT_SIZE);
+        Ok(RawResponse {
+            header: msg.message.header,
+            body,
+            form: msg.form,
+        })
+    }
+
+    /// Sends a raw message, and receives its response.
+    /// See [`Connection::send_raw_content`] and [`Connection::receive_raw_content`].
+    ///
+    /// **Unstable**: not covered by semver guarantees, see the [module documentation][self].
+    pub async fn send_recv_raw_content(
+        &self,
+        content: impl Into<RawContent>,
+        options: &SendOptions,
+    ) -> crate::Result<RawResponse> {
+        let msg_id = self.send_raw_content(content, options).await?;
+        self.receive_raw_content(msg_id, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RawContent;
+    use smb_msg::{Command, EchoRequest, Header, RequestContent};
+
+    #[test]
+    fn test_bytes_content() {
+        let message = RawContent::bytes(Command::Echo, [4, 0, 0, 1]).into_message();
+        assert_eq!(message.header.command, Command::Echo);
+
+        let mut raw = binrw::io::Cursor::new(Vec::new());
+        binrw::BinWrite::write(&message, &mut raw).unwrap();
+        assert_eq!(raw.into_inner()[Header::STRUCT_SIZE..], [4, 0, 0, 1]);
+
+        let message = RawContent::from(RequestContent::Echo(EchoRequest::default())).into_message();
+        assert_eq!(message.header.command, Command::Echo);
+    }
+}
@@ -1,13 +1,17 @@
+use crate::Error;
 use crate::session::{SessionAndChannel, SessionInfo};
 use crate::sync_helpers::*;
 use crate::{compression::*, msg_handler::*};
 use binrw::prelude::*;
 use maybe_async::*;
 use smb_msg::*;
-use smb_transport::IoVec;
+use smb_transport::{BufferPool, IoVec, IoVecBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::{collections::HashMap, io::Cursor, sync::Arc};
 
+use super::config::SignatureFailurePolicy;
 use super::connection_info::ConnectionInfo;
+use super::quirks::{self, Quirk, QuirksRegistry};
 
 /// The [`Transformer`] structure is responsible for transforming messages to and from bytes,
 /// send over NetBios TCP connection.
@@ -21,6 +25,19 @@ pub struct Transformer {
     sessions: RwLock<HashMap<u64, Arc<RwLock<SessionAndChannel>>>>,
 
     config: RwLock<TransformerConfig>,
+
+    /// Buffers for serializing outgoing messages and receiving frames, see [`Transformer::buffer_pool`].
+    buffer_pool: BufferPool,
+
+    /// Counters of the compressed outgoing messages, see [`Transformer::compression_stats`].
+    compression_stats: CompressionCounters,
+}
+
+#[derive(Default)]
+struct CompressionCounters {
+    messages_compressed: AtomicU64,
+    original_bytes: AtomicU64,
+    compressed_bytes: AtomicU64,
 }
 
 #[derive(Default, Debug)]
@@ -28,11 +45,48 @@ struct TransformerConfig {
     /// Compressors for this connection.
     compress: Option<(Compressor, Decompressor)>,
 
+    /// Outgoing messages are only compressed if they are larger than this, in bytes.
+    compression_threshold: usize,
+
     negotiated: bool,
+
+    /// The quirks of the server, once negotiated.
+    quirks: Option<Arc<QuirksRegistry>>,
+
+    /// How incoming messages with an invalid signature are handled.
+    signature_failure_policy: SignatureFailurePolicy,
 }
 
 #[maybe_async(AFIT)]
 impl Transformer {
+    /// Creates a transformer that takes its message buffers from `buffer_pool`,
+    /// e.g. [`BufferPool::disabled`] to allocate a new buffer for every message.
+    pub fn with_buffer_pool(buffer_pool: BufferPool) -> Self {
+        Self {
+            buffer_pool,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the pool of the connection's message buffers.
+    ///
+    /// Outgoing messages are serialized into buffers taken from the pool, and the worker returns
+    /// them to the pool once they are sent. Received frames are read into buffers taken from the pool,
+    /// and frames that are only used for decoding (encrypted or compressed ones) are returned to it.
+    pub fn buffer_pool(&self) -> &BufferPool {
+        &self.buffer_pool
+    }
+
+    /// Returns the counters of the compressed outgoing messages.
+    pub fn compression_stats(&self) -> CompressionStats {
+        let stats = &self.compression_stats;
+        CompressionStats {
+            messages_compressed: stats.messages_compressed.load(Ordering::Relaxed),
+            original_bytes: stats.original_bytes.load(Ordering::Relaxed),
+            compressed_bytes: stats.compressed_bytes.load(Ordering::Relaxed),
+        }
+    }
+
     /// Notifies that the connection negotiation has been completed,
     /// with the given [`ConnectionInfo`].
     pub async fn negotiated(&self, neg_info: &ConnectionInfo) -> crate::Result<()> {
@@ -53,8 +107,11 @@ impl Transformer {
                 .as_ref()
                 .map(|c| (Compressor::new(c), Decompressor::new(c)));
             config.compress = compress;
+            config.compression_threshold = neg_info.config.compression_threshold() as usize;
         }
 
+        config.quirks = Some(neg_info.quirks.clone());
+        config.signature_failure_policy = neg_info.config.signature_failure_policy;
         config.negotiated = true;
 
         Ok(())
@@ -110,6 +167,16 @@ impl Transformer {
         Ok(())
     }
 
+    /// Returns whether a session with the specified ID is registered.
+    #[cfg(test)]
+    pub(crate) async fn has_session(&self, session_id: u64) -> bool {
+        self.sessions
+            .read()
+            .await
+            .unwrap()
+            .contains_key(&session_id)
+    }
+
     /// (Internal)
     ///
     /// Locates the current channel per the provded session ID,
@@ -158,24 +225,167 @@ impl Transformer {
     /// Transforms an outgoing message to a raw SMB message.
     pub async fn transform_outgoing(&self, mut msg: OutgoingMessage) -> crate::Result<IoVec> {
         let should_encrypt = msg.encrypt;
+        let session_id = msg.message.header.session_id;
+
+        // 1. Sign
+        let mut outgoing_data = self.encode_outgoing(&mut msg, false).await?;
+
+        // 2. Compress, before encrypting, so that the compressed message is the one that gets encrypted.
+        if msg.compress {
+            outgoing_data = self.compress_outgoing(outgoing_data).await?;
+        }
+
+        // 3. Encrypt
+        if should_encrypt {
+            self.encrypt_outgoing(
+                &mut outgoing_data,
+                session_id,
+                msg.message.header.message_id,
+            )
+            .await?;
+        }
+
+        Ok(outgoing_data)
+    }
+
+    /// (Internal)
+    ///
+    /// Compresses an outgoing message, if compression is negotiated, the message is larger than
+    /// [`ConnectionConfig::compression_threshold`][crate::ConnectionConfig::compression_threshold],
+    /// and compressing makes it smaller. Otherwise, returns the message as is.
+    async fn compress_outgoing(&self, outgoing_data: IoVec) -> crate::Result<IoVec> {
+        let rconfig = self.config.read().await?;
+        let compressor = match &rconfig.compress {
+            Some((compressor, _)) if compressor.can_compress() => compressor,
+            _ => return Ok(outgoing_data),
+        };
+        let original_size = outgoing_data.total_size();
+        if original_size <= rconfig.compression_threshold {
+            return Ok(outgoing_data);
+        }
+
+        // Each buffer (e.g. the header and the payload of a write request) is compressed as a segment, without copying it.
+        let segments = outgoing_data.iter().map(|b| &**b).collect::<Vec<&[u8]>>();
+        let compressed = compressor.compress_segments(&segments)?;
+        let compressed_size = compressed.total_size();
+        if compressed_size >= original_size {
+            log::trace!(
+                "Compressing the message does not reduce its size ({original_size} -> {compressed_size}), sending it uncompressed"
+            );
+            return Ok(outgoing_data);
+        }
+        self.buffer_pool.recycle_iovec(outgoing_data);
+
+        let mut compressed_result = IoVec::default();
+        let write_compressed = compressed_result.add_owned(self.buffer_pool.take(compressed_size));
+        compressed.write(&mut Cursor::new(write_compressed))?;
+
+        let stats = &self.compression_stats;
+        stats.messages_compressed.fetch_add(1, Ordering::Relaxed);
+        stats
+            .original_bytes
+            .fetch_add(original_size as u64, Ordering::Relaxed);
+        stats
+            .compressed_bytes
+            .fetch_add(compressed_size as u64, Ordering::Relaxed);
+        Ok(compressed_result)
+    }
+
+    /// Transforms outgoing messages to a single raw, compounded SMB message (MS-SMB2 3.2.4.1.4).
+    ///
+    /// Each message is signed on its own, and the whole compound is encrypted using the session of the first message.
+    /// Compounded messages are never compressed.
+    ///
+    /// All the messages must be either encrypted or not.
+    pub async fn transform_outgoing_compound(
+        &self,
+        msgs: Vec<OutgoingMessage>,
+    ) -> crate::Result<IoVec> {
+        let (should_encrypt, session_id, msg_id) = match msgs.first() {
+            Some(first) => (
+                first.encrypt,
+                first.message.header.session_id,
+                first.message.header.message_id,
+            ),
+            None => {
+                return Err(Error::InvalidArgument(
+                    "A compound must contain at least one message".to_string(),
+                ));
+            }
+        };
+        if msgs.iter().any(|msg| msg.encrypt != should_encrypt) {
+            return Err(Error::InvalidArgument(
+                "All the messages of a compound must be either encrypted or not".to_string(),
+            ));
+        }
+
+        let count = msgs.len();
+        // Each message has up to 3 buffers, and the compound may have an encryption header.
+        let mut outgoing_data = self.buffer_pool.take_iovec(count * 3 + 1);
+        for (i, mut msg) in msgs.into_iter().enumerate() {
+            let is_last = i + 1 == count;
+            let mut encoded = self.encode_outgoing(&mut msg, !is_last).await?;
+            outgoing_data.append_from(&mut encoded);
+            self.buffer_pool.recycle_iovec(encoded);
+        }
+
+        if should_encrypt {
+            self.encrypt_outgoing(&mut outgoing_data, session_id, msg_id)
+                .await?;
+        }
+        Ok(outgoing_data)
+    }
+
+    /// (Internal)
+    ///
+    /// Writes an outgoing message, and signs it if required.
+    ///
+    /// If `chained`, another message follows it in a compound: the message is padded to 8 bytes,
+    /// and its next command offset is set accordingly.
+    async fn encode_outgoing(
+        &self,
+        msg: &mut OutgoingMessage,
+        chained: bool,
+    ) -> crate::Result<IoVec> {
         let should_sign = msg.message.header.flags.signed();
         let session_id = msg.message.header.session_id;
 
-        let mut outgoing_data = IoVec::default();
+        // Room for the header and content, additional data, padding, and the encryption header.
+        let mut outgoing_data = self.buffer_pool.take_iovec(4);
         // Plain header + content
         {
-            let buffer = outgoing_data.add_owned(Vec::with_capacity(Header::STRUCT_SIZE));
+            let buffer = outgoing_data.add_owned(self.buffer_pool.take(Header::STRUCT_SIZE));
             msg.message.write(&mut Cursor::new(buffer))?;
         }
         // Additional data, if any
         if msg.additional_data.as_ref().is_some_and(|d| !d.is_empty()) {
-            outgoing_data.add_shared(msg.additional_data.unwrap().clone());
+            outgoing_data.add_shared(msg.additional_data.take().unwrap());
+        }
+
+        if chained {
+            const CHAINED_MESSAGE_ALIGNMENT: usize = 8;
+            let size = outgoing_data.total_size();
+            let padded_size = size.next_multiple_of(CHAINED_MESSAGE_ALIGNMENT);
+            msg.message.header.next_command = padded_size.try_into().map_err(|_| {
+                Error::InvalidArgument("Compounded message is too large".to_string())
+            })?;
+            let header_buffer = outgoing_data.get_mut(0).unwrap();
+            msg.message
+                .header
+                .write(&mut Cursor::new(&mut header_buffer[..Header::STRUCT_SIZE]))?;
+            if padded_size > size {
+                match outgoing_data.last_mut() {
+                    Some(IoVecBuf::Owned(last)) => last.resize(last.len() + padded_size - size, 0),
+                    _ => {
+                        outgoing_data.add_owned(self.buffer_pool.take_zeroed(padded_size - size));
+                    }
+                }
+            }
         }
 
-        // 1. Sign
         if should_sign {
             debug_assert!(
-                !should_encrypt,
+                !msg.encrypt,
                 "Should not sign and encrypt at the same time!"
             );
 
@@ -206,69 +416,61 @@ impl Transformer {
             );
         };
 
-        // 2. Compress
-        const COMPRESSION_THRESHOLD: usize = 1024;
-        outgoing_data = {
-            if msg.compress && outgoing_data.total_size() > COMPRESSION_THRESHOLD {
-                let rconfig = self.config.read().await?;
-                if let Some(compress) = &rconfig.compress {
-                    // Build a vector of the entire data. In the future, this may be optimized to avoid copying.
-                    // currently, there's not chained compression, and copy will occur anyway.
-                    outgoing_data.consolidate();
-                    let compressed = compress.0.compress(outgoing_data.first().unwrap())?;
-
-                    let mut compressed_result = IoVec::default();
-                    let write_compressed =
-                        compressed_result.add_owned(Vec::with_capacity(compressed.total_size()));
-                    compressed.write(&mut Cursor::new(write_compressed))?;
-                    compressed_result
-                } else {
-                    outgoing_data
-                }
-            } else {
-                outgoing_data
-            }
-        };
+        Ok(outgoing_data)
+    }
 
-        // 3. Encrypt
-        if should_encrypt {
-            let mut encryptor = self
-                ._with_session(session_id, |session| {
-                    let encryptor = session.encryptor()?.ok_or(crate::Error::TranformFailed(
-                        TransformError {
+    /// (Internal)
+    ///
+    /// Encrypts outgoing data using the encryptor of the session.
+    async fn encrypt_outgoing(
+        &self,
+        outgoing_data: &mut IoVec,
+        session_id: u64,
+        msg_id: u64,
+    ) -> crate::Result<()> {
+        let mut encryptor = self
+            ._with_session(session_id, |session| {
+                let encryptor =
+                    session
+                        .encryptor()?
+                        .ok_or(crate::Error::TranformFailed(TransformError {
                             outgoing: true,
                             phase: TransformPhase::EncryptDecrypt,
                             session_id: Some(session_id),
                             why: "Message is required to be encrypted, but no encryptor is set up!",
-                            msg_id: Some(msg.message.header.message_id),
-                        },
-                    ))?;
-                    Ok(encryptor.clone())
-                })
-                .await?;
-
-            debug_assert!(should_encrypt && !should_sign);
-
-            let encrypted_header = encryptor.encrypt_message(&mut outgoing_data, session_id)?;
+                            msg_id: Some(msg_id),
+                        }))?;
+                Ok(encryptor.clone())
+            })
+            .await?;
 
-            let write_encryption_header =
-                outgoing_data.insert_owned(0, Vec::with_capacity(EncryptedHeader::STRUCTURE_SIZE));
+        let encrypted_header = encryptor.encrypt_message(outgoing_data, session_id)?;
 
-            encrypted_header.write(&mut Cursor::new(write_encryption_header))?;
-        }
+        let write_encryption_header =
+            outgoing_data.insert_owned(0, self.buffer_pool.take(EncryptedHeader::STRUCTURE_SIZE));
 
-        Ok(outgoing_data)
+        encrypted_header.write(&mut Cursor::new(write_encryption_header))?;
+        Ok(())
     }
 
-    /// Transforms an incoming message buffer to an [`IncomingMessage`].
-    pub async fn transform_incoming(&self, data: Vec<u8>) -> crate::Result<IncomingMessage> {
-        let message = Response::try_from(data.as_ref())?;
-
+    /// Transforms an incoming message buffer to [`IncomingMessage`]s.
+    ///
+    /// A buffer holds more than a single message if the server compounded its responses:
+    /// the messages are returned in order, each with its own result.
+    /// Errors that can be associated with a message, such as [`Error::ResponseParseError`] or a failed
+    /// signature verification, fail only that message. Other errors fail the whole buffer.
+    pub async fn transform_incoming(
+        &self,
+        data: Vec<u8>,
+    ) -> crate::Result<Vec<crate::Result<IncomingMessage>>> {
         let mut form = MessageForm::default();
 
         // 3. Decrpt
-        let (message, raw) = if let Response::Encrypted(encrypted_message) = message {
-            let session_id = encrypted_message.header.session_id;
+        let raw = if data.starts_with(Self::ENCRYPTED_PROTOCOL_ID) {
+            // Only the header is parsed: the message is decrypted in the received buffer.
+            let header = EncryptedHeader::read(&mut Cursor::new(&data))
+                .map_err(|e| Error::CorruptFrame(format!("invalid encrypted header: {e}")))?;
+            let session_id = header.session_id;
 
             let mut decryptor = self
                 ._with_session(session_id, |session| {
@@ -285,18 +487,27 @@ impl Transformer {
                 })
                 .await?;
             form.encrypted = true;
-            decryptor.decrypt_message(encrypted_message)?
+            let mut data = data;
+            decryptor.decrypt_in_place(&header, &mut data)?;
+            data
         } else {
-            (message, data)
+            data
         };
 
         // 2. Decompress
-        debug_assert!(!matches!(message, Response::Encrypted(_)));
-        let (message, raw) = if let Response::Compressed(compressed_message) = message {
+        let raw = if raw.starts_with(Self::COMPRESSED_PROTOCOL_ID) {
+            let compressed_message = match self.parse_incoming(&raw).await? {
+                Response::Compressed(compressed_message) => compressed_message,
+                _ => return Err(Error::CorruptFrame("expected a compressed message".into())),
+            };
             let rconfig = self.config.read().await?;
             form.compressed = true;
             match &rconfig.compress {
-                Some(compress) => compress.1.decompress(&compressed_message)?,
+                Some(compress) => {
+                    let decompressed = compress.1.decompress_raw(&compressed_message)?;
+                    self.buffer_pool.recycle(raw);
+                    decompressed
+                }
                 None => {
                     return Err(crate::Error::TranformFailed(TransformError {
                         outgoing: false,
@@ -308,15 +519,36 @@ impl Transformer {
                 }
             }
         } else {
-            (message, raw)
+            raw
         };
 
-        let mut message = match message {
+        let chained = self.split_chained(raw)?;
+        let mut messages = Vec::with_capacity(chained.len());
+        for raw in chained {
+            match self.transform_plain_incoming(raw, form.clone()).await {
+                // The stream can't be trusted anymore, so the rest of the messages are dropped too.
+                Err(e @ Error::CorruptFrame(_)) => return Err(e),
+                result => messages.push(result),
+            }
+        }
+        Ok(messages)
+    }
+
+    /// (Internal)
+    ///
+    /// Parses a single plain incoming message, and verifies it.
+    async fn transform_plain_incoming(
+        &self,
+        raw: Vec<u8>,
+        mut form: MessageForm,
+    ) -> crate::Result<IncomingMessage> {
+        let mut message = match self.parse_incoming(&raw).await? {
             Response::Plain(message) => message,
-            _ => panic!("Unexpected message type"),
+            _ => return Err(Error::CorruptFrame("expected a plain message".into())),
         };
 
-        let iovec = IoVec::from(raw);
+        let mut iovec = self.buffer_pool.take_iovec(1);
+        iovec.add_owned(raw);
         // If fails, return TranformFailed, with message id.
         // this allows to notify the error to the task that was waiting for this message.
         match self
@@ -339,6 +571,125 @@ impl Transformer {
         Ok(IncomingMessage::new(message, iovec, form))
     }
 
+    /// (Internal)
+    ///
+    /// Splits plain, possibly compounded, message data into the data of each message.
+    /// The data of each message includes its padding, which is also covered by its signature.
+    ///
+    /// The data of the messages following the first one is copied into buffers taken from the pool.
+    fn split_chained(&self, mut raw: Vec<u8>) -> crate::Result<Vec<Vec<u8>>> {
+        const NEXT_COMMAND_OFFSET: usize = 20;
+        let mut chained = vec![];
+        let mut first_end = raw.len();
+        let mut start = 0;
+        loop {
+            let next_command = raw
+                .get(start + NEXT_COMMAND_OFFSET..start + NEXT_COMMAND_OFFSET + size_of::<u32>())
+                .map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()) as usize);
+            let end = if next_command == 0 {
+                raw.len()
+            } else if next_command < Header::STRUCT_SIZE || next_command >= raw.len() - start {
+                return Err(Error::CorruptFrame(format!(
+                    "invalid next command offset {next_command} in a message of {} bytes",
+                    raw.len() - start
+                )));
+            } else {
+                start + next_command
+            };
+
+            if start == 0 {
+                first_end = end;
+            } else {
+                let mut message = self.buffer_pool.take(end - start);
+                message.extend_from_slice(&raw[start..end]);
+                chained.push(message);
+            }
+            if next_command == 0 {
+                break;
+            }
+            start = end;
+        }
+        raw.truncate(first_end);
+        chained.insert(0, raw);
+        Ok(chained)
+    }
+
+    /// (Internal)
+    ///
+    /// Parses an incoming message, falling back to [`Self::parse_lenient`].
+    ///
+    /// If parsing fails, but the message has a valid SMB2 header, returns [`Error::ResponseParseError`]
+    /// so only the matching request fails. Otherwise, returns [`Error::CorruptFrame`].
+    async fn parse_incoming(&self, data: &[u8]) -> crate::Result<Response> {
+        let e = match Response::try_from(data) {
+            Ok(message) => return Ok(message),
+            Err(e) => e,
+        };
+        if let Some(message) = self.parse_lenient(data).await? {
+            return Ok(message);
+        }
+        Err(Self::parse_error(data, e))
+    }
+
+    /// Returns the error for a message that failed to parse with `e`.
+    fn parse_error(data: &[u8], e: binrw::Error) -> Error {
+        if !data.starts_with(Self::PLAIN_PROTOCOL_ID) {
+            return Error::CorruptFrame(format!("unexpected protocol ID: {e}"));
+        }
+        match Header::read(&mut Cursor::new(data)) {
+            Ok(header) => {
+                log::error!(
+                    "Failed to parse {} response with message ID {}: {e}",
+                    header.command,
+                    header.message_id
+                );
+                Error::ResponseParseError {
+                    msg_id: header.message_id,
+                    command: header.command,
+                    status: header.status,
+                    reason: e.to_string(),
+                    body: data[Header::STRUCT_SIZE..].to_vec(),
+                }
+            }
+            Err(header_error) => {
+                Error::CorruptFrame(format!("invalid SMB2 header: {header_error}"))
+            }
+        }
+    }
+
+    const PLAIN_PROTOCOL_ID: &[u8] = b"\xfeSMB";
+    const ENCRYPTED_PROTOCOL_ID: &[u8] = b"\xfdSMB";
+    const COMPRESSED_PROTOCOL_ID: &[u8] = b"\xfcSMB";
+
+    /// (Internal)
+    ///
+    /// Parses a plain message that failed to parse, after clearing its non-zero reserved fields.
+    /// The original message data is still used for signature verification.
+    ///
+    /// Returns `None` if the message still fails to parse, or if lenient parsing is disabled.
+    async fn parse_lenient(&self, data: &[u8]) -> crate::Result<Option<Response>> {
+        let quirks = match &self.config.read().await?.quirks {
+            Some(quirks) => quirks.clone(),
+            None => return Ok(None),
+        };
+
+        let mut data = data.to_vec();
+        if clear_reserved_fields(&mut data) == 0 {
+            return Ok(None);
+        }
+        let Ok(message) = Response::try_from(data.as_ref()) else {
+            return Ok(None);
+        };
+        if !quirks.detected(
+            Quirk::LenientReservedFields,
+            "received a message with non-zero reserved fields",
+        ) {
+            return Ok(None);
+        }
+        log::trace!(target: quirks::LOG_TARGET, "Ignoring non-zero reserved fields of incoming message");
+        Ok(Some(message))
+    }
+
     /// (Internal)
     ///
     /// A helper method to verify the incoming message.
@@ -379,7 +730,22 @@ impl Transformer {
             })
             .await?;
 
-        signer.verify_signature(&mut message.header, raw)?;
+        match signer.verify_signature(&mut message.header, raw) {
+            Err(Error::SignatureVerificationFailed)
+                if self.config.read().await?.signature_failure_policy
+                    == SignatureFailurePolicy::Warn =>
+            {
+                // Insecure: treat the message as if its signature was valid. See `SignatureFailurePolicy`.
+                log::warn!(
+                    "Message #{} has an invalid signature (signature={}), processing it anyway!",
+                    message.header.message_id,
+                    message.header.signature
+                );
+                form.signed = true;
+                return Ok(());
+            }
+            result => result?,
+        }
         log::debug!(
             "Message #{} verified (signature={}).",
             message.header.message_id,
@@ -470,3 +836,126 @@ pub enum TransformPhase {
     /// Encryption and decryption.
     EncryptDecrypt,
 }
+
+#[cfg(test)]
+mod tests {
+    use smb_msg::{Command, EchoRequest, ResponseContent};
+
+    use super::Transformer;
+    use crate::Error;
+    use crate::msg_handler::OutgoingMessage;
+
+    /// An unsolicited (message ID -1) response of command 0x7f, followed by 4 bytes of content.
+    const UNKNOWN_NOTIFICATION: &str = concat!(
+        "fe534d424000000000000000", // protocol ID, structure size, credit charge, status
+        "7f0000000100000000000000", // command, credits, flags (server to redir), next command
+        "ffffffffffffffff000000000000000000000000000000000000000000000000",
+        "0000000000000000",
+        "0800aabb",
+    );
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_transform_unknown_command() {
+        let data = smb_tests::hex_to_u8_array!(UNKNOWN_NOTIFICATION);
+        let mut messages = Transformer::default()
+            .transform_incoming(data)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        let message = messages.pop().unwrap().unwrap().message;
+        assert_eq!(message.header.command, Command::Unknown(0x7f));
+        assert!(matches!(
+            message.content,
+            ResponseContent::Unknown { command: 0x7f, body } if body == [0x08, 0x00, 0xaa, 0xbb]
+        ));
+    }
+
+    /// An ECHO response with message ID 5, and an invalid structure size of 5.
+    const CORRUPT_ECHO_RESPONSE: &str = concat!(
+        "fe534d424000000000000000",
+        "0d0001000100000000000000",
+        "0500000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000",
+        "05000000",
+    );
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_transform_corrupt_body() {
+        let data = smb_tests::hex_to_u8_array!(CORRUPT_ECHO_RESPONSE);
+        let result = Transformer::default()
+            .transform_incoming(data)
+            .await
+            .unwrap();
+        assert!(matches!(
+            &result[..],
+            [Err(Error::ResponseParseError { msg_id: 5, command: Command::Echo, body, .. })]
+                if body == &[0x05, 0x00, 0x00, 0x00]
+        ));
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_transform_corrupt_frame() {
+        let data = smb_tests::hex_to_u8_array!(CORRUPT_ECHO_RESPONSE);
+        let mut bad_protocol_id = data.clone();
+        bad_protocol_id[0] = 0xff;
+        let truncated_header = data[..0x3f].to_vec();
+        for data in [bad_protocol_id, truncated_header] {
+            let result = Transformer::default().transform_incoming(data).await;
+            assert!(matches!(result, Err(Error::CorruptFrame(_))));
+        }
+    }
+
+    /// Two compounded ECHO responses, with message IDs 1 and 2. The first one is padded to 8 bytes.
+    const CHAINED_ECHO_RESPONSES: &str = concat!(
+        "fe534d424000000000000000",
+        "0d0001000100000048000000",
+        "0100000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000",
+        "0400000000000000",
+        "fe534d424000000000000000",
+        "0d0001000100000000000000",
+        "0200000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000",
+        "04000000",
+    );
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_transform_chained() {
+        let data = smb_tests::hex_to_u8_array!(CHAINED_ECHO_RESPONSES);
+        let messages = Transformer::default()
+            .transform_incoming(data.clone())
+            .await
+            .unwrap();
+        let messages = messages.into_iter().map(|m| m.unwrap()).collect::<Vec<_>>();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].message.header.message_id, 1);
+        assert_eq!(messages[0].raw.total_size(), 0x48);
+        assert_eq!(messages[1].message.header.message_id, 2);
+        assert!(matches!(
+            messages[1].message.content,
+            ResponseContent::Echo(_)
+        ));
+
+        let mut bad_offset = data;
+        bad_offset[20] = 0x80;
+        let result = Transformer::default().transform_incoming(bad_offset).await;
+        assert!(matches!(result, Err(Error::CorruptFrame(_))));
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_transform_outgoing_compound() {
+        let msgs = (0..3)
+            .map(|_| OutgoingMessage::new(EchoRequest::default().into()))
+            .collect();
+        let mut data = Transformer::default()
+            .transform_outgoing_compound(msgs)
+            .await
+            .unwrap();
+        let data = data.consolidate();
+        // Each ECHO request is 68 bytes, padded to 72 bytes when followed by another one.
+        assert_eq!(data.len(), 0x48 * 2 + 0x44);
+        for (offset, next_command) in [(0, 0x48), (0x48, 0x48), (0x90, 0)] {
+            assert_eq!(data[offset + 20], next_command);
+        }
+    }
+}
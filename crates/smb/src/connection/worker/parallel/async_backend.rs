@@ -1,3 +1,4 @@
+use crate::connection::worker::Worker;
 use crate::msg_handler::IncomingMessage;
 use crate::{error::*, sync_helpers::*};
 use smb_transport::{IoVec, SmbTransport, SmbTransportRead, SmbTransportWrite, TransportError};
@@ -36,6 +37,11 @@ impl AsyncBackend {
                     self_ref.token.cancel();
                     break;
                 }
+                Err(e @ Error::CorruptFrame(_)) => {
+                    log::error!("Closing connection: {e}");
+                    self_ref.token.cancel();
+                    break;
+                }
                 Err(Error::ConnectionStopped) => {
                     break;
                 }
@@ -48,9 +54,7 @@ impl AsyncBackend {
         // Cleanup
         log::debug!("Cleaning up worker loop.");
         if let Ok(mut state) = worker.state.lock().await {
-            for (_, tx) in state.awaiting.drain() {
-                let _notify_result = tx.send(Err(Error::ConnectionStopped));
-            }
+            worker.fail_awaiting(&mut state);
         }
     }
 
@@ -97,7 +101,7 @@ impl AsyncBackend {
     ) -> crate::Result<()> {
         select! {
             // Receive a message from the server.
-            message_from_server = rtransport.receive() => {
+            message_from_server = rtransport.receive_pooled(worker.transformer().buffer_pool()) => {
                 worker.incoming_data_callback(message_from_server).await
             }
             // Cancel the loop.
@@ -207,10 +211,8 @@ impl MultiWorkerBackend for AsyncBackend {
     fn send_notify(
         tx: Self::AwaitingNotifier,
         msg: crate::Result<IncomingMessage>,
-    ) -> crate::Result<()> {
-        tx.send(msg).map_err(|_| {
-            Error::MessageProcessingError("Failed to send message to awaiting task.".to_string())
-        })
+    ) -> Option<crate::Result<IncomingMessage>> {
+        tx.send(msg).err()
     }
 
     fn make_send_channel_pair() -> (
@@ -36,8 +36,11 @@ pub trait MultiWorkerBackend {
         waiter: Self::AwaitingWaiter,
         timeout: Duration,
     ) -> crate::Result<IncomingMessage>;
+    /// Notifies the awaiting task with `msg`.
+    ///
+    /// If the awaiting task is gone (e.g. it timed out or was dropped), `msg` is returned back.
     fn send_notify(
         tx: Self::AwaitingNotifier,
         msg: crate::Result<IncomingMessage>,
-    ) -> crate::Result<()>;
+    ) -> Option<crate::Result<IncomingMessage>>;
 }
@@ -3,11 +3,14 @@ use crate::connection::worker::Worker;
 use crate::msg_handler::ReceiveOptions;
 use crate::sync_helpers::*;
 use maybe_async::*;
-use smb_msg::ResponseContent;
+use smb_msg::{Command, ResponseContent, Status};
 use smb_transport::{IoVec, SmbTransport, SmbTransportWrite, TransportError};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Duration;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use crate::{
     Error,
@@ -54,6 +57,7 @@ where
 /// Holds state for the worker, regarding messages to be received:
 /// - awaiting: tasks that are waiting for a specific message ID.
 /// - pending: messages that are waiting to be receive()-d.
+/// - abandoned: message IDs whose responses are no longer awaited, see [`Worker::abandon`].
 #[derive(Debug)]
 pub struct WorkerAwaitState<T>
 where
@@ -64,6 +68,8 @@ where
     pub awaiting: HashMap<u64, T::AwaitingNotifier>,
     /// Stores the pending messages, waiting to be receive()-d.
     pub pending: HashMap<u64, crate::Result<IncomingMessage>>,
+    /// Stores the IDs of abandoned requests, whose responses are yet to be received.
+    pub abandoned: HashSet<u64>,
 }
 
 impl<T> WorkerAwaitState<T>
@@ -75,6 +81,7 @@ where
         Self {
             awaiting: HashMap::new(),
             pending: HashMap::new(),
+            abandoned: HashSet::new(),
         }
     }
 }
@@ -90,6 +97,18 @@ where
         self.stopped.load(std::sync::atomic::Ordering::SeqCst)
     }
 
+    /// Marks the worker as stopped, and fails all the awaiting tasks with [`Error::ConnectionStopped`].
+    ///
+    /// This should be called by the backend once its receive loop finishes.
+    pub(crate) fn fail_awaiting(&self, state: &mut WorkerAwaitState<T>) {
+        self.stopped
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        for (_, tx) in state.awaiting.drain() {
+            let _notify_result = T::send_notify(tx, Err(Error::ConnectionStopped));
+        }
+        state.abandoned.clear();
+    }
+
     /// This is a function that should be used by multi worker implementations (async/mtd),
     /// after gettting a messages from the server, this function processes it and
     /// notifies the awaiting tasks.
@@ -98,10 +117,35 @@ where
         message: Result<Vec<u8>, TransportError>,
     ) -> crate::Result<()> {
         log::trace!("Received message from server.");
-        let message = message?;
+        let message = match message {
+            Ok(message) => message,
+            // The transport framing is broken, so the next frames can't be trusted either.
+            Err(e @ (TransportError::ParseError(_) | TransportError::InvalidMessage)) => {
+                return Err(Error::CorruptFrame(e.to_string()));
+            }
+            Err(e) => return Err(e.into()),
+        };
 
         // Tranform the message and verify it.
-        let msg = self.transformer.transform_incoming(message).await;
+        match self.transformer.transform_incoming(message).await {
+            Ok(msgs) => {
+                // Compounded responses are dispatched one by one, as if they were received separately.
+                for msg in msgs {
+                    self.dispatch_incoming(msg).await?;
+                }
+                Ok(())
+            }
+            Err(e) => self.dispatch_incoming(Err(e)).await,
+        }
+    }
+
+    /// (Internal)
+    ///
+    /// Notifies the task awaiting an incoming message, or stores it until awaited.
+    async fn dispatch_incoming(
+        self: &Arc<Self>,
+        msg: crate::Result<IncomingMessage>,
+    ) -> crate::Result<()> {
         let (msg, msg_id) = match msg {
             // Good flow, message is OK.
             Ok(msg) => {
@@ -113,6 +157,14 @@ where
                 Some(msg_id) => (Err(crate::Error::TranformFailed(e)), msg_id),
                 None => return Err(Error::TranformFailed(e)),
             },
+            // Only the body is broken: fail the matching request, and keep the connection alive.
+            Err(e @ crate::Error::ResponseParseError { msg_id, .. }) => {
+                if msg_id == u64::MAX {
+                    log::warn!("Dropping notification message that failed to parse: {e}");
+                    return Ok(());
+                }
+                (Err(e), msg_id)
+            }
             Err(e) => {
                 log::error!("Failed to transform message: {e:?}");
                 return Err(e);
@@ -126,11 +178,21 @@ where
             // no need to notify anyone else.
             let msg = msg?;
 
+            // Newer servers may send notifications this client does not know about.
+            if let ResponseContent::Unknown { command, .. } = msg.message.content {
+                log::warn!(
+                    "Dropping notification message of unknown command {}.",
+                    Command::Unknown(command)
+                );
+                return Ok(());
+            }
+
             // Server-to-client commands check.
-            // allow only oplock break and server to client notification.
+            // allow only oplock and lease breaks, and server to client notification.
             if !matches!(
                 msg.message.content,
                 ResponseContent::OplockBreakNotify(_)
+                    | ResponseContent::LeaseBreakNotify(_)
                     | ResponseContent::ServerToClientNotification(_)
             ) {
                 return Err(Error::MessageProcessingError(
@@ -152,19 +214,64 @@ where
         }
 
         // Update the state: If awaited, wake up the task. Else, store it.
-        let mut state = self.state.lock().await?;
-        let message_waiter = state.awaiting.remove(&msg_id);
-        match message_waiter {
-            Some(tx) => {
-                log::trace!("Waking up awaiting task for key {msg_id}.");
-                T::send_notify(tx, msg)?;
+        let msg = {
+            let mut state = self.state.lock().await?;
+            let message_waiter = state.awaiting.remove(&msg_id);
+            match message_waiter {
+                Some(tx) => {
+                    log::trace!("Waking up awaiting task for key {msg_id}.");
+                    match T::send_notify(tx, msg) {
+                        None => return Ok(()),
+                        // The task stopped waiting, but did not abandon the request yet.
+                        Some(msg) => msg,
+                    }
+                }
+                None if state.abandoned.remove(&msg_id) => msg,
+                None => {
+                    log::trace!("Storing message until awaited: {msg_id}.",);
+                    state.pending.insert(msg_id, msg);
+                    return Ok(());
+                }
             }
-            None => {
-                log::trace!("Storing message until awaited: {msg_id}.",);
-                state.pending.insert(msg_id, msg);
+        };
+        self.dispatch_abandoned(msg_id, msg).await
+    }
+
+    /// (Internal)
+    ///
+    /// Handles the response of an abandoned request.
+    ///
+    /// Final responses are passed to the notify channel, so the connection accounts for their credits.
+    /// Interim responses are dropped, and the final response of the request is still expected.
+    async fn dispatch_abandoned(
+        &self,
+        msg_id: u64,
+        msg: crate::Result<IncomingMessage>,
+    ) -> crate::Result<()> {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(e) => {
+                log::debug!("Dropping failed response of abandoned request {msg_id}: {e}");
+                return Ok(());
             }
+        };
+
+        let header = &msg.message.header;
+        if header.flags.async_command() && header.status == Status::Pending as u32 {
+            log::debug!("Dropping interim response of abandoned request {msg_id}.");
+            self.state.lock().await?.abandoned.insert(msg_id);
+            return Ok(());
+        }
+
+        log::debug!("Received response of abandoned request {msg_id}.");
+        match self.notify_messages_channel.get() {
+            Some(s2c_channel) => s2c_channel.send(msg).await.map_err(|_| {
+                Error::MessageProcessingError(
+                    "Failed to send abandoned response to notify channel.".to_string(),
+                )
+            }),
+            None => Ok(()),
         }
-        Ok(())
     }
 
     /// This function is used to set the notify channel for the worker.
@@ -198,6 +305,7 @@ where
             }
         };
         wtransport.send(&message).await?;
+        self.transformer.buffer_pool().recycle_iovec(message);
 
         Ok(())
     }
@@ -274,6 +382,29 @@ where
         Ok(SendMessageResult::new(id, raw_message_copy))
     }
 
+    async fn send_compound(
+        &self,
+        msgs: Vec<OutgoingMessage>,
+    ) -> crate::Result<Vec<SendMessageResult>> {
+        let ids = msgs
+            .iter()
+            .map(|msg| msg.message.header.message_id)
+            .collect::<Vec<_>>();
+        let message = { self.transformer.transform_outgoing_compound(msgs).await? };
+
+        log::trace!("Compound with IDs {ids:?} is passed to the worker for sending",);
+
+        let message = T::wrap_msg_to_send(message);
+        self.sender.send(message).await.map_err(|_| {
+            Error::MessageProcessingError("Failed to send message to worker!".to_string())
+        })?;
+
+        Ok(ids
+            .into_iter()
+            .map(|id| SendMessageResult::new(id, None))
+            .collect())
+    }
+
     async fn receive_next(&self, options: &ReceiveOptions<'_>) -> crate::Result<IncomingMessage> {
         let wait_for_receive = {
             let mut state = self.state.lock().await?;
@@ -312,6 +443,24 @@ where
         Ok(result)
     }
 
+    async fn abandon(&self, msg_id: u64) -> crate::Result<()> {
+        let msg = {
+            let mut state = self.state.lock().await?;
+            if self.stopped() {
+                return Ok(());
+            }
+            state.awaiting.remove(&msg_id);
+            match state.pending.remove(&msg_id) {
+                Some(msg) => msg,
+                None => {
+                    state.abandoned.insert(msg_id);
+                    return Ok(());
+                }
+            }
+        };
+        self.dispatch_abandoned(msg_id, msg).await
+    }
+
     fn transformer(&self) -> &Transformer {
         &self.transformer
     }
@@ -331,3 +480,222 @@ where
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io::Cursor;
+    use std::net::SocketAddr;
+
+    use binrw::BinWrite;
+    #[cfg(feature = "async")]
+    use futures_core::future::BoxFuture;
+    #[cfg(feature = "async")]
+    use futures_util::FutureExt;
+    use smb_msg::{Header, HeaderFlags, Status};
+    use smb_transport::{BufferPool, SmbTransportRead};
+
+    use super::{
+        Arc, Command, Duration, Error, IncomingMessage, ReceiveOptions, SmbTransport,
+        SmbTransportWrite, TransportError, Worker, maybe_async,
+    };
+
+    type TestWorker = super::super::ParallelWorker;
+
+    /// A fake server, that replies with the pre-recorded frames, and discards anything sent to it.
+    struct FakeServer {
+        frames: VecDeque<Result<Vec<u8>, TransportError>>,
+    }
+
+    struct FakeServerWriter;
+
+    impl SmbTransport for FakeServer {
+        #[cfg(feature = "async")]
+        fn connect<'a>(
+            &'a mut self,
+            _server_name: &'a str,
+            _address: SocketAddr,
+        ) -> BoxFuture<'a, smb_transport::error::Result<()>> {
+            async { Ok(()) }.boxed()
+        }
+        #[cfg(not(feature = "async"))]
+        fn connect(
+            &mut self,
+            _server_name: &str,
+            _address: SocketAddr,
+        ) -> smb_transport::error::Result<()> {
+            Ok(())
+        }
+
+        fn default_port(&self) -> u16 {
+            445
+        }
+
+        fn split(
+            self: Box<Self>,
+        ) -> smb_transport::error::Result<(Box<dyn SmbTransportRead>, Box<dyn SmbTransportWrite>)>
+        {
+            Ok((self, Box::new(FakeServerWriter)))
+        }
+
+        fn remote_address(&self) -> smb_transport::error::Result<SocketAddr> {
+            Ok(SocketAddr::from(([127, 0, 0, 1], 445)))
+        }
+    }
+
+    impl SmbTransportRead for FakeServer {
+        #[cfg(feature = "async")]
+        fn receive_exact<'a>(
+            &'a mut self,
+            _out_buf: &'a mut [u8],
+        ) -> BoxFuture<'a, smb_transport::error::Result<()>> {
+            unimplemented!("frames are returned by receive_pooled")
+        }
+        #[cfg(not(feature = "async"))]
+        fn receive_exact(&mut self, _out_buf: &mut [u8]) -> smb_transport::error::Result<()> {
+            unimplemented!("frames are returned by receive_pooled")
+        }
+
+        #[cfg(feature = "async")]
+        fn receive_pooled<'a>(
+            &'a mut self,
+            _pool: &'a BufferPool,
+        ) -> BoxFuture<'a, smb_transport::error::Result<Vec<u8>>> {
+            async {
+                match self.frames.pop_front() {
+                    Some(frame) => frame,
+                    None => std::future::pending().await,
+                }
+            }
+            .boxed()
+        }
+        #[cfg(not(feature = "async"))]
+        fn receive_pooled(&mut self, _pool: &BufferPool) -> smb_transport::error::Result<Vec<u8>> {
+            match self.frames.pop_front() {
+                Some(frame) => frame,
+                None => {
+                    std::thread::sleep(Duration::from_millis(10));
+                    Err(std::io::Error::from(std::io::ErrorKind::WouldBlock).into())
+                }
+            }
+        }
+
+        #[cfg(not(feature = "async"))]
+        fn set_read_timeout(&self, _timeout: Duration) -> smb_transport::error::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Discards anything sent to the fake server.
+    macro_rules! impl_discard_writes {
+        ($($ty:ty),*) => {
+            $(
+                impl SmbTransportWrite for $ty {
+                    #[cfg(feature = "async")]
+                    fn send_raw<'a>(
+                        &'a mut self,
+                        _buf: &'a [u8],
+                    ) -> BoxFuture<'a, smb_transport::error::Result<()>> {
+                        async { Ok(()) }.boxed()
+                    }
+                    #[cfg(not(feature = "async"))]
+                    fn send_raw(&mut self, _buf: &[u8]) -> smb_transport::error::Result<()> {
+                        Ok(())
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_discard_writes!(FakeServer, FakeServerWriter);
+
+    /// Returns an ECHO response frame, with the specified structure size (4 is valid).
+    fn echo_response(msg_id: u64, structure_size: u16) -> Vec<u8> {
+        let header = Header {
+            credit_charge: 0,
+            status: Status::Success as u32,
+            command: Command::Echo,
+            credit_request: 1,
+            flags: HeaderFlags::new().with_server_to_redir(true),
+            next_command: 0,
+            message_id: msg_id,
+            tree_id: Some(0),
+            async_id: None,
+            session_id: 0,
+            signature: 0,
+        };
+        let mut data = Cursor::new(Vec::new());
+        header.write(&mut data).unwrap();
+        let mut data = data.into_inner();
+        data.extend_from_slice(&structure_size.to_le_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data
+    }
+
+    #[maybe_async]
+    async fn start_worker(
+        frames: impl IntoIterator<Item = Result<Vec<u8>, TransportError>>,
+    ) -> Arc<TestWorker> {
+        let server = FakeServer {
+            frames: frames.into_iter().collect(),
+        };
+        TestWorker::start(Box::new(server), Duration::from_secs(5))
+            .await
+            .unwrap()
+    }
+
+    #[maybe_async]
+    async fn receive(worker: &TestWorker, msg_id: u64) -> crate::Result<IncomingMessage> {
+        worker
+            .receive_next(&ReceiveOptions {
+                msg_id,
+                ..ReceiveOptions::new()
+            })
+            .await
+    }
+
+    const CORRUPT_MSG_ID: u64 = 5;
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_corrupt_body_fails_only_its_request() {
+        let frames = (1..=8).map(|msg_id| match msg_id {
+            CORRUPT_MSG_ID => Ok(echo_response(msg_id, 5)),
+            _ => Ok(echo_response(msg_id, 4)),
+        });
+        let worker = start_worker(frames).await;
+
+        for msg_id in 1..=8 {
+            let result = receive(&worker, msg_id).await;
+            if msg_id == CORRUPT_MSG_ID {
+                assert!(matches!(
+                    result,
+                    Err(Error::ResponseParseError { msg_id: CORRUPT_MSG_ID, command: Command::Echo, ref body, .. })
+                        if body == &[5, 0, 0, 0]
+                ));
+            } else {
+                assert_eq!(result.unwrap().message.header.message_id, msg_id);
+            }
+        }
+        assert!(!worker.stopped());
+        worker.stop().await.unwrap();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_corrupt_frame_stops_connection() {
+        let mut bad_protocol_id = echo_response(2, 4);
+        bad_protocol_id[0] = 0xff;
+        for corrupt in [
+            Ok(bad_protocol_id),
+            Ok(echo_response(2, 4)[..Header::STRUCT_SIZE - 1].to_vec()),
+            Err(TransportError::InvalidMessage),
+        ] {
+            let worker =
+                start_worker([Ok(echo_response(1, 4)), corrupt, Ok(echo_response(3, 4))]).await;
+            // Frames after the corrupt one are not processed, and awaiting requests fail.
+            let result = receive(&worker, 3).await;
+            assert!(matches!(result, Err(Error::ConnectionStopped)));
+            assert!(worker.stopped());
+            worker.stop().await.unwrap();
+        }
+    }
+}
@@ -4,6 +4,7 @@ use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use std::time::Duration;
 
+use crate::connection::worker::Worker;
 use crate::{Error, msg_handler::IncomingMessage};
 
 use super::{backend_trait::MultiWorkerBackend, base::ParallelWorker};
@@ -28,7 +29,7 @@ impl ThreadingBackend {
 
     fn loop_receive(&self, mut rtransport: Box<dyn SmbTransportRead>) {
         while !self.is_cancelled() {
-            let next = rtransport.receive();
+            let next = rtransport.receive_pooled(self.worker.transformer().buffer_pool());
             // Handle polling fail
             if let Err(TransportError::IoError(ref e)) = next {
                 if e.kind() == std::io::ErrorKind::WouldBlock {
@@ -43,6 +44,12 @@ impl ThreadingBackend {
                         .store(true, std::sync::atomic::Ordering::SeqCst);
                     break;
                 }
+                Err(e @ Error::CorruptFrame(_)) => {
+                    log::error!("Closing connection: {e}");
+                    self.stopped
+                        .store(true, std::sync::atomic::Ordering::SeqCst);
+                    break;
+                }
                 Err(Error::ConnectionStopped) => {
                     break;
                 }
@@ -53,9 +60,7 @@ impl ThreadingBackend {
         }
         log::debug!("Receive loop finished. Cleaning up.");
         if let Ok(mut state) = self.worker.state.lock() {
-            for (_, tx) in state.awaiting.drain() {
-                let _notify_result = tx.send(Err(Error::ConnectionStopped));
-            }
+            self.worker.fail_awaiting(&mut state);
         }
     }
 
@@ -148,8 +153,8 @@ impl MultiWorkerBackend for ThreadingBackend {
             .take()
             .ok_or(Error::ConnectionStopped)?;
 
-        // wake up the sender to stop the loop.
-        self.worker.sender.send(None).unwrap();
+        // wake up the sender to stop the loop, unless it stopped already, after the connection was closed.
+        let _ = self.worker.sender.send(None);
 
         // Join the threads.
         handles
@@ -194,10 +199,8 @@ impl MultiWorkerBackend for ThreadingBackend {
     fn send_notify(
         tx: Self::AwaitingNotifier,
         msg: crate::Result<IncomingMessage>,
-    ) -> crate::Result<()> {
-        tx.send(msg).map_err(|_| {
-            Error::MessageProcessingError("Failed to send message to awaiting task.".to_string())
-        })
+    ) -> Option<crate::Result<IncomingMessage>> {
+        tx.send(msg).err().map(|e| e.0)
     }
 
     fn make_send_channel_pair() -> (
@@ -3,9 +3,11 @@ use crate::{
     error::*,
     msg_handler::{IncomingMessage, OutgoingMessage, ReceiveOptions, SendMessageResult},
 };
+use smb_msg::{Command, ResponseContent};
 use smb_transport::{SmbTransport, TransportError};
 use std::sync::OnceLock;
 use std::{
+    collections::VecDeque,
     sync::{Arc, Mutex},
     time::Duration,
 };
@@ -20,6 +22,8 @@ pub struct SingleWorker {
     transport: Mutex<OnceLock<Box<dyn SmbTransport>>>,
     transformer: Transformer,
     timeout: Mutex<Option<Duration>>,
+    /// Messages that were received in a compound, after the one that was returned.
+    chained: Mutex<VecDeque<crate::Result<IncomingMessage>>>,
 }
 
 impl Worker for SingleWorker {
@@ -29,6 +33,7 @@ impl Worker for SingleWorker {
             transport: Mutex::new(OnceLock::from(transport)),
             transformer: Transformer::default(),
             timeout: Mutex::new(Some(timeout)),
+            chained: Default::default(),
         }))
     }
 
@@ -54,40 +59,49 @@ impl Worker for SingleWorker {
         let raw_msg = if return_raw_data {
             Some(msg_to_send)
         } else {
+            self.transformer.buffer_pool().recycle_iovec(msg_to_send);
             None
         };
 
         Ok(SendMessageResult::new(msg_id, raw_msg))
     }
 
-    fn receive_next(&self, options: &ReceiveOptions<'_>) -> crate::Result<IncomingMessage> {
-        // Receive next message
-        let mut self_mut = self.transport.lock()?;
-        let transport = self_mut.get_mut().ok_or(crate::Error::ConnectionStopped)?;
+    fn send_compound(&self, msgs: Vec<OutgoingMessage>) -> crate::Result<Vec<SendMessageResult>> {
+        let ids = msgs
+            .iter()
+            .map(|msg| msg.message.header.message_id)
+            .collect::<Vec<_>>();
 
-        if options.timeout.is_some() {
-            // TODO: implement receive with timeout in options.
-            unimplemented!("Receive with timeout is not supported in SingleWorker");
-        }
+        let msg_to_send = self.transformer.transform_outgoing_compound(msgs)?;
 
-        let msg = transport.receive().map_err(|e| match e {
-            TransportError::IoError(ioe) => {
-                if ioe.kind() == std::io::ErrorKind::WouldBlock {
-                    Error::OperationTimeout(
-                        TimedOutTask::ReceiveNextMessage,
-                        self.timeout
-                            .lock()
-                            .map(|v| v.unwrap_or(Duration::ZERO))
-                            .unwrap_or(Duration::MAX),
-                    )
-                } else {
-                    crate::Error::IoError(ioe)
-                }
+        let mut t = self.transport.lock()?;
+        t.get_mut()
+            .ok_or(crate::Error::ConnectionStopped)?
+            .send(&msg_to_send)?;
+        self.transformer.buffer_pool().recycle_iovec(msg_to_send);
+
+        Ok(ids
+            .into_iter()
+            .map(|id| SendMessageResult::new(id, None))
+            .collect())
+    }
+
+    fn receive_next(&self, options: &ReceiveOptions<'_>) -> crate::Result<IncomingMessage> {
+        let chained = self.chained.lock()?.pop_front();
+        let im = match chained {
+            Some(im) => im?,
+            None => self.receive_frame(options)?,
+        };
+        // Newer servers may send notifications this client does not know about.
+        if im.message.header.message_id == u64::MAX {
+            if let ResponseContent::Unknown { command, .. } = im.message.content {
+                log::warn!(
+                    "Dropping notification message of unknown command {}.",
+                    Command::Unknown(command)
+                );
+                return self.receive_next(options);
             }
-            _ => e.into(),
-        })?;
-        // Transform the message
-        let im = self.transformer.transform_incoming(msg)?;
+        }
         // Make sure this is our message.
         // In async clients, this is no issue, but here, we can't deal with unordered/unexpected message IDs.
         if im.message.header.message_id != options.msg_id {
@@ -104,6 +118,66 @@ impl Worker for SingleWorker {
     }
 }
 
+impl SingleWorker {
+    /// Receives the next frame from the server, returning its first message.
+    ///
+    /// If the frame holds compounded messages, the rest of them are queued, to be returned by the next receives.
+    fn receive_frame(&self, options: &ReceiveOptions<'_>) -> crate::Result<IncomingMessage> {
+        // Receive next message
+        let mut self_mut = self.transport.lock()?;
+        let transport = self_mut.get_mut().ok_or(crate::Error::ConnectionStopped)?;
+
+        let default_timeout = self
+            .timeout
+            .lock()
+            .map(|v| v.unwrap_or(Duration::ZERO))
+            .unwrap_or(Duration::MAX);
+        // Temporarily override the transport's read timeout, if requested.
+        if let Some(timeout) = options.timeout {
+            transport.set_read_timeout(timeout)?;
+        }
+        let effective_timeout = options.timeout.unwrap_or(default_timeout);
+
+        let msg = transport
+            .receive_pooled(self.transformer.buffer_pool())
+            .map_err(|e| match e {
+                TransportError::IoError(ioe) => {
+                    if ioe.kind() == std::io::ErrorKind::WouldBlock {
+                        Error::OperationTimeout(TimedOutTask::ReceiveNextMessage, effective_timeout)
+                    } else {
+                        crate::Error::IoError(ioe)
+                    }
+                }
+                TransportError::ParseError(_) | TransportError::InvalidMessage => {
+                    Error::CorruptFrame(e.to_string())
+                }
+                _ => e.into(),
+            });
+        if options.timeout.is_some() {
+            transport.set_read_timeout(default_timeout)?;
+        }
+        // Transform the message
+        let msgs = match msg.and_then(|msg| self.transformer.transform_incoming(msg)) {
+            Ok(msgs) => msgs,
+            // The stream can't be trusted anymore, so the connection is torn down.
+            Err(e @ Error::CorruptFrame(_)) => {
+                log::error!("Closing connection: {e}");
+                self_mut.take();
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
+        drop(self_mut);
+
+        let mut msgs = msgs.into_iter();
+        let first = msgs
+            .next()
+            .ok_or_else(|| Error::InvalidState("Received a frame with no messages".to_string()))?;
+        self.chained.lock()?.extend(msgs);
+        first
+    }
+}
+
 impl std::fmt::Debug for SingleWorker {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("SingleWorker")
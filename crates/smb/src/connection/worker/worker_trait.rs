@@ -30,6 +30,15 @@ pub trait Worker: Sized + std::fmt::Debug {
 
     async fn send(&self, msg: OutgoingMessage) -> crate::Result<SendMessageResult>;
 
+    /// Sends messages to the server in a single compound (MS-SMB2 3.2.4.1.4).
+    ///
+    /// The responses of the messages are received one by one, using [`Worker::receive`].
+    /// Raw data is never returned for compounded messages.
+    async fn send_compound(
+        &self,
+        msgs: Vec<OutgoingMessage>,
+    ) -> crate::Result<Vec<SendMessageResult>>;
+
     /// (Internal)
     ///
     /// This function is implemented to receive a single message from the server,
@@ -42,6 +51,17 @@ pub trait Worker: Sized + std::fmt::Debug {
     /// * The message received from the server, matching the filters.
     async fn receive_next(&self, options: &ReceiveOptions<'_>) -> crate::Result<IncomingMessage>;
 
+    /// Stops waiting for the response of the request with `msg_id`,
+    /// e.g. after its receive timed out, or its caller was dropped.
+    ///
+    /// The response, once received, is passed to the notify channel rather than stored,
+    /// so the connection still accounts for its credits.
+    ///
+    /// Default implementation does nothing, for workers that receive responses in order.
+    async fn abandon(&self, _msg_id: u64) -> crate::Result<()> {
+        Ok(())
+    }
+
     #[cfg(feature = "async")]
     async fn receive_next_cancellable(
         &self,
@@ -97,13 +117,7 @@ pub trait Worker: Sized + std::fmt::Debug {
             return Ok(curr);
         }
 
-        // Handle async.
-        if !options.allow_async {
-            return Err(Error::InvalidArgument(
-                "Async command is not allowed in this context.".to_string(),
-            ));
-        }
-
+        // Handle async: any request may go async, and the final response is awaited.
         // If not pending, that's the result, right away!
         if curr.message.header.status != Status::Pending as u32 {
             return Ok(curr);
@@ -128,8 +142,11 @@ pub trait Worker: Sized + std::fmt::Debug {
             async_msg_ids.set(options.msg_id, async_id);
         }
 
+        // Credits are granted by the interim responses, which are not returned:
+        // they are accounted for with the final response.
+        let mut granted_credits = curr.message.header.credit_request;
         loop {
-            let msg = self.receive_next_cancellable(options).await?;
+            let mut msg = self.receive_next_cancellable(options).await?;
 
             // Check if the message is async and has the same ID.
             if !msg.message.header.flags.async_command()
@@ -149,8 +166,14 @@ pub trait Worker: Sized + std::fmt::Debug {
 
             // We've got a result!
             if msg.message.header.status != Status::Pending as u32 {
+                msg.message.header.credit_request = msg
+                    .message
+                    .header
+                    .credit_request
+                    .saturating_add(granted_credits);
                 return Ok(msg);
             }
+            granted_credits = granted_credits.saturating_add(msg.message.header.credit_request);
 
             log::debug!(
                 "Received another async pending message with ID {} and status {}.",
@@ -2,7 +2,7 @@ mod encryption;
 mod kbkdf;
 mod signing;
 
-pub use encryption::{ENCRYPTING_ALGOS, EncryptingAlgo, make_encrypting_algo};
+pub use encryption::{ENCRYPTING_ALGOS, EncryptingAlgo, EncryptionResult, make_encrypting_algo};
 pub use kbkdf::{DerivedKey, KeyToDerive, kbkdf_hmacsha256};
 pub use signing::{SIGNING_ALGOS, SigningAlgo, make_signing_algo};
 
@@ -180,24 +180,12 @@ impl DialectMethods for Smb311 {
             ));
         }
 
-        let signing_algo = if let Some(signing_algo) = response.get_ctx_signing_algo() {
-            if !crypto::SIGNING_ALGOS.contains(&signing_algo) {
-                return Err(Error::NegotiationError(
-                    "Unsupported signing algorithm selected!".into(),
-                ));
-            }
-            Some(signing_algo)
-        } else {
-            None
-        };
+        let signing_algo = response.get_ctx_signing_algo();
+        validate_selected_signing_algo(signing_algo, config.allowed_signing_algorithms())?;
 
-        // Make sure preauth integrity capability is SHA-512, if it exists in response:
+        // Make sure preauth integrity algorithm is supported, if it exists in response:
         if let Some(algo) = response.get_ctx_integrity_algo() {
-            if !preauth_hash::SUPPORTED_ALGOS.contains(&algo) {
-                return Err(Error::NegotiationError(
-                    "Unsupported preauth integrity algorithm received".into(),
-                ));
-            }
+            preauth_hash::validate_selected_algo(algo, config.preauth_hash_algorithms())?;
         }
 
         // And verify that the encryption algorithm is supported.
@@ -224,6 +212,25 @@ impl DialectMethods for Smb311 {
     }
 }
 
+/// Makes sure the signing algorithm selected by the server was offered.
+///
+/// A server that does not select an algorithm implies AES-CMAC (MS-SMB2 3.2.5.2).
+fn validate_selected_signing_algo(
+    selected: Option<SigningAlgorithmId>,
+    offered: &[SigningAlgorithmId],
+) -> crate::Result<()> {
+    match selected {
+        Some(selected) if !offered.contains(&selected) => Err(Error::NegotiationError(format!(
+            "Signing algorithm selected by the server was not offered: {selected:?}"
+        ))),
+        None if !offered.contains(&SigningAlgorithmId::AesCmac) => Err(Error::NegotiationError(
+            "No signing algorithm selected by the server, and the implied AES-CMAC was not offered"
+                .into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
 /// SMB 3.0 and 3.0.2
 struct Smb30X;
 impl Smb30X {
@@ -274,3 +281,27 @@ impl DialectMethods for Smb201 {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_selected_signing_algo() {
+        let offered = [SigningAlgorithmId::AesCmac];
+        assert!(
+            validate_selected_signing_algo(Some(SigningAlgorithmId::AesCmac), &offered).is_ok()
+        );
+        assert!(validate_selected_signing_algo(None, &offered).is_ok());
+        assert!(matches!(
+            validate_selected_signing_algo(Some(SigningAlgorithmId::AesGmac), &offered),
+            Err(Error::NegotiationError(_))
+        ));
+
+        let offered = [SigningAlgorithmId::AesGmac];
+        assert!(matches!(
+            validate_selected_signing_algo(None, &offered),
+            Err(Error::NegotiationError(_))
+        ));
+    }
+}
@@ -45,17 +45,54 @@ pub enum Error {
     #[error("Negotiation error: {0}")]
     NegotiationError(String),
 
+    /// Indicates that the server only supports SMBv1, which is not supported by this crate.
+    ///
+    /// Returned when the server responds to the multi-protocol negotiate request with an SMBv1 response
+    /// (see [`ConnectionConfig::smb2_only_negotiate`][crate::ConnectionConfig::smb2_only_negotiate]).
+    #[error("The server only supports SMBv1")]
+    Smb1OnlyServer,
+
     #[error("Signature verification failed!")]
     SignatureVerificationFailed,
-    #[error("Unexpected message status: {}.", Status::try_display_as_status(*.0))]
-    UnexpectedMessageStatus(u32),
-    // TODO: This vs UnexpectedMessageStatus?!
-    #[error("Server returned an error message with status: {}.", Status::try_display_as_status(*.0))]
-    ReceivedErrorMessage(u32, ErrorResponse),
+    /// The server did not confirm the negotiation when it was validated, after a tree connect (MS-SMB2 3.2.5.5).
+    ///
+    /// The negotiation may have been tampered with, e.g. to downgrade the dialect or its security,
+    /// so the connection is terminated.
+    #[error(
+        "Negotiation validation failed: the server validated {field} {validated}, but negotiated {negotiated}"
+    )]
+    NegotiateValidationFailed {
+        /// The mismatched property of the negotiation.
+        field: &'static str,
+        negotiated: String,
+        validated: String,
+    },
+    /// The server failed a request with a status other than the expected ones.
+    ///
+    /// The error response is included when the server sent one. Use [`Error::status`]
+    /// or the `is_*` predicates to check for common statuses.
+    #[error("Server returned status: {0}.")]
+    ServerError(Status, Option<ErrorResponse>),
+    /// Like [`Error::ServerError`], for status codes that are not defined in [`Status`].
+    #[error("Server returned an unknown status: {0:#010x}.")]
+    UnknownServerStatus(u32, Option<ErrorResponse>),
     #[error("Unexpected command: {0}")]
     UnexpectedMessageCommand(Command),
     #[error("Missing permissions to perform {0}")]
     MissingPermissions(String),
+    /// The server failed an open with `STATUS_PRIVILEGE_NOT_HELD`, since the user does not hold
+    /// (or has not enabled) a privilege that the requested access requires.
+    /// See [`FileCreateArgs::for_backup`][crate::FileCreateArgs::for_backup] and [`FileCreateArgs::with_system_security`][crate::FileCreateArgs::with_system_security].
+    #[error("Opening {path} requires {privilege}, which is not held by the user.")]
+    PrivilegeNotHeld {
+        path: String,
+        privilege: &'static str,
+    },
+
+    /// The server logged in a user that supplied a password as a guest, and
+    /// [`ConnectionConfig::refuse_guest_fallback`][crate::ConnectionConfig::refuse_guest_fallback] is set.
+    #[error("The server logged in {user} as a guest, which is refused by the connection config.")]
+    GuestFallbackRefused { user: String },
 
     /// Indicates an error sourced from the underlying authentication SSPI
     /// (Security Support Provider Interface) library.
@@ -66,6 +103,11 @@ pub enum Error {
     #[error("Sspi error: {0}")]
     SspiError(#[from] sspi::Error),
 
+    /// Kerberos authentication failed, since no service ticket could be obtained for the server's SPN,
+    /// usually because the KDC does not know it. See [`UncPath::kerberos_spn`][crate::UncPath::kerberos_spn].
+    #[error("No Kerberos ticket could be obtained for SPN {spn}: {source}")]
+    KerberosNoServiceTicket { spn: String, source: sspi::Error },
+
     #[error("Provided buffer size too small to contain {data_type}")]
     BufferTooSmall {
         data_type: &'static str,
@@ -81,8 +123,47 @@ pub enum Error {
     CompressionError(#[from] crate::compression::CompressionError),
     #[error("Message processing failed. {0}")]
     MessageProcessingError(String),
+    /// The SMB2 header of a response was parsed, but its body was not.
+    ///
+    /// Only the request with the matching message ID fails with this error, and the connection remains usable.
+    /// `body` holds the raw message body, following the header, to be attached to bug reports.
+    #[error("Failed to parse {command} response with message ID {msg_id}: {reason}")]
+    ResponseParseError {
+        msg_id: u64,
+        command: Command,
+        status: u32,
+        reason: String,
+        body: Vec<u8>,
+    },
+    /// The server sent data that cannot be framed as an SMB2 message (for example, a bad protocol ID,
+    /// or a message shorter than the SMB2 header), so the integrity of the stream is lost.
+    /// The connection is torn down, and all pending requests fail with [`Error::ConnectionStopped`].
+    #[error("Received a corrupt frame: {0}")]
+    CorruptFrame(String),
     #[error("Operation timed out: {0:?}, took >{1:?}")]
     OperationTimeout(TimedOutTask, std::time::Duration),
+    /// The response of a request, of the specified command and message ID, was not received in time.
+    /// See [`ConnectionConfig::request_timeout`][crate::ConnectionConfig::request_timeout].
+    #[error("Request {0} (message ID {1}) timed out")]
+    Timeout(Command, u64),
+    /// Indicates that the session setup (authentication) exchange did not complete in time.
+    /// See [`ConnectionConfig::session_setup_timeout`][crate::ConnectionConfig::session_setup_timeout].
+    #[error(
+        "Authentication against {server} timed out waiting for security token #{step}, took >{timeout:?}"
+    )]
+    AuthenticationTimeout {
+        server: String,
+        /// The (1-based) number of the session setup leg that timed out.
+        step: usize,
+        timeout: std::time::Duration,
+    },
+    /// Indicates that connecting to a tree (share) did not complete in time.
+    /// See [`ConnectionConfig::tree_connect_timeout`][crate::ConnectionConfig::tree_connect_timeout].
+    #[error("Tree connect to {share} timed out, took >{timeout:?}")]
+    TreeConnectTimeout {
+        share: String,
+        timeout: std::time::Duration,
+    },
     #[error("Lock error.")]
     LockError,
     #[cfg(feature = "async")]
@@ -108,6 +189,71 @@ pub enum Error {
     DfsError(UncPath),
     #[error("Not found: {0}")]
     NotFound(String),
+    #[error("{name} collides with {conflicts_with} on the local file system, by {kind}.")]
+    LocalNameCollision {
+        name: String,
+        conflicts_with: String,
+        kind: crate::resource::NameCollisionKind,
+    },
+    /// The server failed a rename with `STATUS_OBJECT_NAME_COLLISION`, since a file already exists at `path`.
+    /// See [`ResourceHandle::move_to`][crate::ResourceHandle::move_to].
+    #[error("Cannot rename to {path}: a file with that name already exists.")]
+    ObjectNameCollision { path: String },
+    /// The server failed a hard link with `STATUS_NOT_SAME_DEVICE`, since `path` is on another volume than the linked file.
+    /// See [`ResourceHandle::hardlink_to`][crate::ResourceHandle::hardlink_to].
+    #[error("Cannot link to {path}: it is on another volume.")]
+    NotSameDevice { path: String },
+    /// The server failed to delete a directory with `STATUS_DIRECTORY_NOT_EMPTY`.
+    /// See [`Tree::remove_dir`][crate::Tree::remove_dir].
+    #[error("Cannot remove {path}: the directory is not empty.")]
+    DirectoryNotEmpty { path: String },
+    /// The server wrote less data than requested, even after retrying the remainder.
+    /// See [`ConnectionConfig::short_write_retries`][crate::ConnectionConfig::short_write_retries].
+    #[error("Short write at offset {offset}: {written} of {requested} bytes were written.")]
+    ShortWrite {
+        requested: usize,
+        written: usize,
+        offset: u64,
+    },
+    /// The server failed a write with `STATUS_DISK_FULL`, after `written` bytes of the write at `offset` were written.
+    #[error("Disk full: {written} bytes were written at offset {offset}.")]
+    DiskFull { written: usize, offset: u64 },
+    /// The server failed a write with `STATUS_QUOTA_EXCEEDED`, after `written` bytes of the write at `offset` were written.
+    #[error("Quota exceeded: {written} bytes were written at offset {offset}.")]
+    QuotaExceeded { written: usize, offset: u64 },
+    /// A read or write of a file opened for unbuffered I/O is not aligned to the sector size of the file's volume,
+    /// which the server would fail with `STATUS_INVALID_PARAMETER`, so it is not sent.
+    /// See [`FileCreateArgs::with_no_intermediate_buffering`][crate::FileCreateArgs::with_no_intermediate_buffering]
+    /// and [`File::sector_size`][crate::File::sector_size].
+    #[error(
+        "Unbuffered I/O must be aligned to the sector size of {sector_size} bytes, but {length} bytes at offset {offset} are not."
+    )]
+    UnalignedUnbufferedIo {
+        offset: u64,
+        length: usize,
+        sector_size: u32,
+    },
+    /// The session encrypted as many messages as allowed by [`ConnectionConfig::encryption_nonce_limit`][crate::ConnectionConfig::encryption_nonce_limit],
+    /// so encrypting more messages with its keys could reuse a nonce. A new session must be set up.
+    #[error(
+        "Encryption nonces of session {session_id:#x} are exhausted after {encrypted} messages."
+    )]
+    NonceExhaustion { session_id: u64, encrypted: u64 },
+    /// The file is larger than the limit of [`Client::read_file`][crate::Client::read_file].
+    /// See [`ClientConfig::read_file_size_limit`][crate::ClientConfig::read_file_size_limit].
+    #[error("File is too large to read into memory: {size} bytes, limit is {limit} bytes.")]
+    FileTooLarge { size: u64, limit: u64 },
+    /// The digest of the data at the destination of a transfer differs from the digest of the transferred data.
+    /// See [`IntegrityOptions`][crate::resource::IntegrityOptions].
+    #[error("Integrity check failed: expected digest {expected}, found {actual}.")]
+    IntegrityCheckFailed {
+        expected: crate::resource::IntegrityDigest,
+        actual: crate::resource::IntegrityDigest,
+    },
+    /// The server refused to reopen a durable handle from a [`DurableHandleTicket`][crate::DurableHandleTicket].
+    /// This is usually because the handle no longer exists: its timeout elapsed, or the server restarted.
+    #[error("Failed to reclaim the durable handle of {path}: {}.", Status::try_display_as_status(*status))]
+    DurableReclaimFailed { path: String, status: u32 },
 
     #[error("Channel {1} for session {0} not found.")]
     ChannelNotFound(u64, u32),
@@ -126,8 +272,97 @@ pub enum Error {
     Other(&'static str),
 }
 
+impl Error {
+    /// Builds the error of a server response with an unexpected `status`,
+    /// as [`Error::ServerError`] if the status is known, and as [`Error::UnknownServerStatus`] otherwise.
+    pub(crate) fn server_status(status: u32, response: Option<ErrorResponse>) -> Self {
+        match Status::try_from(status) {
+            Ok(status) => Error::ServerError(status, response),
+            Err(_) => Error::UnknownServerStatus(status, response),
+        }
+    }
+
+    /// Returns the status the server failed the request with, if it is a known [`Status`].
+    pub fn status(&self) -> Option<Status> {
+        match self {
+            Error::ServerError(status, _) => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw NT status the server failed the request with, known or not.
+    pub fn raw_status(&self) -> Option<u32> {
+        match self {
+            Error::ServerError(status, _) => Some(*status as u32),
+            Error::UnknownServerStatus(status, _) => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether the server failed the request with `STATUS_ACCESS_DENIED`.
+    pub fn is_access_denied(&self) -> bool {
+        self.status() == Some(Status::AccessDenied)
+    }
+
+    /// Whether the server failed the request since the file, or a directory in its path, does not exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self.status(),
+            Some(Status::ObjectNameNotFound | Status::ObjectPathNotFound | Status::NoSuchFile)
+        )
+    }
+
+    /// Whether the server failed the request with `STATUS_SHARING_VIOLATION`,
+    /// since the file is open by another client with an incompatible share access.
+    pub fn is_sharing_violation(&self) -> bool {
+        self.status() == Some(Status::SharingViolation)
+    }
+
+    /// Whether the connection the request was sent over failed, rather than the request itself.
+    pub(crate) fn is_connection_failure(&self) -> bool {
+        matches!(
+            self,
+            Error::ConnectionStopped | Error::TransportError(_) | Error::IoError(_)
+        )
+    }
+}
+
 impl<T> From<PoisonError<T>> for Error {
     fn from(_: PoisonError<T>) -> Self {
         Error::LockError
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_status() {
+        let error = Error::server_status(Status::U32_ACCESS_DENIED, None);
+        assert!(matches!(
+            error,
+            Error::ServerError(Status::AccessDenied, None)
+        ));
+        assert_eq!(error.status(), Some(Status::AccessDenied));
+        assert_eq!(error.raw_status(), Some(Status::U32_ACCESS_DENIED));
+        assert!(error.is_access_denied());
+        assert!(!error.is_not_found());
+
+        let error = Error::server_status(0xC0001234, Some(ErrorResponse { error_data: vec![] }));
+        assert!(matches!(
+            error,
+            Error::UnknownServerStatus(0xC0001234, Some(_))
+        ));
+        assert_eq!(error.status(), None);
+        assert_eq!(error.raw_status(), Some(0xC0001234));
+        assert_eq!(
+            error.to_string(),
+            "Server returned an unknown status: 0xc0001234."
+        );
+
+        assert!(Error::server_status(Status::U32_OBJECT_PATH_NOT_FOUND, None).is_not_found());
+        assert!(Error::server_status(Status::U32_SHARING_VIOLATION, None).is_sharing_violation());
+        assert!(!Error::NotFound("share".to_string()).is_not_found());
+    }
+}
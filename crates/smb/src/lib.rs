@@ -33,10 +33,12 @@ pub use client::{Client, ClientConfig, UncPath};
 pub use connection::{Connection, ConnectionConfig};
 pub use error::Error;
 pub use resource::{
-    Directory, File, FileCreateArgs, GetLen, Pipe, PipeRpcConnection, ReadAt, ReadAtChannel,
-    Resource, ResourceHandle, WriteAt, WriteAtChannel,
+    Directory, DirectoryEntry, DurableHandleTicket, DurableOpenOptions, File, FileCreateArgs,
+    FileWriter, FileWriterOptions, GetLen, OpenFileId, Pipe, PipeRpcConnection,
+    QueryDirectoryOptions, ReadAt, ReadAtChannel, RenameMode, RenameOptions, Resource,
+    ResourceHandle, RpcBinding, StreamInfo, WriteAt, WriteAtChannel,
 };
-pub use session::Session;
+pub use session::{Credentials, Session};
 pub use tree::{DfsRootTreeRef, Tree};
 
 pub use smb_dtyp::*;
@@ -49,3 +51,6 @@ pub type Result<T> = std::result::Result<T, crate::Error>;
 
 // Re-exports of some dependencies for convenience
 pub mod sync_helpers;
+
+#[cfg(test)]
+mod test_util;
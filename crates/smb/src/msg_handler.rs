@@ -14,6 +14,7 @@ pub struct OutgoingMessage {
     pub return_raw_data: bool,
 
     /// Ask the sender to compress the message before sending, if possible.
+    /// This value defaults to false: only write requests are compressed.
     pub compress: bool,
     /// Ask the sender to encrypt the message before sending, if possible.
     pub encrypt: bool,
@@ -27,18 +28,33 @@ pub struct OutgoingMessage {
 
     /// Channel ID to use for this message, if any.
     pub channel_id: Option<u32>,
+
+    /// The payload size to calculate the credit charge of the message by,
+    /// instead of the one derived from its content. Used when replaying a typed message as a raw one.
+    pub credit_payload_size: Option<u32>,
+
+    /// Whether this is a request being sent again, e.g. after its session expired.
+    pub is_retry: bool,
 }
 
 impl OutgoingMessage {
     pub fn new(content: RequestContent) -> OutgoingMessage {
+        Self::from_message(PlainRequest::new(content))
+    }
+
+    /// Creates an outgoing message from a complete plain message,
+    /// e.g. one whose header command differs from its content.
+    pub fn from_message(message: PlainRequest) -> OutgoingMessage {
         OutgoingMessage {
-            message: PlainRequest::new(content),
+            message,
             return_raw_data: false,
-            compress: true,
+            compress: false,
             encrypt: false,
             has_response: true,
             additional_data: None,
             channel_id: None,
+            credit_payload_size: None,
+            is_retry: false,
         }
     }
 
@@ -47,6 +63,11 @@ impl OutgoingMessage {
         self
     }
 
+    pub fn with_compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
     pub fn with_return_raw_data(mut self, return_raw_data: bool) -> Self {
         self.return_raw_data = return_raw_data;
         self
@@ -69,11 +90,18 @@ pub struct SendMessageResult {
     pub msg_id: u64,
     // If finalized, this is set.
     pub raw: Option<IoVec>,
+    /// The channel the message was sent over, if one was specified by [`OutgoingMessage::channel_id`],
+    /// or picked by the session. The response must be received from the same channel.
+    pub channel_id: Option<u32>,
 }
 
 impl SendMessageResult {
     pub fn new(msg_id: u64, raw: Option<IoVec>) -> SendMessageResult {
-        SendMessageResult { msg_id, raw }
+        SendMessageResult {
+            msg_id,
+            raw,
+            channel_id: None,
+        }
     }
 }
 
@@ -100,7 +128,7 @@ impl IncomingMessage {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct MessageForm {
     pub compressed: bool,
     pub encrypted: bool,
@@ -142,6 +170,10 @@ impl Default for AsyncMessageIds {
 
 /// Options for receiving a message.
 ///
+/// When the server goes async on a request, and sends an interim response ([`Status::Pending`]),
+/// the receive keeps waiting for the final response of the request, which is returned instead.
+/// Cancellation ([`async_cancel`][Self::async_cancel]) and [`timeout`][Self::timeout] apply to each wait.
+///
 /// Use a builder pattern to set the options:
 /// ```
 /// use smb_msg::*;
@@ -168,15 +200,6 @@ pub struct ReceiveOptions<'a> {
     /// The channel ID to receive messages from, if any.
     pub channel_id: Option<u32>,
 
-    /// Whether to allow (and wait for) async responses.
-    /// If set to false, an async response from the server will trigger an error.
-    /// If set to true, the handler will allow async messages to be received,
-    /// and will make the caller wait until the final async response is received --
-    /// the async response with status other than [`Status::Pending`].
-    ///
-    /// When using crate feature `async`, see [`async_cancel`][Self::async_cancel].
-    pub allow_async: bool,
-
     #[cfg(feature = "async")]
     /// An optional cancellation token to cancel the receive operation,
     /// if it's an async operation.
@@ -193,8 +216,15 @@ pub struct ReceiveOptions<'a> {
     pub async_msg_ids: Option<Arc<AsyncMessageIds>>,
 
     /// A timeout for the receive operation.
-    /// If not set, the default timeout of the connection is used.
+    /// If not set, the [request timeout][crate::ConnectionConfig::request_timeout] of the connection is used.
+    ///
+    /// Requests that may wait for long by design, like change notifications, should opt out
+    /// using [`without_timeout`][Self::without_timeout].
     pub timeout: Option<std::time::Duration>,
+
+    /// Whether to return the received message as is, skipping the status and content checks.
+    /// [`status`][Self::status] and [`cmd`][Self::cmd] are ignored when set.
+    pub unchecked: bool,
 }
 
 impl<'a> ReceiveOptions<'a> {
@@ -217,11 +247,6 @@ impl<'a> ReceiveOptions<'a> {
         self
     }
 
-    pub fn with_allow_async(mut self, allow_async: bool) -> Self {
-        self.allow_async = allow_async;
-        self
-    }
-
     #[cfg(feature = "async")]
     pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
         self.async_cancel = Some(token);
@@ -243,6 +268,16 @@ impl<'a> ReceiveOptions<'a> {
         self.timeout = Some(timeout);
         self
     }
+
+    /// Waits for the response indefinitely, regardless of the request timeout of the connection.
+    pub fn without_timeout(self) -> Self {
+        self.with_timeout(std::time::Duration::MAX)
+    }
+
+    pub fn with_unchecked(mut self, unchecked: bool) -> Self {
+        self.unchecked = unchecked;
+        self
+    }
 }
 
 impl<'a> Default for ReceiveOptions<'a> {
@@ -251,11 +286,11 @@ impl<'a> Default for ReceiveOptions<'a> {
             status: &[Status::Success],
             cmd: None,
             msg_id: 0,
-            allow_async: false,
             channel_id: None,
             async_cancel: None,
             async_msg_ids: None,
             timeout: None,
+            unchecked: false,
         }
     }
 }
@@ -275,6 +310,21 @@ pub trait MessageHandler {
     /// if there is one, using the provided `ReceiveOptions`.
     async fn recvo(&self, options: ReceiveOptions) -> crate::Result<IncomingMessage>;
 
+    /// Send messages to the server in a single compound, returning the result of each message, in order.
+    ///
+    /// Each handler in the chain should modify the messages as it does in [`sendo`][Self::sendo],
+    /// and call the next handler. The responses are received one by one, using [`recvo`][Self::recvo].
+    ///
+    /// Default implementation fails with [`Error::UnsupportedOperation`][crate::Error::UnsupportedOperation].
+    async fn sendo_compound(
+        &self,
+        _msgs: Vec<OutgoingMessage>,
+    ) -> crate::Result<Vec<SendMessageResult>> {
+        Err(crate::Error::UnsupportedOperation(
+            "Compounding is not supported by this handler".to_string(),
+        ))
+    }
+
     /// Called when a server-to-client message is received.
     ///
     /// # Arguments
@@ -314,11 +364,15 @@ pub trait MessageHandler {
         mut options: ReceiveOptions<'_>,
     ) -> crate::Result<(SendMessageResult, IncomingMessage)> {
         let channel_id = msg.channel_id;
+        // The response is of the command of the request.
+        options
+            .cmd
+            .get_or_insert(msg.message.content.associated_cmd());
         // Send the message and wait for the matching response.
         let send_result = self.sendo(msg).await?;
 
         options.msg_id = send_result.msg_id;
-        options.channel_id = channel_id;
+        options.channel_id = send_result.channel_id.or(channel_id);
 
         let in_result = self.recvo(options).await?;
         Ok((send_result, in_result))
@@ -1,17 +1,26 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicBool, Ordering},
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::SystemTime,
 };
 
 use maybe_async::*;
-use smb_dtyp::SecurityDescriptor;
+use smb_dtyp::{
+    SecurityDescriptor,
+    binrw_util::prelude::{FileTime, SizedWideString},
+};
 use smb_fscc::*;
 use smb_msg::*;
 use time::PrimitiveDateTime;
 
 use crate::{
     Error,
-    connection::connection_info::ConnectionInfo,
+    connection::{
+        connection_info::{BufferLimit, ConnectionInfo},
+        quirks::{self, Quirk},
+    },
     msg_handler::{
         AsyncMessageIds, HandlerReference, IncomingMessage, MessageHandler, OutgoingMessage,
         ReceiveOptions, SendMessageResult,
@@ -20,14 +29,24 @@ use crate::{
 };
 
 pub mod directory;
+pub mod durable;
 pub mod file;
 pub mod file_util;
+pub mod integrity;
+pub mod name_collision;
 pub mod pipe;
+pub mod streams;
+pub mod transfer;
 
 pub use directory::*;
+pub use durable::*;
 pub use file::*;
 pub use file_util::*;
+pub use integrity::*;
+pub use name_collision::*;
 pub use pipe::*;
+pub use streams::*;
+pub use transfer::*;
 
 type Upstream = HandlerReference<TreeMessageHandler>;
 
@@ -37,6 +56,13 @@ pub struct FileCreateArgs {
     pub attributes: FileAttributes,
     pub options: CreateOptions,
     pub desired_access: FileAccessMask,
+    /// If set, requests a durable handle for the opened file. See [`DurableOpenOptions`].
+    pub durable: Option<DurableOpenOptions>,
+    /// If set, opens the version of the file as of this snapshot time, instead of the current one.
+    /// See [`Client::list_snapshots`][crate::Client::list_snapshots].
+    ///
+    /// Snapshots are read-only, so this is expected to be used alongside read-only access.
+    pub snapshot: Option<FileTime>,
 }
 
 impl FileCreateArgs {
@@ -46,29 +72,35 @@ impl FileCreateArgs {
             attributes: FileAttributes::new(),
             options: CreateOptions::new(),
             desired_access: access,
+            durable: None,
+            snapshot: None,
         }
     }
 
     /// Returns arguments for creating a new file,
-    /// with the default access set to Generic All.
+    /// with the default access set to [full control][FileAccessMask::full_control].
     pub fn make_create_new(attributes: FileAttributes, options: CreateOptions) -> FileCreateArgs {
         FileCreateArgs {
             disposition: CreateDisposition::Create,
             attributes,
             options,
-            desired_access: FileAccessMask::new().with_generic_all(true),
+            desired_access: FileAccessMask::full_control(),
+            durable: None,
+            snapshot: None,
         }
     }
 
     /// Returns arguments for creating a new file,
-    /// with the default access set to Generic All.
+    /// with the default access set to [full control][FileAccessMask::full_control].
     /// overwrites existing file, if it exists.
     pub fn make_overwrite(attributes: FileAttributes, options: CreateOptions) -> FileCreateArgs {
         FileCreateArgs {
             disposition: CreateDisposition::OverwriteIf,
             attributes,
             options,
-            desired_access: FileAccessMask::new().with_generic_all(true),
+            desired_access: FileAccessMask::full_control(),
+            durable: None,
+            snapshot: None,
         }
     }
 
@@ -78,13 +110,285 @@ impl FileCreateArgs {
             disposition: CreateDisposition::Open,
             attributes: Default::default(),
             options: Default::default(),
+            desired_access: FileAccessMask::standard_rw(),
+            durable: None,
+            snapshot: None,
+        }
+    }
+
+    /// Returns arguments for opening an existing file or directory for backup,
+    /// with [backup intent][CreateOptions::open_for_backup_intent].
+    ///
+    /// If the user holds `SeBackupPrivilege` (e.g. a member of the Backup Operators group), the server
+    /// grants the access regardless of the file's DACL. Otherwise, the server checks the DACL as usual,
+    /// and the open may fail with `STATUS_ACCESS_DENIED`.
+    ///
+    /// The access is set to read the data, attributes, extended attributes and security descriptor of the file,
+    /// without its SACL (see [`FileCreateArgs::with_system_security`]), and the file is hinted to be read sequentially.
+    pub fn for_backup() -> FileCreateArgs {
+        FileCreateArgs {
+            disposition: CreateDisposition::Open,
+            attributes: FileAttributes::new(),
+            options: CreateOptions::new()
+                .with_open_for_backup_intent(true)
+                .with_sequential_only(true),
+            desired_access: FileAccessMask::new()
+                .with_file_read_data(true)
+                .with_file_read_attributes(true)
+                .with_file_read_ea(true)
+                .with_read_control(true)
+                .with_synchronize(true),
+            durable: None,
+            snapshot: None,
+        }
+    }
+
+    /// Returns arguments for opening a file or directory for restore, creating it if it does not exist,
+    /// with [backup intent][CreateOptions::open_for_backup_intent].
+    ///
+    /// If the user holds `SeRestorePrivilege`, the server grants the access regardless of the file's DACL,
+    /// and allows setting any owner on the file. Otherwise, the server checks the DACL as usual.
+    ///
+    /// The access is set to write the data, attributes, extended attributes, owner and DACL of the file,
+    /// and to delete it, and the file is hinted to be written sequentially.
+    /// Existing data is not truncated: set [`FileCreateArgs::disposition`] to [`CreateDisposition::OverwriteIf`]
+    /// to restore a file's data from scratch, and [`FileCreateArgs::options`] to create a directory.
+    pub fn for_restore() -> FileCreateArgs {
+        FileCreateArgs {
+            disposition: CreateDisposition::OpenIf,
+            attributes: FileAttributes::new(),
+            options: CreateOptions::new()
+                .with_open_for_backup_intent(true)
+                .with_sequential_only(true),
             desired_access: FileAccessMask::new()
-                .with_generic_read(true)
-                .with_generic_write(true),
+                .with_file_write_data(true)
+                .with_file_append_data(true)
+                .with_file_write_attributes(true)
+                .with_file_write_ea(true)
+                .with_delete(true)
+                .with_write_dacl(true)
+                .with_write_owner(true)
+                .with_synchronize(true),
+            durable: None,
+            snapshot: None,
+        }
+    }
+
+    /// Returns the arguments, with [`FileAccessMask::access_system_security`] set or cleared.
+    ///
+    /// This access is required for querying and setting the SACL of the file
+    /// (see [`ResourceHandle::query_security_info`]), and requires the user to hold `SeSecurityPrivilege`.
+    /// If it does not, opening the file fails with [`Error::PrivilegeNotHeld`].
+    pub fn with_system_security(mut self, system_security: bool) -> FileCreateArgs {
+        self.desired_access
+            .set_access_system_security(system_security);
+        self
+    }
+
+    /// Returns the arguments, with [`CreateOptions::open_reparse_point`] set or cleared.
+    ///
+    /// When set, the server opens a reparse point (e.g. a symbolic link) itself rather than its target.
+    /// This is required for [`ResourceHandle::get_reparse_point`] and [`ResourceHandle::set_reparse_point`]
+    /// on symbolic links.
+    pub fn with_open_reparse_point(mut self, open_reparse_point: bool) -> FileCreateArgs {
+        self.options.set_open_reparse_point(open_reparse_point);
+        self
+    }
+
+    /// Returns the arguments, with [`CreateOptions::write_through`] set or cleared.
+    ///
+    /// When set, the server completes writes to the file only once their data reached stable storage,
+    /// so the data survives a crash of the server without flushing the file.
+    pub fn with_write_through(mut self, write_through: bool) -> FileCreateArgs {
+        self.options.set_write_through(write_through);
+        self
+    }
+
+    /// Returns the arguments, with [`CreateOptions::no_intermediate_buffering`] set or cleared.
+    ///
+    /// When set, the server does not cache the data of the file, and requires reads and writes of the file
+    /// to be aligned to the sector size of its volume (see [`File::sector_size`]): reads and writes at offsets,
+    /// or of lengths, that are not a multiple of it fail with [`Error::UnalignedUnbufferedIo`], without being sent.
+    pub fn with_no_intermediate_buffering(
+        mut self,
+        no_intermediate_buffering: bool,
+    ) -> FileCreateArgs {
+        self.options
+            .set_no_intermediate_buffering(no_intermediate_buffering);
+        self
+    }
+
+    /// Returns the arguments, with the options set in `options` set in addition to the current options.
+    ///
+    /// Fails with [`Error::InvalidArgument`] for combinations that the server rejects with `STATUS_INVALID_PARAMETER`:
+    /// * [`CreateOptions::directory_file`] along with [`CreateOptions::non_directory_file`].
+    /// * [`CreateOptions::directory_file`] with a disposition that supersedes or overwrites the file.
+    /// * [`CreateOptions::delete_on_close`] without [`FileAccessMask::delete`] access.
+    ///
+    /// [`CreateOptions::open_by_file_id`] is also rejected, since the name of the open is then a file ID:
+    /// use [`Tree::open_by_id`][crate::Tree::open_by_id] instead.
+    pub fn with_create_options(mut self, options: CreateOptions) -> crate::Result<FileCreateArgs> {
+        let current = u32::from_le_bytes(self.options.into_bytes());
+        let added = u32::from_le_bytes(options.into_bytes());
+        self.options = CreateOptions::from_bytes((current | added).to_le_bytes());
+        self.check_options()?;
+        Ok(self)
+    }
+
+    /// Checks that the options may be combined with each other, and with the disposition and access of the arguments.
+    fn check_options(&self) -> crate::Result<()> {
+        let options = &self.options;
+        if options.open_by_file_id() {
+            return Err(Error::InvalidArgument(
+                "open_by_file_id may not be set directly, use Tree::open_by_id".to_string(),
+            ));
+        }
+        if options.directory_file() && options.non_directory_file() {
+            return Err(Error::InvalidArgument(
+                "directory_file and non_directory_file may not both be set".to_string(),
+            ));
+        }
+        if options.directory_file()
+            && matches!(
+                self.disposition,
+                CreateDisposition::Superseded
+                    | CreateDisposition::Overwrite
+                    | CreateDisposition::OverwriteIf
+            )
+        {
+            return Err(Error::InvalidArgument(format!(
+                "a directory may not be opened with the {:?} disposition",
+                self.disposition
+            )));
+        }
+        if options.delete_on_close()
+            && !(self.desired_access.delete() || self.desired_access.generic_all())
+        {
+            return Err(Error::InvalidArgument(
+                "delete_on_close requires the delete access".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the privilege the server fails the open with `STATUS_PRIVILEGE_NOT_HELD` for, if any.
+    fn required_privilege(&self) -> Option<&'static str> {
+        let access = &self.desired_access;
+        if access.access_system_security() {
+            return Some("SeSecurityPrivilege");
+        }
+        if !self.options.open_for_backup_intent() {
+            return None;
+        }
+        let writes = access.file_write_data()
+            || access.file_append_data()
+            || access.file_write_attributes()
+            || access.file_write_ea()
+            || access.delete()
+            || access.write_dacl()
+            || access.write_owner()
+            || access.generic_write()
+            || access.generic_all();
+        Some(if writes {
+            "SeRestorePrivilege"
+        } else {
+            "SeBackupPrivilege"
+        })
+    }
+
+    /// Returns the arguments, set to open the version of the file as of `snapshot`, if set.
+    /// See [`FileCreateArgs::snapshot`].
+    pub fn with_snapshot(mut self, snapshot: Option<FileTime>) -> FileCreateArgs {
+        self.snapshot = snapshot;
+        self
+    }
+}
+
+/// The ID of a file, to open it by, rather than by its name. See [`Tree::open_by_id`][crate::Tree::open_by_id].
+///
+/// The 64-bit ID is the `file_id` of information classes such as [`FileIdBothDirectoryInformation`],
+/// while the 128-bit ID is the `file_id` of [`FileIdExtdDirectoryInformation`], or the `file_id_128`
+/// of [`FileIdAllExtdDirectoryInformation`]. Not all file systems support opening files by ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpenFileId {
+    Id64(u64),
+    Id128(u128),
+}
+
+impl OpenFileId {
+    /// Returns the name buffer of a create request that opens the file by this ID,
+    /// which is the little-endian file ID (MS-SMB2 2.2.13).
+    fn name_buffer(&self) -> SizedWideString {
+        let bytes = match self {
+            OpenFileId::Id64(id) => id.to_le_bytes().to_vec(),
+            OpenFileId::Id128(id) => id.to_le_bytes().to_vec(),
+        };
+        bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect()
+    }
+}
+
+impl From<u64> for OpenFileId {
+    fn from(id: u64) -> Self {
+        OpenFileId::Id64(id)
+    }
+}
+
+impl From<u128> for OpenFileId {
+    fn from(id: u128) -> Self {
+        OpenFileId::Id128(id)
+    }
+}
+
+impl std::fmt::Display for OpenFileId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenFileId::Id64(id) => write!(f, "<file ID {id:#018x}>"),
+            OpenFileId::Id128(id) => write!(f, "<file ID {id:#034x}>"),
         }
     }
 }
 
+/// Options for [`ResourceHandle::rename`] and [`ResourceHandle::move_to`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenameOptions {
+    /// Whether to replace an existing file at the destination.
+    /// If not set, and such a file exists, the rename fails with [`Error::ObjectNameCollision`].
+    pub replace_if_exists: bool,
+    /// See [`RenameMode`].
+    pub mode: RenameMode,
+}
+
+/// Selects the information class used to rename a resource.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenameMode {
+    /// Renames using [`FileRenameInformation`], which is supported by all servers.
+    #[default]
+    Classic,
+    /// Renames using [`FileRenameInformationEx`], with these flags, e.g. POSIX semantics,
+    /// to replace a destination file that is currently open.
+    /// [`FileRenameFlags::replace_if_exists`] is taken from [`RenameOptions::replace_if_exists`].
+    ///
+    /// If the server does not support the class, and fails with [`Status::InvalidInfoClass`]
+    /// (or [`Status::InvalidParameter`] or [`Status::NotSupported`]),
+    /// the rename is sent again in the [classic][RenameMode::Classic] mode, without the flags.
+    Ex(FileRenameFlags),
+}
+
+impl RenameMode {
+    /// Renames with POSIX semantics, also replacing read-only destination files,
+    /// as supported by Windows dev drives and some Samba servers.
+    pub fn posix() -> Self {
+        RenameMode::Ex(
+            FileRenameFlags::new()
+                .with_posix_semantics(true)
+                .with_ignore_readonly_attribute(true),
+        )
+    }
+}
+
 /// A resource opened by a create request.
 pub enum Resource {
     File(File),
@@ -93,9 +397,13 @@ pub enum Resource {
 }
 
 impl Resource {
+    /// Opens or creates the resource named `name`, or, if `by_id` is set, opens the file with this ID,
+    /// in which case `name` is only used for display.
     #[maybe_async]
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn create(
         name: &str,
+        by_id: Option<OpenFileId>,
         upstream: &Upstream,
         create_args: &FileCreateArgs,
         conn_info: &Arc<ConnectionInfo>,
@@ -117,44 +425,235 @@ impl Resource {
             ));
         }
 
-        if name.starts_with("\\") {
+        if by_id.is_none() && name.starts_with("\\") {
             return Err(Error::InvalidArgument(
                 "Resource name cannot start with a backslash.".to_string(),
             ));
         }
+        if by_id.is_some() && create_args.durable.is_some() {
+            // Reclaiming a durable handle requires the name of the file.
+            return Err(Error::InvalidArgument(format!(
+                "Cannot request a durable handle for {name}, which is opened by its ID."
+            )));
+        }
+        let mut create_options = create_args.options;
+        if by_id.is_some() {
+            create_options.set_open_by_file_id(true);
+        }
+
+        let durable = match &create_args.durable {
+            Some(options) => Some(DurableOpen::request(
+                options,
+                upstream.tree_name(),
+                upstream.info()?,
+                conn_info,
+            )?),
+            None => None,
+        };
 
+        let quirks = &conn_info.quirks;
+        // A file ID is not a path, so DFS does not apply to it.
+        let dfs_operation = by_id.is_none() && Self::dfs_operation(name, conn_info, is_dfs);
+        let mut query_on_disk_id = !quirks.is_set(Quirk::NoQueryOnDiskId);
+        let mut retried = false;
+        let response = loop {
+            let mut contexts = vec![QueryMaximalAccessRequest::default().into()];
+            if query_on_disk_id {
+                contexts.push(QueryOnDiskIdReq.into());
+            } else {
+                log::trace!(target: quirks::LOG_TARGET, "Not querying the on-disk ID of '{name}'");
+            }
+            if let Some(durable) = &durable {
+                contexts.extend(durable.request_contexts());
+            }
+            if let Some(timestamp) = create_args.snapshot {
+                contexts.push(TimewarpToken { timestamp }.into());
+            }
+
+            let mut msg = OutgoingMessage::new(
+                CreateRequest {
+                    requested_oplock_level: durable
+                        .as_ref()
+                        .map_or(OplockLevel::None, DurableOpen::oplock_level),
+                    impersonation_level: ImpersonationLevel::Impersonation,
+                    desired_access: create_args.desired_access,
+                    file_attributes: create_args.attributes,
+                    share_access,
+                    create_disposition: create_args.disposition,
+                    create_options,
+                    name: match &by_id {
+                        Some(id) => id.name_buffer(),
+                        None => name.into(),
+                    },
+                    contexts: contexts.into(),
+                }
+                .into(),
+            );
+            // Make sure to set DFS if required.
+            msg.message.header.flags.set_dfs_operation(dfs_operation);
+
+            let result = upstream.sendo_recv(msg).await;
+            match result {
+                // Some servers reject the QFid context: retry once without it.
+                Err(Error::ServerError(Status::InvalidParameter | Status::NotSupported, _))
+                    if query_on_disk_id && !quirks.is_overridden(Quirk::NoQueryOnDiskId) =>
+                {
+                    log::trace!(
+                        target: quirks::LOG_TARGET,
+                        "Creating '{name}' failed, retrying without querying the on-disk ID"
+                    );
+                    query_on_disk_id = false;
+                    retried = true;
+                }
+                Err(e @ Error::ServerError(Status::PrivilegeNotHeld, _)) => {
+                    return Err(match create_args.required_privilege() {
+                        Some(privilege) => Error::PrivilegeNotHeld {
+                            path: name.to_string(),
+                            privilege,
+                        },
+                        None => e,
+                    });
+                }
+                result => break result?,
+            }
+        };
+        if retried {
+            quirks.detected(
+                Quirk::NoQueryOnDiskId,
+                "create succeeded only without the QFid context",
+            );
+        }
+
+        let mut response = response.message.content.to_create()?;
+        log::debug!("Created file '{}', ({:?})", name, response.file_id);
+
+        let contexts: Vec<ResponseCreateContext> =
+            std::mem::take(&mut response.create_contexts).into();
+        let durable = durable.and_then(|durable| {
+            let granted = durable.granted(&response.oplock_level, &contexts);
+            if granted.is_none() {
+                log::debug!("The server did not grant a durable handle for '{name}'.");
+            }
+            granted
+        });
+        Ok(Self::from_response(
+            name,
+            by_id,
+            upstream,
+            &response,
+            &contexts,
+            create_args.desired_access,
+            create_options,
+            conn_info,
+            share_type,
+            durable,
+        ))
+    }
+
+    /// Reopens a durable handle from a ticket, after its connection was lost.
+    ///
+    /// Any failure of the server to reopen the handle fails with [`Error::DurableReclaimFailed`].
+    #[maybe_async]
+    pub(crate) async fn reclaim_durable(
+        ticket: &DurableHandleTicket,
+        upstream: &Upstream,
+        conn_info: &Arc<ConnectionInfo>,
+        share_type: ShareType,
+        is_dfs: bool,
+    ) -> crate::Result<Resource> {
+        if share_type != ShareType::Disk {
+            return Err(Error::InvalidArgument(
+                "Durable handles can only be reclaimed on disk shares.".to_string(),
+            ));
+        }
+
+        let name = ticket.path.as_str();
+        let durable = DurableOpen::from_ticket(ticket);
+        // The server ignores all the other fields when reconnecting.
         let mut msg = OutgoingMessage::new(
             CreateRequest {
-                requested_oplock_level: OplockLevel::None,
+                requested_oplock_level: durable.oplock_level(),
                 impersonation_level: ImpersonationLevel::Impersonation,
-                desired_access: create_args.desired_access,
-                file_attributes: create_args.attributes,
-                share_access,
-                create_disposition: create_args.disposition,
-                create_options: create_args.options,
+                desired_access: FileAccessMask::new(),
+                file_attributes: FileAttributes::new(),
+                share_access: ShareAccessFlags::new(),
+                create_disposition: CreateDisposition::Open,
+                create_options: CreateOptions::new(),
                 name: name.into(),
-                contexts: vec![
-                    QueryMaximalAccessRequest::default().into(),
-                    QueryOnDiskIdReq.into(),
-                ]
-                .into(),
+                contexts: durable.reconnect_contexts(ticket.file_id()).into(),
             }
             .into(),
         );
-        // Make sure to set DFS if required.
-        msg.message.header.flags.set_dfs_operation(is_dfs);
+        msg.message
+            .header
+            .flags
+            .set_dfs_operation(Self::dfs_operation(name, conn_info, is_dfs));
+
+        let response = match upstream.sendo_recv(msg).await {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(match e.raw_status() {
+                    Some(status) => Error::DurableReclaimFailed {
+                        path: name.to_string(),
+                        status,
+                    },
+                    None => e,
+                });
+            }
+        };
+        let mut response = response.message.content.to_create()?;
+        log::debug!(
+            "Reclaimed durable handle of '{name}', ({:?})",
+            response.file_id
+        );
 
-        let response = upstream
-            .sendo_recvo(msg, ReceiveOptions::new().with_allow_async(true))
-            .await?;
+        let contexts: Vec<ResponseCreateContext> =
+            std::mem::take(&mut response.create_contexts).into();
+        Ok(Self::from_response(
+            name,
+            None,
+            upstream,
+            &response,
+            &contexts,
+            FileAccessMask::new(),
+            CreateOptions::new(),
+            conn_info,
+            share_type,
+            Some(durable),
+        ))
+    }
 
-        let response = response.message.content.to_create()?;
-        log::debug!("Created file '{}', ({:?})", name, response.file_id);
+    /// Returns whether to set the DFS operation flag on requests for `name`.
+    fn dfs_operation(name: &str, conn_info: &ConnectionInfo, is_dfs: bool) -> bool {
+        let quirks = &conn_info.quirks;
+        if is_dfs && quirks.is_set(Quirk::NeedsDfsFlagOff) {
+            log::trace!(target: quirks::LOG_TARGET, "Not setting the DFS operation flag for '{name}'");
+            false
+        } else {
+            is_dfs
+        }
+    }
 
+    /// Builds the resource opened by a successful create response.
+    ///
+    /// `desired_access` and `create_options` are the access and the options of the create request, which the server granted.
+    #[allow(clippy::too_many_arguments)]
+    fn from_response(
+        name: &str,
+        by_id: Option<OpenFileId>,
+        upstream: &Upstream,
+        response: &CreateResponse,
+        contexts: &[ResponseCreateContext],
+        desired_access: FileAccessMask,
+        create_options: CreateOptions,
+        conn_info: &Arc<ConnectionInfo>,
+        share_type: ShareType,
+        durable: Option<DurableOpen>,
+    ) -> Resource {
         let is_dir = response.file_attributes.directory();
 
         // Get maximal access
-        let access = match CreateContextResponseData::first_mxac(&response.create_contexts.into()) {
+        let access = match contexts.iter().find_map(|c| c.data.as_mxac()) {
             Some(response) => response.maximal_access,
             _ => {
                 log::debug!(
@@ -163,23 +662,40 @@ impl Resource {
                 FileAccessMask::from_bytes(u32::MAX.to_be_bytes())
             }
         };
+        let access = Self::granted_access(access, desired_access);
 
         // Common information is held in the handle object.
+        let handler = ResourceMessageHandle::new(upstream);
+        let lease_key = contexts
+            .iter()
+            .find_map(|c| c.data.as_rqls())
+            .map(RequestLease::lease_key);
+        if let Err(e) = upstream.connection().oplock_breaks().register(
+            response.file_id,
+            response.oplock_level,
+            lease_key,
+            handler.weak(),
+        ) {
+            log::error!("Failed to register the oplock of '{name}': {e}");
+        }
+
         let handle = ResourceHandle {
             name: name.to_string(),
-            handler: ResourceMessageHandle::new(upstream),
+            by_id,
+            handler,
             open: AtomicBool::new(true),
             _file_id: response.file_id,
             created: response.creation_time.date_time(),
             modified: response.last_write_time.date_time(),
             access,
+            create_options,
             share_type,
+            durable,
             conn_info: conn_info.clone(),
         };
 
         // Construct specific resource and return it.
-
-        let resource = if is_dir {
+        if is_dir {
             Resource::Directory(Directory::new(handle))
         } else {
             match share_type {
@@ -187,8 +703,32 @@ impl Resource {
                 ShareType::Pipe => Resource::Pipe(Pipe::new(handle)),
                 ShareType::Print => unimplemented!("Printer resources are not yet implemented"),
             }
-        };
-        Ok(resource)
+        }
+    }
+
+    /// Returns the access of an open, given its maximal access and the access it requested.
+    ///
+    /// The maximal access is computed by the server from the DACL of the file, so it misses access
+    /// granted by privileges (e.g. opens with backup intent), and it never includes
+    /// [`FileAccessMask::access_system_security`]. Since the open succeeded, the requested access was granted,
+    /// with its generic rights mapped to the specific rights of files.
+    fn granted_access(maximal: FileAccessMask, desired: FileAccessMask) -> FileAccessMask {
+        let generic = FileAccessMask::from_generic(smb_dtyp::GenericRights {
+            read: desired.generic_read(),
+            write: desired.generic_write(),
+            execute: desired.generic_execute(),
+            all: desired.generic_all(),
+        });
+        let requested = desired
+            .with_generic_read(false)
+            .with_generic_write(false)
+            .with_generic_execute(false)
+            .with_generic_all(false)
+            .with_maximum_allowed(false);
+        let bits = [maximal, requested, generic]
+            .into_iter()
+            .fold(0, |bits, mask| bits | u32::from_le_bytes(mask.into_bytes()));
+        FileAccessMask::from_bytes(bits.to_le_bytes())
     }
 
     pub fn as_file(&self) -> Option<&File> {
@@ -226,6 +766,15 @@ impl Resource {
             _ => panic!("Not a directory"),
         }
     }
+
+    /// Returns the handle of the resource, whatever its type is.
+    pub(crate) fn handle(&self) -> &ResourceHandle {
+        match self {
+            Resource::File(file) => file,
+            Resource::Directory(dir) => dir,
+            Resource::Pipe(pipe) => pipe,
+        }
+    }
 }
 
 /// Generates TryInto implementations for Resource enum variants.
@@ -254,6 +803,8 @@ make_resource_try_into!(File, Directory, Pipe,);
 /// Holds the common information for an opened SMB resource.
 pub struct ResourceHandle {
     name: String,
+    /// Set if the resource was opened by its file ID, in which case `name` is only the ID, for display.
+    by_id: Option<OpenFileId>,
     handler: HandlerReference<ResourceMessageHandle>,
 
     // Whether the resource is open or not.
@@ -268,6 +819,11 @@ pub struct ResourceHandle {
     share_type: ShareType,
 
     access: FileAccessMask,
+    /// The options the resource was opened with.
+    create_options: CreateOptions,
+
+    /// Set if the server granted a durable handle.
+    durable: Option<DurableOpen>,
 
     conn_info: Arc<ConnectionInfo>,
 }
@@ -275,10 +831,24 @@ pub struct ResourceHandle {
 #[maybe_async(AFIT)]
 impl ResourceHandle {
     /// Returns the name of the resource.
+    ///
+    /// For resources opened by their file ID (see [`Tree::open_by_id`][crate::Tree::open_by_id]),
+    /// this is the formatted ID, rather than a path.
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Returns the file ID the resource was opened by, if it was opened by ID rather than by name.
+    /// See [`Tree::open_by_id`][crate::Tree::open_by_id].
+    pub fn opened_by_id(&self) -> Option<OpenFileId> {
+        self.by_id
+    }
+
+    /// Returns the tree of the resource.
+    pub(crate) fn tree(&self) -> &TreeMessageHandler {
+        &self.handler.upstream.handler
+    }
+
     /// Returns the creation time of the resource.
     pub fn created(&self) -> PrimitiveDateTime {
         self.created
@@ -289,6 +859,14 @@ impl ResourceHandle {
         self.modified
     }
 
+    /// Returns the options the resource was opened with, see [`FileCreateArgs::options`].
+    ///
+    /// Reclaimed durable handles report the options of the handle they were reclaimed from,
+    /// if reclaimed using [`File::reconnect`], and no options otherwise.
+    pub fn create_options(&self) -> CreateOptions {
+        self.create_options
+    }
+
     /// Returns the current share type of the resource. See [ShareType] for more details.
     pub fn share_type(&self) -> ShareType {
         self.share_type
@@ -313,27 +891,68 @@ impl ResourceHandle {
         Ok(self._file_id)
     }
 
+    /// Returns a ticket for reclaiming the durable handle of the resource,
+    /// if the resource is open and durable.
+    pub(crate) fn durable_ticket(&self) -> Option<DurableHandleTicket> {
+        let durable = self.durable.as_ref()?;
+        Some(durable.ticket(&self.name, self.file_id().ok()?))
+    }
+
+    /// Opens the resource again, by the name (or ID) it was opened with, on the same tree.
+    ///
+    /// This is used to recover from the loss of the handle, e.g. after the resource was deleted and re-created.
+    pub(crate) async fn reopen(&self, args: &FileCreateArgs) -> crate::Result<Resource> {
+        let upstream = &self.handler.upstream;
+        let info = upstream.info()?;
+        Resource::create(
+            &self.name,
+            self.by_id,
+            upstream,
+            args,
+            &self.conn_info,
+            info.share_type,
+            info.share_flags.dfs(),
+        )
+        .await
+    }
+
+    /// Marks the resource as closed, without closing its handle on the server.
+    ///
+    /// This is used once a durable handle was reclaimed by another resource,
+    /// so that this one neither uses the handle, nor closes it on its lost connection.
+    pub(crate) fn forget(&self) {
+        self.open.store(false, std::sync::atomic::Ordering::Relaxed);
+    }
+
     /// (Internal)
     ///
     /// Calculates the transaction size to use for a request,
     /// considering both the requested size (if any), the max transaction size,
     /// and the default transaction size.
     ///
-    /// Prints a warning if the requested size exceeds the max transaction size.
-    fn calc_transact_size(&self, requested: Option<usize>) -> u32 {
-        let max_transact_size = self.conn_info.negotiation.max_transact_size;
+    /// A requested size that exceeds the max transaction size is handled by [`Self::clamp_buffer_size`].
+    fn calc_transact_size(&self, requested: Option<usize>) -> crate::Result<u32> {
         match requested {
-            Some(requested_length) if requested_length > max_transact_size as usize => {
-                log::warn!(
-                    "Requested transaction size (0x{requested_length:x}) exceeds max transaction size, clamping to 0x{max_transact_size:x}",
-                );
-                max_transact_size
+            Some(requested_length) => {
+                self.clamp_buffer_size(requested_length, BufferLimit::Transact)
             }
-            Some(len) => len as u32,
-            None => max_transact_size.min(self.conn_info.config.default_transaction_size()),
+            None => Ok(self
+                .conn_info
+                .negotiation
+                .max_transact_size
+                .min(self.conn_info.config.default_transaction_size())),
         }
     }
 
+    /// (Internal)
+    ///
+    /// Validates a requested buffer size against the negotiated limits,
+    /// according to the configured [`BufferSizePolicy`][crate::connection::BufferSizePolicy].
+    fn clamp_buffer_size(&self, requested: usize, which: BufferLimit) -> crate::Result<u32> {
+        self.conn_info
+            .clamp_or_error(requested, which, self.conn_info.config.buffer_size_policy)
+    }
+
     /// (Internal)
     ///
     /// Sends a Query Information Request and parses the response.
@@ -344,7 +963,7 @@ impl ResourceHandle {
         output_buffer_length: Option<usize>,
         data_type: &'static str,
     ) -> crate::Result<QueryInfoData> {
-        let buffer_length = self.calc_transact_size(output_buffer_length);
+        let buffer_length = self.calc_transact_size(output_buffer_length)?;
         req.output_buffer_length = buffer_length;
 
         let info_type = req.info_type;
@@ -492,6 +1111,60 @@ impl ResourceHandle {
         Ok(result)
     }
 
+    /// Returns the values of the specified extended attributes of the file, as `(name, value)` pairs.
+    ///
+    /// Attributes that the file does not have are returned with an empty value.
+    ///
+    /// The handle must have been opened with [`FileAccessMask::file_read_ea`] access.
+    /// Otherwise, this fails with [`Error::MissingPermissions`], without sending a request.
+    pub async fn get_ea(&self, names: &[&str]) -> crate::Result<Vec<(String, Vec<u8>)>> {
+        if !self.access.file_read_ea() {
+            return Err(Error::MissingPermissions(format!(
+                "reading the extended attributes of {}: the handle lacks read EA",
+                self.name
+            )));
+        }
+        let eas = self.query_full_ea_info(names.to_vec()).await?;
+        Ok(eas
+            .into_iter()
+            .map(|ea| (ea.ea_name.to_string(), ea.ea_value))
+            .collect())
+    }
+
+    /// Sets the specified extended attributes of the file, from `(name, value)` pairs,
+    /// using [`FileFullEaInformation`]. An attribute with an empty value is removed from the file.
+    ///
+    /// The handle must have been opened with [`FileAccessMask::file_write_ea`] access.
+    /// Otherwise, this fails with [`Error::MissingPermissions`], without sending a request.
+    pub async fn set_ea(&self, eas: &[(&str, &[u8])]) -> crate::Result<()> {
+        if !self.access.file_write_ea() {
+            return Err(Error::MissingPermissions(format!(
+                "setting the extended attributes of {}: the handle lacks write EA",
+                self.name
+            )));
+        }
+        if eas.is_empty() {
+            return Err(Error::InvalidArgument(
+                "No extended attributes to set".to_string(),
+            ));
+        }
+        for (name, value) in eas {
+            if name.is_empty() || name.len() > u8::MAX as usize || value.len() > u16::MAX as usize {
+                return Err(Error::InvalidArgument(format!(
+                    "Invalid extended attribute {name}: names are 1-255 bytes long, and values are up to 65535 bytes long"
+                )));
+            }
+        }
+        self.set_info(
+            FileFullEaInformation::builder()
+                .extend(eas.iter().copied())
+                .build(),
+        )
+        .await?;
+        log::debug!("Set {} extended attributes of {}", eas.len(), self.name);
+        Ok(())
+    }
+
     /// Queries the file for information with additional arguments.
     /// # Type Parameters
     /// * `T` - The type of information to query. Must implement the [QueryFileInfoValue] trait.
@@ -531,21 +1204,28 @@ impl ResourceHandle {
     }
 
     /// Queries the file for it's security descriptor.
+    ///
+    /// The handle must have been opened with the access [`SecurityInformation::query_access`] returns:
+    /// [`FileAccessMask::read_control`] for most parts, and [`FileAccessMask::access_system_security`]
+    /// for the SACL (see [`FileCreateArgs::with_system_security`]). Otherwise, the query fails with
+    /// [`Error::MissingPermissions`], without sending a request.
     /// # Arguments
-    /// * `additional_info` - The information to request on the security descriptor.
+    /// * `security_info` - The parts of the security descriptor to query, e.g. [`SecurityInformation::owner_group_dacl`].
     /// # Returns
     /// A `Result` containing the requested information, of type [`SecurityDescriptor`].
     pub async fn query_security_info(
         &self,
-        additional_info: AdditionalInfo,
+        security_info: SecurityInformation,
     ) -> crate::Result<SecurityDescriptor> {
-        self.query_security_info_with_options(additional_info, None)
+        self.query_security_info_with_options(security_info, None)
             .await
     }
 
     /// Queries the file for it's security descriptor.
+    ///
+    /// See [`ResourceHandle::query_security_info`] for the access this requires.
     /// # Arguments
-    /// * `additional_info` - The information to request on the security descriptor.
+    /// * `security_info` - The parts of the security descriptor to query.
     /// * `output_buffer_length` - An optional maximum output buffer to use. This should be less
     /// than or equal to the negotiated max transaction size. If `None`, the default transaction size
     /// will be used (see [`ConnectionConfig::default_transaction_size`][crate::ConnectionConfig::default_transaction_size]).
@@ -553,16 +1233,22 @@ impl ResourceHandle {
     /// A `Result` containing the requested information, of type [`SecurityDescriptor`].
     pub async fn query_security_info_with_options(
         &self,
-        additional_info: AdditionalInfo,
+        security_info: SecurityInformation,
         output_buffer_length: Option<usize>,
     ) -> crate::Result<SecurityDescriptor> {
+        Self::check_security_access(
+            "query",
+            security_info,
+            security_info.query_access(),
+            self.access,
+        )?;
         Ok(self
             .query_common(
                 QueryInfoRequest {
                     info_type: InfoType::Security,
                     info_class: Default::default(),
                     output_buffer_length: 0,
-                    additional_info,
+                    additional_info: security_info.into(),
                     flags: QueryInfoFlags::new(),
                     file_id: self.file_id()?,
                     data: GetInfoRequestData::None(()),
@@ -641,6 +1327,112 @@ impl ResourceHandle {
         Ok(response.out_buffer)
     }
 
+    /// Returns the object ID of the current file, or `None` if the file has no object ID.
+    pub async fn get_object_id(&self) -> crate::Result<Option<FileObjectIdBuffer>> {
+        match self.fsctl(GetObjectIdRequest(())).await {
+            Ok(buffer) => Ok(Some(buffer)),
+            Err(Error::ServerError(Status::ObjectIdNotFound, _)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the object ID of the current file, making the server generate one if the file has no object ID.
+    pub async fn create_or_get_object_id(&self) -> crate::Result<FileObjectIdBuffer> {
+        self.fsctl(CreateOrGetObjectIdRequest(())).await
+    }
+
+    /// Sets the object ID of the current file, including its birth volume and object IDs.
+    ///
+    /// The server fails with `STATUS_OBJECT_NAME_COLLISION` if the file already has an object ID,
+    /// or if the object ID is used by another file on the volume.
+    /// # Arguments
+    /// * `buffer` - The object ID to set.
+    /// * `replace` - Whether to delete the current object ID of the file first, if any.
+    /// # Notes
+    /// * Windows servers require the user to hold the restore privilege (`SeRestorePrivilege`).
+    pub async fn set_object_id(
+        &self,
+        buffer: FileObjectIdBuffer,
+        replace: bool,
+    ) -> crate::Result<()> {
+        if replace {
+            self.delete_object_id().await?;
+        }
+        self.fsctl(SetObjectIdRequest(buffer)).await?;
+        Ok(())
+    }
+
+    /// Sets the extended information (birth volume and object IDs) of the current file's object ID.
+    ///
+    /// Unlike [`ResourceHandle::set_object_id`], this keeps the object ID itself, which must already exist.
+    pub async fn set_object_id_extended(&self, info: ObjectIdExtendedInfo) -> crate::Result<()> {
+        self.fsctl(SetObjectIdExtendedRequest(info)).await?;
+        Ok(())
+    }
+
+    /// Deletes the object ID of the current file. Does nothing if the file has no object ID.
+    pub async fn delete_object_id(&self) -> crate::Result<()> {
+        match self.fsctl(DeleteObjectIdRequest(())).await {
+            Ok(_) | Err(Error::ServerError(Status::ObjectIdNotFound, _)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the reparse point of the current file, or `None` if the file is not a reparse point.
+    ///
+    /// To query the reparse point of a symbolic link rather than its target,
+    /// the resource must be opened with [`FileCreateArgs::with_open_reparse_point`].
+    pub async fn get_reparse_point(&self) -> crate::Result<Option<ReparseDataBuffer>> {
+        match self
+            .fsctl_with_options(
+                GetReparsePointRequest(()),
+                ReparseDataBuffer::MAX_SIZE as u32,
+            )
+            .await
+        {
+            Ok(buffer) => Ok(Some(buffer)),
+            Err(Error::ServerError(Status::NotAReparsePoint, _)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sets the reparse point of the current file, e.g. making it a symbolic link.
+    ///
+    /// The resource should be opened with [`FileCreateArgs::with_open_reparse_point`],
+    /// and a symbolic link to a directory must be set on a directory.
+    /// # Notes
+    /// * Windows servers require the user to hold the create symbolic link privilege
+    ///   (`SeCreateSymbolicLinkPrivilege`) to set symbolic links.
+    pub async fn set_reparse_point(&self, buffer: ReparseDataBuffer) -> crate::Result<()> {
+        self.fsctl(SetReparsePointRequest::from(buffer)).await?;
+        Ok(())
+    }
+
+    /// (Internal) Sends an FSCTL message, also accepting responses of `status` that carry an output buffer,
+    /// such as `STATUS_INVALID_PARAMETER` responses of `FSCTL_SRV_COPYCHUNK`.
+    ///
+    /// Returns the status of the response, along with its output.
+    #[maybe_async]
+    pub(crate) async fn _fsctl_with_status<T: FsctlRequest>(
+        &self,
+        request: T,
+        max_output_response: u32,
+        status: &[Status],
+    ) -> crate::Result<(u32, T::Response)> {
+        const NO_INPUT_IN_RESPONSE: u32 = 0;
+        let (status, response) = self
+            ._ioctl_with_status(
+                T::FSCTL_CODE as u32,
+                request.into(),
+                NO_INPUT_IN_RESPONSE,
+                max_output_response,
+                IoctlRequestFlags::new().with_is_fsctl(true),
+                status,
+            )
+            .await?;
+        Ok((status, response.parse_fsctl::<T::Response>()?))
+    }
+
     /// (Internal)
     #[maybe_async]
     async fn _ioctl(
@@ -651,7 +1443,32 @@ impl ResourceHandle {
         max_out: u32,
         flags: IoctlRequestFlags,
     ) -> crate::Result<IoctlResponse> {
-        let result = self
+        let (_, response) = self
+            ._ioctl_with_status(
+                ctl_code,
+                req_data,
+                max_in,
+                max_out,
+                flags,
+                &[Status::Success],
+            )
+            .await?;
+        Ok(response)
+    }
+
+    /// (Internal)
+    #[maybe_async]
+    async fn _ioctl_with_status(
+        &self,
+        ctl_code: u32,
+        req_data: IoctlReqData,
+        max_in: u32,
+        max_out: u32,
+        flags: IoctlRequestFlags,
+        status: &[Status],
+    ) -> crate::Result<(u32, IoctlResponse)> {
+        let max_out = self.clamp_buffer_size(max_out as usize, BufferLimit::Transact)?;
+        let response = self
             .handler
             .send_recvo(
                 RequestContent::Ioctl(IoctlRequest {
@@ -662,13 +1479,15 @@ impl ResourceHandle {
                     flags,
                     buffer: req_data,
                 }),
-                ReceiveOptions::new().with_allow_async(true),
+                ReceiveOptions::new().with_status(status),
             )
-            .await?
-            .message
-            .content
-            .to_ioctl()?;
-        Ok(result)
+            .await?;
+        let status = response.message.header.status;
+        match response.message.content {
+            // An accepted error status may still come without an output buffer.
+            ResponseContent::Error(error) => Err(Error::server_status(status, Some(error))),
+            content => Ok((status, content.to_ioctl()?)),
+        }
     }
 
     /// Queries the file system information for the current file.
@@ -737,6 +1556,275 @@ impl ResourceHandle {
         .await
     }
 
+    /// Sets the times of the resource, leaving the unspecified ones unchanged.
+    ///
+    /// The times are set using [`FileBasicInformation`], where zero times and attributes mean "unchanged".
+    /// The change time is left for the server to maintain.
+    ///
+    /// ## Arguments
+    /// * `created` - The creation time.
+    /// * `accessed` - The last access time.
+    /// * `modified` - The last write time.
+    ///
+    /// ## Notes
+    /// * The resource must have been opened with [`FileAccessMask::file_write_attributes`] access.
+    /// * Times are truncated to 100 nanoseconds. Times before January 1, 1601 are left unchanged.
+    pub async fn set_times(
+        &self,
+        created: Option<SystemTime>,
+        accessed: Option<SystemTime>,
+        modified: Option<SystemTime>,
+    ) -> crate::Result<()> {
+        let to_file_time = |time: Option<SystemTime>| time.map(FileTime::from).unwrap_or_default();
+        self.set_basic_info(FileBasicInformation {
+            creation_time: to_file_time(created),
+            last_access_time: to_file_time(accessed),
+            last_write_time: to_file_time(modified),
+            change_time: FileTime::ZERO,
+            file_attributes: FileAttributes::new(),
+        })
+        .await
+    }
+
+    /// Sets the attributes of the resource, e.g. [`readonly`][FileAttributes::readonly],
+    /// [`hidden`][FileAttributes::hidden] or [`archive`][FileAttributes::archive], leaving its times unchanged.
+    ///
+    /// The attributes replace the current ones. Since empty attributes mean "unchanged",
+    /// clear all the attributes of a file using [`FileAttributes::with_normal`].
+    ///
+    /// ## Notes
+    /// * The resource must have been opened with [`FileAccessMask::file_write_attributes`] access.
+    /// * The [`directory`][FileAttributes::directory] attribute of a directory may not be cleared.
+    pub async fn set_attributes(&self, attributes: FileAttributes) -> crate::Result<()> {
+        self.set_basic_info(FileBasicInformation {
+            creation_time: FileTime::ZERO,
+            last_access_time: FileTime::ZERO,
+            last_write_time: FileTime::ZERO,
+            change_time: FileTime::ZERO,
+            file_attributes: attributes,
+        })
+        .await
+    }
+
+    async fn set_basic_info(&self, info: FileBasicInformation) -> crate::Result<()> {
+        if !self.access.file_write_attributes() {
+            return Err(Error::MissingPermissions(format!(
+                "setting the times or attributes of {}: the handle lacks file_write_attributes",
+                self.name
+            )));
+        }
+        self.set_info(info).await
+    }
+
+    /// Marks the resource for deletion using [`FileDispositionInformationEx`], with the specified `flags`.
+    ///
+    /// With [`FileDispositionFlags::posix_semantics`], the name of the resource is removed as soon as this handle is closed,
+    /// even if other handles to it are still open. Only file systems with
+    /// [`FileSystemAttributes::supports_posix_unlink_rename`] support that, which is queryable through
+    /// [`FileFsAttributeInformation`]. Otherwise, use [`FileDispositionInformation`] with [`ResourceHandle::set_info`].
+    ///
+    /// ## Notes
+    /// * The resource must have been opened with [`FileAccessMask::delete`] access.
+    pub async fn delete_on_close_ex(&self, flags: FileDispositionFlags) -> crate::Result<()> {
+        if !self.access.delete() {
+            return Err(Error::MissingPermissions(format!(
+                "deleting {}: the handle lacks delete",
+                self.name
+            )));
+        }
+        self.set_info(FileDispositionInformationEx { flags }).await
+    }
+
+    /// Renames the resource, keeping it in the same directory.
+    ///
+    /// To rename a resource into another directory, use [`ResourceHandle::move_to`].
+    ///
+    /// ## Arguments
+    /// * `new_name` - The new name of the resource, without any directory separators.
+    ///   It may differ from the current name only in case.
+    /// * `options` - See [`RenameOptions`].
+    ///
+    /// ## Notes
+    /// * The resource must have been opened with [`FileAccessMask::delete`] access.
+    /// * [`ResourceHandle::name`] keeps returning the name the resource was opened with.
+    /// * Resources opened by their file ID have no known directory, so this fails with [`Error::InvalidState`].
+    pub async fn rename(&self, new_name: &str, options: &RenameOptions) -> crate::Result<()> {
+        if new_name.is_empty() || new_name.contains(['\\', '/']) {
+            return Err(Error::InvalidArgument(format!(
+                "Invalid new name for {}: {new_name:?}",
+                self.name
+            )));
+        }
+        if self.by_id.is_some() {
+            return Err(Error::InvalidState(format!(
+                "Cannot rename {} relative to its directory, since it was opened by its ID. Use move_to instead.",
+                self.name
+            )));
+        }
+        let new_path = match self.name.rsplit_once('\\') {
+            Some((parent, _)) => format!("{parent}\\{new_name}"),
+            None => new_name.to_string(),
+        };
+        self.move_to(&new_path, options).await
+    }
+
+    /// Moves the resource to another path on the same share, possibly in another directory.
+    ///
+    /// ## Arguments
+    /// * `new_path` - The new path of the resource, relative to the root of the share
+    ///   (e.g. `dir\sub\file.txt`). Forward slashes are converted to backslashes.
+    ///   The destination directory must exist.
+    /// * `options` - See [`RenameOptions`].
+    ///
+    /// ## Notes
+    /// * The resource must have been opened with [`FileAccessMask::delete`] access.
+    /// * Resources can not be moved across shares. Use [`Client::rename`][crate::Client::rename] with UNC paths,
+    ///   which checks that.
+    /// * [`ResourceHandle::name`] keeps returning the name the resource was opened with.
+    pub async fn move_to(&self, new_path: &str, options: &RenameOptions) -> crate::Result<()> {
+        let new_path = Self::_share_relative_path(new_path);
+        let new_path = new_path.as_str();
+        if new_path.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "Cannot move {} to the root of the share",
+                self.name
+            )));
+        }
+        if !self.access.delete() {
+            return Err(Error::MissingPermissions(format!(
+                "renaming {}: the handle lacks delete",
+                self.name
+            )));
+        }
+
+        let result = match options.mode {
+            RenameMode::Ex(flags) => {
+                let result = self
+                    .set_info(FileRenameInformationEx {
+                        flags: flags.with_replace_if_exists(options.replace_if_exists),
+                        root_directory: 0,
+                        file_name: new_path.into(),
+                    })
+                    .await;
+                match result {
+                    Err(Error::ServerError(
+                        status @ (Status::InvalidInfoClass
+                        | Status::InvalidParameter
+                        | Status::NotSupported),
+                        _,
+                    )) => {
+                        log::debug!(
+                            "Renaming {} with FileRenameInformationEx failed with {status}, falling back to classic rename",
+                            self.name
+                        );
+                        None
+                    }
+                    result => Some(result),
+                }
+            }
+            RenameMode::Classic => None,
+        };
+        let result = match result {
+            Some(result) => result,
+            None => {
+                self.set_info(FileRenameInformation {
+                    replace_if_exists: options.replace_if_exists.into(),
+                    root_directory: 0,
+                    file_name: new_path.into(),
+                })
+                .await
+            }
+        };
+        result.map_err(|e| match e {
+            Error::ServerError(Status::ObjectNameCollision, _) => Error::ObjectNameCollision {
+                path: new_path.to_string(),
+            },
+            e => e,
+        })?;
+        log::debug!("Renamed {} to {new_path}", self.name);
+        Ok(())
+    }
+
+    /// Creates a hard link to the file, at another path on the same share.
+    ///
+    /// ## Arguments
+    /// * `new_path` - The path of the new link, relative to the root of the share
+    ///   (e.g. `dir\sub\link.txt`). Forward slashes are converted to backslashes.
+    ///   The destination directory must exist.
+    /// * `replace_if_exists` - Whether to replace an existing file at `new_path`.
+    ///   If not set, and such a file exists, fails with [`Error::ObjectNameCollision`].
+    ///
+    /// ## Notes
+    /// * Links may only be created on the volume of the file. Otherwise, this fails with [`Error::NotSameDevice`].
+    /// * Directories can not be linked, and some file systems do not support hard links at all.
+    /// * Both names refer to the same file, which has the same file ID (see [`FileInternalInformation`]).
+    pub async fn hardlink_to(&self, new_path: &str, replace_if_exists: bool) -> crate::Result<()> {
+        let new_path = Self::_share_relative_path(new_path);
+        if new_path.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "Cannot link {} to the root of the share",
+                self.name
+            )));
+        }
+
+        self.set_info(FileLinkInformation {
+            replace_if_exists: replace_if_exists.into(),
+            root_directory: 0,
+            file_name: new_path.as_str().into(),
+        })
+        .await
+        .map_err(|e| match e {
+            Error::ServerError(Status::ObjectNameCollision, _) => Error::ObjectNameCollision {
+                path: new_path.clone(),
+            },
+            Error::ServerError(Status::NotSameDevice, _) => Error::NotSameDevice {
+                path: new_path.clone(),
+            },
+            e => e,
+        })?;
+        log::debug!("Linked {} to {new_path}", self.name);
+        Ok(())
+    }
+
+    /// Returns a path relative to the root of the share, as the names of [`FileRenameInformation`]
+    /// and [`FileLinkInformation`] are when RootDirectory is zero,
+    /// which is the only value allowed over the network (MS-SMB2 2.2.39).
+    fn _share_relative_path(path: &str) -> String {
+        path.replace('/', "\\").trim_start_matches('\\').to_string()
+    }
+
+    /// Flushes the resource, making the server commit its cached data to stable storage.
+    ///
+    /// Flushing a directory is supported by some servers and file systems only.
+    ///
+    /// The handle must have been opened with write or append access.
+    /// Otherwise, this fails with [`Error::MissingPermissions`], without sending a request.
+    pub async fn flush(&self) -> crate::Result<()> {
+        if !self.access.file_write_data() && !self.access.file_append_data() {
+            return Err(Error::MissingPermissions(format!(
+                "flushing {}: the handle lacks write or append data",
+                self.name
+            )));
+        }
+        Self::send_flush(&self.handler, self.file_id()?).await?;
+        log::debug!("Flushed {}.", self.name);
+        Ok(())
+    }
+
+    /// Sends a flush request for the file ID.
+    pub(crate) async fn send_flush(
+        handler: &HandlerReference<ResourceMessageHandle>,
+        file_id: FileId,
+    ) -> crate::Result<()> {
+        handler
+            .send_recv(FlushRequest { file_id }.into())
+            .await?
+            .message
+            .content
+            .to_flush()?;
+        Ok(())
+    }
+
     /// Sets the file system information for the current file.
     /// # Type Parameters
     /// * `T` - The type of information to set. Must implement the [SetFileSystemInfoValue] trait.
@@ -758,23 +1846,75 @@ impl ResourceHandle {
         .await
     }
 
-    /// Sets the file system information for the current file.
+    /// Sets the security descriptor of the current file.
+    ///
+    /// The handle must have been opened with the access [`SecurityInformation::set_access`] returns:
+    /// [`FileAccessMask::write_owner`] for the owner and group, [`FileAccessMask::write_dacl`] for the DACL,
+    /// and [`FileAccessMask::access_system_security`] for the SACL (see [`FileCreateArgs::with_system_security`]).
+    /// Otherwise, this fails with [`Error::MissingPermissions`], without sending a request.
     /// # Arguments
     /// * `info` - The information to set - a [SecurityDescriptor].
-    /// * `additional_info` - The information that is set on the security descriptor.
+    /// * `security_info` - The parts of `info` that are set on the security descriptor.
     pub async fn set_security_info(
         &self,
         info: SecurityDescriptor,
-        additional_info: AdditionalInfo,
+        security_info: SecurityInformation,
     ) -> crate::Result<()> {
+        Self::check_security_access(
+            "set",
+            security_info,
+            security_info.set_access(),
+            self.access,
+        )?;
         self.set_info_common(
             info,
             SetInfoClass::Security(Default::default()),
-            additional_info,
+            security_info.into(),
         )
         .await
     }
 
+    /// Fails with [`Error::MissingPermissions`] if the `granted` access of the handle
+    /// does not cover the access `required` to `operation` the `security_info` parts of its descriptor.
+    fn check_security_access(
+        operation: &str,
+        security_info: SecurityInformation,
+        required: FileAccessMask,
+        granted: FileAccessMask,
+    ) -> crate::Result<()> {
+        let missing = [
+            (
+                required.read_control() && !granted.read_control(),
+                "read_control",
+            ),
+            (required.write_dacl() && !granted.write_dacl(), "write_dacl"),
+            (
+                required.write_owner() && !granted.write_owner(),
+                "write_owner",
+            ),
+            (
+                required.access_system_security() && !granted.access_system_security(),
+                "access_system_security",
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(missing, name)| missing.then_some(name))
+        .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        // The SACL is only ever accessible by opening the file with SeSecurityPrivilege.
+        let hint = if required.access_system_security() && !granted.access_system_security() {
+            " (open the file with FileCreateArgs::with_system_security, which requires SeSecurityPrivilege)"
+        } else {
+            ""
+        };
+        Err(Error::MissingPermissions(format!(
+            "{operation} of {security_info} security information: the handle lacks {}{hint}",
+            missing.join(", ")
+        )))
+    }
+
     /// (Internal)
     ///
     /// Sends a close request to the server for the given file ID.
@@ -843,14 +1983,10 @@ impl ResourceHandle {
     #[maybe_async]
     #[inline]
     pub async fn send_cancel(&self, msg_ids: &AsyncMessageIds) -> crate::Result<SendMessageResult> {
-        let mut outgoing_message = OutgoingMessage::new(CancelRequest {}.into());
-        outgoing_message.message.header.message_id = msg_ids.msg_id.load(Ordering::SeqCst);
-        outgoing_message
-            .message
-            .header
-            .to_async(msg_ids.async_id.load(Ordering::SeqCst));
-
-        self.handler.sendo(outgoing_message).await
+        let msg_id = msg_ids.msg_id.load(Ordering::SeqCst);
+        self.handler
+            .sendo(PendingRequest::make_cancel(msg_id, msg_ids, None))
+            .await
     }
 
     /// Returns whether current resource is opened from the same tree as the other resource.
@@ -867,7 +2003,7 @@ impl ResourceHandle {
     }
 }
 
-struct ResourceMessageHandle {
+pub(crate) struct ResourceMessageHandle {
     upstream: Upstream,
 }
 
@@ -889,13 +2025,121 @@ impl MessageHandler for ResourceMessageHandle {
         self.upstream.sendo(msg).await
     }
 
+    /// Receives the response of a request, cancelling the request on the server
+    /// if the receive times out, is cancelled, or is dropped before the response is received.
     #[maybe_async]
-    #[inline]
     async fn recvo(
         &self,
-        options: crate::msg_handler::ReceiveOptions<'_>,
+        mut options: crate::msg_handler::ReceiveOptions<'_>,
     ) -> crate::Result<crate::msg_handler::IncomingMessage> {
-        self.upstream.recvo(options).await
+        let async_msg_ids = options
+            .async_msg_ids
+            .get_or_insert_with(Default::default)
+            .clone();
+        let pending = PendingRequest {
+            upstream: self.upstream.clone(),
+            msg_id: options.msg_id,
+            channel_id: options.channel_id,
+            async_msg_ids,
+            armed: true,
+        };
+        let result = self.upstream.recvo(options).await;
+        match &result {
+            Err(Error::OperationTimeout(..) | Error::Timeout(..) | Error::Cancelled(_)) => {
+                pending.cancel().await
+            }
+            _ => pending.disarm(),
+        }
+        result
+    }
+
+    #[maybe_async]
+    #[inline]
+    async fn sendo_compound(
+        &self,
+        msgs: Vec<crate::msg_handler::OutgoingMessage>,
+    ) -> crate::Result<Vec<crate::msg_handler::SendMessageResult>> {
+        self.upstream.sendo_compound(msgs).await
+    }
+}
+
+/// A request of a resource, whose response is being awaited.
+///
+/// Unless disarmed once the response is received, the request is cancelled (MS-SMB2 3.2.4.24) when dropped,
+/// since otherwise it would remain pending on the server.
+struct PendingRequest {
+    upstream: Upstream,
+    msg_id: u64,
+    channel_id: Option<u32>,
+    /// Set by the worker, if an interim response is received for the request.
+    async_msg_ids: Arc<AsyncMessageIds>,
+    armed: bool,
+}
+
+#[maybe_async(AFIT)]
+impl PendingRequest {
+    /// Returns a cancel request for the request with `msg_id`.
+    ///
+    /// If an interim response was received for the request, its async ID from `async_msg_ids` is used.
+    fn make_cancel(
+        msg_id: u64,
+        async_msg_ids: &AsyncMessageIds,
+        channel_id: Option<u32>,
+    ) -> OutgoingMessage {
+        let mut msg = OutgoingMessage::new(CancelRequest {}.into());
+        msg.has_response = false;
+        msg.channel_id = channel_id;
+        msg.message.header.message_id = msg_id;
+        if async_msg_ids.msg_id.load(Ordering::SeqCst) == msg_id {
+            msg.message
+                .header
+                .to_async(async_msg_ids.async_id.load(Ordering::SeqCst));
+        }
+        msg
+    }
+
+    /// Sends a cancel request for the request.
+    async fn cancel(mut self) {
+        self.armed = false;
+        Self::send_cancel(&self.upstream, self._make_cancel()).await;
+    }
+
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+
+    fn _make_cancel(&self) -> OutgoingMessage {
+        Self::make_cancel(self.msg_id, &self.async_msg_ids, self.channel_id)
+    }
+
+    async fn send_cancel(upstream: &Upstream, msg: OutgoingMessage) {
+        let msg_id = msg.message.header.message_id;
+        log::debug!("Cancelling pending request {msg_id}.");
+        if let Err(e) = upstream.sendo(msg).await {
+            log::debug!("Failed to cancel pending request {msg_id}: {e}");
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let upstream = self.upstream.clone();
+        let msg = self._make_cancel();
+        tokio::task::spawn(async move { Self::send_cancel(&upstream, msg).await });
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        // Blocking receives are not dropped while waiting, unless unwinding.
+        if self.armed {
+            Self::send_cancel(&self.upstream, self._make_cancel());
+        }
     }
 }
 
@@ -935,3 +2179,518 @@ impl Drop for ResourceHandle {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use smb_fscc::{FileAccessMask, FileAttributes};
+    use smb_msg::{CreateOptions, SecurityInformation};
+
+    use super::{FileCreateArgs, PendingRequest, Resource, ResourceHandle};
+    use crate::{Error, msg_handler::AsyncMessageIds};
+
+    #[test]
+    fn test_make_cancel() {
+        // Before an interim response is received, the request is cancelled by its message ID only.
+        let async_msg_ids = AsyncMessageIds::default();
+        let cancel = PendingRequest::make_cancel(7, &async_msg_ids, Some(2));
+        assert!(cancel.message.content.as_cancel().is_ok());
+        assert!(!cancel.has_response);
+        assert_eq!(cancel.channel_id, Some(2));
+        assert_eq!(cancel.message.header.message_id, 7);
+        assert!(!cancel.message.header.flags.async_command());
+
+        // Afterwards, by the async ID of the interim response.
+        async_msg_ids.set(7, 0x1234);
+        let cancel = PendingRequest::make_cancel(7, &async_msg_ids, None);
+        assert_eq!(cancel.message.header.message_id, 7);
+        assert!(cancel.message.header.flags.async_command());
+        assert_eq!(cancel.message.header.async_id, Some(0x1234));
+
+        // The async ID of another request is not used.
+        let cancel = PendingRequest::make_cancel(9, &async_msg_ids, None);
+        assert!(!cancel.message.header.flags.async_command());
+    }
+
+    #[test]
+    fn test_backup_presets() {
+        let backup = FileCreateArgs::for_backup();
+        assert!(backup.options.open_for_backup_intent());
+        assert!(backup.options.sequential_only());
+        assert!(backup.desired_access.read_control());
+        assert!(!backup.desired_access.access_system_security());
+        assert_eq!(backup.required_privilege(), Some("SeBackupPrivilege"));
+
+        let restore = FileCreateArgs::for_restore();
+        assert!(restore.options.open_for_backup_intent());
+        assert!(restore.desired_access.write_dacl());
+        assert!(restore.desired_access.write_owner());
+        assert_eq!(restore.required_privilege(), Some("SeRestorePrivilege"));
+
+        let backup = FileCreateArgs::for_backup().with_system_security(true);
+        assert!(backup.desired_access.access_system_security());
+        assert_eq!(backup.required_privilege(), Some("SeSecurityPrivilege"));
+        let backup = backup.with_system_security(false);
+        assert_eq!(backup.required_privilege(), Some("SeBackupPrivilege"));
+
+        let mut args = FileCreateArgs::make_open_existing(FileAccessMask::full_control());
+        assert_eq!(args.required_privilege(), None);
+        args.options = CreateOptions::new().with_open_for_backup_intent(true);
+        assert_eq!(args.required_privilege(), Some("SeRestorePrivilege"));
+    }
+
+    #[test]
+    fn test_with_create_options() {
+        let args = FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new())
+            .with_write_through(true)
+            .with_create_options(
+                CreateOptions::new()
+                    .with_non_directory_file(true)
+                    .with_no_intermediate_buffering(true),
+            )
+            .unwrap();
+        assert!(args.options.write_through());
+        assert!(args.options.non_directory_file());
+        assert!(args.options.no_intermediate_buffering());
+        let args = args.with_write_through(false);
+        assert!(!args.options.write_through());
+        assert!(args.options.no_intermediate_buffering());
+
+        let invalid = |args: FileCreateArgs, options: CreateOptions| {
+            matches!(
+                args.with_create_options(options),
+                Err(Error::InvalidArgument(_))
+            )
+        };
+        let dir = CreateOptions::new().with_directory_file(true);
+        let open = || FileCreateArgs::make_open_existing(FileAccessMask::standard_read());
+        assert!(invalid(
+            open().with_create_options(dir).unwrap(),
+            CreateOptions::new().with_non_directory_file(true)
+        ));
+        assert!(invalid(
+            FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new()),
+            dir
+        ));
+        assert!(invalid(
+            open(),
+            CreateOptions::new().with_delete_on_close(true)
+        ));
+        assert!(invalid(
+            open(),
+            CreateOptions::new().with_open_by_file_id(true)
+        ));
+        // Full control includes the delete access.
+        let args = FileCreateArgs::make_create_new(FileAttributes::new(), CreateOptions::new())
+            .with_create_options(dir.with_delete_on_close(true))
+            .unwrap();
+        assert!(args.options.directory_file());
+    }
+
+    #[test]
+    fn test_granted_access() {
+        // The maximal access of a backup open is computed from the DACL, which may grant nothing.
+        let granted = Resource::granted_access(
+            FileAccessMask::new(),
+            FileCreateArgs::for_backup()
+                .with_system_security(true)
+                .desired_access,
+        );
+        assert!(granted.file_read_data());
+        assert!(granted.read_control());
+        assert!(granted.access_system_security());
+        assert!(!granted.file_write_data());
+
+        // Generic rights are mapped, and are not kept as-is.
+        let granted = Resource::granted_access(
+            FileAccessMask::new().with_file_read_attributes(true),
+            FileAccessMask::new()
+                .with_generic_write(true)
+                .with_maximum_allowed(true),
+        );
+        assert_eq!(
+            granted,
+            FileAccessMask::standard_write().with_file_read_attributes(true)
+        );
+    }
+
+    #[test]
+    fn test_security_access_validation() {
+        let read = FileAccessMask::new().with_read_control(true);
+        let dacl_rw = read.with_write_dacl(true);
+        let system = read.with_access_system_security(true);
+        let restore = FileCreateArgs::for_restore().desired_access;
+        let sacl = SecurityInformation::new().with_sacl(true);
+
+        for (info, granted, query_ok, set_ok) in [
+            (SecurityInformation::dacl_only(), read, true, false),
+            (SecurityInformation::dacl_only(), dacl_rw, true, true),
+            (
+                SecurityInformation::owner_group_dacl(),
+                dacl_rw,
+                true,
+                false,
+            ),
+            (
+                SecurityInformation::owner_group_dacl(),
+                restore,
+                false,
+                true,
+            ),
+            (sacl, read, false, false),
+            (sacl, system, true, true),
+            (
+                SecurityInformation::new(),
+                FileAccessMask::new(),
+                true,
+                true,
+            ),
+            (
+                SecurityInformation::new().with_backup(true),
+                FileAccessMask::full_control(),
+                false,
+                false,
+            ),
+        ] {
+            for (operation, required, ok) in [
+                ("query", info.query_access(), query_ok),
+                ("set", info.set_access(), set_ok),
+            ] {
+                let result =
+                    ResourceHandle::check_security_access(operation, info, required, granted);
+                assert_eq!(result.is_ok(), ok, "{operation} of {info} with {granted:?}");
+            }
+        }
+
+        let Err(Error::MissingPermissions(message)) = ResourceHandle::check_security_access(
+            "set",
+            SecurityInformation::owner_group_dacl(),
+            SecurityInformation::owner_group_dacl().set_access(),
+            read,
+        ) else {
+            panic!("Expected missing permissions");
+        };
+        assert_eq!(
+            message,
+            "set of owner | group | dacl security information: the handle lacks write_dacl, write_owner"
+        );
+
+        let Err(Error::MissingPermissions(message)) = ResourceHandle::check_security_access(
+            "query",
+            SecurityInformation::new().with_sacl(true),
+            SecurityInformation::new().with_sacl(true).query_access(),
+            read,
+        ) else {
+            panic!("Expected missing permissions");
+        };
+        assert_eq!(
+            message,
+            "query of sacl security information: the handle lacks access_system_security \
+            (open the file with FileCreateArgs::with_system_security, which requires SeSecurityPrivilege)"
+        );
+    }
+
+    #[test]
+    fn test_open_file_id_name_buffer() {
+        let name = super::OpenFileId::Id64(0x0011_2233_4455_6677).name_buffer();
+        assert_eq!(
+            name.iter().copied().collect::<Vec<u16>>(),
+            [0x6677, 0x4455, 0x2233, 0x0011]
+        );
+        assert_eq!(super::OpenFileId::from(1u128).name_buffer().len(), 8);
+    }
+
+    /// Link paths are sent relative to the share, and cross-volume links fail with a typed error.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_hardlink_to() {
+        use crate::test_util::*;
+        use smb_fscc::{FileAttributes, FileLinkInformation, SetFileInfo, SetFileInfoClass};
+        use smb_msg::{
+            Command, FileId, SetInfoClass, SetInfoData, SetInfoResponse, ShareFlags, Status,
+        };
+        use smb_transport::mock::MockStep;
+
+        const FILE_ID: FileId = FileId {
+            persistent: 0x31,
+            volatile: 0x32,
+        };
+        fn link_step(path: &'static str) -> MockStep {
+            MockStep::expect(Command::SetInfo).matching("links to the path", move |r| {
+                r.content.as_setinfo().is_ok_and(|set| {
+                    set.info_class == SetInfoClass::File(SetFileInfoClass::LinkInformation)
+                        && matches!(&set.data, SetInfoData::File(raw) if raw
+                        .parse(SetFileInfoClass::LinkInformation)
+                        .is_ok_and(|info| info == SetFileInfo::LinkInformation(FileLinkInformation {
+                            replace_if_exists: false.into(),
+                            root_directory: 0,
+                            file_name: path.into(),
+                        })))
+                })
+            })
+        }
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                MockStep::expect(Command::Create)
+                    .respond(create_response(FILE_ID, FileAttributes::new())),
+                link_step("dir\\link.txt").respond(SetInfoResponse {}),
+                link_step("other\\link.txt").respond_error(Status::NotSameDevice),
+                MockStep::expect(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::new());
+        let file = mock.tree.create("file", &args).await.unwrap().unwrap_file();
+        file.hardlink_to("/dir/link.txt", false).await.unwrap();
+        let result = file.hardlink_to("other\\link.txt", false).await;
+        assert!(matches!(
+            result,
+            Err(Error::NotSameDevice { path }) if path == "other\\link.txt"
+        ));
+        let result = file.hardlink_to("\\", false).await;
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+        file.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    /// Ex renames fall back to classic renames on servers that do not support them.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_rename_ex_fallback() {
+        use super::{RenameMode, RenameOptions};
+        use crate::test_util::*;
+        use smb_fscc::{
+            FileAttributes, FileRenameFlags, FileRenameInformation, FileRenameInformationEx,
+            SetFileInfo, SetFileInfoClass,
+        };
+        use smb_msg::{
+            Command, FileId, SetInfoClass, SetInfoData, SetInfoResponse, ShareFlags, Status,
+        };
+        use smb_transport::mock::MockStep;
+
+        const FILE_ID: FileId = FileId {
+            persistent: 0x41,
+            volatile: 0x42,
+        };
+        fn set_info_step(class: SetFileInfoClass, expected: SetFileInfo) -> MockStep {
+            MockStep::expect(Command::SetInfo).matching("renames the file", move |r| {
+                r.content.as_setinfo().is_ok_and(|set| {
+                    set.info_class == SetInfoClass::File(class)
+                        && matches!(&set.data, SetInfoData::File(raw) if raw
+                        .parse(class)
+                        .is_ok_and(|info| info == expected))
+                })
+            })
+        }
+        let rename_ex = || {
+            set_info_step(
+                SetFileInfoClass::RenameExInformation,
+                SetFileInfo::RenameExInformation(FileRenameInformationEx {
+                    flags: FileRenameFlags::new()
+                        .with_replace_if_exists(true)
+                        .with_posix_semantics(true)
+                        .with_ignore_readonly_attribute(true),
+                    root_directory: 0,
+                    file_name: "dir\\b.txt".into(),
+                }),
+            )
+        };
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                MockStep::expect(Command::Create)
+                    .respond(create_response(FILE_ID, FileAttributes::new())),
+                rename_ex().respond(SetInfoResponse {}),
+                rename_ex().respond_error(Status::InvalidInfoClass),
+                set_info_step(
+                    SetFileInfoClass::RenameInformation,
+                    SetFileInfo::RenameInformation(FileRenameInformation {
+                        replace_if_exists: true.into(),
+                        root_directory: 0,
+                        file_name: "dir\\b.txt".into(),
+                    }),
+                )
+                .respond(SetInfoResponse {}),
+                MockStep::expect(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_delete(true));
+        let file = mock
+            .tree
+            .create("dir\\a.txt", &args)
+            .await
+            .unwrap()
+            .unwrap_file();
+        let options = RenameOptions {
+            replace_if_exists: true,
+            mode: RenameMode::posix(),
+        };
+        file.rename("b.txt", &options).await.unwrap();
+        file.move_to("dir/b.txt", &options).await.unwrap();
+        file.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    /// Unspecified times and attributes are sent as zeros, which the server leaves unchanged.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_set_times_and_attributes() {
+        use crate::test_util::*;
+        use smb_dtyp::binrw_util::prelude::FileTime;
+        use smb_fscc::{FileBasicInformation, SetFileInfo, SetFileInfoClass};
+        use smb_msg::{Command, FileId, SetInfoData, SetInfoResponse, ShareFlags};
+        use smb_transport::mock::MockStep;
+        use std::time::{Duration, SystemTime};
+
+        const FILE_ID: FileId = FileId {
+            persistent: 0x45,
+            volatile: 0x46,
+        };
+        const MODIFIED: u64 = 133818609802776324;
+        fn basic_info_step(expected: FileBasicInformation) -> MockStep {
+            let expected = SetFileInfo::BasicInformation(expected);
+            MockStep::expect(Command::SetInfo)
+                .matching("sets the basic information", move |r| {
+                    r.content.as_setinfo().is_ok_and(|set| {
+                        matches!(&set.data, SetInfoData::File(raw) if raw
+                        .parse(SetFileInfoClass::BasicInformation)
+                        .is_ok_and(|info| info == expected))
+                    })
+                })
+                .respond(SetInfoResponse {})
+        }
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                MockStep::expect(Command::Create)
+                    .respond(create_response(FILE_ID, FileAttributes::new())),
+                basic_info_step(FileBasicInformation {
+                    creation_time: FileTime::ZERO,
+                    last_access_time: FileTime::ZERO,
+                    last_write_time: MODIFIED.into(),
+                    change_time: FileTime::ZERO,
+                    file_attributes: FileAttributes::new(),
+                }),
+                basic_info_step(FileBasicInformation {
+                    creation_time: FileTime::ZERO,
+                    last_access_time: FileTime::ZERO,
+                    last_write_time: FileTime::ZERO,
+                    change_time: FileTime::ZERO,
+                    file_attributes: FileAttributes::new().with_readonly(true).with_archive(true),
+                }),
+                MockStep::expect(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+
+        let args = FileCreateArgs::make_open_existing(
+            FileAccessMask::new().with_file_write_attributes(true),
+        );
+        let file = mock.tree.create("file", &args).await.unwrap().unwrap_file();
+        let modified = SystemTime::from(FileTime::from(MODIFIED)) + Duration::from_nanos(50);
+        file.set_times(None, None, Some(modified)).await.unwrap();
+        file.set_attributes(FileAttributes::new().with_readonly(true).with_archive(true))
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_open_by_id() {
+        use crate::test_util::*;
+        use smb_fscc::FileAttributes;
+        use smb_msg::{Command, FileId, ShareFlags};
+        use smb_transport::mock::MockStep;
+
+        const FILE_ID: FileId = FileId {
+            persistent: 0x21,
+            volatile: 0x22,
+        };
+        let mock = connect_mock_tree(
+            // The ID is never treated as a DFS path.
+            ShareFlags::new().with_dfs(true),
+            [
+                MockStep::expect(Command::Create)
+                    .matching("opens by ID", |r| {
+                        !r.header.flags.dfs_operation()
+                            && r.content.as_create().is_ok_and(|create| {
+                                create.create_options.open_by_file_id()
+                                    && create.name.iter().copied().eq([0x0201, 0x0403, 0, 0])
+                            })
+                    })
+                    .respond(create_response(FILE_ID, FileAttributes::new())),
+                MockStep::expect(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_delete(true));
+        let file = mock
+            .tree
+            .open_by_id(0x0403_0201u64, &args)
+            .await
+            .unwrap()
+            .unwrap_file();
+        assert_eq!(
+            file.opened_by_id(),
+            Some(super::OpenFileId::Id64(0x0403_0201))
+        );
+        assert_eq!(file.name(), "<file ID 0x0000000004030201>");
+        // The directory of the file is unknown.
+        let result = file.rename("other.txt", &Default::default()).await;
+        assert!(matches!(result, Err(Error::InvalidState(_))));
+        file.close().await.unwrap();
+
+        // Durable handles can not be reclaimed without a name.
+        let durable_args = FileCreateArgs {
+            durable: Some(Default::default()),
+            ..FileCreateArgs::make_open_existing(FileAccessMask::new())
+        };
+        let result = mock.tree.open_by_id(1u64, &durable_args).await;
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+        mock.handle.assert_done();
+    }
+
+    /// A request that the server never responds to times out, and is cancelled on the server.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_request_timeout() {
+        use crate::test_util::*;
+        use smb_fscc::FileBasicInformation;
+        use smb_msg::{Command, FileId, ShareFlags};
+        use smb_transport::mock::MockStep;
+        use std::time::Duration;
+
+        const FILE_ID: FileId = FileId {
+            persistent: 0x51,
+            volatile: 0x52,
+        };
+        const REQUEST_TIMEOUT: Duration = Duration::from_millis(100);
+        let config = crate::ConnectionConfig {
+            request_timeout: Some(REQUEST_TIMEOUT),
+            ..mock_config()
+        };
+        let mock = connect_mock_tree_with_config(
+            ShareFlags::new(),
+            config,
+            [
+                MockStep::expect(Command::Create)
+                    .respond(create_response(FILE_ID, FileAttributes::new())),
+                // Never responds.
+                MockStep::expect(Command::QueryInfo),
+                MockStep::expect(Command::Cancel)
+                    .matching("cancels the query", |r| r.content.as_cancel().is_ok()),
+                MockStep::expect(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::new());
+        let file = mock.tree.create("file", &args).await.unwrap().unwrap_file();
+        let result = file.query_info::<FileBasicInformation>().await;
+        assert!(matches!(result, Err(Error::Timeout(Command::QueryInfo, _))));
+        // The connection remains usable.
+        file.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+}
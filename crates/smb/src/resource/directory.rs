@@ -1,13 +1,21 @@
 use super::ResourceHandle;
 use crate::Error;
+use crate::connection::connection_info::BufferLimit;
+use crate::connection::quirks::{self, Quirk};
 use crate::msg_handler::{MessageHandler, ReceiveOptions};
 use crate::sync_helpers::*;
 use maybe_async::*;
+use smb_dtyp::binrw_util::prelude::FileTime;
 use smb_fscc::*;
 use smb_msg::*;
 use std::ops::{Deref, DerefMut};
 use std::time::Duration;
 
+mod entry;
+mod iter;
+pub use entry::*;
+pub use iter::*;
+
 /// A directory resource on the server.
 /// This is used to query the directory for its contents,
 /// and may not be created directly -- but via [Resource][super::Resource], opened
@@ -21,6 +29,35 @@ pub struct Directory {
     query_lock: Mutex<()>,
 }
 
+/// The entries of a single query directory response, parsed lazily.
+type QueryDirectoryEntries<T> = ChainedItemReader<T, { QueryDirectoryInfo::CHAINED_ALIGNMENT }>;
+
+/// Options for [`Directory::query_with_options`].
+#[derive(Debug, Clone)]
+pub struct QueryDirectoryOptions {
+    /// The size of the buffer of each query directory request, in bytes.
+    /// Defaults to 64KiB, or to the negotiated max transact size if it is smaller.
+    pub buffer_size: Option<u32>,
+    /// Whether to yield the `.` and `..` entries of the directory.
+    ///
+    /// Servers include or omit these depending on the information class and the pattern,
+    /// so they are excluded by default (like [`std::fs::read_dir`]), by their name.
+    pub include_dot_entries: bool,
+}
+
+impl QueryDirectoryOptions {
+    pub const DEFAULT: Self = Self {
+        buffer_size: None,
+        include_dot_entries: false,
+    };
+}
+
+impl Default for QueryDirectoryOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
 #[maybe_async(AFIT)]
 impl Directory {
     pub fn new(handle: ResourceHandle) -> Self {
@@ -32,19 +69,42 @@ impl Directory {
         }
     }
 
+    /// An internal method that performs a query on the directory, and parses all the returned entries.
+    ///
+    /// See [`Directory::send_query_lazy`].
+    async fn send_query<T>(
+        &self,
+        pattern: &str,
+        flags: QueryDirectoryFlags,
+        buffer_size: u32,
+    ) -> crate::Result<Vec<T>>
+    where
+        T: QueryDirectoryInfoValue + for<'a> binrw::prelude::BinWrite<Args<'a> = ()>,
+    {
+        let mut buffer_size = buffer_size;
+        Ok(self
+            .send_query_lazy(pattern, flags, &mut buffer_size)
+            .await?
+            .collect::<binrw::BinResult<Vec<T>>>()?)
+    }
+
     /// An internal method that performs a query on the directory.
     /// # Arguments
     /// * `pattern` - The pattern to match against the file names in the directory. Use wildcards like `*` and `?` to match multiple files.
-    /// * `restart` - Whether to restart the scan or not. This is used to indicate whether this is the first query or not.
+    /// * `flags` - The query flags. `restart_scans` indicates whether this is the first query or not.
+    /// * `buffer_size` - The size of the query buffer. If the server fails the query since the buffer can't contain
+    ///   a single entry, the same query is sent again with a larger buffer (see [`Directory::grown_query_buffer_size`]),
+    ///   which does not change the position of the enumeration. The grown size is stored back, for later queries.
     /// # Returns
-    /// * A vector of [`QueryDirectoryInfoValue`] objects, containing the results of the query.
-    /// * If the query returned [`Status::NoMoreFiles`], an empty vector is returned.
-    async fn send_query<T>(
+    /// * The entries returned by the query, which are parsed from the response buffer only as the iterator reaches them.
+    /// * If the query returned [`Status::NoMoreFiles`], a finished iterator is returned.
+    ///   This is also the case for single-entry queries that match no entry.
+    async fn send_query_lazy<T>(
         &self,
         pattern: &str,
-        restart: bool,
-        buffer_size: u32,
-    ) -> crate::Result<Vec<T>>
+        flags: QueryDirectoryFlags,
+        buffer_size: &mut u32,
+    ) -> crate::Result<QueryDirectoryEntries<T>>
     where
         T: QueryDirectoryInfoValue + for<'a> binrw::prelude::BinWrite<Args<'a> = ()>,
     {
@@ -52,41 +112,60 @@ impl Directory {
             return Err(Error::MissingPermissions("file_list_directory".to_string()));
         }
 
-        debug_assert!(buffer_size <= self.conn_info.negotiation.max_transact_size);
-        if buffer_size > self.conn_info.negotiation.max_transact_size {
-            return Err(Error::InvalidArgument(format!(
-                "Buffer size {} exceeds maximum transact size {}",
-                buffer_size, self.conn_info.negotiation.max_transact_size
-            )));
-        }
+        *buffer_size = self.clamp_buffer_size(*buffer_size as usize, BufferLimit::Transact)?;
 
         log::debug!("Querying directory {}", self.handle.name());
 
-        let response = self
-            .handle
-            .send_receive(
-                QueryDirectoryRequest {
-                    file_information_class: T::CLASS_ID,
-                    flags: QueryDirectoryFlags::new().with_restart_scans(restart),
-                    file_index: 0,
-                    file_id: self.handle.file_id()?,
-                    output_buffer_length: buffer_size,
-                    file_name: pattern.into(),
+        let response = loop {
+            let response = self
+                .handle
+                .send_receive(
+                    QueryDirectoryRequest {
+                        file_information_class: T::CLASS_ID,
+                        flags,
+                        file_index: 0,
+                        file_id: self.handle.file_id()?,
+                        output_buffer_length: *buffer_size,
+                        file_name: pattern.into(),
+                    }
+                    .into(),
+                )
+                .await;
+            // Some servers report a buffer too small for a single entry with STATUS_BUFFER_OVERFLOW.
+            match response {
+                Err(Error::ServerError(Status::InfoLengthMismatch | Status::BufferOverflow, _)) => {
+                    let max_size = self.conn_info.negotiation.max_transact_size;
+                    match Self::grown_query_buffer_size(*buffer_size, max_size) {
+                        Some(grown) => {
+                            log::debug!(
+                                "Query buffer size {buffer_size} is too small for a directory entry, retrying with {grown}"
+                            );
+                            *buffer_size = grown;
+                        }
+                        None => {
+                            return Err(Error::BufferTooSmall {
+                                data_type: "directory information",
+                                required: None,
+                                provided: *buffer_size as usize,
+                            });
+                        }
+                    }
                 }
-                .into(),
-            )
-            .await;
+                response => break response,
+            }
+        };
 
         let response = match response {
             Ok(res) => res,
-            Err(Error::UnexpectedMessageStatus(Status::U32_NO_MORE_FILES)) => {
+            Err(Error::ServerError(Status::NoMoreFiles, _)) => {
                 log::debug!("No more files in directory");
-                return Ok(vec![]);
+                return Ok(QueryDirectoryEntries::new(vec![]));
             }
-            Err(Error::UnexpectedMessageStatus(Status::U32_INFO_LENGTH_MISMATCH)) => {
-                return Err(Error::InvalidArgument(format!(
-                    "Provided query buffer size {buffer_size} is too small to contain directory information"
-                )));
+            Err(Error::ServerError(Status::NoSuchFile | Status::ObjectNameNotFound, _))
+                if flags.return_single_entry() =>
+            {
+                log::debug!("No entry matches {pattern}");
+                return Ok(QueryDirectoryEntries::new(vec![]));
             }
             Err(e) => {
                 log::error!("Error querying directory: {e}");
@@ -94,15 +173,76 @@ impl Directory {
             }
         };
 
-        Ok(response
+        let entries = response
             .message
             .content
             .to_querydirectory()?
-            .read_output()?)
+            .into_output_iter::<T>();
+        if entries.is_finished() {
+            // The enumeration should end with STATUS_NO_MORE_FILES, but some servers return an empty result.
+            if !self.conn_info.quirks.detected(
+                Quirk::EmptySuccessEndsEnumeration,
+                "received an empty, successful query directory response",
+            ) {
+                return Err(Error::InvalidMessage(
+                    "Received an empty, successful query directory response".to_string(),
+                ));
+            }
+            log::trace!(
+                target: quirks::LOG_TARGET,
+                "Empty query directory response for {}, ending enumeration",
+                self.handle.name()
+            );
+        }
+        Ok(entries)
     }
 
     const QUERY_DIRECTORY_DEFAULT_BUFFER_SIZE: u32 = 0x10000;
 
+    /// Returns the buffer size to send a query again with, after the server failed it since a buffer of `buffer_size`
+    /// bytes can't contain a single entry: twice the size, up to `max_size`, or `None` if the buffer can't grow.
+    fn grown_query_buffer_size(buffer_size: u32, max_size: u32) -> Option<u32> {
+        (buffer_size < max_size).then(|| buffer_size.saturating_mul(2).clamp(1, max_size))
+    }
+
+    /// The default query buffer size, which never exceeds the negotiated max transact size.
+    fn default_query_buffer_size(&self) -> u32 {
+        Self::QUERY_DIRECTORY_DEFAULT_BUFFER_SIZE.min(self.conn_info.negotiation.max_transact_size)
+    }
+
+    /// The buffer size of [`Directory::query_entry`], which is large enough for a single entry with a maximum-length name.
+    const QUERY_ENTRY_BUFFER_SIZE: u32 = 0x1000;
+
+    /// Looks up a single entry of the directory by its name, without opening the entry itself.
+    ///
+    /// This sends a single query directory request, and does not change the last access time of the entry.
+    /// # Arguments
+    /// * `name` - The name of the entry in the directory. Wildcards are not allowed.
+    /// # Returns
+    /// * The information of the entry, or `None` if the directory has no such entry.
+    pub async fn query_entry<T>(&self, name: &str) -> crate::Result<Option<T>>
+    where
+        T: QueryDirectoryInfoValue + for<'a> binrw::prelude::BinWrite<Args<'a> = ()>,
+    {
+        if name.is_empty() || name.contains(['*', '?', '<', '>', '"', '\\']) {
+            return Err(Error::InvalidArgument(format!(
+                "Invalid directory entry name: {name:?}"
+            )));
+        }
+
+        let _guard = self.query_lock.lock().await?;
+        let items = self
+            .send_query::<T>(
+                name,
+                QueryDirectoryFlags::new()
+                    .with_restart_scans(true)
+                    .with_return_single_entry(true),
+                Self::QUERY_ENTRY_BUFFER_SIZE,
+            )
+            .await?;
+        Ok(items.into_iter().next())
+    }
+
     /// Asynchronously iterates over the directory contents, using the provided pattern and information type.
     /// # Arguments
     /// * `pattern` - The pattern to match against the file names in the directory. Use wildcards like `*` and `?` to match multiple files.
@@ -118,22 +258,26 @@ impl Directory {
     ///   create a new instance of this structure to query the directory again.
     /// * You must use [`futures_util::StreamExt`] to consume the stream.
     ///   See (<https://tokio.rs/tokio/tutorial/streams>) for more information on how to use streams.
+    /// * The `.` and `..` entries are not yielded, see [`QueryDirectoryOptions::include_dot_entries`].
     #[cfg(feature = "async")]
     pub fn query<'a, T>(
         this: &'a Arc<Self>,
         pattern: &str,
     ) -> impl Future<Output = crate::Result<iter_stream::QueryDirectoryStream<'a, T>>>
     where
-        T: QueryDirectoryInfoValue + for<'b> binrw::prelude::BinWrite<Args<'b> = ()> + Send,
+        T: QueryDirectoryInfoValue
+            + QueryDirectoryEntryName
+            + for<'b> binrw::prelude::BinWrite<Args<'b> = ()>
+            + Send,
     {
-        Self::query_with_options(this, pattern, Self::QUERY_DIRECTORY_DEFAULT_BUFFER_SIZE)
+        Self::query_with_options(this, pattern, &QueryDirectoryOptions::DEFAULT)
     }
 
     /// Asynchronously iterates over the directory contents, using the provided pattern and information type.
     /// # Arguments
     /// * `pattern` - The pattern to match against the file names in the directory. Use wildcards like `*` and `?` to match multiple files.
     /// * `info` - The information type to query. This is a trait object that implements the [`QueryDirectoryInfoValue`] trait.
-    /// * `buffer_size` - The size of the query buffer, in bytes.
+    /// * `options` - See [`QueryDirectoryOptions`].
     /// # Returns
     /// * An iterator over the directory contents, yielding [`QueryDirectoryInfoValue`] objects.
     /// # Returns
@@ -146,27 +290,29 @@ impl Directory {
     /// * You must use [`futures_util::StreamExt`] to consume the stream.
     ///   See [<https://tokio.rs/tokio/tutorial/streams>] for more information on how to use streams.
     /// * The actual buffer size that may be used depends on the negotiated transact size given by the server.
-    ///   In case of `buffer_size` > `max_transact_size`, the function would use the minimum, and log a warning.
+    ///   In case of [`QueryDirectoryOptions::buffer_size`] > `max_transact_size`, the function clamps the size with a warning, or fails,
+    ///   according to [`ConnectionConfig::buffer_size_policy`][crate::ConnectionConfig::buffer_size_policy].
     #[cfg(feature = "async")]
     pub async fn query_with_options<'a, T>(
         this: &'a Arc<Self>,
         pattern: &str,
-        buffer_size: u32,
+        options: &QueryDirectoryOptions,
     ) -> crate::Result<iter_stream::QueryDirectoryStream<'a, T>>
     where
-        T: QueryDirectoryInfoValue + for<'b> binrw::prelude::BinWrite<Args<'b> = ()> + Send,
+        T: QueryDirectoryInfoValue
+            + QueryDirectoryEntryName
+            + for<'b> binrw::prelude::BinWrite<Args<'b> = ()>
+            + Send,
     {
-        let max_allowed_buffer_size = this.conn_info.negotiation.max_transact_size;
-        if buffer_size > max_allowed_buffer_size {
-            log::warn!(
-                "Buffer size {} is larger than max transact size {}. Using minimum.",
-                buffer_size,
-                max_allowed_buffer_size
-            );
-        }
-        let buffer_size = buffer_size.min(max_allowed_buffer_size);
+        let buffer_size = this.query_buffer_size(options)?;
 
-        iter_stream::QueryDirectoryStream::new(this, pattern.to_string(), buffer_size).await
+        iter_stream::QueryDirectoryStream::new(
+            this,
+            pattern.to_string(),
+            buffer_size,
+            options.include_dot_entries,
+        )
+        .await
     }
 
     /// Synchronously iterates over the directory contents, using the provided pattern and information type.
@@ -178,36 +324,102 @@ impl Directory {
     /// * **IMPORTANT**: Calling this method BLOCKS ANY ADDITIONAL CALLS to this method on THIS structure instance.
     ///   Hence, you should not call this method on the same instance from multiple threads. This is for safety,
     ///   since SMB2 does not allow multiple queries on the same handle at the same time.
+    /// * The `.` and `..` entries are not yielded, see [`QueryDirectoryOptions::include_dot_entries`].
     #[cfg(not(feature = "async"))]
     pub fn query<'a, T>(
         &'a self,
         pattern: &str,
     ) -> crate::Result<iter_sync::QueryDirectoryIterator<'a, T>>
     where
-        T: QueryDirectoryInfoValue,
+        T: QueryDirectoryInfoValue + QueryDirectoryEntryName,
     {
-        Self::query_with_options(self, pattern, Self::QUERY_DIRECTORY_DEFAULT_BUFFER_SIZE)
+        Self::query_with_options(self, pattern, &QueryDirectoryOptions::DEFAULT)
     }
 
     /// Synchronously iterates over the directory contents, using the provided pattern and information type.
     /// # Arguments
     /// * `pattern` - The pattern to match against the file names in the directory. Use wildcards like `*` and `?` to match multiple files.
+    /// * `options` - See [`QueryDirectoryOptions`].
     /// # Returns
     /// * An iterator over the directory contents, yielding [`QueryDirectoryInfoValue`] objects.
     /// # Notes
     /// * **IMPORTANT**: Calling this method BLOCKS ANY ADDITIONAL CALLS to this method on THIS structure instance.
     ///   Hence, you should not call this method on the same instance from multiple threads. This is for safety,
     ///   since SMB2 does not allow multiple queries on the same handle at the same time.
+    /// * In case of [`QueryDirectoryOptions::buffer_size`] > `max_transact_size`, the function clamps the size with a warning, or fails,
+    ///   according to [`ConnectionConfig::buffer_size_policy`][crate::ConnectionConfig::buffer_size_policy].
     #[cfg(not(feature = "async"))]
     pub fn query_with_options<'a, T>(
         &'a self,
         pattern: &str,
-        buffer_size: u32,
+        options: &QueryDirectoryOptions,
     ) -> crate::Result<iter_sync::QueryDirectoryIterator<'a, T>>
     where
-        T: QueryDirectoryInfoValue,
+        T: QueryDirectoryInfoValue + QueryDirectoryEntryName,
+    {
+        let buffer_size = self.query_buffer_size(options)?;
+        iter_sync::QueryDirectoryIterator::new(
+            self,
+            pattern.to_string(),
+            buffer_size,
+            options.include_dot_entries,
+        )
+    }
+
+    /// Lists the directory contents, using the provided pattern and information type.
+    ///
+    /// Unlike [`query_with_options`][Self::query_with_options], this has the same signature whether crate feature `async`
+    /// is enabled or not, and returns [`DirectoryEntries`], which is a `Stream` in async builds,
+    /// and an [`Iterator`] in sync builds. Use [`DirectoryEntries::next_entry`] or [`DirectoryEntries::collect_entries`]
+    /// to consume it the same way in both.
+    /// # Arguments
+    /// * `pattern` - The pattern to match against the file names in the directory. Use wildcards like `*` and `?` to match multiple files.
+    /// * `options` - See [`QueryDirectoryOptions`].
+    /// # Notes
+    /// * The same restrictions as [`query_with_options`][Self::query_with_options] apply: while the returned entries exist,
+    ///   other queries of this directory instance block.
+    pub async fn query_directory<'a, T>(
+        this: &'a Arc<Self>,
+        pattern: &str,
+        options: &QueryDirectoryOptions,
+    ) -> crate::Result<DirectoryEntries<'a, T>>
+    where
+        T: QueryDirectoryInfoValue
+            + QueryDirectoryEntryName
+            + for<'b> binrw::prelude::BinWrite<Args<'b> = ()>
+            + Send
+            + Unpin,
     {
-        iter_sync::QueryDirectoryIterator::new(self, pattern.to_string(), buffer_size)
+        let inner = Self::query_with_options(this, pattern, options).await?;
+        Ok(DirectoryEntries { inner })
+    }
+
+    /// Lists the entries of the directory that match `pattern`, as [`DirectoryEntry`]s.
+    ///
+    /// This queries [`FileIdBothDirectoryInformation`] using [`query_directory`][Self::query_directory],
+    /// so the same notes apply. The `.` and `..` entries are skipped, unless
+    /// [`QueryDirectoryOptions::include_dot_entries`] is set.
+    /// # Arguments
+    /// * `pattern` - The pattern to match against the file names in the directory. Use wildcards like `*` and `?` to match multiple files.
+    /// * `options` - See [`QueryDirectoryOptions`].
+    pub async fn entries<'a>(
+        this: &'a Arc<Self>,
+        pattern: &str,
+        options: &QueryDirectoryOptions,
+    ) -> crate::Result<DirectoryListing<'a>> {
+        let inner =
+            Self::query_directory::<FileIdBothDirectoryInformation>(this, pattern, options).await?;
+        Ok(DirectoryListing { inner })
+    }
+
+    /// The buffer size of each query directory request, according to [`QueryDirectoryOptions::buffer_size`].
+    fn query_buffer_size(&self, options: &QueryDirectoryOptions) -> crate::Result<u32> {
+        match options.buffer_size {
+            Some(buffer_size) => {
+                self.clamp_buffer_size(buffer_size as usize, BufferLimit::Transact)
+            }
+            None => Ok(self.default_query_buffer_size()),
+        }
     }
 
     /// Watches the directory for changes.
@@ -218,6 +430,7 @@ impl Directory {
     /// * A vector of [`FileNotifyInformation`] objects, containing the changes that occurred.
     /// # Notes
     /// * This is a long-running operation, and will block until a result is received. See [`watch_timeout`][Self::watch_timeout] for a version that supports a timeout.
+    /// * When using crate feature `async`, dropping the returned future cancels the pending request on the server.
     pub async fn watch(
         &self,
         filter: NotifyFilter,
@@ -234,7 +447,7 @@ impl Directory {
     /// * A vector of [`FileNotifyInformation`] objects, containing the changes that occurred.
     /// # Notes
     /// * This is a long-running operation, and will block until a result is received or the provided timeout elapses.
-    ///  If the timeout elapses, an error of type [`Error::OperationTimeout`] is returned.
+    ///  If the timeout elapses, the pending request is cancelled on the server, and an error of type [`Error::Timeout`] is returned.
     /// * A similar method without timeout is available as [`watch`][Self::watch].
     pub async fn watch_timeout(
         &self,
@@ -254,6 +467,9 @@ impl Directory {
     #[cfg(feature = "async")]
     /// Watches the directory for changes, returning a [`Stream`][`futures_core::Stream`] of notifications.
     ///
+    /// The returned [`DirectoryChanges`] is an [`Iterator`] in sync builds, with the same calling convention.
+    /// Use [`DirectoryChanges::next_change`] to consume it the same way in both.
+    ///
     /// * See [`watch_stream_cancellable`][Self::watch_stream_cancellable] for a version that supports cancellation,
     ///  via a [`CancellationToken`].
     ///
@@ -264,22 +480,31 @@ impl Directory {
     /// * A stream of [`FileNotifyInformation`] objects, containing the changes that occurred.
     ///
     /// # Notes
-    /// Error handling in this stream is done by returning `Result<FileNotifyInformation>`.
+    /// * The change notification request is re-sent as soon as the previous one completes, so changes
+    ///   between batches of notifications are not missed.
+    /// * The stream ends once the server cleans up the watch (`STATUS_NOTIFY_CLEANUP`), e.g. when the directory is closed.
+    ///   Dropping the stream cancels the pending request.
+    /// * The stream holds a reference to the directory, so it may outlive `this`, e.g. when spawned as a task.
+    /// * Errors are yielded as `Err` items, and end the stream.
     pub fn watch_stream(
         this: &Arc<Self>,
         filter: NotifyFilter,
         recursive: bool,
-    ) -> crate::Result<impl futures_core::Stream<Item = crate::Result<FileNotifyInformation>>> {
+    ) -> crate::Result<ChangeNotifyStream> {
         Self::watch_stream_cancellable(this, filter, recursive, Default::default())
     }
 
     #[cfg(feature = "async")]
+    /// Watches the directory for changes, like [`watch_stream`][Self::watch_stream], until `cancel` is cancelled.
+    ///
+    /// Cancelling sends a cancel request for the pending change notification, and the stream ends once the server
+    /// acknowledges it.
     pub fn watch_stream_cancellable(
         this: &Arc<Self>,
         filter: NotifyFilter,
         recursive: bool,
         cancel: tokio_util::sync::CancellationToken,
-    ) -> crate::Result<impl futures_core::Stream<Item = crate::Result<FileNotifyInformation>>> {
+    ) -> crate::Result<ChangeNotifyStream> {
         // Since watching for notifications is more passive, this does not require the same level
         // of synchronization as querying the directory - since we won't DoS the server by sending
         // too many requests.
@@ -291,7 +516,7 @@ impl Directory {
         let (watch_tx, mut watch_rx) = tokio::sync::mpsc::channel(1024);
 
         let receive_options = ReceiveOptions::default()
-            .with_timeout(Duration::MAX)
+            .without_timeout()
             .with_async_msg_ids(Default::default());
 
         // Receive task is required to avoid race conditions.
@@ -397,46 +622,65 @@ impl Directory {
             }
         });
 
-        Ok(ReceiverStream::new(receiver))
+        Ok(DirectoryChanges {
+            inner: ReceiverStream::new(receiver),
+        })
     }
 
-    // TODO: Doc
-
+    /// Returns a [`DirectoryChanges`] iterator that watches the directory for changes.
+    /// It is a `Stream` in async builds, with the same calling convention.
+    ///
+    /// The change notification request is re-sent by a worker thread as soon as the previous one completes,
+    /// so changes between batches of notifications are not missed. The iterator ends once the server cleans up
+    /// the watch (`STATUS_NOTIFY_CLEANUP`), e.g. when the directory is closed, or once it is cancelled
+    /// using its [`NotifyDirectoryIteratorCanceller`]. Dropping the iterator cancels the pending request.
+    /// Errors are yielded as `Err` items, and end the iterator.
     #[cfg(feature = "multi_threaded")]
     pub fn watch_stream(
         this: &Arc<Self>,
         filter: NotifyFilter,
         recursive: bool,
-    ) -> crate::Result<
-        impl Iterator<Item = crate::Result<FileNotifyInformation>> + NotifyDirectoryIteratorCancellable,
-    > {
+    ) -> crate::Result<ChangeNotifyStream> {
         let cancel_handle = NotifyDirectoryIteratorCanceller::new(this);
-        iter_mtd::NotifyDirectoryIterator::new(cancel_handle, filter, recursive)
+        let inner = iter_mtd::NotifyDirectoryIterator::new(cancel_handle, filter, recursive)?;
+        Ok(DirectoryChanges { inner })
     }
 
-    /// Returns an iterator that watches the directory for changes.
+    /// Returns a [`DirectoryChanges`] iterator that watches the directory for changes.
+    /// It is a `Stream` in async builds, with the same calling convention.
+    ///
+    /// The change notification request is re-sent as soon as the previous batch of notifications is consumed.
+    /// The iterator ends once the server cleans up the watch (`STATUS_NOTIFY_CLEANUP`), e.g. when the directory is closed.
+    /// Errors are yielded as `Err` items, and end the iterator.
+    /// Single-threaded builds cannot cancel a pending request, so the iterator blocks until the next notification.
     #[cfg(feature = "single_threaded")]
     pub fn watch_stream(
         this: &Arc<Self>,
         filter: NotifyFilter,
         recursive: bool,
-    ) -> crate::Result<impl Iterator<Item = crate::Result<FileNotifyInformation>> + '_> {
-        // Simply watch in loop and chain the results.
-        let veci = std::iter::from_fn(move || {
-            match this
-                ._watch_options(filter, recursive, ReceiveOptions::default())
-                .into()
-            {
-                Ok(result) => Some(Ok(result)),
-                Err(e) => Some(Err(e)),
+    ) -> crate::Result<ChangeNotifyStream> {
+        let this = this.clone();
+        let mut done = false;
+        let batches = std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            match this._watch_options(filter, recursive, ReceiveOptions::default()) {
+                DirectoryWatchResult::Notifications(v) => Some(v.into_iter().map(Ok).collect()),
+                DirectoryWatchResult::Cleanup | DirectoryWatchResult::Cancelled => {
+                    log::debug!("Watch ended by server. Stopping iterator.");
+                    None
+                }
+                x => {
+                    done = true;
+                    let x: crate::Result<_> = x.into();
+                    Some(vec![Err(x.unwrap_err())])
+                }
             }
-        })
-        // Flatten the results into a single item iterator
-        .flat_map(|result| match result {
-            Ok(vec) => vec.into_iter().map(Ok).collect::<Vec<_>>().into_iter(),
-            Err(e) => vec![Err(e)].into_iter(),
         });
-        Ok(veci)
+        Ok(DirectoryChanges {
+            inner: Box::new(batches.flatten()),
+        })
     }
 
     /// (Internal) Watches the directory for changes, with an optional timeout.
@@ -446,7 +690,7 @@ impl Directory {
     /// * `timeout` - to set the timeout for the receive operation.
     /// * `async_msg_ids` - to allow async notifications.
     /// * `async_cancel` - to allow cancellation of the receive operation, when crate feature `async` is enabled.
-    async fn _watch_options(
+    pub(crate) async fn _watch_options(
         &self,
         filter: NotifyFilter,
         recursive: bool,
@@ -457,7 +701,10 @@ impl Directory {
                 "list_directory".to_string(),
             ));
         }
-        let output_buffer_length = self.calc_transact_size(None);
+        let output_buffer_length = match self.calc_transact_size(None) {
+            Ok(length) => length,
+            Err(e) => return DirectoryWatchResult::Error(e),
+        };
 
         let file_id = match self.file_id() {
             Ok(id) => id,
@@ -476,7 +723,6 @@ impl Directory {
                 }
                 .into(),
                 ReceiveOptions {
-                    allow_async: true,
                     #[cfg(feature = "async")]
                     async_cancel: options.async_cancel,
                     async_msg_ids: options.async_msg_ids,
@@ -506,7 +752,7 @@ impl Directory {
                 }
                 s => {
                     log::debug!("Unexpected status while watching directory: {s:?}");
-                    return DirectoryWatchResult::Error(Error::UnexpectedMessageStatus(s));
+                    return DirectoryWatchResult::Error(Error::server_status(s, None));
                 }
             },
             // Other cancellation (token)
@@ -572,6 +818,45 @@ impl Directory {
             .into())
     }
 
+    /// Queries a page of quota entries, returning an empty page once the enumeration is over.
+    pub(crate) async fn query_quota_page(
+        &self,
+        info: QueryQuotaInfo,
+    ) -> crate::Result<Vec<FileQuotaInformation>> {
+        match self.query_quota_info(info).await {
+            Err(Error::ServerError(Status::NoMoreEntries, _)) => Ok(vec![]),
+            result => result,
+        }
+    }
+
+    /// Iterates over all the quota entries of the volume of the directory, which is usually the root of the share.
+    ///
+    /// The entries are queried page by page, as the stream is consumed: the first query restarts the scan,
+    /// and the next ones continue it. The stream must be pinned before it is polled.
+    /// Errors are yielded as `Err` items, and end the stream.
+    ///
+    /// Quota queries of the same handle continue the same scan, so do not query quotas while iterating.
+    #[cfg(feature = "async")]
+    pub fn iter_quota(
+        &self,
+    ) -> impl futures_core::Stream<Item = crate::Result<FileQuotaInformation>> + '_ {
+        futures_util::stream::unfold(QuotaPages::new(self), |mut pages| async move {
+            pages.next().await.map(|entry| (entry, pages))
+        })
+    }
+
+    /// Iterates over all the quota entries of the volume of the directory, which is usually the root of the share.
+    ///
+    /// The entries are queried page by page, as the iterator is consumed: the first query restarts the scan,
+    /// and the next ones continue it. Errors are yielded as `Err` items, and end the iterator.
+    ///
+    /// Quota queries of the same handle continue the same scan, so do not query quotas while iterating.
+    #[cfg(not(feature = "async"))]
+    pub fn iter_quota(&self) -> impl Iterator<Item = crate::Result<FileQuotaInformation>> + '_ {
+        let mut pages = QuotaPages::new(self);
+        std::iter::from_fn(move || pages.next())
+    }
+
     /// Sets the quota information for the current file.
     /// # Arguments
     /// * `info` - The information to set - a vector of [`FileQuotaInformation`].
@@ -585,6 +870,105 @@ impl Directory {
             )
             .await
     }
+
+    /// Sets multiple quota entries of the volume at once, in a single request.
+    ///
+    /// The server only uses the SID, threshold and limit of each entry. Does nothing if `entries` is empty.
+    pub async fn set_quota_entries(&self, entries: &[FileQuotaInformation]) -> crate::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        self.set_quota_info(entries.to_vec()).await
+    }
+
+    /// Returns whether the entries of the directory are named case-sensitively.
+    /// See [`FileCaseSensitiveInformation`].
+    pub async fn is_case_sensitive(&self) -> crate::Result<bool> {
+        let info = self.query_info::<FileCaseSensitiveInformation>().await?;
+        Ok(info.flags.case_sensitive_dir())
+    }
+
+    /// Makes the entries of the directory named case-sensitively, or case-insensitively.
+    /// See [`FileCaseSensitiveInformation`].
+    pub async fn set_case_sensitive(&self, case_sensitive: bool) -> crate::Result<()> {
+        self.set_info(FileCaseSensitiveInformation {
+            flags: FileCaseSensitiveFlags::new().with_case_sensitive_dir(case_sensitive),
+        })
+        .await
+    }
+
+    /// Lists the snapshots (previous versions) of the directory, e.g., Volume Shadow Copies on Windows servers,
+    /// or `shadow_copy2` snapshots on Samba servers.
+    ///
+    /// # Returns
+    /// The times of the snapshots, as returned by the server.
+    /// Use these to open files and directories as of a snapshot, see [`FileCreateArgs::snapshot`][super::FileCreateArgs::snapshot].
+    pub async fn enumerate_snapshots(&self) -> crate::Result<Vec<FileTime>> {
+        /// Windows supports up to 512 snapshots per volume.
+        const MAX_SNAPSHOTS: usize = 512;
+        const RESPONSE_HEADER_SIZE: usize = 12;
+        /// `@GMT-YYYY.MM.DD-HH.MM.SS`, null-terminated, in UTF-16.
+        const SNAPSHOT_NAME_SIZE: usize = (SNAPSHOT_NAME_LEN + 1) * 2;
+        let buffer_size = self.clamp_buffer_size(
+            RESPONSE_HEADER_SIZE + MAX_SNAPSHOTS * SNAPSHOT_NAME_SIZE + 2,
+            BufferLimit::Transact,
+        )?;
+
+        let response = self
+            .handle
+            .fsctl_with_options(SrvEnumerateSnapshotsRequest(()), buffer_size)
+            .await?;
+        if response.number_of_snap_shots_returned < response.number_of_snap_shots {
+            log::warn!(
+                "Only {} of {} snapshots of {} were returned",
+                response.number_of_snap_shots_returned,
+                response.number_of_snap_shots,
+                self.handle.name()
+            );
+        }
+
+        Ok(response
+            .snap_shots
+            .iter()
+            .filter_map(|name| {
+                let name = name.to_string();
+                let time = parse_snapshot_name(&name);
+                if time.is_none() {
+                    log::warn!("Ignoring snapshot with an invalid name: {name}");
+                }
+                time
+            })
+            .collect())
+    }
+}
+
+/// The length of a snapshot name, `@GMT-YYYY.MM.DD-HH.MM.SS`.
+const SNAPSHOT_NAME_LEN: usize = 24;
+
+/// Parses a snapshot name, as returned by [`Directory::enumerate_snapshots`]: `@GMT-YYYY.MM.DD-HH.MM.SS`.
+fn parse_snapshot_name(name: &str) -> Option<FileTime> {
+    let value = name.strip_prefix("@GMT-")?;
+    let b = value.as_bytes();
+    if name.len() != SNAPSHOT_NAME_LEN
+        || [4, 7, 13, 16].iter().any(|&i| b[i] != b'.')
+        || b[10] != b'-'
+    {
+        return None;
+    }
+    let field = |at: usize, len: usize| -> Option<u16> {
+        let field = value.get(at..at + len)?;
+        if !field.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        field.parse().ok()
+    };
+    // All fields but the year are 2 digits long, so they fit in a u8.
+    let [month, day, hour, minute, second] =
+        [5, 8, 11, 14, 17].map(|at| field(at, 2).map(|value| value as u8));
+    let month = time::Month::try_from(month?).ok()?;
+    let date = time::Date::from_calendar_date(field(0, 4)?.into(), month, day?).ok()?;
+    let time = time::Time::from_hms(hour?, minute?, second?).ok()?;
+    Some(time::PrimitiveDateTime::new(date, time).into())
 }
 
 /// Single result from a directory watch operation.
@@ -640,6 +1024,49 @@ impl DerefMut for Directory {
     }
 }
 
+/// Pages through the quota entries of a directory, for [`Directory::iter_quota`].
+struct QuotaPages<'a> {
+    directory: &'a Directory,
+    /// Entries of the last page, that were not yet consumed.
+    page: std::vec::IntoIter<FileQuotaInformation>,
+    restart_scan: bool,
+    done: bool,
+}
+
+#[maybe_async(AFIT)]
+impl<'a> QuotaPages<'a> {
+    fn new(directory: &'a Directory) -> Self {
+        Self {
+            directory,
+            page: Vec::new().into_iter(),
+            restart_scan: true,
+            done: false,
+        }
+    }
+
+    async fn next(&mut self) -> Option<crate::Result<FileQuotaInformation>> {
+        loop {
+            if let Some(entry) = self.page.next() {
+                return Some(Ok(entry));
+            }
+            if self.done {
+                return None;
+            }
+
+            let info = QueryQuotaInfo::new(false, self.restart_scan, vec![]);
+            self.restart_scan = false;
+            match self.directory.query_quota_page(info).await {
+                Ok(page) if page.is_empty() => self.done = true,
+                Ok(page) => self.page = page.into_iter(),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
 #[cfg(feature = "async")]
 pub mod iter_stream {
     use super::*;
@@ -649,13 +1076,15 @@ pub mod iter_stream {
 
     /// A stream that allows you to iterate over the contents of a directory.
     /// See [Directory::query] for more information on how to use it.
+    ///
+    /// The stream ends after the first error it yields.
     pub struct QueryDirectoryStream<'a, T> {
-        /// A channel to receive the results from the query.
-        /// This is used to send the results from the query loop to the stream.
+        /// A channel to receive the results from the query loop, which runs in a separate task.
+        ///
+        /// The channel is bounded, and is the only backpressure on the query loop:
+        /// it parses and sends the entries of a response one at a time, and queries the server
+        /// for more entries only after all the entries of the previous response have been sent.
         receiver: tokio::sync::mpsc::Receiver<crate::Result<T>>,
-        /// This is used to wake up the query (against the server) loop when more data is required,
-        /// since the iterator is lazy and will not fetch data until it is needed.
-        notify_fetch_next: Arc<tokio::sync::Notify>,
         /// Holds the lock while iterating the directory,
         /// to prevent multiple queries at the same time.
         /// See [Directory::query] for more information.
@@ -664,33 +1093,32 @@ pub mod iter_stream {
 
     impl<'a, T> QueryDirectoryStream<'a, T>
     where
-        T: QueryDirectoryInfoValue + for<'b> binrw::prelude::BinWrite<Args<'b> = ()> + Send,
+        T: QueryDirectoryInfoValue
+            + QueryDirectoryEntryName
+            + for<'b> binrw::prelude::BinWrite<Args<'b> = ()>
+            + Send,
     {
+        /// The number of entries that may be parsed ahead of the consumer of the stream.
+        const CHANNEL_CAPACITY: usize = 64;
+
         pub async fn new(
             directory: &'a Arc<Directory>,
             pattern: String,
             buffer_size: u32,
+            include_dot_entries: bool,
         ) -> crate::Result<Self> {
-            let (sender, receiver) = tokio::sync::mpsc::channel(1024);
-            let notify_fetch_next = Arc::new(tokio::sync::Notify::new());
+            // The lock is taken before the query loop starts, so it never races another query.
+            let guard = directory.query_lock.lock().await?;
+            let (sender, receiver) = tokio::sync::mpsc::channel(Self::CHANNEL_CAPACITY);
             {
-                let notify_fetch_next = notify_fetch_next.clone();
                 let directory = directory.clone();
                 tokio::spawn(async move {
-                    Self::fetch_loop(
-                        directory,
-                        pattern,
-                        buffer_size,
-                        sender,
-                        notify_fetch_next.clone(),
-                    )
-                    .await;
+                    Self::fetch_loop(directory, pattern, buffer_size, include_dot_entries, sender)
+                        .await;
                 });
             }
-            let guard = directory.query_lock.lock().await?;
             Ok(Self {
                 receiver,
-                notify_fetch_next,
                 _lock_guard: guard,
             })
         }
@@ -699,38 +1127,41 @@ pub mod iter_stream {
             directory: Arc<Directory>,
             pattern: String,
             buffer_size: u32,
+            include_dot_entries: bool,
             sender: mpsc::Sender<crate::Result<T>>,
-            notify_fetch_next: Arc<tokio::sync::Notify>,
         ) {
+            let mut buffer_size = buffer_size;
             let mut is_first = true;
             loop {
                 let result = directory
-                    .send_query::<T>(&pattern, is_first, buffer_size)
+                    .send_query_lazy::<T>(
+                        &pattern,
+                        QueryDirectoryFlags::new().with_restart_scans(is_first),
+                        &mut buffer_size,
+                    )
                     .await;
                 is_first = false;
 
-                match result {
-                    Ok(items) => {
-                        if items.is_empty() {
-                            // No more files, exit the loop
-                            break;
-                        }
-                        for item in items {
-                            if sender.send(Ok(item)).await.is_err() {
-                                return; // Receiver dropped
-                            }
-                        }
-                    }
+                let entries = match result {
+                    Ok(entries) if entries.is_finished() => break, // No more files
+                    Ok(entries) => entries,
                     Err(e) => {
-                        if sender.send(Err(e)).await.is_err() {
-                            return; // Receiver dropped
-                        }
+                        let _ = sender.send(Err(e)).await;
+                        return;
+                    }
+                };
+                // Filtered after the end of the enumeration is checked, since a batch may only contain these.
+                for entry in entries {
+                    let entry = match entry {
+                        Ok(entry) if !include_dot_entries && entry.is_dot_entry() => continue,
+                        Ok(entry) => Ok(entry),
+                        Err(e) => Err(e.into()),
+                    };
+                    let failed = entry.is_err();
+                    if sender.send(entry).await.is_err() || failed {
+                        return; // Receiver dropped, or the rest of the batch can't be parsed
                     }
                 }
-
-                // Notify the stream that a new batch is available
-                notify_fetch_next.notify_waiters();
-                notify_fetch_next.notified().await;
             }
         }
     }
@@ -742,17 +1173,7 @@ pub mod iter_stream {
         type Item = crate::Result<T>;
 
         fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-            let this = self.get_mut();
-            match this.receiver.poll_recv(cx) {
-                Poll::Ready(Some(value)) => {
-                    if this.receiver.is_empty() {
-                        this.notify_fetch_next.notify_waiters() // Notify that batch is done
-                    }
-                    Poll::Ready(Some(value))
-                }
-                Poll::Ready(None) => Poll::Ready(None), // Stream is closed!
-                Poll::Pending => Poll::Pending,
-            }
+            self.get_mut().receiver.poll_recv(cx)
         }
     }
 }
@@ -765,8 +1186,10 @@ pub mod iter_sync {
     where
         T: QueryDirectoryInfoValue,
     {
-        /// Results from last call to [`Directory::send_query`], that were not yet consumed.
-        backlog: Vec<T>,
+        /// Entries of the last call to [`Directory::send_query_lazy`], that were not yet consumed.
+        backlog: QueryDirectoryEntries<T>,
+        /// Whether the enumeration ended, or failed.
+        done: bool,
         /// The directory to query.
         directory: &'a Directory,
         /// The pattern to match against the file names in the directory.
@@ -775,6 +1198,8 @@ pub mod iter_sync {
         is_first: bool,
         /// The buffer size to use for each query.
         buffer_size: u32,
+        /// Whether to yield the `.` and `..` entries.
+        include_dot_entries: bool,
 
         /// The lock being held while iterating the directory.
         _iter_lock_guard: MutexGuard<'a, ()>,
@@ -788,13 +1213,16 @@ pub mod iter_sync {
             directory: &'a Directory,
             pattern: String,
             buffer_size: u32,
+            include_dot_entries: bool,
         ) -> crate::Result<Self> {
             Ok(Self {
-                backlog: Vec::new(),
+                backlog: QueryDirectoryEntries::new(vec![]),
+                done: false,
                 directory,
                 pattern,
                 is_first: true,
                 buffer_size,
+                include_dot_entries,
                 _iter_lock_guard: directory.query_lock.lock()?,
             })
         }
@@ -802,37 +1230,45 @@ pub mod iter_sync {
 
     impl<'a, T> Iterator for QueryDirectoryIterator<'a, T>
     where
-        T: QueryDirectoryInfoValue + for<'b> binrw::prelude::BinWrite<Args<'b> = ()>,
+        T: QueryDirectoryInfoValue
+            + QueryDirectoryEntryName
+            + for<'b> binrw::prelude::BinWrite<Args<'b> = ()>,
     {
         type Item = crate::Result<T>;
 
         fn next(&mut self) -> Option<Self::Item> {
-            // Pop from backlog if we have any results left.
-            if !self.backlog.is_empty() {
-                return Some(Ok(self.backlog.remove(0)));
-            }
-
-            // If we have no backlog, we need to query the directory again.
-            let query_result =
-                self.directory
-                    .send_query::<T>(&self.pattern, self.is_first, self.buffer_size);
-            self.is_first = false;
-            match query_result {
-                Ok(next_backlog) => {
-                    if next_backlog.is_empty() {
-                        // No more items
-                        None
-                    } else {
-                        // Store the items in the backlog and return the first one.
-                        self.backlog = next_backlog;
-                        self.next()
+            while !self.done {
+                // Parse the next entry of the backlog, if we have any left.
+                for entry in self.backlog.by_ref() {
+                    match entry {
+                        Ok(entry) if !self.include_dot_entries && entry.is_dot_entry() => continue,
+                        Ok(entry) => return Some(Ok(entry)),
+                        Err(e) => {
+                            // The rest of the batch can't be parsed.
+                            self.done = true;
+                            return Some(Err(e.into()));
+                        }
                     }
                 }
-                Err(e) => {
-                    // Another error occurred, return it.
-                    Some(Err(e))
+
+                // If we have no backlog, we need to query the directory again.
+                let query_result = self.directory.send_query_lazy::<T>(
+                    &self.pattern,
+                    QueryDirectoryFlags::new().with_restart_scans(self.is_first),
+                    &mut self.buffer_size,
+                );
+                self.is_first = false;
+                match query_result {
+                    // No more items
+                    Ok(entries) if entries.is_finished() => self.done = true,
+                    Ok(entries) => self.backlog = entries,
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
                 }
             }
+            None
         }
     }
 }
@@ -895,7 +1331,7 @@ mod iter_mtd {
             });
         }
 
-        /// Blocks the current thread until the cancellation is confirmed.
+        /// Blocks the current thread until the cancellation is confirmed, or the watch ended otherwise.
         pub fn wait_cancelled(
             &self,
         ) -> std::result::Result<(), std::sync::PoisonError<std::sync::MutexGuard<'_, bool>>>
@@ -906,6 +1342,11 @@ mod iter_mtd {
             Ok(())
         }
 
+        /// Whether the worker thread stopped, however the watch ended.
+        fn is_done(&self) -> bool {
+            self.cancel_done.lock().is_ok_and(|done| *done)
+        }
+
         pub(crate) fn notify_cancelled(
             &self,
         ) -> std::result::Result<(), std::sync::PoisonError<std::sync::MutexGuard<'_, bool>>>
@@ -954,7 +1395,7 @@ mod iter_mtd {
                 let receive_options = ReceiveOptions::new()
                     .with_async_msg_ids(async_msg_ids.clone())
                     .with_cancellation_flag(cancel_flag.clone())
-                    .with_timeout(Duration::MAX);
+                    .without_timeout();
                 move || {
                     while !cancel_flag.load(Ordering::SeqCst) {
                         let watch_result = canceller.directory._watch_options(
@@ -975,21 +1416,12 @@ mod iter_mtd {
                             }
                             Cancelled => {
                                 // Cancelled by user, exit the loop.
-                                canceller
-                                    .notify_cancelled()
-                                    .map_err(|e| {
-                                        log::error!("Error notifying cancellation: {e}");
-                                        e
-                                    })
-                                    .ok();
                                 log::debug!("Watch cancelled by user");
                                 break;
                             }
                             Cleanup => {
-                                // Server cleaned up the watch, exit the loop.
+                                // Server cleaned up the watch, exit the loop, ending the iterator.
                                 log::debug!("Watch cleaned up by server");
-                                tx.send(Err(crate::Error::Cancelled("watch cleaned up by server")))
-                                    .ok();
                                 break;
                             }
                             x => {
@@ -1001,6 +1433,15 @@ mod iter_mtd {
                             }
                         }
                     }
+
+                    // The worker is done, however it stopped, so waiting for cancellation must not block.
+                    canceller
+                        .notify_cancelled()
+                        .map_err(|e| {
+                            log::error!("Error notifying cancellation: {e}");
+                            e
+                        })
+                        .ok();
                 }
             });
 
@@ -1029,10 +1470,426 @@ mod iter_mtd {
 
     impl Drop for NotifyDirectoryIterator {
         fn drop(&mut self) {
-            self.canceller.cancel();
+            // Once the watch ended, there is no pending request to cancel.
+            if !self.canceller.is_done() {
+                self.canceller.cancel();
+            }
         }
     }
 }
 
 #[cfg(feature = "multi_threaded")]
 pub use iter_mtd::{NotifyDirectoryIteratorCancellable, NotifyDirectoryIteratorCanceller};
+
+#[cfg(test)]
+mod tests {
+    use super::{Directory, QueryDirectoryOptions, parse_snapshot_name};
+    use crate::test_util::*;
+    use binrw::BinWrite;
+    use maybe_async::maybe_async;
+    use smb_dtyp::binrw_util::prelude::FileTime;
+    use smb_fscc::{
+        ChainedItemList, DirAccessMask, FileAttributes, FileIdBothDirectoryInformation,
+        FileNamesInformation, FileNotifyInformation, NotifyAction, QueryDirectoryInfo,
+    };
+    use smb_msg::{
+        ChangeNotifyResponse, Command, FileId, NotifyFilter, QueryDirectoryResponse, ShareFlags,
+        Status,
+    };
+    use smb_transport::mock::MockStep;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use time::macros::datetime;
+
+    const DIR_ID: FileId = FileId {
+        persistent: 0x10,
+        volatile: 0x20,
+    };
+
+    /// A query directory response, with the names of `entries`.
+    fn names_response(entries: &[&str]) -> QueryDirectoryResponse {
+        let entries = entries
+            .iter()
+            .map(|name| FileNamesInformation {
+                file_index: 0,
+                file_name: (*name).into(),
+            })
+            .collect::<Vec<_>>();
+        let mut output_buffer = std::io::Cursor::new(vec![]);
+        ChainedItemList::<_, { QueryDirectoryInfo::CHAINED_ALIGNMENT }>::from(entries)
+            .write_le(&mut output_buffer)
+            .unwrap();
+        QueryDirectoryResponse {
+            output_buffer: output_buffer.into_inner(),
+        }
+    }
+
+    /// The step that opens the `dir` directory.
+    fn open_dir_step() -> MockStep {
+        MockStep::expect(Command::Create)
+            .matching("opens dir", |r| {
+                r.content
+                    .as_create()
+                    .is_ok_and(|create| create.name == "dir")
+            })
+            .respond(create_response(
+                DIR_ID,
+                FileAttributes::new().with_directory(true),
+            ))
+    }
+
+    /// The step of a query directory request, which (re)starts the scan if `restart` is set.
+    fn query_step(restart: bool) -> MockStep {
+        MockStep::expect(Command::QueryDirectory).matching("query flags", move |r| {
+            r.content.as_querydirectory().is_ok_and(|query| {
+                query.file_id == DIR_ID
+                    && query.file_name == "*"
+                    && query.flags.restart_scans() == restart
+            })
+        })
+    }
+
+    /// Opens the `dir` directory over a mock transport, that then follows `steps`,
+    /// and lists its names.
+    #[maybe_async]
+    async fn list_mock_dir(
+        steps: impl IntoIterator<Item = MockStep>,
+    ) -> (MockTree, Arc<Directory>, Vec<crate::Result<String>>) {
+        list_mock_dir_with_options(steps, &QueryDirectoryOptions::DEFAULT).await
+    }
+
+    /// Just like [`list_mock_dir`], listing the names with the specified options.
+    #[maybe_async]
+    async fn list_mock_dir_with_options(
+        steps: impl IntoIterator<Item = MockStep>,
+        options: &QueryDirectoryOptions,
+    ) -> (MockTree, Arc<Directory>, Vec<crate::Result<String>>) {
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            std::iter::once(open_dir_step()).chain(steps),
+        )
+        .await;
+        let directory = mock
+            .tree
+            .open_existing("dir", DirAccessMask::new().with_list_directory(true).into())
+            .await
+            .unwrap()
+            .unwrap_dir();
+        let directory = Arc::new(directory);
+
+        let mut entries =
+            Directory::query_directory::<FileNamesInformation>(&directory, "*", options)
+                .await
+                .unwrap();
+        let mut names = vec![];
+        while let Some(entry) = entries.next_entry().await {
+            names.push(entry.map(|entry| entry.file_name.to_string()));
+        }
+        drop(entries);
+        (mock, directory, names)
+    }
+
+    fn close_step() -> MockStep {
+        MockStep::expect(Command::Close)
+            .matching("closes dir", |r| {
+                r.content
+                    .as_close()
+                    .is_ok_and(|close| close.file_id == DIR_ID)
+            })
+            .respond(close_response())
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_query_directory_pages() {
+        let (mock, directory, names) = list_mock_dir([
+            query_step(true).respond(names_response(&[".", "..", "a"])),
+            query_step(false).respond(names_response(&["b", "c"])),
+            query_step(false).respond_error(Status::NoMoreFiles),
+            close_step(),
+        ])
+        .await;
+        let names = names
+            .into_iter()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(names, ["a", "b", "c"]);
+
+        directory.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    /// A query the server fails with STATUS_INFO_LENGTH_MISMATCH is sent again with a larger buffer,
+    /// without restarting the scan, and later queries keep the larger buffer.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_query_directory_grows_buffer() {
+        fn with_buffer(step: MockStep, length: u32) -> MockStep {
+            step.matching("output buffer length", move |r| {
+                r.content
+                    .as_querydirectory()
+                    .is_ok_and(|query| query.output_buffer_length == length)
+            })
+        }
+        let options = QueryDirectoryOptions {
+            buffer_size: Some(0x200),
+            ..Default::default()
+        };
+        let (mock, directory, names) = list_mock_dir_with_options(
+            [
+                with_buffer(query_step(true), 0x200).respond(names_response(&["a"])),
+                with_buffer(query_step(false), 0x200).respond_error(Status::InfoLengthMismatch),
+                with_buffer(query_step(false), 0x400).respond(names_response(&["b"])),
+                with_buffer(query_step(false), 0x400).respond_error(Status::NoMoreFiles),
+                close_step(),
+            ],
+            &options,
+        )
+        .await;
+        let names = names
+            .into_iter()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(names, ["a", "b"]);
+
+        directory.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_query_directory_connection_lost() {
+        let (mock, _directory, names) = list_mock_dir([
+            query_step(true).respond(names_response(&["a", "b"])),
+            query_step(false).disconnect(),
+        ])
+        .await;
+
+        // The entries of the first page are yielded, and the iteration ends with an error.
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[0].as_ref().unwrap(), "a");
+        assert_eq!(names[1].as_ref().unwrap(), "b");
+        assert!(names[2].is_err());
+        assert!(mock.handle.is_disconnected());
+        mock.handle.assert_done();
+    }
+
+    /// Entries are typed, and the dot entries are skipped unless requested.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_entries() {
+        let entry =
+            |name: &str, attributes: FileAttributes, size: u64| FileIdBothDirectoryInformation {
+                file_index: 0,
+                creation_time: FileTime::ZERO,
+                last_access_time: FileTime::ZERO,
+                last_write_time: datetime!(2025-04-11 17:24:47).into(),
+                change_time: FileTime::ZERO,
+                end_of_file: size,
+                allocation_size: 0,
+                file_attributes: attributes,
+                ea_size: Some(0),
+                reparse_tag: None,
+                short_name_length: 0,
+                short_name: Default::default(),
+                file_id: size + 1,
+                file_name: name.into(),
+            };
+        let dir = FileAttributes::new().with_directory(true);
+        let response = || {
+            let entries = vec![
+                entry(".", dir, 0),
+                entry("..", dir, 0),
+                entry("file.txt", FileAttributes::new().with_archive(true), 10),
+                entry("sub", dir, 0),
+            ];
+            let mut output_buffer = std::io::Cursor::new(vec![]);
+            ChainedItemList::<_, { QueryDirectoryInfo::CHAINED_ALIGNMENT }>::from(entries)
+                .write_le(&mut output_buffer)
+                .unwrap();
+            QueryDirectoryResponse {
+                output_buffer: output_buffer.into_inner(),
+            }
+        };
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                open_dir_step(),
+                query_step(true).respond(response()),
+                query_step(false).respond_error(Status::NoMoreFiles),
+                query_step(true).respond(response()),
+                query_step(false).respond_error(Status::NoMoreFiles),
+                close_step(),
+            ],
+        )
+        .await;
+        let directory = mock
+            .tree
+            .open_existing("dir", DirAccessMask::new().with_list_directory(true).into())
+            .await
+            .unwrap()
+            .unwrap_dir();
+        let directory = Arc::new(directory);
+
+        let listing = Directory::entries(&directory, "*", &QueryDirectoryOptions::DEFAULT)
+            .await
+            .unwrap();
+        let entries = listing.collect_entries().await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "file.txt");
+        assert!(!entries[0].is_dir);
+        assert_eq!(entries[0].size, 10);
+        assert_eq!(entries[0].file_id, 11);
+        assert!(entries[0].attributes.archive());
+        assert_eq!(
+            entries[0].last_write_time,
+            datetime!(2025-04-11 17:24:47).into()
+        );
+        assert_eq!(entries[1].name, "sub");
+        assert!(entries[1].is_dir);
+
+        let options = QueryDirectoryOptions {
+            include_dot_entries: true,
+            ..QueryDirectoryOptions::DEFAULT
+        };
+        let listing = Directory::entries(&directory, "*", &options).await.unwrap();
+        let entries = listing.collect_entries().await.unwrap();
+        let names = entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [".", "..", "file.txt", "sub"]);
+
+        directory.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_watch_changes() {
+        let notify_step = || {
+            MockStep::expect(Command::ChangeNotify).matching("watches dir", |r| {
+                r.content
+                    .as_changenotify()
+                    .is_ok_and(|notify| notify.file_id == DIR_ID)
+            })
+        };
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                open_dir_step(),
+                notify_step().respond(ChangeNotifyResponse {
+                    buffer: vec![
+                        FileNotifyInformation {
+                            action: NotifyAction::Added,
+                            file_name: "a".into(),
+                        },
+                        FileNotifyInformation {
+                            action: NotifyAction::Removed,
+                            file_name: "b".into(),
+                        },
+                    ]
+                    .into(),
+                }),
+                notify_step().respond_with_status(
+                    Status::NotifyCleanup,
+                    ChangeNotifyResponse {
+                        buffer: vec![].into(),
+                    },
+                ),
+                close_step(),
+            ],
+        )
+        .await;
+        let directory = mock
+            .tree
+            .open_existing("dir", DirAccessMask::new().with_list_directory(true).into())
+            .await
+            .unwrap()
+            .unwrap_dir();
+        let directory = Arc::new(directory);
+
+        // The watch ends once the server cleans it up.
+        let mut changes =
+            Directory::watch_stream(&directory, NotifyFilter::new().with_file_name(true), false)
+                .unwrap();
+        let mut notified = vec![];
+        while let Some(change) = changes.next_change().await {
+            let change = change.unwrap();
+            notified.push((change.action, change.file_name.to_string()));
+        }
+        drop(changes);
+        assert_eq!(
+            notified,
+            [
+                (NotifyAction::Added, "a".to_string()),
+                (NotifyAction::Removed, "b".to_string())
+            ]
+        );
+
+        directory.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    /// Watching waits for changes longer than the request timeout of the connection.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_watch_without_request_timeout() {
+        let config = crate::ConnectionConfig {
+            request_timeout: Some(Duration::from_millis(50)),
+            ..mock_config()
+        };
+        let mock = connect_mock_tree_with_config(
+            ShareFlags::new(),
+            config,
+            [
+                open_dir_step(),
+                MockStep::expect(Command::ChangeNotify)
+                    .respond(ChangeNotifyResponse {
+                        buffer: vec![FileNotifyInformation {
+                            action: NotifyAction::Modified,
+                            file_name: "a".into(),
+                        }]
+                        .into(),
+                    })
+                    .delay(Duration::from_millis(300)),
+                close_step(),
+            ],
+        )
+        .await;
+        let directory = mock
+            .tree
+            .open_existing("dir", DirAccessMask::new().with_list_directory(true).into())
+            .await
+            .unwrap()
+            .unwrap_dir();
+
+        let changes = directory
+            .watch(NotifyFilter::new().with_last_write(true), false)
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        directory.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    #[test]
+    fn test_parse_snapshot_name() {
+        assert_eq!(
+            parse_snapshot_name("@GMT-2025.01.20-15.36.20"),
+            Some(FileTime::from(datetime!(2025-01-20 15:36:20)))
+        );
+        for invalid in [
+            "@GMT-2025.01.20-15.36",
+            "@GMT-2025.13.20-15.36.20",
+            "@GMT-2025.01.20 15.36.20",
+            "GMT-2025.01.20-15.36.20x",
+            "@GMT-+025.01.20-15.36.20",
+        ] {
+            assert_eq!(parse_snapshot_name(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_grown_query_buffer_size() {
+        const MAX: u32 = 0x10000;
+        assert_eq!(Directory::grown_query_buffer_size(0x400, MAX), Some(0x800));
+        assert_eq!(Directory::grown_query_buffer_size(0x9000, MAX), Some(MAX));
+        assert_eq!(Directory::grown_query_buffer_size(MAX, MAX), None);
+        assert_eq!(Directory::grown_query_buffer_size(0, MAX), Some(1));
+    }
+}
@@ -0,0 +1,139 @@
+//! [`DirectoryEntry`], the typed entries listed by [`Directory::entries`][super::Directory::entries].
+
+use smb_dtyp::binrw_util::prelude::FileTime;
+use smb_fscc::{FileAttributes, FileIdBothDirectoryInformation};
+
+use crate::UncPath;
+
+/// An entry of a directory, as listed by [`Directory::entries`][super::Directory::entries].
+///
+/// This holds the commonly used fields of the [`FileIdBothDirectoryInformation`] returned by the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    /// The name of the entry.
+    ///
+    /// Names that are not valid UTF-16 (e.g. unpaired surrogates, which some file systems allow)
+    /// are converted lossily, replacing the invalid units with `U+FFFD`. See [`raw_name`][Self::raw_name].
+    pub name: String,
+    /// The name of the entry, as returned by the server, in UTF-16 units.
+    /// Use this, rather than [`name`][Self::name], to open entries whose name is not valid UTF-16.
+    pub raw_name: Vec<u16>,
+    /// Whether the entry is a directory.
+    pub is_dir: bool,
+    /// The size of the file, in bytes. This is the end of file position, and is zero for directories.
+    pub size: u64,
+    /// The allocation size of the file, in bytes. Usually a multiple of the cluster size.
+    pub allocation_size: u64,
+    /// The time when the entry was created.
+    pub creation_time: FileTime,
+    /// The time when the entry was last accessed.
+    pub last_access_time: FileTime,
+    /// The time when data was last written to the entry.
+    pub last_write_time: FileTime,
+    /// The time when the entry was last changed.
+    pub change_time: FileTime,
+    /// The file ID of the entry, which may be used to open it by its ID. Zero if the file system has no file IDs.
+    pub file_id: u64,
+    /// The attributes of the entry.
+    pub attributes: FileAttributes,
+}
+
+impl DirectoryEntry {
+    /// Returns whether [`name`][Self::name] is exactly the name of the entry,
+    /// which is the case unless the name returned by the server is not valid UTF-16.
+    pub fn has_valid_name(&self) -> bool {
+        char::decode_utf16(self.raw_name.iter().copied()).all(|c| c.is_ok())
+    }
+
+    /// Returns whether the entry is a reparse point, e.g. a symbolic link or a junction.
+    pub fn is_reparse_point(&self) -> bool {
+        self.attributes.reparse_point()
+    }
+
+    /// Returns the path of the entry, given the path of the directory it was listed from.
+    pub fn path_in(&self, directory: &UncPath) -> UncPath {
+        directory.clone().with_add_path(&self.name)
+    }
+}
+
+impl From<FileIdBothDirectoryInformation> for DirectoryEntry {
+    fn from(info: FileIdBothDirectoryInformation) -> Self {
+        let raw_name = Vec::from(info.file_name);
+        Self {
+            name: String::from_utf16_lossy(&raw_name),
+            raw_name,
+            is_dir: info.file_attributes.directory(),
+            size: info.end_of_file,
+            allocation_size: info.allocation_size,
+            creation_time: info.creation_time,
+            last_access_time: info.last_access_time,
+            last_write_time: info.last_write_time,
+            change_time: info.change_time,
+            file_id: info.file_id,
+            attributes: info.file_attributes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use smb_dtyp::binrw_util::prelude::SizedWideString;
+
+    use super::*;
+
+    fn info(name: SizedWideString, attributes: FileAttributes) -> FileIdBothDirectoryInformation {
+        FileIdBothDirectoryInformation {
+            file_index: 0,
+            creation_time: FileTime::ZERO,
+            last_access_time: FileTime::ZERO,
+            last_write_time: FileTime::ZERO,
+            change_time: FileTime::ZERO,
+            end_of_file: 5,
+            allocation_size: 8,
+            file_attributes: attributes,
+            ea_size: Some(0),
+            reparse_tag: None,
+            short_name_length: 0,
+            short_name: Default::default(),
+            file_id: 0x1234,
+            file_name: name,
+        }
+    }
+
+    #[test]
+    fn test_from_info() {
+        let entry = DirectoryEntry::from(info("file.txt".into(), FileAttributes::new()));
+        assert_eq!(entry.name, "file.txt");
+        assert!(entry.has_valid_name());
+        assert!(!entry.is_dir);
+        assert_eq!((entry.size, entry.allocation_size), (5, 8));
+        assert_eq!(entry.file_id, 0x1234);
+
+        let directory = UncPath::from_str(r"\\server\share\dir").unwrap();
+        assert_eq!(
+            entry.path_in(&directory).to_string(),
+            r"\\server\share\dir\file.txt"
+        );
+
+        let entry = DirectoryEntry::from(info(
+            "sub".into(),
+            FileAttributes::new().with_directory(true),
+        ));
+        assert!(entry.is_dir);
+    }
+
+    #[test]
+    fn test_invalid_utf16_name() {
+        // An unpaired high surrogate.
+        let raw_name = vec![b'a' as u16, 0xd800, b'b' as u16];
+        let entry = DirectoryEntry::from(info(
+            raw_name.iter().copied().collect(),
+            FileAttributes::new(),
+        ));
+        assert_eq!(entry.name, "a\u{fffd}b");
+        assert_eq!(entry.raw_name, raw_name);
+        assert!(!entry.has_valid_name());
+    }
+}
@@ -0,0 +1,209 @@
+//! [`DirectoryEntries`], [`DirectoryListing`] and [`DirectoryChanges`] (also named [`ChangeNotifyStream`]), which have the same interface in async and sync builds.
+//!
+//! Both implement `Stream` when crate feature `async` is enabled, and [`Iterator`] otherwise.
+//! Their `next_entry` and `next_change` methods are `async` only when crate feature `async` is enabled, so code that
+//! consumes them may be written once, using [`maybe_async`].
+
+use super::DirectoryEntry;
+#[cfg(feature = "multi_threaded")]
+use super::iter_mtd::{
+    NotifyDirectoryIterator, NotifyDirectoryIteratorCancellable, NotifyDirectoryIteratorCanceller,
+};
+#[cfg(feature = "async")]
+use super::iter_stream::QueryDirectoryStream;
+#[cfg(not(feature = "async"))]
+use super::iter_sync::QueryDirectoryIterator;
+use smb_fscc::{
+    FileIdBothDirectoryInformation, FileNotifyInformation, QueryDirectoryEntryName,
+    QueryDirectoryInfoValue,
+};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+
+/// The entries of a directory, as listed by [`Directory::query_directory`][super::Directory::query_directory].
+///
+/// This holds the query lock of the directory until it is dropped. The listing ends after the first error it yields.
+pub struct DirectoryEntries<'a, T>
+where
+    T: QueryDirectoryInfoValue,
+{
+    #[cfg(feature = "async")]
+    pub(super) inner: QueryDirectoryStream<'a, T>,
+    #[cfg(not(feature = "async"))]
+    pub(super) inner: QueryDirectoryIterator<'a, T>,
+}
+
+impl<T> DirectoryEntries<'_, T>
+where
+    T: QueryDirectoryInfoValue
+        + QueryDirectoryEntryName
+        + for<'b> binrw::prelude::BinWrite<Args<'b> = ()>
+        + Send
+        + Unpin,
+{
+    /// Returns the next entry of the directory, or `None` once all the entries were listed.
+    #[cfg(feature = "async")]
+    pub async fn next_entry(&mut self) -> Option<crate::Result<T>> {
+        futures_util::StreamExt::next(self).await
+    }
+
+    /// Returns the next entry of the directory, or `None` once all the entries were listed.
+    #[cfg(not(feature = "async"))]
+    pub fn next_entry(&mut self) -> Option<crate::Result<T>> {
+        self.next()
+    }
+
+    /// Lists all the remaining entries of the directory, failing on the first error.
+    #[cfg(feature = "async")]
+    pub async fn collect_entries(self) -> crate::Result<Vec<T>> {
+        futures_util::TryStreamExt::try_collect(self).await
+    }
+
+    /// Lists all the remaining entries of the directory, failing on the first error.
+    #[cfg(not(feature = "async"))]
+    pub fn collect_entries(self) -> crate::Result<Vec<T>> {
+        self.collect()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> futures_core::Stream for DirectoryEntries<'_, T>
+where
+    T: QueryDirectoryInfoValue + Unpin + Send,
+{
+    type Item = crate::Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().inner).poll_next(cx)
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl<T> Iterator for DirectoryEntries<'_, T>
+where
+    T: QueryDirectoryInfoValue
+        + QueryDirectoryEntryName
+        + for<'b> binrw::prelude::BinWrite<Args<'b> = ()>,
+{
+    type Item = crate::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// The typed entries of a directory, as listed by [`Directory::entries`][super::Directory::entries].
+///
+/// The same as [`DirectoryEntries`] of [`FileIdBothDirectoryInformation`], with each entry converted to a [`DirectoryEntry`].
+pub struct DirectoryListing<'a> {
+    pub(super) inner: DirectoryEntries<'a, FileIdBothDirectoryInformation>,
+}
+
+impl DirectoryListing<'_> {
+    /// Returns the next entry of the directory, or `None` once all the entries were listed.
+    #[cfg(feature = "async")]
+    pub async fn next_entry(&mut self) -> Option<crate::Result<DirectoryEntry>> {
+        futures_util::StreamExt::next(self).await
+    }
+
+    /// Returns the next entry of the directory, or `None` once all the entries were listed.
+    #[cfg(not(feature = "async"))]
+    pub fn next_entry(&mut self) -> Option<crate::Result<DirectoryEntry>> {
+        self.next()
+    }
+
+    /// Lists all the remaining entries of the directory, failing on the first error.
+    #[cfg(feature = "async")]
+    pub async fn collect_entries(self) -> crate::Result<Vec<DirectoryEntry>> {
+        futures_util::TryStreamExt::try_collect(self).await
+    }
+
+    /// Lists all the remaining entries of the directory, failing on the first error.
+    #[cfg(not(feature = "async"))]
+    pub fn collect_entries(self) -> crate::Result<Vec<DirectoryEntry>> {
+        self.collect()
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for DirectoryListing<'_> {
+    type Item = crate::Result<DirectoryEntry>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().inner)
+            .poll_next(cx)
+            .map(|entry| entry.map(|entry| entry.map(DirectoryEntry::from)))
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl Iterator for DirectoryListing<'_> {
+    type Item = crate::Result<DirectoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|entry| entry.map(DirectoryEntry::from))
+    }
+}
+
+/// The changes in a directory, as watched by [`Directory::watch_stream`][super::Directory::watch_stream].
+///
+/// This holds a reference to the directory, so it may outlive the [`Directory`][super::Directory] it was created from.
+/// The watch ends once the server cleans it up (`STATUS_NOTIFY_CLEANUP`), e.g. when the directory is closed,
+/// or after the first error it yields. Dropping it cancels the pending request, except in single-threaded builds.
+pub struct DirectoryChanges {
+    #[cfg(feature = "async")]
+    pub(super) inner: tokio_stream::wrappers::ReceiverStream<crate::Result<FileNotifyInformation>>,
+    #[cfg(feature = "multi_threaded")]
+    pub(super) inner: NotifyDirectoryIterator,
+    #[cfg(feature = "single_threaded")]
+    pub(super) inner: Box<dyn Iterator<Item = crate::Result<FileNotifyInformation>>>,
+}
+
+/// A [`DirectoryChanges`] by the name of the change notification stream it is:
+/// a `Stream` of [`FileNotifyInformation`] in async builds, and an [`Iterator`] in sync builds.
+pub type ChangeNotifyStream = DirectoryChanges;
+
+impl DirectoryChanges {
+    /// Returns the next change in the directory, waiting for it if required,
+    /// or `None` once the watch ended.
+    #[cfg(feature = "async")]
+    pub async fn next_change(&mut self) -> Option<crate::Result<FileNotifyInformation>> {
+        futures_util::StreamExt::next(self).await
+    }
+
+    /// Returns the next change in the directory, waiting for it if required,
+    /// or `None` once the watch ended.
+    #[cfg(not(feature = "async"))]
+    pub fn next_change(&mut self) -> Option<crate::Result<FileNotifyInformation>> {
+        self.next()
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for DirectoryChanges {
+    type Item = crate::Result<FileNotifyInformation>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().inner).poll_next(cx)
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl Iterator for DirectoryChanges {
+    type Item = crate::Result<FileNotifyInformation>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+#[cfg(feature = "multi_threaded")]
+impl NotifyDirectoryIteratorCancellable for DirectoryChanges {
+    fn get_canceller(&self) -> &NotifyDirectoryIteratorCanceller {
+        self.inner.get_canceller()
+    }
+}
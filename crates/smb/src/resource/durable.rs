@@ -0,0 +1,335 @@
+//! Durable opens, and tickets for reclaiming them from a new client.
+
+use std::time::Duration;
+
+use smb_dtyp::Guid;
+use smb_msg::*;
+
+use crate::{Error, connection::connection_info::ConnectionInfo, tree::TreeConnectInfo};
+
+/// Options for opening a durable handle, see [`FileCreateArgs::durable`][crate::FileCreateArgs::durable].
+///
+/// The server keeps a durable handle open for a while after the connection is lost,
+/// so it can be reclaimed with [`Client::reclaim_durable`][crate::Client::reclaim_durable],
+/// even from another process (see [`File::export_durable_ticket`][crate::File::export_durable_ticket]).
+///
+/// Durable handles are requested using the SMB 3.x `DH2Q` create context, with a lease if the server
+/// supports leasing, and a batch oplock otherwise. The server may still open the file without durability.
+#[derive(Debug, Default, Clone)]
+pub struct DurableOpenOptions {
+    /// How long the server keeps the handle after the connection is lost.
+    /// If not set, the server decides.
+    pub timeout: Option<Duration>,
+    /// Requests a persistent handle, which also survives a server failover.
+    /// Servers grant persistent handles only on continuously available shares,
+    /// so creating the file fails with [`Error::UnsupportedOperation`] on any other share.
+    pub persistent: bool,
+}
+
+/// The state required to reclaim a durable handle, exported by [`File::export_durable_ticket`][crate::File::export_durable_ticket].
+///
+/// With the `serde` feature, tickets can be serialized, to reclaim the handle
+/// after the client process restarts, using [`Client::reclaim_durable`][crate::Client::reclaim_durable].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DurableHandleTicket {
+    /// The server of the share, as used to connect to it.
+    pub server: String,
+    /// The share name.
+    pub share: String,
+    /// The path of the file, relative to the share.
+    pub path: String,
+    /// Identifies the open on the server.
+    #[cfg_attr(feature = "serde", serde(with = "guid_string"))]
+    pub create_guid: Guid,
+    /// The persistent part of the file ID.
+    pub persistent_file_id: u64,
+    /// The volatile part of the file ID.
+    pub volatile_file_id: u64,
+    /// The key of the lease held by the open, if the server granted it with a lease.
+    pub lease_key: Option<u128>,
+    /// Whether the server granted a persistent handle.
+    pub persistent: bool,
+    /// How long the server keeps the handle after the connection is lost.
+    pub timeout: Duration,
+}
+
+impl DurableHandleTicket {
+    pub(crate) fn file_id(&self) -> FileId {
+        FileId {
+            persistent: self.persistent_file_id,
+            volatile: self.volatile_file_id,
+        }
+    }
+}
+
+/// The state of a durable open, held by its [`ResourceHandle`][crate::ResourceHandle].
+#[derive(Debug, Clone)]
+pub(crate) struct DurableOpen {
+    /// The `\\server\share` name of the tree of the open.
+    tree_name: String,
+    create_guid: Guid,
+    lease_key: Option<u128>,
+    persistent: bool,
+    timeout: Duration,
+}
+
+impl DurableOpen {
+    /// Returns the state for requesting a new durable open on the tree.
+    pub fn request(
+        options: &DurableOpenOptions,
+        tree_name: &str,
+        tree_info: &TreeConnectInfo,
+        conn_info: &ConnectionInfo,
+    ) -> crate::Result<Self> {
+        if !conn_info.negotiation.dialect_rev.is_smb3() {
+            return Err(Error::UnsupportedOperation(
+                "Durable handles are only supported with SMB 3.x dialects.".to_string(),
+            ));
+        }
+        Self::check_persistent(options, tree_name, tree_info)?;
+        let timeout = options.timeout.unwrap_or_default();
+        if u32::try_from(timeout.as_millis()).is_err() {
+            return Err(Error::InvalidArgument(format!(
+                "Durable handle timeout is too long: {timeout:?}"
+            )));
+        }
+
+        let lease_key = conn_info
+            .negotiation
+            .caps
+            .leasing()
+            .then(rand::random::<u128>);
+        Ok(Self {
+            tree_name: tree_name.to_string(),
+            create_guid: Guid::generate(),
+            lease_key,
+            persistent: options.persistent,
+            timeout,
+        })
+    }
+
+    /// Persistent handles are only granted on continuously available shares (MS-SMB2 3.3.5.9.10),
+    /// so requesting one on any other share fails, rather than silently opening a handle that is not persistent.
+    fn check_persistent(
+        options: &DurableOpenOptions,
+        tree_name: &str,
+        tree_info: &TreeConnectInfo,
+    ) -> crate::Result<()> {
+        if options.persistent && !tree_info.capabilities.continuous_availability() {
+            return Err(Error::UnsupportedOperation(format!(
+                "Persistent handles require a continuously available share, which {tree_name} is not."
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the state of an open to reclaim from a ticket.
+    pub fn from_ticket(ticket: &DurableHandleTicket) -> Self {
+        Self {
+            tree_name: format!(r"\\{}\{}", ticket.server, ticket.share),
+            create_guid: ticket.create_guid,
+            lease_key: ticket.lease_key,
+            persistent: ticket.persistent,
+            timeout: ticket.timeout,
+        }
+    }
+
+    /// The oplock level to request with the open.
+    ///
+    /// Servers grant durability only to opens holding a handle-caching lease or a batch oplock.
+    pub fn oplock_level(&self) -> OplockLevel {
+        match self.lease_key {
+            Some(_) => OplockLevel::Lease,
+            None => OplockLevel::Batch,
+        }
+    }
+
+    /// Returns the create contexts requesting a new durable open.
+    pub fn request_contexts(&self) -> Vec<RequestCreateContext> {
+        let mut contexts = vec![
+            DurableHandleRequestV2 {
+                timeout: self.timeout.as_millis() as u32,
+                flags: DurableHandleV2Flags::new().with_persistent(self.persistent),
+                create_guid: self.create_guid,
+            }
+            .into(),
+        ];
+        contexts.extend(self.lease_context());
+        contexts
+    }
+
+    /// Returns the create contexts reconnecting to the durable open of `file_id`.
+    pub fn reconnect_contexts(&self, file_id: FileId) -> Vec<RequestCreateContext> {
+        let mut contexts = vec![
+            DurableHandleReconnectV2 {
+                file_id,
+                create_guid: self.create_guid,
+                flags: DurableHandleV2Flags::new().with_persistent(self.persistent),
+            }
+            .into(),
+        ];
+        contexts.extend(self.lease_context());
+        contexts
+    }
+
+    fn lease_context(&self) -> Option<RequestCreateContext> {
+        self.lease_key.map(|lease_key| {
+            RequestLease::RqLsReqv2(RequestLeaseV2 {
+                lease_key,
+                lease_state: LeaseState::new()
+                    .with_read_caching(true)
+                    .with_write_caching(true)
+                    .with_handle_caching(true),
+                lease_flags: LeaseFlags::new(),
+                parent_lease_key: 0,
+                epoch: 0,
+            })
+            .into()
+        })
+    }
+
+    /// Returns the granted state, if the server granted durability to the open.
+    pub fn granted(
+        self,
+        oplock_level: &OplockLevel,
+        contexts: &[ResponseCreateContext],
+    ) -> Option<Self> {
+        let granted = contexts.iter().find_map(|c| c.data.as_dh2q())?;
+        let lease_key = self.lease_key.filter(|_| {
+            *oplock_level == OplockLevel::Lease
+                && contexts.iter().any(|c| c.data.as_rqls().is_some())
+        });
+        Some(Self {
+            lease_key,
+            persistent: granted.flags.persistent(),
+            timeout: Duration::from_millis(granted.timeout.into()),
+            ..self
+        })
+    }
+
+    /// Returns a ticket for reclaiming the open of `path`.
+    pub fn ticket(&self, path: &str, file_id: FileId) -> DurableHandleTicket {
+        let (server, share) = self
+            .tree_name
+            .trim_start_matches('\\')
+            .split_once('\\')
+            .unwrap_or((&self.tree_name, ""));
+        DurableHandleTicket {
+            server: server.to_string(),
+            share: share.to_string(),
+            path: path.to_string(),
+            create_guid: self.create_guid,
+            persistent_file_id: file_id.persistent,
+            volatile_file_id: file_id.volatile,
+            lease_key: self.lease_key,
+            persistent: self.persistent,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// (De)serializes a [`Guid`] as its string form.
+#[cfg(feature = "serde")]
+mod guid_string {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+    use smb_dtyp::Guid;
+
+    pub fn serialize<S: Serializer>(guid: &Guid, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(guid)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Guid, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(lease_key: Option<u128>) -> DurableOpen {
+        DurableOpen {
+            tree_name: r"\\server\share".to_string(),
+            create_guid: Guid::generate(),
+            lease_key,
+            persistent: false,
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_ticket_round_trip() {
+        let file_id = FileId {
+            persistent: 1,
+            volatile: 2,
+        };
+        let open = open(Some(0x1234));
+        let ticket = open.ticket(r"dir\file.txt", file_id);
+        assert_eq!(
+            (ticket.server.as_str(), ticket.share.as_str()),
+            ("server", "share")
+        );
+        assert_eq!(ticket.file_id(), file_id);
+
+        let reclaimed = DurableOpen::from_ticket(&ticket);
+        assert_eq!(reclaimed.ticket(r"dir\file.txt", file_id), ticket);
+    }
+
+    #[test]
+    fn test_persistent_requires_ca_share() {
+        let tree_info = |continuous_availability| TreeConnectInfo {
+            share_type: ShareType::Disk,
+            share_flags: ShareFlags::new(),
+            capabilities: TreeCapabilities::new()
+                .with_continuous_availability(continuous_availability),
+            maximal_access: 0x001f01ff,
+        };
+        let persistent = DurableOpenOptions {
+            timeout: None,
+            persistent: true,
+        };
+        DurableOpen::check_persistent(&persistent, r"\\server\share", &tree_info(true)).unwrap();
+        assert!(matches!(
+            DurableOpen::check_persistent(&persistent, r"\\server\share", &tree_info(false)),
+            Err(Error::UnsupportedOperation(_))
+        ));
+        // Durable handles that are not persistent are requested on any share.
+        DurableOpen::check_persistent(&Default::default(), r"\\server\share", &tree_info(false))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_reconnect_contexts() {
+        let file_id = FileId {
+            persistent: 1,
+            volatile: 2,
+        };
+        let open = open(Some(0x1234));
+        let contexts = open.reconnect_contexts(file_id);
+        assert_eq!(open.oplock_level(), OplockLevel::Lease);
+        assert_eq!(
+            contexts.iter().find_map(|c| c.data.as_dh2c()),
+            Some(&DurableHandleReconnectV2 {
+                file_id,
+                create_guid: open.create_guid,
+                flags: DurableHandleV2Flags::new(),
+            })
+        );
+        assert!(matches!(
+            contexts.iter().find_map(|c| c.data.as_rqls()),
+            Some(RequestLease::RqLsReqv2(lease)) if lease.lease_key == 0x1234
+        ));
+
+        // Without a lease, a batch oplock is held instead.
+        let open = DurableOpen {
+            lease_key: None,
+            ..open
+        };
+        let contexts = open.reconnect_contexts(file_id);
+        assert_eq!(open.oplock_level(), OplockLevel::Batch);
+        assert!(contexts.iter().all(|c| c.data.as_rqls().is_none()));
+    }
+}
@@ -4,28 +4,46 @@ use super::*;
 use std::io::prelude::*;
 use std::ops::{Deref, DerefMut};
 
+#[cfg(feature = "async")]
+mod async_io;
+mod read_stream;
+mod writer;
+
+#[cfg(not(feature = "async"))]
+pub use read_stream::ReadStreamIterator;
+pub use writer::*;
+
 /// An opened file on the server.
 ///
-/// # [std::io] Support
-/// The [File] struct also supports the [Read][std::io::Read] and [Write][std::io::Write] traits.
-/// Note that both of these traits are blocking, and will block the current thread until the operation is complete.
-/// Use [File::read_block] and [File::write_block] for non-blocking operations.
-/// The [File] struct also implements the [Seek][std::io::Seek] trait.
-/// This allows you to seek to a specific position in the file, combined with the [Read][std::io::Read] and [Write][std::io::Write] traits.
-/// Using any of the implemented [std::io] traits mentioned above should have no effect on calling the other, non-blocking methods.
-/// Since we would NOT like to call a tokio task from a blocking context, these traits are **NOT** implemented in the async context!
+/// # I/O traits Support
+/// In sync builds, the [File] struct supports the [Read][std::io::Read], [Write][std::io::Write]
+/// and [Seek][std::io::Seek] traits. Note that these traits are blocking, and will block the current thread
+/// until the operation is complete.
+///
+/// In async builds, the [File] struct supports the [`AsyncRead`][tokio::io::AsyncRead],
+/// [`AsyncWrite`][tokio::io::AsyncWrite] and [`AsyncSeek`][tokio::io::AsyncSeek] traits instead,
+/// so it can be used with [`tokio::io::copy`] and similar utilities.
+///
+/// Both read and write from the file's current position, which is set by seeking,
+/// and send a single request of up to the negotiated maximum read or write size at a time.
+/// Flushing sends a flush request, if anything was written since the last flush.
+/// Using any of these traits has no effect on calling the other methods, which take an explicit offset.
 ///
 /// You may not directly create this struct. Instead, use the [Tree::create][crate::tree::Tree::create] method to gain
 /// a proper handle against the server in the shape of a [Resource], that can be then converted to a [File].
 pub struct File {
     handle: ResourceHandle,
 
-    #[cfg(not(feature = "async"))]
+    /// The position of the I/O traits implementations.
     pos: u64,
-    #[cfg(not(feature = "async"))]
+    /// Whether the file was written using the I/O traits implementations, since the last flush.
     dirty: bool,
+    #[cfg(feature = "async")]
+    pending: std::sync::Mutex<async_io::PendingIo>,
 
     end_of_file: u64,
+    /// See [`File::sector_size`].
+    sector_size: std::sync::OnceLock<u32>,
 }
 
 #[maybe_async(AFIT)]
@@ -34,10 +52,11 @@ impl File {
         File {
             handle,
             end_of_file,
-            #[cfg(not(feature = "async"))]
             pos: 0,
-            #[cfg(not(feature = "async"))]
             dirty: false,
+            #[cfg(feature = "async")]
+            pending: Default::default(),
+            sector_size: Default::default(),
         }
     }
 
@@ -47,6 +66,78 @@ impl File {
         self.access
     }
 
+    /// Returns a ticket for reclaiming the durable handle of the file, using
+    /// [`Client::reclaim_durable`][crate::Client::reclaim_durable] after the connection is lost.
+    ///
+    /// Returns `None` if the file is closed, or if the server did not grant a durable handle
+    /// (see [`FileCreateArgs::durable`][crate::FileCreateArgs::durable]).
+    pub fn export_durable_ticket(&self) -> Option<DurableHandleTicket> {
+        self.handle.durable_ticket()
+    }
+
+    /// Reopens the durable handle of the file on `tree`, after the connection of the file was lost.
+    ///
+    /// `tree` must be connected to the same share, by the same user, on a new connection; e.g., drop the lost
+    /// connection using [`Client::abandon`][crate::Client::abandon], and connect the share again using
+    /// [`Client::share_connect`][crate::Client::share_connect] and [`Client::get_tree`][crate::Client::get_tree].
+    ///
+    /// On success, the returned file replaces this one, at the same I/O traits position.
+    /// This file is then marked as closed, without closing the handle on the lost connection.
+    ///
+    /// ## Returns
+    /// * [`Error::InvalidState`] if the file is closed, or the server did not grant it a durable handle.
+    /// * [`Error::DurableReclaimFailed`] if the server no longer holds the handle,
+    ///   e.g. with `STATUS_OBJECT_NAME_NOT_FOUND` once its timeout elapsed.
+    pub async fn reconnect(&self, tree: &crate::Tree) -> crate::Result<File> {
+        let ticket = self.export_durable_ticket().ok_or_else(|| {
+            Error::InvalidState(format!(
+                "Cannot reconnect '{}': the file is closed, or not durable",
+                self.name()
+            ))
+        })?;
+        let mut file = tree.reclaim_durable(&ticket).await?;
+        file.pos = self.pos;
+        file.handle.create_options = self.handle.create_options;
+        self.handle.forget();
+        Ok(file)
+    }
+
+    /// Returns whether the file was opened for unbuffered I/O,
+    /// see [`FileCreateArgs::with_no_intermediate_buffering`][crate::FileCreateArgs::with_no_intermediate_buffering].
+    pub fn is_unbuffered(&self) -> bool {
+        self.handle.create_options().no_intermediate_buffering()
+    }
+
+    /// Returns the logical sector size of the volume of the file, in bytes,
+    /// as queried using [`FileFsSectorSizeInformation`] on the first call.
+    ///
+    /// Reads and writes of files opened for unbuffered I/O must be aligned to this size.
+    pub async fn sector_size(&self) -> crate::Result<u32> {
+        if let Some(sector_size) = self.sector_size.get() {
+            return Ok(*sector_size);
+        }
+        let info = self.query_fs_info::<FileFsSectorSizeInformation>().await?;
+        let sector_size = info.logical_bytes_per_sector.max(1);
+        Ok(*self.sector_size.get_or_init(|| sector_size))
+    }
+
+    /// Fails with [`Error::UnalignedUnbufferedIo`] if the file was opened for unbuffered I/O,
+    /// and `length` bytes at `offset` are not aligned to its sector size.
+    async fn _check_alignment(&self, offset: u64, length: usize) -> crate::Result<()> {
+        if !self.is_unbuffered() {
+            return Ok(());
+        }
+        let sector_size = self.sector_size().await?;
+        if offset % sector_size as u64 != 0 || length % sector_size as usize != 0 {
+            return Err(Error::UnalignedUnbufferedIo {
+                offset,
+                length,
+                sector_size,
+            });
+        }
+        Ok(())
+    }
+
     /// Read a block of data from an opened file.
     /// # Arguments
     /// * `buf` - The buffer to read the data into. A maximum of `buf.len()` bytes will be read.
@@ -54,6 +145,9 @@ impl File {
     /// * `unbuffered` - Whether to try using unbuffered I/O (if supported by the server).
     /// # Returns
     /// The number of bytes read, up to `buf.len()`.
+    ///
+    /// If the file was opened for unbuffered I/O, fails with [`std::io::ErrorKind::InvalidInput`]
+    /// if the read is not aligned to the sector size, see [`Error::UnalignedUnbufferedIo`].
     pub async fn read_block(
         &self,
         buf: &mut [u8],
@@ -77,13 +171,61 @@ impl File {
             return Ok(0);
         }
 
+        self._read_block(buf, pos, channel, unbuffered).await
+    }
+
+    /// Like [`File::read_block`], regardless of the end of the file when it was opened,
+    /// for reading data written through the handle after that.
+    ///
+    /// Reading at or past the actual end of the file fails with `STATUS_END_OF_FILE`.
+    pub(crate) async fn _read_block(
+        &self,
+        buf: &mut [u8],
+        pos: u64,
+        channel: Option<u32>,
+        unbuffered: bool,
+    ) -> std::io::Result<usize> {
+        let length = self
+            .clamp_buffer_size(buf.len(), BufferLimit::Read)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        self._check_alignment(pos, length as usize)
+            .await
+            .map_err(|e| match e {
+                Error::UnalignedUnbufferedIo { .. } => {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+                }
+                e => std::io::Error::other(e),
+            })?;
+
         log::debug!(
             "Reading up to {} bytes at offset {} from {}",
-            buf.len(),
+            length,
             pos,
             self.handle.name()
         );
 
+        let request = ReadRequest {
+            flags: self._read_flags(unbuffered),
+            length,
+            offset: pos,
+            file_id: self.handle.file_id().map_err(std::io::Error::other)?,
+            minimum_count: 1,
+        };
+        let content = Self::_send_read(&self.handle.handler, request, channel).await?;
+        let actual_read_length = content.buffer.len();
+        log::debug!(
+            "Read {} bytes from {}.",
+            actual_read_length,
+            self.handle.name()
+        );
+
+        buf[..actual_read_length].copy_from_slice(&content.buffer);
+
+        Ok(actual_read_length)
+    }
+
+    /// The flags of read requests: compressed if enabled, and unbuffered if requested and supported.
+    fn _read_flags(&self, unbuffered: bool) -> ReadFlags {
         let mut flags = ReadFlags::new();
         if self.handle.conn_info.config.compression_enabled
             && self.handle.conn_info.dialect.supports_compression()
@@ -94,39 +236,25 @@ impl File {
         if unbuffered && self.handle.conn_info.negotiation.dialect_rev >= Dialect::Smb0302 {
             flags.set_read_unbuffered(true);
         }
+        flags
+    }
 
-        let request = OutgoingMessage::new(
-            ReadRequest {
-                flags,
-                length: buf.len() as u32,
-                offset: pos,
-                file_id: self.handle.file_id().map_err(std::io::Error::other)?,
-                minimum_count: 1,
-            }
-            .into(),
-        )
-        .with_channel_id(channel);
-
-        let response = self
-            .handle
+    /// Sends a single read request, returning the response.
+    async fn _send_read(
+        handler: &HandlerReference<ResourceMessageHandle>,
+        request: ReadRequest,
+        channel: Option<u32>,
+    ) -> std::io::Result<ReadResponse> {
+        let request = OutgoingMessage::new(request.into()).with_channel_id(channel);
+        let response = handler
             .sendo_recvo(request, ReceiveOptions::new())
             .await
             .map_err(|e| std::io::Error::other(e.to_string()))?;
-        let content = response
+        response
             .message
             .content
             .to_read()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        let actual_read_length = content.buffer.len();
-        log::debug!(
-            "Read {} bytes from {}.",
-            actual_read_length,
-            self.handle.name()
-        );
-
-        buf[..actual_read_length].copy_from_slice(&content.buffer);
-
-        Ok(actual_read_length)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
     }
 
     /// Write a block of data to an opened file.
@@ -155,24 +283,141 @@ impl File {
     /// * `buf` - The data to write.
     /// * `pos` - The offset in the file to write to.
     /// # Returns
-    /// The number of bytes written.
+    /// The number of bytes written, which is always the length of `buf`.
+    /// # Notes
+    /// * If the server writes less data than requested, the remainder is retried
+    ///   up to [`ConnectionConfig::short_write_retries`][crate::ConnectionConfig::short_write_retries] times,
+    ///   before failing with [`Error::ShortWrite`]. On failure, the inner error holds the [`Error`] variant.
+    /// * If the file was opened for unbuffered I/O, writes that are not aligned to its sector size
+    ///   fail with [`Error::UnalignedUnbufferedIo`], without being sent.
     pub async fn write_block_zc(
         &self,
         buf: Arc<[u8]>,
         pos: u64,
         channel: Option<u32>,
     ) -> std::io::Result<usize> {
+        self._write_all_block(buf, pos, channel, WriteFlags::new())
+            .await
+            .map_err(Self::_write_error_to_io)
+    }
+
+    /// Writes the whole buffer at the specified offset, splitting it into requests of up to
+    /// the negotiated maximum write size.
+    ///
+    /// Up to [`ConnectionConfig::io_pipeline_depth`][crate::ConnectionConfig::io_pipeline_depth] requests
+    /// are kept in flight, each charged a part of the connection's credits.
+    ///
+    /// Short writes are retried as described in [`File::write_block_zc`], so this either writes
+    /// the whole buffer, or fails. On failure, other parts of the buffer may have been written.
+    pub async fn write_all_at(&self, buf: &[u8], pos: u64) -> crate::Result<()> {
+        self._write_all_at(buf, pos, WriteFlags::new()).await
+    }
+
+    /// [`File::write_all_at`], sending the write requests with `flags`.
+    pub(crate) async fn _write_all_at(
+        &self,
+        buf: &[u8],
+        pos: u64,
+        flags: WriteFlags,
+    ) -> crate::Result<()> {
+        let layout = self.pipeline_layout(self.conn_info.negotiation.max_write_size);
+        let jobs = buf
+            .chunks(layout.chunk_size)
+            .enumerate()
+            .map(|(i, chunk)| (pos + (i * layout.chunk_size) as u64, chunk))
+            .collect::<Vec<_>>();
+        run_pipelined(layout.depth, jobs, |(offset, chunk)| {
+            self._write_all_block(chunk.into(), offset, None, flags)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Reads into the whole buffer from the specified offset, splitting the read into requests of up to
+    /// the negotiated maximum read size.
+    ///
+    /// Requests are pipelined as in [`File::write_all_at`].
+    ///
+    /// ## Returns
+    /// The number of bytes read, which is less than `buf.len()` only if the end of the file was reached.
+    pub async fn read_all_at(&self, buf: &mut [u8], pos: u64) -> crate::Result<usize> {
+        let layout = self.pipeline_layout(self.conn_info.negotiation.max_read_size);
+        let chunk_size = layout.chunk_size;
+        let jobs = buf
+            .chunks_mut(chunk_size)
+            .enumerate()
+            .map(|(i, chunk)| (pos + (i * chunk_size) as u64, chunk))
+            .collect::<Vec<_>>();
+        let counts = run_pipelined(layout.depth, jobs, |(offset, chunk)| async move {
+            Ok(self.read_block(chunk, offset, None, false).await?)
+        })
+        .await?;
+
+        // The read is complete up to the first short read, which is continued one request at a time.
+        let mut read = 0;
+        for count in counts {
+            read += count;
+            if count < chunk_size {
+                break;
+            }
+        }
+        self._read_all_at_serial(buf, pos, read, chunk_size).await
+    }
+
+    /// Continues reading into `buf` from `read` bytes into it, one request at a time.
+    async fn _read_all_at_serial(
+        &self,
+        buf: &mut [u8],
+        pos: u64,
+        mut read: usize,
+        chunk_size: usize,
+    ) -> crate::Result<usize> {
+        while read < buf.len() {
+            let end = buf.len().min(read + chunk_size);
+            let count = self
+                .read_block(&mut buf[read..end], pos + read as u64, None, false)
+                .await?;
+            if count == 0 {
+                break;
+            }
+            read += count;
+        }
+        Ok(read)
+    }
+
+    /// Returns the depth and request size of pipelined reads or writes, up to `max_size` bytes each.
+    fn pipeline_layout(&self, max_size: u32) -> PipelineLayout {
+        let config = &self.conn_info.config;
+        PipelineLayout::new(
+            max_size,
+            self.conn_info.negotiation.caps.large_mtu(),
+            config.io_pipeline_depth(),
+            config.credits_backlog(),
+        )
+    }
+
+    /// Writes the whole buffer, retrying the remainder of short writes.
+    ///
+    /// If the buffer exceeds the negotiated maximum write size, and the size is clamped
+    /// by the configured [`BufferSizePolicy`][crate::connection::BufferSizePolicy],
+    /// it is written in multiple requests.
+    async fn _write_all_block(
+        &self,
+        buf: Arc<[u8]>,
+        pos: u64,
+        channel: Option<u32>,
+        flags: WriteFlags,
+    ) -> crate::Result<usize> {
         if buf.is_empty() {
             return Ok(0);
         }
 
         if !self.access.file_write_data() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::PermissionDenied,
-                "No write permission",
-            ));
+            return Err(Error::MissingPermissions("file_write_data".to_string()));
         }
+        self._check_alignment(pos, buf.len()).await?;
 
+        let chunk_size = (self.clamp_buffer_size(buf.len(), BufferLimit::Write)? as usize).max(1);
         log::debug!(
             "Writing {} bytes at offset {} to {}",
             buf.len(),
@@ -180,56 +425,125 @@ impl File {
             self.handle.name()
         );
 
+        if chunk_size >= buf.len() {
+            self._write_chunk(Arc::clone(&buf), pos, channel, flags)
+                .await?;
+        } else {
+            for (i, chunk) in buf.chunks(chunk_size).enumerate() {
+                let offset = pos + (i * chunk_size) as u64;
+                self._write_chunk(chunk.into(), offset, channel, flags)
+                    .await?;
+            }
+        }
+        log::debug!("Wrote {} bytes to {}.", buf.len(), self.handle.name());
+        Ok(buf.len())
+    }
+
+    /// Writes a buffer of up to the negotiated maximum write size, retrying the remainder of short writes.
+    async fn _write_chunk(
+        &self,
+        buf: Arc<[u8]>,
+        pos: u64,
+        channel: Option<u32>,
+        flags: WriteFlags,
+    ) -> crate::Result<()> {
+        let mut progress =
+            WriteProgress::new(buf.len(), pos, self.conn_info.config.short_write_retries());
+        while let Some(remaining) = progress.remaining() {
+            // Copying only takes place when retrying a short write.
+            let data = if remaining.start == 0 {
+                Arc::clone(&buf)
+            } else {
+                Arc::from(&buf[remaining.clone()])
+            };
+            let count = Self::_send_write(
+                &self.handle.handler,
+                self.handle.file_id()?,
+                data,
+                progress.next_offset(),
+                channel,
+                flags,
+            )
+            .await
+            .map_err(|e| progress.map_error(e))?;
+            progress.advance(count)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a single write request, returning the count of the response.
+    async fn _send_write(
+        handler: &HandlerReference<ResourceMessageHandle>,
+        file_id: FileId,
+        data: Arc<[u8]>,
+        pos: u64,
+        channel: Option<u32>,
+        flags: WriteFlags,
+    ) -> crate::Result<usize> {
         // Arc is accepted to provide safety regarding the buffer's lifetime,
         // without forcing an actual copy of the data.
-        let outgoing = OutgoingMessage::new(
-            WriteRequest::new(
-                pos,
-                self.handle.file_id().map_err(std::io::Error::other)?,
-                WriteFlags::new(),
-                buf.len() as u32,
-            )
-            .into(),
-        )
-        .with_additional_data(Arc::clone(&buf))
-        .with_channel_id(channel);
+        let outgoing =
+            OutgoingMessage::new(WriteRequest::new(pos, file_id, flags, data.len() as u32).into())
+                .with_additional_data(data)
+                .with_compress(true)
+                .with_channel_id(channel);
 
-        let response = self
-            .handle
-            .sendo_recvo(outgoing, ReceiveOptions::new().with_allow_async(true))
-            .await
-            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        let response = handler.sendo_recv(outgoing).await?;
+        Ok(response.message.content.to_write()?.count as usize)
+    }
 
-        let content = response
-            .message
-            .content
-            .to_write()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        let actual_written_length = content.count as usize;
-        log::debug!(
-            "Wrote {} bytes to {}.",
-            actual_written_length,
-            self.handle.name()
-        );
-        Ok(actual_written_length)
+    fn _write_error_to_io(e: Error) -> std::io::Error {
+        if let Error::IoError(e) = e {
+            return e;
+        }
+        let kind = match &e {
+            Error::MissingPermissions(_) => std::io::ErrorKind::PermissionDenied,
+            Error::UnalignedUnbufferedIo { .. } => std::io::ErrorKind::InvalidInput,
+            Error::ShortWrite { .. } => std::io::ErrorKind::WriteZero,
+            Error::DiskFull { .. } => std::io::ErrorKind::StorageFull,
+            Error::QuotaExceeded { .. } => std::io::ErrorKind::QuotaExceeded,
+            _ => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, e)
     }
 
     /// Sends a flush request to the server to flush the file.
+    ///
+    /// See [`ResourceHandle::flush`]. Fails with [`std::io::ErrorKind::PermissionDenied`]
+    /// if the file was not opened with write or append access.
     pub async fn flush(&self) -> std::io::Result<()> {
-        let _response = self
-            .handle
-            .send_recvo(
-                FlushRequest {
-                    file_id: self.handle.file_id().map_err(std::io::Error::other)?,
-                }
-                .into(),
-                ReceiveOptions::new().with_allow_async(true),
-            )
+        self.handle.flush().await.map_err(Self::_write_error_to_io)
+    }
+
+    /// Sends a flush request.
+    async fn _send_flush(
+        handler: &HandlerReference<ResourceMessageHandle>,
+        file_id: FileId,
+    ) -> std::io::Result<()> {
+        ResourceHandle::send_flush(handler, file_id)
             .await
-            .map_err(|e| std::io::Error::other(e.to_string()))?;
+            .map_err(|e| std::io::Error::other(e.to_string()))
+    }
 
-        log::debug!("Flushed {}.", self.handle.name());
-        Ok(())
+    /// Returns the position the I/O traits implementations seek to.
+    ///
+    /// Seeking beyond the end of the file is allowed, as in [`std::io::Seek`].
+    fn seek_position(&self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            std::io::SeekFrom::Start(pos) => return Ok(pos),
+            std::io::SeekFrom::End(offset) => (self.end_of_file, offset),
+            std::io::SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        base.checked_add_signed(offset).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek position")
+        })
+    }
+
+    /// Updates the position of the I/O traits implementations, after writing `written` bytes at it.
+    fn advance_written(&mut self, written: usize) {
+        self.pos += written as u64;
+        self.end_of_file = self.end_of_file.max(self.pos);
+        self.dirty = true;
     }
 
     /// Performs a server-side copy from another file on the same server.
@@ -237,13 +551,31 @@ impl File {
     /// * `from` - The file to copy from.
     /// # Notes
     /// * This copy must be performed against a file from the same share (tree) as this file.
+    /// * See [`File::copy_to`], which also returns the number of bytes copied.
     pub async fn srv_copy(&self, from: &File) -> crate::Result<()> {
-        if !self.access.file_write_data() {
+        from.copy_to(self).await?;
+        Ok(())
+    }
+
+    /// Performs a server-side copy of this file to another file on the same share,
+    /// returning the number of bytes copied.
+    ///
+    /// The data is copied by the server, using `FSCTL_SRV_COPYCHUNK`, without passing through the client.
+    /// The destination is resized to the length of this file.
+    /// # Arguments
+    /// * `dest` - The file to copy to. Must be opened with write access, from the same share (tree) as this file.
+    /// # Notes
+    /// * The copy is split into requests within the server's limits. If the server rejects a request
+    ///   for exceeding its limits, the request is retried within the limits the server returned.
+    /// * If the server does not support server-side copy, [`Error::UnsupportedOperation`] is returned,
+    ///   and the caller may fall back to copying the data through the client.
+    pub async fn copy_to(&self, dest: &File) -> crate::Result<u64> {
+        if !dest.access.file_write_data() {
             return Err(Error::InvalidState(
                 "No write permission on destination file".to_string(),
             ));
         }
-        if !from.access.file_read_data() {
+        if !self.access.file_read_data() {
             return Err(Error::InvalidState(
                 "No read permission on source file".to_string(),
             ));
@@ -251,85 +583,213 @@ impl File {
 
         // Even if we weren't testing it properly, the remote would have returned
         // [Status::ObjectNameNotFound] error for unmatching trees.
-        if !self.same_tree(from) {
+        if !dest.same_tree(self) {
             return Err(Error::InvalidArgument(
                 "Source and destination files must be opened from the same share (tree)"
                     .to_string(),
             ));
         }
 
-        let other_end_of_file = from.get_len().await?;
-        self.set_len(other_end_of_file).await?;
+        let resume_key = match self.fsctl(SrvRequestResumeKeyRequest(())).await {
+            Ok(response) => response.resume_key,
+            Err(Error::ServerError(
+                status @ (Status::NotSupported | Status::InvalidDeviceRequest0),
+                _,
+            )) => {
+                return Err(Error::UnsupportedOperation(format!(
+                    "Server-side copy of {} is not supported by the server: {status}",
+                    self.name(),
+                )));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let len = self.get_len().await?;
+        dest.set_len(len).await?;
+
+        // FSCTL_SRV_COPYCHUNK also requires read access on the destination,
+        // but FSCTL_SRV_COPYCHUNK_WRITE is only supported from SMB 2.1.
+        let copy_write = dest.handle.conn_info.negotiation.dialect_rev >= Dialect::Smb021;
+        let mut limits = CopychunkLimits::DEFAULT;
+        let mut copied = 0;
+        while copied < len {
+            let request = SrvCopychunkCopy {
+                source_key: resume_key,
+                chunks: limits.make_chunks(copied, len),
+            };
+            let requested = request.chunks.iter().map(|c| c.length as u64).sum::<u64>();
+            const ACCEPTED_STATUS: &[Status] = &[Status::Success, Status::InvalidParameter];
+            let (status, response) = if copy_write {
+                dest._fsctl_with_status(
+                    SrvCopyChunkCopyWrite(request),
+                    SrvCopychunkResponse::SIZE as u32,
+                    ACCEPTED_STATUS,
+                )
+                .await?
+            } else {
+                dest._fsctl_with_status(request, SrvCopychunkResponse::SIZE as u32, ACCEPTED_STATUS)
+                    .await?
+            };
+
+            if status == Status::U32_INVALID_PARAMETER {
+                limits = limits.reduce_to(&response)?;
+                log::debug!("Server-side copy limits exceeded, retrying within {limits:?}");
+                continue;
+            }
+            let written = response.total_bytes_written as u64;
+            if written == 0 || written > requested {
+                return Err(Error::InvalidMessage(format!(
+                    "Server reported copying {written} bytes at offset {copied}, of {requested} bytes requested"
+                )));
+            }
+            copied += written;
+        }
+
+        log::debug!(
+            "Copied {copied} bytes from {} to {} on the server.",
+            self.name(),
+            dest.name()
+        );
+        Ok(copied)
+    }
+
+    /// Makes the file sparse, or makes it not sparse, using `FSCTL_SET_SPARSE`.
+    ///
+    /// The server does not have to allocate storage for zeroed ranges of a sparse file,
+    /// which may be found by [`File::query_allocated_ranges`].
+    /// Making a file not sparse allocates all of its ranges.
+    pub async fn set_sparse(&self, sparse: bool) -> crate::Result<()> {
+        if !self.access.file_write_data() && !self.access.file_write_attributes() {
+            return Err(Error::MissingPermissions(
+                "file_write_data or file_write_attributes".to_string(),
+            ));
+        }
+        self.fsctl(SetSparseRequest {
+            set_sparse: sparse.into(),
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the allocated ranges of the file within `len` bytes from `offset`,
+    /// using `FSCTL_QUERY_ALLOCATED_RANGES`.
+    ///
+    /// Ranges of a sparse file that are not returned contain zeros, and need not be read.
+    /// For a file that is not sparse, the whole queried range is returned.
+    ///
+    /// If the server returns a partial list, the query is resumed from the end of the last returned range,
+    /// until all ranges are returned.
+    pub async fn query_allocated_ranges(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> crate::Result<Vec<FileAllocatedRangeBuffer>> {
+        /// The output size of each query, fitting 4096 ranges.
+        const QUERY_OUTPUT_SIZE: u32 = 0x10000;
+
+        if !self.access.file_read_data() {
+            return Err(Error::MissingPermissions("file_read_data".to_string()));
+        }
 
-        let resume_key_response = from.fsctl(SrvRequestResumeKeyRequest(())).await?;
-        let resume_key = resume_key_response.resume_key;
+        let end = offset.saturating_add(len);
+        let mut ranges = Vec::new();
+        let mut next_offset = offset;
+        while next_offset < end {
+            let (status, result) = self
+                ._fsctl_with_status(
+                    FileAllocatedRangeBuffer {
+                        file_offset: next_offset,
+                        length: end - next_offset,
+                    },
+                    QUERY_OUTPUT_SIZE,
+                    &[Status::Success, Status::BufferOverflow],
+                )
+                .await?;
+            let result = Vec::from(result);
+            let last_end = result.last().map(FileAllocatedRangeBuffer::end);
+            ranges.extend(result);
+            if status != Status::U32_BUFFER_OVERFLOW {
+                break;
+            }
 
-        let chunks = (0..other_end_of_file)
-            .step_by(CHUNK_SIZE)
-            .map(|start| {
-                let len_left = other_end_of_file - start;
-                SrvCopychunkItem {
-                    source_offset: start,
-                    target_offset: start,
-                    length: std::cmp::min(CHUNK_SIZE as u32, len_left as u32),
+            match last_end {
+                Some(last_end) if last_end > next_offset => next_offset = last_end,
+                _ => {
+                    return Err(Error::InvalidMessage(format!(
+                        "Server returned a partial list of allocated ranges, without ranges after offset {next_offset}"
+                    )));
                 }
-            })
-            .collect::<Vec<_>>();
+            }
+        }
+        Ok(ranges)
+    }
+
+    /// Locks `len` bytes of the file from `offset`, using an SMB2 LOCK request.
+    ///
+    /// Exclusive locks conflict with any other lock of an overlapping range, from another handle.
+    /// Shared locks only conflict with exclusive locks.
+    ///
+    /// If `fail_immediately` is set, a conflicting lock fails the request with
+    /// [`Status::LockNotGranted`]. Otherwise, the request waits until the conflicting locks are released.
+    ///
+    /// Locks are released by [`File::unlock_range`], or when the file is closed.
+    pub async fn lock_range(
+        &self,
+        offset: u64,
+        len: u64,
+        exclusive: bool,
+        fail_immediately: bool,
+    ) -> crate::Result<()> {
+        let flags = LockFlag::new()
+            .with_shared(!exclusive)
+            .with_exclusive(exclusive)
+            .with_fail_immediately(fail_immediately);
+        self._lock(offset, len, flags).await?;
+        log::debug!("Locked {len} bytes at offset {offset} of {}.", self.name());
+        Ok(())
+    }
+
+    /// Unlocks a range locked by [`File::lock_range`] on this handle.
+    ///
+    /// The range must match a locked range exactly.
+    /// Otherwise, the request fails with [`Status::RangeNotLocked`].
+    pub async fn unlock_range(&self, offset: u64, len: u64) -> crate::Result<()> {
+        self._lock(offset, len, LockFlag::new().with_unlock(true))
+            .await?;
+        log::debug!(
+            "Unlocked {len} bytes at offset {offset} of {}.",
+            self.name()
+        );
+        Ok(())
+    }
 
-        const CHUNK_SIZE: usize = 1024 * 1024; // 1 MB
-        let req = SrvCopychunkCopy {
-            source_key: resume_key,
-            chunks,
+    async fn _lock(&self, offset: u64, length: u64, flags: LockFlag) -> crate::Result<()> {
+        // Lock sequences protect replayed lock requests on resilient and persistent handles.
+        // Lock requests are not replayed, and servers skip the verification of index 0 (MS-SMB2 3.3.5.14).
+        let request = LockRequest {
+            lock_sequence: LockSequence::new(),
+            file_id: self.file_id()?,
+            locks: vec![LockElement {
+                offset,
+                length,
+                flags,
+            }],
         };
-        let copy_response = self.fsctl(req).await?;
-        if copy_response.total_bytes_written as u64 != other_end_of_file {
-            return Err(Error::InvalidArgument(format!(
-                "Expected to write {} bytes, but wrote {} bytes",
-                other_end_of_file, copy_response.total_bytes_written
-            )));
-        }
+        self.handle
+            .handler
+            .send_recv(request.into())
+            .await?
+            .message
+            .content
+            .to_lock()?;
         Ok(())
     }
 }
 
-// Despite being available, seeking means nothing here,
-// since it may only be used when calling read/write from the std::io traits.
 #[cfg(not(feature = "async"))]
 impl Seek for File {
     fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
-        let next_pos = match pos {
-            std::io::SeekFrom::Start(pos) => pos,
-            std::io::SeekFrom::End(pos) => {
-                let pos = self.end_of_file as i64 + pos;
-                if pos < 0 {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidInput,
-                        "Invalid seek position",
-                    ));
-                }
-                pos.try_into().map_err(|_| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek position")
-                })?
-            }
-            std::io::SeekFrom::Current(pos) => {
-                let pos = self.pos as i64 + pos;
-                if pos < 0 {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidInput,
-                        "Invalid seek position",
-                    ));
-                }
-                pos.try_into().map_err(|_| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek position")
-                })?
-            }
-        };
-        if next_pos > self.end_of_file {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Invalid seek position",
-            ));
-        }
+        self.pos = self.seek_position(pos)?;
         Ok(self.pos)
     }
 }
@@ -337,7 +797,10 @@ impl Seek for File {
 #[cfg(not(feature = "async"))]
 impl Read for File {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let read_length = File::read_block(self, buf, self.pos, None, false)
+        let length = buf
+            .len()
+            .min(self.conn_info.negotiation.max_read_size.max(1) as usize);
+        let read_length = File::read_block(self, &mut buf[..length], self.pos, None, false)
             .map_err(|e| std::io::Error::other(e.to_string()))?;
         self.pos += read_length as u64;
         Ok(read_length)
@@ -347,9 +810,11 @@ impl Read for File {
 #[cfg(not(feature = "async"))]
 impl Write for File {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let written_length = File::write_block(self, buf, self.pos, None)?;
-        self.pos += written_length as u64;
-        self.dirty = true;
+        let length = buf
+            .len()
+            .min(self.conn_info.negotiation.max_write_size.max(1) as usize);
+        let written_length = File::write_block(self, &buf[..length], self.pos, None)?;
+        self.advance_written(written_length);
         Ok(written_length)
     }
 
@@ -357,7 +822,9 @@ impl Write for File {
         if !self.dirty {
             return Ok(());
         }
-        File::flush(self)
+        File::flush(self)?;
+        self.dirty = false;
+        Ok(())
     }
 }
 
@@ -383,9 +850,8 @@ impl WriteAtChannel for File {
         offset: u64,
         channel: Option<u32>,
     ) -> crate::Result<usize> {
-        self.write_block(buf, offset, channel)
+        self._write_all_block(buf.into(), offset, channel, WriteFlags::new())
             .await
-            .map_err(crate::Error::IoError)
     }
 }
 
@@ -417,3 +883,567 @@ impl DerefMut for File {
         &mut self.handle
     }
 }
+
+/// Tracks the progress of a single write, which the server may split by writing less than requested.
+struct WriteProgress {
+    requested: usize,
+    offset: u64,
+    written: usize,
+    retries_left: u32,
+}
+
+impl WriteProgress {
+    fn new(requested: usize, offset: u64, retries: u32) -> Self {
+        Self {
+            requested,
+            offset,
+            written: 0,
+            retries_left: retries,
+        }
+    }
+
+    /// Returns the range of the buffer that is yet to be written, or `None` if the write is complete.
+    fn remaining(&self) -> Option<std::ops::Range<usize>> {
+        (self.written < self.requested).then_some(self.written..self.requested)
+    }
+
+    /// Returns the file offset of the remaining data.
+    fn next_offset(&self) -> u64 {
+        self.offset + self.written as u64
+    }
+
+    /// Records the count of a write response.
+    fn advance(&mut self, count: usize) -> crate::Result<()> {
+        let remaining = self.requested - self.written;
+        if count > remaining {
+            return Err(Error::InvalidMessage(format!(
+                "Server reported writing {count} bytes, but only {remaining} bytes were sent"
+            )));
+        }
+        self.written += count;
+        if self.written == self.requested {
+            return Ok(());
+        }
+
+        if self.retries_left == 0 {
+            return Err(Error::ShortWrite {
+                requested: self.requested,
+                written: self.written,
+                offset: self.offset,
+            });
+        }
+        self.retries_left -= 1;
+        log::warn!(
+            "Short write at offset {}: {count} of {remaining} bytes were written, retrying the remainder",
+            self.next_offset() - count as u64,
+        );
+        Ok(())
+    }
+
+    /// Maps errors that indicate the server is out of space, including the progress made so far.
+    fn map_error(&self, e: Error) -> Error {
+        match e.status() {
+            Some(Status::DiskFull) => Error::DiskFull {
+                written: self.written,
+                offset: self.offset,
+            },
+            Some(Status::QuotaExceeded) => Error::QuotaExceeded {
+                written: self.written,
+                offset: self.offset,
+            },
+            _ => e,
+        }
+    }
+}
+
+/// The limits of a single server-side copy request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CopychunkLimits {
+    max_chunks: u32,
+    max_chunk_size: u32,
+    max_total_size: u32,
+}
+
+impl CopychunkLimits {
+    /// The default limits of the server, as of MS-SMB2 3.3.3, which Windows servers use.
+    const DEFAULT: Self = Self {
+        max_chunks: 256,
+        max_chunk_size: 1024 * 1024,
+        max_total_size: 16 * 1024 * 1024,
+    };
+
+    /// Returns the chunks of the next request of a copy of `len` bytes, starting at `offset`.
+    fn make_chunks(&self, offset: u64, len: u64) -> Vec<SrvCopychunkItem> {
+        let end = len.min(offset + self.max_total_size as u64);
+        (offset..end)
+            .step_by(self.max_chunk_size as usize)
+            .take(self.max_chunks as usize)
+            .map(|start| SrvCopychunkItem {
+                source_offset: start,
+                target_offset: start,
+                length: (end - start).min(self.max_chunk_size as u64) as u32,
+            })
+            .collect()
+    }
+
+    /// Returns the limits of the server, from the response to a request that exceeded them.
+    ///
+    /// Fails if the server returned no limits, or limits that are not below the current ones,
+    /// since retrying would not make progress.
+    fn reduce_to(&self, response: &SrvCopychunkResponse) -> crate::Result<Self> {
+        let limits = Self {
+            max_chunks: response.chunks_written,
+            max_chunk_size: response.chunk_bytes_written,
+            max_total_size: response.total_bytes_written,
+        };
+        let is_reduced = limits.max_chunks < self.max_chunks
+            || limits.max_chunk_size < self.max_chunk_size
+            || limits.max_total_size < self.max_total_size;
+        if limits.max_chunks == 0
+            || limits.max_chunk_size == 0
+            || limits.max_total_size == 0
+            || !is_reduced
+        {
+            return Err(Error::UnsupportedOperation(format!(
+                "Server-side copy was rejected by the server, with limits {limits:?}"
+            )));
+        }
+        Ok(Self {
+            max_chunks: limits.max_chunks.min(self.max_chunks),
+            max_chunk_size: limits.max_chunk_size.min(self.max_chunk_size),
+            max_total_size: limits.max_total_size.min(self.max_total_size),
+        })
+    }
+}
+
+/// The layout of the requests of [`File::read_all_at`] and [`File::write_all_at`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PipelineLayout {
+    /// The number of requests in flight.
+    depth: usize,
+    /// The size of each request.
+    chunk_size: usize,
+}
+
+impl PipelineLayout {
+    fn new(max_size: u32, large_mtu: bool, depth: u16, credits: u16) -> Self {
+        let max_size = max_size.max(1) as usize;
+        if !large_mtu || depth <= 1 {
+            return Self {
+                depth: 1,
+                chunk_size: max_size,
+            };
+        }
+        // Split the credits between the requests, so that all of them can be in flight together.
+        let credits_per_request = (credits / depth).max(1) as usize;
+        Self {
+            depth: depth as usize,
+            chunk_size: max_size.min(
+                credits_per_request
+                    * crate::connection::ConnectionMessageHandler::CREDIT_CALC_RATIO as usize,
+            ),
+        }
+    }
+}
+
+/// Runs `f` on each of the jobs, keeping up to `depth` of them in flight.
+///
+/// Returns the results in the order of the jobs, or the first error.
+#[cfg(feature = "async")]
+async fn run_pipelined<T, R, F, Fut>(depth: usize, jobs: Vec<T>, f: F) -> crate::Result<Vec<R>>
+where
+    F: FnMut(T) -> Fut,
+    Fut: std::future::Future<Output = crate::Result<R>>,
+{
+    use futures_util::{StreamExt, TryStreamExt, stream};
+    stream::iter(jobs)
+        .map(f)
+        .buffered(depth.max(1))
+        .try_collect()
+        .await
+}
+
+/// Runs `f` on each of the jobs, on up to `depth` threads.
+///
+/// Returns the results in the order of the jobs, or the first error.
+#[cfg(feature = "multi_threaded")]
+fn run_pipelined<T: Send, R: Send>(
+    depth: usize,
+    jobs: Vec<T>,
+    f: impl Fn(T) -> crate::Result<R> + Sync,
+) -> crate::Result<Vec<R>> {
+    if depth <= 1 || jobs.len() <= 1 {
+        return jobs.into_iter().map(f).collect();
+    }
+    let threads = depth.min(jobs.len());
+    let jobs = std::sync::Mutex::new(jobs.into_iter().enumerate());
+    let mut results = std::thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    // Jobs are taken in order, so the jobs left after a failure are all after it.
+                    while let Some((i, job)) = jobs.lock().unwrap().next() {
+                        let result = f(job);
+                        let failed = result.is_err();
+                        results.push((i, result));
+                        if failed {
+                            break;
+                        }
+                    }
+                    results
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Runs `f` on each of the jobs, one at a time.
+#[cfg(feature = "single_threaded")]
+fn run_pipelined<T, R>(
+    _depth: usize,
+    jobs: Vec<T>,
+    f: impl FnMut(T) -> crate::Result<R>,
+) -> crate::Result<Vec<R>> {
+    jobs.into_iter().map(f).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CopychunkLimits, PipelineLayout, WriteProgress};
+    use crate::Error;
+    use smb_msg::{ErrorResponse, SrvCopychunkResponse, Status};
+
+    const OFFSET: u64 = 0x1000;
+
+    /// Writes `data` into `file`, where each write request is handled by `server`,
+    /// which returns the number of bytes it actually wrote.
+    fn write_with(
+        file: &mut [u8],
+        data: &[u8],
+        retries: u32,
+        mut server: impl FnMut(usize) -> crate::Result<usize>,
+    ) -> crate::Result<()> {
+        let mut progress = WriteProgress::new(data.len(), OFFSET, retries);
+        while let Some(remaining) = progress.remaining() {
+            let count = server(remaining.len()).map_err(|e| progress.map_error(e))?;
+            let start = (progress.next_offset() - OFFSET) as usize;
+            let copied = count.min(remaining.len());
+            file[start..start + copied].copy_from_slice(&data[remaining][..copied]);
+            progress.advance(count)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_writes_retried() {
+        let data = (0..100u8).collect::<Vec<_>>();
+        let mut file = vec![0; data.len()];
+        let mut counts = [30, 0, 50].into_iter();
+        write_with(&mut file, &data, 3, |requested| {
+            Ok(counts.next().unwrap_or(requested))
+        })
+        .unwrap();
+        assert_eq!(file, data);
+    }
+
+    #[test]
+    fn test_short_write_retries_exhausted() {
+        let data = [0xab; 100];
+        let mut file = vec![0; data.len()];
+        let result = write_with(&mut file, &data, 2, |requested| Ok(requested.min(10)));
+        assert!(matches!(
+            result,
+            Err(Error::ShortWrite {
+                requested: 100,
+                written: 30,
+                offset: OFFSET
+            })
+        ));
+        assert_eq!(file[..30], data[..30]);
+    }
+
+    #[test]
+    fn test_write_count_too_large() {
+        let mut file = vec![0; 0x10];
+        let result = write_with(&mut file, &[0; 8], 3, |requested| Ok(requested + 1));
+        assert!(matches!(result, Err(Error::InvalidMessage(_))));
+    }
+
+    #[test]
+    fn test_short_write_disk_full() {
+        let data = [0xcd; 100];
+        let mut file = vec![0; data.len()];
+        let mut first = true;
+        let result = write_with(&mut file, &data, 3, |_| {
+            if std::mem::take(&mut first) {
+                Ok(60)
+            } else {
+                Err(Error::ServerError(
+                    Status::DiskFull,
+                    Some(ErrorResponse { error_data: vec![] }),
+                ))
+            }
+        });
+        assert!(matches!(
+            result,
+            Err(Error::DiskFull {
+                written: 60,
+                offset: OFFSET
+            })
+        ));
+
+        let result = write_with(&mut file, &data, 3, |_| {
+            Err(Error::ServerError(Status::QuotaExceeded, None))
+        });
+        assert!(matches!(
+            result,
+            Err(Error::QuotaExceeded {
+                written: 0,
+                offset: OFFSET
+            })
+        ));
+    }
+
+    #[test]
+    fn test_copychunk_default_limits() {
+        const MB: u64 = 1024 * 1024;
+        let limits = CopychunkLimits::DEFAULT;
+        let chunks = limits.make_chunks(0, 40 * MB);
+        assert_eq!(chunks.len(), 16);
+        assert!(chunks.iter().all(|c| c.length as u64 == MB));
+
+        // The last request is shorter, and so is its last chunk.
+        let chunks = limits.make_chunks(32 * MB, 40 * MB + 10);
+        assert_eq!(chunks.len(), 9);
+        assert_eq!(chunks[0].source_offset, 32 * MB);
+        assert_eq!(chunks[8].target_offset, 40 * MB);
+        assert_eq!(chunks[8].length, 10);
+    }
+
+    #[test]
+    fn test_copychunk_limits_reduced() {
+        let limits = CopychunkLimits::DEFAULT
+            .reduce_to(&SrvCopychunkResponse {
+                chunks_written: 16,
+                chunk_bytes_written: 0x10000,
+                total_bytes_written: 0x200000,
+            })
+            .unwrap();
+        let chunks = limits.make_chunks(0, 0x1000000);
+        assert_eq!(chunks.len(), 16);
+        assert!(chunks.iter().all(|c| c.length == 0x10000));
+
+        // Larger limits than the current ones are ignored.
+        let limits = limits
+            .reduce_to(&SrvCopychunkResponse {
+                chunks_written: 256,
+                chunk_bytes_written: 0x1000,
+                total_bytes_written: 0x1000000,
+            })
+            .unwrap();
+        assert_eq!(
+            limits,
+            CopychunkLimits {
+                max_chunks: 16,
+                max_chunk_size: 0x1000,
+                max_total_size: 0x200000,
+            }
+        );
+    }
+
+    #[test]
+    fn test_copychunk_limits_not_reduced() {
+        let result = CopychunkLimits::DEFAULT.reduce_to(&SrvCopychunkResponse {
+            chunks_written: 256,
+            chunk_bytes_written: 1024 * 1024,
+            total_bytes_written: 16 * 1024 * 1024,
+        });
+        assert!(matches!(result, Err(Error::UnsupportedOperation(_))));
+    }
+
+    #[test]
+    fn test_pipeline_layout() {
+        const MB: u32 = 1024 * 1024;
+        // The default 128 credits are split between 4 requests of 2MB.
+        assert_eq!(
+            PipelineLayout::new(8 * MB, true, 4, 128),
+            PipelineLayout {
+                depth: 4,
+                chunk_size: 2 * MB as usize
+            }
+        );
+        // Smaller maximum sizes are kept.
+        assert_eq!(
+            PipelineLayout::new(MB, true, 4, 128),
+            PipelineLayout {
+                depth: 4,
+                chunk_size: MB as usize
+            }
+        );
+        // At least a single credit per request.
+        assert_eq!(PipelineLayout::new(8 * MB, true, 16, 8).chunk_size, 0x10000);
+        // No pipelining without multi-credit requests.
+        assert_eq!(
+            PipelineLayout::new(0x10000, false, 4, 128),
+            PipelineLayout {
+                depth: 1,
+                chunk_size: 0x10000
+            }
+        );
+    }
+
+    /// Unaligned I/O of an unbuffered file fails without being sent, and the sector size is queried once.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_unbuffered_alignment() {
+        use crate::FileCreateArgs;
+        use crate::test_util::*;
+        use binrw::BinWrite;
+        use smb_fscc::{
+            FileAccessMask, FileAttributes, FileFsSectorSizeInformation, SectorSizeInfoFlags,
+        };
+        use smb_msg::{Command, FileId, QueryInfoResponse, ShareFlags, WriteResponse};
+        use smb_transport::mock::MockStep;
+
+        const FILE_ID: FileId = FileId {
+            persistent: 0x30,
+            volatile: 0x40,
+        };
+        let mut info = std::io::Cursor::new(vec![]);
+        FileFsSectorSizeInformation {
+            logical_bytes_per_sector: 512,
+            physical_bytes_per_sector: 4096,
+            physical_bytes_per_sector_for_performance: 4096,
+            effective_physical_bytes_per_sector_for_atomicity: 4096,
+            flags: SectorSizeInfoFlags::new(),
+            byte_offset_for_sector_alignment: 0,
+            byte_offset_for_partition_alignment: 0,
+        }
+        .write_le(&mut info)
+        .unwrap();
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                MockStep::expect(Command::Create)
+                    .matching("opens unbuffered", |r| {
+                        r.content.as_create().is_ok_and(|create| {
+                            create.create_options.no_intermediate_buffering()
+                                && create.create_options.write_through()
+                        })
+                    })
+                    .respond(create_response(FILE_ID, FileAttributes::new())),
+                MockStep::expect(Command::QueryInfo)
+                    .respond(QueryInfoResponse::new(info.into_inner())),
+                MockStep::expect(Command::Write)
+                    .matching("writes a sector", |r| {
+                        r.content
+                            .as_write()
+                            .is_ok_and(|write| write.offset == 1024 && write.length == 512)
+                    })
+                    .respond(WriteResponse { count: 512 }),
+                MockStep::expect(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::standard_rw())
+            .with_write_through(true)
+            .with_no_intermediate_buffering(true);
+        let file = mock.tree.create("file", &args).await.unwrap().unwrap_file();
+        assert!(file.is_unbuffered());
+
+        let result = file.write_block(&[0; 100], 1024, None).await;
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(matches!(
+            error.into_inner().unwrap().downcast::<Error>().as_deref(),
+            Ok(Error::UnalignedUnbufferedIo {
+                offset: 1024,
+                length: 100,
+                sector_size: 512
+            })
+        ));
+        let result = file.write_all_at(&[0; 512], 100).await;
+        assert!(matches!(result, Err(Error::UnalignedUnbufferedIo { .. })));
+        let written = file.write_block(&[0; 512], 1024, None).await.unwrap();
+        assert_eq!(written, 512);
+        let sector_size = file.sector_size().await.unwrap();
+        assert_eq!(sector_size, 512);
+
+        file.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    /// Both reads go async, and the final response of the first one is delivered last.
+    #[cfg(not(feature = "single_threaded"))]
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_interim_async_reads() {
+        use crate::test_util::*;
+        use smb_fscc::{FileAccessMask, FileAttributes};
+        use smb_msg::{Command, CreateResponse, FileId, ReadResponse, ShareFlags};
+        use smb_transport::mock::MockStep;
+
+        const FILE_ID: FileId = FileId {
+            persistent: 0x50,
+            volatile: 0x60,
+        };
+        fn read_step(offset: u64, async_id: u64) -> MockStep {
+            MockStep::expect(Command::Read)
+                .matching("reads at the offset", move |r| {
+                    r.content.as_read().is_ok_and(|read| read.offset == offset)
+                })
+                .respond(ReadResponse {
+                    buffer: vec![(offset >> 8) as u8; 0x10],
+                })
+                .interim(async_id)
+        }
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                MockStep::expect(Command::Create).respond(CreateResponse {
+                    endof_file: 0x1000,
+                    ..create_response(FILE_ID, FileAttributes::new())
+                }),
+                read_step(0x100, 1).deferred(),
+                read_step(0x200, 2),
+                MockStep::expect(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+        let file = mock
+            .tree
+            .open_existing("file", FileAccessMask::new().with_generic_read(true))
+            .await
+            .unwrap()
+            .unwrap_file();
+        // The first read is sent once the file is open.
+        let first_request = connect_steps(ShareFlags::new()).len() + 2;
+
+        let (mut first, mut second) = ([0; 0x10], [0; 0x10]);
+        #[cfg(feature = "async")]
+        let (first_read, second_read) =
+            futures_util::join!(file.read_block(&mut first, 0x100, None, false), async {
+                wait_for_requests(&mock.handle, first_request).await;
+                file.read_block(&mut second, 0x200, None, false).await
+            });
+        #[cfg(not(feature = "async"))]
+        let (first_read, second_read) = std::thread::scope(|s| {
+            let first_read = s.spawn(|| file.read_block(&mut first, 0x100, None, false));
+            wait_for_requests(&mock.handle, first_request);
+            let second_read = file.read_block(&mut second, 0x200, None, false);
+            (first_read.join().unwrap(), second_read)
+        });
+
+        assert_eq!(first_read.unwrap(), 0x10);
+        assert_eq!(second_read.unwrap(), 0x10);
+        assert_eq!(first, [1; 0x10]);
+        assert_eq!(second, [2; 0x10]);
+        file.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+}
@@ -0,0 +1,173 @@
+//! [`AsyncRead`], [`AsyncWrite`] and [`AsyncSeek`] implementations for [`File`].
+
+use std::io::SeekFrom;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+
+use futures_core::future::BoxFuture;
+use smb_msg::{ReadFlags, ReadRequest, WriteFlags};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+use super::File;
+
+/// The requests in progress of the I/O traits implementations of a [`File`].
+///
+/// Reads and writes are tracked separately, so a file may be read and written concurrently,
+/// e.g. when split by [`tokio::io::split`].
+#[derive(Default)]
+pub(crate) struct PendingIo {
+    read: Option<BoxFuture<'static, std::io::Result<Vec<u8>>>>,
+    write: Option<BoxFuture<'static, std::io::Result<usize>>>,
+    flush: Option<BoxFuture<'static, std::io::Result<()>>>,
+}
+
+impl File {
+    fn io_error(e: crate::Error) -> std::io::Error {
+        match e {
+            crate::Error::IoError(e) => e,
+            e => std::io::Error::other(e),
+        }
+    }
+
+    /// Completes the pending write, if any, updating the position by its result.
+    fn poll_pending_write(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<usize>> {
+        let pending = self.pending.get_mut().unwrap();
+        let Some(write) = pending.write.as_mut() else {
+            return Poll::Ready(Ok(0));
+        };
+        let result = ready!(write.as_mut().poll(cx));
+        pending.write = None;
+        let written = result?;
+        self.advance_written(written);
+        Poll::Ready(Ok(written))
+    }
+}
+
+impl AsyncRead for File {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let pending = this.pending.get_mut().unwrap();
+        if pending.read.is_none() {
+            if buf.remaining() == 0 || this.pos >= this.end_of_file {
+                return Poll::Ready(Ok(()));
+            }
+            if !this.handle.access.file_read_data() {
+                return Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    "No read permission",
+                )));
+            }
+            let length = buf
+                .remaining()
+                .min(this.handle.conn_info.negotiation.max_read_size.max(1) as usize);
+            let request = ReadRequest {
+                flags: ReadFlags::new(),
+                length: length as u32,
+                offset: this.pos,
+                file_id: this.handle.file_id().map_err(Self::io_error)?,
+                minimum_count: 1,
+            };
+            let handler = this.handle.handler.clone();
+            pending.read = Some(Box::pin(async move {
+                Ok(File::_send_read(&handler, request, None).await?.buffer)
+            }));
+        }
+
+        let result = ready!(pending.read.as_mut().unwrap().as_mut().poll(cx));
+        pending.read = None;
+        let data = result?;
+        // The buffer may be shorter than the one the read was started with.
+        let length = data.len().min(buf.remaining());
+        buf.put_slice(&data[..length]);
+        this.pos += length as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for File {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let pending = this.pending.get_mut().unwrap();
+        if pending.write.is_none() {
+            if buf.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            if !this.handle.access.file_write_data() {
+                return Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    "No write permission",
+                )));
+            }
+            let length = buf
+                .len()
+                .min(this.handle.conn_info.negotiation.max_write_size.max(1) as usize);
+            let data = Arc::<[u8]>::from(&buf[..length]);
+            let handler = this.handle.handler.clone();
+            let file_id = this.handle.file_id().map_err(Self::io_error)?;
+            let pos = this.pos;
+            pending.write = Some(Box::pin(async move {
+                match File::_send_write(&handler, file_id, data, pos, None, WriteFlags::new()).await
+                {
+                    Ok(0) => Err(std::io::ErrorKind::WriteZero.into()),
+                    Ok(written) => Ok(written),
+                    Err(e) => Err(File::_write_error_to_io(e)),
+                }
+            }));
+        }
+        this.poll_pending_write(cx)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending_write(cx))?;
+
+        let pending = this.pending.get_mut().unwrap();
+        if pending.flush.is_none() {
+            if !this.dirty {
+                return Poll::Ready(Ok(()));
+            }
+            let handler = this.handle.handler.clone();
+            let file_id = this.handle.file_id().map_err(Self::io_error)?;
+            pending.flush = Some(Box::pin(async move {
+                File::_send_flush(&handler, file_id).await
+            }));
+        }
+        let result = ready!(pending.flush.as_mut().unwrap().as_mut().poll(cx));
+        pending.flush = None;
+        result?;
+        this.dirty = false;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Flushes the file, without closing it.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl AsyncSeek for File {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> std::io::Result<()> {
+        let this = self.get_mut();
+        let pending = this.pending.get_mut().unwrap();
+        if pending.read.is_some() || pending.write.is_some() {
+            return Err(std::io::Error::other(
+                "Can not seek while a read or a write is in progress",
+            ));
+        }
+        this.pos = this.seek_position(position)?;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        Poll::Ready(Ok(self.pos))
+    }
+}
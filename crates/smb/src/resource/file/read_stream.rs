@@ -0,0 +1,252 @@
+//! [`File::read_stream`], which reads a range of a file as a sequence of chunks.
+
+use std::ops::Range;
+
+use crate::Error;
+use crate::connection::connection_info::BufferLimit;
+use smb_msg::{ReadRequest, Status};
+
+use super::File;
+
+/// The layout of the requests of [`File::read_stream`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ReadStreamLayout {
+    /// The number of requests in flight.
+    read_ahead: usize,
+    /// The size of each request.
+    chunk_size: usize,
+}
+
+impl ReadStreamLayout {
+    fn new(chunk_size: usize, read_ahead: usize, max_size: u32, credits: u16) -> Self {
+        let chunk_size = chunk_size.clamp(1, max_size.max(1) as usize);
+        // Keep only as many requests in flight as the credits cover.
+        let credits_per_request = chunk_size
+            .div_ceil(crate::connection::ConnectionMessageHandler::CREDIT_CALC_RATIO as usize);
+        let max_read_ahead = (credits as usize / credits_per_request).max(1);
+        Self {
+            read_ahead: read_ahead.clamp(1, max_read_ahead),
+            chunk_size,
+        }
+    }
+
+    /// Splits the range into the `(offset, length)` of each request.
+    fn chunks(self, range: Range<u64>) -> impl Iterator<Item = (u64, usize)> + Send + 'static {
+        let chunk_size = self.chunk_size as u64;
+        let end = range.end;
+        (range.start..end)
+            .step_by(self.chunk_size)
+            .map(move |offset| (offset, chunk_size.min(end - offset) as usize))
+    }
+}
+
+impl File {
+    fn read_stream_layout(&self, chunk_size: usize, read_ahead: usize) -> ReadStreamLayout {
+        let negotiation = &self.conn_info.negotiation;
+        // Multi-credit requests require large MTU support.
+        let max_size = match negotiation.caps.large_mtu() {
+            true => negotiation.max_read_size,
+            false => negotiation
+                .max_read_size
+                .min(crate::connection::ConnectionMessageHandler::CREDIT_CALC_RATIO),
+        };
+        ReadStreamLayout::new(
+            chunk_size,
+            read_ahead,
+            max_size,
+            self.conn_info.config.credits_backlog(),
+        )
+    }
+
+    /// Reads a single chunk of [`File::read_stream`].
+    ///
+    /// Reading past the end of the file returns an empty chunk, rather than [`Status::EndOfFile`].
+    #[maybe_async::maybe_async]
+    async fn _read_stream_chunk(&self, offset: u64, length: usize) -> crate::Result<Vec<u8>> {
+        if !self.access.file_read_data() {
+            return Err(Error::MissingPermissions("file_read_data".to_string()));
+        }
+
+        let request = ReadRequest {
+            flags: self._read_flags(false),
+            length: self.clamp_buffer_size(length, BufferLimit::Read)?,
+            offset,
+            file_id: self.handle.file_id()?,
+            minimum_count: 1,
+        };
+        match self.handle.send_receive(request.into()).await {
+            Ok(response) => Ok(response.message.content.to_read()?.buffer),
+            Err(Error::ServerError(Status::EndOfFile, _)) => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads the specified range of the file, as a stream of chunks of up to `chunk_size` bytes each.
+    ///
+    /// Up to `read_ahead` read requests are kept in flight, as long as the credits of the connection
+    /// (see [`ConnectionConfig::credits_backlog`][crate::ConnectionConfig::credits_backlog]) cover them,
+    /// and chunks are yielded in order. `chunk_size` is clamped to the negotiated maximum read size.
+    ///
+    /// The stream ends at the end of the range, or at the end of the file, which may be reached earlier,
+    /// e.g. if the file was truncated while it was read. The stream also ends after its first error.
+    ///
+    /// Chunks are plain vectors, which convert to `bytes::Bytes` without copying.
+    #[cfg(feature = "async")]
+    pub fn read_stream(
+        &self,
+        range: Range<u64>,
+        chunk_size: usize,
+        read_ahead: usize,
+    ) -> impl futures_core::Stream<Item = crate::Result<Vec<u8>>> + Send + '_ {
+        use futures_util::{StreamExt, future::ready, stream};
+
+        let layout = self.read_stream_layout(chunk_size, read_ahead);
+        stream::iter(layout.chunks(range))
+            .map(move |(offset, length)| async move {
+                let chunk = self._read_stream_chunk(offset, length).await?;
+                Ok((chunk, length))
+            })
+            .buffered(layout.read_ahead)
+            .scan(false, |done, result: crate::Result<(Vec<u8>, usize)>| {
+                if *done {
+                    return ready(None);
+                }
+                let chunk = match result {
+                    // A short read means the end of the file was reached.
+                    Ok((chunk, length)) => {
+                        *done = chunk.len() < length;
+                        Ok(chunk)
+                    }
+                    Err(e) => {
+                        *done = true;
+                        Err(e)
+                    }
+                };
+                ready(Some(chunk))
+            })
+            .filter(|chunk| ready(!matches!(chunk, Ok(chunk) if chunk.is_empty())))
+    }
+
+    /// Reads the specified range of the file, as an iterator of chunks of up to `chunk_size` bytes each.
+    ///
+    /// Multi-threaded builds send up to `read_ahead` read requests at a time, as long as the credits of
+    /// the connection (see [`ConnectionConfig::credits_backlog`][crate::ConnectionConfig::credits_backlog])
+    /// cover them, and chunks are yielded in order. `chunk_size` is clamped to the negotiated maximum read size.
+    ///
+    /// The iterator ends at the end of the range, or at the end of the file, which may be reached earlier,
+    /// e.g. if the file was truncated while it was read. The iterator also ends after its first error.
+    #[cfg(not(feature = "async"))]
+    pub fn read_stream(
+        &self,
+        range: Range<u64>,
+        chunk_size: usize,
+        read_ahead: usize,
+    ) -> ReadStreamIterator<'_> {
+        let layout = self.read_stream_layout(chunk_size, read_ahead);
+        ReadStreamIterator {
+            file: self,
+            chunks: Box::new(layout.chunks(range)),
+            read_ahead: layout.read_ahead,
+            backlog: Default::default(),
+            done: false,
+        }
+    }
+}
+
+/// An iterator over the chunks of a range of a file. See [`File::read_stream`].
+#[cfg(not(feature = "async"))]
+pub struct ReadStreamIterator<'a> {
+    file: &'a File,
+    /// The `(offset, length)` of the chunks that were not requested yet.
+    chunks: Box<dyn Iterator<Item = (u64, usize)> + Send>,
+    read_ahead: usize,
+    /// Chunks that were read, but not yet yielded.
+    backlog: std::collections::VecDeque<(Vec<u8>, usize)>,
+    done: bool,
+}
+
+#[cfg(not(feature = "async"))]
+impl Iterator for ReadStreamIterator<'_> {
+    type Item = crate::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.backlog.is_empty() {
+            let jobs = self
+                .chunks
+                .by_ref()
+                .take(self.read_ahead)
+                .collect::<Vec<_>>();
+            let file = self.file;
+            match super::run_pipelined(self.read_ahead, jobs, |(offset, length)| {
+                Ok((file._read_stream_chunk(offset, length)?, length))
+            }) {
+                Ok(chunks) => self.backlog.extend(chunks),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        let (chunk, length) = self.backlog.pop_front()?;
+        // A short read means the end of the file was reached.
+        if chunk.len() < length {
+            self.done = true;
+            if chunk.is_empty() {
+                return None;
+            }
+        }
+        Some(Ok(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadStreamLayout;
+
+    const MB: usize = 0x100000;
+
+    #[test]
+    fn test_read_stream_layout() {
+        // Read-ahead is limited by the credits, each 64KiB of a request costing a credit.
+        assert_eq!(
+            ReadStreamLayout::new(MB, 4, 8 * MB as u32, 128),
+            ReadStreamLayout {
+                read_ahead: 4,
+                chunk_size: MB
+            }
+        );
+        assert_eq!(
+            ReadStreamLayout::new(MB, 16, 8 * MB as u32, 128),
+            ReadStreamLayout {
+                read_ahead: 8,
+                chunk_size: MB
+            }
+        );
+        // Chunks are clamped to the max read size, and at least one request is in flight.
+        assert_eq!(
+            ReadStreamLayout::new(8 * MB, 0, MB as u32, 1),
+            ReadStreamLayout {
+                read_ahead: 1,
+                chunk_size: MB
+            }
+        );
+        assert_eq!(ReadStreamLayout::new(0, 2, MB as u32, 128).chunk_size, 1);
+    }
+
+    #[test]
+    fn test_read_stream_chunks() {
+        let layout = ReadStreamLayout::new(0x1000, 1, 0x10000, 128);
+        assert_eq!(
+            layout.chunks(0x800..0x2900).collect::<Vec<_>>(),
+            [(0x800, 0x1000), (0x1800, 0x1000), (0x2800, 0x100)]
+        );
+        assert_eq!(layout.chunks(0x1000..0x1000).count(), 0);
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 0x2000..0x1000;
+        assert_eq!(layout.chunks(reversed).count(), 0);
+    }
+}
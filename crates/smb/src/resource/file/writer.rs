@@ -0,0 +1,292 @@
+//! [`FileWriter`], which buffers small sequential writes to a [`File`].
+
+use std::time::{Duration, Instant};
+
+use maybe_async::maybe_async;
+use smb_fscc::FileStandardInformation;
+use smb_msg::{Dialect, WriteFlags};
+
+use super::File;
+
+/// Options of a [`FileWriter`].
+#[derive(Debug, Clone, Default)]
+pub struct FileWriterOptions {
+    /// The number of buffered bytes that makes the writer write them to the server.
+    ///
+    /// Defaults to the negotiated maximum write size, so each flush is a single request.
+    pub buffer_size: Option<usize>,
+
+    /// The longest time data stays buffered: a write that finds older buffered data flushes the buffer.
+    ///
+    /// The writer has no background task, so buffered data is only written by writes,
+    /// [`FileWriter::flush`] and [`FileWriter::finish`].
+    pub max_delay: Option<Duration>,
+
+    /// Whether [`FileWriter::finish`] writes the remaining data through to stable storage.
+    ///
+    /// On SMB 3.0.2 and later, the last write is sent with [`WriteFlags::write_through`].
+    /// Otherwise, or if there is no remaining data, a flush request is sent instead.
+    pub write_through: bool,
+}
+
+/// Buffers small sequential writes to a [`File`], writing them in as few requests as possible.
+///
+/// The writer keeps its own position in the file, which starts at the offset it was created at,
+/// and advances by the written data only. Writes to the file made not through the writer,
+/// e.g. using [`File::write_all_at`], do not affect it.
+///
+/// Buffered data must be written by [`FileWriter::finish`] (or [`FileWriter::flush`]) before the writer
+/// is dropped: data that is still buffered when the writer is dropped is discarded, with a warning.
+///
+/// ```no_run
+/// # use smb::*;
+/// # async fn example(file: File) -> smb::Result<()> {
+/// let mut writer = FileWriter::append(&file, Default::default()).await?;
+/// for i in 0..1000 {
+///     writer.write(format!("record {i}\n").as_bytes()).await?;
+/// }
+/// writer.finish().await?;
+/// # Ok(()) }
+/// ```
+pub struct FileWriter<'a> {
+    file: &'a File,
+    options: FileWriterOptions,
+    /// The offset of the first buffered byte.
+    offset: u64,
+    buffer: Vec<u8>,
+    /// When the oldest buffered data was buffered.
+    buffered_at: Option<Instant>,
+}
+
+#[maybe_async(AFIT)]
+impl<'a> FileWriter<'a> {
+    /// Creates a writer, that writes to `file` starting at `offset`.
+    pub fn new(file: &'a File, offset: u64, options: FileWriterOptions) -> Self {
+        Self {
+            file,
+            options,
+            offset,
+            buffer: Vec::new(),
+            buffered_at: None,
+        }
+    }
+
+    /// Creates a writer, that appends to the current end of `file`, as queried from the server.
+    pub async fn append(file: &'a File, options: FileWriterOptions) -> crate::Result<Self> {
+        let end_of_file = file
+            .query_info::<FileStandardInformation>()
+            .await?
+            .end_of_file;
+        Ok(Self::new(file, end_of_file, options))
+    }
+
+    /// Returns the offset the next written data is written at.
+    pub fn position(&self) -> u64 {
+        self.offset + self.buffer.len() as u64
+    }
+
+    /// Returns the number of buffered bytes, which were not written to the server yet.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Writes `data` at the [position][Self::position] of the writer.
+    ///
+    /// The data is buffered, and written to the server once the buffer holds
+    /// [`buffer_size`][FileWriterOptions::buffer_size] bytes, or is older than
+    /// [`max_delay`][FileWriterOptions::max_delay]. Data larger than the buffer is written right away.
+    pub async fn write(&mut self, data: &[u8]) -> crate::Result<()> {
+        let buffer_size = self.buffer_size();
+        if self.buffer.is_empty() && data.len() >= buffer_size {
+            self.file
+                ._write_all_at(data, self.offset, WriteFlags::new())
+                .await?;
+            self.offset += data.len() as u64;
+            return Ok(());
+        }
+
+        let buffered_at = *self.buffered_at.get_or_insert_with(Instant::now);
+        self.buffer.extend_from_slice(data);
+        let expired = self
+            .options
+            .max_delay
+            .is_some_and(|max_delay| buffered_at.elapsed() >= max_delay);
+        if self.buffer.len() >= buffer_size || expired {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered data to the server.
+    ///
+    /// If writing fails, the data is kept buffered, and may be flushed again.
+    pub async fn flush(&mut self) -> crate::Result<()> {
+        self.flush_with(WriteFlags::new()).await
+    }
+
+    /// Writes the buffered data to the server, and returns the final [position][Self::position] of the writer.
+    ///
+    /// See [`FileWriterOptions::write_through`].
+    pub async fn finish(mut self) -> crate::Result<u64> {
+        let write_through = self.options.write_through
+            && !self.buffer.is_empty()
+            && self.file.conn_info.negotiation.dialect_rev >= Dialect::Smb0302;
+        self.flush_with(WriteFlags::new().with_write_through(write_through))
+            .await?;
+        if self.options.write_through && !write_through {
+            self.file.handle.flush().await?;
+        }
+        Ok(self.offset)
+    }
+
+    async fn flush_with(&mut self, flags: WriteFlags) -> crate::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.file
+            ._write_all_at(&self.buffer, self.offset, flags)
+            .await?;
+        log::trace!(
+            "Flushed {} buffered bytes at offset {} to {}.",
+            self.buffer.len(),
+            self.offset,
+            self.file.name()
+        );
+        self.offset += self.buffer.len() as u64;
+        self.buffer.clear();
+        self.buffered_at = None;
+        Ok(())
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.options
+            .buffer_size
+            .unwrap_or(self.file.conn_info.negotiation.max_write_size as usize)
+            .max(1)
+    }
+}
+
+impl Drop for FileWriter<'_> {
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() {
+            log::warn!(
+                "Discarding {} unflushed bytes at offset {} of {}: the writer was dropped without being flushed.",
+                self.buffer.len(),
+                self.offset,
+                self.file.name()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileCreateArgs;
+    use crate::test_util::*;
+    use smb_fscc::{FileAccessMask, FileAttributes};
+    use smb_msg::{Command, FileId, FlushResponse, ShareFlags, WriteResponse};
+    use smb_transport::mock::MockStep;
+
+    const FILE_ID: FileId = FileId {
+        persistent: 0x70,
+        volatile: 0x80,
+    };
+
+    fn write_step(offset: u64, data: &'static [u8]) -> MockStep {
+        MockStep::expect(Command::Write)
+            .matching("writes the data at the offset", move |r| {
+                r.content.as_write().is_ok_and(|write| {
+                    write.offset == offset && write.length as usize == data.len()
+                })
+            })
+            .respond(WriteResponse {
+                count: data.len() as u32,
+            })
+    }
+
+    /// Small writes are coalesced at the writer's own position, regardless of writes made directly to the file.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_coalesced_writes() {
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                MockStep::expect(Command::Create)
+                    .respond(create_response(FILE_ID, FileAttributes::new())),
+                write_step(0x100, &[1; 0x40]),
+                write_step(0, &[2; 0x10]),
+                write_step(0x140, &[1; 0x40]),
+                write_step(0x180, &[3; 0x80]),
+                write_step(0x200, &[1; 0x20]),
+                MockStep::expect(Command::Flush).respond(FlushResponse {}),
+                MockStep::expect(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::standard_rw());
+        let file = mock.tree.create("log", &args).await.unwrap().unwrap_file();
+
+        let mut writer = FileWriter::new(
+            &file,
+            0x100,
+            FileWriterOptions {
+                buffer_size: Some(0x40),
+                write_through: true,
+                ..Default::default()
+            },
+        );
+        for _ in 0..4 {
+            writer.write(&[1; 0x10]).await.unwrap();
+        }
+        assert_eq!(writer.buffered(), 0);
+        for _ in 0..3 {
+            writer.write(&[1; 0x10]).await.unwrap();
+        }
+        assert_eq!(writer.buffered(), 0x30);
+        file.write_all_at(&[2; 0x10], 0).await.unwrap();
+        writer.write(&[1; 0x10]).await.unwrap();
+        // Larger than the buffer, and written right away.
+        writer.write(&[3; 0x80]).await.unwrap();
+        writer.write(&[1; 0x20]).await.unwrap();
+        assert_eq!(writer.position(), 0x220);
+        // SMB 2.1 has no write-through flag, so the file is flushed.
+        let end = writer.finish().await.unwrap();
+        assert_eq!(end, 0x220);
+
+        file.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_max_delay() {
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                MockStep::expect(Command::Create)
+                    .respond(create_response(FILE_ID, FileAttributes::new())),
+                write_step(0, &[1; 0x10]),
+                MockStep::expect(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+        let args = FileCreateArgs::make_open_existing(FileAccessMask::standard_rw());
+        let file = mock.tree.create("log", &args).await.unwrap().unwrap_file();
+
+        let mut writer = FileWriter::new(
+            &file,
+            0,
+            FileWriterOptions {
+                max_delay: Some(Duration::ZERO),
+                ..Default::default()
+            },
+        );
+        writer.write(&[1; 0x10]).await.unwrap();
+        assert_eq!(writer.buffered(), 0);
+        // Nothing is left to write.
+        let end = writer.finish().await.unwrap();
+        assert_eq!(end, 0x10);
+
+        file.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+}
@@ -213,18 +213,81 @@ mod impls {
 #[cfg(feature = "std-fs-impls")]
 pub use impls::*;
 
+/// Tracks the ranges written to a destination, possibly out of order by concurrent writes,
+/// to find the prefix of the destination that is written in full.
+#[cfg(not(feature = "single_threaded"))]
+#[derive(Debug, Default)]
+pub(crate) struct WrittenRanges {
+    /// Start offset => end offset.
+    ranges: std::collections::BTreeMap<u64, u64>,
+}
+
+#[cfg(not(feature = "single_threaded"))]
+impl WrittenRanges {
+    pub fn add(&mut self, offset: u64, len: u64) {
+        if len == 0 {
+            return;
+        }
+        let end = self.ranges.entry(offset).or_default();
+        *end = (*end).max(offset + len);
+    }
+
+    /// Returns the length of the range written in full from offset 0.
+    pub fn prefix(&self) -> u64 {
+        let mut prefix = 0;
+        for (&start, &end) in &self.ranges {
+            if start > prefix {
+                break;
+            }
+            prefix = prefix.max(end);
+        }
+        prefix
+    }
+}
+
+/// Returns how many bytes a write wrote before failing, if it failed because the server is out of space.
+pub(crate) fn written_before_out_of_space(e: &crate::Error) -> Option<usize> {
+    match e {
+        crate::Error::DiskFull { written, .. } | crate::Error::QuotaExceeded { written, .. } => {
+            Some(*written)
+        }
+        _ => None,
+    }
+}
+
+/// Converts an out-of-space error of a single write to the error of a whole transfer,
+/// which starts at offset 0 and wrote its first `prefix` bytes in full.
+pub(crate) fn out_of_space_after(e: crate::Error, prefix: u64) -> crate::Error {
+    match e {
+        crate::Error::DiskFull { .. } => crate::Error::DiskFull {
+            written: prefix as usize,
+            offset: 0,
+        },
+        crate::Error::QuotaExceeded { .. } => crate::Error::QuotaExceeded {
+            written: prefix as usize,
+            offset: 0,
+        },
+        e => e,
+    }
+}
+
 #[cfg(not(feature = "single_threaded"))]
 mod copy {
     use super::*;
 
     use std::{
         collections::HashMap,
-        sync::{Arc, atomic::AtomicU64},
+        sync::{
+            Arc,
+            atomic::{AtomicBool, AtomicU64},
+        },
     };
 
     #[derive(Debug)]
     pub struct CopyState {
         current_block: AtomicU64,
+        written: std::sync::Mutex<WrittenRanges>,
+        failed: AtomicBool,
 
         last_block: u64,
         total_size: u64,
@@ -262,6 +325,44 @@ mod copy {
         pub fn num_total_jobs(&self) -> usize {
             self.channel_jobs.values().sum()
         }
+
+        /// Returns whether a copy job failed. The remaining jobs stop copying once a job fails.
+        pub fn failed(&self) -> bool {
+            self.failed.load(std::sync::atomic::Ordering::SeqCst)
+        }
+
+        fn new(
+            total_size: u64,
+            max_chunk_size: u64,
+            channel_jobs: HashMap<Option<u32>, usize>,
+        ) -> Self {
+            Self {
+                current_block: AtomicU64::new(0),
+                written: Default::default(),
+                failed: AtomicBool::new(false),
+                last_block: total_size / max_chunk_size,
+                total_size,
+                max_chunk_size,
+                channel_jobs,
+            }
+        }
+
+        fn add_written(&self, offset: u64, len: usize) {
+            self.written.lock().unwrap().add(offset, len as u64);
+        }
+
+        /// Returns the result of the copy, from the results of its jobs.
+        ///
+        /// Out-of-space errors report the prefix of the destination that was written in full.
+        fn finish(
+            &self,
+            results: impl IntoIterator<Item = crate::Result<()>>,
+        ) -> crate::Result<()> {
+            match results.into_iter().find_map(Result::err) {
+                Some(e) => Err(out_of_space_after(e, self.written.lock().unwrap().prefix())),
+                None => Ok(()),
+            }
+        }
     }
 
     /// Generic block copy function.
@@ -273,7 +374,9 @@ mod copy {
     ///
     /// # Returns
     /// - `Ok(())` if the copy was successful.
-    /// - `Err(crate::Error)` if an error occurred.
+    /// - `Err(crate::Error)` if an error occurred. If the destination ran out of space,
+    ///   this is [`Error::DiskFull`][crate::Error::DiskFull] or [`Error::QuotaExceeded`][crate::Error::QuotaExceeded],
+    ///   with `written` set to the number of bytes at the start of the destination that were written in full.
     ///
     /// # Notes
     /// - To report progress, use the [`prepare_parallel_copy`] function to get a `CopyState`, and then
@@ -391,22 +494,9 @@ mod copy {
 
         if file_length == 0 {
             log::debug!("Source file is empty, nothing to copy.");
-            return Ok(CopyState {
-                current_block: AtomicU64::new(0),
-                last_block: 0,
-                total_size: 0,
-                max_chunk_size: CHUNK_SIZE,
-                channel_jobs,
-            });
         }
 
-        Ok(CopyState {
-            current_block: AtomicU64::new(0),
-            last_block: file_length / CHUNK_SIZE,
-            total_size: file_length,
-            max_chunk_size: CHUNK_SIZE,
-            channel_jobs,
-        })
+        Ok(CopyState::new(file_length, CHUNK_SIZE, channel_jobs))
     }
 
     /// Starts a parallel copy using the provided [`CopyState`].
@@ -440,8 +530,8 @@ mod copy {
             }
         }
 
-        handles.join_all().await;
-        Ok(())
+        let results = handles.join_all().await;
+        state.finish(results)
     }
 
     /// Starts a parallel copy using the provided [`CopyState`].
@@ -472,11 +562,12 @@ mod copy {
             }
         }
 
-        for handle in handles {
-            handle.join().unwrap()?;
-        }
-
-        Ok(())
+        // Join all the jobs before returning, so the written prefix is final.
+        let results = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>();
+        state.finish(results)
     }
 
     #[maybe_async]
@@ -494,7 +585,7 @@ mod copy {
 
         let mut curr_chunk = vec![0u8; state.max_chunk_size as usize];
 
-        loop {
+        while !state.failed() {
             let current_block = state
                 .current_block
                 .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
@@ -512,17 +603,38 @@ mod copy {
             } as usize;
 
             let offset = current_block * state.max_chunk_size;
-            let bytes_read = from
+            let bytes_read = match from
                 .read_at_channel(&mut curr_chunk[..chunk_size], offset, channel_id)
-                .await?;
+                .await
+            {
+                Ok(bytes_read) => bytes_read,
+                Err(e) => {
+                    state
+                        .failed
+                        .store(true, std::sync::atomic::Ordering::SeqCst);
+                    return Err(e);
+                }
+            };
             if bytes_read < chunk_size {
                 log::warn!(
                     "Task {task_id}@{channel_id:?}: Read less bytes than expected. File might be corrupt. Expected: {chunk_size}: {bytes_read}"
                 );
             }
             let valid_chunk_end = bytes_read;
-            to.write_at_channel(&curr_chunk[..valid_chunk_end], offset, channel_id)
-                .await?;
+            match to
+                .write_at_channel(&curr_chunk[..valid_chunk_end], offset, channel_id)
+                .await
+            {
+                Ok(written) => state.add_written(offset, written),
+                Err(e) => {
+                    log::debug!("Task {task_id}@{channel_id:?}: Write at {offset} failed: {e}");
+                    state.add_written(offset, written_before_out_of_space(&e).unwrap_or(0));
+                    state
+                        .failed
+                        .store(true, std::sync::atomic::Ordering::SeqCst);
+                    return Err(e);
+                }
+            }
         }
         log::debug!("Copy task {task_id}@{channel_id:?} completed",);
         Ok(())
@@ -591,7 +703,11 @@ mod copy {
                     "Read less bytes than expected. File might be corrupt. Expected: {chunk_size}: {bytes_read}"
                 );
             }
-            to.write_at(&curr_chunk[..bytes_read], offset)?;
+            to.write_at(&curr_chunk[..bytes_read], offset)
+                .map_err(|e| {
+                    let written = written_before_out_of_space(&e).unwrap_or(0) as u64;
+                    out_of_space_after(e, offset + written)
+                })?;
             offset += bytes_read as u64;
             if let Some(callback) = progress_callback {
                 callback(offset);
@@ -0,0 +1,468 @@
+//! Verification of transferred data, by comparing digests of what was sent (or received)
+//! with the data at the destination. See [`IntegrityOptions`].
+
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+
+use maybe_async::maybe_async;
+use smb_dtyp::binrw_util::prelude::FileTime;
+use smb_fscc::{FileInternalInformation, FileNetworkOpenInformation};
+
+use super::{File, GetLen, ReadAt, ReadAtChannel, TransferOptions};
+use crate::Error;
+
+/// How the data at the destination of a transfer is verified, once the transfer completes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityMode {
+    /// Reads the remote file again, and compares its digest with the digest of the transferred data.
+    #[default]
+    ReadBack,
+    /// Avoids reading the remote file again where possible: the transfer is verified by comparing
+    /// the size of the remote file with the number of bytes transferred, and by checking that the remote file
+    /// was not replaced (by its file ID, see [`FileInternalInformation`]) or, when downloading,
+    /// modified during the transfer.
+    ///
+    /// Falls back to [`IntegrityMode::ReadBack`] if the server does not support querying that information,
+    /// or if any of it does not match, so mismatches are reported with the digests of both sides.
+    Metadata,
+}
+
+/// A streaming hash function, computing the digest of data passed to it in chunks.
+///
+/// Implement this trait to verify transfers with hash functions other than SHA-256,
+/// see [`HashAlgorithm::Custom`].
+pub trait StreamHasher: Send {
+    /// Hashes the next chunk of the data.
+    fn update(&mut self, data: &[u8]);
+    /// Returns the digest of all the data passed to [`StreamHasher::update`].
+    fn finish(self: Box<Self>) -> Vec<u8>;
+}
+
+impl StreamHasher for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<u8> {
+        sha2::Digest::finalize(*self).to_vec()
+    }
+}
+
+/// Creates a new [`StreamHasher`], for [`HashAlgorithm::Custom`].
+pub type StreamHasherFactory = dyn Fn() -> Box<dyn StreamHasher> + Send + Sync;
+
+/// The hash function used to compute the digests of transferred data.
+#[derive(Default, Clone)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    /// A hash function provided by the caller.
+    Custom(Arc<StreamHasherFactory>),
+}
+
+impl HashAlgorithm {
+    /// Returns a new hasher of the algorithm.
+    pub fn hasher(&self) -> Box<dyn StreamHasher> {
+        match self {
+            HashAlgorithm::Sha256 => Box::new(<sha2::Sha256 as sha2::Digest>::new()),
+            HashAlgorithm::Custom(factory) => factory(),
+        }
+    }
+}
+
+impl Debug for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Sha256 => write!(f, "Sha256"),
+            HashAlgorithm::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// Options for verifying the data of transfers.
+///
+/// Verification is opt-in: set [`TransferOptions::integrity`] to verify uploads and downloads,
+/// or use [`verify_copy`] to verify copies.
+#[derive(Debug, Default, Clone)]
+pub struct IntegrityOptions {
+    /// See [`IntegrityMode`].
+    pub mode: IntegrityMode,
+    /// The hash function to compute the digests with.
+    pub algorithm: HashAlgorithm,
+    /// The size of the chunks data is read in, when reading it again to compute its digest.
+    /// If unset, defaults to [`TransferOptions::DEFAULT_CHUNK_SIZE`].
+    /// Reads of remote files are further limited to the negotiated maximum read size.
+    pub chunk_size: Option<u32>,
+}
+
+impl IntegrityOptions {
+    fn chunk_size(&self) -> usize {
+        self.chunk_size
+            .unwrap_or(TransferOptions::DEFAULT_CHUNK_SIZE)
+            .max(1) as usize
+    }
+}
+
+/// The digest of some data, along with its length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityDigest {
+    /// The number of bytes hashed.
+    pub len: u64,
+    /// The digest of the data, as computed by the [`HashAlgorithm`].
+    pub digest: Vec<u8>,
+}
+
+impl Display for IntegrityDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for b in &self.digest {
+            write!(f, "{b:02x}")?;
+        }
+        write!(f, " ({} bytes)", self.len)
+    }
+}
+
+/// Computes the digest of data that is transferred sequentially, chunk by chunk.
+pub(crate) struct StreamHash {
+    hasher: Box<dyn StreamHasher>,
+    len: u64,
+}
+
+impl StreamHash {
+    pub fn new(options: &IntegrityOptions) -> Self {
+        Self {
+            hasher: options.algorithm.hasher(),
+            len: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        self.len += data.len() as u64;
+    }
+
+    pub fn finish(self) -> IntegrityDigest {
+        IntegrityDigest {
+            len: self.len,
+            digest: self.hasher.finish(),
+        }
+    }
+}
+
+/// Fails with [`Error::IntegrityCheckFailed`] if the digests differ.
+fn compare(expected: IntegrityDigest, actual: IntegrityDigest) -> crate::Result<IntegrityDigest> {
+    if expected != actual {
+        return Err(Error::IntegrityCheckFailed { expected, actual });
+    }
+    Ok(actual)
+}
+
+/// Computes the digest of the first `len` bytes of `reader`, reading them in chunks of [`IntegrityOptions::chunk_size`].
+///
+/// If `reader` ends before `len` bytes, the digest is of the bytes read until then.
+#[maybe_async]
+pub async fn hash_at<R: ReadAt + ?Sized>(
+    reader: &R,
+    len: u64,
+    options: &IntegrityOptions,
+) -> crate::Result<IntegrityDigest> {
+    let mut hash = StreamHash::new(options);
+    let mut chunk = vec![0; options.chunk_size()];
+    while hash.len < len {
+        let remaining = (len - hash.len).min(chunk.len() as u64) as usize;
+        let read = reader.read_at(&mut chunk[..remaining], hash.len).await?;
+        if read == 0 {
+            log::warn!("Data ended after {} of {len} bytes", hash.len);
+            break;
+        }
+        hash.update(&chunk[..read]);
+    }
+    Ok(hash.finish())
+}
+
+/// Verifies that the data of `to` is identical to the data of `from`, typically after copying it,
+/// by reading both and comparing their digests. Both are read in full, regardless of [`IntegrityOptions::mode`].
+///
+/// ## Returns
+/// The digest of the data, or [`Error::IntegrityCheckFailed`] with the digests of both, if they differ.
+#[maybe_async]
+pub async fn verify_copy<F: ReadAt + GetLen, T: ReadAt + GetLen>(
+    from: &F,
+    to: &T,
+    options: &IntegrityOptions,
+) -> crate::Result<IntegrityDigest> {
+    let expected = hash_at(from, from.get_len().await?, options).await?;
+    let actual = hash_at(to, to.get_len().await?, options).await?;
+    compare(expected, actual)
+}
+
+/// Reads a remote file up to its current end, including data written through the handle after it was opened.
+struct ReadBack<'a>(&'a File);
+
+impl ReadAtChannel for ReadBack<'_> {
+    #[maybe_async]
+    async fn read_at_channel(
+        &self,
+        buf: &mut [u8],
+        offset: u64,
+        channel: Option<u32>,
+    ) -> crate::Result<usize> {
+        let max_read_size = self.0.conn_info.negotiation.max_read_size as usize;
+        let len = buf.len().min(max_read_size);
+        Ok(self
+            .0
+            ._read_block(&mut buf[..len], offset, channel, false)
+            .await?)
+    }
+}
+
+/// Computes the digest of the current content of a remote file. The file must be opened with read access.
+#[maybe_async]
+async fn hash_remote(file: &File, options: &IntegrityOptions) -> crate::Result<IntegrityDigest> {
+    let len = file
+        .query_info::<FileNetworkOpenInformation>()
+        .await?
+        .end_of_file;
+    log::debug!("Reading back {len} bytes of {} to verify them", file.name());
+    hash_at(&ReadBack(file), len, options).await
+}
+
+/// The state of a remote file compared by [`IntegrityMode::Metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RemoteState {
+    file_id: u64,
+    size: u64,
+    last_write_time: FileTime,
+}
+
+impl RemoteState {
+    /// Queries the state of the file, if verified by [`IntegrityMode::Metadata`].
+    ///
+    /// Returns `None` if verified by reading back, or if the server fails to return the state,
+    /// in which case verification falls back to reading back.
+    #[maybe_async]
+    pub async fn query(file: &File, options: &IntegrityOptions) -> Option<Self> {
+        if options.mode != IntegrityMode::Metadata {
+            return None;
+        }
+        match Self::_query(file).await {
+            Ok(state) => Some(state),
+            Err(e) => {
+                log::debug!(
+                    "Failed to query the state of {}, verifying it by reading back: {e}",
+                    file.name()
+                );
+                None
+            }
+        }
+    }
+
+    #[maybe_async]
+    async fn _query(file: &File) -> crate::Result<Self> {
+        let internal = file.query_info::<FileInternalInformation>().await?;
+        let info = file.query_info::<FileNetworkOpenInformation>().await?;
+        Ok(Self {
+            file_id: internal.index_number,
+            size: info.end_of_file,
+            last_write_time: info.last_write_time,
+        })
+    }
+}
+
+/// Verifies that a remote file holds the uploaded data, whose digest is `sent`.
+///
+/// `before` is the state of the file before the upload, see [`RemoteState::query`].
+/// The upload itself modifies the file, so only its file ID is compared.
+#[maybe_async]
+pub(crate) async fn verify_upload(
+    file: &File,
+    sent: IntegrityDigest,
+    before: Option<RemoteState>,
+    options: &IntegrityOptions,
+) -> crate::Result<IntegrityDigest> {
+    if let Some(before) = before {
+        let after = RemoteState::query(file, options).await;
+        if after.is_some_and(|after| after.file_id == before.file_id && after.size == sent.len) {
+            log::debug!("Verified the upload to {} by its metadata", file.name());
+            return Ok(sent);
+        }
+    }
+    compare(sent, hash_remote(file, options).await?)
+}
+
+/// Verifies that the data downloaded from a remote file, whose digest is `received`,
+/// is the content of the file.
+///
+/// `before` is the state of the file before the download, see [`RemoteState::query`].
+#[cfg(feature = "std-fs-impls")]
+#[maybe_async]
+pub(crate) async fn verify_download(
+    file: &File,
+    received: IntegrityDigest,
+    before: Option<RemoteState>,
+    options: &IntegrityOptions,
+) -> crate::Result<IntegrityDigest> {
+    if let Some(before) = before {
+        let after = RemoteState::query(file, options).await;
+        if after == Some(before) && before.size == received.len {
+            log::debug!("Verified the download of {} by its metadata", file.name());
+            return Ok(received);
+        }
+    }
+    // The digest of the remote file is the expected one.
+    compare(hash_remote(file, options).await?, received)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(feature = "std-fs-impls", not(feature = "async")))]
+    use std::{fs, io::Write};
+    #[cfg(all(feature = "std-fs-impls", feature = "async"))]
+    use {tokio::fs, tokio::io::AsyncWriteExt};
+
+    /// In-memory data, read at most `max_read` bytes at a time.
+    struct Data {
+        data: Vec<u8>,
+        max_read: usize,
+    }
+
+    impl ReadAtChannel for Data {
+        #[maybe_async]
+        async fn read_at_channel(
+            &self,
+            buf: &mut [u8],
+            offset: u64,
+            _channel: Option<u32>,
+        ) -> crate::Result<usize> {
+            let data = &self.data[(offset as usize).min(self.data.len())..];
+            let read = buf.len().min(data.len()).min(self.max_read);
+            buf[..read].copy_from_slice(&data[..read]);
+            Ok(read)
+        }
+    }
+
+    impl GetLen for Data {
+        #[maybe_async]
+        async fn get_len(&self) -> crate::Result<u64> {
+            Ok(self.data.len() as u64)
+        }
+    }
+
+    fn make_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn options(chunk_size: u32) -> IntegrityOptions {
+        IntegrityOptions {
+            chunk_size: Some(chunk_size),
+            ..Default::default()
+        }
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_hash_at_matches_streaming_hash() {
+        let data = make_data(1000);
+        let mut hash = StreamHash::new(&Default::default());
+        for chunk in data.chunks(7) {
+            hash.update(chunk);
+        }
+        let streamed = hash.finish();
+        assert_eq!(streamed.len, 1000);
+        assert_eq!(
+            streamed.digest,
+            <sha2::Sha256 as sha2::Digest>::digest(&data).to_vec()
+        );
+
+        // Short reads are continued.
+        let reader = Data {
+            data,
+            max_read: 100,
+        };
+        let digest = hash_at(&reader, 1000, &options(64)).await.unwrap();
+        assert_eq!(digest, streamed);
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_custom_hash_algorithm() {
+        /// Sums the bytes of the data.
+        struct Sum(u64);
+        impl StreamHasher for Sum {
+            fn update(&mut self, data: &[u8]) {
+                self.0 += data.iter().map(|&b| b as u64).sum::<u64>();
+            }
+            fn finish(self: Box<Self>) -> Vec<u8> {
+                self.0.to_le_bytes().to_vec()
+            }
+        }
+
+        let options = IntegrityOptions {
+            algorithm: HashAlgorithm::Custom(Arc::new(|| Box::new(Sum(0)))),
+            ..Default::default()
+        };
+        let reader = Data {
+            data: vec![1, 2, 3],
+            max_read: usize::MAX,
+        };
+        let digest = hash_at(&reader, 3, &options).await.unwrap();
+        assert_eq!(digest.digest, 6u64.to_le_bytes());
+    }
+
+    #[cfg(feature = "std-fs-impls")]
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_verify_copy_detects_corrupt_local_copy() {
+        let data = make_data(100_000);
+        let source = Data {
+            data: data.clone(),
+            max_read: usize::MAX,
+        };
+        let path = std::env::temp_dir().join(format!(
+            "smb-rs-integrity-{}-{:?}.bin",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        fs::write(&path, &data).await.unwrap();
+
+        let copy = crate::sync_helpers::Mutex::new(fs::File::open(&path).await.unwrap());
+        let digest = verify_copy(&source, &copy, &options(4096)).await.unwrap();
+        assert_eq!(digest.len, data.len() as u64);
+
+        // Flip a single bit of the local copy.
+        let mut corrupt = data.clone();
+        corrupt[54_321] ^= 0x10;
+        let mut file = fs::File::create(&path).await.unwrap();
+        file.write_all(&corrupt).await.unwrap();
+        file.flush().await.unwrap();
+
+        let copy = crate::sync_helpers::Mutex::new(fs::File::open(&path).await.unwrap());
+        let result = verify_copy(&source, &copy, &options(4096)).await;
+        fs::remove_file(&path).await.unwrap();
+        match result {
+            Err(Error::IntegrityCheckFailed { expected, actual }) => {
+                assert_eq!(expected, digest);
+                assert_eq!(actual.len, expected.len);
+                assert_ne!(actual.digest, expected.digest);
+            }
+            result => panic!("Unexpected result: {result:?}"),
+        }
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_verify_copy_detects_truncated_copy() {
+        let data = make_data(1000);
+        let source = Data {
+            data: data.clone(),
+            max_read: usize::MAX,
+        };
+        let copy = Data {
+            data: data[..999].to_vec(),
+            max_read: usize::MAX,
+        };
+        let result = verify_copy(&source, &copy, &options(64)).await;
+        assert!(matches!(
+            result,
+            Err(Error::IntegrityCheckFailed { expected, actual })
+                if expected.len == 1000 && actual.len == 999
+        ));
+    }
+}
@@ -0,0 +1,305 @@
+//! Detection of file names that collide on local file systems.
+//!
+//! Remote directories may contain names that differ only by case (e.g. `File.txt` and `file.txt`,
+//! on case-sensitive NTFS directories or Samba shares), or only by Unicode normalization (e.g. NFC and NFD forms of `é`).
+//! Such names refer to the same file on case-insensitive or normalization-insensitive local file systems,
+//! such as the default file systems of Windows and macOS, so downloading them would overwrite one with the other.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use unicode_normalization::UnicodeNormalization;
+
+use super::TransferReport;
+use crate::Error;
+
+/// What to do with a remote name that collides with another name in the same local directory.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NameCollisionPolicy {
+    /// Fail with [`Error::LocalNameCollision`].
+    #[default]
+    Error,
+    /// Use a different local name, by adding a numeric suffix to the name (e.g. `file (1).txt`).
+    Rename,
+    /// Skip the colliding entry, and log a warning.
+    Skip,
+}
+
+/// The reason two names collide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameCollisionKind {
+    /// The names differ only by case.
+    Case,
+    /// The names differ by Unicode normalization (and possibly by case).
+    Normalization,
+}
+
+impl Display for NameCollisionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameCollisionKind::Case => write!(f, "case"),
+            NameCollisionKind::Normalization => write!(f, "unicode normalization"),
+        }
+    }
+}
+
+/// A name collision that was detected (and handled) during a transfer. See [`resolve_local_names`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameCollision {
+    /// The remote name of the colliding entry.
+    pub name: String,
+    /// The remote name of the entry that keeps its name.
+    pub conflicts_with: String,
+    pub kind: NameCollisionKind,
+    /// The local name that was used instead, or `None` if the entry was skipped.
+    pub local_name: Option<String>,
+}
+
+/// Returns the key under which names are considered equal on case- and normalization-insensitive file systems.
+fn collision_key(name: &str) -> String {
+    name.nfc().flat_map(char::to_lowercase).collect()
+}
+
+/// Resolves the local names of the entries of a single remote directory.
+///
+/// Names that collide are detected before anything is written: the first name in lexicographic order
+/// keeps its name, and the rest are handled according to `policy`. This makes the result independent
+/// of the order in which the server lists the entries.
+///
+/// ## Arguments
+/// * `names` - The remote names of the entries of the directory.
+/// * `policy` - See [`NameCollisionPolicy`].
+/// * `report` - Detected collisions are recorded in its [`TransferReport::collisions`].
+///
+/// ## Returns
+/// The local name of each entry, in the order of `names`, or `None` for skipped entries.
+/// If there are no collisions, these are the remote names.
+pub fn resolve_local_names<S: AsRef<str>>(
+    names: &[S],
+    policy: NameCollisionPolicy,
+    report: &mut TransferReport,
+) -> crate::Result<Vec<Option<String>>> {
+    resolve_local_names_with_existing(names, &[] as &[&str], policy, report)
+}
+
+/// Resolves the local names of the entries of a single remote directory,
+/// that is downloaded into a local directory which may already have content.
+///
+/// This is the same as [`resolve_local_names`], except that the `existing` local names always keep their names:
+/// remote names that collide with them are handled according to `policy`.
+/// A remote name that is equal to an existing name is not a collision, since it refers to the same local entry,
+/// which is overwritten.
+///
+/// ## Arguments
+/// * `names` - The remote names of the entries of the directory.
+/// * `existing` - The names of the entries that already exist in the local directory.
+/// * `policy` - See [`NameCollisionPolicy`].
+/// * `report` - Detected collisions are recorded in its [`TransferReport::collisions`].
+pub fn resolve_local_names_with_existing<S: AsRef<str>, E: AsRef<str>>(
+    names: &[S],
+    existing: &[E],
+    policy: NameCollisionPolicy,
+    report: &mut TransferReport,
+) -> crate::Result<Vec<Option<String>>> {
+    let mut result: Vec<Option<String>> =
+        names.iter().map(|n| Some(n.as_ref().to_string())).collect();
+
+    let existing: HashMap<String, &str> = existing
+        .iter()
+        .map(|name| (collision_key(name.as_ref()), name.as_ref()))
+        .collect();
+    let mut by_key: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        by_key
+            .entry(collision_key(name.as_ref()))
+            .or_default()
+            .push(i);
+    }
+
+    // Renamed entries must not collide with any of the other names.
+    let mut used_keys: HashSet<String> = by_key.keys().chain(existing.keys()).cloned().collect();
+
+    let mut groups = by_key
+        .into_iter()
+        .filter(|(key, group)| {
+            group.len() > 1
+                || existing
+                    .get(key)
+                    .is_some_and(|&local| local != names[group[0]].as_ref())
+        })
+        .collect::<Vec<_>>();
+    if groups.is_empty() {
+        return Ok(result);
+    }
+    // Handle the groups in a deterministic order, as well.
+    for (_, group) in groups.iter_mut() {
+        group.sort_by(|&a, &b| names[a].as_ref().cmp(names[b].as_ref()));
+    }
+    groups.sort_by(|(_, a), (_, b)| names[a[0]].as_ref().cmp(names[b[0]].as_ref()));
+
+    for (key, group) in groups {
+        let (kept, colliding) = match existing.get(&key) {
+            Some(&local) => (
+                local,
+                group
+                    .into_iter()
+                    .filter(|&i| names[i].as_ref() != local)
+                    .collect(),
+            ),
+            None => (names[group[0]].as_ref(), group[1..].to_vec()),
+        };
+        for i in colliding {
+            let name = names[i].as_ref();
+            let kind = if name.to_lowercase() == kept.to_lowercase() {
+                NameCollisionKind::Case
+            } else {
+                NameCollisionKind::Normalization
+            };
+            let local_name = match policy {
+                NameCollisionPolicy::Error => {
+                    return Err(Error::LocalNameCollision {
+                        name: name.to_string(),
+                        conflicts_with: kept.to_string(),
+                        kind,
+                    });
+                }
+                NameCollisionPolicy::Rename => {
+                    let renamed = (1..)
+                        .map(|n| with_suffix(name, n))
+                        .find(|candidate| used_keys.insert(collision_key(candidate)))
+                        .unwrap();
+                    log::info!("Downloading {name} as {renamed}: collides with {kept} ({kind})");
+                    Some(renamed)
+                }
+                NameCollisionPolicy::Skip => {
+                    log::warn!("Skipping {name}: collides with {kept} ({kind})");
+                    None
+                }
+            };
+            result[i] = local_name.clone();
+            report.collisions.push(NameCollision {
+                name: name.to_string(),
+                conflicts_with: kept.to_string(),
+                kind,
+                local_name,
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// Adds a numeric suffix to a name, before its extension: `file.txt` => `file (1).txt`.
+fn with_suffix(name: &str, n: usize) -> String {
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => format!("{} ({n}){}", &name[..dot], &name[dot..]),
+        _ => format!("{name} ({n})"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        NameCollisionKind, NameCollisionPolicy, resolve_local_names,
+        resolve_local_names_with_existing,
+    };
+    use crate::Error;
+    use crate::resource::TransferReport;
+
+    const NFC_E: &str = "caf\u{e9}.txt";
+    const NFD_E: &str = "cafe\u{301}.txt";
+
+    #[test]
+    fn test_no_collisions() {
+        let names = ["a.txt", "b.txt", "A b", NFC_E];
+        let mut report = TransferReport::default();
+        let result = resolve_local_names(&names, NameCollisionPolicy::Error, &mut report).unwrap();
+        assert_eq!(
+            result,
+            names
+                .iter()
+                .map(|n| Some(n.to_string()))
+                .collect::<Vec<_>>()
+        );
+        assert!(report.collisions.is_empty());
+    }
+
+    #[test]
+    fn test_case_collision_error() {
+        let names = ["file.txt", "File.txt"];
+        let result = resolve_local_names(
+            &names,
+            NameCollisionPolicy::Error,
+            &mut TransferReport::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::LocalNameCollision { name, conflicts_with, kind: NameCollisionKind::Case })
+                if name == "file.txt" && conflicts_with == "File.txt"
+        ));
+    }
+
+    #[test]
+    fn test_collision_rename() {
+        // "file (1).txt" already exists, so the renamed entry must skip it.
+        let names = ["file.txt", "other", "File.txt", "file (1).txt", "FILE.TXT"];
+        let mut report = TransferReport::default();
+        let result = resolve_local_names(&names, NameCollisionPolicy::Rename, &mut report).unwrap();
+        assert_eq!(
+            result,
+            [
+                "file (3).txt",
+                "other",
+                "File (2).txt",
+                "file (1).txt",
+                "FILE.TXT"
+            ]
+            .map(|n| Some(n.to_string()))
+        );
+        assert_eq!(report.collisions.len(), 2);
+        assert!(
+            report
+                .collisions
+                .iter()
+                .all(|c| c.conflicts_with == "FILE.TXT")
+        );
+    }
+
+    #[test]
+    fn test_normalization_collision_skip() {
+        let names = [NFD_E, NFC_E];
+        let mut report = TransferReport::default();
+        let result = resolve_local_names(&names, NameCollisionPolicy::Skip, &mut report).unwrap();
+        // "cafe\u{301}" < "caf\u{e9}", so the NFD name is kept.
+        assert_eq!(result, vec![Some(NFD_E.to_string()), None]);
+        assert_eq!(report.collisions.len(), 1);
+        assert_eq!(report.collisions[0].kind, NameCollisionKind::Normalization);
+        assert_eq!(report.collisions[0].local_name, None);
+    }
+
+    #[test]
+    fn test_existing_collision_rename() {
+        // "file.txt" is restored over the existing file, while "File.txt" and "Notes" collide with existing names.
+        let names = ["file.txt", "File.txt", "Notes", "other"];
+        let existing = ["file.txt", "notes"];
+        let mut report = TransferReport::default();
+        let result = resolve_local_names_with_existing(
+            &names,
+            &existing,
+            NameCollisionPolicy::Rename,
+            &mut report,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            ["file.txt", "File (1).txt", "Notes (1)", "other"].map(|n| Some(n.to_string()))
+        );
+        assert_eq!(
+            report
+                .collisions
+                .iter()
+                .map(|c| (c.name.as_str(), c.conflicts_with.as_str()))
+                .collect::<Vec<_>>(),
+            [("File.txt", "file.txt"), ("Notes", "notes")]
+        );
+    }
+}
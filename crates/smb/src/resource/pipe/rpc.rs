@@ -0,0 +1,575 @@
+//! [`RpcBinding`], a DCE/RPC connection over a named pipe.
+
+use super::Pipe;
+use maybe_async::maybe_async;
+use smb_dtyp::Guid;
+use smb_msg::Status;
+use smb_rpc::{SmbRpcError, interface::*, pdu::*};
+
+/// A DCE/RPC binding to an interface, over a [`Pipe`].
+///
+/// The binding sends calls as stub data, already encoded in the negotiated transfer syntax,
+/// and returns the encoded output of the calls. Requests and responses larger than the negotiated
+/// fragment sizes are split and reassembled transparently.
+///
+/// Typed interfaces (see [`smb_rpc::interface`]) are bound by [`Pipe::bind`], on top of this type.
+/// ```no_run
+/// # use smb::*;
+/// # use smb_rpc::ndr64::NDR64_SYNTAX_ID;
+/// # async fn example(pipe: Pipe) -> smb::Result<()> {
+/// let srvsvc = Guid::parse_uuid("4b324fc8-1670-01d3-1278-5a47bf6ee188").unwrap();
+/// let mut binding = RpcBinding::bind(pipe, srvsvc, 3, NDR64_SYNTAX_ID).await?;
+/// # let stub = vec![];
+/// let output = binding.call(15, &stub).await?;
+/// # Ok(()) }
+/// ```
+pub struct RpcBinding {
+    pipe: Pipe,
+    next_call_id: u32,
+    /// Selected, accepted, context ID from binding.
+    context_id: u16,
+    /// Whether the server supports FSCTL_PIPE_TRANSCEIVE on the pipe.
+    transceive: bool,
+
+    abstract_syntax: DceRpcSyntaxId,
+    transfer_syntax: DceRpcSyntaxId,
+    max_xmit_frag: u16,
+    max_recv_frag: u16,
+}
+
+/// Former name of [`RpcBinding`].
+pub type PipeRpcConnection = RpcBinding;
+
+#[maybe_async(AFIT)]
+impl RpcBinding {
+    /// Binds the pipe to the interface `interface_uuid`, of major version `version`,
+    /// using `transfer_syntax` to encode the calls (e.g. [NDR64][smb_rpc::ndr64::NDR64_SYNTAX_ID]).
+    ///
+    /// Returns [`SmbRpcError::BindRejected`] if the server rejects the association,
+    /// and [`Error::InvalidMessage`][crate::Error::InvalidMessage] if it does not accept
+    /// the interface with the transfer syntax.
+    pub async fn bind(
+        pipe: Pipe,
+        interface_uuid: Guid,
+        version: u32,
+        transfer_syntax: DceRpcSyntaxId,
+    ) -> crate::Result<Self> {
+        let abstract_syntax = DceRpcSyntaxId {
+            uuid: interface_uuid,
+            version,
+        };
+        let tranfer_syntaxes: [DceRpcSyntaxId; 2] =
+            [transfer_syntax.clone(), BIND_TIME_NEGOTIATION];
+        let context_elements = Self::make_bind_contexts(&abstract_syntax, &tranfer_syntaxes);
+
+        const START_CALL_ID: u32 = 2;
+        const NO_ASSOC_GROUP_ID: u32 = 0;
+        let mut transceive = true;
+        let bind_ack = Self::rpc_rw(
+            &pipe,
+            &mut transceive,
+            START_CALL_ID,
+            DcRpcCoPktBind {
+                max_xmit_frag: Self::DEFAULT_FRAG_LIMIT,
+                max_recv_frag: Self::DEFAULT_FRAG_LIMIT,
+                assoc_group_id: NO_ASSOC_GROUP_ID,
+                context_elements,
+            }
+            .into(),
+        )
+        .await?;
+
+        let bind_ack = match bind_ack.into_content() {
+            DcRpcCoPktResponseContent::BindAck(bind_ack) => {
+                log::debug!("Bounded to pipe with port spec {}", bind_ack.port_spec);
+                bind_ack
+            }
+            DcRpcCoPktResponseContent::BindNak(bind_nak) => {
+                return Err(SmbRpcError::BindRejected(bind_nak.reason).into());
+            }
+            content => {
+                return Err(crate::Error::InvalidMessage(format!(
+                    "Expected BindAck, got: {content:?}",
+                )));
+            }
+        };
+
+        let context_id = Self::check_bind_results(&bind_ack, &tranfer_syntaxes)?;
+
+        // The server's transmit size limits what we receive, and vice versa.
+        Ok(RpcBinding {
+            pipe,
+            next_call_id: START_CALL_ID + 1,
+            context_id,
+            transceive,
+            abstract_syntax,
+            transfer_syntax,
+            max_xmit_frag: bind_ack.max_recv_frag.min(Self::DEFAULT_FRAG_LIMIT),
+            max_recv_frag: bind_ack.max_xmit_frag.min(Self::DEFAULT_FRAG_LIMIT),
+        })
+    }
+
+    /// Calls the operation `opnum` of the bound interface, with the encoded input `stub_input`,
+    /// and returns the encoded output of the operation.
+    ///
+    /// If the server fails the call, returns [`SmbRpcError::Fault`] with the status of the fault.
+    pub async fn call(&mut self, opnum: u16, stub_input: &[u8]) -> crate::Result<Vec<u8>> {
+        let call_id = self.next_call_id;
+        self.next_call_id += 1;
+
+        let mut fragments = request_fragments(
+            call_id,
+            self.context_id,
+            opnum,
+            stub_input,
+            self.max_xmit_frag,
+        )?;
+        let last = fragments
+            .pop()
+            .expect("at least one fragment is always built");
+        for fragment in fragments {
+            self.pipe.write_message(&fragment).await?;
+        }
+
+        let mut response = ResponseReassembly::new(call_id, self.context_id, self.max_recv_frag);
+        let mut message = Self::exchange(&self.pipe, &mut self.transceive, &last).await?;
+        loop {
+            if response.push(&message)? {
+                return Ok(response.stub_data);
+            }
+            message = self.pipe.read_message().await?;
+        }
+    }
+
+    fn make_bind_contexts(
+        syntax_id: &DceRpcSyntaxId,
+        transfer_syntaxes: &[DceRpcSyntaxId],
+    ) -> Vec<DcRpcCoPktBindContextElement> {
+        let mut result = vec![];
+
+        for (i, syntax) in transfer_syntaxes.iter().enumerate() {
+            result.push(DcRpcCoPktBindContextElement {
+                context_id: i as u16,
+                abstract_syntax: syntax_id.clone(),
+                transfer_syntaxes: vec![syntax.clone()],
+            });
+        }
+
+        result
+    }
+
+    fn check_bind_results(
+        bind_ack: &DcRpcCoPktBindAck,
+        transfer_syntaxes: &[DceRpcSyntaxId],
+    ) -> crate::Result<u16> {
+        if bind_ack.results.len() != transfer_syntaxes.len() {
+            return Err(crate::Error::InvalidMessage(format!(
+                "BindAck results length {} does not match transfer syntaxes length {}",
+                bind_ack.results.len(),
+                transfer_syntaxes.len()
+            )));
+        }
+        let mut context_id_selected = None;
+        for (indx, (ack_context, syntax)) in
+            bind_ack.results.iter().zip(transfer_syntaxes).enumerate()
+        {
+            if syntax
+                .uuid
+                .to_string()
+                .starts_with(BIND_TIME_NEGOTIATION_PREFIX)
+            {
+                // Bind time feature negotiation element. Currently ignored.
+                log::debug!(
+                    "Bind time feature negotiation flags: {:?}",
+                    ack_context.result as u16
+                );
+                continue;
+            }
+            if ack_context.result != DceRpcCoPktBindAckDefResult::Acceptance {
+                return Err(crate::Error::InvalidMessage(format!(
+                    "BindAck result for syntax {syntax} was not acceptance: {ack_context:?}"
+                )));
+            }
+            if &ack_context.syntax != syntax {
+                return Err(crate::Error::InvalidMessage(format!(
+                    "BindAck abstract syntax {} does not match expected {}",
+                    ack_context.syntax, syntax
+                )));
+            }
+            context_id_selected = Some(indx as u16);
+        }
+
+        if let Some(context_id) = context_id_selected {
+            log::debug!("Selected context ID: {context_id}");
+            Ok(context_id)
+        } else {
+            Err(crate::Error::InvalidMessage(
+                "No accepted context ID found in BindAck".to_string(),
+            ))
+        }
+    }
+
+    pub const PACKED_DREP: u32 = 0x10;
+    /// The fragment sizes proposed when binding.
+    const DEFAULT_FRAG_LIMIT: u16 = 4280;
+
+    /// Performs a read+write operation on the pipe, sending a request and receiving it's response.
+    #[maybe_async]
+    async fn rpc_rw(
+        pipe: &Pipe,
+        transceive: &mut bool,
+        call_id: u32,
+        to_send: DcRpcCoPktRequestContent,
+    ) -> crate::Result<DceRpcCoResponsePkt> {
+        let dcerpc_request_buffer: Vec<u8> = DceRpcCoRequestPkt::new(
+            to_send,
+            call_id,
+            DceRpcCoPktFlags::new()
+                .with_first_frag(true)
+                .with_last_frag(true),
+            Self::PACKED_DREP,
+        )
+        .try_into()?;
+        let response_buffer = Self::exchange(pipe, transceive, &dcerpc_request_buffer).await?;
+        let response = DceRpcCoResponsePkt::try_from(response_buffer.as_ref())?;
+
+        if response.packed_drep() != Self::PACKED_DREP {
+            return Err(crate::Error::InvalidMessage(format!(
+                "Currently Unsupported packed DREP: {}",
+                response.packed_drep()
+            )));
+        }
+
+        if !response.pfc_flags().first_frag() || !response.pfc_flags().last_frag() {
+            return Err(crate::Error::InvalidMessage(
+                "Expected first and last fragment flags to be set".to_string(),
+            ));
+        }
+
+        Ok(response)
+    }
+
+    /// Sends a request message on the pipe and returns the response message.
+    ///
+    /// Uses [`Pipe::transact`] while the server supports it, and falls back to a write followed by a read otherwise.
+    #[maybe_async]
+    async fn exchange(
+        pipe: &Pipe,
+        transceive: &mut bool,
+        request: &[u8],
+    ) -> crate::Result<Vec<u8>> {
+        if *transceive {
+            match pipe.transact(request).await {
+                Err(e)
+                    if matches!(
+                        e.status(),
+                        Some(Status::NotSupported | Status::InvalidDeviceRequest0)
+                    ) =>
+                {
+                    log::debug!("Pipe transceive is not supported ({e}), using write and read");
+                    *transceive = false;
+                }
+                result => return result,
+            }
+        }
+        pipe.write_message(request).await?;
+        pipe.read_message().await
+    }
+
+    pub fn pipe(&self) -> &Pipe {
+        &self.pipe
+    }
+
+    /// The interface the pipe is bound to.
+    pub fn abstract_syntax(&self) -> &DceRpcSyntaxId {
+        &self.abstract_syntax
+    }
+
+    /// The transfer syntax of the stub data of calls.
+    pub fn transfer_syntax(&self) -> &DceRpcSyntaxId {
+        &self.transfer_syntax
+    }
+
+    /// The negotiated maximal size of a fragment sent to the server, in bytes.
+    pub fn max_xmit_frag(&self) -> u16 {
+        self.max_xmit_frag
+    }
+
+    /// The negotiated maximal size of a fragment received from the server, in bytes.
+    pub fn max_recv_frag(&self) -> u16 {
+        self.max_recv_frag
+    }
+}
+
+impl BoundRpcConnection for RpcBinding {
+    #[maybe_async]
+    async fn send_receive_raw(
+        &mut self,
+        opnum: u16,
+        stub_input: &[u8],
+    ) -> Result<Vec<u8>, SmbRpcError> {
+        self.call(opnum, stub_input).await.map_err(|e| match e {
+            crate::Error::RpcError(e) => e,
+            e => SmbRpcError::SendReceiveError(format!("Failed to send RPC request: {e}")),
+        })
+    }
+}
+
+/// The size of the common header and the request header, preceding the stub data of a request fragment.
+const REQUEST_HEADER_SIZE: usize = 24;
+
+/// Splits a call to request PDUs of up to `max_xmit_frag` bytes each.
+fn request_fragments(
+    call_id: u32,
+    context_id: u16,
+    opnum: u16,
+    stub_input: &[u8],
+    max_xmit_frag: u16,
+) -> crate::Result<Vec<Vec<u8>>> {
+    // Keep each fragment's stub data 8-byte aligned, as NDR64 requires.
+    let max_stub_size = (max_xmit_frag as usize).saturating_sub(REQUEST_HEADER_SIZE) & !7;
+    if max_stub_size == 0 {
+        return Err(crate::Error::InvalidArgument(format!(
+            "Maximal transmit fragment size {max_xmit_frag} is too small"
+        )));
+    }
+
+    let chunk_count = stub_input.len().div_ceil(max_stub_size).max(1);
+    let mut result = Vec::with_capacity(chunk_count);
+    for i in 0..chunk_count {
+        let start = i * max_stub_size;
+        let end = (start + max_stub_size).min(stub_input.len());
+        let req = DcRpcCoPktRequest {
+            alloc_hint: (stub_input.len() - start) as u32,
+            context_id,
+            opnum,
+            stub_data: stub_input[start..end].to_vec(),
+        };
+        let fragment: Vec<u8> = DceRpcCoRequestPkt::new(
+            req.into(),
+            call_id,
+            DceRpcCoPktFlags::new()
+                .with_first_frag(i == 0)
+                .with_last_frag(i == chunk_count - 1),
+            RpcBinding::PACKED_DREP,
+        )
+        .try_into()?;
+        result.push(fragment);
+    }
+    Ok(result)
+}
+
+/// Collects the stub data of the response fragments of a call.
+struct ResponseReassembly {
+    call_id: u32,
+    context_id: u16,
+    max_recv_frag: u16,
+    stub_data: Vec<u8>,
+    first: bool,
+}
+
+impl ResponseReassembly {
+    /// The offset of the fragment length field in the common header.
+    const FRAG_LENGTH_OFFSET: usize = 8;
+
+    fn new(call_id: u32, context_id: u16, max_recv_frag: u16) -> Self {
+        Self {
+            call_id,
+            context_id,
+            max_recv_frag,
+            stub_data: Vec::new(),
+            first: true,
+        }
+    }
+
+    /// Adds the fragments in a message read from the pipe, and returns whether the last fragment was received.
+    fn push(&mut self, mut message: &[u8]) -> crate::Result<bool> {
+        while !message.is_empty() {
+            let frag_length = message
+                .get(Self::FRAG_LENGTH_OFFSET..Self::FRAG_LENGTH_OFFSET + 2)
+                .map(|l| u16::from_le_bytes([l[0], l[1]]) as usize)
+                .ok_or_else(|| {
+                    crate::Error::InvalidMessage("Truncated RPC fragment header".to_string())
+                })?;
+            if frag_length > message.len() {
+                return Err(crate::Error::InvalidMessage(format!(
+                    "RPC fragment length {frag_length} exceeds the {} received bytes",
+                    message.len()
+                )));
+            }
+            if frag_length > self.max_recv_frag as usize {
+                log::warn!(
+                    "RPC fragment length {frag_length} exceeds the negotiated {}",
+                    self.max_recv_frag
+                );
+            }
+            let (fragment, rest) = message.split_at(frag_length);
+            if self.push_fragment(fragment)? {
+                if !rest.is_empty() {
+                    return Err(crate::Error::InvalidMessage(format!(
+                        "{} unexpected bytes after the last RPC fragment",
+                        rest.len()
+                    )));
+                }
+                return Ok(true);
+            }
+            message = rest;
+        }
+        Ok(false)
+    }
+
+    fn push_fragment(&mut self, fragment: &[u8]) -> crate::Result<bool> {
+        let pkt = DceRpcCoResponsePkt::try_from(fragment)?;
+        if pkt.packed_drep() != RpcBinding::PACKED_DREP {
+            return Err(crate::Error::InvalidMessage(format!(
+                "Currently Unsupported packed DREP: {}",
+                pkt.packed_drep()
+            )));
+        }
+        if pkt.call_id() != self.call_id {
+            return Err(crate::Error::InvalidMessage(format!(
+                "Response call ID {} does not match expected {}",
+                pkt.call_id(),
+                self.call_id
+            )));
+        }
+        let flags = pkt.pfc_flags();
+        if flags.first_frag() != self.first {
+            return Err(crate::Error::InvalidMessage(format!(
+                "Unexpected first fragment flag {} in RPC response",
+                flags.first_frag()
+            )));
+        }
+        self.first = false;
+
+        let (context_id, stub_data) = match pkt.into_content() {
+            DcRpcCoPktResponseContent::Response(response) => {
+                (response.context_id, response.stub_data)
+            }
+            DcRpcCoPktResponseContent::Fault(fault) => {
+                return Err(SmbRpcError::Fault(RpcFaultStatus::from(fault.status)).into());
+            }
+            content => {
+                return Err(crate::Error::InvalidMessage(format!(
+                    "Expected DceRpcCoPktResponseContent::Response, got: {content:?}",
+                )));
+            }
+        };
+        if context_id != self.context_id {
+            return Err(crate::Error::InvalidMessage(format!(
+                "Response context ID {} does not match expected {}",
+                context_id, self.context_id
+            )));
+        }
+        self.stub_data.extend_from_slice(&stub_data);
+        Ok(flags.last_frag())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response_fragment(call_id: u32, stub: &[u8], first: bool, last: bool) -> Vec<u8> {
+        DceRpcCoResponsePkt::new(
+            DcRpcCoPktResponse {
+                context_id: 0,
+                cancel_count: 0,
+                stub_data: stub.to_vec(),
+            }
+            .into(),
+            call_id,
+            DceRpcCoPktFlags::new()
+                .with_first_frag(first)
+                .with_last_frag(last),
+            RpcBinding::PACKED_DREP,
+        )
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_request_fragments() {
+        let stub = (0..1000u32).map(|i| i as u8).collect::<Vec<_>>();
+        let fragments = request_fragments(7, 1, 15, &stub, 280).unwrap();
+        assert_eq!(fragments.len(), 4);
+
+        let mut reassembled = vec![];
+        for (i, fragment) in fragments.iter().enumerate() {
+            assert!(fragment.len() <= 280);
+            let pkt = DceRpcCoRequestPkt::try_from(fragment.as_ref()).unwrap();
+            assert_eq!(pkt.call_id(), 7);
+            assert_eq!(pkt.pfc_flags().first_frag(), i == 0);
+            assert_eq!(pkt.pfc_flags().last_frag(), i == fragments.len() - 1);
+            match pkt.into_content() {
+                DcRpcCoPktRequestContent::Request(req) => {
+                    assert_eq!(req.alloc_hint as usize, stub.len() - reassembled.len());
+                    assert_eq!(req.context_id, 1);
+                    assert_eq!(req.opnum, 15);
+                    reassembled.extend(req.stub_data);
+                }
+                content => panic!("Unexpected content: {content:?}"),
+            }
+        }
+        assert_eq!(reassembled, stub);
+
+        let single = request_fragments(7, 1, 15, &[], 280).unwrap();
+        assert_eq!(single.len(), 1);
+    }
+
+    #[test]
+    fn test_response_reassembly() {
+        let mut reassembly = ResponseReassembly::new(3, 0, 4280);
+        assert!(
+            !reassembly
+                .push(&response_fragment(3, &[1, 2], true, false))
+                .unwrap()
+        );
+        // Two fragments may arrive in a single message.
+        let mut message = response_fragment(3, &[3, 4], false, false);
+        message.extend(response_fragment(3, &[5], false, true));
+        assert!(reassembly.push(&message).unwrap());
+        assert_eq!(reassembly.stub_data, [1, 2, 3, 4, 5]);
+
+        let mut reassembly = ResponseReassembly::new(3, 0, 4280);
+        assert!(matches!(
+            reassembly.push(&response_fragment(4, &[], true, true)),
+            Err(crate::Error::InvalidMessage(_))
+        ));
+        let mut truncated = response_fragment(3, &[1, 2, 3, 4], true, true);
+        truncated.pop();
+        let mut reassembly = ResponseReassembly::new(3, 0, 4280);
+        assert!(matches!(
+            reassembly.push(&truncated),
+            Err(crate::Error::InvalidMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_response_fault() {
+        let fault: Vec<u8> = DceRpcCoResponsePkt::new(
+            DcRpcCoPktFault {
+                alloc_hint: 0,
+                context_id: 0,
+                cancel_count: 0,
+                status: RpcFaultStatus::UNKNOWN_INTERFACE,
+                stub_data: vec![],
+            }
+            .into(),
+            3,
+            DceRpcCoPktFlags::new()
+                .with_first_frag(true)
+                .with_last_frag(true),
+            RpcBinding::PACKED_DREP,
+        )
+        .try_into()
+        .unwrap();
+        let mut reassembly = ResponseReassembly::new(3, 0, 4280);
+        assert!(matches!(
+            reassembly.push(&fault),
+            Err(crate::Error::RpcError(SmbRpcError::Fault(
+                RpcFaultStatus::UnknownInterface
+            )))
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+//! Enumeration of the data streams of a file, see [`ResourceHandle::list_streams`].
+//!
+//! Files on NTFS may contain alternate data streams, in addition to their default (unnamed) data stream.
+//! A stream is opened by appending its name to the file name, separated by a colon, e.g. `file.txt:stream`.
+
+use maybe_async::*;
+use smb_fscc::{FileStreamInformation, FileStreamInformationInner};
+
+use super::ResourceHandle;
+
+/// A data stream of a file, as returned by [`ResourceHandle::list_streams`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// The name of the stream, without the leading colon and the stream type.
+    ///
+    /// Empty for the default data stream of the file.
+    pub name: String,
+    /// The type of the stream, e.g. `$DATA`.
+    pub stream_type: String,
+    /// The size of the stream, in bytes.
+    pub size: u64,
+    /// The number of bytes allocated for the stream.
+    pub allocation_size: u64,
+}
+
+impl StreamInfo {
+    /// The type of data streams.
+    pub const DATA_STREAM_TYPE: &'static str = "$DATA";
+
+    /// Returns whether this is the default, unnamed, data stream of the file.
+    pub fn is_default(&self) -> bool {
+        self.name.is_empty()
+    }
+
+    /// Returns the name to open this stream of the file `file_name` with,
+    /// e.g. using [`Tree::create`][crate::Tree::create] or [`UncPath::with_path`][crate::UncPath::with_path].
+    ///
+    /// ```
+    /// # use smb::StreamInfo;
+    /// let stream = StreamInfo::from_stream_name(":Zone.Identifier:$DATA", 63, 64);
+    /// assert_eq!(stream.name, "Zone.Identifier");
+    /// assert_eq!(stream.path_for(r"dir\file.txt"), r"dir\file.txt:Zone.Identifier");
+    /// ```
+    pub fn path_for(&self, file_name: &str) -> String {
+        if self.is_default() {
+            file_name.to_string()
+        } else if self.stream_type == Self::DATA_STREAM_TYPE {
+            format!("{file_name}:{}", self.name)
+        } else {
+            format!("{file_name}:{}:{}", self.name, self.stream_type)
+        }
+    }
+
+    /// Builds the stream information from a stream name, as returned by the server (`:name:type`).
+    pub fn from_stream_name(stream_name: &str, size: u64, allocation_size: u64) -> Self {
+        let stream_name = stream_name.strip_prefix(':').unwrap_or(stream_name);
+        let (name, stream_type) = stream_name.rsplit_once(':').unwrap_or((stream_name, ""));
+        Self {
+            name: name.to_string(),
+            stream_type: stream_type.to_string(),
+            size,
+            allocation_size,
+        }
+    }
+}
+
+impl From<&FileStreamInformationInner> for StreamInfo {
+    fn from(value: &FileStreamInformationInner) -> Self {
+        Self::from_stream_name(
+            &value.stream_name.to_string(),
+            value.stream_size,
+            value.stream_allocation_size,
+        )
+    }
+}
+
+#[maybe_async(AFIT)]
+impl ResourceHandle {
+    /// Returns the data streams of the file, including its default data stream.
+    ///
+    /// Each stream may be opened by the name returned from [`StreamInfo::path_for`].
+    ///
+    /// ## Notes
+    /// * Directories usually have no data streams, but may have alternate data streams.
+    /// * File systems without streams support (e.g. FAT) return the default data stream only.
+    pub async fn list_streams(&self) -> crate::Result<Vec<StreamInfo>> {
+        let streams = self.query_info::<FileStreamInformation>().await?;
+        Ok(streams.iter().map(StreamInfo::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamInfo;
+
+    #[test]
+    fn test_stream_info_from_name() {
+        let default = StreamInfo::from_stream_name("::$DATA", 10, 16);
+        assert!(default.is_default());
+        assert_eq!(default.stream_type, "$DATA");
+        assert_eq!(default.path_for("file.txt"), "file.txt");
+
+        let named = StreamInfo::from_stream_name(":mystream:$DATA", 7, 8);
+        assert_eq!(named.name, "mystream");
+        assert_eq!(named.path_for("file.txt"), "file.txt:mystream");
+        assert!(!named.is_default());
+        assert_eq!((named.size, named.allocation_size), (7, 8));
+
+        let other = StreamInfo::from_stream_name(":index:$INDEX_ALLOCATION", 0, 0);
+        assert_eq!(other.path_for("dir"), "dir:index:$INDEX_ALLOCATION");
+    }
+}
@@ -0,0 +1,666 @@
+//! High-level helpers for transferring data to and from remote files.
+
+#[cfg(feature = "async")]
+use super::WrittenRanges;
+use super::{
+    File, IntegrityOptions, NameCollision, NameCollisionPolicy, RemoteState, SetLen, StreamHash,
+    WriteAtChannel, out_of_space_after, verify_upload, written_before_out_of_space,
+};
+use crate::connection::connection_info::BufferLimit;
+use maybe_async::*;
+use smb_fscc::FileDispositionInformation;
+#[cfg(feature = "glob")]
+use {
+    crate::client::{PathFilter, PatternMatches},
+    std::sync::Arc,
+};
+
+#[cfg(feature = "async")]
+use futures_util::{
+    StreamExt,
+    future::{self, Either},
+    stream::FuturesUnordered,
+};
+#[cfg(not(feature = "async"))]
+use std::io::Read;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Options for transferring data to and from remote files.
+#[derive(Debug, Clone)]
+pub struct TransferOptions {
+    /// The size of each chunk, in bytes.
+    /// If unset, defaults to [`TransferOptions::DEFAULT_CHUNK_SIZE`].
+    /// The effective chunk size never exceeds the negotiated maximum write size: a larger chunk size is clamped,
+    /// or fails the transfer, according to [`ConnectionConfig::buffer_size_policy`][crate::ConnectionConfig::buffer_size_policy].
+    pub chunk_size: Option<u32>,
+
+    /// The maximum number of chunks that may be in flight at the same time.
+    /// Memory usage of a transfer is bounded by `chunk_size * (max_in_flight + 1)`.
+    ///
+    /// This is ignored in sync builds, where chunks are always transferred one by one.
+    pub max_in_flight: usize,
+
+    /// Whether to set the end of the destination file to the number of bytes written,
+    /// once the transfer completes.
+    ///
+    /// This truncates any existing data beyond the transferred data, for example,
+    /// when the destination was opened with [`CreateDisposition::OpenIf`][smb_msg::CreateDisposition::OpenIf].
+    /// Alternatively, open the destination with [`CreateDisposition::Overwrite`][smb_msg::CreateDisposition::Overwrite]
+    /// (or OverwriteIf), to truncate it before the transfer starts.
+    pub set_end_of_file: bool,
+
+    /// Whether to delete a partially written destination file if the transfer fails,
+    /// or keep the data written so far.
+    ///
+    /// When the server runs out of space, the error reports how many bytes were written in full,
+    /// so a kept partial file can be resumed from there.
+    ///
+    /// Deleting requires the destination to be opened with the `DELETE` access right.
+    /// The file is actually deleted once it is closed.
+    pub delete_partial: bool,
+
+    /// What to do with remote names that collide on the local file system, when downloading multiple files.
+    /// See [`NameCollisionPolicy`] and [`resolve_local_names`][super::resolve_local_names].
+    pub name_collision_policy: NameCollisionPolicy,
+
+    /// If set, the data of each transferred file is hashed as it is transferred, and verified once the file is transferred,
+    /// failing with [`Error::IntegrityCheckFailed`][crate::Error::IntegrityCheckFailed] if it differs. See [`IntegrityOptions`].
+    ///
+    /// The whole remote file is verified, so uploads to existing files should also set [`TransferOptions::set_end_of_file`].
+    /// Verifying by reading back requires the remote file to be opened with read access.
+    pub integrity: Option<IntegrityOptions>,
+
+    /// If set, remote entries excluded by the filter are not downloaded, when downloading multiple files.
+    /// See [`PathFilter`].
+    #[cfg(feature = "glob")]
+    pub filter: Option<Arc<PathFilter>>,
+}
+
+impl TransferOptions {
+    pub const DEFAULT_CHUNK_SIZE: u32 = 0x100_000;
+    pub const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: None,
+            max_in_flight: Self::DEFAULT_MAX_IN_FLIGHT,
+            set_end_of_file: true,
+            delete_partial: false,
+            name_collision_policy: NameCollisionPolicy::default(),
+            integrity: None,
+            #[cfg(feature = "glob")]
+            filter: None,
+        }
+    }
+}
+
+/// A summary of a transfer of multiple files.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TransferReport {
+    /// The remote names that collided on the local file system, and how each was handled.
+    pub collisions: Vec<NameCollision>,
+    /// The number of entries each pattern of [`TransferOptions::filter`] decided on, in the order of the patterns.
+    /// Empty if no filter is set.
+    #[cfg(feature = "glob")]
+    pub filter_matches: Vec<PatternMatches>,
+}
+
+/// Uploads the data of a stream of unknown length to a remote file, with bounded memory usage.
+///
+/// Chunks are written as they arrive, extending the file naturally.
+/// An empty stream results in an empty file, if [`TransferOptions::set_end_of_file`] is set.
+///
+/// ## Arguments
+/// * `reader` - The stream to read the data from, until it ends.
+/// * `file` - The destination file, opened with write access.
+/// * `options` - See [`TransferOptions`].
+///
+/// ## Returns
+/// The number of bytes written.
+///
+/// If the server runs out of space, fails with [`Error::DiskFull`][crate::Error::DiskFull]
+/// or [`Error::QuotaExceeded`][crate::Error::QuotaExceeded], after all the writes in flight complete.
+/// The error's `written` is the number of bytes at the start of the file that were written in full.
+#[maybe_async]
+pub async fn upload<R>(reader: R, file: &File, options: &TransferOptions) -> crate::Result<u64>
+where
+    R: UploadSource,
+{
+    let result = _upload(reader, file, options).await;
+    if result.is_err() && options.delete_partial {
+        log::debug!("Upload to {} failed, deleting partial file", file.name());
+        if let Err(e) = file.set_info(FileDispositionInformation::default()).await {
+            log::warn!("Failed to delete partial file {}: {e}", file.name());
+        }
+    }
+    result
+}
+
+/// A stream to upload data from, using [`upload`].
+#[cfg(feature = "async")]
+pub trait UploadSource: AsyncRead + Unpin {}
+#[cfg(feature = "async")]
+impl<T: AsyncRead + Unpin> UploadSource for T {}
+
+/// A stream to upload data from, using [`upload`].
+#[cfg(not(feature = "async"))]
+pub trait UploadSource: Read {}
+#[cfg(not(feature = "async"))]
+impl<T: Read> UploadSource for T {}
+
+#[maybe_async]
+async fn _upload<R: UploadSource>(
+    reader: R,
+    file: &File,
+    options: &TransferOptions,
+) -> crate::Result<u64> {
+    let chunk_size = chunk_size(file, options)?;
+    let before = match &options.integrity {
+        Some(integrity) => RemoteState::query(file, integrity).await,
+        None => None,
+    };
+    let mut hash = options.integrity.as_ref().map(StreamHash::new);
+    let written = write_chunks(
+        reader,
+        file,
+        chunk_size,
+        options.max_in_flight,
+        hash.as_mut(),
+    )
+    .await?;
+    let written = finish_upload(file, written, options).await?;
+    if let (Some(integrity), Some(hash)) = (&options.integrity, hash) {
+        verify_upload(file, hash.finish(), before, integrity).await?;
+    }
+    Ok(written)
+}
+
+/// Writes the stream to `writer` in chunks of `chunk_size`, with up to `max_in_flight` writes in flight.
+/// If `hash` is set, the chunks are hashed as they are read from the stream.
+///
+/// When a write fails, all the writes in flight are completed before returning,
+/// so an out-of-space error reports the exact prefix of the destination that was written.
+#[async_impl]
+async fn write_chunks<R: UploadSource, W: WriteAtChannel>(
+    mut reader: R,
+    writer: &W,
+    chunk_size: usize,
+    max_in_flight: usize,
+    mut hash: Option<&mut StreamHash>,
+) -> crate::Result<u64> {
+    let max_in_flight = max_in_flight.max(1);
+
+    let mut in_flight = FuturesUnordered::new();
+    let mut progress = UploadProgress::default();
+    let mut offset = 0;
+    while progress.failure.is_none() {
+        let read = read_chunk(&mut reader, chunk_size);
+        futures_util::pin_mut!(read);
+        // Keep writing the chunks in flight while waiting for the next one.
+        let chunk = loop {
+            if in_flight.is_empty() {
+                break read.await;
+            }
+            match future::select(read.as_mut(), in_flight.next()).await {
+                Either::Left((chunk, _)) => break chunk,
+                Either::Right((written, _)) => progress.complete(written.unwrap()),
+            }
+        };
+        let chunk = match chunk {
+            Ok(chunk) if chunk.is_empty() => break,
+            Ok(chunk) => chunk,
+            Err(e) => {
+                progress.fail(e);
+                break;
+            }
+        };
+        if progress.failure.is_some() {
+            break;
+        }
+        if let Some(hash) = &mut hash {
+            hash.update(&chunk);
+        }
+
+        let chunk_offset = offset;
+        offset += chunk.len() as u64;
+        in_flight.push(write_chunk(writer, chunk, chunk_offset));
+
+        // Keep at most `max_in_flight` chunks in memory.
+        while in_flight.len() >= max_in_flight {
+            progress.complete(in_flight.next().await.unwrap());
+        }
+    }
+    // Drain the writes in flight, also after a failure, to know exactly what was written.
+    while let Some(written) = in_flight.next().await {
+        progress.complete(written);
+    }
+
+    match progress.failure {
+        Some(e) => Err(out_of_space_after(e, progress.written.prefix())),
+        None => Ok(offset),
+    }
+}
+
+#[sync_impl]
+fn write_chunks<R: UploadSource, W: WriteAtChannel>(
+    mut reader: R,
+    writer: &W,
+    chunk_size: usize,
+    _max_in_flight: usize,
+    mut hash: Option<&mut StreamHash>,
+) -> crate::Result<u64> {
+    let mut offset = 0;
+    loop {
+        let chunk = read_chunk(&mut reader, chunk_size)?;
+        if chunk.is_empty() {
+            break;
+        }
+        if let Some(hash) = &mut hash {
+            hash.update(&chunk);
+        }
+
+        let chunk_len = chunk.len() as u64;
+        if let (_, Err(e)) = write_chunk(writer, chunk, offset) {
+            let written = written_before_out_of_space(&e).unwrap_or(0) as u64;
+            return Err(out_of_space_after(e, offset + written));
+        }
+        offset += chunk_len;
+    }
+    Ok(offset)
+}
+
+/// The progress of the writes of an upload, which may complete out of order.
+#[cfg(feature = "async")]
+#[derive(Default)]
+struct UploadProgress {
+    written: WrittenRanges,
+    /// The first error of the upload.
+    failure: Option<crate::Error>,
+}
+
+#[cfg(feature = "async")]
+impl UploadProgress {
+    fn complete(&mut self, (offset, result): (u64, crate::Result<usize>)) {
+        match result {
+            Ok(written) => self.written.add(offset, written as u64),
+            Err(e) => {
+                let written = written_before_out_of_space(&e).unwrap_or(0);
+                self.written.add(offset, written as u64);
+                self.fail(e);
+            }
+        }
+    }
+
+    fn fail(&mut self, e: crate::Error) {
+        if self.failure.is_none() {
+            log::debug!("Upload failed, waiting for the writes in flight: {e}");
+            self.failure = Some(e);
+        }
+    }
+}
+
+/// Returns the upload chunk size. Only an explicitly configured chunk size is subject to
+/// the configured [`BufferSizePolicy`][crate::connection::BufferSizePolicy].
+fn chunk_size(file: &File, options: &TransferOptions) -> crate::Result<usize> {
+    let chunk_size = match options.chunk_size {
+        Some(chunk_size) => file.clamp_buffer_size(chunk_size as usize, BufferLimit::Write)?,
+        None => TransferOptions::DEFAULT_CHUNK_SIZE.min(file.conn_info.negotiation.max_write_size),
+    };
+    Ok(chunk_size.max(1) as usize)
+}
+
+/// Reads up to `chunk_size` bytes, returning less only when the stream ends.
+#[maybe_async]
+async fn read_chunk<R: UploadSource>(reader: &mut R, chunk_size: usize) -> crate::Result<Vec<u8>> {
+    let mut chunk = vec![0; chunk_size];
+    let mut filled = 0;
+    while filled < chunk_size {
+        match reader.read(&mut chunk[filled..]).await {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    chunk.truncate(filled);
+    Ok(chunk)
+}
+
+/// Writes a whole chunk, returning its offset along with the result.
+/// Short writes are retried by [`File`], and fail with [`Error::ShortWrite`][crate::Error::ShortWrite].
+#[maybe_async]
+async fn write_chunk<W: WriteAtChannel>(
+    writer: &W,
+    chunk: Vec<u8>,
+    offset: u64,
+) -> (u64, crate::Result<usize>) {
+    (offset, writer.write_at_channel(&chunk, offset, None).await)
+}
+
+#[maybe_async]
+async fn finish_upload(file: &File, written: u64, options: &TransferOptions) -> crate::Result<u64> {
+    if options.set_end_of_file {
+        file.set_len(written).await?;
+    }
+    log::debug!("Uploaded {written} bytes to {}", file.name());
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SetLen, StreamHash, TransferOptions, WriteAtChannel, upload, write_chunks};
+    use crate::resource::{FileCreateArgs, GetLen, ReadAtChannel, hash_at};
+    use crate::test_util::*;
+    use maybe_async::maybe_async;
+    use smb_fscc::{FileAccessMask, FileAttributes, SetFileInfoClass};
+    use smb_msg::{Command, FileId, SetInfoClass, SetInfoResponse, ShareFlags, WriteResponse};
+    use smb_transport::mock::MockStep;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// A destination that runs out of space after `capacity` bytes,
+    /// failing writes past it the way [`File`] does when the server returns `STATUS_DISK_FULL`.
+    #[derive(Clone)]
+    struct FullDisk {
+        data: Arc<Mutex<Vec<u8>>>,
+        capacity: u64,
+    }
+
+    impl FullDisk {
+        fn new(capacity: u64) -> Self {
+            Self {
+                data: Default::default(),
+                capacity,
+            }
+        }
+
+        fn data(&self) -> Vec<u8> {
+            self.data.lock().unwrap().clone()
+        }
+    }
+
+    impl WriteAtChannel for FullDisk {
+        #[maybe_async]
+        async fn write_at_channel(
+            &self,
+            buf: &[u8],
+            offset: u64,
+            _channel: Option<u32>,
+        ) -> crate::Result<usize> {
+            let written = buf.len().min(self.capacity.saturating_sub(offset) as usize);
+            let end = offset as usize + written;
+            let mut data = self.data.lock().unwrap();
+            if written > 0 {
+                if data.len() < end {
+                    data.resize(end, 0);
+                }
+                data[offset as usize..end].copy_from_slice(&buf[..written]);
+            }
+            if written < buf.len() {
+                return Err(crate::Error::DiskFull { written, offset });
+            }
+            Ok(written)
+        }
+    }
+
+    impl SetLen for FullDisk {
+        #[maybe_async]
+        async fn set_len(&self, _len: u64) -> crate::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A destination that counts its writes, each of which takes a while to complete in async builds.
+    #[derive(Default)]
+    struct SlowDisk {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        completed: AtomicUsize,
+    }
+
+    impl WriteAtChannel for SlowDisk {
+        #[maybe_async]
+        async fn write_at_channel(
+            &self,
+            buf: &[u8],
+            _offset: u64,
+            _channel: Option<u32>,
+        ) -> crate::Result<usize> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            write_delay().await;
+            self.completed.fetch_add(1, Ordering::SeqCst);
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(buf.len())
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn write_delay() {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn write_delay() {}
+
+    /// A stream of `len` bytes, that fails with an I/O error instead of ending.
+    struct FailingSource {
+        data: Vec<u8>,
+        position: usize,
+    }
+
+    impl FailingSource {
+        fn new(len: usize) -> Self {
+            Self {
+                data: make_data(len),
+                position: 0,
+            }
+        }
+
+        fn read_next(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let data = &self.data[self.position..];
+            if data.is_empty() {
+                return Err(std::io::Error::other("the source failed"));
+            }
+            let read = buf.len().min(data.len());
+            buf[..read].copy_from_slice(&data[..read]);
+            self.position += read;
+            Ok(read)
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl tokio::io::AsyncRead for FailingSource {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            let read = self.read_next(buf.initialize_unfilled());
+            std::task::Poll::Ready(read.map(|read| buf.advance(read)))
+        }
+    }
+
+    #[cfg(not(feature = "async"))]
+    impl std::io::Read for FailingSource {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.read_next(buf)
+        }
+    }
+
+    struct Source(Arc<Vec<u8>>);
+
+    impl ReadAtChannel for Source {
+        #[maybe_async]
+        async fn read_at_channel(
+            &self,
+            buf: &mut [u8],
+            offset: u64,
+            _channel: Option<u32>,
+        ) -> crate::Result<usize> {
+            let data = &self.0[offset as usize..];
+            let read = buf.len().min(data.len());
+            buf[..read].copy_from_slice(&data[..read]);
+            Ok(read)
+        }
+    }
+
+    impl GetLen for Source {
+        #[maybe_async]
+        async fn get_len(&self) -> crate::Result<u64> {
+            Ok(self.0.len() as u64)
+        }
+    }
+
+    fn make_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_upload_disk_full_reports_prefix() {
+        let data = make_data(1000);
+        let disk = FullDisk::new(700);
+        let result = write_chunks(data.as_slice(), &disk, 64, 4, None).await;
+        assert!(matches!(
+            result,
+            Err(crate::Error::DiskFull {
+                written: 700,
+                offset: 0
+            })
+        ));
+        assert_eq!(disk.data(), data[..700]);
+
+        let disk = FullDisk::new(data.len() as u64);
+        let written = write_chunks(data.as_slice(), &disk, 64, 4, None)
+            .await
+            .unwrap();
+        assert_eq!(written, data.len() as u64);
+        assert_eq!(disk.data(), data);
+    }
+
+    /// When the source fails mid-stream, the writes in flight complete before the upload returns.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_upload_source_failure_drains_writes() {
+        let disk = SlowDisk::default();
+        // Three full chunks are read, and the read of the fourth fails.
+        let result = write_chunks(FailingSource::new(200), &disk, 64, 4, None).await;
+        assert!(matches!(result, Err(crate::Error::IoError(_))));
+        assert_eq!(disk.completed.load(Ordering::SeqCst), 3);
+        assert_eq!(disk.in_flight.load(Ordering::SeqCst), 0);
+        // All three were in flight when the read failed.
+        #[cfg(feature = "async")]
+        assert_eq!(disk.max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    /// Uploads a failing source to a mock file, which is deleted only if `delete_partial` is set.
+    #[maybe_async]
+    async fn upload_failing_source(delete_partial: bool) {
+        const FILE_ID: FileId = FileId {
+            persistent: 0x61,
+            volatile: 0x62,
+        };
+        let mut steps = vec![
+            MockStep::expect(Command::Create)
+                .respond(create_response(FILE_ID, FileAttributes::new())),
+        ];
+        steps.extend((0..3).map(|_| {
+            MockStep::expect(Command::Write)
+                .respond(WriteResponse { count: 64 })
+                .delay(std::time::Duration::from_millis(20))
+        }));
+        if delete_partial {
+            steps.push(
+                MockStep::expect(Command::SetInfo)
+                    .matching("deletes the file", |r| {
+                        r.content.as_setinfo().is_ok_and(|set| {
+                            set.info_class
+                                == SetInfoClass::File(SetFileInfoClass::DispositionInformation)
+                        })
+                    })
+                    .respond(SetInfoResponse {}),
+            );
+        }
+        steps.push(MockStep::expect(Command::Close).respond(close_response()));
+        let mock = connect_mock_tree(ShareFlags::new(), steps).await;
+        let args =
+            FileCreateArgs::make_open_existing(FileAccessMask::standard_rw().with_delete(true));
+        let file = mock
+            .tree
+            .create("upload", &args)
+            .await
+            .unwrap()
+            .unwrap_file();
+
+        let options = TransferOptions {
+            chunk_size: Some(64),
+            delete_partial,
+            ..Default::default()
+        };
+        let result = upload(FailingSource::new(200), &file, &options).await;
+        assert!(matches!(result, Err(crate::Error::IoError(_))));
+        // The writes completed, and nothing but the deletion (if any) and the close follows them.
+        file.close().await.unwrap();
+        mock.handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_upload_source_failure_deletes_partial() {
+        upload_failing_source(true).await;
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_upload_source_failure_keeps_partial() {
+        upload_failing_source(false).await;
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_upload_hashes_sent_data() {
+        let data = make_data(1000);
+        let disk = FullDisk::new(data.len() as u64);
+        let mut hash = StreamHash::new(&Default::default());
+        write_chunks(data.as_slice(), &disk, 64, 4, Some(&mut hash))
+            .await
+            .unwrap();
+        let sent = hash.finish();
+        let options = Default::default();
+        let stored = hash_at(&Source(Arc::new(disk.data())), 1000, &options)
+            .await
+            .unwrap();
+        assert_eq!(sent, stored);
+
+        let mut corrupt = disk.data();
+        corrupt[500] ^= 0x01;
+        let corrupt = hash_at(&Source(Arc::new(corrupt)), 1000, &options)
+            .await
+            .unwrap();
+        assert_ne!(sent, corrupt);
+    }
+
+    #[cfg(not(feature = "single_threaded"))]
+    #[maybe_async]
+    async fn copy(from: Source, to: FullDisk) -> crate::Result<()> {
+        super::super::block_copy(from, to, 4).await
+    }
+
+    #[cfg(feature = "single_threaded")]
+    fn copy(from: Source, to: FullDisk) -> crate::Result<()> {
+        super::super::block_copy(from, to)
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_block_copy_disk_full_reports_prefix() {
+        const BLOCK: usize = 0x10000;
+        let data = Arc::new(make_data(5 * BLOCK + 100));
+        let capacity = 3 * BLOCK + 1234;
+        let disk = FullDisk::new(capacity as u64);
+        let result = copy(Source(data.clone()), disk.clone()).await;
+        assert!(matches!(
+            result,
+            Err(crate::Error::DiskFull { written, offset: 0 }) if written == capacity
+        ));
+        assert_eq!(disk.data()[..capacity], data[..capacity]);
+    }
+}
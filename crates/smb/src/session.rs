@@ -2,6 +2,13 @@
 //!
 //! This module contains the session setup logic, as well as the session message handling,
 //! including encryption and signing of messages.
+//!
+//! ## Known limitations
+//! - Encryption nonces are derived from a per-session message counter. Once the counter reaches
+//!   [`ConnectionConfig::encryption_nonce_limit`][crate::ConnectionConfig::encryption_nonce_limit],
+//!   encrypting fails with [`Error::NonceExhaustion`], rather than reusing a nonce.
+//!   The session is not renewed automatically: re-authentication keeps the keys of the session,
+//!   so the caller must set up a new session (see [`Session::stats`]).
 
 use crate::UncPath;
 use crate::connection::connection_info::ConnectionInfo;
@@ -22,23 +29,32 @@ use smb_msg::{Notification, ResponseContent, Status, session_setup::*};
 use smb_transport::IoVec;
 use std::collections::HashMap;
 use std::ops::Deref;
-use std::sync::atomic::{AtomicBool, AtomicU32};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 
+mod anonymous_ntlm;
+pub mod auth_tokens;
 mod authenticator;
 mod channel;
+mod credentials;
 mod encryptor_decryptor;
+mod io_channels;
+mod replay;
 mod setup;
 mod signer;
 #[cfg(feature = "kerberos")]
 mod sspi_network_client;
 mod state;
 
+pub use auth_tokens::{AuthTokenHook, SecurityToken, TokenDirection};
 pub use channel::*;
+pub use credentials::Credentials;
 pub use encryptor_decryptor::{MessageDecryptor, MessageEncryptor};
 
 pub use signer::MessageSigner;
-pub use state::{ChannelInfo, SessionInfo};
+pub use state::{ChannelInfo, SessionInfo, SessionInfoSnapshot, SessionStats};
 
+use io_channels::IoChannels;
+use replay::ReplayableMessage;
 use setup::*;
 
 pub struct Session {
@@ -57,14 +73,14 @@ impl Session {
     ///
     /// [Session::bind] may be used instead, to bind an existing session to a new connection.
     pub(crate) async fn create(
-        identity: sspi::AuthIdentity,
+        credentials: Credentials,
         upstream: &ChannelUpstream,
         conn_info: &Arc<ConnectionInfo>,
     ) -> crate::Result<Session> {
         const FIRST_CHANNEL_ID: u32 = 0;
 
         let setup_result = SessionSetup::<SmbSessionNew>::new(
-            identity,
+            credentials.clone(),
             upstream,
             conn_info,
             FIRST_CHANNEL_ID,
@@ -74,8 +90,11 @@ impl Session {
 
         let primary_channel = Self::_common_setup(setup_result).await?;
 
-        let handler =
-            HandlerReference::new(SessionMessageHandler::new(primary_channel.handler.clone()));
+        let handler = HandlerReference::new(SessionMessageHandler::new(
+            primary_channel.handler.clone(),
+            credentials,
+            conn_info,
+        ));
 
         Ok(Session {
             session_handler: handler,
@@ -90,7 +109,7 @@ impl Session {
     /// Returns the channel ID (in the scope of the current session) of the newly created channel.
     pub(crate) async fn bind(
         &self,
-        identity: sspi::AuthIdentity,
+        credentials: Credentials,
         handler: &HandlerReference<ConnectionMessageHandler>,
         conn_info: &Arc<ConnectionInfo>,
     ) -> crate::Result<u32> {
@@ -125,7 +144,7 @@ impl Session {
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
 
         let setup_result = SessionSetup::<SmbSessionBind>::new(
-            identity,
+            credentials,
             handler,
             conn_info,
             new_channel_id,
@@ -146,6 +165,7 @@ impl Session {
             .write()
             .await?
             .insert(new_channel_id, channel_handler);
+        self.session_handler.io_channels.activate();
 
         Ok(new_channel_id)
     }
@@ -178,12 +198,87 @@ impl Session {
     /// Connects to the specified tree on the current session.
     /// ## Arguments
     /// * `name` - The name of the tree to connect to.
+    ///
+    /// On SMB 3.0 and 3.0.2 connections, the first tree connect of a signed session also validates the negotiation
+    /// of the connection (MS-SMB2 3.2.5.5). If the validation fails, the connection is terminated.
     pub async fn tree_connect(&self, name: &UncPath) -> crate::Result<Tree> {
         let name = name.clone().with_no_path().to_string();
         let tree = Tree::connect(&name, &self.session_handler, &self.conn_info).await?;
+        if self.conn_info.needs_negotiate_validation() && !self._is_unsigned().await? {
+            self._validate_negotiate(&tree).await?;
+        }
         Ok(tree)
     }
 
+    /// Whether the session is a guest or anonymous session, whose messages are not signed.
+    async fn _is_unsigned(&self) -> crate::Result<bool> {
+        let session_state = self.handler.session_state().read().await?;
+        let session = session_state.session.read().await?;
+        session.allow_unsigned()
+    }
+
+    /// Validates the negotiation of the connection over `tree`, terminating the connection if validation fails.
+    async fn _validate_negotiate(&self, tree: &Tree) -> crate::Result<()> {
+        let result = tree.validate_negotiate().await;
+        if let Err(e) = &result {
+            log::error!("Validating the negotiation failed, terminating the connection: {e}");
+            if let Some(worker) = self.session_handler.connection().worker() {
+                worker.stop().await.unwrap_or_else(|e| {
+                    log::debug!(
+                        "Failed to stop the worker after a negotiation validation failure: {e}"
+                    );
+                });
+            }
+        }
+        result
+    }
+
+    /// Returns the counters of the session.
+    ///
+    /// Encryption fails with [`Error::NonceExhaustion`] once [`SessionStats::messages_encrypted`]
+    /// reaches [`SessionStats::encryption_nonce_limit`]: the session must then be replaced by a new one.
+    pub async fn stats(&self) -> crate::Result<SessionStats> {
+        let session_state = self.handler.session_state().read().await?;
+        let session = session_state.session.read().await?;
+        Ok(session.stats())
+    }
+
+    /// Returns a snapshot of the properties of the session, such as whether it is a guest session,
+    /// and whether its messages are encrypted.
+    /// See [`Connection::negotiate_info`][crate::Connection::negotiate_info] for the properties of the connection.
+    pub async fn info(&self) -> crate::Result<SessionInfoSnapshot> {
+        let channel_count = self.alt_channels.read().await?.len() + 1;
+        let session_state = self.handler.session_state().read().await?;
+        let session = session_state.session.read().await?;
+        session.snapshot(channel_count)
+    }
+
+    /// Re-authenticates the session, using the credentials it was set up with (MS-SMB2 3.2.4.2.3).
+    ///
+    /// The session keeps its ID and keys, so its trees and open handles remain valid.
+    /// This renews the authentication of sessions that expire, e.g. when their Kerberos ticket expires,
+    /// after which the server fails requests with [`Status::NetworkSessionExpired`].
+    /// See [`Session::set_auto_reauth`] to re-authenticate automatically.
+    pub async fn renew(&self) -> crate::Result<()> {
+        self.session_handler.renew().await
+    }
+
+    /// Sets whether to re-authenticate the session automatically, when the server fails a request
+    /// with [`Status::NetworkSessionExpired`], and then send the failed request again, once.
+    ///
+    /// Requests sent as compounds are not sent again. Disabled by default;
+    /// the [`Client`][crate::Client] enables it according to [`ClientConfig::auto_reauth`][crate::ClientConfig::auto_reauth].
+    pub fn set_auto_reauth(&self, auto_reauth: bool) {
+        self.session_handler
+            .auto_reauth
+            .store(auto_reauth, Ordering::SeqCst);
+    }
+
+    /// Marks the session as logged off, without sending a logoff request.
+    pub(crate) fn abandon(&self) {
+        self.session_handler.abandon();
+    }
+
     /// Logs off the session.
     ///
     /// Any resources held by the session will be released,
@@ -232,11 +327,31 @@ pub(crate) struct SessionMessageHandler {
     channel_handlers: RwLock<HashMap<u32, HandlerReference<ChannelMessageHandler>>>,
 
     dropping: AtomicBool,
+
+    /// The credentials to re-authenticate the session with. See [`Session::renew`].
+    credentials: Credentials,
+    conn_info: Arc<ConnectionInfo>,
+    /// See [`Session::set_auto_reauth`].
+    auto_reauth: AtomicBool,
+    /// The number of completed re-authentications.
+    renewals: AtomicU64,
+    /// Held while re-authenticating.
+    renew_lock: Mutex<()>,
+    /// Copies of requests that are waiting for their responses, by alternate channel and message ID, to send them again
+    /// if they fail since the session expired, or since their alternate channel failed. Only kept while
+    /// [`SessionMessageHandler::auto_reauth`] is set, or for requests spread over the channels by [`SessionMessageHandler::io_channels`].
+    replays: Mutex<HashMap<(Option<u32>, u64), ReplayableMessage>>,
+    /// Spreads large reads and writes over the channels of the session.
+    io_channels: IoChannels,
 }
 
 #[maybe_async(AFIT)]
 impl SessionMessageHandler {
-    pub fn new(primary_channel: HandlerReference<ChannelMessageHandler>) -> Self {
+    pub fn new(
+        primary_channel: HandlerReference<ChannelMessageHandler>,
+        credentials: Credentials,
+        conn_info: &Arc<ConnectionInfo>,
+    ) -> Self {
         let session_id = primary_channel.session_id();
         let primary_channel_id = primary_channel.channel_id();
         Self {
@@ -245,9 +360,27 @@ impl SessionMessageHandler {
             primary_channel: primary_channel.clone(),
             channel_handlers: RwLock::new(HashMap::from([(primary_channel_id, primary_channel)])),
             dropping: AtomicBool::new(false),
+            credentials,
+            conn_info: conn_info.clone(),
+            auto_reauth: AtomicBool::new(false),
+            renewals: AtomicU64::new(0),
+            renew_lock: Default::default(),
+            replays: Default::default(),
+            io_channels: Default::default(),
         }
     }
 
+    /// Returns the connection of the primary channel of the session.
+    pub fn connection(&self) -> &ChannelUpstream {
+        self.primary_channel.connection()
+    }
+
+    /// Skips the logoff of the session when it is dropped.
+    pub fn abandon(&self) {
+        self.dropping
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
     pub async fn logoff(&self) -> crate::Result<()> {
         if self
             .dropping
@@ -283,6 +416,88 @@ impl SessionMessageHandler {
         Ok(())
     }
 
+    /// Re-authenticates the session. See [`Session::renew`].
+    pub async fn renew(&self) -> crate::Result<()> {
+        self.renew_after(self.renewals.load(Ordering::SeqCst)).await
+    }
+
+    /// Re-authenticates the session, unless it was re-authenticated since `renewals` re-authentications completed.
+    ///
+    /// This makes concurrent requests that fail since the session expired re-authenticate it only once.
+    async fn renew_after(&self, renewals: u64) -> crate::Result<()> {
+        let _renewing = self.renew_lock.lock().await?;
+        if self.renewals.load(Ordering::SeqCst) != renewals {
+            log::debug!("Session {} was already re-authenticated.", self.session_id);
+            return Ok(());
+        }
+
+        log::debug!("Re-authenticating session {}.", self.session_id);
+        SessionSetup::new_reauth(
+            self.credentials.clone(),
+            self.connection(),
+            &self.conn_info,
+            self.primary_channel.session_state(),
+        )
+        .await?
+        .setup()
+        .await?;
+        self.renewals.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Receives a response, and if it failed since the session expired,
+    /// re-authenticates the session and sends the request of the response again, once.
+    ///
+    /// If the response was to be received over an alternate channel that failed,
+    /// the request is sent again over the primary channel instead.
+    async fn _recvo_or_replay(
+        &self,
+        options: ReceiveOptions<'_>,
+        replay: ReplayableMessage,
+    ) -> crate::Result<IncomingMessage> {
+        let result = self
+            ._with_channel(options.channel_id, RecvoWithChannel(options.clone()))
+            .await;
+        let session_expired = matches!(
+            result,
+            Err(Error::ServerError(Status::NetworkSessionExpired, _))
+        ) && self.auto_reauth.load(Ordering::SeqCst);
+        match (self._alt_channel_id(options.channel_id), &result) {
+            (Some(channel_id), Err(e)) if e.is_connection_failure() => {
+                self.io_channels.fail(channel_id, e);
+                let sent = self
+                    ._with_channel(
+                        None,
+                        SendoWithChannel(replay.into_outgoing().with_channel_id(None)),
+                    )
+                    .await?;
+
+                let mut options = options;
+                options.msg_id = sent.msg_id;
+                options.channel_id = None;
+                return self._with_channel(None, RecvoWithChannel(options)).await;
+            }
+            _ if !session_expired => return result,
+            _ => {}
+        }
+
+        log::info!(
+            "Session {} expired, re-authenticating and sending message {} again.",
+            self.session_id,
+            options.msg_id
+        );
+        self.renew_after(replay.renewals).await?;
+        let channel_id = replay.channel_id();
+        let sent = self
+            ._with_channel(channel_id, SendoWithChannel(replay.into_outgoing()))
+            .await?;
+
+        let mut options = options;
+        options.msg_id = sent.msg_id;
+        self._with_channel(channel_id, RecvoWithChannel(options))
+            .await
+    }
+
     /// Logs off the session and invalidates it.
     ///
     /// # Notes
@@ -295,6 +510,11 @@ impl SessionMessageHandler {
         });
     }
 
+    /// Returns the channel ID, unless it is that of the primary channel.
+    fn _alt_channel_id(&self, channel_id: Option<u32>) -> Option<u32> {
+        channel_id.filter(|&channel_id| channel_id != self.primary_channel_id)
+    }
+
     #[inline]
     async fn _with_channel<T: WithChannel>(
         &self,
@@ -318,13 +538,86 @@ impl SessionMessageHandler {
 
 #[maybe_async(AFIT)]
 impl MessageHandler for SessionMessageHandler {
-    async fn sendo(&self, msg: OutgoingMessage) -> crate::Result<SendMessageResult> {
-        self._with_channel(msg.channel_id, SendoWithChannel(msg))
-            .await
+    async fn sendo(&self, mut msg: OutgoingMessage) -> crate::Result<SendMessageResult> {
+        let spread_channel_id =
+            match self.io_channels.is_active() && IoChannels::is_spreadable(&msg) {
+                true => {
+                    let channel_ids = self
+                        .channel_handlers
+                        .read()
+                        .await?
+                        .keys()
+                        .copied()
+                        .collect();
+                    self._alt_channel_id(self.io_channels.pick(channel_ids))
+                }
+                false => None,
+            };
+        if spread_channel_id.is_some() {
+            msg.channel_id = spread_channel_id;
+        }
+
+        let replay = match (self.auto_reauth.load(Ordering::SeqCst) || spread_channel_id.is_some())
+            && msg.has_response
+        {
+            true => Some(ReplayableMessage::new(
+                &msg,
+                self.renewals.load(Ordering::SeqCst),
+            )?),
+            false => None,
+        };
+        let channel_id = msg.channel_id;
+        let (channel_id, mut result, replay) = match (
+            spread_channel_id,
+            self._with_channel(channel_id, SendoWithChannel(msg)).await,
+        ) {
+            (Some(spread_channel_id), Err(e)) if e.is_connection_failure() => {
+                self.io_channels.fail(spread_channel_id, &e);
+                let replay = replay
+                    .expect("spread messages are copied")
+                    .with_channel_id(None);
+                let result = self
+                    ._with_channel(None, SendoWithChannel(replay.clone().into_outgoing()))
+                    .await?;
+                (None, result, Some(replay))
+            }
+            (_, result) => (channel_id, result?, replay),
+        };
+        result.channel_id = channel_id;
+        if let Some(replay) = replay {
+            self.replays
+                .lock()
+                .await?
+                .insert((self._alt_channel_id(channel_id), result.msg_id), replay);
+        }
+        Ok(result)
     }
 
     async fn recvo(&self, options: ReceiveOptions<'_>) -> crate::Result<IncomingMessage> {
-        self._with_channel(options.channel_id, RecvoWithChannel(options))
+        let replay = match self.auto_reauth.load(Ordering::SeqCst) || self.io_channels.is_active() {
+            true => self
+                .replays
+                .lock()
+                .await?
+                .remove(&(self._alt_channel_id(options.channel_id), options.msg_id)),
+            false => None,
+        };
+        match replay {
+            Some(replay) => self._recvo_or_replay(options, replay).await,
+            None => {
+                self._with_channel(options.channel_id, RecvoWithChannel(options))
+                    .await
+            }
+        }
+    }
+
+    async fn sendo_compound(
+        &self,
+        msgs: Vec<OutgoingMessage>,
+    ) -> crate::Result<Vec<SendMessageResult>> {
+        // A compound is sent over a single channel: that of its first message.
+        let channel_id = msgs.first().and_then(|msg| msg.channel_id);
+        self._with_channel(channel_id, SendoCompoundWithChannel(msgs))
             .await
     }
 }
@@ -350,6 +643,18 @@ impl WithChannel for SendoWithChannel {
     }
 }
 
+struct SendoCompoundWithChannel(Vec<OutgoingMessage>);
+#[maybe_async(AFIT)]
+impl WithChannel for SendoCompoundWithChannel {
+    type Result = Vec<SendMessageResult>;
+    async fn work(
+        self,
+        href: &HandlerReference<ChannelMessageHandler>,
+    ) -> crate::Result<Self::Result> {
+        href.sendo_compound(self.0).await
+    }
+}
+
 struct RecvoWithChannel<'a>(ReceiveOptions<'a>);
 #[maybe_async(AFIT)]
 impl WithChannel for RecvoWithChannel<'_> {
@@ -384,6 +689,8 @@ impl Drop for SessionMessageHandler {
         let session_id = self.session_id;
         let primary_channel_id = self.primary_channel_id;
         let primary_channel = self.primary_channel.clone();
+        let credentials = self.credentials.clone();
+        let conn_info = self.conn_info.clone();
 
         tokio::task::spawn(async move {
             let temp_handler = SessionMessageHandler {
@@ -392,6 +699,13 @@ impl Drop for SessionMessageHandler {
                 primary_channel_id,
                 primary_channel,
                 channel_handlers: Default::default(),
+                credentials,
+                conn_info,
+                auto_reauth: AtomicBool::new(false),
+                renewals: AtomicU64::new(0),
+                renew_lock: Default::default(),
+                replays: Default::default(),
+                io_channels: Default::default(),
             };
             temp_handler.logoff_async().await;
         });
@@ -0,0 +1,251 @@
+//! NTLM anonymous (null session) authentication.
+//!
+//! SSPI always computes NTLMv2 responses from the credentials, so it can not produce the anonymous
+//! AUTHENTICATE_MESSAGE, which has an empty user name, an empty NtChallengeResponse and a single zero byte
+//! LmChallengeResponse (MS-NLMP 3.1.5.1.2). This module produces the two client tokens, wrapped in SPNEGO (RFC 4178),
+//! the same way SSPI wraps the NTLM tokens of [`Credentials::Password`][super::Credentials::Password].
+
+use crate::Error;
+
+/// The NTLM client of a null session.
+#[derive(Debug, Default)]
+pub(crate) struct AnonymousNtlm {
+    step: AnonymousNtlmStep,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum AnonymousNtlmStep {
+    #[default]
+    Negotiate,
+    Authenticate,
+    Done,
+}
+
+impl AnonymousNtlm {
+    const SIGNATURE: &'static [u8] = b"NTLMSSP\0";
+    const NEGOTIATE_MESSAGE: u32 = 1;
+    const CHALLENGE_MESSAGE: u32 = 2;
+    const AUTHENTICATE_MESSAGE: u32 = 3;
+
+    // Negotiate flags, MS-NLMP 2.2.2.5.
+    const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+    const NEGOTIATE_OEM: u32 = 0x0000_0002;
+    const REQUEST_TARGET: u32 = 0x0000_0004;
+    const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+    const NEGOTIATE_ANONYMOUS: u32 = 0x0000_0800;
+    const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+    const NEGOTIATE_EXTENDED_SESSION_SECURITY: u32 = 0x0008_0000;
+
+    const NEGOTIATE_FLAGS: u32 = Self::NEGOTIATE_UNICODE
+        | Self::NEGOTIATE_OEM
+        | Self::REQUEST_TARGET
+        | Self::NEGOTIATE_NTLM
+        | Self::NEGOTIATE_ANONYMOUS
+        | Self::NEGOTIATE_ALWAYS_SIGN
+        | Self::NEGOTIATE_EXTENDED_SESSION_SECURITY;
+
+    /// The SessionBaseKey of a null session is all zeros (MS-NLMP 3.3.2).
+    pub const SESSION_KEY: [u8; 16] = [0; 16];
+
+    pub fn is_authenticated(&self) -> bool {
+        self.step == AnonymousNtlmStep::Done
+    }
+
+    /// Returns the next token to send, for the token received from the server.
+    pub fn next(&mut self, gss_token: &[u8]) -> crate::Result<Vec<u8>> {
+        match self.step {
+            AnonymousNtlmStep::Negotiate => {
+                self.step = AnonymousNtlmStep::Authenticate;
+                Ok(spnego::neg_token_init(&Self::negotiate_message()))
+            }
+            AnonymousNtlmStep::Authenticate => {
+                let challenge = Self::find_message(gss_token, Self::CHALLENGE_MESSAGE)?;
+                let message = Self::authenticate_message(challenge)?;
+                self.step = AnonymousNtlmStep::Done;
+                Ok(spnego::neg_token_resp(&message))
+            }
+            AnonymousNtlmStep::Done => {
+                Err(Error::InvalidState("Authentication already done.".into()))
+            }
+        }
+    }
+
+    /// Returns the NEGOTIATE_MESSAGE (MS-NLMP 2.2.1.1), with no domain and workstation.
+    fn negotiate_message() -> Vec<u8> {
+        const LENGTH: u32 = 32;
+        let mut message = Vec::with_capacity(LENGTH as usize);
+        message.extend_from_slice(Self::SIGNATURE);
+        message.extend_from_slice(&Self::NEGOTIATE_MESSAGE.to_le_bytes());
+        message.extend_from_slice(&Self::NEGOTIATE_FLAGS.to_le_bytes());
+        // DomainNameFields, WorkstationFields
+        Self::write_fields(&mut message, 0, LENGTH);
+        Self::write_fields(&mut message, 0, LENGTH);
+        message
+    }
+
+    /// Returns the anonymous AUTHENTICATE_MESSAGE (MS-NLMP 2.2.1.3), for the server's CHALLENGE_MESSAGE.
+    fn authenticate_message(challenge: &[u8]) -> crate::Result<Vec<u8>> {
+        const PAYLOAD_OFFSET: u32 = 64;
+        let server_flags = challenge
+            .get(20..24)
+            .map(|flags| u32::from_le_bytes(flags.try_into().unwrap()))
+            .ok_or_else(|| {
+                Error::InvalidMessage("NTLM CHALLENGE_MESSAGE is too short.".to_string())
+            })?;
+
+        let charset = if server_flags & Self::NEGOTIATE_UNICODE != 0 {
+            Self::NEGOTIATE_UNICODE
+        } else {
+            Self::NEGOTIATE_OEM
+        };
+        let flags = Self::NEGOTIATE_ANONYMOUS
+            | Self::NEGOTIATE_NTLM
+            | Self::NEGOTIATE_ALWAYS_SIGN
+            | charset
+            | (server_flags & Self::NEGOTIATE_EXTENDED_SESSION_SECURITY);
+
+        let mut message = Vec::with_capacity(PAYLOAD_OFFSET as usize + 1);
+        message.extend_from_slice(Self::SIGNATURE);
+        message.extend_from_slice(&Self::AUTHENTICATE_MESSAGE.to_le_bytes());
+        // LmChallengeResponseFields: Z(1)
+        Self::write_fields(&mut message, 1, PAYLOAD_OFFSET);
+        // NtChallengeResponseFields, DomainNameFields, UserNameFields, WorkstationFields,
+        // EncryptedRandomSessionKeyFields: all empty.
+        for _ in 0..5 {
+            Self::write_fields(&mut message, 0, PAYLOAD_OFFSET + 1);
+        }
+        message.extend_from_slice(&flags.to_le_bytes());
+        debug_assert_eq!(message.len(), PAYLOAD_OFFSET as usize);
+        message.push(0);
+        Ok(message)
+    }
+
+    /// Writes the length, maximum length and offset fields of a payload item.
+    fn write_fields(message: &mut Vec<u8>, len: u16, offset: u32) {
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&offset.to_le_bytes());
+    }
+
+    /// Finds the NTLM message of the specified type in a (possibly SPNEGO-wrapped) token.
+    fn find_message(token: &[u8], message_type: u32) -> crate::Result<&[u8]> {
+        let start = token
+            .windows(Self::SIGNATURE.len())
+            .position(|window| window == Self::SIGNATURE)
+            .ok_or_else(|| {
+                Error::InvalidMessage("Server token does not contain an NTLM message.".to_string())
+            })?;
+        let message = &token[start..];
+        let found_type = message
+            .get(8..12)
+            .map(|t| u32::from_le_bytes(t.try_into().unwrap()));
+        if found_type != Some(message_type) {
+            return Err(Error::InvalidMessage(format!(
+                "Expected NTLM message of type {message_type}, got {found_type:?}."
+            )));
+        }
+        Ok(message)
+    }
+}
+
+/// Minimal DER encoding of the SPNEGO tokens sent by the client.
+mod spnego {
+    /// 1.3.6.1.5.5.2
+    const SPNEGO_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x02];
+    /// 1.3.6.1.4.1.311.2.2.10
+    const NTLMSSP_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x02, 0x0a];
+
+    /// Returns the initial token: an application-tagged NegTokenInit, offering NTLMSSP only.
+    pub fn neg_token_init(mech_token: &[u8]) -> Vec<u8> {
+        let mech_types = tlv(0xa0, &tlv(0x30, &tlv(0x06, NTLMSSP_OID)));
+        let mech_token = tlv(0xa2, &tlv(0x04, mech_token));
+        let neg_token_init = tlv(0xa0, &tlv(0x30, &[mech_types, mech_token].concat()));
+        tlv(0x60, &[tlv(0x06, SPNEGO_OID), neg_token_init].concat())
+    }
+
+    /// Returns a NegTokenResp, carrying the response token only.
+    pub fn neg_token_resp(response_token: &[u8]) -> Vec<u8> {
+        tlv(0xa1, &tlv(0x30, &tlv(0xa2, &tlv(0x04, response_token))))
+    }
+
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut result = vec![tag];
+        let len = content.len();
+        if len < 0x80 {
+            result.push(len as u8);
+        } else {
+            let len_bytes = len.to_be_bytes();
+            let skip = len_bytes.iter().take_while(|&&b| b == 0).count();
+            result.push(0x80 | (len_bytes.len() - skip) as u8);
+            result.extend_from_slice(&len_bytes[skip..]);
+        }
+        result.extend_from_slice(content);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::auth_tokens::{NtlmMessageInfo, SpnegoTokenInfo};
+
+    // The same SPNEGO tokens as in the auth_tokens tests.
+    const NEG_TOKEN_INIT: &str = "605706062b0601050502a04d304ba00e300c060a2b06010401823702020aa23904374e544c4d535350000100000097b208e2090009002e00000006000600280000000a005d580000000f41564956564d574f524b47524f5550";
+    const NEG_TOKEN_RESP: &str = "a181b03081ada0030a0101a10c060a2b06010401823702020aa281970481944e544c4d53535000020000000c000c003800000015c28ae2abf194bdb756daa9140001000000000050005000440000000a005d580000000f410056004900560056004d0002000c00410056004900560056004d0001000c00410056004900560056004d0004000c00410076006900760056006d0003000c00410076006900760056006d0007000800a876d878c569db0100000000";
+
+    fn from_hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_neg_token_init_encoding() {
+        let token = from_hex(NEG_TOKEN_INIT);
+        let ntlm = AnonymousNtlm::find_message(&token, AnonymousNtlm::NEGOTIATE_MESSAGE).unwrap();
+        assert_eq!(spnego::neg_token_init(ntlm), token);
+    }
+
+    #[test]
+    fn test_anonymous_exchange() {
+        let mut ntlm = AnonymousNtlm::default();
+
+        let negotiate = SpnegoTokenInfo::decode(&ntlm.next(&[]).unwrap()).unwrap();
+        let negotiate = negotiate.ntlm.unwrap();
+        assert_eq!(negotiate.message_type, 1);
+        assert_ne!(negotiate.flags & AnonymousNtlm::NEGOTIATE_ANONYMOUS, 0);
+        assert!(!ntlm.is_authenticated());
+
+        let token = ntlm.next(&from_hex(NEG_TOKEN_RESP)).unwrap();
+        assert!(ntlm.is_authenticated());
+        let authenticate = AnonymousNtlm::find_message(&token, 3).unwrap();
+        assert_eq!(
+            NtlmMessageInfo::decode(authenticate),
+            Some(NtlmMessageInfo {
+                message_type: 3,
+                flags: AnonymousNtlm::NEGOTIATE_ANONYMOUS
+                    | AnonymousNtlm::NEGOTIATE_NTLM
+                    | AnonymousNtlm::NEGOTIATE_ALWAYS_SIGN
+                    | AnonymousNtlm::NEGOTIATE_UNICODE
+                    | AnonymousNtlm::NEGOTIATE_EXTENDED_SESSION_SECURITY,
+            })
+        );
+        // LmChallengeResponse is Z(1), everything else is empty.
+        assert_eq!(&authenticate[12..20], &[1, 0, 1, 0, 64, 0, 0, 0]);
+        for fields in authenticate[20..60].chunks(8) {
+            assert_eq!(&fields[..4], &[0; 4]);
+        }
+        assert_eq!(&authenticate[64..], &[0]);
+
+        assert!(ntlm.next(&[]).is_err());
+    }
+
+    #[test]
+    fn test_unexpected_server_token() {
+        let mut ntlm = AnonymousNtlm::default();
+        ntlm.next(&[]).unwrap();
+        assert!(ntlm.next(&from_hex(NEG_TOKEN_INIT)).is_err());
+        assert!(ntlm.next(&[0x60, 0x00]).is_err());
+    }
+}
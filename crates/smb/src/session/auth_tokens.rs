@@ -0,0 +1,433 @@
+//! Inspection of the security tokens exchanged during authentication.
+//!
+//! This is intended for debugging authentication issues, without having to capture packets.
+//! Set [`ConnectionConfig::auth_token_hook`][crate::ConnectionConfig::auth_token_hook] to receive
+//! each token, and use [`SpnegoTokenInfo::decode`] to get a summary of its contents.
+//!
+//! **Security tokens contain sensitive material** (e.g. NTLM responses, Kerberos tickets),
+//! which may be used to attack the authenticated account. Do not log them in production.
+
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+/// The direction of an exchanged security token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenDirection {
+    /// A token sent by the client, in a session setup request.
+    Outgoing,
+    /// A token sent by the server, in the negotiate response or in a session setup response.
+    Incoming,
+}
+
+impl Display for TokenDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenDirection::Outgoing => write!(f, "outgoing"),
+            TokenDirection::Incoming => write!(f, "incoming"),
+        }
+    }
+}
+
+/// A security token, exchanged during session setup.
+#[derive(Debug, Clone, Copy)]
+pub struct SecurityToken<'a> {
+    pub direction: TokenDirection,
+    /// The session setup leg the token belongs to.
+    ///
+    /// Step 0 is the token in the negotiate response, which is provided by the server
+    /// before any session setup request is sent. Steps 1 and on contain a request token
+    /// and the matching response token.
+    pub step: usize,
+    /// The raw token, as sent on the wire.
+    pub data: &'a [u8],
+}
+
+impl SecurityToken<'_> {
+    /// Decodes the SPNEGO structure of the token. See [`SpnegoTokenInfo::decode`].
+    pub fn decode(&self) -> Option<SpnegoTokenInfo> {
+        SpnegoTokenInfo::decode(self.data)
+    }
+}
+
+type AuthTokenCallback = dyn Fn(&SecurityToken<'_>) + Send + Sync;
+
+/// A caller-provided hook, invoked for each security token exchanged during session setup.
+///
+/// See the [module documentation][self] for more information.
+#[derive(Clone)]
+pub struct AuthTokenHook(Arc<AuthTokenCallback>);
+
+impl AuthTokenHook {
+    /// The log target used by [`AuthTokenHook::log`].
+    pub const LOG_TARGET: &'static str = "smb::auth_tokens";
+
+    /// Creates a new hook, invoking the specified callback for each token.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&SecurityToken<'_>) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+
+    /// Creates a hook that logs each token, along with its decoded summary,
+    /// at debug level to the [`AuthTokenHook::LOG_TARGET`] target.
+    pub fn log() -> Self {
+        Self::new(|token| {
+            let summary = match token.decode() {
+                Some(info) => info.to_string(),
+                None => "<not SPNEGO>".to_string(),
+            };
+            log::debug!(
+                target: Self::LOG_TARGET,
+                "{} token (step {}): {} bytes, {summary}, raw: {}",
+                token.direction,
+                token.step,
+                token.data.len(),
+                hex_string(token.data)
+            );
+        })
+    }
+
+    pub(crate) fn invoke(&self, direction: TokenDirection, step: usize, data: &[u8]) {
+        (self.0)(&SecurityToken {
+            direction,
+            step,
+            data,
+        });
+    }
+}
+
+impl std::fmt::Debug for AuthTokenHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AuthTokenHook").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for AuthTokenHook {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for AuthTokenHook {}
+
+fn hex_string(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// A summary of an NTLM message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NtlmMessageInfo {
+    /// The NTLM message type: 1 (NEGOTIATE), 2 (CHALLENGE) or 3 (AUTHENTICATE).
+    pub message_type: u32,
+    /// The negotiate flags of the message, as defined in MS-NLMP 2.2.2.5.
+    pub flags: u32,
+}
+
+impl NtlmMessageInfo {
+    const SIGNATURE: &'static [u8] = b"NTLMSSP\0";
+
+    /// Decodes the NTLM message header, if the data is an NTLM message.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let rest = data.strip_prefix(Self::SIGNATURE)?;
+        let message_type = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?);
+        // Offsets of the NegotiateFlags field, from the start of the message.
+        let flags_offset = match message_type {
+            1 => 12,
+            2 => 20,
+            3 => 60,
+            _ => return None,
+        };
+        let flags = u32::from_le_bytes(data.get(flags_offset..flags_offset + 4)?.try_into().ok()?);
+        Some(Self {
+            message_type,
+            flags,
+        })
+    }
+
+    /// Returns the name of the message type.
+    pub fn message_type_name(&self) -> &'static str {
+        match self.message_type {
+            1 => "NEGOTIATE",
+            2 => "CHALLENGE",
+            3 => "AUTHENTICATE",
+            _ => "UNKNOWN",
+        }
+    }
+}
+
+/// A summary of a SPNEGO (RFC 4178) token.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SpnegoTokenInfo {
+    /// The mechanism types offered by the sender, in dotted OID notation (`NegTokenInit`).
+    pub mech_types: Vec<String>,
+    /// The mechanism selected by the server, in dotted OID notation (`NegTokenResp`).
+    pub supported_mech: Option<String>,
+    /// The negotiation state (`NegTokenResp`):
+    /// 0 (accept-completed), 1 (accept-incomplete), 2 (reject) or 3 (request-mic).
+    pub neg_state: Option<u8>,
+    /// The NTLM message inside the token, if any.
+    pub ntlm: Option<NtlmMessageInfo>,
+}
+
+impl SpnegoTokenInfo {
+    /// The SPNEGO mechanism OID.
+    pub const SPNEGO_OID: &'static str = "1.3.6.1.5.5.2";
+
+    /// Decodes a SPNEGO token, or a raw NTLM message.
+    ///
+    /// Returns `None` if the data is neither, or is malformed.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if let Some(ntlm) = NtlmMessageInfo::decode(data) {
+            return Some(Self {
+                ntlm: Some(ntlm),
+                ..Default::default()
+            });
+        }
+
+        let (tag, value, _) = read_tlv(data)?;
+        match tag {
+            // InitialContextToken ::= [APPLICATION 0] { thisMech, NegTokenInit wrapped in [0] }
+            0x60 => {
+                let (oid_tag, oid, rest) = read_tlv(value)?;
+                if oid_tag != 0x06 || decode_oid(oid)? != Self::SPNEGO_OID {
+                    return None;
+                }
+                let (init_tag, init, _) = read_tlv(rest)?;
+                if init_tag != 0xa0 {
+                    return None;
+                }
+                Self::decode_neg_token_init(init)
+            }
+            // NegTokenResp, wrapped in [1]
+            0xa1 => Self::decode_neg_token_resp(value),
+            _ => None,
+        }
+    }
+
+    fn decode_neg_token_init(data: &[u8]) -> Option<Self> {
+        let mut result = Self::default();
+        for (tag, field) in read_sequence(data)? {
+            match tag {
+                // mechTypes
+                0xa0 => {
+                    let (seq_tag, mut mechs, _) = read_tlv(field)?;
+                    if seq_tag != 0x30 {
+                        return None;
+                    }
+                    while !mechs.is_empty() {
+                        let (oid_tag, oid, rest) = read_tlv(mechs)?;
+                        if oid_tag == 0x06 {
+                            result.mech_types.push(decode_oid(oid)?);
+                        }
+                        mechs = rest;
+                    }
+                }
+                // mechToken
+                0xa2 => result.ntlm = Self::decode_inner_ntlm(field),
+                _ => {}
+            }
+        }
+        Some(result)
+    }
+
+    fn decode_neg_token_resp(data: &[u8]) -> Option<Self> {
+        let mut result = Self::default();
+        for (tag, field) in read_sequence(data)? {
+            let (_, value, _) = read_tlv(field)?;
+            match tag {
+                // negState ENUMERATED
+                0xa0 => result.neg_state = value.first().copied(),
+                // supportedMech
+                0xa1 => result.supported_mech = Some(decode_oid(value)?),
+                // responseToken
+                0xa2 => result.ntlm = NtlmMessageInfo::decode(value),
+                _ => {}
+            }
+        }
+        Some(result)
+    }
+
+    fn decode_inner_ntlm(field: &[u8]) -> Option<NtlmMessageInfo> {
+        let (_, value, _) = read_tlv(field)?;
+        NtlmMessageInfo::decode(value)
+    }
+}
+
+impl Display for SpnegoTokenInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if !self.mech_types.is_empty() {
+            let mechs = self
+                .mech_types
+                .iter()
+                .map(|m| mech_display(m))
+                .collect::<Vec<_>>();
+            parts.push(format!("offered: [{}]", mechs.join(", ")));
+        }
+        if let Some(mech) = &self.supported_mech {
+            parts.push(format!("selected: {}", mech_display(mech)));
+        }
+        if let Some(state) = self.neg_state {
+            let state = match state {
+                0 => "accept-completed",
+                1 => "accept-incomplete",
+                2 => "reject",
+                3 => "request-mic",
+                _ => "unknown",
+            };
+            parts.push(format!("state: {state}"));
+        }
+        if let Some(ntlm) = &self.ntlm {
+            parts.push(format!(
+                "NTLM {} (flags: {:#010x})",
+                ntlm.message_type_name(),
+                ntlm.flags
+            ));
+        }
+        if parts.is_empty() {
+            return write!(f, "SPNEGO (empty)");
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Returns a well-known name of a mechanism OID, if any.
+pub fn mech_name(oid: &str) -> Option<&'static str> {
+    match oid {
+        "1.3.6.1.4.1.311.2.2.10" => Some("NTLMSSP"),
+        "1.3.6.1.4.1.311.2.2.30" => Some("NEGOEX"),
+        "1.2.840.113554.1.2.2" => Some("Kerberos"),
+        "1.2.840.48018.1.2.2" => Some("MS Kerberos"),
+        "1.2.840.113554.1.2.2.3" => Some("Kerberos U2U"),
+        _ => None,
+    }
+}
+
+fn mech_display(oid: &str) -> String {
+    match mech_name(oid) {
+        Some(name) => format!("{name} ({oid})"),
+        None => oid.to_string(),
+    }
+}
+
+/// Reads a single DER TLV, returning the tag, the value, and the remaining data.
+fn read_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, data) = data.split_first()?;
+    let (&first, mut data) = data.split_first()?;
+    let len = if first & 0x80 == 0 {
+        first as usize
+    } else {
+        let num_bytes = (first & 0x7f) as usize;
+        if num_bytes == 0 || num_bytes > size_of::<usize>() || data.len() < num_bytes {
+            return None;
+        }
+        let (len_bytes, rest) = data.split_at(num_bytes);
+        data = rest;
+        len_bytes
+            .iter()
+            .fold(0usize, |acc, &b| (acc << 8) | b as usize)
+    };
+    if data.len() < len {
+        return None;
+    }
+    let (value, rest) = data.split_at(len);
+    Some((tag, value, rest))
+}
+
+/// Reads the fields of a DER SEQUENCE, returning each field's tag and its contents.
+fn read_sequence(data: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let (tag, mut fields, _) = read_tlv(data)?;
+    if tag != 0x30 {
+        return None;
+    }
+    let mut result = Vec::new();
+    while !fields.is_empty() {
+        let (tag, value, rest) = read_tlv(fields)?;
+        result.push((tag, value));
+        fields = rest;
+    }
+    Some(result)
+}
+
+/// Decodes a DER OID value into its dotted notation.
+fn decode_oid(data: &[u8]) -> Option<String> {
+    let (&first, rest) = data.split_first()?;
+    let mut arcs = vec![(first / 40) as u64, (first % 40) as u64];
+    let mut current = 0u64;
+    for &b in rest {
+        current = current.checked_mul(128)? | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            arcs.push(current);
+            current = 0;
+        }
+    }
+    Some(
+        arcs.iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>()
+            .join("."),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NtlmMessageInfo, SpnegoTokenInfo, mech_name};
+
+    const NTLMSSP_OID: &str = "1.3.6.1.4.1.311.2.2.10";
+
+    // The same session setup buffers as in the smb-msg session setup tests.
+    const NEG_TOKEN_INIT: &str = "605706062b0601050502a04d304ba00e300c060a2b06010401823702020aa23904374e544c4d535350000100000097b208e2090009002e00000006000600280000000a005d580000000f41564956564d574f524b47524f5550";
+    const NEG_TOKEN_RESP: &str = "a181b03081ada0030a0101a10c060a2b06010401823702020aa281970481944e544c4d53535000020000000c000c003800000015c28ae2abf194bdb756daa9140001000000000050005000440000000a005d580000000f410056004900560056004d0002000c00410056004900560056004d0001000c00410056004900560056004d0004000c00410076006900760056006d0003000c00410076006900760056006d0007000800a876d878c569db0100000000";
+
+    fn from_hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_neg_token_init() {
+        let info = SpnegoTokenInfo::decode(&from_hex(NEG_TOKEN_INIT)).unwrap();
+        assert_eq!(info.mech_types, vec![NTLMSSP_OID.to_string()]);
+        assert_eq!(info.supported_mech, None);
+        assert_eq!(info.neg_state, None);
+        assert_eq!(
+            info.ntlm,
+            Some(NtlmMessageInfo {
+                message_type: 1,
+                flags: 0xe208b297
+            })
+        );
+        assert_eq!(mech_name(&info.mech_types[0]), Some("NTLMSSP"));
+    }
+
+    #[test]
+    fn test_decode_neg_token_resp() {
+        let info = SpnegoTokenInfo::decode(&from_hex(NEG_TOKEN_RESP)).unwrap();
+        assert!(info.mech_types.is_empty());
+        assert_eq!(info.supported_mech.as_deref(), Some(NTLMSSP_OID));
+        assert_eq!(info.neg_state, Some(1));
+        assert_eq!(
+            info.ntlm,
+            Some(NtlmMessageInfo {
+                message_type: 2,
+                flags: 0xe28ac215
+            })
+        );
+        assert_eq!(
+            info.to_string(),
+            "selected: NTLMSSP (1.3.6.1.4.1.311.2.2.10), state: accept-incomplete, NTLM CHALLENGE (flags: 0xe28ac215)"
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert_eq!(SpnegoTokenInfo::decode(&[]), None);
+        assert_eq!(SpnegoTokenInfo::decode(&[0x60, 0x05, 0x06]), None);
+        let mut truncated = from_hex(NEG_TOKEN_INIT);
+        truncated.truncate(40);
+        assert_eq!(SpnegoTokenInfo::decode(&truncated), None);
+    }
+}
@@ -1,21 +1,34 @@
 use std::sync::Arc;
 
-use crate::Error;
+use super::Credentials;
+use super::anonymous_ntlm::AnonymousNtlm;
 use crate::connection::AuthMethodsConfig;
 use crate::connection::connection_info::ConnectionInfo;
+use crate::{Error, UncPath};
 use maybe_async::*;
 use sspi::{
     AcquireCredentialsHandleResult, AuthIdentity, BufferType, ClientRequestFlags, CredentialUse,
-    DataRepresentation, InitializeSecurityContextResult, Negotiate, SecurityBuffer, Sspi,
-    ntlm::NtlmConfig,
+    DataRepresentation, ErrorKind, InitializeSecurityContextResult, Negotiate, SecurityBuffer,
+    Sspi, ntlm::NtlmConfig,
 };
-use sspi::{CredentialsBuffers, NegotiateConfig, SspiImpl, Username};
+use sspi::{CredentialsBuffers, NegotiateConfig, SspiImpl};
 
 #[derive(Debug)]
 pub struct Authenticator {
-    server_hostname: String,
-    user_name: Username,
+    credentials: Credentials,
+    mechanism: Mechanism,
+}
+
+#[derive(Debug)]
+enum Mechanism {
+    Sspi(Box<SspiContext>),
+    Anonymous(AnonymousNtlm),
+}
 
+#[derive(Debug)]
+struct SspiContext {
+    /// The SPN of the server, if Kerberos may be used. See [`UncPath::kerberos_spn`].
+    target_name: Option<String>,
     ssp: Negotiate,
     cred_handle: AcquireCredentialsHandleResult<Option<CredentialsBuffers>>,
     current_state: Option<InitializeSecurityContextResult>,
@@ -23,9 +36,30 @@ pub struct Authenticator {
 
 impl Authenticator {
     pub fn build(
-        identity: AuthIdentity,
+        credentials: Credentials,
         conn_info: &Arc<ConnectionInfo>,
     ) -> crate::Result<Authenticator> {
+        let mechanism = match credentials.to_identity()? {
+            Some(identity) => Mechanism::Sspi(Box::new(Self::build_sspi(identity, conn_info)?)),
+            None if conn_info.config.auth_methods.ntlm => {
+                Mechanism::Anonymous(AnonymousNtlm::default())
+            }
+            None => {
+                return Err(Error::InvalidConfiguration(
+                    "Anonymous sessions require NTLM authentication to be enabled.".to_string(),
+                ));
+            }
+        };
+        Ok(Authenticator {
+            credentials,
+            mechanism,
+        })
+    }
+
+    fn build_sspi(
+        identity: AuthIdentity,
+        conn_info: &Arc<ConnectionInfo>,
+    ) -> crate::Result<SspiContext> {
         let client_computer_name = conn_info
             .config
             .client_name
@@ -37,45 +71,72 @@ impl Authenticator {
             Some(Self::get_available_ssp_pkgs(&conn_info.config.auth_methods)),
             client_computer_name,
         ))?;
-        let user_name = identity.username.clone();
-
         let cred_handle = negotiate_ssp
             .acquire_credentials_handle()
             .with_credential_use(CredentialUse::Outbound)
             .with_auth_data(&sspi::Credentials::AuthIdentity(identity.clone()))
             .execute(&mut negotiate_ssp)?;
 
-        Ok(Authenticator {
-            server_hostname: conn_info.server_name.clone(),
+        let kerberos = cfg!(feature = "kerberos") && conn_info.config.auth_methods.kerberos;
+        let target_name = kerberos.then(|| UncPath::server_spn(&conn_info.server_name));
+        Ok(SspiContext {
+            target_name,
             ssp: negotiate_ssp,
             cred_handle,
             current_state: None,
-            user_name,
         })
     }
 
-    pub fn user_name(&self) -> &Username {
-        &self.user_name
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
     }
 
     pub fn is_authenticated(&self) -> crate::Result<bool> {
-        if self.current_state.is_none() {
-            return Ok(false);
+        match &self.mechanism {
+            Mechanism::Sspi(context) => Ok(context
+                .current_state
+                .as_ref()
+                .is_some_and(|state| state.status == sspi::SecurityStatus::Ok)),
+            Mechanism::Anonymous(anonymous) => Ok(anonymous.is_authenticated()),
         }
-        Ok(self.current_state.as_ref().unwrap().status == sspi::SecurityStatus::Ok)
     }
 
     pub fn session_key(&self) -> crate::Result<[u8; 16]> {
-        // Use the first 16 bytes of the session key.
-        let key_info = self.ssp.query_context_session_key()?;
-        let k = &key_info.session_key.as_ref()[..16];
-        Ok(k.try_into().unwrap())
+        match &self.mechanism {
+            Mechanism::Sspi(context) => {
+                // Use the first 16 bytes of the session key.
+                let key_info = context.ssp.query_context_session_key()?;
+                let k = &key_info.session_key.as_ref()[..16];
+                Ok(k.try_into().unwrap())
+            }
+            Mechanism::Anonymous(_) => Ok(AnonymousNtlm::SESSION_KEY),
+        }
     }
 
-    fn make_sspi_target_name(server_fqdn: &str) -> String {
-        format!("cifs/{server_fqdn}")
+    #[maybe_async]
+    pub async fn next(&mut self, gss_token: &[u8]) -> crate::Result<Vec<u8>> {
+        if self.is_authenticated()? {
+            return Err(Error::InvalidState("Authentication already done.".into()));
+        }
+
+        match &mut self.mechanism {
+            Mechanism::Sspi(context) => context.next(gss_token).await,
+            Mechanism::Anonymous(anonymous) => anonymous.next(gss_token),
+        }
+    }
+
+    fn get_available_ssp_pkgs(config: &AuthMethodsConfig) -> String {
+        let krb_pku2u_config = if cfg!(feature = "kerberos") && config.kerberos {
+            "kerberos,!pku2u"
+        } else {
+            "!kerberos,!pku2u"
+        };
+        let ntlm_config = if config.ntlm { "ntlm" } else { "!ntlm" };
+        format!("{ntlm_config},{krb_pku2u_config}")
     }
+}
 
+impl SspiContext {
     fn get_context_requirements() -> ClientRequestFlags {
         ClientRequestFlags::DELEGATE
             | ClientRequestFlags::MUTUAL_AUTH
@@ -87,11 +148,7 @@ impl Authenticator {
     const SSPI_REQ_DATA_REPRESENTATION: DataRepresentation = DataRepresentation::Native;
 
     #[maybe_async]
-    pub async fn next(&mut self, gss_token: &[u8]) -> crate::Result<Vec<u8>> {
-        if self.is_authenticated()? {
-            return Err(Error::InvalidState("Authentication already done.".into()));
-        }
-
+    async fn next(&mut self, gss_token: &[u8]) -> crate::Result<Vec<u8>> {
         if self.current_state.is_some()
             && self.current_state.as_ref().unwrap().status != sspi::SecurityStatus::ContinueNeeded
         {
@@ -101,7 +158,6 @@ impl Authenticator {
         }
 
         let mut output_buffer = vec![SecurityBuffer::new(Vec::new(), BufferType::Token)];
-        let target_name = Self::make_sspi_target_name(&self.server_hostname);
         let mut builder = self
             .ssp
             .initialize_security_context()
@@ -110,23 +166,22 @@ impl Authenticator {
             .with_target_data_representation(Self::SSPI_REQ_DATA_REPRESENTATION)
             .with_output(&mut output_buffer);
 
-        if cfg!(feature = "kerberos") {
-            builder = builder.with_target_name(&target_name)
+        if let Some(target_name) = &self.target_name {
+            builder = builder.with_target_name(target_name)
         }
 
         let mut input_buffers = vec![];
         input_buffers.push(SecurityBuffer::new(gss_token.to_owned(), BufferType::Token));
         builder = builder.with_input(&mut input_buffers);
 
-        let result = {
-            let mut generator = self.ssp.initialize_security_context_impl(&mut builder)?;
+        let result = match self.ssp.initialize_security_context_impl(&mut builder) {
             // Kerberos requires a network client to be set up.
             // We avoid compiling with the network client if kerberos is not enabled,
             // so be sure to avoid using it in that case.
             // while default, sync network client is supported in sspi,
             // an implementation of the async one had to be added in this module.
             #[cfg(feature = "kerberos")]
-            {
+            Ok(mut generator) => {
                 use super::sspi_network_client::ReqwestNetworkClient;
                 #[cfg(feature = "async")]
                 {
@@ -134,18 +189,18 @@ impl Authenticator {
                         &mut generator,
                         &mut ReqwestNetworkClient::new(),
                     )
-                    .await?
+                    .await
                 }
                 #[cfg(not(feature = "async"))]
                 {
-                    generator.resolve_with_client(&ReqwestNetworkClient {})?
+                    generator.resolve_with_client(&ReqwestNetworkClient {})
                 }
             }
             #[cfg(not(feature = "kerberos"))]
-            {
-                generator.resolve_to_result()?
-            }
-        };
+            Ok(mut generator) => generator.resolve_to_result(),
+            Err(e) => Err(e),
+        }
+        .map_err(|e| Self::map_error(e, self.target_name.as_deref()))?;
 
         self.current_state = Some(result);
 
@@ -157,6 +212,27 @@ impl Authenticator {
         Ok(output_buffer)
     }
 
+    /// Maps an SSPI error, telling apart the failures to get a Kerberos service ticket for the server.
+    fn map_error(error: sspi::Error, target_name: Option<&str>) -> Error {
+        let Some(spn) = target_name else {
+            return error.into();
+        };
+        let no_ticket = match error.error_type {
+            ErrorKind::TargetUnknown | ErrorKind::WrongPrincipalName => true,
+            // KDC_ERR_S_PRINCIPAL_UNKNOWN, which is reported like the client's KDC_ERR_C_PRINCIPAL_UNKNOWN.
+            ErrorKind::UnknownCredentials => error.description.contains("server not found"),
+            _ => false,
+        };
+        if no_ticket {
+            Error::KerberosNoServiceTicket {
+                spn: spn.to_string(),
+                source: error,
+            }
+        } else {
+            error.into()
+        }
+    }
+
     /// This method, despite being very similar to [`sspi::generator::Generator::resolve_with_async_client`],
     /// adds the `Send` bound to the network client, which is required for our async code.
     ///
@@ -180,14 +256,52 @@ impl Authenticator {
             }
         }
     }
+}
 
-    fn get_available_ssp_pkgs(config: &AuthMethodsConfig) -> String {
-        let krb_pku2u_config = if cfg!(feature = "kerberos") && config.kerberos {
-            "kerberos,!pku2u"
-        } else {
-            "!kerberos,!pku2u"
+#[cfg(test)]
+mod tests {
+    use super::{Error, ErrorKind, SspiContext};
+
+    #[test]
+    fn test_map_error_no_service_ticket() {
+        const SPN: &str = "cifs/server.example.com";
+        let server_unknown = || {
+            sspi::Error::new(
+                ErrorKind::UnknownCredentials,
+                "server not found in Kerberos database",
+            )
         };
-        let ntlm_config = if config.ntlm { "ntlm" } else { "!ntlm" };
-        format!("{ntlm_config},{krb_pku2u_config}")
+        assert!(matches!(
+            SspiContext::map_error(server_unknown(), Some(SPN)),
+            Error::KerberosNoServiceTicket { spn, .. } if spn == SPN
+        ));
+        assert!(matches!(
+            SspiContext::map_error(
+                sspi::Error::new(ErrorKind::TargetUnknown, "unknown"),
+                Some(SPN)
+            ),
+            Error::KerberosNoServiceTicket { .. }
+        ));
+
+        // Unknown clients, other failures, and NTLM-only setups are reported as SSPI errors.
+        let client_unknown = sspi::Error::new(
+            ErrorKind::UnknownCredentials,
+            "client not found in Kerberos database",
+        );
+        assert!(matches!(
+            SspiContext::map_error(client_unknown, Some(SPN)),
+            Error::SspiError(_)
+        ));
+        assert!(matches!(
+            SspiContext::map_error(
+                sspi::Error::new(ErrorKind::LogonDenied, "denied"),
+                Some(SPN)
+            ),
+            Error::SspiError(_)
+        ));
+        assert!(matches!(
+            SspiContext::map_error(server_unknown(), None),
+            Error::SspiError(_)
+        ));
     }
 }
@@ -1,4 +1,6 @@
 use super::*;
+use crate::connection::MessageProtectionPolicy;
+use smb_msg::Command;
 
 pub(crate) type ChannelUpstream = HandlerReference<ConnectionMessageHandler>;
 
@@ -25,7 +27,8 @@ impl Channel {
                 .ok_or_else(|| Error::InvalidState("Channel not set in setup result".into()))?;
             (session.id(), channel.id())
         };
-        let handler = ChannelMessageHandler::new(session_id, channel_id, upstream, setup_result);
+        let handler =
+            ChannelMessageHandler::new(session_id, channel_id, upstream, setup_result, conn_info);
         Ok(Self {
             channel_id,
             handler,
@@ -58,6 +61,13 @@ pub struct ChannelMessageHandler {
     upstream: ChannelUpstream,
 
     session_state: Arc<RwLock<SessionAndChannel>>,
+
+    /// See [`ConnectionConfig::protection_policy`][crate::ConnectionConfig::protection_policy].
+    protection_policy: MessageProtectionPolicy,
+    /// Whether the server requires all messages to be signed.
+    signing_required: bool,
+    /// Whether this handler re-authenticates a ready session, see [`ChannelMessageHandler::make_for_reauth`].
+    reauthenticating: bool,
 }
 
 #[maybe_async(AFIT)]
@@ -67,12 +77,16 @@ impl ChannelMessageHandler {
         channel_id: u32,
         upstream: &ChannelUpstream,
         setup_result: &Arc<RwLock<SessionAndChannel>>,
+        conn_info: &Arc<ConnectionInfo>,
     ) -> HandlerReference<ChannelMessageHandler> {
         HandlerReference::new(ChannelMessageHandler {
             session_id,
             channel_id,
             upstream: upstream.clone(),
             session_state: setup_result.clone(),
+            protection_policy: conn_info.config.protection_policy,
+            signing_required: conn_info.negotiation.security_mode.signing_required(),
+            reauthenticating: false,
         })
     }
 
@@ -86,6 +100,24 @@ impl ChannelMessageHandler {
             channel_id: u32::MAX,
             upstream: upstream.clone(),
             session_state: setup_result.clone(),
+            // Session setup is always fully protected.
+            protection_policy: MessageProtectionPolicy::default(),
+            signing_required: true,
+            reauthenticating: false,
+        })
+    }
+
+    /// Same as [`ChannelMessageHandler::make_for_setup`], for re-authenticating the ready session of `session_state`.
+    ///
+    /// Requests are protected with the existing keys of the session,
+    /// and the security validation of intermediate responses may be skipped.
+    pub(crate) async fn make_for_reauth(
+        session_state: &Arc<RwLock<SessionAndChannel>>,
+        upstream: &ChannelUpstream,
+    ) -> crate::Result<Self> {
+        Ok(Self {
+            reauthenticating: true,
+            ..Self::make_for_setup(session_state, upstream).await?
         })
     }
 
@@ -121,6 +153,13 @@ impl ChannelMessageHandler {
                 "Message not for this session!".to_string(),
             ));
         }
+        // Bulk messages may be left unprotected by the configured policy.
+        // We never send those when the server requires otherwise, so accept the unprotected response.
+        let command = incoming.message.header.command;
+        let encryption_required =
+            encryption_required && self.protection_policy.allows_encryption(command);
+        let unsigned_allowed = unsigned_allowed || !self.protection_policy.allows_signing(command);
+
         // Make sure encryption is used when required.
         if !incoming.form.encrypted && encryption_required {
             return Err(Error::InvalidMessage(
@@ -157,6 +196,12 @@ impl ChannelMessageHandler {
 
         if !skip_security_validation {
             self._verify_incoming(&incoming).await?;
+        } else if self.reauthenticating {
+            if incoming.message.header.session_id != self.session_id {
+                return Err(Error::InvalidMessage(
+                    "Message not for this session!".to_string(),
+                ));
+            }
         } else {
             // Note: this is performed here for extra security,
             // while we could have just checked the session state, let's require
@@ -183,22 +228,12 @@ impl ChannelMessageHandler {
             .await
     }
 
-    pub fn session_id(&self) -> u64 {
-        self.session_id
-    }
-
-    pub fn channel_id(&self) -> u32 {
-        self.channel_id
-    }
-
-    pub fn session_state(&self) -> &Arc<RwLock<SessionAndChannel>> {
-        &self.session_state
-    }
-}
-
-#[maybe_async(AFIT)]
-impl MessageHandler for ChannelMessageHandler {
-    async fn sendo(&self, mut msg: OutgoingMessage) -> crate::Result<SendMessageResult> {
+    /// (Internal)
+    ///
+    /// Sets the session ID of an outgoing message, and requests it to be signed or encrypted,
+    /// according to the session's configuration.
+    #[maybe_async]
+    async fn _prepare_outgoing(&self, msg: &mut OutgoingMessage) -> crate::Result<()> {
         {
             let session = self.session_state.read().await?;
             let session = session.session.read().await?;
@@ -206,6 +241,7 @@ impl MessageHandler for ChannelMessageHandler {
                 return Err(Error::InvalidState("Session is invalid".to_string()));
             }
 
+            let command = msg.message.header.command;
             // It is possible for a lower level to request encryption.
             if msg.encrypt {
                 // Session must be ready to encrypt messages.
@@ -214,23 +250,79 @@ impl MessageHandler for ChannelMessageHandler {
                         "Session is not ready, cannot encrypt message".to_string(),
                     ));
                 }
+                self._check_encryption_allowed(command)?;
             }
             // Otherwise, we should check the session's configuration.
             else if session.is_ready() || session.is_setting_up() {
                 // Encrypt if configured for the session,
                 if session.is_ready() && session.should_encrypt()? {
+                    self._check_encryption_allowed(command)?;
                     msg.encrypt = true;
                 }
                 // Sign
                 else if !session.allow_unsigned()? {
-                    msg.message.header.flags.set_signed(true);
+                    if self.protection_policy.allows_signing(command) {
+                        msg.message.header.flags.set_signed(true);
+                    } else if self.signing_required {
+                        return Err(Error::InvalidConfiguration(format!(
+                            "Server requires signing, but the protection policy does not allow signing {command} messages"
+                        )));
+                    }
                 }
             }
         }
         msg.message.header.session_id = self.session_id;
+        Ok(())
+    }
+
+    /// (Internal)
+    ///
+    /// Makes sure the configured [`MessageProtectionPolicy`] allows encrypting messages of the specified command,
+    /// when encryption is required by the server or the configuration.
+    fn _check_encryption_allowed(&self, command: Command) -> crate::Result<()> {
+        if !self.protection_policy.allows_encryption(command) {
+            return Err(Error::InvalidConfiguration(format!(
+                "Encryption is required, but the protection policy does not allow encrypting {command} messages"
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn session_id(&self) -> u64 {
+        self.session_id
+    }
+
+    pub fn channel_id(&self) -> u32 {
+        self.channel_id
+    }
+
+    pub fn session_state(&self) -> &Arc<RwLock<SessionAndChannel>> {
+        &self.session_state
+    }
+
+    /// Returns the connection of the channel.
+    pub(crate) fn connection(&self) -> &ChannelUpstream {
+        &self.upstream
+    }
+}
+
+#[maybe_async(AFIT)]
+impl MessageHandler for ChannelMessageHandler {
+    async fn sendo(&self, mut msg: OutgoingMessage) -> crate::Result<SendMessageResult> {
+        self._prepare_outgoing(&mut msg).await?;
         self.upstream.sendo(msg).await
     }
 
+    async fn sendo_compound(
+        &self,
+        mut msgs: Vec<OutgoingMessage>,
+    ) -> crate::Result<Vec<SendMessageResult>> {
+        for msg in msgs.iter_mut() {
+            self._prepare_outgoing(msg).await?;
+        }
+        self.upstream.sendo_compound(msgs).await
+    }
+
     async fn recvo(&self, options: ReceiveOptions<'_>) -> crate::Result<IncomingMessage> {
         let incoming = self.upstream.recvo(options).await?;
 
@@ -0,0 +1,119 @@
+//! Credentials used to set up sessions.
+
+use crate::Error;
+use sspi::{AuthIdentity, Secret, Username};
+
+/// The credentials to authenticate with, when setting up a session.
+///
+/// See [`Client::share_connect_with`][crate::Client::share_connect_with] and [`Connection::authenticate`][crate::Connection::authenticate].
+///
+/// Guest and anonymous sessions are neither signed nor encrypted, so
+/// [`ConnectionConfig::allow_unsigned_guest_access`][crate::ConnectionConfig::allow_unsigned_guest_access]
+/// must be set to use them.
+///
+/// Kerberos authentication uses [`Credentials::Password`]. Credentials from an existing Kerberos ticket cache
+/// or keytab are not supported, see [`AuthMethodsConfig::kerberos`][crate::connection::AuthMethodsConfig::kerberos].
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// A null session: no user name and no password (MS-NLMP 3.1.5.1.2).
+    ///
+    /// Servers usually only allow null sessions to access the IPC$ share, and shares marked as public
+    /// (e.g. `guest ok = yes` on Samba, when `map to guest` allows it).
+    Anonymous,
+    /// The guest account of the server, with an empty password.
+    Guest,
+    /// A user name and a password.
+    ///
+    /// The user name may be in the `DOMAIN\user` or `user@domain` forms.
+    Password { user: String, password: String },
+}
+
+impl Credentials {
+    /// The name of the guest account, used by [`Credentials::Guest`].
+    pub const GUEST_USER: &'static str = "Guest";
+
+    /// Returns a [`Credentials::Password`] for the user and password.
+    pub fn password(user: impl Into<String>, password: impl Into<String>) -> Self {
+        Self::Password {
+            user: user.into(),
+            password: password.into(),
+        }
+    }
+
+    /// Returns the name of the user, or an empty string for [`Credentials::Anonymous`].
+    pub fn user_name(&self) -> &str {
+        match self {
+            Credentials::Anonymous => "",
+            Credentials::Guest => Self::GUEST_USER,
+            Credentials::Password { user, .. } => user,
+        }
+    }
+
+    /// Returns the identity to authenticate with using SSPI, or `None` for [`Credentials::Anonymous`],
+    /// which is not supported by SSPI, and is set up by the crate.
+    pub(crate) fn to_identity(&self) -> crate::Result<Option<AuthIdentity>> {
+        let (user, password) = match self {
+            Credentials::Anonymous => return Ok(None),
+            Credentials::Guest => (Self::GUEST_USER, ""),
+            Credentials::Password { user, password } => (user.as_str(), password.as_str()),
+        };
+        Ok(Some(AuthIdentity {
+            username: Username::parse(user).map_err(|e| Error::SspiError(e.into()))?,
+            password: Secret::from(password.to_string()),
+        }))
+    }
+}
+
+impl From<AuthIdentity> for Credentials {
+    fn from(identity: AuthIdentity) -> Self {
+        Credentials::Password {
+            user: identity.username.inner().to_string(),
+            password: identity.password.as_ref().clone(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Credentials::Anonymous => write!(f, "Anonymous"),
+            Credentials::Guest => write!(f, "Guest"),
+            Credentials::Password { user, .. } => f
+                .debug_struct("Password")
+                .field("user", user)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_identity() {
+        assert!(Credentials::Anonymous.to_identity().unwrap().is_none());
+
+        let guest = Credentials::Guest.to_identity().unwrap().unwrap();
+        assert_eq!(guest.username.account_name(), Credentials::GUEST_USER);
+        assert!(guest.password.as_ref().is_empty());
+
+        let user = Credentials::password(r"DOMAIN\user", "pass")
+            .to_identity()
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.username.account_name(), "user");
+        assert_eq!(user.username.domain_name(), Some("DOMAIN"));
+        assert_eq!(
+            Credentials::from(user),
+            Credentials::password(r"DOMAIN\user", "pass")
+        );
+    }
+
+    #[test]
+    fn test_debug_hides_password() {
+        let debug = format!("{:?}", Credentials::password("user", "secret"));
+        assert!(debug.contains("user"));
+        assert!(!debug.contains("secret"));
+    }
+}
@@ -4,19 +4,49 @@ use binrw::prelude::*;
 use rand::RngCore;
 use rand::rngs::OsRng;
 use std::io::Cursor;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::crypto;
+use crate::{Error, crypto};
 use smb_msg::{Response, encrypted::*};
 use smb_transport::IoVec;
 
+/// Encrypts messages of a session.
+///
+/// Each nonce is made of a 64-bit message counter, followed by random bytes that are fixed for the session's key.
+/// The counter is shared between clones of the encryptor, so nonces are never reused, as long as
+/// less than `nonce_limit` messages are encrypted.
 #[derive(Debug)]
 pub struct MessageEncryptor {
     algo: Box<dyn crypto::EncryptingAlgo>,
+    nonce_counter: Arc<AtomicU64>,
+    nonce_salt: [u8; 16],
+    nonce_limit: u64,
 }
 
 impl MessageEncryptor {
-    pub fn new(algo: Box<dyn crypto::EncryptingAlgo>) -> MessageEncryptor {
-        MessageEncryptor { algo }
+    const NONCE_COUNTER_SIZE: usize = size_of::<u64>();
+
+    pub fn new(algo: Box<dyn crypto::EncryptingAlgo>, nonce_limit: u64) -> MessageEncryptor {
+        debug_assert!(algo.nonce_size() >= Self::NONCE_COUNTER_SIZE);
+        let mut nonce_salt = [0; 16];
+        OsRng.fill_bytes(&mut nonce_salt);
+        MessageEncryptor {
+            algo,
+            nonce_counter: Default::default(),
+            nonce_salt,
+            nonce_limit,
+        }
+    }
+
+    /// Returns the number of messages encrypted so far, by this encryptor and its clones.
+    pub fn messages_encrypted(&self) -> u64 {
+        self.nonce_counter.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of messages that may be encrypted, before failing with [`Error::NonceExhaustion`].
+    pub fn nonce_limit(&self) -> u64 {
+        self.nonce_limit
     }
 
     /// Encrypts message in-place.
@@ -30,7 +60,7 @@ impl MessageEncryptor {
         // Serialize message:
         let mut header = EncryptedHeader {
             signature: 0,
-            nonce: self.gen_nonce(),
+            nonce: self.gen_nonce(session_id)?,
             original_message_size: message.total_size() as u32,
             session_id,
         };
@@ -50,11 +80,29 @@ impl MessageEncryptor {
         Ok(header)
     }
 
-    fn gen_nonce(&self) -> [u8; 16] {
+    /// Generates the next nonce, or fails if the nonce limit is reached.
+    fn gen_nonce(&self, session_id: u64) -> crate::Result<[u8; 16]> {
+        let counter = self
+            .nonce_counter
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                (count < self.nonce_limit).then_some(count + 1)
+            })
+            .map_err(|encrypted| Error::NonceExhaustion {
+                session_id,
+                encrypted,
+            })?;
+
+        let nonce_size = self.algo.nonce_size();
         let mut nonce = [0; 16];
-        // Generate self.algo.nonce_size() random bytes:
-        OsRng.fill_bytes(&mut nonce[..self.algo.nonce_size()]);
-        nonce
+        nonce[..Self::NONCE_COUNTER_SIZE].copy_from_slice(&counter.to_le_bytes());
+        nonce[Self::NONCE_COUNTER_SIZE..nonce_size]
+            .copy_from_slice(&self.nonce_salt[Self::NONCE_COUNTER_SIZE..nonce_size]);
+        Ok(nonce)
+    }
+
+    #[cfg(test)]
+    fn set_messages_encrypted(&self, count: u64) {
+        self.nonce_counter.store(count, Ordering::Relaxed);
     }
 }
 
@@ -62,6 +110,9 @@ impl Clone for MessageEncryptor {
     fn clone(&self) -> Self {
         MessageEncryptor {
             algo: self.algo.clone_box(),
+            nonce_counter: self.nonce_counter.clone(),
+            nonce_salt: self.nonce_salt,
+            nonce_limit: self.nonce_limit,
         }
     }
 }
@@ -80,21 +131,56 @@ impl MessageDecryptor {
         &mut self,
         msg_in: EncryptedMessage,
     ) -> crate::Result<(Response, Vec<u8>)> {
-        // decrypt in-place
-        let mut buffer = msg_in.encrypted_message;
-        let aead_bytes = msg_in.header.aead_bytes();
-        let nonce = msg_in.header.nonce;
-        let signature = msg_in.header.signature;
-        self.algo
-            .decrypt(&mut buffer, &aead_bytes, &nonce, signature)?;
-
-        log::trace!("Decrypted message data bytes: {:x?}", &buffer);
+        let buffer = self.decrypt(msg_in)?;
         // deserialize
         let result = Response::read(&mut Cursor::new(&buffer))?;
-
-        log::debug!("Decrypted with signature {}", msg_in.header.signature);
         Ok((result, buffer))
     }
+
+    /// Decrypts the message, returning the raw decrypted message, without parsing it.
+    pub fn decrypt(&mut self, msg_in: EncryptedMessage) -> crate::Result<Vec<u8>> {
+        let mut buffer = msg_in.encrypted_message;
+        self.decrypt_payload(&msg_in.header, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Decrypts a received encrypted frame in-place, leaving only the decrypted message in `frame`.
+    ///
+    /// `header` is the encrypted header at the beginning of `frame`. Unlike [`Self::decrypt`],
+    /// the message is not copied into a new buffer: the header is removed by moving the decrypted
+    /// message to the beginning of `frame`, so it keeps its allocation.
+    pub fn decrypt_in_place(
+        &mut self,
+        header: &EncryptedHeader,
+        frame: &mut Vec<u8>,
+    ) -> crate::Result<()> {
+        if frame.len() < EncryptedHeader::STRUCTURE_SIZE {
+            return Err(Error::InvalidArgument(format!(
+                "encrypted frame of {} bytes is shorter than its header",
+                frame.len()
+            )));
+        }
+        self.decrypt_payload(header, &mut frame[EncryptedHeader::STRUCTURE_SIZE..])?;
+        frame.drain(..EncryptedHeader::STRUCTURE_SIZE);
+        Ok(())
+    }
+
+    fn decrypt_payload(
+        &mut self,
+        header: &EncryptedHeader,
+        payload: &mut [u8],
+    ) -> crate::Result<()> {
+        self.algo.decrypt(
+            payload,
+            &header.aead_bytes(),
+            &header.nonce,
+            header.signature,
+        )?;
+
+        log::trace!("Decrypted message data bytes: {:x?}", payload);
+        log::debug!("Decrypted with signature {}", header.signature);
+        Ok(())
+    }
 }
 
 impl Clone for MessageDecryptor {
@@ -104,3 +190,112 @@ impl Clone for MessageDecryptor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConnectionConfig;
+    use crate::crypto::{CryptoError, EncryptingAlgo, EncryptionResult};
+    use smb_msg::EncryptionNonce;
+
+    /// An algorithm that leaves the payload as is, and uses a CCM-sized nonce.
+    #[derive(Debug, Clone)]
+    struct PlainAlgo;
+
+    impl EncryptingAlgo for PlainAlgo {
+        fn encrypt(
+            &mut self,
+            _payload: &mut [u8],
+            _header_data: &[u8],
+            _nonce: &EncryptionNonce,
+        ) -> Result<EncryptionResult, CryptoError> {
+            Ok(EncryptionResult { signature: 0 })
+        }
+
+        fn decrypt(
+            &mut self,
+            _payload: &mut [u8],
+            _header_data: &[u8],
+            _nonce: &EncryptionNonce,
+            _signature: u128,
+        ) -> Result<(), CryptoError> {
+            Ok(())
+        }
+
+        fn nonce_size(&self) -> usize {
+            11
+        }
+
+        fn clone_box(&self) -> Box<dyn EncryptingAlgo> {
+            Box::new(self.clone())
+        }
+    }
+
+    const SESSION_ID: u64 = 0x1234;
+
+    fn encrypt(encryptor: &mut MessageEncryptor) -> crate::Result<EncryptedHeader> {
+        encryptor.encrypt_message(&mut IoVec::from(vec![0; 0x40]), SESSION_ID)
+    }
+
+    #[test]
+    fn test_nonce_counter_shared_between_clones() {
+        let mut encryptor = MessageEncryptor::new(Box::new(PlainAlgo), 10);
+        let mut clone = encryptor.clone();
+        let first = encrypt(&mut encryptor).unwrap().nonce;
+        let second = encrypt(&mut clone).unwrap().nonce;
+
+        assert_eq!(first[..8], 0u64.to_le_bytes());
+        assert_eq!(second[..8], 1u64.to_le_bytes());
+        // Same salt, and zero padding after the nonce.
+        assert_eq!(first[8..], second[8..]);
+        assert_eq!(first[11..], [0; 5]);
+        assert_eq!(encryptor.messages_encrypted(), 2);
+    }
+
+    #[test]
+    fn test_nonce_exhaustion() {
+        const LIMIT: u64 = ConnectionConfig::DEFAULT_ENCRYPTION_NONCE_LIMIT;
+        let mut encryptor = MessageEncryptor::new(Box::new(PlainAlgo), LIMIT);
+        encryptor.set_messages_encrypted(LIMIT - 1);
+
+        let last = encrypt(&mut encryptor).unwrap().nonce;
+        assert_eq!(last[..8], (LIMIT - 1).to_le_bytes());
+        for _ in 0..2 {
+            let result = encrypt(&mut encryptor);
+            assert!(matches!(
+                result,
+                Err(Error::NonceExhaustion {
+                    session_id: SESSION_ID,
+                    encrypted: LIMIT
+                })
+            ));
+        }
+        assert_eq!(encryptor.messages_encrypted(), LIMIT);
+    }
+
+    #[test]
+    fn test_decrypt_in_place() {
+        let message = (0..0x40u8).collect::<Vec<_>>();
+        let mut encryptor = MessageEncryptor::new(Box::new(PlainAlgo), 10);
+        let header = encryptor
+            .encrypt_message(&mut IoVec::from(message.clone()), SESSION_ID)
+            .unwrap();
+        let mut frame = Cursor::new(Vec::with_capacity(
+            EncryptedHeader::STRUCTURE_SIZE + message.len(),
+        ));
+        header.write(&mut frame).unwrap();
+        let mut frame = frame.into_inner();
+        frame.extend_from_slice(&message);
+
+        let mut decryptor = MessageDecryptor::new(Box::new(PlainAlgo));
+        let allocation = frame.as_ptr();
+        decryptor.decrypt_in_place(&header, &mut frame).unwrap();
+        assert_eq!(frame, message);
+        // The decrypted message is left in the received buffer, rather than in a copy of it.
+        assert_eq!(frame.as_ptr(), allocation);
+
+        let mut truncated = vec![0; EncryptedHeader::STRUCTURE_SIZE - 1];
+        let result = decryptor.decrypt_in_place(&header, &mut truncated);
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+    }
+}
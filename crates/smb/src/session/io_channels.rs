@@ -0,0 +1,130 @@
+//! Spreading the large reads and writes of a session over its channels.
+//!
+//! Once alternate channels are bound to a session (see [`Connection::bind_session`][crate::Connection::bind_session]),
+//! read and write requests of at least [`IoChannels::MIN_SPREAD_SIZE`] bytes that do not specify a channel
+//! are sent over the channels of the session in turn, including the primary one.
+//!
+//! An alternate channel whose connection fails is no longer used, and the requests it was sending
+//! are sent again over the primary channel. Reads and writes of the same range may be repeated safely.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use smb_msg::RequestContent;
+
+use crate::msg_handler::OutgoingMessage;
+
+#[derive(Debug, Default)]
+pub(crate) struct IoChannels {
+    /// Whether alternate channels were bound to the session.
+    active: AtomicBool,
+    /// The number of requests spread so far.
+    next: AtomicUsize,
+    /// The alternate channels that failed, which requests are no longer spread over.
+    failed: Mutex<HashSet<u32>>,
+}
+
+impl IoChannels {
+    /// The minimal payload size of requests that are spread over the channels, which is that of a single credit.
+    pub const MIN_SPREAD_SIZE: u32 = 0x10000;
+
+    /// Starts spreading requests once an alternate channel is bound to the session.
+    pub fn activate(&self) {
+        self.active.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether alternate channels were bound to the session, even if they all failed since.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Whether the message may be sent over any channel of the session.
+    pub fn is_spreadable(msg: &OutgoingMessage) -> bool {
+        let size = match &msg.message.content {
+            RequestContent::Read(read) => read.length,
+            RequestContent::Write(write) => write.length,
+            _ => return false,
+        };
+        msg.channel_id.is_none() && msg.has_response && size >= Self::MIN_SPREAD_SIZE
+    }
+
+    /// Returns the channel to send the next spread request over, out of the channels of the session,
+    /// skipping the failed ones.
+    pub fn pick(&self, mut channel_ids: Vec<u32>) -> Option<u32> {
+        {
+            let failed = self.failed.lock().unwrap_or_else(|e| e.into_inner());
+            channel_ids.retain(|id| !failed.contains(id));
+        }
+        if channel_ids.is_empty() {
+            return None;
+        }
+        channel_ids.sort_unstable();
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        Some(channel_ids[next % channel_ids.len()])
+    }
+
+    /// Stops spreading requests over the failed channel.
+    pub fn fail(&self, channel_id: u32, error: &crate::Error) {
+        log::warn!(
+            "Channel {channel_id} failed, sending its requests over the primary channel instead: {error}"
+        );
+        self.failed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(channel_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smb_msg::{FileId, ReadFlags, ReadRequest, WriteFlags, WriteRequest};
+
+    use super::*;
+
+    fn read(length: u32) -> OutgoingMessage {
+        OutgoingMessage::new(
+            ReadRequest {
+                flags: ReadFlags::new(),
+                length,
+                offset: 0,
+                file_id: FileId::default(),
+                minimum_count: 1,
+            }
+            .into(),
+        )
+    }
+
+    #[test]
+    fn test_is_spreadable() {
+        assert!(IoChannels::is_spreadable(&read(0x10000)));
+        assert!(!IoChannels::is_spreadable(&read(0xffff)));
+        assert!(!IoChannels::is_spreadable(
+            &read(0x10000).with_channel_id(Some(0))
+        ));
+        let write = OutgoingMessage::new(
+            WriteRequest::new(0, FileId::default(), WriteFlags::new(), 0x20000).into(),
+        );
+        assert!(IoChannels::is_spreadable(&write));
+    }
+
+    #[test]
+    fn test_pick_skips_failed_channels() {
+        let channels = IoChannels::default();
+        let picks = (0..4)
+            .map(|_| channels.pick(vec![2, 0, 1]).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(picks, [0, 1, 2, 0]);
+
+        channels.fail(1, &crate::Error::ConnectionStopped);
+        let picks = (0..4)
+            .map(|_| channels.pick(vec![0, 1, 2]).unwrap())
+            .collect::<Vec<_>>();
+        assert!(picks.iter().all(|&id| id != 1));
+        assert!(picks.contains(&0) && picks.contains(&2));
+
+        channels.fail(0, &crate::Error::ConnectionStopped);
+        channels.fail(2, &crate::Error::ConnectionStopped);
+        assert_eq!(channels.pick(vec![0, 1, 2]), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! Replaying requests that failed since their session expired.
+//!
+//! See [`Session::set_auto_reauth`][crate::Session::set_auto_reauth].
+
+use std::cmp::max;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use binrw::prelude::*;
+use smb_msg::{Header, PlainRequest, RequestContent};
+
+use crate::msg_handler::OutgoingMessage;
+
+/// A serialized copy of a sent request, to send it again after the session is re-authenticated.
+///
+/// Request contents can't be cloned, so the content is kept as its raw body,
+/// and replayed as [`RequestContent::Unknown`] of the same command.
+#[derive(Debug, Clone)]
+pub(crate) struct ReplayableMessage {
+    header: Header,
+    body: Vec<u8>,
+    credit_payload_size: u32,
+
+    additional_data: Option<Arc<[u8]>>,
+    return_raw_data: bool,
+    compress: bool,
+    encrypt: bool,
+    channel_id: Option<u32>,
+
+    /// The number of times the session was renewed before the message was sent.
+    pub renewals: u64,
+}
+
+impl ReplayableMessage {
+    /// Copies `msg`, which is about to be sent after `renewals` session renewals.
+    pub fn new(msg: &OutgoingMessage, renewals: u64) -> crate::Result<Self> {
+        // Offsets in the body are relative to the start of the header, so the whole message is written.
+        let mut data = Cursor::new(vec![]);
+        msg.message.write(&mut data)?;
+        let body = data.into_inner().split_off(Header::STRUCT_SIZE);
+
+        let content = &msg.message.content;
+        let credit_payload_size = msg
+            .credit_payload_size
+            .unwrap_or_else(|| max(content.req_payload_size(), content.expected_resp_size()));
+        Ok(Self {
+            header: msg.message.header.clone(),
+            body,
+            credit_payload_size,
+            additional_data: msg.additional_data.clone(),
+            return_raw_data: msg.return_raw_data,
+            compress: msg.compress,
+            encrypt: msg.encrypt,
+            channel_id: msg.channel_id,
+            renewals,
+        })
+    }
+
+    /// Returns the channel the message was sent over.
+    pub fn channel_id(&self) -> Option<u32> {
+        self.channel_id
+    }
+
+    /// Sets the channel to send the message again over.
+    pub fn with_channel_id(mut self, channel_id: Option<u32>) -> Self {
+        self.channel_id = channel_id;
+        self
+    }
+
+    /// Returns a message to send again, with the same header and body as the copied one.
+    pub fn into_outgoing(self) -> OutgoingMessage {
+        let command = self.header.command;
+        let mut message = PlainRequest::new_with_command(
+            RequestContent::Unknown {
+                command: command.code(),
+                body: self.body,
+            },
+            command,
+        );
+        message.header = self.header;
+
+        let mut msg = OutgoingMessage::from_message(message)
+            .with_return_raw_data(self.return_raw_data)
+            .with_compress(self.compress)
+            .with_encrypt(self.encrypt)
+            .with_channel_id(self.channel_id);
+        msg.additional_data = self.additional_data;
+        msg.credit_payload_size = Some(self.credit_payload_size);
+        msg.is_retry = true;
+        msg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use binrw::prelude::*;
+    use smb_msg::{FileId, ReadRequest, RequestContent, TreeConnectRequest};
+
+    use super::ReplayableMessage;
+    use crate::msg_handler::OutgoingMessage;
+
+    fn write(msg: &OutgoingMessage) -> Vec<u8> {
+        let mut data = Cursor::new(vec![]);
+        msg.message.write(&mut data).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn test_replay_same_message() {
+        // The path of a tree connect request is located by an offset from the header.
+        let mut tree_connect =
+            OutgoingMessage::new(TreeConnectRequest::new(r"\\server\share").into())
+                .with_channel_id(Some(1));
+        tree_connect.message.header.session_id = 5;
+
+        let replay = ReplayableMessage::new(&tree_connect, 3).unwrap();
+        assert_eq!(replay.renewals, 3);
+        let replayed = replay.into_outgoing();
+        assert_eq!(replayed.message.header, tree_connect.message.header);
+        assert_eq!(replayed.channel_id, Some(1));
+        assert_eq!(write(&replayed), write(&tree_connect));
+    }
+
+    #[test]
+    fn test_replay_keeps_credit_payload_size() {
+        let read = OutgoingMessage::new(RequestContent::Read(ReadRequest {
+            flags: Default::default(),
+            length: 0x30000,
+            offset: 0,
+            file_id: FileId {
+                persistent: 1,
+                volatile: 2,
+            },
+            minimum_count: 0,
+        }));
+        let replayed = ReplayableMessage::new(&read, 0).unwrap().into_outgoing();
+        assert_eq!(replayed.credit_payload_size, Some(0x30000));
+        assert_eq!(write(&replayed), write(&read));
+    }
+}
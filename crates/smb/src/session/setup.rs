@@ -1,4 +1,7 @@
+use crate::error::TimedOutTask;
+use crate::session::auth_tokens::TokenDirection;
 use crate::session::authenticator::Authenticator;
+use std::time::Instant;
 
 use super::*;
 
@@ -30,6 +33,11 @@ where
     channel: Option<ChannelInfo>,
     new_channel_id: u32,
 
+    /// The current (1-based) session setup leg, for error reporting.
+    step: usize,
+    /// The time by which the whole setup must complete, if limited.
+    deadline: Option<Instant>,
+
     _phantom: std::marker::PhantomData<T>,
 }
 
@@ -39,13 +47,13 @@ where
     T: SessionSetupProperties,
 {
     pub async fn new(
-        identity: sspi::AuthIdentity,
+        credentials: Credentials,
         upstream: &'a ChannelUpstream,
         conn_info: &'a Arc<ConnectionInfo>,
         new_channel_id: u32,
         primary_session: Option<&Arc<RwLock<SessionAndChannel>>>,
     ) -> crate::Result<Self> {
-        let authenticator = Authenticator::build(identity, conn_info)?;
+        let authenticator = Authenticator::build(credentials, conn_info)?;
 
         let mut result = Self {
             last_setup_response: None,
@@ -58,6 +66,8 @@ where
             conn_info,
             channel: None,
             new_channel_id,
+            step: 0,
+            deadline: None,
             _phantom: std::marker::PhantomData,
         };
 
@@ -93,12 +103,39 @@ where
     /// by calling impl functions, this function's behavior is modified to support both new sessions and binding to existing sessions.
     pub(crate) async fn setup(&mut self) -> crate::Result<Arc<RwLock<SessionAndChannel>>> {
         log::debug!(
-            "Setting up session for user {} (@{}).",
-            self.authenticator.user_name().account_name(),
-            self.authenticator.user_name().domain_name().unwrap_or("")
+            "Setting up session with credentials {:?}.",
+            self.authenticator.credentials()
         );
 
+        let timeout = self.conn_info.config.session_setup_timeout();
+        if !timeout.is_zero() {
+            self.deadline = Some(Instant::now() + timeout);
+        }
+
+        // Async: limit the whole exchange, including the SSPI computations (e.g. KDC requests).
+        // Sync: only the receive operations are limited, by the remaining time until the deadline.
+        #[cfg(feature = "async")]
+        let result = if timeout.is_zero() {
+            self._setup_loop().await
+        } else {
+            tokio::time::timeout(timeout, self._setup_loop())
+                .await
+                .unwrap_or(Err(Error::OperationTimeout(
+                    TimedOutTask::ReceiveNextMessage,
+                    timeout,
+                )))
+        };
+        #[cfg(not(feature = "async"))]
         let result = self._setup_loop().await;
+
+        let result = result.map_err(|e| match e {
+            Error::OperationTimeout(..) | Error::Timeout(..) => Error::AuthenticationTimeout {
+                server: self.conn_info.server_name.clone(),
+                step: self.step,
+                timeout,
+            },
+            e => e,
+        });
         match result {
             Ok(()) => Ok(self.result.take().unwrap()),
             Err(e) => {
@@ -118,17 +155,26 @@ where
     /// This function loops until the authentication is complete, requesting GSS tokens
     /// and passing them to the server.
     async fn _setup_loop(&mut self) -> crate::Result<()> {
+        // The server's initial token, from the negotiate response.
+        self.on_auth_token(
+            TokenDirection::Incoming,
+            &self.conn_info.negotiation.auth_buffer,
+        );
+
         // While there's a response to process, do so.
         while !self.authenticator.is_authenticated()? {
+            self.step += 1;
             let next_buf = match self.last_setup_response.as_ref() {
                 Some(response) => self.authenticator.next(&response.buffer).await?,
                 None => self.authenticator.next(&[]).await?,
             };
+            self.on_auth_token(TokenDirection::Outgoing, &next_buf);
             let is_auth_done = self.authenticator.is_authenticated()?;
 
             // If keys are exchanged, set them up, to enable validation of next response!
+            // Re-authentication keeps the keys of the session (MS-SMB2 3.2.5.3.1), so nothing is set up.
             let request = self.send_setup_request(next_buf).await?;
-            if is_auth_done {
+            if is_auth_done && !T::REAUTHENTICATION {
                 self.preauth_hash = self.preauth_hash.take().unwrap().finish().into();
                 self.make_channel().await?;
             }
@@ -137,6 +183,7 @@ where
             let message_form = response.form;
             let session_id = response.message.header.session_id;
             let session_setup_response = response.message.content.to_sessionsetup()?;
+            self.on_auth_token(TokenDirection::Incoming, &session_setup_response.buffer);
 
             // First iteration: construct a session state object.
             // TODO: currently, there's a bug which prevents authentication on first attempt
@@ -161,6 +208,7 @@ where
                         "Expected a signed message!".to_string(),
                     ));
                 }
+                self.check_guest_fallback(&session_setup_response.session_flags)?;
             } else {
                 self.next_preauth_hash(&response.raw);
             }
@@ -179,6 +227,25 @@ where
         Ok(())
     }
 
+    /// Fails if the server logged in a user that supplied a password as a guest,
+    /// and [`ConnectionConfig::refuse_guest_fallback`][crate::ConnectionConfig::refuse_guest_fallback] is set.
+    fn check_guest_fallback(&self, flags: &SessionFlags) -> crate::Result<()> {
+        let Credentials::Password { user, .. } = self.authenticator.credentials() else {
+            return Ok(());
+        };
+        if flags.is_guest_or_null_session() && self.conn_info.config.refuse_guest_fallback {
+            return Err(Error::GuestFallbackRefused { user: user.clone() });
+        }
+        Ok(())
+    }
+
+    /// Passes an exchanged token to the configured hook, if any.
+    fn on_auth_token(&self, direction: TokenDirection, data: &[u8]) {
+        if let Some(hook) = &self.conn_info.config.auth_token_hook {
+            hook.invoke(direction, self.step, data);
+        }
+    }
+
     async fn set_session(&mut self, session: Arc<RwLock<SessionInfo>>) -> crate::Result<()> {
         let session_id = session.read().await?.id();
         let result = SessionAndChannel::new(session_id, session);
@@ -208,9 +275,19 @@ where
             &[Status::MoreProcessingRequired]
         };
 
-        let roptions = ReceiveOptions::new()
+        let mut roptions = ReceiveOptions::new()
             .with_status(expected_status)
             .with_msg_id_filter(for_msg_id);
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::OperationTimeout(
+                    TimedOutTask::ReceiveNextMessage,
+                    self.conn_info.config.session_setup_timeout(),
+                ));
+            }
+            roptions = roptions.with_timeout(remaining);
+        }
 
         let channel_set_up = self.result.is_some()
             && self
@@ -221,14 +298,13 @@ where
                 .await?
                 .channel
                 .is_some();
-        let skip_security_validation = !is_auth_done && !channel_set_up;
-        if self.handler.is_some() {
+        // The intermediate responses of a re-authentication are not necessarily signed.
+        let skip_security_validation = !is_auth_done && (!channel_set_up || T::REAUTHENTICATION);
+        if let Some(handler) = &self.handler {
             log::trace!(
                 "setup loop: receiving with channel handler; skip_security_validation={skip_security_validation}"
             );
-            self.handler
-                .as_ref()
-                .unwrap()
+            handler
                 .recvo_internal(roptions, skip_security_validation)
                 .await
         } else {
@@ -288,7 +364,7 @@ where
             .as_ref()
             .unwrap()
             .unwrap_final_hash()
-            .copied()
+            .cloned()
     }
 
     fn next_preauth_hash(&mut self, data: &IoVec) -> &PreauthHashState {
@@ -307,8 +383,43 @@ where
     }
 }
 
+#[maybe_async]
+impl<'a> SessionSetup<'a, SmbSessionReauth> {
+    /// Creates a setup that re-authenticates the existing session of `session_state`,
+    /// which is set up on `upstream`, keeping its ID and keys.
+    pub async fn new_reauth(
+        credentials: Credentials,
+        upstream: &'a ChannelUpstream,
+        conn_info: &'a Arc<ConnectionInfo>,
+        session_state: &Arc<RwLock<SessionAndChannel>>,
+    ) -> crate::Result<Self> {
+        let channel_id = {
+            let state = session_state.read().await?;
+            if !state.session.read().await?.is_ready() {
+                return Err(Error::InvalidState(
+                    "Cannot re-authenticate a session that is not ready.".to_string(),
+                ));
+            }
+            state
+                .channel
+                .as_ref()
+                .ok_or_else(|| Error::InvalidState("Channel not set in session state".into()))?
+                .id()
+        };
+
+        let mut result = Self::new(credentials, upstream, conn_info, channel_id, None).await?;
+        result.handler =
+            Some(ChannelMessageHandler::make_for_reauth(session_state, upstream).await?);
+        result.result = Some(session_state.clone());
+        Ok(result)
+    }
+}
+
 #[maybe_async(AFIT)]
 pub(crate) trait SessionSetupProperties {
+    /// Whether the setup re-authenticates an existing session, rather than setting up new keys.
+    const REAUTHENTICATION: bool = false;
+
     /// This function is called when setup error is encountered, to perform any necessary cleanup.
     async fn error_cleanup<T>(setup: &mut SessionSetup<'_, T>) -> crate::Result<()>
     where
@@ -489,3 +600,80 @@ impl SessionSetupProperties for SmbSessionNew {
         Ok(session_info)
     }
 }
+
+/// Re-authentication of an existing session (MS-SMB2 3.2.4.2.3), see [`SessionSetup::new_reauth`].
+pub(crate) struct SmbSessionReauth;
+
+#[maybe_async(AFIT)]
+impl SessionSetupProperties for SmbSessionReauth {
+    const REAUTHENTICATION: bool = true;
+
+    async fn error_cleanup<T>(_setup: &mut SessionSetup<'_, T>) -> crate::Result<()>
+    where
+        T: SessionSetupProperties,
+    {
+        // The session is left as is: the server decides whether it is still usable.
+        Ok(())
+    }
+
+    async fn init_session<T>(
+        _setup: &SessionSetup<'_, T>,
+        _session_id: u64,
+    ) -> crate::Result<Arc<RwLock<SessionInfo>>>
+    where
+        T: SessionSetupProperties,
+    {
+        Err(Error::InvalidState(
+            "Re-authenticated session should be provided in construction!".to_string(),
+        ))
+    }
+
+    async fn on_setup_success<T>(_setup: &mut SessionSetup<'_, T>) -> crate::Result<()>
+    where
+        T: SessionSetupProperties,
+    {
+        log::info!("Session re-authenticated.");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use smb_dtyp::Guid;
+    use smb_msg::ShareFlags;
+    use smb_transport::mock::{MockScript, MockTransport};
+
+    use crate::connection::worker::Worker;
+    use crate::test_util::*;
+    use crate::{Connection, ConnectionConfig, Credentials, Error};
+
+    /// The server stalls at the second leg of the session setup, until after the session setup timeout.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_session_setup_timeout() {
+        let mut steps = connect_steps(ShareFlags::new());
+        steps.truncate(3);
+        let second_leg = steps.pop().unwrap().delay(Duration::from_secs(2));
+        let script = MockScript::from_iter(steps).step(second_leg);
+        let (transport, _handle) = MockTransport::new(script);
+        let config = ConnectionConfig {
+            session_setup_timeout: Some(Duration::from_millis(200)),
+            ..mock_config()
+        };
+        let connection =
+            Connection::from_transport(Box::new(transport), "server", Guid::generate(), config)
+                .await
+                .unwrap();
+
+        let result = connection.authenticate(Credentials::Anonymous).await;
+        assert!(matches!(
+            result,
+            Err(Error::AuthenticationTimeout { step: 2, timeout, .. }) if timeout == Duration::from_millis(200)
+        ));
+        // The session the first leg created is removed from the connection.
+        let transformer = connection.worker().unwrap().transformer();
+        let has_session = transformer.has_session(SESSION_ID).await;
+        assert!(!has_session);
+    }
+}
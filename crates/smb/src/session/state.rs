@@ -50,7 +50,7 @@ impl SessionAlgosFactory {
                 "Building session algorithms for dialect {:?} with session key {:02x?} and preauth hash {:02x?}",
                 info.negotiation.dialect_rev,
                 session_key,
-                preauth_hash.as_ref().map(|h| h.as_ref())
+                preauth_hash.as_deref()
             );
         }
 
@@ -184,7 +184,10 @@ impl SessionAlgosFactory {
         )?;
 
         Ok(Some((
-            MessageEncryptor::new(make_encrypting_algo(cipher, &enc_key)?),
+            MessageEncryptor::new(
+                make_encrypting_algo(cipher, &enc_key)?,
+                info.config.encryption_nonce_limit(),
+            ),
             MessageDecryptor::new(make_encrypting_algo(cipher, &dec_key)?),
         )))
     }
@@ -220,6 +223,38 @@ enum SessionInfoState {
     Invalid,
 }
 
+/// Counters of a session. See [`Session::stats`][crate::Session::stats].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SessionStats {
+    /// The number of messages encrypted with the keys of the session.
+    pub messages_encrypted: u64,
+    /// The number of messages that may be encrypted with the keys of the session,
+    /// or `None` if the session has no encryption keys.
+    /// See [`ConnectionConfig::encryption_nonce_limit`][crate::ConnectionConfig::encryption_nonce_limit].
+    pub encryption_nonce_limit: Option<u64>,
+}
+
+/// A snapshot of the properties of a session, for diagnostic purposes.
+///
+/// Returned by [`Session::info`][crate::Session::info].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SessionInfoSnapshot {
+    pub session_id: u64,
+    /// Whether the server authenticated the session as a guest.
+    pub is_guest: bool,
+    /// Whether the session is anonymous (null session).
+    pub is_anonymous: bool,
+    /// Whether messages of the session are signed, which is not the case for guest and anonymous sessions.
+    pub signed: bool,
+    /// Whether messages of the session are encrypted, as required by the server or by
+    /// [`ConnectionConfig::encryption_mode`][crate::ConnectionConfig::encryption_mode].
+    /// Trees of the server may still require encryption of their own messages.
+    pub encrypted: bool,
+    /// The number of channels of the session, including the primary one.
+    pub channel_count: usize,
+}
+
 /// Holds the information of a session, to be used for actions requiring data from session,
 /// without accessing the entire session object.
 /// This struct should be single-per-session, and wrapped in a shared pointer.
@@ -446,6 +481,38 @@ impl SessionInfo {
         }
     }
 
+    /// Returns a snapshot of the properties of the session, which must be ready.
+    pub fn snapshot(&self, channel_count: usize) -> crate::Result<SessionInfoSnapshot> {
+        match &self.state {
+            Some(SessionInfoState::Ready {
+                flags,
+                force_encryption,
+                ..
+            }) => Ok(SessionInfoSnapshot {
+                session_id: self.session_id,
+                is_guest: flags.is_guest(),
+                is_anonymous: flags.is_null_session(),
+                signed: !flags.is_guest_or_null_session(),
+                encrypted: flags.encrypt_data() || *force_encryption,
+                channel_count,
+            }),
+            _ => Err(crate::Error::InvalidState(
+                "Session is not ready!".to_string(),
+            )),
+        }
+    }
+
+    /// Returns the counters of the session.
+    pub fn stats(&self) -> SessionStats {
+        match self.encryptor() {
+            Ok(Some(encryptor)) => SessionStats {
+                messages_encrypted: encryptor.messages_encrypted(),
+                encryption_nonce_limit: Some(encryptor.nonce_limit()),
+            },
+            _ => SessionStats::default(),
+        }
+    }
+
     pub fn encryptor(&self) -> crate::Result<Option<&MessageEncryptor>> {
         match &self.state {
             Some(SessionInfoState::Ready { algos, .. }) => Ok(algos.encryptor.as_ref()),
@@ -0,0 +1,179 @@
+//! Helpers for unit tests of the upper layers, against a scripted [`MockTransport`].
+
+use std::time::Duration;
+
+use maybe_async::maybe_async;
+use smb_dtyp::{Guid, binrw_util::prelude::FileTime};
+use smb_fscc::FileAttributes;
+use smb_msg::*;
+use smb_transport::mock::{MockHandle, MockScript, MockStep, MockTransport};
+
+use crate::{Connection, ConnectionConfig, Credentials, Session, Tree, UncPath};
+
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+pub const SESSION_ID: u64 = 0x4000_0000_0011;
+pub const TREE_ID: u32 = 5;
+pub const SHARE: &str = r"\\server\share";
+
+/// A NEG_TOKEN_RESP with an NTLM CHALLENGE_MESSAGE, as in the tests of anonymous NTLM.
+const CHALLENGE_TOKEN: &str = "a181b03081ada0030a0101a10c060a2b06010401823702020aa281970481944e544c4d53535000020000000c000c003800000015c28ae2abf194bdb756daa9140001000000000050005000440000000a005d580000000f410056004900560056004d0002000c00410056004900560056004d0001000c00410056004900560056004d0004000c00410076006900760056006d0003000c00410076006900760056006d0007000800a876d878c569db0100000000";
+
+/// A connection, an anonymous session and a tree, set up over a [`MockTransport`].
+pub struct MockTree {
+    pub handle: MockHandle,
+    pub tree: Tree,
+    _session: Session,
+    pub connection: Connection,
+}
+
+/// The steps that negotiate SMB 2.1, set up an anonymous session, and connect to [`SHARE`].
+pub fn connect_steps(share_flags: ShareFlags) -> Vec<MockStep> {
+    let token = (0..CHALLENGE_TOKEN.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&CHALLENGE_TOKEN[i..i + 2], 16).unwrap())
+        .collect();
+    vec![
+        MockStep::expect(Command::Negotiate).respond(NegotiateResponse {
+            security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+            dialect_revision: NegotiateDialect::Smb021,
+            server_guid: Guid::from([0x11; 16]),
+            capabilities: GlobalCapabilities::new().with_large_mtu(true),
+            max_transact_size: 0x100000,
+            max_read_size: 0x100000,
+            max_write_size: 0x100000,
+            system_time: FileTime::default(),
+            server_start_time: FileTime::default(),
+            buffer: vec![],
+            negotiate_context_list: None,
+        }),
+        MockStep::expect(Command::SessionSetup)
+            .respond_with_status(
+                Status::MoreProcessingRequired,
+                SessionSetupResponse {
+                    session_flags: SessionFlags::new(),
+                    buffer: token,
+                },
+            )
+            .session_id(SESSION_ID),
+        MockStep::expect(Command::SessionSetup)
+            .matching("in the session", |r| r.header.session_id == SESSION_ID)
+            .respond(SessionSetupResponse {
+                session_flags: SessionFlags::new().with_is_null_session(true),
+                buffer: vec![],
+            }),
+        MockStep::expect(Command::TreeConnect)
+            .respond(TreeConnectResponse {
+                share_type: ShareType::Disk,
+                share_flags,
+                capabilities: TreeCapabilities::new(),
+                maximal_access: 0x001f01ff,
+            })
+            .tree_id(TREE_ID),
+    ]
+}
+
+/// Connects to [`SHARE`] over a [`MockTransport`], which then follows `steps`.
+#[maybe_async]
+pub async fn connect_mock_tree(
+    share_flags: ShareFlags,
+    steps: impl IntoIterator<Item = MockStep>,
+) -> MockTree {
+    connect_mock_tree_with_config(share_flags, mock_config(), steps).await
+}
+
+/// The configuration of the connections of [`connect_mock_tree`], which [`connect_steps`] negotiate.
+pub fn mock_config() -> ConnectionConfig {
+    ConnectionConfig {
+        timeout: Some(TIMEOUT),
+        min_dialect: Some(Dialect::Smb021),
+        max_dialect: Some(Dialect::Smb021),
+        smb2_only_negotiate: true,
+        allow_unsigned_guest_access: true,
+        ..Default::default()
+    }
+}
+
+/// Just like [`connect_mock_tree`], using the specified connection configuration, e.g. one based on [`mock_config`].
+#[maybe_async]
+pub async fn connect_mock_tree_with_config(
+    share_flags: ShareFlags,
+    config: ConnectionConfig,
+    steps: impl IntoIterator<Item = MockStep>,
+) -> MockTree {
+    let script = MockScript::from_iter(connect_steps(share_flags)).steps(steps);
+    let (transport, handle) = MockTransport::new(script);
+    let connection =
+        Connection::from_transport(Box::new(transport), "server", Guid::generate(), config)
+            .await
+            .unwrap();
+    let session = connection
+        .authenticate(Credentials::Anonymous)
+        .await
+        .unwrap();
+    let tree = session
+        .tree_connect(&SHARE.parse::<UncPath>().unwrap())
+        .await
+        .unwrap();
+    MockTree {
+        handle,
+        tree,
+        _session: session,
+        connection,
+    }
+}
+
+/// Waits until the transport received `count` requests, including the ones of [`connect_steps`].
+#[cfg(feature = "async")]
+pub async fn wait_for_requests(handle: &MockHandle, count: usize) {
+    for _ in 0..500 {
+        if handle.received().len() >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("the transport did not receive {count} requests");
+}
+
+/// Waits until the transport received `count` requests, including the ones of [`connect_steps`].
+#[cfg(feature = "multi_threaded")]
+pub fn wait_for_requests(handle: &MockHandle, count: usize) {
+    for _ in 0..500 {
+        if handle.received().len() >= count {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("the transport did not receive {count} requests");
+}
+
+/// A create response, that opened the file or directory with the specified ID and attributes.
+pub fn create_response(file_id: FileId, file_attributes: FileAttributes) -> CreateResponse {
+    CreateResponse {
+        oplock_level: OplockLevel::None,
+        flags: CreateResponseFlags::new(),
+        create_action: CreateAction::Opened,
+        creation_time: FileTime::default(),
+        last_access_time: FileTime::default(),
+        last_write_time: FileTime::default(),
+        change_time: FileTime::default(),
+        allocation_size: 0,
+        endof_file: 0,
+        file_attributes,
+        file_id,
+        create_contexts: Default::default(),
+    }
+}
+
+/// A close response, with no attributes.
+pub fn close_response() -> CloseResponse {
+    CloseResponse {
+        flags: CloseFlags::new(),
+        creation_time: FileTime::default(),
+        last_access_time: FileTime::default(),
+        last_write_time: FileTime::default(),
+        change_time: FileTime::default(),
+        allocation_size: 0,
+        endof_file: 0,
+        file_attributes: FileAttributes::new(),
+    }
+}
@@ -1,35 +1,59 @@
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 use maybe_async::*;
 use smb_msg::{FileId, FsctlRequest, IoctlRequest, IoctlRequestFlags};
 
 use crate::FileCreateArgs;
-use crate::connection::connection_info::ConnectionInfo;
+use crate::connection::connection_info::{BufferLimit, ConnectionInfo};
+use crate::sync_helpers::OnceCell;
 use smb_fscc::{FileAccessMask, FileAttributes};
 use smb_msg::{
-    CreateOptions, RequestContent, ShareFlags, ShareType,
+    CreateOptions, Dialect, RequestContent, ShareFlags, ShareType, Status, TreeCapabilities,
     create::CreateDisposition,
-    tree_connect::{TreeConnectRequest, TreeDisconnectRequest},
+    tree_connect::{TreeConnectRequest, TreeConnectResponse, TreeDisconnectRequest},
 };
 
 use crate::{
-    Error, Resource,
-    msg_handler::{HandlerReference, MessageHandler},
-    session::SessionMessageHandler,
+    Directory, DurableHandleTicket, Error, File, OpenFileId, Resource,
+    msg_handler::{HandlerReference, MessageHandler, ReceiveOptions},
+    session::{ChannelUpstream, SessionMessageHandler},
 };
 mod dfs_tree;
+mod fs_info;
+mod fs_ops;
+mod fs_statistics;
 mod ipc_tree;
+mod quota;
+mod share_info;
+mod stat;
 use crate::msg_handler::OutgoingMessage;
 pub use dfs_tree::*;
+pub(crate) use fs_ops::dir_path_prefixes;
+pub use fs_statistics::*;
 pub use ipc_tree::*;
+pub use share_info::ShareInfoSnapshot;
+pub(crate) use stat::PathInfo;
 
 type Upstream = HandlerReference<SessionMessageHandler>;
 
 #[derive(Debug, Clone)]
 pub struct TreeConnectInfo {
-    share_type: ShareType,
-    share_flags: ShareFlags,
+    pub(crate) share_type: ShareType,
+    pub(crate) share_flags: ShareFlags,
+    pub(crate) capabilities: TreeCapabilities,
+    pub(crate) maximal_access: u32,
+}
+
+impl From<&TreeConnectResponse> for TreeConnectInfo {
+    fn from(response: &TreeConnectResponse) -> Self {
+        Self {
+            share_type: response.share_type,
+            share_flags: response.share_flags,
+            capabilities: response.capabilities,
+            maximal_access: response.maximal_access,
+        }
+    }
 }
 
 /// Represents an SMB share.
@@ -49,9 +73,21 @@ impl Tree {
         conn_info: &Arc<ConnectionInfo>,
     ) -> crate::Result<Tree> {
         // send and receive tree request & response.
+        let timeout = conn_info.config.tree_connect_timeout();
+        let mut options = ReceiveOptions::new();
+        if !timeout.is_zero() {
+            options = options.with_timeout(timeout);
+        }
         let response = upstream
-            .send_recv(TreeConnectRequest::new(name).into())
-            .await?;
+            .send_recvo(TreeConnectRequest::new(name).into(), options)
+            .await
+            .map_err(|e| match e {
+                Error::OperationTimeout(..) | Error::Timeout(..) => Error::TreeConnectTimeout {
+                    share: name.to_string(),
+                    timeout,
+                },
+                e => e,
+            })?;
 
         let content = response.message.content.to_treeconnect()?;
 
@@ -94,10 +130,7 @@ impl Tree {
 
         log::info!("Connected to tree {name} (#{tree_id})");
 
-        let tree_connect_info = TreeConnectInfo {
-            share_type: content.share_type,
-            share_flags: content.share_flags,
-        };
+        let tree_connect_info = TreeConnectInfo::from(&content);
 
         let t = Tree {
             handler: TreeMessageHandler::new(
@@ -128,6 +161,36 @@ impl Tree {
         let info = self.handler.info()?;
         Resource::create(
             file_name,
+            None,
+            &self.handler,
+            args,
+            &self.conn_info,
+            info.share_type,
+            info.share_flags.dfs(),
+        )
+        .await
+    }
+
+    /// Opens an existing file or directory by its file ID, rather than by its name.
+    /// # Arguments
+    /// * `file_id` - The 64-bit or 128-bit ID of the file, e.g. as listed by [`Directory::query`]. See [`OpenFileId`].
+    /// * `args` - The arguments for the create operation, as in [Tree::create].
+    ///     The [`open_by_file_id`][CreateOptions::open_by_file_id] option is set automatically.
+    /// # Notes
+    /// * The [`name`][crate::resource::ResourceHandle::name] of the opened resource is the formatted ID,
+    ///     and operations that depend on the path of the resource, such as
+    ///     [`rename`][crate::resource::ResourceHandle::rename], fail with [`Error::InvalidState`].
+    /// * Durable handles can not be requested for resources opened by ID.
+    pub async fn open_by_id(
+        &self,
+        file_id: impl Into<OpenFileId>,
+        args: &FileCreateArgs,
+    ) -> crate::Result<Resource> {
+        let file_id = file_id.into();
+        let info = self.handler.info()?;
+        Resource::create(
+            &file_id.to_string(),
+            Some(file_id),
             &self.handler,
             args,
             &self.conn_info,
@@ -152,6 +215,8 @@ impl Tree {
                 options: CreateOptions::new(),
                 desired_access,
                 attributes: FileAttributes::new(),
+                durable: None,
+                snapshot: None,
             },
         )
         .await
@@ -172,6 +237,8 @@ impl Tree {
                 options: CreateOptions::new().with_directory_file(true),
                 desired_access,
                 attributes: FileAttributes::new().with_directory(true),
+                durable: None,
+                snapshot: None,
             },
         )
         .await
@@ -188,6 +255,16 @@ impl Tree {
             .await
     }
 
+    /// Opens the root directory of the share.
+    async fn _open_root(&self, access: FileAccessMask) -> crate::Result<Directory> {
+        match self.open_existing("", access).await? {
+            Resource::Directory(dir) => Ok(dir),
+            _ => Err(Error::InvalidState(
+                "The share root is not a directory".to_string(),
+            )),
+        }
+    }
+
     pub fn is_dfs_root(&self) -> crate::Result<bool> {
         let info = self.handler.info()?;
         Ok(info.share_flags.dfs_root() && info.share_flags.dfs())
@@ -212,6 +289,93 @@ impl Tree {
         IpcTreeRef::new(self)
     }
 
+    /// Reopens a durable handle from a ticket, after its connection was lost.
+    ///
+    /// See [`Client::reclaim_durable`][crate::Client::reclaim_durable] for more information.
+    pub async fn reclaim_durable(&self, ticket: &DurableHandleTicket) -> crate::Result<File> {
+        let info = self.handler.info()?;
+        let resource = Resource::reclaim_durable(
+            ticket,
+            &self.handler,
+            &self.conn_info,
+            info.share_type,
+            info.share_flags.dfs(),
+        )
+        .await?;
+        match resource {
+            Resource::File(file) => Ok(file),
+            _ => Err(Error::InvalidState(format!(
+                "Reclaimed durable handle of '{}' is not a file",
+                ticket.path
+            ))),
+        }
+    }
+
+    /// Forces encrypting all the messages of the tree, or stops forcing it.
+    ///
+    /// Messages of shares that require encryption (see [`ShareFlags::encrypt_data`]) are always encrypted,
+    /// as are all the messages of sessions that require it. Otherwise, messages are only signed, unless
+    /// encryption is forced by this method. Responses to messages of an encrypted tree must be encrypted too.
+    ///
+    /// This also applies to the resources opened from the tree, including those already open.
+    /// # Errors
+    /// * [`Error::UnsupportedOperation`] if forcing encryption on a connection that can not encrypt:
+    ///   the dialect is older than SMB 3.0, no cipher was negotiated, or encryption is disabled by
+    ///   [`ConnectionConfig::encryption_mode`][crate::ConnectionConfig::encryption_mode].
+    /// * [`Error::InvalidArgument`] if stopping to force encryption on a share that requires it.
+    pub fn set_encryption(&self, encrypt: bool) -> crate::Result<()> {
+        let info = self.handler.info()?;
+        if encrypt && !self.can_encrypt() {
+            return Err(Error::UnsupportedOperation(format!(
+                "Encryption of tree {} is not available on this connection",
+                self.handler.tree_name()
+            )));
+        }
+        if !encrypt && info.share_flags.encrypt_data() {
+            return Err(Error::InvalidArgument(format!(
+                "Share {} requires encryption",
+                self.handler.tree_name()
+            )));
+        }
+        self.handler
+            .force_encryption
+            .store(encrypt, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Returns whether all the messages of the tree are encrypted, either because the share requires it,
+    /// or because it was forced by [`Tree::set_encryption`].
+    ///
+    /// Note that messages of other trees may be encrypted as well, if the session requires it.
+    pub fn is_encrypted(&self) -> crate::Result<bool> {
+        self.handler.info()?;
+        Ok(self.handler.encrypts())
+    }
+
+    /// Returns a snapshot of the properties of the share, such as whether it is continuously available,
+    /// and whether its messages are encrypted.
+    pub fn share_info(&self) -> crate::Result<ShareInfoSnapshot> {
+        let info = self.handler.info()?;
+        Ok(ShareInfoSnapshot::new(
+            self.handler.tree_name(),
+            info,
+            self.handler.encrypts(),
+        ))
+    }
+
+    fn can_encrypt(&self) -> bool {
+        let negotiation = &self.conn_info.negotiation;
+        self.conn_info.dialect.supports_encryption()
+            && !self.conn_info.config.encryption_mode.is_disabled()
+            && (negotiation.dialect_rev != Dialect::Smb0311
+                || negotiation.encryption_cipher.is_some())
+    }
+
+    /// Marks the tree as disconnected, without notifying the server.
+    pub(crate) fn abandon(&self) {
+        self.handler.abandon();
+    }
+
     /// Disconnects from the tree (share) on the server.
     ///
     /// After calling this method, none of the resources held open by the tree are accessible.
@@ -220,6 +384,29 @@ impl Tree {
         Ok(())
     }
 
+    /// Validates the negotiation of the connection (MS-SMB2 3.2.5.5), using FSCTL_VALIDATE_NEGOTIATE_INFO.
+    ///
+    /// Servers that fail the request with STATUS_NOT_SUPPORTED or STATUS_FILE_CLOSED do not validate negotiations,
+    /// which is tolerated. Fails with [`Error::NegotiateValidationFailed`] if the server does not confirm the negotiation.
+    #[maybe_async]
+    pub(crate) async fn validate_negotiate(&self) -> crate::Result<()> {
+        let request = match &self.conn_info.client_negotiate {
+            Some(request) => request.clone(),
+            None => return Ok(()),
+        };
+        const RESPONSE_SIZE: u32 = 24;
+        match self.fsctl_with_options(request, RESPONSE_SIZE).await {
+            Ok(response) => self.conn_info.check_negotiate_validation(&response)?,
+            Err(e) if matches!(e.status(), Some(Status::NotSupported | Status::FileClosed)) => {
+                log::warn!("The server does not validate the negotiation: {e}");
+            }
+            Err(e) => return Err(e),
+        }
+        log::debug!("Negotiation validated");
+        self.conn_info.set_negotiate_validated();
+        Ok(())
+    }
+
     // TODO: Make it common with ResourceHandle::fsctl_with_options
     #[maybe_async]
     pub(crate) async fn fsctl_with_options<T: FsctlRequest>(
@@ -228,6 +415,11 @@ impl Tree {
         max_output_response: u32,
     ) -> crate::Result<T::Response> {
         const NO_INPUT_IN_RESPONSE: u32 = 0;
+        let max_output_response = self.conn_info.clamp_or_error(
+            max_output_response as usize,
+            BufferLimit::Transact,
+            self.conn_info.config.buffer_size_policy,
+        )?;
         let response = self
             .handler
             .send_recv(RequestContent::Ioctl(IoctlRequest {
@@ -254,6 +446,12 @@ pub(crate) struct TreeMessageHandler {
 
     tree_name: String,
     info: TreeConnectInfo,
+    /// Whether to encrypt all the messages of the tree, even if the share does not require it.
+    /// See [`Tree::set_encryption`].
+    force_encryption: AtomicBool,
+    /// Whether the file system of the share supports POSIX delete semantics,
+    /// queried on the first removal through [`Tree::remove_file`] or [`Tree::remove_dir`].
+    posix_unlink: OnceCell<bool>,
 }
 
 impl TreeMessageHandler {
@@ -270,6 +468,8 @@ impl TreeMessageHandler {
             upstream: upstream.clone(),
             info,
             tree_name,
+            force_encryption: AtomicBool::new(false),
+            posix_unlink: OnceCell::new(),
         })
     }
 
@@ -292,10 +492,50 @@ impl TreeMessageHandler {
             // Already disconnected
             return Ok(());
         }
-        let encrypt = self.info.share_flags.encrypt_data();
+        let encrypt = self.encrypts();
         Self::_disconnect(self.upstream.clone(), tree_id, encrypt).await
     }
 
+    /// Returns the connection of the session of the tree.
+    pub fn connection(&self) -> &ChannelUpstream {
+        self.upstream.connection()
+    }
+
+    /// Returns the `\\server\share` name of the tree.
+    pub fn tree_name(&self) -> &str {
+        &self.tree_name
+    }
+
+    /// Returns whether the file system of the share supports POSIX delete semantics, if already known.
+    pub fn posix_unlink(&self) -> &OnceCell<bool> {
+        &self.posix_unlink
+    }
+
+    /// Marks the tree as disconnected, without sending a tree disconnect request.
+    pub fn abandon(&self) {
+        self.tree_id.store(Self::INVALID_TREE_ID, Ordering::SeqCst);
+    }
+
+    /// Returns whether the messages of the tree are encrypted,
+    /// because the share requires it or it was forced by [`Tree::set_encryption`].
+    fn encrypts(&self) -> bool {
+        self.info.share_flags.encrypt_data() || self.force_encryption.load(Ordering::SeqCst)
+    }
+
+    fn prepare_outgoing(&self, msg: &mut OutgoingMessage) {
+        Self::prepare_message(msg, self.tree_id.load(Ordering::SeqCst), self.encrypts());
+    }
+
+    /// Sets the tree ID of an outgoing message, and requests encryption if the tree is encrypted.
+    fn prepare_message(msg: &mut OutgoingMessage, tree_id: u32, encrypt: bool) {
+        if !msg.message.header.flags.async_command() {
+            msg.message.header.tree_id = tree_id.into();
+            if encrypt {
+                msg.encrypt = true;
+            }
+        }
+    }
+
     pub fn info(&self) -> crate::Result<&TreeConnectInfo> {
         if self.tree_id.load(Ordering::Relaxed) == Self::INVALID_TREE_ID {
             return Err(Error::InvalidState("Tree is closed".to_string()));
@@ -311,16 +551,19 @@ impl MessageHandler for TreeMessageHandler {
         &self,
         mut msg: crate::msg_handler::OutgoingMessage,
     ) -> crate::Result<crate::msg_handler::SendMessageResult> {
-        if !msg.message.header.flags.async_command() {
-            msg.message.header.tree_id = self.tree_id.load(Ordering::SeqCst).into();
-            if self.info.share_flags.encrypt_data() {
-                msg.encrypt = true;
-            }
-        }
-
+        self.prepare_outgoing(&mut msg);
         self.upstream.sendo(msg).await
     }
 
+    #[maybe_async]
+    async fn sendo_compound(
+        &self,
+        mut msgs: Vec<crate::msg_handler::OutgoingMessage>,
+    ) -> crate::Result<Vec<crate::msg_handler::SendMessageResult>> {
+        msgs.iter_mut().for_each(|msg| self.prepare_outgoing(msg));
+        self.upstream.sendo_compound(msgs).await
+    }
+
     #[maybe_async]
     async fn recvo(
         &self,
@@ -336,10 +579,11 @@ impl MessageHandler for TreeMessageHandler {
             ));
         }
 
-        // Make sure encryption is enforced if the share requires it.
-        if !msg.form.encrypted && self.info()?.share_flags.encrypt_data() {
+        // Make sure encryption is enforced if the tree is encrypted.
+        self.info()?;
+        if !msg.form.encrypted && self.encrypts() {
             return Err(Error::InvalidMessage(
-                "Received unencrypted message on encrypted share".to_string(),
+                "Received unencrypted message on encrypted tree".to_string(),
             ));
         }
 
@@ -370,7 +614,7 @@ impl Drop for TreeMessageHandler {
 
         let upstream = self.upstream.clone();
         let tree_name = self.tree_name.clone();
-        let encrypt = self.info.share_flags.encrypt_data();
+        let encrypt = self.encrypts();
         tokio::task::spawn(async move {
             Self::_disconnect(upstream, tree_id, encrypt)
                 .await
@@ -381,3 +625,141 @@ impl Drop for TreeMessageHandler {
         });
     }
 }
+
+#[cfg(test)]
+mod connect_tests {
+    use std::time::Duration;
+
+    use smb_dtyp::Guid;
+    use smb_msg::ShareFlags;
+    use smb_transport::mock::{MockScript, MockTransport};
+
+    use crate::test_util::*;
+    use crate::{Connection, ConnectionConfig, Credentials, Error, UncPath};
+
+    /// The server does not respond to the tree connect request until after the tree connect timeout.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_tree_connect_timeout() {
+        let mut steps = connect_steps(ShareFlags::new());
+        let tree_connect = steps.pop().unwrap().delay(Duration::from_secs(2));
+        let script = MockScript::from_iter(steps).step(tree_connect);
+        let (transport, _handle) = MockTransport::new(script);
+        let config = ConnectionConfig {
+            tree_connect_timeout: Some(Duration::from_millis(200)),
+            ..mock_config()
+        };
+        let connection =
+            Connection::from_transport(Box::new(transport), "server", Guid::generate(), config)
+                .await
+                .unwrap();
+        let session = connection
+            .authenticate(Credentials::Anonymous)
+            .await
+            .unwrap();
+
+        let result = session
+            .tree_connect(&SHARE.parse::<UncPath>().unwrap())
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::TreeConnectTimeout { share, .. }) if share == SHARE
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "encrypt_aes128ccm"))]
+mod tests {
+    use std::sync::Arc;
+
+    use smb_dtyp::Guid;
+    use smb_msg::{Dialect, EchoRequest, GlobalCapabilities, NegotiateSecurityMode, SessionFlags};
+
+    use super::TreeMessageHandler;
+    use crate::ConnectionConfig;
+    use crate::connection::connection_info::{ConnectionInfo, NegotiatedProperties};
+    use crate::connection::preauth_hash::PreauthHashState;
+    use crate::connection::quirks::{QuirksRegistry, ServerFingerprint};
+    use crate::connection::transformer::Transformer;
+    use crate::dialects::DialectImpl;
+    use crate::msg_handler::OutgoingMessage;
+    use crate::session::{SessionAndChannel, SessionInfo};
+    use crate::sync_helpers::*;
+
+    const SESSION_ID: u64 = 0x1234;
+
+    fn make_connection_info() -> ConnectionInfo {
+        let dialect = Dialect::Smb0302;
+        let negotiation = NegotiatedProperties {
+            server_guid: Guid::ZERO,
+            caps: GlobalCapabilities::new().with_encryption(true),
+            security_mode: NegotiateSecurityMode::new().with_signing_enabled(true),
+            max_transact_size: 0x10000,
+            max_read_size: 0x10000,
+            max_write_size: 0x10000,
+            auth_buffer: vec![],
+            signing_algo: None,
+            encryption_cipher: None,
+            compression: None,
+            preauth_integrity: None,
+            dialect_rev: dialect,
+        };
+        let config = ConnectionConfig::default();
+        let quirks = QuirksRegistry::new(
+            &ServerFingerprint {
+                server_guid: negotiation.server_guid,
+                dialect,
+                auth_buffer: &negotiation.auth_buffer,
+            },
+            config.quirks,
+        );
+        ConnectionInfo {
+            server_name: "server".to_string(),
+            server_address: "127.0.0.1:445".parse().unwrap(),
+            transport: "tcp",
+            negotiation,
+            dialect: DialectImpl::new(dialect),
+            config,
+            preauth_hash: PreauthHashState::unsupported(),
+            client_guid: Guid::ZERO,
+            client_negotiate: None,
+            negotiate_validated: Default::default(),
+            quirks: Arc::new(quirks),
+        }
+    }
+
+    /// Returns a transformer of a negotiated connection, with a ready session that does not require encryption.
+    #[maybe_async::maybe_async]
+    async fn make_transformer() -> Transformer {
+        let info = make_connection_info();
+        let mut session = SessionInfo::new(SESSION_ID);
+        session.setup(&[0x11; 16], &None, &info).unwrap();
+        session.ready(SessionFlags::new(), &info).unwrap();
+        let session = SessionAndChannel::new(SESSION_ID, Arc::new(RwLock::new(session)));
+
+        let transformer = Transformer::default();
+        transformer.negotiated(&info).await.unwrap();
+        transformer
+            .session_started(&Arc::new(RwLock::new(session)))
+            .await
+            .unwrap();
+        transformer
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_encryption_per_tree() {
+        let transformer = make_transformer().await;
+        // Two trees of the same session, where only the second one is encrypted.
+        for (tree_id, encrypt) in [(1, false), (2, true), (1, false), (2, true)] {
+            let mut msg = OutgoingMessage::new(EchoRequest::default().into());
+            msg.message.header.session_id = SESSION_ID;
+            TreeMessageHandler::prepare_message(&mut msg, tree_id, encrypt);
+            assert_eq!(msg.message.header.tree_id, Some(tree_id));
+            assert_eq!(msg.encrypt, encrypt);
+
+            let mut data = transformer.transform_outgoing(msg).await.unwrap();
+            let data = data.consolidate();
+            let expected_protocol_id: &[u8] = if encrypt { b"\xfdSMB" } else { b"\xfeSMB" };
+            assert_eq!(&data[..4], expected_protocol_id);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::ops::Deref;
 
-use crate::msg_handler::{MessageHandler, ReceiveOptions};
+use crate::msg_handler::MessageHandler;
 use maybe_async::*;
 use smb_msg::{FileId, FsctlCodes, IoctlReqData, IoctlRequest, IoctlRequestFlags, dfsc::*};
 
@@ -26,22 +26,58 @@ impl<'a> DfsRootTreeRef<'a> {
     /// See [MS-DFSC](<https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-dfsc/04657125-a7d5-4c62-9bec-85af601fa14c>) for more information.
     #[maybe_async]
     pub async fn dfs_get_referrals(&self, path: &str) -> crate::Result<RespGetDfsReferral> {
+        self._get_referrals(
+            FsctlCodes::DfsGetReferrals,
+            IoctlReqData::FsctlDfsGetReferrals(ReqGetDfsReferral {
+                max_referral_level: ReferralLevel::V4,
+                request_file_name: path.into(),
+            }),
+        )
+        .await
+    }
+
+    /// Performs an extended DFS referral request to the server, which carries the site name of the client:
+    /// the server orders the targets of the referral by their cost from that site,
+    /// instead of from the site of the address of the client.
+    ///
+    /// The response is the same as of [`DfsRootTreeRef::dfs_get_referrals`].
+    #[maybe_async]
+    pub async fn dfs_get_referrals_ex(
+        &self,
+        path: &str,
+        site_name: Option<&str>,
+    ) -> crate::Result<RespGetDfsReferral> {
+        self._get_referrals(
+            FsctlCodes::DfsGetReferralsEx,
+            IoctlReqData::FsctlDfsGetReferralsEx(ReqGetDfsReferralEx {
+                max_referral_level: ReferralLevel::V4,
+                request_data: DfsRequestData {
+                    request_file_name: path.into(),
+                    site_name: site_name.map(Into::into),
+                },
+            }),
+        )
+        .await
+    }
+
+    #[maybe_async]
+    async fn _get_referrals(
+        &self,
+        ctl_code: FsctlCodes,
+        buffer: IoctlReqData,
+    ) -> crate::Result<RespGetDfsReferral> {
         let res = self
             .handler
-            .send_recvo(
+            .send_recv(
                 IoctlRequest {
-                    ctl_code: FsctlCodes::DfsGetReferrals as u32,
+                    ctl_code: ctl_code as u32,
                     file_id: FileId::FULL,
                     max_input_response: 1024,
                     max_output_response: 1024,
                     flags: IoctlRequestFlags::new().with_is_fsctl(true),
-                    buffer: IoctlReqData::FsctlDfsGetReferrals(ReqGetDfsReferral {
-                        max_referral_level: ReferralLevel::V4,
-                        request_file_name: path.into(),
-                    }),
+                    buffer,
                 }
                 .into(),
-                ReceiveOptions::new().with_allow_async(true),
             )
             .await?;
         let res = res
@@ -60,3 +96,151 @@ impl<'a> Deref for DfsRootTreeRef<'a> {
         self.tree
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+    use smb_msg::{
+        Command, FileId, FsctlCodes, IoctlReqData, IoctlResponse, RespGetDfsReferral, ShareFlags,
+    };
+    use smb_transport::mock::MockStep;
+
+    const DOCS_PATH: &str = r"\server\share\Docs";
+
+    /// The referral response of the DFSC tests, with two V4 targets of `\ADC.aviv.local\dfs\Docs`.
+    const DOCS_REFERRAL: &str = "300002000200000004002200000004000807000044007600a8000000000000000000000000000000000004002200000000000807000022005400a
+        800000000000000000000000000000000005c004100440043002e0061007600690076002e006c006f00630061006c005c006400660073005c0044006f00
+        6300730000005c004100440043002e0061007600690076002e006c006f00630061006c005c006400660073005c0044006f006300730000005c004100440
+        043005c005300680061007200650073005c0044006f006300730000005c0046005300520056005c005300680061007200650073005c004d007900530068
+        006100720065000000";
+
+    fn dfs_root_flags() -> ShareFlags {
+        ShareFlags::new().with_dfs(true).with_dfs_root(true)
+    }
+
+    /// The step of a DFS referral request of `path`.
+    fn referral_step(path: &'static str) -> MockStep {
+        MockStep::expect(Command::Ioctl).matching(path, move |r| {
+            r.content.as_ioctl().is_ok_and(|ioctl| {
+                ioctl.ctl_code == FsctlCodes::DfsGetReferrals as u32
+                    && matches!(&ioctl.buffer, IoctlReqData::FsctlDfsGetReferrals(req)
+                        if req.request_file_name.to_string() == path)
+            })
+        })
+    }
+
+    /// The step of an extended DFS referral request of `path`, from `site_name`.
+    fn referral_ex_step(path: &'static str, site_name: Option<&'static str>) -> MockStep {
+        MockStep::expect(Command::Ioctl).matching(path, move |r| {
+            r.content.as_ioctl().is_ok_and(|ioctl| {
+                ioctl.ctl_code == FsctlCodes::DfsGetReferralsEx as u32
+                    && matches!(&ioctl.buffer, IoctlReqData::FsctlDfsGetReferralsEx(req)
+                        if req.request_data.request_file_name == path
+                            && req.request_data.site_name.as_ref().map(|s| s.to_string()).as_deref() == site_name)
+            })
+        })
+    }
+
+    fn docs_referral_response() -> IoctlResponse {
+        IoctlResponse {
+            ctl_code: FsctlCodes::DfsGetReferrals as u32,
+            file_id: FileId::FULL,
+            in_buffer: vec![],
+            out_buffer: smb_tests::hex_to_u8_array! {DOCS_REFERRAL},
+        }
+    }
+
+    fn assert_docs_referral(referral: &RespGetDfsReferral) {
+        assert_eq!(referral.path_consumed, 48);
+        assert_eq!(referral.referral_entries.len(), 2);
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_dfs_get_referrals() {
+        let mock = connect_mock_tree(
+            dfs_root_flags(),
+            [referral_step(DOCS_PATH).respond(docs_referral_response())],
+        )
+        .await;
+        let referral = mock
+            .tree
+            .as_dfs_tree()
+            .unwrap()
+            .dfs_get_referrals(DOCS_PATH)
+            .await
+            .unwrap();
+        assert_docs_referral(&referral);
+        mock.handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_dfs_get_referrals_ex() {
+        const SITE_NAME: &str = "Default-First-Site-Name";
+        let mock = connect_mock_tree(
+            dfs_root_flags(),
+            [
+                referral_ex_step(DOCS_PATH, Some(SITE_NAME)).respond(docs_referral_response()),
+                referral_ex_step(DOCS_PATH, None).respond(docs_referral_response()),
+            ],
+        )
+        .await;
+        let dfs = mock.tree.as_dfs_tree().unwrap();
+        let referral = dfs
+            .dfs_get_referrals_ex(DOCS_PATH, Some(SITE_NAME))
+            .await
+            .unwrap();
+        assert_docs_referral(&referral);
+        let referral = dfs.dfs_get_referrals_ex(DOCS_PATH, None).await.unwrap();
+        assert_docs_referral(&referral);
+        mock.handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_dfs_get_referrals_not_dfs_root() {
+        let mock = connect_mock_tree(ShareFlags::new(), []).await;
+        assert!(mock.tree.as_dfs_tree().is_err());
+        mock.handle.assert_done();
+    }
+
+    /// The response of the first request is delivered after the response of the second one.
+    #[cfg(not(feature = "single_threaded"))]
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_dfs_get_referrals_out_of_order() {
+        use smb_msg::Status;
+        const MISSING_PATH: &str = r"\server\share\Missing";
+
+        let mock = connect_mock_tree(
+            dfs_root_flags(),
+            [
+                referral_step(DOCS_PATH)
+                    .respond(docs_referral_response())
+                    .deferred(),
+                referral_step(MISSING_PATH).respond_error(Status::ObjectPathNotFound),
+            ],
+        )
+        .await;
+        let dfs = mock.tree.as_dfs_tree().unwrap();
+        // The first request is sent once the tree is connected.
+        let first_request = connect_steps(dfs_root_flags()).len() + 1;
+
+        #[cfg(feature = "async")]
+        let (docs, missing) = futures_util::join!(dfs.dfs_get_referrals(DOCS_PATH), async {
+            wait_for_requests(&mock.handle, first_request).await;
+            dfs.dfs_get_referrals(MISSING_PATH).await
+        });
+        #[cfg(not(feature = "async"))]
+        let (docs, missing) = std::thread::scope(|s| {
+            let docs = s.spawn(|| dfs.dfs_get_referrals(DOCS_PATH));
+            wait_for_requests(&mock.handle, first_request);
+            let missing = dfs.dfs_get_referrals(MISSING_PATH);
+            (docs.join().unwrap(), missing)
+        });
+
+        assert_docs_referral(&docs.unwrap());
+        assert_eq!(
+            missing.unwrap_err().status(),
+            Some(Status::ObjectPathNotFound)
+        );
+        mock.handle.assert_done();
+    }
+}
@@ -0,0 +1,31 @@
+//! File system information of a share's volume, see [`Tree::query_fs_info`].
+
+use maybe_async::*;
+use smb_fscc::{FileAccessMask, QueryFileSystemInfoValue};
+
+use super::Tree;
+
+#[maybe_async(AFIT)]
+impl Tree {
+    /// Queries file system information of the share's volume, such as its size and free space
+    /// ([`FileFsFullSizeInformation`][smb_fscc::FileFsFullSizeInformation]), its label and serial number
+    /// ([`FileFsVolumeInformation`][smb_fscc::FileFsVolumeInformation]), or the file system's name and capabilities
+    /// ([`FileFsAttributeInformation`][smb_fscc::FileFsAttributeInformation]).
+    ///
+    /// The root directory of the share is opened for the query, and closed right after it.
+    /// To query an already open file, use [`ResourceHandle::query_fs_info`][crate::ResourceHandle::query_fs_info].
+    ///
+    /// ## Notes
+    /// * Sizes are reported in allocation units. Servers may limit them to the quota of the user.
+    pub async fn query_fs_info<T>(&self) -> crate::Result<T>
+    where
+        T: QueryFileSystemInfoValue,
+    {
+        let root = self
+            ._open_root(FileAccessMask::new().with_file_read_attributes(true))
+            .await?;
+        let result = root.query_fs_info::<T>().await;
+        root.close().await?;
+        result
+    }
+}
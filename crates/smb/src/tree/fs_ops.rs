@@ -0,0 +1,237 @@
+//! Path-based file system operations, similar to those of [`std::fs`]:
+//! checking whether paths exist, querying their metadata, and creating and removing files and directories.
+//!
+//! Each operation opens the path, performs the operation, and closes the opened handle, even if the operation failed.
+//! See the [`Client`][crate::Client] counterparts, which take UNC paths, and resolve DFS paths.
+
+use maybe_async::*;
+use smb_fscc::{
+    FileAccessMask, FileAllInformation, FileAttributes, FileDispositionFlags,
+    FileDispositionInformation, FileFsAttributeInformation,
+};
+use smb_msg::{CreateDisposition, CreateOptions, Status};
+
+use super::Tree;
+use crate::{Error, FileCreateArgs, Resource, ResourceHandle};
+
+impl FileCreateArgs {
+    /// Returns arguments for opening an existing path, to query its attributes.
+    pub(crate) fn make_read_attributes() -> FileCreateArgs {
+        FileCreateArgs::make_open_existing(
+            FileAccessMask::new()
+                .with_file_read_attributes(true)
+                .with_synchronize(true),
+        )
+    }
+
+    /// Returns arguments for opening an existing file (or directory, if `directory` is set), to delete it.
+    ///
+    /// Reparse points (e.g. symbolic links) are opened, rather than followed, so the link itself is deleted.
+    pub(crate) fn make_remove(directory: bool) -> FileCreateArgs {
+        FileCreateArgs {
+            options: CreateOptions::new()
+                .with_directory_file(directory)
+                .with_non_directory_file(!directory)
+                .with_open_reparse_point(true),
+            ..FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_delete(true)
+                    .with_synchronize(true),
+            )
+        }
+    }
+
+    /// Returns arguments for creating a directory. If `open_existing` is set, an existing directory is opened instead.
+    pub(crate) fn make_create_dir(open_existing: bool) -> FileCreateArgs {
+        FileCreateArgs {
+            disposition: match open_existing {
+                true => CreateDisposition::OpenIf,
+                false => CreateDisposition::Create,
+            },
+            attributes: FileAttributes::new().with_directory(true),
+            options: CreateOptions::new().with_directory_file(true),
+            desired_access: FileAccessMask::new()
+                .with_file_read_attributes(true)
+                .with_synchronize(true),
+            durable: None,
+            snapshot: None,
+        }
+    }
+}
+
+#[maybe_async(AFIT)]
+impl Resource {
+    /// Queries [`FileAllInformation`] of the resource, and closes it.
+    pub(crate) async fn query_all_and_close(self) -> crate::Result<FileAllInformation> {
+        let handle = self.handle();
+        let info = handle.query_info::<FileAllInformation>().await;
+        handle.close().await?;
+        info
+    }
+
+    /// Deletes the resource, which was opened as `path`, and closes it.
+    ///
+    /// Fails with [`Error::DirectoryNotEmpty`] if the resource is a directory that is not empty.
+    pub(crate) async fn delete_and_close(self, path: &str) -> crate::Result<()> {
+        let handle = self.handle();
+        let deleted = Self::_delete(handle).await.map_err(|e| match e {
+            Error::ServerError(Status::DirectoryNotEmpty, _) => Error::DirectoryNotEmpty {
+                path: path.to_string(),
+            },
+            e => e,
+        });
+        handle.close().await?;
+        deleted
+    }
+
+    /// Marks the resource for deletion.
+    ///
+    /// POSIX semantics are used when the file system supports them, so the name is removed
+    /// once the handle is closed, even if other handles are still open.
+    /// Otherwise, or if the server rejects [`FileDispositionInformationEx`][smb_fscc::FileDispositionInformationEx],
+    /// the resource is deleted when its last handle is closed.
+    async fn _delete(handle: &ResourceHandle) -> crate::Result<()> {
+        if Self::_supports_posix_unlink(handle).await {
+            let flags = FileDispositionFlags::new()
+                .with_delete(true)
+                .with_posix_semantics(true);
+            match handle.delete_on_close_ex(flags).await {
+                Err(Error::ServerError(
+                    status @ (Status::InvalidInfoClass
+                    | Status::InvalidParameter
+                    | Status::NotSupported),
+                    _,
+                )) => {
+                    log::debug!(
+                        "POSIX delete of {} failed with {status}, falling back to classic delete",
+                        handle.name()
+                    );
+                }
+                result => return result,
+            }
+        }
+        handle.set_info(FileDispositionInformation::default()).await
+    }
+
+    /// Returns whether the file system of the share supports POSIX delete semantics.
+    ///
+    /// [`FileFsAttributeInformation`] is queried through `handle` the first time, and cached in the tree.
+    async fn _supports_posix_unlink(handle: &ResourceHandle) -> bool {
+        let cached = handle.tree().posix_unlink();
+        if let Some(supported) = cached.get() {
+            return *supported;
+        }
+        let supported = match handle.query_fs_info::<FileFsAttributeInformation>().await {
+            Ok(info) => info.attributes.supports_posix_unlink_rename(),
+            Err(e) => {
+                log::debug!(
+                    "Failed to query file system attributes, assuming no POSIX delete: {e}"
+                );
+                false
+            }
+        };
+        // Concurrent removals may race to set it, with the same value.
+        let _ = cached.set(supported);
+        supported
+    }
+
+    /// Closes the resource, discarding it.
+    pub(crate) async fn close_handle(self) -> crate::Result<()> {
+        self.handle().close().await
+    }
+}
+
+#[maybe_async(AFIT)]
+impl Tree {
+    /// Returns whether the specified path exists in the share.
+    ///
+    /// The path is opened with [`FileAccessMask::file_read_attributes`] access only, and closed right away.
+    pub async fn exists(&self, path: &str) -> crate::Result<bool> {
+        match self
+            .create(path, &FileCreateArgs::make_read_attributes())
+            .await
+        {
+            Ok(resource) => resource.close_handle().await.map(|_| true),
+            Err(e) if e.is_not_found() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the metadata of the specified file or directory.
+    ///
+    /// The path is opened with [`FileAccessMask::file_read_attributes`] access only,
+    /// so it does not conflict with the share mode of other opens.
+    pub async fn metadata(&self, path: &str) -> crate::Result<FileAllInformation> {
+        self.create(path, &FileCreateArgs::make_read_attributes())
+            .await?
+            .query_all_and_close()
+            .await
+    }
+
+    /// Deletes the specified file. Fails if the path is a directory.
+    ///
+    /// If the file is a symbolic link, the link is deleted, rather than its target.
+    pub async fn remove_file(&self, path: &str) -> crate::Result<()> {
+        self.create(path, &FileCreateArgs::make_remove(false))
+            .await?
+            .delete_and_close(path)
+            .await
+    }
+
+    /// Deletes the specified empty directory. Fails if the path is not a directory.
+    ///
+    /// Fails with [`Error::DirectoryNotEmpty`] if the directory is not empty.
+    pub async fn remove_dir(&self, path: &str) -> crate::Result<()> {
+        self.create(path, &FileCreateArgs::make_remove(true))
+            .await?
+            .delete_and_close(path)
+            .await
+    }
+
+    /// Creates the specified directory. Its parent directory must exist.
+    ///
+    /// Fails with `STATUS_OBJECT_NAME_COLLISION` if the path already exists.
+    pub async fn create_dir(&self, path: &str) -> crate::Result<()> {
+        self.create(path, &FileCreateArgs::make_create_dir(false))
+            .await?
+            .close_handle()
+            .await
+    }
+
+    /// Creates the specified directory, and all of its missing parent directories.
+    ///
+    /// Existing directories in the path, including the directory itself, are left as is.
+    pub async fn create_dir_all(&self, path: &str) -> crate::Result<()> {
+        for dir in dir_path_prefixes(path) {
+            self.create(dir, &FileCreateArgs::make_create_dir(true))
+                .await?
+                .close_handle()
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the paths of the directories leading to `path`, including itself, from the share root down.
+///
+/// For example, `a`, `a\b` and `a\b\c` for `a\b\c`. Empty components (e.g. a trailing backslash) are skipped.
+pub(crate) fn dir_path_prefixes(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('\\')
+        .map(|(i, _)| &path[..i])
+        .chain(std::iter::once(path))
+        .filter(|prefix| !prefix.is_empty() && !prefix.ends_with('\\'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dir_path_prefixes;
+
+    #[test]
+    fn test_dir_path_prefixes() {
+        let prefixes = |path| dir_path_prefixes(path).collect::<Vec<_>>();
+        assert_eq!(prefixes(r"a\b\c"), [r"a", r"a\b", r"a\b\c"]);
+        assert_eq!(prefixes(r"a\\b\"), [r"a", r"a\\b"]);
+        assert_eq!(prefixes("dir"), ["dir"]);
+        assert!(prefixes("").is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+//! File system statistics of a share's volume, see [`Tree::filesystem_statistics`].
+
+use maybe_async::*;
+use smb_fscc::FileAccessMask;
+use smb_msg::{
+    FileSystemGetStatisticsExRequest, FileSystemGetStatisticsRequest, FileSystemStatistics,
+    FileSystemStatisticsEx, Status,
+};
+
+use super::Tree;
+use crate::{Directory, Error};
+
+/// The per-processor statistics of a volume, as returned by the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessorStatistics {
+    /// Returned by `FSCTL_FILESYSTEM_GET_STATISTICS_EX`.
+    Ex(Vec<FileSystemStatisticsEx>),
+    /// Returned by `FSCTL_FILESYSTEM_GET_STATISTICS`, for servers that do not support the extended version.
+    Legacy(Vec<FileSystemStatistics>),
+}
+
+/// The file system statistics of a volume, see [`Tree::filesystem_statistics`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSystemStatisticsReport {
+    /// The statistics, summed over all processors.
+    ///
+    /// Legacy statistics are widened to the extended structure, with the counters that only exist in it set to 0.
+    pub totals: FileSystemStatisticsEx,
+    /// The statistics of each processor.
+    pub per_processor: ProcessorStatistics,
+}
+
+#[maybe_async(AFIT)]
+impl Tree {
+    /// Returns the file system statistics of the share's volume, such as user and metadata I/O counters,
+    /// and NTFS specific counters (MFT, bitmap and log file I/O).
+    ///
+    /// `FSCTL_FILESYSTEM_GET_STATISTICS_EX` is tried first, falling back to `FSCTL_FILESYSTEM_GET_STATISTICS`
+    /// if the server does not support it.
+    ///
+    /// ## Notes
+    /// * The output of the FSCTLs has an entry per processor of the server, so it is bounded by the
+    ///   default transaction size (see [`ConnectionConfig::default_transaction_size`][crate::ConnectionConfig::default_transaction_size]).
+    /// * Samba does not implement these FSCTLs.
+    pub async fn filesystem_statistics(&self) -> crate::Result<FileSystemStatisticsReport> {
+        let root = self
+            ._open_root(FileAccessMask::new().with_file_read_attributes(true))
+            .await?;
+        let result = self._query_statistics(&root).await;
+        root.close().await?;
+
+        let per_processor = result?;
+        let totals = match &per_processor {
+            ProcessorStatistics::Ex(processors) => FileSystemStatisticsEx::sum(processors.iter()),
+            ProcessorStatistics::Legacy(processors) => {
+                let processors = processors
+                    .iter()
+                    .map(FileSystemStatisticsEx::from)
+                    .collect::<Vec<_>>();
+                FileSystemStatisticsEx::sum(processors.iter())
+            }
+        }
+        .ok_or_else(|| {
+            Error::InvalidMessage("The server returned no file system statistics".to_string())
+        })?;
+        Ok(FileSystemStatisticsReport {
+            totals,
+            per_processor,
+        })
+    }
+
+    async fn _query_statistics(&self, root: &Directory) -> crate::Result<ProcessorStatistics> {
+        let max_output_response = self
+            .conn_info
+            .negotiation
+            .max_transact_size
+            .min(self.conn_info.config.default_transaction_size());
+        match root
+            .fsctl_with_options(FileSystemGetStatisticsExRequest(()), max_output_response)
+            .await
+        {
+            Ok(response) => return Ok(ProcessorStatistics::Ex(response.processors)),
+            Err(Error::ServerError(
+                Status::InvalidDeviceRequest0
+                | Status::NotSupported
+                | Status::NotImplemented
+                | Status::InvalidParameter,
+                _,
+            )) => {
+                log::debug!("Extended file system statistics are not supported, falling back");
+            }
+            Err(e) => return Err(e),
+        }
+
+        let response = root
+            .fsctl_with_options(FileSystemGetStatisticsRequest(()), max_output_response)
+            .await?;
+        Ok(ProcessorStatistics::Legacy(response.processors))
+    }
+}
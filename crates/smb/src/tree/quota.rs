@@ -0,0 +1,86 @@
+//! Typed access to the per-user quotas of a share's volume.
+
+use maybe_async::*;
+use smb_dtyp::SID;
+use smb_fscc::{FileAccessMask, FileFsControlInformation, FileQuotaInformation, QuotaEntry};
+use smb_msg::QueryQuotaInfo;
+
+use super::Tree;
+use crate::Directory;
+
+#[maybe_async(AFIT)]
+impl Tree {
+    /// Returns the quota entry of the specified SID, or `None` if the volume has no entry for it.
+    pub async fn get_quota(&self, sid: &SID) -> crate::Result<Option<QuotaEntry>> {
+        let root = self._open_quota_root(false).await?;
+        let info = QueryQuotaInfo::for_sids([sid.clone()]).with_return_single(true);
+        let result = root.query_quota_page(info).await;
+        root.close().await?;
+        Ok(result?.into_iter().next().map(QuotaEntry::from))
+    }
+
+    /// Sets the quota entry of the entry's SID, creating it if it does not exist.
+    ///
+    /// Only the [`threshold`][QuotaEntry::threshold] and [`limit`][QuotaEntry::limit] of the entry are
+    /// used by the server.
+    pub async fn set_quota(&self, entry: &QuotaEntry) -> crate::Result<()> {
+        let root = self._open_quota_root(true).await?;
+        let result = root
+            .set_quota_entries(&[FileQuotaInformation::from(entry.clone())])
+            .await;
+        root.close().await?;
+        result
+    }
+
+    /// Returns all the quota entries of the volume.
+    pub async fn list_quotas(&self) -> crate::Result<Vec<QuotaEntry>> {
+        let root = self._open_quota_root(false).await?;
+        let result = Self::_list_quotas(&root).await;
+        root.close().await?;
+        result
+    }
+
+    /// Sets the default quota threshold and limit of the volume,
+    /// which apply to users that have no quota entry of their own.
+    ///
+    /// Use [`QuotaEntry::UNLIMITED`] to clear either value.
+    pub async fn set_default_quota(&self, threshold: u64, limit: u64) -> crate::Result<()> {
+        let root = self._open_quota_root(true).await?;
+        let result = Self::_set_default_quota(&root, threshold, limit).await;
+        root.close().await?;
+        result
+    }
+
+    #[cfg(feature = "async")]
+    async fn _list_quotas(root: &Directory) -> crate::Result<Vec<QuotaEntry>> {
+        use futures_util::TryStreamExt;
+        root.iter_quota()
+            .map_ok(QuotaEntry::from)
+            .try_collect()
+            .await
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn _list_quotas(root: &Directory) -> crate::Result<Vec<QuotaEntry>> {
+        root.iter_quota()
+            .map(|entry| entry.map(QuotaEntry::from))
+            .collect()
+    }
+
+    async fn _set_default_quota(root: &Directory, threshold: u64, limit: u64) -> crate::Result<()> {
+        let mut control = root.query_fs_info::<FileFsControlInformation>().await?;
+        control.default_quota_threshold = threshold;
+        control.default_quota_limit = limit;
+        root.set_filesystem_info(control).await
+    }
+
+    /// Opens the root directory of the share, which holds the quota information of the volume.
+    async fn _open_quota_root(&self, write: bool) -> crate::Result<Directory> {
+        let access = if write {
+            FileAccessMask::standard_rw()
+        } else {
+            FileAccessMask::standard_read()
+        };
+        self._open_root(access).await
+    }
+}
@@ -0,0 +1,120 @@
+//! [`ShareInfoSnapshot`], the properties of a connected share, returned by [`Tree::share_info`][super::Tree::share_info].
+
+use smb_msg::{ShareCacheMode, ShareType};
+
+#[cfg(feature = "serde")]
+use crate::connection::connection_info::serde_fmt;
+
+use super::TreeConnectInfo;
+
+/// A snapshot of the properties of a share, as returned by the server when connecting to it, for diagnostic purposes.
+///
+/// Returned by [`Tree::share_info`][super::Tree::share_info].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ShareInfoSnapshot {
+    /// The `\\server\share` name of the tree.
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serde_fmt::debug"))]
+    pub share_type: ShareType,
+    /// Whether the share is in a DFS namespace.
+    pub dfs: bool,
+    /// Whether the share is continuously available, so the server may grant persistent handles on it.
+    pub continuously_available: bool,
+    /// Whether the share is a scale-out share, available from all the nodes of a cluster at once.
+    pub scale_out: bool,
+    /// Whether the share is provided by a failover cluster.
+    pub cluster: bool,
+    /// Whether the share may move between the nodes of a cluster, so clients should connect to its owner.
+    pub asymmetric: bool,
+    /// Whether the server only lists the entries of directories the user may access.
+    pub access_based_enumeration: bool,
+    /// Whether the share requires the messages of the tree to be encrypted.
+    pub encryption_required: bool,
+    /// Whether the messages of the tree are encrypted, either because the share requires it,
+    /// or because it was forced by [`Tree::set_encryption`][super::Tree::set_encryption].
+    pub encrypted: bool,
+    /// The offline caching policy of the share.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serde_fmt::debug"))]
+    pub caching_mode: ShareCacheMode,
+    /// The maximal access the user has on the share, as an access mask.
+    pub maximal_access: u32,
+}
+
+impl ShareInfoSnapshot {
+    pub(crate) fn new(name: &str, info: &TreeConnectInfo, encrypted: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            share_type: info.share_type,
+            dfs: info.share_flags.dfs() || info.capabilities.dfs(),
+            continuously_available: info.capabilities.continuous_availability(),
+            scale_out: info.capabilities.scaleout(),
+            cluster: info.capabilities.cluster(),
+            asymmetric: info.capabilities.asymmetric(),
+            access_based_enumeration: info.share_flags.access_based_directory_enum(),
+            encryption_required: info.share_flags.encrypt_data(),
+            encrypted,
+            caching_mode: info.share_flags.caching_mode(),
+            maximal_access: info.maximal_access,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use binrw::BinRead;
+    use smb_msg::TreeConnectResponse;
+
+    use super::*;
+
+    /// A tree connect response of a continuously available scale-out share of a cluster,
+    /// that requires encryption and enumerates directories based on access.
+    const CA_SHARE_RESPONSE: [u8; 16] = [
+        0x10, 0x00, 0x01, 0x00, 0x10, 0x88, 0x00, 0x00, 0x70, 0x00, 0x00, 0x00, 0xff, 0x01, 0x1f,
+        0x00,
+    ];
+
+    fn connect_info(response: &[u8]) -> TreeConnectInfo {
+        let response = TreeConnectResponse::read_le(&mut Cursor::new(response)).unwrap();
+        TreeConnectInfo::from(&response)
+    }
+
+    #[test]
+    fn test_share_info_from_response() {
+        let info = connect_info(&CA_SHARE_RESPONSE);
+        assert_eq!(
+            ShareInfoSnapshot::new(r"\\server\share", &info, true),
+            ShareInfoSnapshot {
+                name: r"\\server\share".to_string(),
+                share_type: ShareType::Disk,
+                dfs: false,
+                continuously_available: true,
+                scale_out: true,
+                cluster: true,
+                asymmetric: false,
+                access_based_enumeration: true,
+                encryption_required: true,
+                encrypted: true,
+                caching_mode: ShareCacheMode::Auto,
+                maximal_access: 0x001f01ff,
+            }
+        );
+    }
+
+    #[test]
+    fn test_share_info_plain_share() {
+        let info = connect_info(&[
+            0x10, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xa9, 0x00,
+            0x12, 0x00,
+        ]);
+        let snapshot = ShareInfoSnapshot::new(r"\\server\share", &info, false);
+        assert!(!snapshot.continuously_available);
+        assert!(!snapshot.scale_out && !snapshot.cluster && !snapshot.asymmetric);
+        assert!(!snapshot.access_based_enumeration);
+        assert!(!snapshot.encryption_required && !snapshot.encrypted);
+        assert_eq!(snapshot.caching_mode, ShareCacheMode::Manual);
+        assert_eq!(snapshot.maximal_access, 0x001200a9);
+    }
+}
@@ -0,0 +1,544 @@
+//! Querying the metadata and content of paths using compounded requests.
+//!
+//! A path is opened, operated on, and closed in a single compound of related requests (MS-SMB2 3.2.4.1.4),
+//! taking a single round trip instead of one per request.
+//! See [`Client::stat_many`][crate::Client::stat_many], [`Tree::stat`] and [`Tree::read_small_file`].
+
+use maybe_async::*;
+use smb_fscc::{
+    FileAccessMask, FileAllInformation, FileAttributeTagInformation, FileAttributes,
+    FileNetworkOpenInformation, FileStandardInformation, QueryFileInfoClass, QueryFileInfoValue,
+};
+use smb_msg::{
+    AdditionalInfo, CloseRequest, CreateDisposition, CreateOptions, CreateRequest, FileId,
+    GetInfoRequestData, ImpersonationLevel, InfoType, OplockLevel, QueryInfoClass, QueryInfoFlags,
+    QueryInfoRequest, ReadFlags, ReadRequest, RequestContent, ShareAccessFlags, ShareType, Status,
+};
+
+use super::Tree;
+use crate::{
+    Error, File, FileCreateArgs, Resource,
+    msg_handler::{IncomingMessage, MessageHandler, OutgoingMessage, ReceiveOptions},
+};
+
+/// The metadata of a path, as queried by [`Tree::send_stat_compound`].
+#[derive(Debug)]
+pub(crate) struct PathInfo {
+    pub all: FileAllInformation,
+    pub tag: FileAttributeTagInformation,
+}
+
+/// A compound that opens a path, operates on it, and closes it, that was sent,
+/// and whose responses are yet to be received. See [`Tree::receive_open_compound`].
+#[derive(Debug)]
+pub(crate) struct PendingOpenCompound {
+    /// The message IDs of the create request, the related operations, and the close request, in order.
+    msg_ids: Vec<u64>,
+}
+
+#[maybe_async(AFIT)]
+impl Tree {
+    /// The maximum number of bytes that [`Tree::read_small_file`] reads in a single round trip.
+    pub const SMALL_FILE_SIZE: u32 = 0x10000;
+
+    /// Returns the metadata of the file or directory at the specified path in the tree.
+    ///
+    /// The path is opened, queried and closed in a single compound request, taking a single round trip.
+    /// Paths of DFS shares, and of connections that do not support multi-credit requests, are queried
+    /// using separate requests.
+    ///
+    /// The path is opened with [`FileAccessMask::file_read_attributes`] access only, so it does not conflict
+    /// with the share mode of other opens. Reparse points are opened, not followed.
+    pub async fn stat(&self, name: &str) -> crate::Result<FileNetworkOpenInformation> {
+        let access = FileAccessMask::new().with_file_read_attributes(true);
+        let options = CreateOptions::new().with_open_reparse_point(true);
+        if !self.can_compound_opens()? {
+            let mut args = FileCreateArgs::make_open_existing(access);
+            args.options = options;
+            let resource = self.create(name, &args).await?;
+            let handle = resource.handle();
+            let info = handle.query_info::<FileNetworkOpenInformation>().await;
+            handle.close().await?;
+            return info;
+        }
+
+        let buffer_length = self.query_buffer_length();
+        let pending = self
+            .send_open_compound(
+                name,
+                access,
+                options,
+                vec![Self::_query_request::<FileNetworkOpenInformation>(
+                    buffer_length,
+                )],
+            )
+            .await?;
+        let [info] = self._receive_operations(pending).await?;
+        Self::_parse_query_response(info?)
+    }
+
+    /// Reads the whole content of the file at the specified path in the tree.
+    ///
+    /// The file is opened, read and closed in a single compound request, so files of up to
+    /// [`Tree::SMALL_FILE_SIZE`] bytes are read in a single round trip. Larger files are then read once again,
+    /// using separate requests (see [`File::read_all_at`][crate::File::read_all_at]).
+    /// Files of DFS shares, and of connections that do not support multi-credit requests, are always read
+    /// using separate requests.
+    ///
+    /// Directories fail with [`Status::FileIsADirectory`].
+    pub async fn read_small_file(&self, name: &str) -> crate::Result<Vec<u8>> {
+        if !self.can_compound_opens()? {
+            return self._read_file_opened(name).await;
+        }
+
+        let read_length = self
+            .conn_info
+            .negotiation
+            .max_read_size
+            .min(Self::SMALL_FILE_SIZE);
+        let read: RequestContent = ReadRequest {
+            flags: ReadFlags::new(),
+            length: read_length,
+            offset: 0,
+            file_id: FileId::FULL,
+            minimum_count: 0,
+        }
+        .into();
+        let pending = self
+            .send_open_compound(
+                name,
+                Self::_read_file_access(),
+                CreateOptions::new().with_non_directory_file(true),
+                vec![
+                    Self::_query_request::<FileStandardInformation>(self.query_buffer_length()),
+                    read,
+                ],
+            )
+            .await?;
+        let [standard, read] = self._receive_operations(pending).await?;
+
+        let standard: FileStandardInformation = Self::_parse_query_response(standard?)?;
+        let data = match read {
+            Ok(response) => response.message.content.to_read()?.buffer,
+            // Reading from the end of the file fails, so empty files are read this way.
+            Err(Error::ServerError(Status::EndOfFile, _)) => vec![],
+            Err(e) => return Err(e),
+        };
+        if standard.end_of_file > data.len() as u64 {
+            log::debug!(
+                "{name} has {} bytes, more than read in a compound. Reading it again.",
+                standard.end_of_file
+            );
+            return self._read_file_opened(name).await;
+        }
+        Ok(data)
+    }
+
+    /// Returns whether the paths of the tree may be opened, operated on and closed in compounds,
+    /// using [`Tree::send_open_compound`].
+    ///
+    /// Compounding requires multi-credit support, and names in DFS shares must be resolved
+    /// before they are opened, so these are opened one request at a time.
+    pub(crate) fn can_compound_opens(&self) -> crate::Result<bool> {
+        let info = self.handler.info()?;
+        Ok(self.conn_info.negotiation.caps.large_mtu()
+            && info.share_type == ShareType::Disk
+            && !info.share_flags.dfs())
+    }
+
+    /// Sends a single compound that opens the specified path, queries its metadata, and closes it,
+    /// without waiting for the responses.
+    ///
+    /// The path is opened with [`FileAccessMask::file_read_attributes`] access only, so it does not conflict
+    /// with the share mode of other opens. Reparse points are opened, not followed.
+    pub(crate) async fn send_stat_compound(
+        &self,
+        name: &str,
+    ) -> crate::Result<PendingOpenCompound> {
+        let buffer_length = self.query_buffer_length();
+        self.send_open_compound(
+            name,
+            FileAccessMask::new().with_file_read_attributes(true),
+            CreateOptions::new().with_open_reparse_point(true),
+            vec![
+                Self::_query_request::<FileAllInformation>(buffer_length),
+                Self::_query_request::<FileAttributeTagInformation>(buffer_length),
+            ],
+        )
+        .await
+    }
+
+    /// Receives the responses of a compound sent using [`Tree::send_stat_compound`].
+    ///
+    /// All the responses are received, even if the path failed to open.
+    /// In that case, the error of the create request is returned.
+    pub(crate) async fn receive_stat_compound(
+        &self,
+        pending: PendingOpenCompound,
+    ) -> crate::Result<PathInfo> {
+        let [all, tag] = self._receive_operations(pending).await?;
+        Ok(PathInfo {
+            all: Self::_parse_query_response(all?)?,
+            tag: Self::_parse_query_response(tag?)?,
+        })
+    }
+
+    /// Sends a single compound that opens the specified path, sends the specified `operations` on the opened file,
+    /// and closes it, without waiting for the responses.
+    ///
+    /// The operations must refer to the opened file using [`FileId::FULL`].
+    /// The path is opened for `desired_access`, sharing read, write and delete access with other opens.
+    pub(crate) async fn send_open_compound(
+        &self,
+        name: &str,
+        desired_access: FileAccessMask,
+        create_options: CreateOptions,
+        operations: Vec<RequestContent>,
+    ) -> crate::Result<PendingOpenCompound> {
+        if name.starts_with("\\") {
+            return Err(Error::InvalidArgument(
+                "Resource name cannot start with a backslash.".to_string(),
+            ));
+        }
+
+        let create = OutgoingMessage::new(
+            CreateRequest {
+                requested_oplock_level: OplockLevel::None,
+                impersonation_level: ImpersonationLevel::Impersonation,
+                desired_access,
+                file_attributes: FileAttributes::new(),
+                share_access: ShareAccessFlags::new()
+                    .with_read(true)
+                    .with_write(true)
+                    .with_delete(true),
+                create_disposition: CreateDisposition::Open,
+                create_options,
+                name: name.into(),
+                contexts: vec![].into(),
+            }
+            .into(),
+        );
+        let close = CloseRequest {
+            file_id: FileId::FULL,
+        }
+        .into();
+        let msgs = std::iter::once(create)
+            .chain(
+                operations
+                    .into_iter()
+                    .chain(std::iter::once(close))
+                    .map(Self::_related),
+            )
+            .collect();
+
+        let sent = self.handler.sendo_compound(msgs).await?;
+        Ok(PendingOpenCompound {
+            msg_ids: sent.iter().map(|result| result.msg_id).collect(),
+        })
+    }
+
+    /// Receives the responses of a compound sent using [`Tree::send_open_compound`],
+    /// returning the responses of the operations, in order.
+    ///
+    /// All the responses are received, even if the path failed to open.
+    /// In that case, the error of the create request is returned.
+    ///
+    /// Once a request of a compound fails, servers may fail the rest of its related requests with the same status,
+    /// including the close request. If the path was opened but the close request failed,
+    /// the file is closed using a separate request.
+    pub(crate) async fn receive_open_compound(
+        &self,
+        pending: PendingOpenCompound,
+    ) -> crate::Result<Vec<crate::Result<IncomingMessage>>> {
+        let mut responses = Vec::with_capacity(pending.msg_ids.len());
+        for msg_id in pending.msg_ids {
+            responses.push(self._receive_related(msg_id).await);
+        }
+        let close = responses
+            .pop()
+            .ok_or_else(|| Error::InvalidState("Unexpected number of sent messages".to_string()))?;
+        let mut responses = responses.into_iter();
+        let create = responses.next().ok_or_else(|| {
+            Error::InvalidState("Unexpected number of sent messages".to_string())
+        })??;
+
+        match close {
+            Ok(_) | Err(Error::ServerError(Status::FileClosed, _)) => {}
+            Err(e) => {
+                log::debug!("The close request of a compound failed: {e}. Closing separately.");
+                self._close_opened(&create).await;
+            }
+        }
+        Ok(responses.collect())
+    }
+
+    /// Receives the responses of a compound with exactly `N` operations. See [`Tree::receive_open_compound`].
+    async fn _receive_operations<const N: usize>(
+        &self,
+        pending: PendingOpenCompound,
+    ) -> crate::Result<[crate::Result<IncomingMessage>; N]> {
+        self.receive_open_compound(pending)
+            .await?
+            .try_into()
+            .map_err(|_| Error::InvalidState("Unexpected number of sent messages".to_string()))
+    }
+
+    /// Closes the file opened by the create request of a compound, whose close request failed.
+    async fn _close_opened(&self, create: &IncomingMessage) {
+        let file_id = match create.message.content.as_create() {
+            Ok(create) => create.file_id,
+            Err(e) => {
+                log::warn!("Failed to close the handle of a compound: {e}");
+                return;
+            }
+        };
+        let result = self
+            .handler
+            .send_recvo(CloseRequest { file_id }.into(), ReceiveOptions::new())
+            .await;
+        if let Err(e) = result {
+            log::warn!("Failed to close the handle of a compound: {e}");
+        }
+    }
+
+    /// Reads the whole content of a file using separate requests.
+    async fn _read_file_opened(&self, name: &str) -> crate::Result<Vec<u8>> {
+        let mut args = FileCreateArgs::make_open_existing(Self::_read_file_access());
+        args.options = args.options.with_non_directory_file(true);
+        let Resource::File(file) = self.create(name, &args).await? else {
+            return Err(Error::InvalidArgument(format!("{name} is not a file")));
+        };
+
+        let result = Self::_read_whole_file(&file, name).await;
+        file.close().await?;
+        result
+    }
+
+    async fn _read_whole_file(file: &File, name: &str) -> crate::Result<Vec<u8>> {
+        let end_of_file = file
+            .query_info::<FileStandardInformation>()
+            .await?
+            .end_of_file;
+        let length = end_of_file.try_into().map_err(|_| {
+            Error::InvalidArgument(format!("{name} is too large to be read into memory"))
+        })?;
+        let mut data = vec![0; length];
+        let read = file.read_all_at(&mut data, 0).await?;
+        data.truncate(read);
+        Ok(data)
+    }
+
+    fn _read_file_access() -> FileAccessMask {
+        FileAccessMask::new()
+            .with_file_read_data(true)
+            .with_file_read_attributes(true)
+            .with_synchronize(true)
+    }
+
+    /// The output buffer length of the query requests of compounds.
+    fn query_buffer_length(&self) -> u32 {
+        self.conn_info
+            .negotiation
+            .max_transact_size
+            .min(self.conn_info.config.default_transaction_size())
+    }
+
+    /// Marks a message as related to the previous one in a compound, operating on the file it opened.
+    fn _related(content: RequestContent) -> OutgoingMessage {
+        let mut msg = OutgoingMessage::new(content);
+        msg.message.header.flags.set_related_operations(true);
+        msg
+    }
+
+    fn _query_request<T: QueryFileInfoValue>(output_buffer_length: u32) -> RequestContent {
+        QueryInfoRequest {
+            info_type: InfoType::File,
+            info_class: QueryInfoClass::File(T::CLASS_ID),
+            output_buffer_length,
+            additional_info: AdditionalInfo::new(),
+            flags: QueryInfoFlags::new(),
+            file_id: FileId::FULL,
+            data: GetInfoRequestData::None(()),
+        }
+        .into()
+    }
+
+    fn _parse_query_response<T: QueryFileInfoValue>(response: IncomingMessage) -> crate::Result<T> {
+        let class: QueryFileInfoClass = T::CLASS_ID;
+        Ok(response
+            .message
+            .content
+            .to_queryinfo()?
+            .parse(InfoType::File)?
+            .as_file()?
+            .parse(class)?
+            .try_into()?)
+    }
+
+    async fn _receive_related(&self, msg_id: u64) -> crate::Result<IncomingMessage> {
+        self.handler
+            .recvo(ReceiveOptions::new().with_msg_id_filter(msg_id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use binrw::BinWrite;
+    use smb_dtyp::binrw_util::prelude::FileTime;
+    use smb_fscc::{FileAttributes, FileNetworkOpenInformation, FileStandardInformation};
+    use smb_msg::{
+        Command, FileId, PlainRequest, QueryInfoResponse, ReadResponse, ShareFlags, Status,
+    };
+    use smb_transport::mock::MockStep;
+
+    use crate::{Error, test_util::*};
+
+    const FILE_ID: FileId = FileId {
+        persistent: 0x11,
+        volatile: 0x22,
+    };
+
+    fn query_response(info: impl for<'a> BinWrite<Args<'a> = ()>) -> QueryInfoResponse {
+        let mut data = Cursor::new(vec![]);
+        info.write_le(&mut data).unwrap();
+        QueryInfoResponse::new(data.into_inner())
+    }
+
+    /// Whether a request operates on the file opened by the previous request of its compound.
+    fn is_related(request: &PlainRequest) -> bool {
+        request.header.flags.related_operations()
+    }
+
+    fn create_step(name: &'static str) -> MockStep {
+        MockStep::expect(Command::Create).matching(name, move |r| {
+            !is_related(r)
+                && r.content
+                    .as_create()
+                    .is_ok_and(|create| create.name == name)
+        })
+    }
+
+    fn related_step(command: Command) -> MockStep {
+        MockStep::expect(command).matching("related", is_related)
+    }
+
+    fn network_open_info(end_of_file: u64) -> FileNetworkOpenInformation {
+        FileNetworkOpenInformation {
+            creation_time: FileTime::default(),
+            last_access_time: FileTime::default(),
+            last_write_time: FileTime::default(),
+            change_time: FileTime::default(),
+            allocation_size: 4096,
+            end_of_file,
+            file_attributes: FileAttributes::new().with_archive(true),
+        }
+    }
+
+    fn standard_info(end_of_file: u64) -> FileStandardInformation {
+        FileStandardInformation {
+            allocation_size: 4096,
+            end_of_file,
+            number_of_links: 1,
+            delete_pending: false.into(),
+            directory: false.into(),
+        }
+    }
+
+    /// Asserts that the last `count` requests were sent in a single compound.
+    fn assert_compounded(handle: &smb_transport::mock::MockHandle, count: usize) {
+        let received = handle.received();
+        let compound = &received[received.len() - count..];
+        let (last, chained) = compound.split_last().unwrap();
+        assert!(chained.iter().all(|header| header.next_command != 0));
+        assert_eq!(last.next_command, 0);
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_stat() {
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                create_step("a.txt").respond(create_response(FILE_ID, FileAttributes::new())),
+                related_step(Command::QueryInfo).respond(query_response(network_open_info(13))),
+                related_step(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+        let info = mock.tree.stat("a.txt").await.unwrap();
+        assert_eq!(info, network_open_info(13));
+        assert_compounded(&mock.handle, 3);
+        mock.handle.assert_done();
+    }
+
+    /// Once the create request fails, the related requests fail with its status.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_stat_not_found() {
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                create_step("missing.txt").respond_error(Status::ObjectNameNotFound),
+                related_step(Command::QueryInfo).respond_error(Status::ObjectNameNotFound),
+                related_step(Command::Close).respond_error(Status::ObjectNameNotFound),
+            ],
+        )
+        .await;
+        let result = mock.tree.stat("missing.txt").await;
+        assert!(matches!(
+            result,
+            Err(Error::ServerError(Status::ObjectNameNotFound, _))
+        ));
+        mock.handle.assert_done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_read_small_file() {
+        const DATA: &[u8] = b"Hello, compound!";
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                create_step("a.txt").respond(create_response(FILE_ID, FileAttributes::new())),
+                related_step(Command::QueryInfo)
+                    .respond(query_response(standard_info(DATA.len() as u64))),
+                related_step(Command::Read).respond(ReadResponse {
+                    buffer: DATA.to_vec(),
+                }),
+                related_step(Command::Close).respond(close_response()),
+            ],
+        )
+        .await;
+        let data = mock.tree.read_small_file("a.txt").await.unwrap();
+        assert_eq!(data, DATA);
+        assert_compounded(&mock.handle, 4);
+        mock.handle.assert_done();
+    }
+
+    /// Reading an empty file fails with an end of file status, which may fail the related close as well:
+    /// the file is then closed using a separate request.
+    #[maybe_async::test(not(feature = "async"), async(feature = "async", tokio::test))]
+    async fn test_read_small_file_empty() {
+        let mock = connect_mock_tree(
+            ShareFlags::new(),
+            [
+                create_step("empty.txt").respond(create_response(FILE_ID, FileAttributes::new())),
+                related_step(Command::QueryInfo).respond(query_response(standard_info(0))),
+                related_step(Command::Read).respond_error(Status::EndOfFile),
+                related_step(Command::Close).respond_error(Status::EndOfFile),
+                MockStep::expect(Command::Close)
+                    .matching("unrelated, of the opened file", |r| {
+                        !is_related(r)
+                            && r.content
+                                .as_close()
+                                .is_ok_and(|close| close.file_id == FILE_ID)
+                    })
+                    .respond(close_response()),
+            ],
+        )
+        .await;
+        let data = mock.tree.read_small_file("empty.txt").await.unwrap();
+        assert!(data.is_empty());
+        mock.handle.assert_done();
+    }
+}
@@ -0,0 +1,84 @@
+//! Security token hook tests.
+
+mod common;
+
+use common::{TestConstants, default_connection_config, make_server_connection};
+use serial_test::serial;
+use smb::session::{AuthTokenHook, TokenDirection};
+use std::sync::{Arc, Mutex};
+
+type Recorded = Arc<Mutex<Vec<(TokenDirection, usize, Vec<u8>)>>>;
+
+fn recording_hook() -> (AuthTokenHook, Recorded) {
+    let recorded = Recorded::default();
+    let hook = {
+        let recorded = recorded.clone();
+        AuthTokenHook::new(move |token| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((token.direction, token.step, token.data.to_vec()))
+        })
+    };
+    (hook, recorded)
+}
+
+fn count(recorded: &Recorded, direction: TokenDirection) -> usize {
+    recorded
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(d, ..)| *d == direction)
+        .count()
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_auth_token_hook_ntlm() -> Result<(), Box<dyn std::error::Error>> {
+    let (hook, recorded) = recording_hook();
+    let mut config = default_connection_config();
+    config.auth_token_hook = Some(hook);
+    let (_client, _share) =
+        make_server_connection(TestConstants::DEFAULT_SHARE, Some(config)).await?;
+
+    // Negotiate response token, then NEGOTIATE -> CHALLENGE, and AUTHENTICATE -> accept-completed.
+    assert_eq!(count(&recorded, TokenDirection::Incoming), 3);
+    assert_eq!(count(&recorded, TokenDirection::Outgoing), 2);
+
+    let recorded = recorded.lock().unwrap();
+    let steps = recorded.iter().map(|(_, s, _)| *s).collect::<Vec<_>>();
+    assert_eq!(steps, vec![0, 1, 1, 2, 2]);
+
+    let ntlm_types = recorded
+        .iter()
+        .filter_map(|(_, _, data)| smb::session::auth_tokens::SpnegoTokenInfo::decode(data)?.ntlm)
+        .map(|ntlm| ntlm.message_type)
+        .collect::<Vec<_>>();
+    assert_eq!(ntlm_types, vec![1, 2, 3]);
+    Ok(())
+}
+
+#[cfg(feature = "kerberos")]
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a KDC in the test environment"]
+async fn test_auth_token_hook_kerberos() -> Result<(), Box<dyn std::error::Error>> {
+    let (hook, recorded) = recording_hook();
+    let mut config = default_connection_config();
+    config.auth_methods.kerberos = true;
+    config.auth_methods.ntlm = false;
+    config.auth_token_hook = Some(hook);
+    let (_client, _share) =
+        make_server_connection(TestConstants::DEFAULT_SHARE, Some(config)).await?;
+
+    // Negotiate response token, then AP-REQ -> AP-REP.
+    assert_eq!(count(&recorded, TokenDirection::Incoming), 2);
+    assert_eq!(count(&recorded, TokenDirection::Outgoing), 1);
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! Backup intent tests, see [`FileCreateArgs::for_backup`].
+//!
+//! These require a Windows server, and a user in its Backup Operators group,
+//! set in `SMB_RUST_TESTS_BACKUP_USER_NAME` and `SMB_RUST_TESTS_BACKUP_PASSWORD`.
+
+mod common;
+
+use std::env::var;
+
+use common::{TestConstants, default_connection_config, make_server_connection};
+use serial_test::serial;
+use smb::{Client, ClientConfig, Error, FileCreateArgs, SecurityInformation};
+
+const BACKUP_USER: &str = "SMB_RUST_TESTS_BACKUP_USER_NAME";
+const BACKUP_PASSWORD: &str = "SMB_RUST_TESTS_BACKUP_PASSWORD";
+
+const SOURCE_FILE: &str = "backup_test_source.txt";
+const RESTORED_FILE: &str = "backup_test_restored.txt";
+const CONTENT: &[u8] = b"Backed up by a backup operator";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a Windows server and a backup operator account"]
+async fn test_backup_and_restore() -> Result<(), Box<dyn std::error::Error>> {
+    let (admin, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let source_path = share_path.clone().with_path(SOURCE_FILE);
+    let restored_path = share_path.clone().with_path(RESTORED_FILE);
+
+    let source = admin
+        .create_file(
+            &source_path,
+            &FileCreateArgs::make_overwrite(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file();
+    source.write_all_at(CONTENT, 0).await?;
+    source.close().await?;
+
+    let backup = Client::new(ClientConfig {
+        connection: default_connection_config(),
+        ..Default::default()
+    });
+    backup
+        .share_connect(&share_path, &var(BACKUP_USER)?, var(BACKUP_PASSWORD)?)
+        .await?;
+
+    // Back up the data and the security descriptor of the file.
+    let file = backup
+        .create_file(&source_path, &FileCreateArgs::for_backup())
+        .await?
+        .unwrap_file();
+    let mut data = vec![0; CONTENT.len()];
+    let read = file.read_all_at(&mut data, 0).await?;
+    assert_eq!(read, CONTENT.len());
+    assert_eq!(data, CONTENT);
+    let security_info = SecurityInformation::owner_group_dacl();
+    let descriptor = file.query_security_info(security_info).await?;
+    let sacl = file
+        .query_security_info(SecurityInformation::new().with_sacl(true))
+        .await;
+    assert!(
+        matches!(sacl, Err(Error::MissingPermissions(_))),
+        "The SACL requires access_system_security"
+    );
+    file.close().await?;
+
+    // Backup Operators do not hold SeSecurityPrivilege by default.
+    match backup
+        .create_file(
+            &source_path,
+            &FileCreateArgs::for_backup().with_system_security(true),
+        )
+        .await
+    {
+        Err(Error::PrivilegeNotHeld { privilege, .. }) => {
+            assert_eq!(privilege, "SeSecurityPrivilege")
+        }
+        Ok(_) => panic!("Expected the SACL access to require SeSecurityPrivilege"),
+        Err(e) => return Err(e.into()),
+    }
+
+    // Restore the file, along with its owner.
+    let restored = backup
+        .create_file(&restored_path, &FileCreateArgs::for_restore())
+        .await?
+        .unwrap_file();
+    restored.write_all_at(CONTENT, 0).await?;
+    restored
+        .set_security_info(descriptor.clone(), security_info)
+        .await?;
+    restored.close().await?;
+
+    let restored = admin
+        .create_file(&restored_path, &FileCreateArgs::for_backup())
+        .await?
+        .unwrap_file();
+    let restored_descriptor = restored
+        .query_security_info(SecurityInformation::new().with_owner(true))
+        .await?;
+    assert_eq!(restored_descriptor.owner_sid, descriptor.owner_sid);
+    restored.close().await?;
+
+    admin.delete(&source_path).await?;
+    admin.delete(&restored_path).await?;
+    Ok(())
+}
@@ -87,14 +87,47 @@ async fn test_basic_auth_fail() -> smb::Result<()> {
 async fn do_test_basic_auth_fail() -> smb::Result<()> {
     let res = _do_minimal_connection_test(None, None).await.unwrap_err();
     match res {
-        smb::Error::UnexpectedMessageStatus(status) => {
-            assert_eq!(status, Status::LogonFailure as u32);
+        smb::Error::ServerError(status, _) => {
+            assert_eq!(status, Status::LogonFailure);
         }
         _ => panic!("Expected LogonFailure error"),
     }
     smb::Result::Ok(())
 }
 
+/// Paths that differ only in the case of the server and share names resolve to the same tree.
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_share_path_case_insensitive() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let server = share_path.server();
+    let variants = [
+        UncPath::new(&server.to_uppercase())?
+            .with_share(&TestConstants::DEFAULT_SHARE.to_uppercase())?,
+        UncPath::new(&server.to_lowercase())?
+            .with_share(&TestConstants::DEFAULT_SHARE.to_lowercase())?,
+    ];
+
+    let tree = client.get_tree(&share_path).await?;
+    let user = std::env::var(TestEnv::USER).unwrap_or(TestEnv::DEFAULT_USER.to_string());
+    let password =
+        std::env::var(TestEnv::PASSWORD).unwrap_or(TestEnv::DEFAULT_PASSWORD.to_string());
+    for variant in variants {
+        // Connecting again is a no-op, not a second tree.
+        client
+            .share_connect(&variant, &user, password.clone())
+            .await?;
+        let variant_tree = client
+            .get_tree(&variant.with_path("Some\\File.txt"))
+            .await?;
+        assert!(std::sync::Arc::ptr_eq(&tree, &variant_tree));
+    }
+    Ok(())
+}
+
 #[maybe_async::maybe_async]
 async fn _test_connection_timeout_fail(
     transport_config: TransportConfig,
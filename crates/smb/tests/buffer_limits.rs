@@ -0,0 +1,89 @@
+//! Tests for buffer sizes that exceed the limits negotiated with the server.
+
+mod common;
+
+use std::env::var;
+
+use common::{TestConstants, TestEnv, default_connection_config};
+use serial_test::serial;
+use smb::connection::BufferSizePolicy;
+use smb::{Client, ClientConfig, ConnectionConfig, Error, FileCreateArgs, UncPath};
+use smb_fscc::{FileBasicInformation, FileFsSizeInformation};
+use smb_msg::QueryInfoFlags;
+
+const FILE_NAME: &str = "buffer_limits_test.txt";
+const SMALL_TRANSACT_SIZE: u32 = 0x10000;
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_buffer_size_policy() -> Result<(), Box<dyn std::error::Error>> {
+    let server = var(TestEnv::SMALL_TRANSACT_SERVER)
+        .unwrap_or(TestEnv::DEFAULT_SMALL_TRANSACT_SERVER.to_string());
+    let share_path = UncPath::new(&server)?.with_share(TestConstants::DEFAULT_SHARE)?;
+    let path = share_path.clone().with_path(FILE_NAME);
+    let user = var(TestEnv::USER).unwrap_or(TestEnv::DEFAULT_USER.to_string());
+    let password = var(TestEnv::PASSWORD).unwrap_or(TestEnv::DEFAULT_PASSWORD.to_string());
+
+    for policy in [BufferSizePolicy::Clamp, BufferSizePolicy::Error] {
+        let client = Client::new(ClientConfig {
+            connection: ConnectionConfig {
+                buffer_size_policy: policy,
+                ..default_connection_config()
+            },
+            ..Default::default()
+        });
+        client
+            .share_connect(&share_path, &user, password.clone())
+            .await?;
+        let connection = client.get_connection(share_path.server()).await?;
+        let max_transact_size = connection
+            .conn_info()
+            .unwrap()
+            .negotiation
+            .max_transact_size;
+        assert_eq!(max_transact_size, SMALL_TRANSACT_SIZE);
+
+        let file = client
+            .create_file(
+                &path,
+                &FileCreateArgs::make_overwrite(Default::default(), Default::default()),
+            )
+            .await?
+            .unwrap_file();
+
+        // Buffers up to the limit are always accepted.
+        let limit = Some(SMALL_TRANSACT_SIZE as usize);
+        file.query_info_with_options::<FileBasicInformation>(QueryInfoFlags::new(), limit)
+            .await?;
+        file.query_fs_info_with_options::<FileFsSizeInformation>(limit)
+            .await?;
+
+        let oversized = Some(SMALL_TRANSACT_SIZE as usize + 1);
+        let info = file
+            .query_info_with_options::<FileBasicInformation>(QueryInfoFlags::new(), oversized)
+            .await;
+        let fs_info = file
+            .query_fs_info_with_options::<FileFsSizeInformation>(oversized)
+            .await;
+        match policy {
+            BufferSizePolicy::Clamp => {
+                info?;
+                fs_info?;
+            }
+            BufferSizePolicy::Error => {
+                assert!(matches!(info, Err(Error::InvalidArgument(_))));
+                assert!(matches!(fs_info, Err(Error::InvalidArgument(_))));
+            }
+        }
+
+        // Default buffer sizes are never rejected.
+        file.query_info::<FileBasicInformation>().await?;
+        file.close().await?;
+        client.delete(&path).await?;
+        client.close().await?;
+    }
+    Ok(())
+}
@@ -6,6 +6,11 @@ pub struct TestEnv;
 
 impl TestEnv {
     pub const SERVER: &'static str = "SMB_RUST_TESTS_SERVER";
+    /// A second server, for tests that copy data between servers.
+    pub const SECOND_SERVER: &'static str = "SMB_RUST_TESTS_SECOND_SERVER";
+    /// A server with a max transact size of 64KiB, for buffer size limit tests.
+    pub const SMALL_TRANSACT_SERVER: &'static str = "SMB_RUST_TESTS_SMALL_TRANSACT_SERVER";
+    pub const DEFAULT_SMALL_TRANSACT_SERVER: &'static str = "127.0.0.1:1445";
     pub const USER: &'static str = "SMB_RUST_TESTS_USER_NAME";
     pub const DEFAULT_USER: &'static str = "LocalAdmin";
     pub const PASSWORD: &'static str = "SMB_RUST_TESTS_PASSWORD";
@@ -20,11 +25,15 @@ pub struct TestConstants;
 impl TestConstants {
     pub const DEFAULT_SHARE: &'static str = "MyShare";
     pub const PUBLIC_GUEST_SHARE: &'static str = "PublicShare";
+    /// A share with a `shadow_copy2` snapshot, see the tests Dockerfile.
+    pub const SNAPSHOT_SHARE: &'static str = "SnapshotShare";
 }
 
 pub fn default_connection_config() -> ConnectionConfig {
-    let mut conn_config = ConnectionConfig::default();
-    conn_config.timeout = Some(std::time::Duration::from_secs(10));
+    let mut conn_config = ConnectionConfig {
+        timeout: Some(std::time::Duration::from_secs(10)),
+        ..Default::default()
+    };
     conn_config.auth_methods.kerberos = false;
     conn_config.auth_methods.ntlm = true;
     conn_config
@@ -0,0 +1,60 @@
+//! Compression of write requests.
+#![cfg(feature = "compress")]
+
+mod common;
+
+use common::{TestConstants, default_connection_config, make_server_connection};
+use serial_test::serial;
+use smb::ConnectionConfig;
+
+const FILE_NAME: &str = "compression_test.bin";
+const FILE_SIZE: usize = 100 * 1024 * 1024;
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a server that supports SMB compression (e.g. Windows Server 2022)"]
+async fn test_compressed_write() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(
+        TestConstants::DEFAULT_SHARE,
+        ConnectionConfig {
+            compression_enabled: true,
+            ..default_connection_config()
+        }
+        .into(),
+    )
+    .await?;
+    let path = share_path.clone().with_path(FILE_NAME);
+    let connection = client.get_connection(share_path.server()).await?;
+    assert!(
+        connection
+            .conn_info()
+            .unwrap()
+            .negotiation
+            .compression
+            .is_some()
+    );
+
+    // Runs of zeros, separated by repeated text: compressed using Pattern_V1 and LZ4.
+    let content = (0..FILE_SIZE)
+        .map(|i| match i % 0x10000 < 0x8000 {
+            true => 0,
+            false => b"compressible"[i % 12],
+        })
+        .collect::<Vec<_>>();
+    client
+        .write_file(&path, &content, &Default::default())
+        .await?;
+
+    let stats = connection.compression_stats().unwrap();
+    assert!(stats.messages_compressed > 0);
+    assert!(stats.original_bytes >= FILE_SIZE as u64);
+    assert!(stats.compressed_bytes * 10 < stats.original_bytes);
+
+    let data = client.read_file(&path).await?;
+    assert!(data == content);
+    client.delete(&path).await?;
+    Ok(())
+}
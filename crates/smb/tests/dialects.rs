@@ -8,7 +8,7 @@ use smb::{
     ConnectionConfig, Directory, FileCreateArgs, ReadAt, WriteAt, connection::EncryptionMode,
 };
 use smb_fscc::*;
-use smb_msg::{AdditionalInfo, CreateOptions, Dialect};
+use smb_msg::{CreateOptions, Dialect, SecurityInformation};
 use std::sync::Arc;
 mod common;
 
@@ -78,7 +78,7 @@ async fn test_smb_integration_dialect_encrpytion_mode(
 
         // Query security info (owner only)
         let r = file
-            .query_security_info(AdditionalInfo::new().with_owner_security_information(true))
+            .query_security_info(SecurityInformation::new().with_owner(true))
             .await?;
 
         file.close().await?;
@@ -106,7 +106,7 @@ async fn test_smb_integration_dialect_encrpytion_mode(
         let mut found = false;
 
         ds.for_each(|entry| {
-            if entry.unwrap().file_name.to_string() == TEST_FILE {
+            if entry.unwrap().file_name == TEST_FILE {
                 found = true;
             }
             async {}
@@ -0,0 +1,144 @@
+//! Exclusion of the `.` and `..` entries from directory listings, for each information class.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{
+    Client, DirAccessMask, Directory, FileBothDirectoryInformation, FileCreateArgs,
+    FileDirectoryInformation, FileFullDirectoryInformation, FileIdBothDirectoryInformation,
+    FileIdFullDirectoryInformation, FileNamesInformation, QueryDirectoryEntryName,
+    QueryDirectoryInfoValue, QueryDirectoryOptions, UncPath,
+};
+use std::sync::Arc;
+
+#[cfg(feature = "async")]
+use futures_util::StreamExt;
+
+const DOT_ENTRIES_DIR: &str = "dot_entries_test";
+const FILE_NAMES: [&str; 2] = ["a.txt", "b.txt"];
+
+#[maybe_async::maybe_async]
+async fn make_test_dir(client: &Client, dir: &UncPath) -> smb::Result<()> {
+    client
+        .create_file(
+            dir,
+            &FileCreateArgs::make_create_new(
+                smb_fscc::FileAttributes::new().with_directory(true),
+                smb_msg::CreateOptions::new().with_directory_file(true),
+            ),
+        )
+        .await?
+        .unwrap_dir()
+        .close()
+        .await?;
+    for name in FILE_NAMES {
+        client
+            .create_file(
+                &dir.clone().with_add_path(name),
+                &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+            )
+            .await?
+            .unwrap_file()
+            .close()
+            .await?;
+    }
+    Ok(())
+}
+
+/// Lists the names of the entries of the directory, sorted.
+#[maybe_async::maybe_async]
+async fn list_names<T>(
+    client: &Client,
+    dir: &UncPath,
+    options: &QueryDirectoryOptions,
+) -> smb::Result<Vec<String>>
+where
+    T: QueryDirectoryInfoValue
+        + QueryDirectoryEntryName
+        + for<'b> binrw::BinWrite<Args<'b> = ()>
+        + Send
+        + Unpin
+        + 'static,
+{
+    let directory = client
+        .create_file(
+            dir,
+            &FileCreateArgs::make_open_existing(
+                DirAccessMask::new()
+                    .with_list_directory(true)
+                    .with_synchronize(true)
+                    .into(),
+            ),
+        )
+        .await?
+        .unwrap_dir();
+    let directory = Arc::new(directory);
+    let entries = Directory::query_with_options::<T>(&directory, "*", options).await?;
+    #[cfg(feature = "async")]
+    let entries = entries.collect::<Vec<_>>().await;
+    let mut names = vec![];
+    for entry in entries {
+        names.push(entry?.entry_name().to_string());
+    }
+    directory.close().await?;
+    names.sort();
+    Ok(names)
+}
+
+/// Asserts that the listing of the test directory using `T` excludes the `.` and `..` entries by default,
+/// and that including them yields nothing but them, in addition to the files.
+#[maybe_async::maybe_async]
+async fn check_info_class<T>(client: &Client, dir: &UncPath) -> smb::Result<()>
+where
+    T: QueryDirectoryInfoValue
+        + QueryDirectoryEntryName
+        + for<'b> binrw::BinWrite<Args<'b> = ()>
+        + Send
+        + Unpin
+        + 'static,
+{
+    let class = std::any::type_name::<T>();
+    let names = list_names::<T>(client, dir, &Default::default()).await?;
+    assert_eq!(names, FILE_NAMES, "{class}");
+
+    let names = list_names::<T>(
+        client,
+        dir,
+        &QueryDirectoryOptions {
+            include_dot_entries: true,
+            ..Default::default()
+        },
+    )
+    .await?;
+    let files = names
+        .iter()
+        .filter(|name| *name != "." && *name != "..")
+        .collect::<Vec<_>>();
+    assert_eq!(files, FILE_NAMES, "{class}");
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_query_excludes_dot_entries() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let dir = share_path.with_path(DOT_ENTRIES_DIR);
+    make_test_dir(&client, &dir).await?;
+
+    check_info_class::<FileDirectoryInformation>(&client, &dir).await?;
+    check_info_class::<FileFullDirectoryInformation>(&client, &dir).await?;
+    check_info_class::<FileIdFullDirectoryInformation>(&client, &dir).await?;
+    check_info_class::<FileBothDirectoryInformation>(&client, &dir).await?;
+    check_info_class::<FileIdBothDirectoryInformation>(&client, &dir).await?;
+    check_info_class::<FileNamesInformation>(&client, &dir).await?;
+
+    for name in FILE_NAMES {
+        client.delete(&dir.clone().with_add_path(name)).await?;
+    }
+    client.delete(&dir).await?;
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! Client::download_dir tests.
+#![cfg(feature = "std-fs-impls")]
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::client::DownloadDirOptions;
+use smb::{Client, FileCreateArgs, UncPath};
+use smb_fscc::{FileAccessMask, FileAttributes, FileDispositionInformation};
+use smb_msg::CreateOptions;
+
+const DOWNLOAD_DIR: &str = "download_dir_test";
+/// Deeper than the trees that used to hang hand-rolled recursive downloads.
+const DEPTH: usize = 6;
+
+/// Creates a chain of `DEPTH` nested directories, each with a file and a file to be filtered out.
+/// Returns the created paths, parents first.
+#[maybe_async::maybe_async]
+async fn make_tree(client: &Client, root: &UncPath) -> smb::Result<Vec<UncPath>> {
+    let mut created = vec![];
+    let mut dir = root.clone();
+    for depth in 0..DEPTH {
+        client
+            .create_file(
+                &dir,
+                &FileCreateArgs::make_create_new(
+                    FileAttributes::new().with_directory(true),
+                    CreateOptions::new().with_directory_file(true),
+                ),
+            )
+            .await?
+            .unwrap_dir()
+            .close()
+            .await?;
+        created.push(dir.clone());
+        for name in ["file.txt", "skip.tmp"] {
+            let path = dir.clone().with_add_path(name);
+            client
+                .write_file(&path, format!("{depth}").as_bytes(), &Default::default())
+                .await?;
+            created.push(path);
+        }
+        dir = dir.with_add_path(&format!("dir{depth}"));
+    }
+    Ok(created)
+}
+
+/// Deletes the created paths, children first.
+#[maybe_async::maybe_async]
+async fn delete_tree(client: &Client, created: Vec<UncPath>) -> smb::Result<()> {
+    for path in created.into_iter().rev() {
+        let resource = client
+            .create_file(
+                &path,
+                &FileCreateArgs::make_open_existing(FileAccessMask::new().with_delete(true)),
+            )
+            .await?;
+        let handle = match &resource {
+            smb::Resource::File(f) => f.handle(),
+            smb::Resource::Directory(d) => d.handle(),
+            smb::Resource::Pipe(p) => p.handle(),
+        };
+        handle
+            .set_info(FileDispositionInformation::default())
+            .await?;
+        handle.close().await?;
+    }
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_download_nested_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let root = share_path.with_path(DOWNLOAD_DIR);
+    let created = make_tree(&client, &root).await?;
+
+    let local = std::env::temp_dir().join("smb_rs_download_dir");
+    let _ = std::fs::remove_dir_all(&local);
+    let options = DownloadDirOptions {
+        concurrency: 3,
+        filter: Some(Arc::new(|entry| !entry.name().ends_with(".tmp"))),
+        ..Default::default()
+    };
+    let report = client.download_dir(&root, &local, options).await?;
+    assert!(report.collisions.is_empty());
+
+    let mut dir = local.clone();
+    for depth in 0..DEPTH {
+        assert_eq!(
+            std::fs::read_to_string(dir.join("file.txt"))?,
+            depth.to_string()
+        );
+        assert!(!dir.join("skip.tmp").exists());
+        dir = dir.join(format!("dir{depth}"));
+    }
+    assert!(!dir.exists());
+
+    std::fs::remove_dir_all(&local)?;
+    delete_tree(&client, created).await?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! Durable handle tests.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{CreateOptions, DurableOpenOptions, Error, FileAttributes, FileCreateArgs};
+
+const FILE_NAME: &str = "durable_test.txt";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a server with durable handles enabled"]
+async fn test_reclaim_durable_handle() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.clone().with_path(FILE_NAME);
+
+    let args = FileCreateArgs {
+        durable: Some(DurableOpenOptions {
+            timeout: Some(Duration::from_secs(60)),
+            ..Default::default()
+        }),
+        ..FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new())
+    };
+    let file = client.create_file(&path, &args).await?.unwrap_file();
+    file.write_all_at(b"first, ", 0).await?;
+    let ticket = file
+        .export_durable_ticket()
+        .ok_or("the server did not grant a durable handle")?;
+
+    // Lose the connection without closing the file, as if the process exited.
+    client.abandon().await?;
+    drop(file);
+    drop(client);
+
+    let (client, _) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let file = client.reclaim_durable(&ticket).await?;
+    file.write_all_at(b"second", 7).await?;
+    file.close().await?;
+    let data = client.read_file(&path).await?;
+    assert_eq!(data, b"first, second");
+
+    // Once closed, the handle can no longer be reclaimed.
+    let result = client.reclaim_durable(&ticket).await;
+    assert!(matches!(result, Err(Error::DurableReclaimFailed { .. })));
+
+    client.delete(&path).await?;
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a server with durable handles enabled"]
+async fn test_reconnect_durable_file() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.clone().with_path(FILE_NAME);
+
+    let args = FileCreateArgs {
+        durable: Some(DurableOpenOptions::default()),
+        ..FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new())
+    };
+    let file = client.create_file(&path, &args).await?.unwrap_file();
+    file.write_all_at(b"before, ", 0).await?;
+
+    // Lose the connection between operations, and connect the share again.
+    client.abandon().await?;
+    let (new_client, _) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let tree = new_client.get_tree(&share_path).await?;
+    let reconnected = file.reconnect(&tree).await?;
+    reconnected.write_all_at(b"after", 8).await?;
+
+    // The lost handle is replaced by the reconnected one.
+    let result = file.reconnect(&tree).await;
+    assert!(matches!(result, Err(Error::InvalidState(_))));
+    drop(file);
+
+    reconnected.close().await?;
+    let data = new_client.read_file(&path).await?;
+    assert_eq!(data, b"before, after");
+
+    new_client.delete(&path).await?;
+    Ok(())
+}
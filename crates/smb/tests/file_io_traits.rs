@@ -0,0 +1,92 @@
+//! Read/Write/Seek trait implementations of File tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{FileAccessMask, FileCreateArgs};
+
+const SOURCE_FILE: &str = "io_traits_source.bin";
+const DESTINATION_FILE: &str = "io_traits_destination.bin";
+/// Larger than the maximum read and write sizes common servers negotiate,
+/// so copying has to be split into several requests.
+const SIZE: usize = 9 * 1024 * 1024 + 0x321;
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_copy_between_files() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::SeekFrom;
+    #[cfg(not(feature = "async"))]
+    use std::io::{Read, Seek, Write};
+    #[cfg(feature = "async")]
+    use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let source_path = share_path.clone().with_path(SOURCE_FILE);
+    let destination_path = share_path.with_path(DESTINATION_FILE);
+    let content = (0..SIZE).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    client
+        .write_file(&source_path, &content, &Default::default())
+        .await?;
+
+    let mut source = client
+        .create_file(
+            &source_path,
+            &FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_read(true)),
+        )
+        .await?
+        .unwrap_file();
+    let mut destination = client
+        .create_file(
+            &destination_path,
+            &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file();
+
+    #[cfg(feature = "async")]
+    let copied = tokio::io::copy(&mut source, &mut destination).await?;
+    #[cfg(not(feature = "async"))]
+    let copied = std::io::copy(&mut source, &mut destination)?;
+    assert_eq!(copied, SIZE as u64);
+    // Qualified, since File::flush takes precedence over the trait methods.
+    #[cfg(feature = "async")]
+    AsyncWriteExt::flush(&mut destination).await?;
+    #[cfg(not(feature = "async"))]
+    Write::flush(&mut destination)?;
+
+    // Seek back and overwrite a part of the destination, then read it again.
+    #[cfg(feature = "async")]
+    {
+        destination.seek(SeekFrom::Start(0x1000)).await?;
+        destination.write_all(&[0xff; 0x10]).await?;
+        source.seek(SeekFrom::End(-0x10)).await?;
+        let mut tail = Vec::new();
+        source.read_to_end(&mut tail).await?;
+        assert_eq!(tail, content[SIZE - 0x10..]);
+    }
+    #[cfg(not(feature = "async"))]
+    {
+        destination.seek(SeekFrom::Start(0x1000))?;
+        destination.write_all(&[0xff; 0x10])?;
+        source.seek(SeekFrom::End(-0x10))?;
+        let mut tail = Vec::new();
+        source.read_to_end(&mut tail)?;
+        assert_eq!(tail, content[SIZE - 0x10..]);
+    }
+
+    source.close().await?;
+    destination.close().await?;
+
+    let mut expected = content;
+    expected[0x1000..0x1010].fill(0xff);
+    let data = client.read_file(&destination_path).await?;
+    assert!(data == expected);
+
+    client.delete(&source_path).await?;
+    client.delete(&destination_path).await?;
+    Ok(())
+}
@@ -0,0 +1,203 @@
+//! Client::write_file / Client::read_file, File::write_all_at / File::read_all_at and File::read_stream tests.
+
+mod common;
+
+use common::{
+    TestConstants, default_connection_config, make_server_connection, make_server_connection_ex,
+};
+use serial_test::serial;
+use smb::client::{WriteFileMode, WriteFileOptions};
+use smb::{ClientConfig, ConnectionConfig, Error, FileAccessMask, FileAttributes, FileCreateArgs};
+use smb_msg::CreateOptions;
+
+const FILE_NAME: &str = "file_ops_test.bin";
+const SIZE_LIMIT: u64 = 0x10_0000;
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_write_and_read_file() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection_ex(
+        TestConstants::DEFAULT_SHARE,
+        ClientConfig {
+            connection: default_connection_config(),
+            read_file_size_limit: Some(SIZE_LIMIT),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let path = share_path.clone().with_path(FILE_NAME);
+    let connection = client.get_connection(share_path.server()).await?;
+    let max_write_size = connection.conn_info().unwrap().negotiation.max_write_size as usize;
+
+    // Empty buffer creates an empty file.
+    let create_new = WriteFileOptions {
+        mode: WriteFileMode::CreateNew,
+        ..Default::default()
+    };
+    client.write_file(&path, &[], &create_new).await?;
+    let data = client.read_file(&path).await?;
+    assert!(data.is_empty());
+
+    // Creating an existing file fails.
+    let result = client.write_file(&path, b"data", &create_new).await;
+    assert!(result.is_err());
+
+    // Exactly the maximum write size, and one byte more.
+    for size in [max_write_size, max_write_size + 1] {
+        let content = (0..size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        client
+            .write_file(&path, &content, &Default::default())
+            .await?;
+        let data = client.read_file(&path).await?;
+        assert_eq!(data, content);
+    }
+
+    // Overwriting a longer file truncates it, and appending extends it.
+    client
+        .write_file(&path, b"short", &Default::default())
+        .await?;
+    let append = WriteFileOptions {
+        mode: WriteFileMode::Append,
+        ..Default::default()
+    };
+    client.write_file(&path, b" file", &append).await?;
+    let data = client.read_file(&path).await?;
+    assert_eq!(data, b"short file");
+
+    // Files above the size limit are not read.
+    client
+        .write_file(
+            &path,
+            &vec![0; SIZE_LIMIT as usize + 1],
+            &Default::default(),
+        )
+        .await?;
+    let result = client.read_file(&path).await;
+    assert!(matches!(
+        result,
+        Err(Error::FileTooLarge {
+            size,
+            limit: SIZE_LIMIT
+        }) if size == SIZE_LIMIT + 1
+    ));
+
+    client.delete(&path).await?;
+    Ok(())
+}
+
+/// Reads and writes of multiple requests, with few credits, so that the pipelined requests are small.
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_pipelined_read_write() -> Result<(), Box<dyn std::error::Error>> {
+    const SIZE: usize = 0x10_0000 + 0x1234;
+    let (client, share_path) = make_server_connection(
+        TestConstants::DEFAULT_SHARE,
+        Some(ConnectionConfig {
+            credits_backlog: Some(8),
+            io_pipeline_depth: Some(4),
+            ..default_connection_config()
+        }),
+    )
+    .await?;
+    let path = share_path.clone().with_path(FILE_NAME);
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new()),
+        )
+        .await?
+        .unwrap_file();
+
+    let content = (0..SIZE).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    file.write_all_at(&content, 0).await?;
+    file.close().await?;
+
+    let data = client.read_file(&path).await?;
+    assert_eq!(data, content);
+    // Reads beyond the end of the file stop there.
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_file_read_data(true)
+                    .with_synchronize(true),
+            ),
+        )
+        .await?
+        .unwrap_file();
+    let mut data = vec![0; SIZE + 0x30000];
+    let read = file.read_all_at(&mut data, 0).await?;
+    assert_eq!(read, SIZE);
+    assert_eq!(&data[..SIZE], &content);
+    file.close().await?;
+
+    client.delete(&path).await?;
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_read_stream() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "async")]
+    use futures_util::StreamExt;
+    use sha2::{Digest, Sha256};
+
+    const SIZE: usize = 0x30_0000 + 0x1234;
+    const STREAM_FILE_NAME: &str = "file_ops_read_stream.bin";
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.clone().with_path(STREAM_FILE_NAME);
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new()),
+        )
+        .await?
+        .unwrap_file();
+    let content = (0..SIZE).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    file.write_all_at(&content, 0).await?;
+    file.close().await?;
+
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_file_read_data(true)
+                    .with_synchronize(true),
+            ),
+        )
+        .await?
+        .unwrap_file();
+    let mut data = vec![0; SIZE];
+    let read = file.read_all_at(&mut data, 0).await?;
+    assert_eq!(read, SIZE);
+    let expected = Sha256::digest(&data);
+
+    // The range ends past the end of the file, where the stream stops.
+    let chunks = file.read_stream(0..(SIZE as u64 + 0x20000), 0x10000, 4);
+    #[cfg(feature = "async")]
+    let chunks = chunks.collect::<Vec<_>>().await;
+    let mut hasher = Sha256::new();
+    let mut streamed = 0;
+    for chunk in chunks {
+        let chunk = chunk?;
+        streamed += chunk.len();
+        hasher.update(&chunk);
+    }
+    assert_eq!(streamed, SIZE);
+    assert_eq!(hasher.finalize(), expected);
+    file.close().await?;
+
+    client.delete(&path).await?;
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! File::flush / ResourceHandle::flush tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{DirAccessMask, Error, FileAccessMask, FileAttributes, FileCreateArgs, ResourceHandle};
+use smb_msg::CreateOptions;
+
+const FILE_NAME: &str = "flush_test.bin";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_write_flush_read() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.clone().with_path(FILE_NAME);
+
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new()),
+        )
+        .await?
+        .unwrap_file();
+    let content = (0..0x1000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    file.write_block(&content, 0, None).await?;
+    file.flush().await?;
+    let mut data = vec![0; content.len()];
+    let read = file.read_block(&mut data, 0, None, false).await?;
+    assert_eq!(read, content.len());
+    assert_eq!(data, content);
+    file.close().await?;
+
+    // Read-only handles can not be flushed.
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_file_read_data(true)
+                    .with_synchronize(true),
+            ),
+        )
+        .await?
+        .unwrap_file();
+    let result = ResourceHandle::flush(&file).await;
+    assert!(matches!(result, Err(Error::MissingPermissions(_))));
+    let result = file.flush().await;
+    assert_eq!(
+        result.unwrap_err().kind(),
+        std::io::ErrorKind::PermissionDenied
+    );
+    file.close().await?;
+
+    // Directories opened for adding files can be flushed.
+    let directory = client
+        .create_file(
+            &share_path,
+            &FileCreateArgs::make_open_existing(
+                DirAccessMask::new()
+                    .with_list_directory(true)
+                    .with_add_file(true)
+                    .with_synchronize(true)
+                    .into(),
+            ),
+        )
+        .await?
+        .unwrap_dir();
+    directory.flush().await?;
+    directory.close().await?;
+
+    client.delete(&path).await?;
+    Ok(())
+}
@@ -0,0 +1,34 @@
+//! Tree::query_fs_info tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{FileFsAttributeInformation, FileFsFullSizeInformation, FileFsSizeInformation};
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_tree_fs_info() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let tree = client.get_tree(&share_path).await?;
+
+    let size = tree.query_fs_info::<FileFsSizeInformation>().await?;
+    assert!(size.total_allocation_units > 0);
+    assert!(size.available_allocation_units <= size.total_allocation_units);
+    assert!(size.sectors_per_allocation_unit > 0 && size.bytes_per_sector > 0);
+
+    let full_size = tree.query_fs_info::<FileFsFullSizeInformation>().await?;
+    assert_eq!(
+        full_size.total_allocation_units,
+        size.total_allocation_units
+    );
+
+    let attributes = tree.query_fs_info::<FileFsAttributeInformation>().await?;
+    assert!(attributes.maximum_component_name_length > 0);
+    assert!(attributes.attributes.case_preserved_names());
+    assert!(!attributes.file_system_name.to_string().is_empty());
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Tree::exists / metadata / create_dir(_all) / remove_file / remove_dir tests, and their Client counterparts.
+//! Also covers POSIX delete semantics of the remove helpers.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{Error, FileAttributes, FileCreateArgs};
+use smb_fscc::FileFsAttributeInformation;
+use smb_msg::CreateOptions;
+
+const DIR_NAME: &str = "fs_ops_test";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_tree_fs_ops() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let tree = client.get_tree(&share_path).await?;
+
+    let nested = format!(r"{DIR_NAME}\a\b");
+    let exists = tree.exists(DIR_NAME).await?;
+    assert!(!exists);
+    tree.create_dir_all(&nested).await?;
+    // Existing components, including the directory itself, are tolerated.
+    tree.create_dir_all(&nested).await?;
+    let exists = tree.exists(&nested).await?;
+    assert!(exists);
+    let result = tree.create_dir(&nested).await;
+    assert!(result.is_err());
+
+    let file_name = format!(r"{nested}\file.txt");
+    client
+        .write_file(
+            &share_path.clone().with_path(&file_name),
+            b"content",
+            &Default::default(),
+        )
+        .await?;
+    let metadata = tree.metadata(&file_name).await?;
+    assert_eq!(metadata.standard.end_of_file, 7);
+    assert!(!metadata.basic.file_attributes.directory());
+    let metadata = tree.metadata(&nested).await?;
+    assert!(metadata.basic.file_attributes.directory());
+
+    // Removing with the wrong type, or removing a non-empty directory, fails.
+    let result = tree.remove_dir(&file_name).await;
+    assert!(result.is_err());
+    let result = tree.remove_file(&nested).await;
+    assert!(result.is_err());
+    let result = tree.remove_dir(&nested).await;
+    assert!(matches!(result, Err(Error::DirectoryNotEmpty { .. })));
+
+    tree.remove_file(&file_name).await?;
+    let exists = tree.exists(&file_name).await?;
+    assert!(!exists);
+    for dir in [nested.as_str(), &format!(r"{DIR_NAME}\a"), DIR_NAME] {
+        tree.remove_dir(dir).await?;
+    }
+    let exists = tree.exists(DIR_NAME).await?;
+    assert!(!exists);
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_client_fs_ops() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+
+    let dir = share_path.clone().with_path(DIR_NAME);
+    let nested = share_path.clone().with_path(&format!(r"{DIR_NAME}\nested"));
+    client.create_dir_all(&nested).await?;
+    let metadata = client.metadata(&nested).await?;
+    assert!(metadata.basic.file_attributes.directory());
+    let result = client.remove_dir(&dir).await;
+    assert!(matches!(result, Err(Error::DirectoryNotEmpty { .. })));
+
+    client.remove_dir(&nested).await?;
+    client.remove_dir(&dir).await?;
+    let exists = client.exists(&dir).await?;
+    assert!(!exists);
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_remove_file_while_open() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let tree = client.get_tree(&share_path).await?;
+    let posix_unlink = tree
+        .query_fs_info::<FileFsAttributeInformation>()
+        .await?
+        .attributes
+        .supports_posix_unlink_rename();
+
+    let file_name = "fs_ops_posix_delete.txt";
+    let file = tree
+        .create(
+            file_name,
+            &FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new()),
+        )
+        .await?
+        .unwrap_file();
+    tree.remove_file(file_name).await?;
+
+    // With POSIX semantics, the name is gone while the other handle is still open.
+    // Otherwise, it is deleted only once the other handle is closed.
+    let exists = tree.exists(file_name).await;
+    if posix_unlink {
+        assert!(!exists?);
+    }
+    file.close().await?;
+    let exists = tree.exists(file_name).await?;
+    assert!(!exists);
+    Ok(())
+}
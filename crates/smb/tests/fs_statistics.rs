@@ -0,0 +1,32 @@
+//! File system statistics tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::tree::ProcessorStatistics;
+use smb::{FileSystemStatisticsData, FileSystemType};
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a Windows server with an NTFS share"]
+async fn test_filesystem_statistics() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let tree = client.get_tree(&share_path).await?;
+
+    let report = tree.filesystem_statistics().await?;
+    assert_eq!(report.totals.file_system_type, FileSystemType::Ntfs);
+    assert!(matches!(
+        report.totals.data,
+        FileSystemStatisticsData::Ntfs(_)
+    ));
+    let processors = match &report.per_processor {
+        ProcessorStatistics::Ex(processors) => processors.len(),
+        ProcessorStatistics::Legacy(processors) => processors.len(),
+    };
+    assert!(processors > 0);
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! Guest and anonymous sessions tests, against the `guest ok = yes` share of the test server.
+
+mod common;
+
+use common::{TestConstants, TestEnv, default_connection_config, smb_tests_server};
+use serial_test::serial;
+use smb::{
+    Client, ClientConfig, ConnectionConfig, Credentials, DirAccessMask, Directory, Error,
+    FileCreateArgs, FileDirectoryInformation, UncPath,
+};
+use std::sync::Arc;
+
+#[cfg(feature = "async")]
+use futures_util::StreamExt;
+
+fn guest_connection_config() -> ConnectionConfig {
+    ConnectionConfig {
+        allow_unsigned_guest_access: true,
+        ..default_connection_config()
+    }
+}
+
+/// Connects to the public share with the credentials, and lists its root directory.
+#[maybe_async::maybe_async]
+async fn list_public_share(credentials: Credentials) -> smb::Result<()> {
+    let client = Client::new(ClientConfig {
+        connection: guest_connection_config(),
+        ..Default::default()
+    });
+    let share_path =
+        UncPath::new(&smb_tests_server())?.with_share(TestConstants::PUBLIC_GUEST_SHARE)?;
+    client.share_connect_with(&share_path, credentials).await?;
+
+    let directory = client
+        .create_file(
+            &share_path,
+            &FileCreateArgs::make_open_existing(
+                DirAccessMask::new()
+                    .with_list_directory(true)
+                    .with_synchronize(true)
+                    .into(),
+            ),
+        )
+        .await?
+        .unwrap_dir();
+    let directory = Arc::new(directory);
+    let entries = Directory::query::<FileDirectoryInformation>(&directory, "*").await?;
+    #[cfg(feature = "async")]
+    let entries = entries.collect::<Vec<_>>().await;
+    let names = entries
+        .into_iter()
+        .map(|entry| entry.map(|entry| entry.file_name.to_string()))
+        .collect::<smb::Result<Vec<_>>>()?;
+    assert!(names.iter().any(|name| name == "."));
+    directory.close().await?;
+
+    client.close().await
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_anonymous_list_dir() -> smb::Result<()> {
+    list_public_share(Credentials::Anonymous).await
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_guest_list_dir() -> smb::Result<()> {
+    list_public_share(Credentials::Guest).await
+}
+
+/// An unknown user, which the test server logs in as a guest, is refused when `refuse_guest_fallback` is set.
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_refuse_guest_fallback() -> smb::Result<()> {
+    let client = Client::new(ClientConfig {
+        connection: ConnectionConfig {
+            refuse_guest_fallback: true,
+            ..guest_connection_config()
+        },
+        ..Default::default()
+    });
+    let share_path =
+        UncPath::new(&smb_tests_server())?.with_share(TestConstants::PUBLIC_GUEST_SHARE)?;
+    let result = client
+        .share_connect(&share_path, TestEnv::GUEST_USER, String::new())
+        .await;
+    assert!(matches!(result, Err(Error::GuestFallbackRefused { .. })));
+    let session = client.get_session(&share_path).await;
+    assert!(matches!(session, Err(Error::NotFound(_))));
+
+    // Explicit guest credentials are not affected.
+    client
+        .share_connect_with(&share_path, Credentials::Guest)
+        .await?;
+    client.close().await
+}
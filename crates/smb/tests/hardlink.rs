@@ -0,0 +1,75 @@
+//! ResourceHandle::hardlink_to tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{Client, Error, FileAccessMask, FileCreateArgs, FileInternalInformation, UncPath};
+
+const LINK_FILE: &str = "hardlink_test.txt";
+const LINK_NAME: &str = "hardlink_test_link.txt";
+
+/// Returns the file ID of the file at the path. All the hard links of a file have the same ID.
+#[maybe_async::maybe_async]
+async fn file_id(client: &Client, path: &UncPath) -> smb::Result<u64> {
+    let file = client
+        .create_file(
+            path,
+            &FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_file_read_attributes(true)
+                    .with_synchronize(true),
+            ),
+        )
+        .await?
+        .unwrap_file();
+    let info = file.query_info::<FileInternalInformation>().await;
+    file.close().await?;
+    Ok(info?.index_number)
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_hardlink() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.clone().with_path(LINK_FILE);
+    let link = share_path.clone().with_path(LINK_NAME);
+    client
+        .write_file(&path, b"linked", &Default::default())
+        .await?;
+
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_open_existing(FileAccessMask::standard_read()),
+        )
+        .await?
+        .unwrap_file();
+    // Forward slashes and a leading separator are sent relative to the share root.
+    file.hardlink_to(&format!("/{LINK_NAME}"), false).await?;
+    // Linking again fails, unless replacing the existing link.
+    let collision = file.hardlink_to(LINK_NAME, false).await;
+    assert!(matches!(
+        collision,
+        Err(Error::ObjectNameCollision { path }) if path == LINK_NAME
+    ));
+    file.hardlink_to(LINK_NAME, true).await?;
+    file.close().await?;
+
+    // Both names resolve to the same file.
+    let file_id_a = file_id(&client, &path).await?;
+    let file_id_b = file_id(&client, &link).await?;
+    assert_eq!(file_id_a, file_id_b);
+    let content = client.read_file(&link).await?;
+    assert_eq!(content, b"linked");
+
+    // Deleting one of the names keeps the file.
+    client.delete(&path).await?;
+    let content = client.read_file(&link).await?;
+    assert_eq!(content, b"linked");
+    client.delete(&link).await?;
+    Ok(())
+}
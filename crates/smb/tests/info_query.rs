@@ -34,7 +34,7 @@ async fn test_file_query_information() -> Result<(), Box<dyn std::error::Error>>
     })
     .await?;
 
-    let test_result = do_test_query_information(&file).await;
+    let test_result = do_test_query_information(file).await;
 
     file.close().await?;
     client.close().await?;
@@ -42,6 +42,88 @@ async fn test_file_query_information() -> Result<(), Box<dyn std::error::Error>>
     Ok(test_result?)
 }
 
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_file_extended_attributes() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let file = client
+        .create_file(
+            &share_path.clone().with_path("extended_attributes.txt"),
+            &FileCreateArgs::make_overwrite(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file();
+    file.set_info(FileDispositionInformation {
+        delete_pending: true.into(),
+    })
+    .await?;
+
+    // An odd-sized first entry, so that the next one must be aligned.
+    file.set_ea(&[("USER.FIRST", b"abc"), ("USER.SECOND", b"second value")])
+        .await?;
+    let eas = file.get_ea(&["USER.FIRST", "USER.SECOND"]).await?;
+    assert_eq!(
+        eas,
+        [
+            ("USER.FIRST".to_string(), b"abc".to_vec()),
+            ("USER.SECOND".to_string(), b"second value".to_vec())
+        ]
+    );
+
+    // An empty value removes the attribute.
+    file.set_ea(&[("USER.FIRST", b"")]).await?;
+    let eas = file.get_ea(&["USER.FIRST"]).await?;
+    assert_eq!(eas, [("USER.FIRST".to_string(), vec![])]);
+
+    file.close().await?;
+    client.close().await?;
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_file_set_times_and_attributes() -> Result<(), Box<dyn std::error::Error>> {
+    use std::time::{Duration, SystemTime};
+
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let file = client
+        .create_file(
+            &share_path.clone().with_path("set_times.txt"),
+            &FileCreateArgs::make_overwrite(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file();
+    file.set_info(FileDispositionInformation {
+        delete_pending: true.into(),
+    })
+    .await?;
+    let before = file.query_info::<FileBasicInformation>().await?;
+
+    // Setting only the modification time leaves the creation time untouched.
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    file.set_times(None, None, Some(modified)).await?;
+    let after = file.query_info::<FileBasicInformation>().await?;
+    assert_eq!(SystemTime::from(after.last_write_time), modified);
+    assert_eq!(after.creation_time, before.creation_time);
+
+    file.set_attributes(FileAttributes::new().with_hidden(true).with_archive(true))
+        .await?;
+    let after = file.query_info::<FileBasicInformation>().await?;
+    assert!(after.file_attributes.hidden());
+    assert!(!after.file_attributes.readonly());
+    assert_eq!(SystemTime::from(after.last_write_time), modified);
+
+    file.close().await?;
+    client.close().await?;
+    Ok(())
+}
+
 #[maybe_async::maybe_async]
 async fn do_test_query_information(file: &File) -> smb::Result<()> {
     const TEST_DATA: &[u8] = b"Hello, world!";
@@ -73,7 +155,7 @@ async fn do_test_query_information(file: &File) -> smb::Result<()> {
 
     let std_info = file.query_info::<FileStandardInformation>().await?;
     assert_eq!(std_info.end_of_file, TEST_DATA.len() as u64);
-    assert_eq!(std_info.allocation_size >= TEST_DATA.len() as u64, true);
+    assert!(std_info.allocation_size >= TEST_DATA.len() as u64);
     assert_eq!(std_info.delete_pending, true.into());
 
     file.query_info::<FileStreamInformation>().await?;
@@ -0,0 +1,72 @@
+//! File::lock_range / File::unlock_range tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{Client, Error, File, FileAttributes, FileCreateArgs, UncPath};
+use smb_msg::{CreateOptions, Status};
+
+const FILE_NAME: &str = "lock_test.bin";
+
+#[maybe_async::maybe_async]
+async fn open_rw(client: &Client, path: &UncPath) -> smb::Result<File> {
+    Ok(client
+        .create_file(
+            path,
+            &FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new()),
+        )
+        .await?
+        .unwrap_file())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_lock_ranges() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.clone().with_path(FILE_NAME);
+    client
+        .write_file(&path, &[0; 0x100], &Default::default())
+        .await?;
+    let first = open_rw(&client, &path).await?;
+    let second = open_rw(&client, &path).await?;
+
+    // Shared locks of the same range, from two handles.
+    first.lock_range(0, 0x10, false, true).await?;
+    second.lock_range(0, 0x10, false, true).await?;
+
+    // An overlapping exclusive lock conflicts with the shared lock of the other handle.
+    let result = second.lock_range(8, 0x10, true, true).await;
+    assert!(matches!(
+        result,
+        Err(Error::ServerError(Status::LockNotGranted, _))
+    ));
+    // Ranges that do not overlap are not affected.
+    second.lock_range(0x10, 0x10, true, true).await?;
+
+    first.unlock_range(0, 0x10).await?;
+    second.unlock_range(0, 0x10).await?;
+    first.lock_range(0, 0x10, true, true).await?;
+    let result = second.lock_range(0, 1, false, true).await;
+    assert!(matches!(
+        result,
+        Err(Error::ServerError(Status::LockNotGranted, _))
+    ));
+
+    // Only locked ranges can be unlocked.
+    let result = second.unlock_range(0, 0x10).await;
+    assert!(matches!(
+        result,
+        Err(Error::ServerError(Status::RangeNotLocked, _))
+    ));
+    first.unlock_range(0, 0x10).await?;
+    second.unlock_range(0x10, 0x10).await?;
+
+    first.close().await?;
+    second.close().await?;
+    client.delete(&path).await?;
+    Ok(())
+}
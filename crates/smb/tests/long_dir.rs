@@ -1,7 +1,10 @@
 #![cfg(all(feature = "sign", feature = "encrypt"))]
 
 use serial_test::serial;
-use smb::{ConnectionConfig, Directory, connection::EncryptionMode, sync_helpers::*, tree::Tree};
+use smb::{
+    ConnectionConfig, Directory, QueryDirectoryOptions, connection::EncryptionMode,
+    sync_helpers::*, tree::Tree,
+};
 use smb_fscc::*;
 use smb_msg::CreateOptions;
 use std::sync::Arc;
@@ -14,7 +17,7 @@ use common::make_server_connection;
 use smb::FileCreateArgs;
 
 const LONG_DIR: &str = "longdir";
-const FILE_PREFIX: &'static str = "test_file_with_a_long_name_to_take_up_some_space_when_dir_query_performed_and_consume_buffer_size_";
+const FILE_PREFIX: &str = "test_file_with_a_long_name_to_take_up_some_space_when_dir_query_performed_and_consume_buffer_size_";
 const NUM_FILES: usize = 100;
 
 /// This test is to check if we can iterate over a long directory
@@ -88,7 +91,10 @@ async fn test_smb_iterating_long_directory() -> Result<(), Box<dyn std::error::E
     let found = Directory::query_with_options::<FileFullDirectoryInformation>(
         &directory,
         &format!("{}*", FILE_PREFIX),
-        SMALL_BUFFER_SIZE_FOR_MANY_ITERATIONS,
+        &QueryDirectoryOptions {
+            buffer_size: Some(SMALL_BUFFER_SIZE_FOR_MANY_ITERATIONS),
+            ..Default::default()
+        },
     )
     .await?
     .fold(0, |sum, entry| {
@@ -171,3 +177,139 @@ pub async fn remove_file_by_name(tree: &Tree, file_name: &str) -> smb::Result<()
     .await?;
     Ok(())
 }
+
+/// Consumes the stream slowly, across multiple query round trips,
+/// so that the query loop repeatedly waits for the consumer to make room for more entries.
+#[cfg(feature = "async")]
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+#[serial]
+async fn test_smb_iterating_long_directory_slow_consumer() -> Result<(), Box<dyn std::error::Error>>
+{
+    const SLOW_DIR: &str = "longdir_slow";
+    const NUM_SLOW_FILES: usize = 100;
+
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let tree = client.get_tree(&share_path).await?;
+    tree.create_dir(SLOW_DIR).await?;
+    for i in 0..NUM_SLOW_FILES {
+        let file_name = format!("{SLOW_DIR}\\{FILE_PREFIX}{i}");
+        tree.create(
+            &file_name,
+            &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file()
+        .close()
+        .await?;
+    }
+
+    let directory = Arc::new(
+        tree.open_existing(
+            SLOW_DIR,
+            DirAccessMask::new()
+                .with_list_directory(true)
+                .with_synchronize(true)
+                .into(),
+        )
+        .await?
+        .unwrap_dir(),
+    );
+    let mut stream = Directory::query_with_options::<FileNamesInformation>(
+        &directory,
+        &format!("{FILE_PREFIX}*"),
+        &QueryDirectoryOptions {
+            // A few entries per response.
+            buffer_size: Some(0x300),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let mut found = vec![];
+    while let Some(entry) = stream.next().await {
+        found.push(entry?.file_name.to_string());
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    drop(stream);
+    directory.close().await?;
+
+    found.sort();
+    let mut expected = (0..NUM_SLOW_FILES)
+        .map(|i| format!("{FILE_PREFIX}{i}"))
+        .collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(found, expected);
+
+    for name in expected {
+        tree.remove_file(&format!("{SLOW_DIR}\\{name}")).await?;
+    }
+    tree.remove_dir(SLOW_DIR).await?;
+    Ok(())
+}
+
+/// Lists a directory with an entry of a maximum-length name, using query buffers that are too small for it,
+/// which are grown by the query directory loop, without losing the position of the enumeration.
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_smb_iterating_directory_small_buffer() -> Result<(), Box<dyn std::error::Error>> {
+    const SMALL_BUFFER_DIR: &str = "longdir_small_buffer";
+    let long_name = "n".repeat(255);
+    let mut expected = vec![long_name, "a.txt".to_string(), "b.txt".to_string()];
+    expected.sort();
+
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let tree = client.get_tree(&share_path).await?;
+    tree.create_dir(SMALL_BUFFER_DIR).await?;
+    for name in &expected {
+        tree.create(
+            &format!("{SMALL_BUFFER_DIR}\\{name}"),
+            &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file()
+        .close()
+        .await?;
+    }
+
+    let directory = Arc::new(
+        tree.open_existing(
+            SMALL_BUFFER_DIR,
+            DirAccessMask::new()
+                .with_list_directory(true)
+                .with_synchronize(true)
+                .into(),
+        )
+        .await?
+        .unwrap_dir(),
+    );
+    // 1 KiB fits a single entry of the long name, which 512 bytes do not.
+    for buffer_size in [0x400, 0x200] {
+        let entries = Directory::query_with_options::<FileIdBothDirectoryInformation>(
+            &directory,
+            "*",
+            &QueryDirectoryOptions {
+                buffer_size: Some(buffer_size),
+                ..Default::default()
+            },
+        )
+        .await?;
+        #[cfg(feature = "async")]
+        let entries = entries.collect::<Vec<_>>().await;
+        let mut found = vec![];
+        for entry in entries {
+            found.push(entry?.file_name.to_string());
+        }
+        found.sort();
+        assert_eq!(found, expected, "buffer size {buffer_size:#x}");
+    }
+    directory.close().await?;
+
+    for name in &expected {
+        tree.remove_file(&format!("{SMALL_BUFFER_DIR}\\{name}"))
+            .await?;
+    }
+    tree.remove_dir(SMALL_BUFFER_DIR).await?;
+    Ok(())
+}
@@ -0,0 +1,123 @@
+//! Client::exists / Client::stat and metadata cache tests.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{TestConstants, default_connection_config, make_server_connection_ex};
+use serial_test::serial;
+use smb::client::MetadataCacheStats;
+use smb::{ClientConfig, FileCreateArgs};
+
+const STAT_FILE: &str = "metadata_cache_test.txt";
+const REPEAT: u64 = 5;
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_exists_no_cache() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection_ex(
+        TestConstants::DEFAULT_SHARE,
+        ClientConfig {
+            connection: default_connection_config(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let path = share_path.clone().with_path(STAT_FILE);
+
+    let share_exists = client.exists(&share_path).await?;
+    assert!(share_exists);
+    let file_exists = client.exists(&path).await?;
+    assert!(!file_exists);
+    let child_exists = client.exists(&path.clone().with_add_path("child")).await?;
+    assert!(!child_exists);
+
+    client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file()
+        .close()
+        .await?;
+    for _ in 0..REPEAT {
+        let entry = client.stat(&path).await?.unwrap();
+        assert_eq!(entry.name(), STAT_FILE);
+        assert!(!entry.is_directory());
+    }
+    assert_eq!(client.metadata_cache_stats(), None);
+
+    client.delete(&path).await?;
+    let file_exists = client.exists(&path).await?;
+    assert!(!file_exists);
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_metadata_cache() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection_ex(
+        TestConstants::DEFAULT_SHARE,
+        ClientConfig {
+            connection: default_connection_config(),
+            metadata_cache_ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let path = share_path.clone().with_path(STAT_FILE);
+
+    // Negative results are cached as well.
+    for _ in 0..2 {
+        let file_exists = client.exists(&path).await?;
+        assert!(!file_exists);
+    }
+    assert_eq!(
+        client.metadata_cache_stats().unwrap(),
+        MetadataCacheStats {
+            hits: 1,
+            misses: 1,
+            entries: 1
+        }
+    );
+
+    // Creating the file through the client invalidates it.
+    client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file()
+        .close()
+        .await?;
+    for _ in 0..REPEAT {
+        let entry = client.stat(&path).await?.unwrap();
+        assert_eq!(entry.name(), STAT_FILE);
+    }
+    let stats = client.metadata_cache_stats().unwrap();
+    // Only the first stat after the creation queries the server.
+    assert_eq!((stats.hits, stats.misses), (REPEAT, 2));
+
+    let renamed = share_path.clone().with_path("metadata_cache_renamed.txt");
+    client.rename(&path, &renamed, false).await?;
+    let file_exists = client.exists(&path).await?;
+    assert!(!file_exists);
+    let renamed_exists = client.exists(&renamed).await?;
+    assert!(renamed_exists);
+
+    client.invalidate_metadata(&share_path);
+    assert_eq!(client.metadata_cache_stats().unwrap().entries, 0);
+
+    client.delete(&renamed).await?;
+    let renamed_exists = client.exists(&renamed).await?;
+    assert!(!renamed_exists);
+    Ok(())
+}
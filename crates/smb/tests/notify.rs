@@ -236,6 +236,138 @@ fn stream_iter_watch(sem: Arc<Semaphore>, r: Directory) {
     });
 }
 
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_smb_notify_stream_ends_on_close() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(
+        TestConstants::DEFAULT_SHARE,
+        ConnectionConfig {
+            encryption_mode: EncryptionMode::Disabled,
+            ..Default::default()
+        }
+        .into(),
+    )
+    .await?;
+    let dir = client
+        .create_file(
+            &share_path,
+            &FileCreateArgs::make_open_existing(
+                DirAccessMask::new().with_list_directory(true).into(),
+            ),
+        )
+        .await?
+        .unwrap_dir();
+
+    // Closing the directory makes the server clean up the pending watch, which ends the stream without errors.
+    let results = watch_until_closed(Arc::new(dir)).await?;
+    assert!(results.is_empty(), "Unexpected watch results: {results:?}");
+    Ok(())
+}
+
+#[maybe_async::async_impl]
+async fn watch_until_closed(
+    dir: Arc<Directory>,
+) -> smb::Result<Vec<smb::Result<FileNotifyInformation>>> {
+    use futures_util::StreamExt;
+    let stream = Directory::watch_stream(&dir, NotifyFilter::all(), false)?;
+    let watch = tokio::spawn(stream.collect::<Vec<_>>());
+    sleep(std::time::Duration::from_secs(1)).await;
+    dir.close().await?;
+    let results = tokio::time::timeout(std::time::Duration::from_secs(10), watch)
+        .await
+        .expect("The watch stream did not end after closing the directory")
+        .expect("watch task panicked");
+    Ok(results)
+}
+#[maybe_async::sync_impl]
+fn watch_until_closed(dir: Arc<Directory>) -> smb::Result<Vec<smb::Result<FileNotifyInformation>>> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn({
+        let dir = dir.clone();
+        move || {
+            let results = Directory::watch_stream(&dir, NotifyFilter::all(), false)
+                .map(|iterator| iterator.collect::<Vec<_>>());
+            tx.send(results).ok();
+        }
+    });
+    sleep(std::time::Duration::from_secs(1));
+    dir.close()?;
+    rx.recv_timeout(std::time::Duration::from_secs(10))
+        .expect("The watch iterator did not end after closing the directory")
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_smb_notify_abandoned_watch_is_cancelled() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(
+        TestConstants::DEFAULT_SHARE,
+        ConnectionConfig {
+            encryption_mode: EncryptionMode::Disabled,
+            ..Default::default()
+        }
+        .into(),
+    )
+    .await?;
+    let dir = client
+        .create_file(
+            &share_path,
+            &FileCreateArgs::make_open_existing(
+                DirAccessMask::new()
+                    .with_list_directory(true)
+                    .with_read_attributes(true)
+                    .into(),
+            ),
+        )
+        .await?
+        .unwrap_dir();
+
+    // Timing out cancels the pending watch, so the server completes it with STATUS_CANCELLED.
+    let result = dir
+        .watch_timeout(
+            NotifyFilter::all(),
+            false,
+            std::time::Duration::from_secs(1),
+        )
+        .await;
+    assert!(matches!(result, Err(smb::Error::Timeout(..))));
+    abandon_watch(&dir).await;
+
+    // The handle is still usable, and the responses of the cancelled watches do not confuse later ones.
+    for _ in 0..3 {
+        dir.query_info::<FileBasicInformation>().await?;
+    }
+    let result = dir
+        .watch_timeout(
+            NotifyFilter::all(),
+            false,
+            std::time::Duration::from_secs(1),
+        )
+        .await;
+    assert!(matches!(result, Err(smb::Error::Timeout(..))));
+    dir.close().await?;
+    Ok(())
+}
+
+/// Drops a pending watch, which cancels it.
+#[maybe_async::async_impl]
+async fn abandon_watch(dir: &Directory) {
+    let watch = dir.watch(NotifyFilter::all(), false);
+    let result = tokio::time::timeout(std::time::Duration::from_secs(1), watch).await;
+    assert!(result.is_err(), "Unexpected watch result: {result:?}");
+    // Let the cancellation complete.
+    sleep(std::time::Duration::from_millis(500)).await;
+}
+#[maybe_async::sync_impl]
+fn abandon_watch(_dir: &Directory) {
+    // Blocking watches are not dropped while waiting: they are abandoned by timing out only.
+}
+
 fn on_notification(sem: Arc<Semaphore>, notification: FileNotifyInformation) {
     if notification.action == NotifyAction::Removed {
         sem.add_permits(1);
@@ -270,3 +402,104 @@ async fn delete_file_from_another_connection(
     // We are exiting, and file is closed, and deleted!
     Ok(())
 }
+
+const WATCH_PATH_DIR: &str = "watch_path_test";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_watch_path_survives_recreate() -> Result<(), Box<dyn std::error::Error>> {
+    use smb::client::WatchEvent;
+
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let dir_path = share_path.clone().with_path(WATCH_PATH_DIR);
+    create_dir(&client, &dir_path).await?;
+
+    let mut watch = client
+        .watch_path(
+            &dir_path,
+            NotifyFilter::new().with_file_name(true).with_dir_name(true),
+            false,
+        )
+        .await?;
+
+    create_file(&client, &dir_path.join("before.txt")).await?;
+    let event = next_watch_event(&mut watch).await?;
+    assert_eq!(event, WatchEvent::Added(dir_path.join("before.txt")));
+
+    // Delete and re-create the watched directory, which is only deleted once the watch closes its handle.
+    client.delete(&dir_path.join("before.txt")).await?;
+    client.delete(&dir_path).await?;
+    let mut created = false;
+    for _ in 0..50 {
+        if create_dir(&client, &dir_path).await.is_ok() {
+            created = true;
+            break;
+        }
+        sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(created, "The watched directory was not re-created");
+
+    // Changes between the deletion and the re-opening may be missed.
+    loop {
+        let event = next_watch_event(&mut watch).await?;
+        if event == WatchEvent::Rescan {
+            break;
+        }
+    }
+    create_file(&client, &dir_path.join("after.txt")).await?;
+    let event = next_watch_event(&mut watch).await?;
+    assert_eq!(event, WatchEvent::Added(dir_path.join("after.txt")));
+
+    watch.stop();
+    drop(watch);
+    client.delete(&dir_path.join("after.txt")).await?;
+    client.delete(&dir_path).await?;
+    Ok(())
+}
+
+#[maybe_async::maybe_async]
+async fn create_dir(client: &smb::Client, path: &smb::UncPath) -> smb::Result<()> {
+    client
+        .create_file(
+            path,
+            &FileCreateArgs::make_create_new(
+                FileAttributes::new().with_directory(true),
+                smb_msg::CreateOptions::new().with_directory_file(true),
+            ),
+        )
+        .await?
+        .unwrap_dir()
+        .close()
+        .await
+}
+
+#[maybe_async::maybe_async]
+async fn create_file(client: &smb::Client, path: &smb::UncPath) -> smb::Result<()> {
+    client
+        .create_file(
+            path,
+            &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file()
+        .close()
+        .await
+}
+
+#[maybe_async::async_impl]
+async fn next_watch_event(
+    watch: &mut smb::client::WatchHandle,
+) -> smb::Result<smb::client::WatchEvent> {
+    use futures_util::StreamExt;
+    tokio::time::timeout(std::time::Duration::from_secs(30), watch.next())
+        .await
+        .expect("No watch event was received")
+        .expect("The watch ended")
+}
+#[maybe_async::sync_impl]
+fn next_watch_event(watch: &mut smb::client::WatchHandle) -> smb::Result<smb::client::WatchEvent> {
+    watch.next().expect("The watch ended")
+}
@@ -0,0 +1,61 @@
+//! Object ID tests.
+
+mod common;
+
+use std::env::var;
+
+use common::{TestConstants, TestEnv, make_server_connection};
+use serial_test::serial;
+use smb::{FileCreateArgs, UncPath};
+
+const OBJECT_ID_FILE: &str = "object_id_test.txt";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires two Windows servers, the second one set in SMB_RUST_TESTS_SECOND_SERVER"]
+async fn test_copy_object_id_between_servers() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let second_share_path =
+        UncPath::new(&var(TestEnv::SECOND_SERVER)?)?.with_share(TestConstants::DEFAULT_SHARE)?;
+    let user = var(TestEnv::USER).unwrap_or(TestEnv::DEFAULT_USER.to_string());
+    let password = var(TestEnv::PASSWORD).unwrap_or(TestEnv::DEFAULT_PASSWORD.to_string());
+    client
+        .share_connect(&second_share_path, &user, password)
+        .await?;
+
+    let source_path = share_path.with_path(OBJECT_ID_FILE);
+    let destination_path = second_share_path.with_path(OBJECT_ID_FILE);
+    let create_args = FileCreateArgs::make_create_new(Default::default(), Default::default());
+    let source = client
+        .create_file(&source_path, &create_args)
+        .await?
+        .unwrap_file();
+    let destination = client
+        .create_file(&destination_path, &create_args)
+        .await?
+        .unwrap_file();
+
+    let object_id = source.create_or_get_object_id().await?;
+    let destination_id = destination.get_object_id().await?;
+    assert_eq!(destination_id, None);
+
+    // Same as the object ID preservation of the CLI's copy command.
+    let source_id = source.get_object_id().await?.unwrap();
+    destination.set_object_id(source_id, false).await?;
+    let destination_id = destination.get_object_id().await?;
+    assert_eq!(destination_id, Some(object_id));
+
+    // Setting an object ID again requires replacing it.
+    let result = destination.set_object_id(object_id, false).await;
+    assert!(result.is_err());
+    destination.set_object_id(object_id, true).await?;
+
+    source.close().await?;
+    destination.close().await?;
+    client.delete(&source_path).await?;
+    client.delete(&destination_path).await?;
+    Ok(())
+}
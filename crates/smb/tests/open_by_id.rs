@@ -0,0 +1,98 @@
+//! Opening files by their file ID tests.
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{
+    CreateOptions, Directory, Error, FileAccessMask, FileAttributes, FileCreateArgs,
+    FileIdBothDirectoryInformation,
+};
+
+#[cfg(feature = "async")]
+use futures_util::StreamExt;
+
+const OPEN_BY_ID_DIR: &str = "open_by_id_test";
+const OPEN_BY_ID_FILE: &str = "file.txt";
+const OPEN_BY_ID_DATA: &[u8] = b"Opened by ID!";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a Windows server, Samba does not support opening files by ID"]
+async fn test_open_by_id() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let dir_path = share_path.clone().with_path(OPEN_BY_ID_DIR);
+    let file_path = share_path
+        .clone()
+        .with_path(&format!("{OPEN_BY_ID_DIR}\\{OPEN_BY_ID_FILE}"));
+
+    let directory = client
+        .create_file(
+            &dir_path,
+            &FileCreateArgs::make_create_new(
+                FileAttributes::new().with_directory(true),
+                CreateOptions::new().with_directory_file(true),
+            ),
+        )
+        .await?
+        .unwrap_dir();
+    let file = client
+        .create_file(
+            &file_path,
+            &FileCreateArgs::make_create_new(FileAttributes::new(), CreateOptions::new()),
+        )
+        .await?
+        .unwrap_file();
+    file.write_block(OPEN_BY_ID_DATA, 0, None).await?;
+    file.close().await?;
+
+    // Take the ID of the file from the listing of its directory.
+    let directory = Arc::new(directory);
+    let entries =
+        Directory::query::<FileIdBothDirectoryInformation>(&directory, OPEN_BY_ID_FILE).await?;
+    #[cfg(feature = "async")]
+    let entries = entries.collect::<Vec<_>>().await;
+    let entries = entries.into_iter().collect::<smb::Result<Vec<_>>>()?;
+    assert_eq!(entries.len(), 1);
+    let file_id = entries[0].file_id;
+    directory.close().await?;
+
+    let read_access = FileAccessMask::new()
+        .with_file_read_data(true)
+        .with_delete(true)
+        .with_synchronize(true);
+    let tree = client.get_tree(&share_path).await?;
+    let by_id = tree
+        .open_by_id(file_id, &FileCreateArgs::make_open_existing(read_access))
+        .await?
+        .unwrap_file();
+    assert!(by_id.opened_by_id().is_some());
+    let mut by_id_data = vec![0; OPEN_BY_ID_DATA.len()];
+    let read = by_id.read_block(&mut by_id_data, 0, None, false).await?;
+    assert_eq!(read, OPEN_BY_ID_DATA.len());
+
+    let by_name = client
+        .create_file(&file_path, &FileCreateArgs::make_open_existing(read_access))
+        .await?
+        .unwrap_file();
+    let mut by_name_data = vec![0; OPEN_BY_ID_DATA.len()];
+    by_name
+        .read_block(&mut by_name_data, 0, None, false)
+        .await?;
+    assert_eq!(by_id_data, by_name_data);
+    by_name.close().await?;
+
+    // The directory of a file opened by ID is unknown.
+    let result = by_id.rename("renamed.txt", &Default::default()).await;
+    assert!(matches!(result, Err(Error::InvalidState(_))));
+    by_id.close().await?;
+
+    client.delete(&file_path).await?;
+    client.delete(&dir_path).await?;
+    Ok(())
+}
@@ -0,0 +1,69 @@
+//! Oplock and lease break tests.
+
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use common::{TestConstants, make_server_connection, smb_tests_server};
+use serial_test::serial;
+use smb::connection::BreakNotification;
+use smb::{CreateOptions, DurableOpenOptions, FileAccessMask, FileAttributes, FileCreateArgs};
+
+const FILE_NAME: &str = "oplock_break_test.txt";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a server with durable handles enabled"]
+async fn test_lease_break_acknowledged() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.clone().with_path(FILE_NAME);
+
+    // Durable opens hold a lease, or a batch oplock.
+    let args = FileCreateArgs {
+        durable: Some(DurableOpenOptions::default()),
+        ..FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new())
+    };
+    let file = client.create_file(&path, &args).await?.unwrap_file();
+    file.write_all_at(b"cached", 0).await?;
+
+    let breaks = Arc::new(AtomicUsize::new(0));
+    let connection = client.get_connection(&smb_tests_server()).await?;
+    let breaks_ref = breaks.clone();
+    #[cfg(feature = "async")]
+    connection.on_lease_break(move |notification: BreakNotification| {
+        log::info!("Break: {notification:?}");
+        let breaks = breaks_ref.clone();
+        async move {
+            breaks.fetch_add(1, Ordering::SeqCst);
+        }
+    })?;
+    #[cfg(not(feature = "async"))]
+    connection.on_lease_break(move |notification: BreakNotification| {
+        log::info!("Break: {notification:?}");
+        breaks_ref.fetch_add(1, Ordering::SeqCst);
+    })?;
+
+    // Another client writing to the file breaks the first one's write caching.
+    let (other_client, _) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let start = Instant::now();
+    let other_file = other_client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_write(true)),
+        )
+        .await?
+        .unwrap_file();
+    // Without an acknowledgement, the server waits for the break to time out (35 seconds by default).
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert!(breaks.load(Ordering::SeqCst) >= 1);
+
+    other_file.close().await?;
+    file.close().await?;
+    client.delete(&path).await?;
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Server quirks tests.
+
+mod common;
+
+use common::{TestConstants, default_connection_config, make_server_connection};
+use serial_test::serial;
+use smb::connection::{ServerQuirks, ServerQuirksOverrides};
+use smb::{DirAccessMask, Directory, FileCreateArgs, FileDirectoryInformation};
+use std::sync::Arc;
+
+#[cfg(feature = "async")]
+use futures_util::StreamExt;
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_samba_quirks() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let connection = client.get_connection(share_path.server()).await?;
+    let quirks = &connection.conn_info().unwrap().quirks;
+    assert_eq!(quirks.get(), ServerQuirks::default());
+
+    // Opening and enumerating a directory does not trigger any quirk.
+    let directory = client
+        .create_file(
+            &share_path,
+            &FileCreateArgs::make_open_existing(
+                DirAccessMask::new()
+                    .with_list_directory(true)
+                    .with_synchronize(true)
+                    .into(),
+            ),
+        )
+        .await?
+        .unwrap_dir();
+    let directory = Arc::new(directory);
+    let entries = Directory::query::<FileDirectoryInformation>(&directory, "*").await?;
+    #[cfg(feature = "async")]
+    let entries = entries.collect::<Vec<_>>().await;
+    for entry in entries {
+        entry?;
+    }
+    directory.close().await?;
+    assert_eq!(quirks.get(), ServerQuirks::default());
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_quirks_overrides() -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = default_connection_config();
+    config.quirks = ServerQuirksOverrides {
+        no_query_on_disk_id: Some(true),
+        needs_dfs_flag_off: Some(true),
+        ..Default::default()
+    };
+    let (client, share_path) =
+        make_server_connection(TestConstants::DEFAULT_SHARE, Some(config)).await?;
+    let connection = client.get_connection(share_path.server()).await?;
+    assert_eq!(
+        connection.conn_info().unwrap().quirks.get(),
+        ServerQuirks {
+            no_query_on_disk_id: true,
+            needs_dfs_flag_off: true,
+            ..Default::default()
+        }
+    );
+
+    // Creates still work without the QFid context.
+    client
+        .create_file(
+            &share_path,
+            &FileCreateArgs::make_open_existing(
+                DirAccessMask::new()
+                    .with_list_directory(true)
+                    .with_synchronize(true)
+                    .into(),
+            ),
+        )
+        .await?
+        .unwrap_dir()
+        .close()
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Quota tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{FileAccessMask, FileCreateArgs, FileQuotaInformation, QuotaEntry, SID};
+use smb_msg::QueryQuotaInfo;
+
+const GIB: u64 = 1 << 30;
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a Windows server with quotas enabled"]
+async fn test_quota_entries() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let tree = client.get_tree(&share_path).await?;
+
+    let sid: SID = SID::S_EVERYONE.parse()?;
+    tree.set_quota(&QuotaEntry::new(sid.clone(), 8 * GIB, 10 * GIB))
+        .await?;
+
+    let entry = tree.get_quota(&sid).await?.unwrap();
+    assert_eq!(entry.sid, sid);
+    assert_eq!((entry.threshold, entry.limit), (8 * GIB, 10 * GIB));
+
+    let entries = tree.list_quotas().await?;
+    assert!(entries.iter().any(|e| e == &entry));
+
+    tree.set_default_quota(QuotaEntry::UNLIMITED, QuotaEntry::UNLIMITED)
+        .await?;
+    tree.set_quota(&QuotaEntry::new(
+        sid,
+        QuotaEntry::UNLIMITED,
+        QuotaEntry::UNLIMITED,
+    ))
+    .await?;
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a Windows server with quotas enabled"]
+async fn test_set_and_query_multiple_quota_entries() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let root = client
+        .create_file(
+            &share_path,
+            &FileCreateArgs::make_open_existing(FileAccessMask::standard_rw()),
+        )
+        .await?
+        .unwrap_dir();
+
+    let sids: [SID; 2] = [SID::S_EVERYONE.parse()?, SID::S_ADMINISTRATORS.parse()?];
+    let entries = sids
+        .iter()
+        .map(|sid| QuotaEntry::new(sid.clone(), 8 * GIB, 10 * GIB).into())
+        .collect::<Vec<FileQuotaInformation>>();
+    root.set_quota_entries(&entries).await?;
+
+    let queried = root
+        .query_quota_info(QueryQuotaInfo::for_sids(sids.clone()))
+        .await?;
+    assert_eq!(queried.len(), sids.len());
+    for (entry, sid) in queried.iter().zip(&sids) {
+        assert_eq!(&entry.sid, sid);
+        assert_eq!(
+            (entry.quota_threshold, entry.quota_limit),
+            (8 * GIB, 10 * GIB)
+        );
+    }
+
+    let unlimited = sids
+        .into_iter()
+        .map(|sid| QuotaEntry::new(sid, QuotaEntry::UNLIMITED, QuotaEntry::UNLIMITED).into())
+        .collect::<Vec<FileQuotaInformation>>();
+    root.set_quota_entries(&unlimited).await?;
+    root.close().await?;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! Raw message tests (crate feature `unstable-raw`).
+#![cfg(feature = "unstable-raw")]
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::connection::raw::{RawContent, SendOptions};
+use smb::{Command, DirAccessMask, EchoRequest, FileCreateArgs, RequestContent, Status};
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_raw_echo() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let connection = client.get_connection(share_path.server()).await?;
+    let session = client.get_session(&share_path).await?;
+    let options = SendOptions {
+        session_id: session.session_id(),
+        sign: true,
+        ..Default::default()
+    };
+
+    // A hand-built echo, signed with the keys of the session.
+    let response = connection
+        .send_recv_raw_content(RawContent::bytes(Command::Echo, [4, 0, 0, 0]), &options)
+        .await?;
+    assert_eq!(response.header.command, Command::Echo);
+    assert_eq!(response.header.status, Status::Success as u32);
+    assert_eq!(response.body, [4, 0, 0, 0]);
+
+    // A typed echo works the same.
+    let response = connection
+        .send_recv_raw_content(RequestContent::Echo(EchoRequest::default()), &options)
+        .await?;
+    assert_eq!(response.header.status, Status::Success as u32);
+
+    // An echo with an invalid structure size is returned with its error status.
+    let response = connection
+        .send_recv_raw_content(RawContent::bytes(Command::Echo, [5, 0, 0, 0]), &options)
+        .await?;
+    assert_eq!(response.header.command, Command::Echo);
+    assert_ne!(response.header.status, Status::Success as u32);
+
+    // The connection is still usable for typed requests.
+    let directory = client
+        .create_file(
+            &share_path,
+            &FileCreateArgs::make_open_existing(
+                DirAccessMask::new().with_list_directory(true).into(),
+            ),
+        )
+        .await?
+        .unwrap_dir();
+    directory.close().await?;
+    Ok(())
+}
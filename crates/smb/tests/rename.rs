@@ -0,0 +1,193 @@
+//! ResourceHandle::rename / ResourceHandle::move_to tests.
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{
+    Client, Directory, Error, FileAccessMask, FileAttributes, FileCreateArgs,
+    FileDirectoryInformation, FileFsAttributeInformation, RenameMode, RenameOptions, Resource,
+    UncPath,
+};
+use smb_msg::CreateOptions;
+
+#[cfg(feature = "async")]
+use futures_util::StreamExt;
+
+const RENAME_DIR: &str = "rename_test";
+const REPLACE: RenameOptions = RenameOptions {
+    replace_if_exists: true,
+    mode: RenameMode::Classic,
+};
+
+#[maybe_async::maybe_async]
+async fn open_for_rename(client: &Client, path: &UncPath) -> smb::Result<Resource> {
+    client
+        .create_file(
+            path,
+            &FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_delete(true)
+                    .with_file_read_attributes(true)
+                    .with_synchronize(true),
+            ),
+        )
+        .await
+}
+
+/// Returns the names of the entries of a directory, as stored by the server.
+#[maybe_async::maybe_async]
+async fn list_names(client: &Client, path: &UncPath) -> smb::Result<Vec<String>> {
+    let directory = client
+        .create_file(
+            path,
+            &FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_file_read_data(true)
+                    .with_synchronize(true),
+            ),
+        )
+        .await?
+        .unwrap_dir();
+    let directory = Arc::new(directory);
+    let entries = Directory::query::<FileDirectoryInformation>(&directory, "*").await?;
+    #[cfg(feature = "async")]
+    let entries = entries.collect::<Vec<_>>().await;
+    let names = entries
+        .into_iter()
+        .map(|entry| entry.map(|entry| entry.file_name.to_string()))
+        .filter(|name| !matches!(name.as_deref(), Ok(".") | Ok("..")))
+        .collect::<smb::Result<Vec<_>>>();
+    directory.close().await?;
+    let mut names = names?;
+    names.sort();
+    Ok(names)
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_rename_and_move() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let root = share_path.clone().with_path(RENAME_DIR);
+    let sub = root.clone().with_add_path("sub");
+    for dir in [&root, &sub] {
+        client
+            .create_file(
+                dir,
+                &FileCreateArgs::make_create_new(
+                    FileAttributes::new().with_directory(true),
+                    CreateOptions::new().with_directory_file(true),
+                ),
+            )
+            .await?
+            .unwrap_dir()
+            .close()
+            .await?;
+    }
+    let a = root.clone().with_add_path("a.txt");
+    let b = root.clone().with_add_path("b.txt");
+    client.write_file(&a, b"a", &Default::default()).await?;
+    client.write_file(&b, b"b", &Default::default()).await?;
+
+    let file = open_for_rename(&client, &a).await?.unwrap_file();
+    // Renaming onto an existing file fails, unless replacing it.
+    let collision = file.rename("b.txt", &Default::default()).await;
+    assert!(matches!(
+        collision,
+        Err(Error::ObjectNameCollision { path }) if path == format!("{RENAME_DIR}\\b.txt")
+    ));
+    let invalid = file.rename("sub\\a.txt", &Default::default()).await;
+    assert!(matches!(invalid, Err(Error::InvalidArgument(_))));
+    // A name that differs only in case.
+    file.rename("A.txt", &Default::default()).await?;
+    file.close().await?;
+    let names = list_names(&client, &root).await?;
+    assert_eq!(names, ["A.txt", "b.txt", "sub"]);
+
+    // Replace an existing file, then move it into the subdirectory.
+    let file = open_for_rename(&client, &root.clone().with_add_path("A.txt"))
+        .await?
+        .unwrap_file();
+    file.rename("b.txt", &REPLACE).await?;
+    file.move_to(&format!("{RENAME_DIR}/sub/moved.txt"), &Default::default())
+        .await?;
+    file.close().await?;
+    let names = list_names(&client, &root).await?;
+    assert_eq!(names, ["sub"]);
+    let moved = sub.clone().with_add_path("moved.txt");
+    let content = client.read_file(&moved).await?;
+    assert_eq!(content, b"a");
+
+    // Directories are renamed the same way.
+    let dir = open_for_rename(&client, &sub).await?.unwrap_dir();
+    dir.rename("Renamed", &Default::default()).await?;
+    dir.close().await?;
+    let names = list_names(&client, &root).await?;
+    assert_eq!(names, ["Renamed"]);
+
+    let renamed = root.clone().with_add_path("Renamed");
+    client
+        .delete(&renamed.clone().with_add_path("moved.txt"))
+        .await?;
+    client.delete(&renamed).await?;
+    client.delete(&root).await?;
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_rename_ex_replaces_open_file() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let tree = client.get_tree(&share_path).await?;
+    let posix_rename = tree
+        .query_fs_info::<FileFsAttributeInformation>()
+        .await?
+        .attributes
+        .supports_posix_unlink_rename();
+    if !posix_rename {
+        log::warn!("The file system of the share does not support POSIX renames, skipping test");
+        return Ok(());
+    }
+
+    let source = share_path.clone().with_path("rename_ex_source.txt");
+    let destination = share_path.clone().with_path("rename_ex_destination.txt");
+    client
+        .write_file(&source, b"source", &Default::default())
+        .await?;
+    client
+        .write_file(&destination, b"destination", &Default::default())
+        .await?;
+
+    // The destination is open, and shared for deletion, while it is replaced.
+    let open_destination = client
+        .create_file(
+            &destination,
+            &FileCreateArgs::make_open_existing(FileAccessMask::standard_read()),
+        )
+        .await?
+        .unwrap_file();
+    let file = open_for_rename(&client, &source).await?.unwrap_file();
+    let options = RenameOptions {
+        replace_if_exists: true,
+        mode: RenameMode::posix(),
+    };
+    file.rename("rename_ex_destination.txt", &options).await?;
+    file.close().await?;
+
+    let content = client.read_file(&destination).await?;
+    assert_eq!(content, b"source");
+    let exists = client.exists(&source).await?;
+    assert!(!exists);
+    open_destination.close().await?;
+
+    client.delete(&destination).await?;
+    Ok(())
+}
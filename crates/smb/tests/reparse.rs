@@ -0,0 +1,88 @@
+//! Reparse point tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{
+    FileAccessMask, FileCreateArgs, FileDispositionInformation, ReparseDataBuffer,
+    SymbolicLinkReparseDataBuffer,
+};
+
+const REGULAR_FILE: &str = "reparse_regular_test.txt";
+const SYMLINK_FILE: &str = "reparse_symlink_test";
+const SYMLINK_TARGET: &str = r"..\reparse_symlink_target.txt";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_get_reparse_point_of_regular_file() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.with_path(REGULAR_FILE);
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file();
+
+    let reparse_point = file.get_reparse_point().await?;
+    assert_eq!(reparse_point, None);
+
+    file.close().await?;
+    client.delete(&path).await?;
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a server which allows setting symbolic links, e.g. Windows"]
+async fn test_symlink_reparse_point_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.with_path(SYMLINK_FILE);
+    let link = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_create_new(Default::default(), Default::default())
+                .with_open_reparse_point(true),
+        )
+        .await?
+        .unwrap_file();
+    link.set_reparse_point(ReparseDataBuffer::Symlink(
+        SymbolicLinkReparseDataBuffer::new(SYMLINK_TARGET, true),
+    ))
+    .await?;
+    link.close().await?;
+
+    // Open the link itself, rather than its (missing) target.
+    let link = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_file_read_attributes(true)
+                    .with_delete(true),
+            )
+            .with_open_reparse_point(true),
+        )
+        .await?
+        .unwrap_file();
+    let reparse_point = link.get_reparse_point().await?.unwrap();
+    let ReparseDataBuffer::Symlink(symlink) = &reparse_point else {
+        panic!("Expected a symbolic link, got {reparse_point:?}");
+    };
+    assert_eq!(symlink.substitute_name, SYMLINK_TARGET);
+    assert!(symlink.flags.relative());
+    assert_eq!(reparse_point.target(), Some(SYMLINK_TARGET));
+
+    // Delete the link itself: the client's delete would follow it.
+    link.set_info(FileDispositionInformation::default()).await?;
+    link.close().await?;
+    Ok(())
+}
@@ -1,16 +1,15 @@
 #![cfg(feature = "test-ndr64")]
 
 mod common;
+use common::{TestConstants, default_connection_config, make_server_connection};
 use serial_test::serial;
+use smb::ConnectionConfig;
+use smb_rpc::interface::{ServerInfo, ServerInfoLevel, ShareInfo, ShareInfoLevel, ShareKind};
 
-#[test_log::test(maybe_async::test(
-    not(feature = "async"),
-    async(feature = "async", tokio::test(flavor = "multi_thread"))
-))]
-#[serial]
-async fn test_shares_enum() -> smb::Result<()> {
-    let (client, path) = make_server_connection("IPC$", None).await?;
-    let shares = client.list_shares(&path.server).await?;
+#[maybe_async::maybe_async]
+async fn check_shares_enum(config: Option<ConnectionConfig>) -> smb::Result<()> {
+    let (client, path) = make_server_connection("IPC$", config).await?;
+    let shares = client.list_shares(path.server()).await?;
     assert!(
         shares
             .iter()
@@ -21,3 +20,162 @@ async fn test_shares_enum() -> smb::Result<()> {
     );
     Ok(())
 }
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_shares_enum() -> smb::Result<()> {
+    check_shares_enum(None).await
+}
+
+/// With a small transaction size, the share list does not fit in a single pipe transceive response,
+/// and the rest of the message is read from the pipe.
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_shares_enum_buffer_overflow() -> smb::Result<()> {
+    check_shares_enum(Some(ConnectionConfig {
+        default_transaction_size: Some(0x80),
+        ..default_connection_config()
+    }))
+    .await
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_server_info() -> smb::Result<()> {
+    let (client, path) = make_server_connection("IPC$", None).await?;
+    let info = client
+        .server_info(path.server(), ServerInfoLevel::Info101)
+        .await?;
+    match info {
+        ServerInfo::Info101(info) => assert!(info.server_type.server()),
+        _ => panic!("Unexpected server info level"),
+    }
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a Windows server, and an administrator account"]
+async fn test_share_info_admin_share() -> smb::Result<()> {
+    let (client, path) = make_server_connection("IPC$", None).await?;
+    let info = client
+        .share_info(path.server(), "ADMIN$", ShareInfoLevel::Info502)
+        .await?;
+    match info {
+        ShareInfo::Info502(info) => {
+            assert!(info.share_type.special());
+            assert!(info.path.is_some());
+            assert_eq!(
+                **info.reserved as usize,
+                info.security_descriptor
+                    .as_ref()
+                    .map_or(0, |sd| sd.data.len())
+            );
+        }
+        _ => panic!("Unexpected share info level"),
+    }
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_workstation_info() -> smb::Result<()> {
+    let (client, path) = make_server_connection("IPC$", None).await?;
+    let info = client.workstation_info(path.server()).await?;
+    assert!(info.computer_name.is_some());
+    assert!(info.langroup.is_some());
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a Windows domain controller"]
+async fn test_list_domain_users() -> smb::Result<()> {
+    let (client, path) = make_server_connection("IPC$", None).await?;
+    let users = client.list_domain_users(path.server()).await?;
+    // The built-in administrator account always has RID 500.
+    assert!(users.iter().any(|user| user.rid == 500));
+    Ok(())
+}
+
+/// Encodes the NDR64 input of `NetrShareEnum`, at level 1, with no resume handle.
+fn netr_share_enum_stub(server_name: &str) -> Vec<u8> {
+    let name = server_name.encode_utf16().chain([0]).collect::<Vec<u16>>();
+    let mut stub = vec![];
+    // ServerName: a unique pointer to a conformant varying string.
+    stub.extend(0x20000u64.to_le_bytes());
+    for count in [name.len() as u64, 0, name.len() as u64] {
+        stub.extend(count.to_le_bytes());
+    }
+    stub.extend(name.iter().flat_map(|c| c.to_le_bytes()));
+    stub.resize(stub.len().next_multiple_of(8), 0);
+    // InfoStruct: level 1, with an empty container.
+    stub.extend(1u64.to_le_bytes());
+    stub.extend(1u64.to_le_bytes());
+    stub.extend(0x20000u64.to_le_bytes());
+    stub.extend(0u64.to_le_bytes());
+    stub.extend(0u64.to_le_bytes());
+    // PreferedMaximumLength, and a null ResumeHandle.
+    stub.extend(u32::MAX.to_le_bytes());
+    stub.extend(0u32.to_le_bytes());
+    stub.extend(0u64.to_le_bytes());
+    stub
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_generic_binding() -> smb::Result<()> {
+    let (client, path) = make_server_connection("IPC$", None).await?;
+    let pipe = client.open_pipe(path.server(), "srvsvc").await?;
+    let mut binding = smb::RpcBinding::bind(
+        pipe,
+        smb::Guid::parse_uuid("4b324fc8-1670-01d3-1278-5a47bf6ee188").unwrap(),
+        3,
+        smb_rpc::ndr64::NDR64_SYNTAX_ID,
+    )
+    .await?;
+    assert!(binding.max_xmit_frag() > 0);
+    assert!(binding.max_recv_frag() > 0);
+
+    const NETR_SHARE_ENUM: u16 = 15;
+    let output = binding
+        .call(NETR_SHARE_ENUM, &netr_share_enum_stub(path.server()))
+        .await?;
+    let status = u32::from_le_bytes(output[output.len() - 4..].try_into().unwrap());
+    assert_eq!(status, 0);
+    let share_name = TestConstants::DEFAULT_SHARE
+        .encode_utf16()
+        .flat_map(|c| c.to_le_bytes())
+        .collect::<Vec<u8>>();
+    assert!(output.windows(share_name.len()).any(|w| w == share_name));
+
+    let result = binding.call(0x1000, &[]).await;
+    assert!(matches!(
+        result,
+        Err(smb::Error::RpcError(smb_rpc::SmbRpcError::Fault(
+            smb_rpc::pdu::RpcFaultStatus::OperationRangeError
+        )))
+    ));
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Security descriptor query and set tests.
+
+mod common;
+
+use std::str::FromStr;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{
+    ACE, AccessAce, AccessMask, AceFlags, AceValue, Error, FileAccessMask, FileCreateArgs, SID,
+    SecurityInformation,
+};
+
+const SECURITY_FILE: &str = "security_test.txt";
+/// The built-in Guests group, which the test user is not expected to be a member of.
+const DENIED_SID: &str = "S-1-5-32-546";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_dacl_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.with_path(SECURITY_FILE);
+    client
+        .write_file(&path, b"security", &Default::default())
+        .await?;
+
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_read_control(true)
+                    .with_write_dacl(true),
+            ),
+        )
+        .await?
+        .unwrap_file();
+
+    let descriptor = file
+        .query_security_info(SecurityInformation::dacl_only())
+        .await?;
+    let mut dacl = descriptor.dacl.clone().ok_or("No DACL on the test file")?;
+    let sid = SID::from_str(DENIED_SID)?;
+    let deny_write = ACE {
+        ace_flags: AceFlags::new(),
+        value: AceValue::AccessDenied(AccessAce {
+            access_mask: AccessMask::from_bytes(
+                FileAccessMask::new()
+                    .with_file_write_data(true)
+                    .with_file_append_data(true)
+                    .into_bytes(),
+            ),
+            sid,
+        }),
+    };
+    dacl.insert_ace(deny_write.clone());
+    let mut updated = descriptor.clone();
+    updated.dacl = Some(dacl);
+    file.set_security_info(updated, SecurityInformation::dacl_only())
+        .await?;
+
+    // Explicit access-denied ACEs are ordered first.
+    let requeried = file
+        .query_security_info(SecurityInformation::dacl_only())
+        .await?;
+    let requeried_dacl = requeried.dacl.ok_or("No DACL after setting it")?;
+    assert_eq!(requeried_dacl.ace.first(), Some(&deny_write));
+    assert_eq!(
+        requeried_dacl.ace.len(),
+        descriptor.dacl.as_ref().unwrap().ace.len() + 1
+    );
+
+    // The SACL is not accessible without system security access.
+    let result = file
+        .query_security_info(SecurityInformation::new().with_sacl(true))
+        .await;
+    assert!(matches!(result, Err(Error::MissingPermissions(_))));
+
+    file.close().await?;
+    client.delete(&path).await?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! File::copy_to (server-side copy) tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{FileAccessMask, FileCreateArgs};
+
+const SOURCE_FILE: &str = "server_copy_source.bin";
+const DESTINATION_FILE: &str = "server_copy_destination.bin";
+/// Spans several chunks, and ends with a partial one.
+const SIZE: usize = 3 * 1024 * 1024 + 0x123;
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_copy_to() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let source_path = share_path.clone().with_path(SOURCE_FILE);
+    let destination_path = share_path.with_path(DESTINATION_FILE);
+    let content = (0..SIZE).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    client
+        .write_file(&source_path, &content, &Default::default())
+        .await?;
+    // A longer destination is truncated.
+    client
+        .write_file(&destination_path, &vec![0; SIZE * 2], &Default::default())
+        .await?;
+
+    let source = client
+        .create_file(
+            &source_path,
+            &FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_read(true)),
+        )
+        .await?
+        .unwrap_file();
+    // FSCTL_SRV_COPYCHUNK_WRITE requires no read access on the destination.
+    let destination = client
+        .create_file(
+            &destination_path,
+            &FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_file_write_data(true)
+                    .with_file_read_attributes(true),
+            ),
+        )
+        .await?
+        .unwrap_file();
+    let copied = source.copy_to(&destination).await?;
+    assert_eq!(copied, SIZE as u64);
+    destination.close().await?;
+
+    let data = client.read_file(&destination_path).await?;
+    assert_eq!(data, content);
+
+    source.close().await?;
+    client.delete(&source_path).await?;
+    client.delete(&destination_path).await?;
+    Ok(())
+}
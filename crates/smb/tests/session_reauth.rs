@@ -0,0 +1,99 @@
+//! Session::renew / ClientConfig::auto_reauth tests.
+
+mod common;
+
+use common::{
+    TestConstants, default_connection_config, make_server_connection, make_server_connection_ex,
+};
+use serial_test::serial;
+use smb::{ClientConfig, FileAttributes, FileCreateArgs};
+use smb_msg::CreateOptions;
+use std::time::Duration;
+
+const FILE_NAME: &str = "session_reauth_test.bin";
+
+/// How long to wait for the session to expire, on a server that expires sessions quickly
+/// (e.g. Samba with a short `session timeout`).
+const SESSION_EXPIRY_WAIT: Duration = Duration::from_secs(90);
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_session_renew() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.clone().with_path(FILE_NAME);
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new()),
+        )
+        .await?
+        .unwrap_file();
+    file.write_block(b"before", 0, None).await?;
+
+    // The session keeps its ID, so the open handle remains valid.
+    let session = client.get_session(&share_path).await?;
+    let session_id = session.session_id();
+    session.renew().await?;
+    session.renew().await?;
+    assert_eq!(session.session_id(), session_id);
+
+    file.write_block(b"after", 6, None).await?;
+    let mut data = [0; 11];
+    let read = file.read_block(&mut data, 0, None, false).await?;
+    assert_eq!(&data[..read], b"beforeafter");
+    file.close().await?;
+
+    client.remove_file(&path).await?;
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "requires a server that expires sessions quickly"]
+async fn test_session_auto_reauth() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection_ex(
+        TestConstants::DEFAULT_SHARE,
+        ClientConfig {
+            connection: default_connection_config(),
+            auto_reauth: true,
+            ..Default::default()
+        },
+    )
+    .await?;
+    let path = share_path.clone().with_path(FILE_NAME);
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new()),
+        )
+        .await?
+        .unwrap_file();
+
+    wait(SESSION_EXPIRY_WAIT).await;
+
+    // Fails with STATUS_NETWORK_SESSION_EXPIRED, re-authenticates and is sent again.
+    file.write_block(b"content", 0, None).await?;
+    let mut data = [0; 7];
+    let read = file.read_block(&mut data, 0, None, false).await?;
+    assert_eq!(&data[..read], b"content");
+    file.close().await?;
+
+    client.remove_file(&path).await?;
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+async fn wait(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+#[cfg(not(feature = "async"))]
+fn wait(duration: Duration) {
+    std::thread::sleep(duration);
+}
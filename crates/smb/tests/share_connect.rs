@@ -0,0 +1,158 @@
+//! Client::share_connect / Client::share_disconnect bookkeeping tests.
+
+mod common;
+
+use std::env::var;
+
+use common::{TestConstants, TestEnv, make_server_connection};
+use serial_test::serial;
+#[cfg(not(feature = "single_threaded"))]
+use smb::{Client, Session, UncPath};
+use smb::{Error, FileAccessMask, FileCreateArgs};
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_share_disconnect() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let connection = client.get_connection(share_path.server()).await?;
+    let file_path = share_path.clone().with_path("share_disconnect.txt");
+
+    client.share_disconnect(&share_path).await?;
+    let tree = client.get_tree(&share_path).await;
+    assert!(matches!(tree, Err(Error::NotFound(_))));
+    let session = client.get_session(&share_path).await;
+    assert!(matches!(session, Err(Error::NotFound(_))));
+    let disconnected_again = client.share_disconnect(&share_path).await;
+    assert!(matches!(disconnected_again, Err(Error::NotFound(_))));
+    // The connection is kept, and reused to connect the share again.
+    let kept = client.get_connection(share_path.server()).await?;
+    assert!(std::sync::Arc::ptr_eq(&connection, &kept));
+
+    let user = var(TestEnv::USER).unwrap_or(TestEnv::DEFAULT_USER.to_string());
+    let password = var(TestEnv::PASSWORD).unwrap_or(TestEnv::DEFAULT_PASSWORD.to_string());
+    client
+        .share_connect(&file_path, user.as_str(), password)
+        .await?;
+    client
+        .write_file(&file_path, b"reconnected", &Default::default())
+        .await?;
+    client.delete(&file_path).await?;
+
+    client.close().await?;
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_share_connect_failure_rolls_back() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let missing_share = share_path.clone().with_share("NoSuchShare")?;
+    let user = var(TestEnv::USER).unwrap_or(TestEnv::DEFAULT_USER.to_string());
+    let password = var(TestEnv::PASSWORD).unwrap_or(TestEnv::DEFAULT_PASSWORD.to_string());
+
+    // Authentication succeeds, but the tree connect fails.
+    for _ in 0..3 {
+        let result = client
+            .share_connect(&missing_share, user.as_str(), password.clone())
+            .await;
+        assert!(result.is_err());
+        let session = client.get_session(&missing_share).await;
+        assert!(matches!(session, Err(Error::NotFound(_))));
+    }
+
+    // The connected share and its connection are not affected.
+    client.get_connection(share_path.server()).await?;
+    let args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_read(true));
+    client
+        .create_file(&share_path, &args)
+        .await?
+        .unwrap_dir()
+        .close()
+        .await?;
+
+    client.close().await?;
+    Ok(())
+}
+
+/// Connects `share` and opens its root directory, returning the session the share is connected with.
+#[cfg(not(feature = "single_threaded"))]
+#[maybe_async::maybe_async]
+async fn connect_and_open(
+    client: &Client,
+    share: &UncPath,
+) -> smb::Result<std::sync::Arc<Session>> {
+    let user = var(TestEnv::USER).unwrap_or(TestEnv::DEFAULT_USER.to_string());
+    let password = var(TestEnv::PASSWORD).unwrap_or(TestEnv::DEFAULT_PASSWORD.to_string());
+    client.share_connect(share, user.as_str(), password).await?;
+    let args = FileCreateArgs::make_open_existing(FileAccessMask::new().with_generic_read(true));
+    client
+        .create_file(share, &args)
+        .await?
+        .unwrap_dir()
+        .close()
+        .await?;
+    client.get_session(share).await
+}
+
+/// Connects the same shares from many concurrent tasks, which must reuse a single connection
+/// to the server, and a single session for each share, rather than connecting them again, or failing.
+#[cfg(not(feature = "single_threaded"))]
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_concurrent_share_connect() -> Result<(), Box<dyn std::error::Error>> {
+    const TASKS: usize = 100;
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let shares = [
+        TestConstants::DEFAULT_SHARE,
+        TestConstants::PUBLIC_GUEST_SHARE,
+        TestConstants::SNAPSHOT_SHARE,
+    ]
+    .map(|share| share_path.clone().with_share(share).unwrap());
+    let connection = client.get_connection(share_path.server()).await?;
+
+    #[cfg(feature = "async")]
+    let sessions = tokio::time::timeout(
+        std::time::Duration::from_secs(120),
+        futures_util::future::try_join_all(
+            (0..TASKS).map(|i| connect_and_open(&client, &shares[i % shares.len()])),
+        ),
+    )
+    .await
+    .expect("concurrent connects should not deadlock")?;
+    #[cfg(not(feature = "async"))]
+    let sessions = std::thread::scope(|scope| {
+        let handles = (0..TASKS)
+            .map(|i| {
+                let (client, share) = (&client, &shares[i % shares.len()]);
+                scope.spawn(move || connect_and_open(client, share))
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<smb::Result<Vec<_>>>()
+    })?;
+
+    for (i, session) in sessions.iter().enumerate() {
+        let share = &shares[i % shares.len()];
+        let share_session = client.get_session(share).await?;
+        assert!(
+            std::sync::Arc::ptr_eq(session, &share_session),
+            "{share} was connected with more than one session"
+        );
+    }
+    let kept = client.get_connection(share_path.server()).await?;
+    assert!(std::sync::Arc::ptr_eq(&connection, &kept));
+
+    client.close().await?;
+    Ok(())
+}
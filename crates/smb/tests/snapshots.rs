@@ -0,0 +1,78 @@
+//! Restoring from shadow_copy2 snapshots: Client::list_snapshots / Client::download_snapshot_tree tests.
+#![cfg(feature = "std-fs-impls")]
+
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::FileCreateArgs;
+use smb::resource::{NameCollisionPolicy, TransferOptions};
+use smb_dtyp::binrw_util::prelude::FileTime;
+use smb_fscc::FileAccessMask;
+use time::macros::datetime;
+
+/// The last write time of `docs/deleted.txt` in the snapshot, 2024-06-01 12:00:00 UTC.
+const DELETED_MODIFIED: u64 = 1_717_243_200;
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_restore_deleted_file() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::SNAPSHOT_SHARE, None).await?;
+    // The single snapshot of the share, `@GMT-2025.01.01-00.00.00`.
+    let snapshot = FileTime::from(datetime!(2025-01-01 00:00:00));
+    let snapshots = client.list_snapshots(&share_path).await?;
+    assert_eq!(snapshots, vec![snapshot]);
+
+    // The file was deleted since the snapshot, so it can only be opened as of the snapshot.
+    let deleted = share_path.clone().with_path("docs/deleted.txt");
+    let args = FileCreateArgs::make_open_existing(FileAccessMask::standard_read());
+    let current = client.create_file(&deleted, &args).await;
+    assert!(current.is_err());
+
+    let local = std::env::temp_dir().join("smb_rs_snapshot_restore");
+    let _ = std::fs::remove_dir_all(&local);
+    std::fs::create_dir_all(&local)?;
+    // Collides with the snapshot's "Report.txt" on case-insensitive file systems, and must be kept.
+    std::fs::write(local.join("report.txt"), "local")?;
+
+    let options = TransferOptions {
+        name_collision_policy: NameCollisionPolicy::Rename,
+        ..Default::default()
+    };
+    let report = client
+        .download_snapshot_tree(&share_path, snapshot, "docs", &local, &options)
+        .await?;
+
+    assert_eq!(
+        std::fs::read_to_string(local.join("deleted.txt"))?,
+        "deleted"
+    );
+    assert_eq!(
+        std::fs::read_to_string(local.join("sub").join("nested.txt"))?,
+        "nested"
+    );
+    assert_eq!(std::fs::read_to_string(local.join("report.txt"))?, "local");
+    assert_eq!(
+        std::fs::read_to_string(local.join("Report (1).txt"))?,
+        "report"
+    );
+    // Not in the snapshot.
+    assert!(!local.join("current.txt").exists());
+
+    assert_eq!(report.collisions.len(), 1);
+    assert_eq!(report.collisions[0].name, "Report.txt");
+    assert_eq!(report.collisions[0].conflicts_with, "report.txt");
+
+    assert_eq!(
+        std::fs::metadata(local.join("deleted.txt"))?.modified()?,
+        SystemTime::UNIX_EPOCH + Duration::from_secs(DELETED_MODIFIED)
+    );
+
+    std::fs::remove_dir_all(&local)?;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! Sparse file tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::FileCreateArgs;
+use smb::resource::SetLen;
+
+const SPARSE_FILE: &str = "sparse_test.bin";
+const DATA_OFFSET: u64 = 0x100000;
+const FILE_SIZE: u64 = 0x400000;
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_sparse_allocated_ranges() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.with_path(SPARSE_FILE);
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+        )
+        .await?
+        .unwrap_file();
+
+    file.set_sparse(true).await?;
+    file.set_len(FILE_SIZE).await?;
+    file.write_block(&[0xab; 0x1000], DATA_OFFSET, None).await?;
+
+    // Only the written data is allocated, up to the allocation granularity of the server.
+    let ranges = file.query_allocated_ranges(0, FILE_SIZE).await?;
+    assert!(!ranges.is_empty());
+    assert!(
+        ranges
+            .iter()
+            .any(|r| r.file_offset <= DATA_OFFSET && r.end() > DATA_OFFSET)
+    );
+    let allocated = ranges.iter().map(|r| r.length).sum::<u64>();
+    assert!(allocated < FILE_SIZE);
+
+    // Queries are limited to the queried range.
+    let ranges = file.query_allocated_ranges(0, 0x1000).await?;
+    assert!(ranges.is_empty());
+
+    // A file that is not sparse is allocated in whole.
+    file.set_sparse(false).await?;
+    let ranges = file.query_allocated_ranges(0, FILE_SIZE).await?;
+    assert_eq!(ranges.iter().map(|r| r.length).sum::<u64>(), FILE_SIZE);
+
+    file.close().await?;
+    client.delete(&path).await?;
+    Ok(())
+}
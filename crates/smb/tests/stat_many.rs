@@ -0,0 +1,262 @@
+//! Client::stat_many tests, for paths spanning multiple shares and servers.
+
+mod common;
+
+use std::collections::HashMap;
+use std::env::var;
+
+use common::{TestConstants, TestEnv, make_server_connection};
+use serial_test::serial;
+use smb::client::{DirEntry, StatManyOptions};
+use smb::{Client, FileCreateArgs, UncPath};
+
+#[cfg(feature = "async")]
+use futures_util::StreamExt;
+
+const STAT_DIR: &str = "stat_many_test";
+const FILE_COUNT: usize = 5;
+const BENCH_FILE_COUNT: usize = 10_000;
+
+/// Creates a directory with `count` files in it, returning the paths of the files.
+#[maybe_async::maybe_async]
+async fn make_files(client: &Client, dir: &UncPath, count: usize) -> smb::Result<Vec<UncPath>> {
+    client
+        .create_file(
+            dir,
+            &FileCreateArgs::make_create_new(
+                smb_fscc::FileAttributes::new().with_directory(true),
+                smb_msg::CreateOptions::new().with_directory_file(true),
+            ),
+        )
+        .await?
+        .unwrap_dir()
+        .close()
+        .await?;
+
+    let mut paths = vec![];
+    for i in 0..count {
+        let path = dir.clone().with_add_path(&format!("file{i}.txt"));
+        client
+            .create_file(
+                &path,
+                &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+            )
+            .await?
+            .unwrap_file()
+            .close()
+            .await?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Deletes the files created by [`make_files`], and then their directory.
+#[maybe_async::maybe_async]
+async fn delete_files(client: &Client, dir: &UncPath, paths: Vec<UncPath>) -> smb::Result<()> {
+    for path in paths {
+        client.delete(&path).await?;
+    }
+    client.delete(dir).await
+}
+
+#[maybe_async::async_impl]
+async fn stat_all(
+    client: &Client,
+    paths: Vec<UncPath>,
+    options: &StatManyOptions,
+) -> HashMap<UncPath, Vec<smb::Result<DirEntry>>> {
+    let stats = client.stat_many(paths, options);
+    futures_util::pin_mut!(stats);
+    let mut result: HashMap<_, Vec<_>> = HashMap::new();
+    while let Some((path, entry)) = stats.next().await {
+        result.entry(path).or_default().push(entry);
+    }
+    result
+}
+
+#[maybe_async::sync_impl]
+fn stat_all(
+    client: &Client,
+    paths: Vec<UncPath>,
+    options: &StatManyOptions,
+) -> HashMap<UncPath, Vec<smb::Result<DirEntry>>> {
+    let mut result: HashMap<_, Vec<_>> = HashMap::new();
+    for (path, entry) in client.stat_many(paths, options) {
+        result.entry(path).or_default().push(entry);
+    }
+    result
+}
+
+/// Asserts that each of the `existing` paths was yielded once with its metadata,
+/// and each of the `failing` paths was yielded once with an error.
+fn assert_stats(
+    results: &HashMap<UncPath, Vec<smb::Result<DirEntry>>>,
+    existing: &[UncPath],
+    failing: &[UncPath],
+) {
+    assert_eq!(results.len(), existing.len() + failing.len());
+    for path in existing {
+        let [Ok(entry)] = results[path].as_slice() else {
+            panic!(
+                "Expected a single entry for {path}, got {:?}",
+                results[path]
+            );
+        };
+        assert_eq!(&entry.path, path);
+        assert!(!entry.is_directory());
+        assert_eq!(entry.info.end_of_file, 0);
+        assert!(
+            path.to_string()
+                .ends_with(&entry.info.file_name.to_string())
+        );
+    }
+    for path in failing {
+        assert!(
+            matches!(results[path].as_slice(), [Err(_)]),
+            "Expected a single error for {path}, got {:?}",
+            results[path]
+        );
+    }
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_stat_many_shares() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let public_path =
+        UncPath::new(share_path.server())?.with_share(TestConstants::PUBLIC_GUEST_SHARE)?;
+    let user = var(TestEnv::USER).unwrap_or(TestEnv::DEFAULT_USER.to_string());
+    let password = var(TestEnv::PASSWORD).unwrap_or(TestEnv::DEFAULT_PASSWORD.to_string());
+    client.share_connect(&public_path, &user, password).await?;
+
+    let private_dir = share_path.clone().with_path(STAT_DIR);
+    let public_dir = public_path.clone().with_path(STAT_DIR);
+    let private_files = make_files(&client, &private_dir, FILE_COUNT).await?;
+    let public_files = make_files(&client, &public_dir, FILE_COUNT).await?;
+
+    // Interleave the shares, so grouping by tree is exercised.
+    let existing = private_files
+        .iter()
+        .zip(public_files.iter())
+        .flat_map(|(a, b)| [a.clone(), b.clone()])
+        .collect::<Vec<_>>();
+    let failing = vec![
+        private_dir.clone().with_add_path("missing.txt"),
+        public_dir.clone().with_add_path("missing"),
+        // The share is not connected.
+        UncPath::new(share_path.server())?
+            .with_share(TestConstants::SNAPSHOT_SHARE)?
+            .with_path("file.txt"),
+    ];
+    let paths = failing
+        .iter()
+        .cloned()
+        .chain(existing.iter().cloned())
+        .collect::<Vec<_>>();
+
+    for max_in_flight in [1, 4] {
+        let results = stat_all(&client, paths.clone(), &StatManyOptions { max_in_flight }).await;
+        assert_stats(&results, &existing, &failing);
+    }
+
+    // Directories are queried as well.
+    let results = stat_all(
+        &client,
+        vec![private_dir.clone(), public_dir.clone()],
+        &Default::default(),
+    )
+    .await;
+    for dir in [&private_dir, &public_dir] {
+        let [Ok(entry)] = results[dir].as_slice() else {
+            panic!("Expected a single entry for {dir}, got {:?}", results[dir]);
+        };
+        assert!(entry.is_directory());
+    }
+
+    delete_files(&client, &private_dir, private_files).await?;
+    delete_files(&client, &public_dir, public_files).await?;
+    Ok(())
+}
+
+/// Queries paths of the default server, and of the small transact server, in one call.
+///
+/// Connections are looked up by the server's address, so this requires the servers
+/// to have different addresses, not just different ports, as in CI.
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_stat_many_servers() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let second_server = var(TestEnv::SMALL_TRANSACT_SERVER)
+        .unwrap_or(TestEnv::DEFAULT_SMALL_TRANSACT_SERVER.to_string());
+    let second_path = UncPath::new(&second_server)?.with_share(TestConstants::DEFAULT_SHARE)?;
+    let user = var(TestEnv::USER).unwrap_or(TestEnv::DEFAULT_USER.to_string());
+    let password = var(TestEnv::PASSWORD).unwrap_or(TestEnv::DEFAULT_PASSWORD.to_string());
+    client.share_connect(&second_path, &user, password).await?;
+
+    let first_dir = share_path.clone().with_path(STAT_DIR);
+    let second_dir = second_path.clone().with_path(STAT_DIR);
+    let first_files = make_files(&client, &first_dir, FILE_COUNT).await?;
+    let second_files = make_files(&client, &second_dir, FILE_COUNT).await?;
+
+    let existing = first_files
+        .iter()
+        .chain(second_files.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    let failing = vec![
+        first_dir.clone().with_add_path("missing.txt"),
+        second_dir.clone().with_add_path("missing.txt"),
+    ];
+    let paths = existing
+        .iter()
+        .chain(failing.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let results = stat_all(&client, paths, &Default::default()).await;
+    assert_stats(&results, &existing, &failing);
+
+    delete_files(&client, &first_dir, first_files).await?;
+    delete_files(&client, &second_dir, second_files).await?;
+    Ok(())
+}
+
+/// Compares [`Client::stat_many`] to sequential [`Client::stat`] calls, for many paths.
+///
+/// Run with `cargo test --release --test stat_many -- --ignored --nocapture` against the container server.
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "benchmark: creates and queries 10k files"]
+async fn bench_stat_many() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let dir = share_path.with_path(STAT_DIR);
+    let files = make_files(&client, &dir, BENCH_FILE_COUNT).await?;
+
+    let start = std::time::Instant::now();
+    for path in &files {
+        let entry = client.stat(path).await?;
+        assert!(entry.is_some());
+    }
+    let sequential = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let results = stat_all(&client, files.clone(), &Default::default()).await;
+    let many = start.elapsed();
+    assert_stats(&results, &files, &[]);
+
+    println!(
+        "{BENCH_FILE_COUNT} paths: sequential stat took {sequential:?}, stat_many took {many:?}"
+    );
+
+    delete_files(&client, &dir, files).await?;
+    Ok(())
+}
@@ -0,0 +1,73 @@
+//! ResourceHandle::list_streams and alternate data streams tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{FileAccessMask, FileAttributes, FileCreateArgs, FileFsAttributeInformation};
+use smb_msg::CreateOptions;
+
+const FILE_NAME: &str = "streams_test.txt";
+const STREAMS: [(&str, &[u8]); 2] = [("first", b"first stream"), ("second", b"second!")];
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_list_and_open_streams() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let tree = client.get_tree(&share_path).await?;
+    let fs_attributes = tree.query_fs_info::<FileFsAttributeInformation>().await?;
+    if !fs_attributes.attributes.named_streams() {
+        log::warn!("The share does not support named streams, skipping test");
+        return Ok(());
+    }
+
+    let path = share_path.clone().with_path(FILE_NAME);
+    client
+        .write_file(&path, b"default stream", &Default::default())
+        .await?;
+    for (name, content) in STREAMS {
+        let stream_path = share_path.clone().with_path(&format!("{FILE_NAME}:{name}"));
+        let stream = client
+            .create_file(
+                &stream_path,
+                &FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new()),
+            )
+            .await?
+            .unwrap_file();
+        stream.write_block(content, 0, None).await?;
+        stream.close().await?;
+    }
+
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_open_existing(
+                FileAccessMask::new()
+                    .with_file_read_attributes(true)
+                    .with_synchronize(true),
+            ),
+        )
+        .await?
+        .unwrap_file();
+    let mut streams = file.list_streams().await?;
+    file.close().await?;
+    streams.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(streams.len(), STREAMS.len() + 1);
+    assert!(streams[0].is_default());
+    assert_eq!(streams[0].size, b"default stream".len() as u64);
+
+    // Open each named stream by the listed name, and read its contents.
+    for (stream, (name, content)) in streams[1..].iter().zip(STREAMS) {
+        assert_eq!(stream.name, name);
+        assert_eq!(stream.size, content.len() as u64);
+        let stream_path = share_path.clone().with_path(&stream.path_for(FILE_NAME));
+        let data = client.read_file(&stream_path).await?;
+        assert_eq!(data, content);
+    }
+
+    client.delete(&path).await?;
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! Tree::stat and Tree::read_small_file tests, which open, operate on and close paths in a single compound.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{
+    Client, CreateOptions, Error, FileAccessMask, FileAttributes, FileCreateArgs,
+    FileNetworkOpenInformation, Status, UncPath,
+};
+
+const TREE_STAT_DIR: &str = "tree_stat_test";
+const TREE_STAT_DATA: &[u8] = b"Read in a single round trip!";
+const BENCH_FILE_COUNT: usize = 1_000;
+
+#[maybe_async::maybe_async]
+async fn make_dir(client: &Client, dir: &UncPath) -> smb::Result<()> {
+    client
+        .create_file(
+            dir,
+            &FileCreateArgs::make_create_new(
+                FileAttributes::new().with_directory(true),
+                CreateOptions::new().with_directory_file(true),
+            ),
+        )
+        .await?
+        .unwrap_dir()
+        .close()
+        .await
+}
+
+#[maybe_async::maybe_async]
+async fn make_file(client: &Client, path: &UncPath, data: &[u8]) -> smb::Result<()> {
+    let file = client
+        .create_file(
+            path,
+            &FileCreateArgs::make_create_new(FileAttributes::new(), CreateOptions::new()),
+        )
+        .await?
+        .unwrap_file();
+    if !data.is_empty() {
+        file.write_all_at(data, 0).await?;
+    }
+    file.close().await
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_tree_stat_and_read_small_file() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let dir = share_path.clone().with_path(TREE_STAT_DIR);
+    make_dir(&client, &dir).await?;
+    let small = dir.clone().with_add_path("small.txt");
+    let empty = dir.clone().with_add_path("empty.txt");
+    // Larger than a single compounded read.
+    let large = dir.clone().with_add_path("large.bin");
+    let large_data = (0..3 * smb::Tree::SMALL_FILE_SIZE)
+        .map(|i| i as u8)
+        .collect::<Vec<_>>();
+    make_file(&client, &small, TREE_STAT_DATA).await?;
+    make_file(&client, &empty, &[]).await?;
+    make_file(&client, &large, &large_data).await?;
+
+    let tree = client.get_tree(&share_path).await?;
+    let name = |path: &UncPath| path.path().unwrap().to_string();
+
+    let info = tree.stat(&name(&small)).await?;
+    assert_eq!(info.end_of_file, TREE_STAT_DATA.len() as u64);
+    assert!(!info.file_attributes.directory());
+    let info = tree.stat(TREE_STAT_DIR).await?;
+    assert!(info.file_attributes.directory());
+
+    let data = tree.read_small_file(&name(&small)).await?;
+    assert_eq!(data, TREE_STAT_DATA);
+    let data = tree.read_small_file(&name(&empty)).await?;
+    assert!(data.is_empty());
+    let data = tree.read_small_file(&name(&large)).await?;
+    assert_eq!(data, large_data);
+
+    let missing = format!("{TREE_STAT_DIR}\\missing.txt");
+    let result = tree.stat(&missing).await;
+    assert!(matches!(
+        result,
+        Err(Error::ServerError(Status::ObjectNameNotFound, _))
+    ));
+    let result = tree.read_small_file(&missing).await;
+    assert!(matches!(
+        result,
+        Err(Error::ServerError(Status::ObjectNameNotFound, _))
+    ));
+    let result = tree.read_small_file(TREE_STAT_DIR).await;
+    assert!(matches!(
+        result,
+        Err(Error::ServerError(Status::FileIsADirectory, _))
+    ));
+
+    // The compounds closed their handles, so the files may be deleted.
+    for path in [&small, &empty, &large, &dir] {
+        client.delete(path).await?;
+    }
+    Ok(())
+}
+
+/// Compares [`smb::Tree::stat`] to an open, a query and a close, each in its own round trip.
+///
+/// Run with `cargo test --release --test tree_stat -- --ignored --nocapture` against the container server.
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+#[ignore = "benchmark: creates and queries 1k files"]
+async fn bench_tree_stat() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let dir = share_path.clone().with_path(TREE_STAT_DIR);
+    make_dir(&client, &dir).await?;
+    let mut names = vec![];
+    for i in 0..BENCH_FILE_COUNT {
+        let name = format!("{TREE_STAT_DIR}\\file{i}.txt");
+        make_file(&client, &share_path.clone().with_path(&name), &[]).await?;
+        names.push(name);
+    }
+    let tree = client.get_tree(&share_path).await?;
+
+    let start = std::time::Instant::now();
+    for name in &names {
+        let file = tree
+            .open_existing(name, FileAccessMask::new().with_file_read_attributes(true))
+            .await?
+            .unwrap_file();
+        file.query_info::<FileNetworkOpenInformation>().await?;
+        file.close().await?;
+    }
+    let separate = start.elapsed();
+
+    let start = std::time::Instant::now();
+    for name in &names {
+        tree.stat(name).await?;
+    }
+    let compounded = start.elapsed();
+
+    println!(
+        "{BENCH_FILE_COUNT} paths: separate requests took {separate:?}, Tree::stat took {compounded:?} ({:.1}x)",
+        separate.as_secs_f64() / compounded.as_secs_f64()
+    );
+
+    for name in &names {
+        client.delete(&share_path.clone().with_path(name)).await?;
+    }
+    client.delete(&dir).await?;
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Write-through and unbuffered (FILE_NO_INTERMEDIATE_BUFFERING) file I/O tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::{Error, FileAccessMask, FileAttributes, FileCreateArgs};
+use smb_msg::CreateOptions;
+
+const WRITE_THROUGH_FILE: &str = "write_through_test.bin";
+const UNBUFFERED_FILE: &str = "unbuffered_test.bin";
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_write_through() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.clone().with_path(WRITE_THROUGH_FILE);
+
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new())
+                .with_write_through(true),
+        )
+        .await?
+        .unwrap_file();
+    assert!(file.create_options().write_through());
+    let content = (0..0x3000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    file.write_block(&content, 0, None).await?;
+
+    // Without flushing, the data is visible on another connection, before the handle is closed.
+    let (other, _) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let reader = other
+        .create_file(
+            &path,
+            &FileCreateArgs::make_open_existing(FileAccessMask::standard_read()),
+        )
+        .await?
+        .unwrap_file();
+    let mut data = vec![0; content.len()];
+    let read = reader.read_all_at(&mut data, 0).await?;
+    assert_eq!(read, content.len());
+    assert_eq!(data, content);
+    reader.close().await?;
+    other.close().await?;
+
+    file.close().await?;
+    client.delete(&path).await?;
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_unbuffered_alignment() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let path = share_path.clone().with_path(UNBUFFERED_FILE);
+
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_overwrite(FileAttributes::new(), CreateOptions::new())
+                .with_create_options(
+                    CreateOptions::new()
+                        .with_non_directory_file(true)
+                        .with_no_intermediate_buffering(true),
+                )?,
+        )
+        .await?
+        .unwrap_file();
+    assert!(file.is_unbuffered());
+    let sector_size = file.sector_size().await? as usize;
+    assert!(sector_size.is_power_of_two());
+
+    let content = (0..4 * sector_size)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    file.write_all_at(&content, 0).await?;
+
+    // Unaligned offsets and lengths are rejected before reaching the server.
+    let result = file.write_all_at(&content[..sector_size], 1).await;
+    assert!(matches!(result, Err(Error::UnalignedUnbufferedIo { .. })));
+    let result = file.write_all_at(&content[..sector_size - 1], 0).await;
+    assert!(matches!(result, Err(Error::UnalignedUnbufferedIo { .. })));
+    file.close().await?;
+
+    let file = client
+        .create_file(
+            &path,
+            &FileCreateArgs::make_open_existing(FileAccessMask::standard_read())
+                .with_no_intermediate_buffering(true),
+        )
+        .await?
+        .unwrap_file();
+    let mut data = vec![0; sector_size];
+    let read = file
+        .read_block(&mut data, sector_size as u64, None, false)
+        .await?;
+    assert_eq!(read, sector_size);
+    assert_eq!(data, content[sector_size..2 * sector_size]);
+    let result = file.read_block(&mut data[..1], 0, None, false).await;
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+    file.close().await?;
+    client.delete(&path).await?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! Upload helper tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::FileCreateArgs;
+use smb::resource::{TransferOptions, upload};
+use smb_fscc::{FileAccessMask, FileDispositionInformation, FileStandardInformation};
+use smb_msg::CreateDisposition;
+
+#[cfg(not(feature = "async"))]
+use std::io::{Read, repeat};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncReadExt, repeat};
+
+const UPLOAD_SIZE: u64 = 300 * 1024 * 1024;
+
+/// Returns the peak resident set size of the current process, in bytes.
+#[cfg(target_os = "linux")]
+fn peak_rss() -> u64 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    let kb = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmHWM:"))
+        .unwrap()
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .unwrap();
+    kb * 1024
+}
+
+#[maybe_async::maybe_async]
+async fn _upload_and_check(
+    name: &str,
+    size: u64,
+    options: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+
+    let file = client
+        .create_file(
+            &share_path.with_path(name),
+            &FileCreateArgs {
+                disposition: CreateDisposition::OverwriteIf,
+                desired_access: FileAccessMask::new()
+                    .with_generic_read(true)
+                    .with_generic_write(true)
+                    .with_delete(true),
+                ..Default::default()
+            },
+        )
+        .await?
+        .unwrap_file();
+
+    let written = upload(repeat(0x5a).take(size), &file, options).await?;
+    assert_eq!(written, size);
+
+    let info = file.query_info::<FileStandardInformation>().await?;
+    assert_eq!(info.end_of_file, size);
+
+    file.set_info(FileDispositionInformation::default()).await?;
+    file.close().await?;
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_upload_unsized_stream_bounded_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let options = TransferOptions::default();
+    _upload_and_check("upload_unsized.bin", UPLOAD_SIZE, &options).await?;
+
+    // Memory usage must be bounded by the chunks in flight, not by the upload size.
+    #[cfg(target_os = "linux")]
+    assert!(peak_rss() < UPLOAD_SIZE / 2);
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_upload_empty_stream() -> Result<(), Box<dyn std::error::Error>> {
+    _upload_and_check("upload_empty.bin", 0, &TransferOptions::default()).await
+}
@@ -0,0 +1,155 @@
+//! Directory walk tests.
+
+mod common;
+
+use common::{TestConstants, make_server_connection};
+use serial_test::serial;
+use smb::client::{WalkOptions, WalkOrder};
+use smb::{Client, FileCreateArgs, UncPath};
+use smb_fscc::{FileAttributes, FileDispositionInformation};
+use smb_msg::CreateOptions;
+
+#[cfg(feature = "async")]
+use futures_util::StreamExt;
+
+const WALK_DIR: &str = "walk_test";
+const WIDTH: usize = 3;
+const DEPTH: usize = 2;
+
+/// Creates a tree of `WIDTH` subdirectories and a file in each directory, `DEPTH` levels deep.
+#[maybe_async::maybe_async]
+async fn make_tree(client: &Client, root: &UncPath) -> smb::Result<()> {
+    let mut dirs = vec![(root.clone(), DEPTH)];
+    while let Some((path, depth)) = dirs.pop() {
+        client
+            .create_file(
+                &path,
+                &FileCreateArgs::make_create_new(
+                    FileAttributes::new().with_directory(true),
+                    CreateOptions::new().with_directory_file(true),
+                ),
+            )
+            .await?
+            .unwrap_dir()
+            .close()
+            .await?;
+        client
+            .create_file(
+                &path.clone().with_add_path("file.txt"),
+                &FileCreateArgs::make_create_new(Default::default(), Default::default()),
+            )
+            .await?
+            .unwrap_file()
+            .close()
+            .await?;
+
+        if depth > 0 {
+            for i in 0..WIDTH {
+                dirs.push((path.clone().with_add_path(&format!("dir{i}")), depth - 1));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[maybe_async::async_impl]
+async fn walk_paths(client: &Client, root: &UncPath, options: &WalkOptions) -> Vec<String> {
+    let walk = client.walk(root, options);
+    futures_util::pin_mut!(walk);
+    let mut result = vec![];
+    while let Some(entry) = walk.next().await {
+        result.push(entry.unwrap().path.to_string());
+    }
+    result
+}
+
+#[maybe_async::sync_impl]
+fn walk_paths(client: &Client, root: &UncPath, options: &WalkOptions) -> Vec<String> {
+    client
+        .walk(root, options)
+        .map(|entry| entry.unwrap().path.to_string())
+        .collect()
+}
+
+/// Deletes the walked tree, deepest entries first.
+#[maybe_async::maybe_async]
+async fn delete_tree(client: &Client, root: &UncPath, mut paths: Vec<String>) -> smb::Result<()> {
+    paths.sort_by_key(|p| std::cmp::Reverse(p.len()));
+    let mut to_delete: Vec<UncPath> = paths.iter().map(|p| p.parse()).collect::<Result<_, _>>()?;
+    to_delete.push(root.clone());
+    for path in to_delete {
+        let resource = client
+            .create_file(
+                &path,
+                &FileCreateArgs::make_open_existing(
+                    smb_fscc::FileAccessMask::new().with_delete(true),
+                ),
+            )
+            .await?;
+        let handle = match &resource {
+            smb::Resource::File(f) => f.handle(),
+            smb::Resource::Directory(d) => d.handle(),
+            smb::Resource::Pipe(p) => p.handle(),
+        };
+        handle
+            .set_info(FileDispositionInformation::default())
+            .await?;
+        handle.close().await?;
+    }
+    Ok(())
+}
+
+#[test_log::test(maybe_async::test(
+    not(feature = "async"),
+    async(feature = "async", tokio::test(flavor = "multi_thread"))
+))]
+#[serial]
+async fn test_walk_parallel() -> Result<(), Box<dyn std::error::Error>> {
+    let (client, share_path) = make_server_connection(TestConstants::DEFAULT_SHARE, None).await?;
+    let root = share_path.with_path(WALK_DIR);
+    make_tree(&client, &root).await?;
+
+    let sequential = walk_paths(
+        &client,
+        &root,
+        &WalkOptions {
+            order: WalkOrder::DepthFirst,
+            ..Default::default()
+        },
+    )
+    .await;
+    // Each directory has a file and WIDTH subdirectories, except for the deepest ones.
+    assert_eq!(
+        sequential.len(),
+        (1 + WIDTH) + WIDTH * (1 + WIDTH) + WIDTH * WIDTH
+    );
+
+    let parallel_dfs = walk_paths(
+        &client,
+        &root,
+        &WalkOptions {
+            parallelism: 4,
+            order: WalkOrder::DepthFirst,
+            ..Default::default()
+        },
+    )
+    .await;
+    assert_eq!(parallel_dfs, sequential);
+
+    let mut parallel_unordered = walk_paths(
+        &client,
+        &root,
+        &WalkOptions {
+            parallelism: 4,
+            ..Default::default()
+        },
+    )
+    .await;
+    let mut expected = sequential.clone();
+    parallel_unordered.sort();
+    expected.sort();
+    assert_eq!(parallel_unordered, expected);
+
+    delete_tree(&client, &root, sequential).await?;
+    Ok(())
+}